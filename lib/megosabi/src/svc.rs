@@ -54,4 +54,6 @@ pub enum Function {
     Alloc,
     /// Frees an allocated memory block
     Dealloc,
+    /// Spawn a thread that runs the exported function on another instance sharing the memory
+    SpawnThread,
 }
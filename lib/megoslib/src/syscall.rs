@@ -173,3 +173,14 @@ pub fn os_alloc(size: usize, align: usize) -> usize {
 pub fn os_dealloc(ptr: usize, size: usize, align: usize) {
     unsafe { svc3(Function::Dealloc, ptr, size, align) };
 }
+
+/// Spawns a thread that runs the exported function `fn(arg: u32)` on another instance of the app,
+/// returns zero if it fails.
+///
+/// The memory of the app has to be shared, and it is the only thing the instances share.
+/// The thread starts with a copy of the globals, including the stack pointer,
+/// so the function has to run without the shadow stack or set up its own.
+#[inline]
+pub fn os_spawn_thread(name: &str, arg: u32) -> usize {
+    unsafe { svc3(Function::SpawnThread, name.as_ptr() as usize, name.len(), arg as usize) }
+}
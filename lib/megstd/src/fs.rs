@@ -8,9 +8,7 @@ use crate::{
     *,
 };
 
-pub struct File {
-    _phantom: (),
-}
+pub struct File(fs_imp::File);
 
 impl File {
     pub fn create<P: AsRef<Path>>(path: P) -> Result<File> {
@@ -47,18 +45,21 @@ impl File {
 }
 
 impl Read for File {
-    fn read(&mut self, _buf: &mut [u8]) -> Result<usize> {
-        todo!()
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        self.0.read(buf)
     }
 }
 
 impl Write for File {
-    fn write(&mut self, _buf: &[u8]) -> Result<usize> {
-        todo!()
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.0.write(buf)
     }
 
+    #[inline]
     fn flush(&mut self) -> Result<()> {
-        todo!()
+        self.0.flush()
     }
 }
 
@@ -108,8 +109,8 @@ impl OpenOptions {
     }
 
     #[inline]
-    pub fn open<P: AsRef<Path>>(&self, _path: P) -> Result<File> {
-        todo!()
+    pub fn open<P: AsRef<Path>>(&self, path: P) -> Result<File> {
+        fs_imp::File::open(path, self.0).map(File)
     }
 }
 
@@ -26,6 +26,12 @@ pub enum ErrorKind {
     Interrupted,
     Other,
     UnexpectedEof,
+    NotADirectory,
+    IsADirectory,
+    DirectoryNotEmpty,
+    ReadOnlyFilesystem,
+    StorageFull,
+    Unsupported,
 }
 
 pub struct Error {
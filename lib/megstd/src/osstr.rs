@@ -17,7 +17,7 @@ pub struct OsStr {
 
 impl OsStr {
     #[inline]
-    fn from_inner(inner: &Slice) -> &OsStr {
+    pub(crate) fn from_inner(inner: &Slice) -> &OsStr {
         unsafe { &*(inner as *const Slice as *const OsStr) }
     }
 
@@ -204,6 +204,11 @@ impl OsString {
 
     // pub fn shrink_to(&mut self, min_capacity: usize)
 
+    #[inline]
+    pub(crate) fn as_mut_vec(&mut self) -> &mut Buf {
+        &mut self.inner
+    }
+
     #[inline]
     pub fn into_boxed_os_str(self) -> Box<OsStr> {
         let rw = Box::into_raw(self.inner.into_boxed_slice()) as *mut OsStr;
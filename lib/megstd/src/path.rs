@@ -1,9 +1,9 @@
 // impl Path for MEG-OS
 // Most of them are clones of Rust's original definition.
 
-use crate::sys::path::MAIN_SEP_STR;
+use crate::sys::path::{MAIN_SEP, MAIN_SEP_STR};
 use crate::{OsStr, OsString};
-use alloc::boxed::Box;
+use alloc::{boxed::Box, string::String};
 use core::{
     cmp, fmt,
     hash::{Hash, Hasher},
@@ -15,7 +15,22 @@ pub struct Path {
     inner: OsStr,
 }
 
+#[inline]
+fn is_sep_byte(b: u8) -> bool {
+    b == MAIN_SEP
+}
+
 impl Path {
+    #[inline]
+    fn from_u8_slice(s: &[u8]) -> &Path {
+        Path::new(OsStr::from_inner(s))
+    }
+
+    #[inline]
+    fn as_u8_slice(&self) -> &[u8] {
+        self.inner.bytes()
+    }
+
     pub fn new<S: AsRef<OsStr> + ?Sized>(s: &S) -> &Path {
         unsafe { &*(s.as_ref() as *const OsStr as *const Path) }
     }
@@ -37,7 +52,7 @@ impl Path {
 
     #[inline]
     pub fn is_absolute(&self) -> bool {
-        todo!()
+        self.has_root()
     }
 
     #[inline]
@@ -47,12 +62,18 @@ impl Path {
 
     #[inline]
     pub fn has_root(&self) -> bool {
-        todo!()
-        // self.components().has_root()
+        self.components().has_root()
     }
 
     pub fn parent(&self) -> Option<&Path> {
-        todo!()
+        let mut comps = self.components();
+        let comp = comps.next_back();
+        comp.and_then(|p| match p {
+            Component::Normal(_) | Component::CurDir | Component::ParentDir => {
+                Some(comps.as_path())
+            }
+            _ => None,
+        })
     }
 
     #[inline]
@@ -62,27 +83,34 @@ impl Path {
 
     #[inline]
     pub fn file_name(&self) -> Option<&OsStr> {
-        todo!()
+        self.components().next_back().and_then(|p| match p {
+            Component::Normal(p) => Some(p),
+            _ => None,
+        })
     }
 
     // pub fn strip_prefix<P>(&self, base: P) -> Result<&Path, StripPrefixError> {
     //     todo!()
     // }
 
-    pub fn starts_with<P: AsRef<Path>>(&self, _base: P) -> bool {
-        todo!()
+    pub fn starts_with<P: AsRef<Path>>(&self, base: P) -> bool {
+        iter_after(self.components(), base.as_ref().components()).is_some()
     }
 
-    pub fn ends_with<P: AsRef<Path>>(&self, _child: P) -> bool {
-        todo!()
+    pub fn ends_with<P: AsRef<Path>>(&self, child: P) -> bool {
+        iter_after(self.components().rev(), child.as_ref().components().rev()).is_some()
     }
 
     pub fn file_stem(&self) -> Option<&OsStr> {
-        todo!()
+        self.file_name()
+            .map(split_file_at_dot)
+            .and_then(|(before, after)| before.or(after))
     }
 
     pub fn extension(&self) -> Option<&OsStr> {
-        todo!()
+        self.file_name()
+            .map(split_file_at_dot)
+            .and_then(|(before, after)| before.and(after))
     }
 
     #[must_use]
@@ -104,9 +132,15 @@ impl Path {
         buf
     }
 
-    // pub fn components(&self) -> Components<'_> {
-    //     todo!()
-    // }
+    pub fn components(&self) -> Components<'_> {
+        let path = self.as_u8_slice();
+        Components {
+            path,
+            has_physical_root: path.first().map(|c| is_sep_byte(*c)).unwrap_or(false),
+            front: State::StartDir,
+            back: State::Body,
+        }
+    }
 
     // #[inline]
     // pub fn canonicalize(&self) -> io::Result<PathBuf> {
@@ -141,6 +175,27 @@ impl AsRef<Path> for OsStr {
     }
 }
 
+impl AsRef<Path> for str {
+    #[inline]
+    fn as_ref(&self) -> &Path {
+        Path::new(self)
+    }
+}
+
+impl AsRef<Path> for String {
+    #[inline]
+    fn as_ref(&self) -> &Path {
+        Path::new(self)
+    }
+}
+
+impl AsRef<Path> for OsString {
+    #[inline]
+    fn as_ref(&self) -> &Path {
+        Path::new(self)
+    }
+}
+
 #[derive(Clone)]
 pub struct PathBuf {
     inner: OsString,
@@ -166,24 +221,50 @@ impl PathBuf {
         self
     }
 
-    #[inline]
-    pub fn push<P: AsRef<Path>>(&mut self, _path: P) {
-        todo!()
+    pub fn push<P: AsRef<Path>>(&mut self, path: P) {
+        let path = path.as_ref();
+        let buf = self.inner.as_mut_vec();
+        if path.is_absolute() {
+            buf.clear();
+        } else if buf.last().map(|c| !is_sep_byte(*c)).unwrap_or(false) {
+            buf.push(MAIN_SEP);
+        }
+        buf.extend_from_slice(path.as_u8_slice());
     }
 
-    #[inline]
     pub fn pop(&mut self) -> bool {
-        todo!()
-    }
-
-    #[inline]
-    pub fn set_file_name<S: AsRef<OsStr>>(&mut self, _file_name: S) {
-        todo!()
+        match self.parent().map(|p| p.as_u8_slice().len()) {
+            Some(len) => {
+                self.inner.as_mut_vec().truncate(len);
+                true
+            }
+            None => false,
+        }
     }
 
-    #[inline]
-    pub fn set_extension<S: AsRef<OsStr>>(&mut self, _extension: S) -> bool {
-        todo!()
+    pub fn set_file_name<S: AsRef<OsStr>>(&mut self, file_name: S) {
+        if self.file_name().is_some() {
+            let popped = self.pop();
+            debug_assert!(popped);
+        }
+        self.push(Path::new(file_name.as_ref()));
+    }
+
+    pub fn set_extension<S: AsRef<OsStr>>(&mut self, extension: S) -> bool {
+        let end_file_stem = match self.file_stem() {
+            None => return false,
+            Some(f) => f.bytes().as_ptr() as usize + f.len(),
+        };
+        let start = self.inner.bytes().as_ptr() as usize;
+        let buf = self.inner.as_mut_vec();
+        buf.truncate(end_file_stem.wrapping_sub(start));
+
+        let extension = extension.as_ref();
+        if !extension.is_empty() {
+            buf.push(b'.');
+            buf.extend_from_slice(extension.bytes());
+        }
+        true
     }
 
     #[inline]
@@ -225,6 +306,12 @@ impl PathBuf {
     // pub fn shrink_to(&mut self, min_capacity: usize)
 }
 
+impl fmt::Debug for PathBuf {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, formatter)
+    }
+}
+
 impl AsRef<OsStr> for PathBuf {
     #[inline]
     fn as_ref(&self) -> &OsStr {
@@ -247,6 +334,13 @@ impl ops::Deref for PathBuf {
     }
 }
 
+impl<T: ?Sized + AsRef<OsStr>> From<&T> for PathBuf {
+    #[inline]
+    fn from(s: &T) -> PathBuf {
+        PathBuf::from(s.as_ref().to_os_string())
+    }
+}
+
 impl From<OsString> for PathBuf {
     #[inline]
     fn from(s: OsString) -> PathBuf {
@@ -279,6 +373,225 @@ impl<'a> Iterator for Ancestors<'a> {
 
 impl FusedIterator for Ancestors<'_> {}
 
+/// Iterator over the components of a path.
+#[derive(Clone)]
+pub struct Components<'a> {
+    path: &'a [u8],
+    has_physical_root: bool,
+    front: State,
+    back: State,
+}
+
+#[derive(Copy, Clone, PartialEq, PartialOrd, Debug)]
+enum State {
+    StartDir = 1,
+    Body = 2,
+    Done = 3,
+}
+
+impl<'a> Components<'a> {
+    #[inline]
+    fn has_root(&self) -> bool {
+        self.has_physical_root
+    }
+
+    /// Whether the path starts with a `.` component that must be yielded
+    fn include_cur_dir(&self) -> bool {
+        if self.has_root() {
+            return false;
+        }
+        let mut iter = self.path.iter();
+        match (iter.next(), iter.next()) {
+            (Some(&b'.'), None) => true,
+            (Some(&b'.'), Some(&b)) => is_sep_byte(b),
+            _ => false,
+        }
+    }
+
+    #[inline]
+    fn len_before_body(&self) -> usize {
+        if self.front <= State::StartDir {
+            self.has_root() as usize + self.include_cur_dir() as usize
+        } else {
+            0
+        }
+    }
+
+    #[inline]
+    fn finished(&self) -> bool {
+        self.front == State::Done || self.back == State::Done || self.front > self.back
+    }
+
+    fn parse_single_component<'b>(&self, comp: &'b [u8]) -> Option<Component<'b>> {
+        match comp {
+            b"." | b"" => None,
+            b".." => Some(Component::ParentDir),
+            _ => Some(Component::Normal(OsStr::from_inner(comp))),
+        }
+    }
+
+    /// Returns the length of the consumed bytes and the next component
+    fn parse_next_component(&self) -> (usize, Option<Component<'a>>) {
+        let (extra, comp) = match self.path.iter().position(|b| is_sep_byte(*b)) {
+            None => (0, self.path),
+            Some(i) => (1, &self.path[..i]),
+        };
+        (comp.len() + extra, self.parse_single_component(comp))
+    }
+
+    /// Returns the length of the consumed bytes and the previous component
+    fn parse_next_component_back(&self) -> (usize, Option<Component<'a>>) {
+        let start = self.len_before_body();
+        let (extra, comp) = match self.path[start..].iter().rposition(|b| is_sep_byte(*b)) {
+            None => (0, &self.path[start..]),
+            Some(i) => (1, &self.path[start + i + 1..]),
+        };
+        (comp.len() + extra, self.parse_single_component(comp))
+    }
+
+    fn trim_left(&mut self) {
+        while !self.path.is_empty() {
+            let (size, comp) = self.parse_next_component();
+            if comp.is_some() {
+                return;
+            }
+            self.path = &self.path[size..];
+        }
+    }
+
+    fn trim_right(&mut self) {
+        while self.path.len() > self.len_before_body() {
+            let (size, comp) = self.parse_next_component_back();
+            if comp.is_some() {
+                return;
+            }
+            self.path = &self.path[..self.path.len() - size];
+        }
+    }
+
+    /// Extracts a slice corresponding to the portion of the path remaining for iteration.
+    pub fn as_path(&self) -> &'a Path {
+        let mut comps = self.clone();
+        if comps.front == State::Body {
+            comps.trim_left();
+        }
+        if comps.back == State::Body {
+            comps.trim_right();
+        }
+        Path::from_u8_slice(comps.path)
+    }
+}
+
+impl<'a> Iterator for Components<'a> {
+    type Item = Component<'a>;
+
+    fn next(&mut self) -> Option<Component<'a>> {
+        while !self.finished() {
+            match self.front {
+                State::StartDir => {
+                    self.front = State::Body;
+                    if self.has_physical_root {
+                        self.path = &self.path[1..];
+                        return Some(Component::RootDir);
+                    } else if self.include_cur_dir() {
+                        self.path = &self.path[1..];
+                        return Some(Component::CurDir);
+                    }
+                }
+                State::Body if !self.path.is_empty() => {
+                    let (size, comp) = self.parse_next_component();
+                    self.path = &self.path[size..];
+                    if comp.is_some() {
+                        return comp;
+                    }
+                }
+                State::Body => {
+                    self.front = State::Done;
+                }
+                State::Done => unreachable!(),
+            }
+        }
+        None
+    }
+}
+
+impl<'a> DoubleEndedIterator for Components<'a> {
+    fn next_back(&mut self) -> Option<Component<'a>> {
+        while !self.finished() {
+            match self.back {
+                State::Body if self.path.len() > self.len_before_body() => {
+                    let (size, comp) = self.parse_next_component_back();
+                    self.path = &self.path[..self.path.len() - size];
+                    if comp.is_some() {
+                        return comp;
+                    }
+                }
+                State::Body => {
+                    self.back = State::StartDir;
+                }
+                State::StartDir => {
+                    self.back = State::Done;
+                    if self.has_physical_root {
+                        self.path = &self.path[..self.path.len() - 1];
+                        return Some(Component::RootDir);
+                    } else if self.include_cur_dir() {
+                        self.path = &self.path[..self.path.len() - 1];
+                        return Some(Component::CurDir);
+                    }
+                }
+                State::Done => unreachable!(),
+            }
+        }
+        None
+    }
+}
+
+impl FusedIterator for Components<'_> {}
+
+impl AsRef<Path> for Components<'_> {
+    #[inline]
+    fn as_ref(&self) -> &Path {
+        self.as_path()
+    }
+}
+
+/// Iterates `prefix` off the front of `iter`, returning the rest if it matches
+fn iter_after<'a, 'b, I, J>(mut iter: I, mut prefix: J) -> Option<I>
+where
+    I: Iterator<Item = Component<'a>> + Clone,
+    J: Iterator<Item = Component<'b>>,
+{
+    loop {
+        let mut iter_next = iter.clone();
+        match (iter_next.next(), prefix.next()) {
+            (Some(ref x), Some(ref y)) if x == y => (),
+            (Some(_), Some(_)) => return None,
+            (Some(_), None) => return Some(iter),
+            (None, None) => return Some(iter),
+            (None, Some(_)) => return None,
+        }
+        iter = iter_next;
+    }
+}
+
+/// Splits a file name into its stem and extension
+fn split_file_at_dot(file: &OsStr) -> (Option<&OsStr>, Option<&OsStr>) {
+    let slice = file.bytes();
+    if slice == b".." {
+        return (Some(file), None);
+    }
+    let i = match slice[1..].iter().rposition(|b| *b == b'.') {
+        Some(i) => i + 1,
+        None => return (Some(file), None),
+    };
+    let before = &slice[..i];
+    let after = &slice[i + 1..];
+    (
+        Some(OsStr::from_inner(before)),
+        Some(OsStr::from_inner(after)),
+    )
+}
+
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
 pub enum Component<'a> {
     Prefix(PrefixComponent<'a>),
@@ -381,3 +694,61 @@ impl Prefix<'_> {
         !self.is_drive()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::borrow::ToOwned;
+    use alloc::vec::Vec;
+
+    fn components(path: &str) -> Vec<&str> {
+        Path::new(path)
+            .components()
+            .map(|v| v.as_os_str().to_str().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn components_normalize() {
+        assert_eq!(components("/a//b/./c/"), ["/", "a", "b", "c"]);
+        assert_eq!(components("./a/.."), [".", "a", ".."]);
+        assert_eq!(components("a/./."), ["a"]);
+        assert!(components("").is_empty());
+
+        let mut comps = Path::new("/a/b").components();
+        assert_eq!(comps.next_back(), Some(Component::Normal(OsStr::new("b"))));
+        assert_eq!(comps.next_back(), Some(Component::Normal(OsStr::new("a"))));
+        assert_eq!(comps.next_back(), Some(Component::RootDir));
+        assert_eq!(comps.next_back(), None);
+    }
+
+    #[test]
+    fn parent() {
+        let parent = |path| Path::new(path).parent().map(|v| v.to_str().unwrap());
+        assert_eq!(parent("/a/b"), Some("/a"));
+        assert_eq!(parent("/a"), Some("/"));
+        assert_eq!(parent("a/b/"), Some("a"));
+        assert_eq!(parent("a"), Some(""));
+        assert_eq!(parent("/"), None);
+        assert_eq!(parent(""), None);
+    }
+
+    #[test]
+    fn join() {
+        let join = |path, other| Path::new(path).join(other).to_str().unwrap().to_owned();
+        assert_eq!(join("/a", "b"), "/a/b");
+        assert_eq!(join("/a/", "b"), "/a/b");
+        assert_eq!(join("/a", "/b"), "/b");
+        assert_eq!(join("", "b"), "b");
+    }
+
+    #[test]
+    fn file_name() {
+        let file_name = |path| Path::new(path).file_name().map(|v| v.to_str().unwrap());
+        assert_eq!(file_name("/a/b.txt"), Some("b.txt"));
+        assert_eq!(file_name("/a/b/"), Some("b"));
+        assert_eq!(file_name("/a/.."), None);
+        assert_eq!(file_name("/"), None);
+        assert_eq!(file_name("."), None);
+    }
+}
@@ -1,27 +1,35 @@
 // FileSystem Implementation
 
-use crate::{io::Result, path::*, sys::fcntl::*, *};
+use crate::{
+    io::{ErrorKind, Result},
+    path::*,
+    sys::fcntl::*,
+    *,
+};
 use bitflags::*;
 
+/// A file of the host system
+///
+/// There is no system call for files yet, so every operation fails with `Unsupported`.
 pub struct File {
     _phantom: (),
 }
 
 impl File {
     pub fn open<P: AsRef<Path>>(_path: P, _options: OpenOptions) -> Result<File> {
-        todo!()
+        Err(ErrorKind::Unsupported.into())
     }
 
     pub fn read(&mut self, _buf: &mut [u8]) -> Result<usize> {
-        todo!()
+        Err(ErrorKind::Unsupported.into())
     }
 
     pub fn write(&mut self, _buf: &[u8]) -> Result<usize> {
-        todo!()
+        Err(ErrorKind::Unsupported.into())
     }
 
     pub fn flush(&mut self) -> Result<()> {
-        todo!()
+        Err(ErrorKind::Unsupported.into())
     }
 }
 
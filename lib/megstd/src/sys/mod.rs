@@ -3,6 +3,7 @@
 pub mod fs_imp;
 
 pub mod path {
+    pub const MAIN_SEP: u8 = b'/';
     pub const MAIN_SEP_STR: &'static str = "/";
 }
 
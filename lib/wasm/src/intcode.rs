@@ -2,6 +2,7 @@
 
 /// Intermediate code for Webassembly runtime
 #[non_exhaustive]
+#[repr(u16)]
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum WasmIntMnemonic {
     /// Undefined
@@ -15,6 +16,12 @@ pub enum WasmIntMnemonic {
     Br,
     /// branch if true
     BrIf,
+    /// branch with moving values to the target block
+    BrUnwind,
+    /// branch if true with moving values to the target block
+    BrIfUnwind,
+    /// branch to the else-clause or the end of block if false
+    If,
     /// Fused check and branch
     FusedI32BrZ,
     FusedI64BrZ,
@@ -24,8 +31,12 @@ pub enum WasmIntMnemonic {
 
     /// Block Marker, this mnemonic will be removed during the compaction phase.
     Block,
+    /// Else-clause marker, this mnemonic will be removed during the compaction phase.
+    Else,
     /// End of block marker, this mnemonic will be removed during the compaction phase.
     End,
+    /// Catch-clause marker, this mnemonic will be removed during the compaction phase.
+    Catch,
 
     /// return from function
     Return,
@@ -33,6 +44,14 @@ pub enum WasmIntMnemonic {
     Call,
     /// call indirect
     CallIndirect,
+    /// tail call function
+    ReturnCall,
+    /// tail call indirect
+    ReturnCallIndirect,
+    /// throw an exception
+    Throw,
+    /// rethrow the caught exception
+    Rethrow,
     /// select value
     Select,
     /// Get a value from a local variable
@@ -65,11 +84,17 @@ pub enum WasmIntMnemonic {
     I64Store8,
     I64Store16,
     I64Store32,
+    F32Load,
+    F64Load,
+    F32Store,
+    F64Store,
     MemorySize,
     MemoryGrow,
 
     I32Const,
     I64Const,
+    F32Const,
+    F64Const,
 
     I32Eqz,
     I32Eq,
@@ -140,6 +165,133 @@ pub enum WasmIntMnemonic {
     I32Extend8S,
     I32Extend16S,
 
+    F32Eq,
+    F32Ne,
+    F32Lt,
+    F32Gt,
+    F32Le,
+    F32Ge,
+    F32Abs,
+    F32Neg,
+    F32Ceil,
+    F32Floor,
+    F32Trunc,
+    F32Nearest,
+    F32Sqrt,
+    F32Add,
+    F32Sub,
+    F32Mul,
+    F32Div,
+    F32Min,
+    F32Max,
+    F32Copysign,
+
+    F64Eq,
+    F64Ne,
+    F64Lt,
+    F64Gt,
+    F64Le,
+    F64Ge,
+    F64Abs,
+    F64Neg,
+    F64Ceil,
+    F64Floor,
+    F64Trunc,
+    F64Nearest,
+    F64Sqrt,
+    F64Add,
+    F64Sub,
+    F64Mul,
+    F64Div,
+    F64Min,
+    F64Max,
+    F64Copysign,
+
+    I32TruncF32S,
+    I32TruncF32U,
+    I32TruncF64S,
+    I32TruncF64U,
+    I64TruncF32S,
+    I64TruncF32U,
+    I64TruncF64S,
+    I64TruncF64U,
+    F32ConvertI32S,
+    F32ConvertI32U,
+    F32ConvertI64S,
+    F32ConvertI64U,
+    F32DemoteF64,
+    F64ConvertI32S,
+    F64ConvertI32U,
+    F64ConvertI64S,
+    F64ConvertI64U,
+    F64PromoteF32,
+    I32ReinterpretF32,
+    I64ReinterpretF64,
+    F32ReinterpretI32,
+    F64ReinterpretI64,
+
+    I32TruncSatF32S,
+    I32TruncSatF32U,
+    I32TruncSatF64S,
+    I32TruncSatF64U,
+    I64TruncSatF32S,
+    I64TruncSatF32U,
+    I64TruncSatF64S,
+    I64TruncSatF64U,
+
+    MemoryInit,
+    DataDrop,
+    MemoryCopy,
+    MemoryFill,
+
+    RefNull,
+    RefIsNull,
+    RefFunc,
+    TableGet,
+    TableSet,
+    TableInit,
+    ElemDrop,
+    TableCopy,
+    TableGrow,
+    TableSize,
+    TableFill,
+
+    MemoryAtomicNotify,
+    MemoryAtomicWait32,
+    MemoryAtomicWait64,
+    AtomicFence,
+    /// Atomic loads and stores by width, the loaded value is zero-extended
+    AtomicLoad8,
+    AtomicLoad16,
+    AtomicLoad32,
+    AtomicLoad64,
+    AtomicStore8,
+    AtomicStore16,
+    AtomicStore32,
+    AtomicStore64,
+    /// Atomic read-modify-write by width, the upper 32 bits of param1 are [WasmAtomicRmwOp]
+    AtomicRmw8,
+    AtomicRmw16,
+    AtomicRmw32,
+    AtomicRmw64,
+    AtomicCmpxchg8,
+    AtomicCmpxchg16,
+    AtomicCmpxchg32,
+    AtomicCmpxchg64,
+
+    /// SIMD loads and stores, the upper 32 bits of param1 are `WasmOpcodeFD`
+    V128Load,
+    V128Store,
+    /// SIMD lane loads and stores, the bits 32-47 of param1 are `WasmOpcodeFD` and the bits 48-63 are the lane index
+    V128LoadLane,
+    V128StoreLane,
+    /// v128.const, param1 is the position of the immediate in ext_params as four 32-bit words
+    V128Const,
+    /// i8x16.shuffle, param1 is the position of the lane indexes in ext_params as four 32-bit words
+    I8x16Shuffle,
+    /// Other SIMD instructions, the lower 32 bits of param1 are `WasmOpcodeFD` and the upper 32 bits are the lane index
+    Simd,
+
     // Fused Instructions
     FusedI32AddI,
     FusedI32SubI,
@@ -154,6 +306,20 @@ pub enum WasmIntMnemonic {
 }
 
 impl WasmIntMnemonic {
+    /// The last mnemonic, which must be updated when a new mnemonic is added to the end.
+    pub const LAST: Self = Self::FusedI32ShrUI;
+
+    /// Returns the mnemonic with the specified number, or `None` if it is out of range.
+    #[inline]
+    pub const fn from_u16(v: u16) -> Option<Self> {
+        if v <= Self::LAST as u16 {
+            // The discriminants are contiguous from zero
+            Some(unsafe { core::mem::transmute::<u16, Self>(v) })
+        } else {
+            None
+        }
+    }
+
     #[inline]
     pub fn is_branch(&self) -> bool {
         use WasmIntMnemonic::*;
@@ -163,3 +329,41 @@ impl WasmIntMnemonic {
         }
     }
 }
+
+/// Operations of the atomic read-modify-write instructions
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum WasmAtomicRmwOp {
+    Add,
+    Sub,
+    And,
+    Or,
+    Xor,
+    Xchg,
+}
+
+impl WasmAtomicRmwOp {
+    #[inline]
+    pub const fn from_u64(v: u64) -> Self {
+        match v {
+            0 => Self::Add,
+            1 => Self::Sub,
+            2 => Self::And,
+            3 => Self::Or,
+            4 => Self::Xor,
+            _ => Self::Xchg,
+        }
+    }
+
+    /// Applies the operation to the old value
+    #[inline]
+    pub const fn apply(&self, old: u64, operand: u64) -> u64 {
+        match *self {
+            Self::Add => old.wrapping_add(operand),
+            Self::Sub => old.wrapping_sub(operand),
+            Self::And => old & operand,
+            Self::Or => old | operand,
+            Self::Xor => old ^ operand,
+            Self::Xchg => operand,
+        }
+    }
+}
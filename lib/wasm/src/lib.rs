@@ -10,8 +10,12 @@ pub use crate::wasm::*;
 
 pub mod intcode;
 pub mod opcode;
+pub mod profile;
 pub mod stack;
 pub mod wasmintr;
+#[cfg(target_arch = "x86_64")]
+pub mod wasmjit;
+pub mod wat;
 
 #[cfg(test)]
 mod tests;
@@ -15,6 +15,14 @@ pub enum WasmOpcode {
     If = 0x04,
     /// `05 else expr; end` (mvp)
     Else = 0x05,
+    /// `06 try block_type; expr; catch; expr; end` (exception_handling)
+    Try = 0x06,
+    /// `07 catch tagidx` (exception_handling)
+    Catch = 0x07,
+    /// `08 throw tagidx` (exception_handling)
+    Throw = 0x08,
+    /// `09 rethrow labelidx` (exception_handling)
+    Rethrow = 0x09,
     /// `0B end` (mvp)
     End = 0x0B,
     /// `0C br labelidx` (mvp)
@@ -33,10 +41,16 @@ pub enum WasmOpcode {
     ReturnCall = 0x12,
     /// `13 return_call_indirect typeidx 0x00` (tail_call)
     ReturnCallIndirect = 0x13,
+    /// `18 delegate labelidx` (exception_handling)
+    Delegate = 0x18,
+    /// `19 catch_all` (exception_handling)
+    CatchAll = 0x19,
     /// `1A drop` (mvp)
     Drop = 0x1A,
     /// `1B select` (mvp)
     Select = 0x1B,
+    /// `1C select vec(valtype)` (reference_types)
+    SelectT = 0x1C,
     /// `20 local.get localidx` (mvp)
     LocalGet = 0x20,
     /// `21 local.set localidx` (mvp)
@@ -47,6 +61,10 @@ pub enum WasmOpcode {
     GlobalGet = 0x23,
     /// `24 global.set globalidx` (mvp)
     GlobalSet = 0x24,
+    /// `25 table.get tableidx` (reference_types)
+    TableGet = 0x25,
+    /// `26 table.set tableidx` (reference_types)
+    TableSet = 0x26,
     /// `28 i32.load align offset` (mvp)
     I32Load = 0x28,
     /// `29 i64.load align offset` (mvp_i64)
@@ -361,6 +379,678 @@ pub enum WasmOpcode {
     I64Extend16S = 0xC3,
     /// `C4 i64.extend32_s` (sign_extend)
     I64Extend32S = 0xC4,
+    /// `D0 ref.null reftype` (reference_types)
+    RefNull = 0xD0,
+    /// `D1 ref.is_null` (reference_types)
+    RefIsNull = 0xD1,
+    /// `D2 ref.func funcidx` (reference_types)
+    RefFunc = 0xD2,
+    /// `FC` prefix for extended instructions
+    PrefixFC = 0xFC,
+    /// `FD` prefix for SIMD instructions
+    PrefixFD = 0xFD,
+    /// `FE` prefix for threads instructions
+    PrefixFE = 0xFE,
+}
+
+/// Extended instructions with the `FC` prefix
+#[non_exhaustive]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum WasmOpcodeFC {
+    /// `FC 00 i32.trunc_sat_f32_s` (nontrapping_float_to_int)
+    I32TruncSatF32S = 0x00,
+    /// `FC 01 i32.trunc_sat_f32_u` (nontrapping_float_to_int)
+    I32TruncSatF32U = 0x01,
+    /// `FC 02 i32.trunc_sat_f64_s` (nontrapping_float_to_int)
+    I32TruncSatF64S = 0x02,
+    /// `FC 03 i32.trunc_sat_f64_u` (nontrapping_float_to_int)
+    I32TruncSatF64U = 0x03,
+    /// `FC 04 i64.trunc_sat_f32_s` (nontrapping_float_to_int)
+    I64TruncSatF32S = 0x04,
+    /// `FC 05 i64.trunc_sat_f32_u` (nontrapping_float_to_int)
+    I64TruncSatF32U = 0x05,
+    /// `FC 06 i64.trunc_sat_f64_s` (nontrapping_float_to_int)
+    I64TruncSatF64S = 0x06,
+    /// `FC 07 i64.trunc_sat_f64_u` (nontrapping_float_to_int)
+    I64TruncSatF64U = 0x07,
+    /// `FC 08 memory.init dataidx 0x00` (bulk_memory)
+    MemoryInit = 0x08,
+    /// `FC 09 data.drop dataidx` (bulk_memory)
+    DataDrop = 0x09,
+    /// `FC 0A memory.copy 0x00 0x00` (bulk_memory)
+    MemoryCopy = 0x0A,
+    /// `FC 0B memory.fill 0x00` (bulk_memory)
+    MemoryFill = 0x0B,
+    /// `FC 0C table.init elemidx tableidx` (bulk_memory)
+    TableInit = 0x0C,
+    /// `FC 0D elem.drop elemidx` (bulk_memory)
+    ElemDrop = 0x0D,
+    /// `FC 0E table.copy tableidx tableidx` (bulk_memory)
+    TableCopy = 0x0E,
+    /// `FC 0F table.grow tableidx` (reference_types)
+    TableGrow = 0x0F,
+    /// `FC 10 table.size tableidx` (reference_types)
+    TableSize = 0x10,
+    /// `FC 11 table.fill tableidx` (reference_types)
+    TableFill = 0x11,
+}
+
+/// Threads instructions with the `FE` prefix
+#[non_exhaustive]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum WasmOpcodeFE {
+    /// `FE 00 memory.atomic.notify align offset` (threads)
+    MemoryAtomicNotify = 0x00,
+    /// `FE 01 memory.atomic.wait32 align offset` (threads)
+    MemoryAtomicWait32 = 0x01,
+    /// `FE 02 memory.atomic.wait64 align offset` (threads)
+    MemoryAtomicWait64 = 0x02,
+    /// `FE 03 atomic.fence 0x00` (threads)
+    AtomicFence = 0x03,
+    /// `FE 10 i32.atomic.load align offset` (threads)
+    I32AtomicLoad = 0x10,
+    /// `FE 11 i64.atomic.load align offset` (threads)
+    I64AtomicLoad = 0x11,
+    /// `FE 12 i32.atomic.load8_u align offset` (threads)
+    I32AtomicLoad8U = 0x12,
+    /// `FE 13 i32.atomic.load16_u align offset` (threads)
+    I32AtomicLoad16U = 0x13,
+    /// `FE 14 i64.atomic.load8_u align offset` (threads)
+    I64AtomicLoad8U = 0x14,
+    /// `FE 15 i64.atomic.load16_u align offset` (threads)
+    I64AtomicLoad16U = 0x15,
+    /// `FE 16 i64.atomic.load32_u align offset` (threads)
+    I64AtomicLoad32U = 0x16,
+    /// `FE 17 i32.atomic.store align offset` (threads)
+    I32AtomicStore = 0x17,
+    /// `FE 18 i64.atomic.store align offset` (threads)
+    I64AtomicStore = 0x18,
+    /// `FE 19 i32.atomic.store8 align offset` (threads)
+    I32AtomicStore8 = 0x19,
+    /// `FE 1A i32.atomic.store16 align offset` (threads)
+    I32AtomicStore16 = 0x1A,
+    /// `FE 1B i64.atomic.store8 align offset` (threads)
+    I64AtomicStore8 = 0x1B,
+    /// `FE 1C i64.atomic.store16 align offset` (threads)
+    I64AtomicStore16 = 0x1C,
+    /// `FE 1D i64.atomic.store32 align offset` (threads)
+    I64AtomicStore32 = 0x1D,
+    /// `FE 1E i32.atomic.rmw.add align offset` (threads)
+    I32AtomicRmwAdd = 0x1E,
+    /// `FE 1F i64.atomic.rmw.add align offset` (threads)
+    I64AtomicRmwAdd = 0x1F,
+    /// `FE 20 i32.atomic.rmw8.add_u align offset` (threads)
+    I32AtomicRmw8AddU = 0x20,
+    /// `FE 21 i32.atomic.rmw16.add_u align offset` (threads)
+    I32AtomicRmw16AddU = 0x21,
+    /// `FE 22 i64.atomic.rmw8.add_u align offset` (threads)
+    I64AtomicRmw8AddU = 0x22,
+    /// `FE 23 i64.atomic.rmw16.add_u align offset` (threads)
+    I64AtomicRmw16AddU = 0x23,
+    /// `FE 24 i64.atomic.rmw32.add_u align offset` (threads)
+    I64AtomicRmw32AddU = 0x24,
+    /// `FE 25 i32.atomic.rmw.sub align offset` (threads)
+    I32AtomicRmwSub = 0x25,
+    /// `FE 26 i64.atomic.rmw.sub align offset` (threads)
+    I64AtomicRmwSub = 0x26,
+    /// `FE 27 i32.atomic.rmw8.sub_u align offset` (threads)
+    I32AtomicRmw8SubU = 0x27,
+    /// `FE 28 i32.atomic.rmw16.sub_u align offset` (threads)
+    I32AtomicRmw16SubU = 0x28,
+    /// `FE 29 i64.atomic.rmw8.sub_u align offset` (threads)
+    I64AtomicRmw8SubU = 0x29,
+    /// `FE 2A i64.atomic.rmw16.sub_u align offset` (threads)
+    I64AtomicRmw16SubU = 0x2A,
+    /// `FE 2B i64.atomic.rmw32.sub_u align offset` (threads)
+    I64AtomicRmw32SubU = 0x2B,
+    /// `FE 2C i32.atomic.rmw.and align offset` (threads)
+    I32AtomicRmwAnd = 0x2C,
+    /// `FE 2D i64.atomic.rmw.and align offset` (threads)
+    I64AtomicRmwAnd = 0x2D,
+    /// `FE 2E i32.atomic.rmw8.and_u align offset` (threads)
+    I32AtomicRmw8AndU = 0x2E,
+    /// `FE 2F i32.atomic.rmw16.and_u align offset` (threads)
+    I32AtomicRmw16AndU = 0x2F,
+    /// `FE 30 i64.atomic.rmw8.and_u align offset` (threads)
+    I64AtomicRmw8AndU = 0x30,
+    /// `FE 31 i64.atomic.rmw16.and_u align offset` (threads)
+    I64AtomicRmw16AndU = 0x31,
+    /// `FE 32 i64.atomic.rmw32.and_u align offset` (threads)
+    I64AtomicRmw32AndU = 0x32,
+    /// `FE 33 i32.atomic.rmw.or align offset` (threads)
+    I32AtomicRmwOr = 0x33,
+    /// `FE 34 i64.atomic.rmw.or align offset` (threads)
+    I64AtomicRmwOr = 0x34,
+    /// `FE 35 i32.atomic.rmw8.or_u align offset` (threads)
+    I32AtomicRmw8OrU = 0x35,
+    /// `FE 36 i32.atomic.rmw16.or_u align offset` (threads)
+    I32AtomicRmw16OrU = 0x36,
+    /// `FE 37 i64.atomic.rmw8.or_u align offset` (threads)
+    I64AtomicRmw8OrU = 0x37,
+    /// `FE 38 i64.atomic.rmw16.or_u align offset` (threads)
+    I64AtomicRmw16OrU = 0x38,
+    /// `FE 39 i64.atomic.rmw32.or_u align offset` (threads)
+    I64AtomicRmw32OrU = 0x39,
+    /// `FE 3A i32.atomic.rmw.xor align offset` (threads)
+    I32AtomicRmwXor = 0x3A,
+    /// `FE 3B i64.atomic.rmw.xor align offset` (threads)
+    I64AtomicRmwXor = 0x3B,
+    /// `FE 3C i32.atomic.rmw8.xor_u align offset` (threads)
+    I32AtomicRmw8XorU = 0x3C,
+    /// `FE 3D i32.atomic.rmw16.xor_u align offset` (threads)
+    I32AtomicRmw16XorU = 0x3D,
+    /// `FE 3E i64.atomic.rmw8.xor_u align offset` (threads)
+    I64AtomicRmw8XorU = 0x3E,
+    /// `FE 3F i64.atomic.rmw16.xor_u align offset` (threads)
+    I64AtomicRmw16XorU = 0x3F,
+    /// `FE 40 i64.atomic.rmw32.xor_u align offset` (threads)
+    I64AtomicRmw32XorU = 0x40,
+    /// `FE 41 i32.atomic.rmw.xchg align offset` (threads)
+    I32AtomicRmwXchg = 0x41,
+    /// `FE 42 i64.atomic.rmw.xchg align offset` (threads)
+    I64AtomicRmwXchg = 0x42,
+    /// `FE 43 i32.atomic.rmw8.xchg_u align offset` (threads)
+    I32AtomicRmw8XchgU = 0x43,
+    /// `FE 44 i32.atomic.rmw16.xchg_u align offset` (threads)
+    I32AtomicRmw16XchgU = 0x44,
+    /// `FE 45 i64.atomic.rmw8.xchg_u align offset` (threads)
+    I64AtomicRmw8XchgU = 0x45,
+    /// `FE 46 i64.atomic.rmw16.xchg_u align offset` (threads)
+    I64AtomicRmw16XchgU = 0x46,
+    /// `FE 47 i64.atomic.rmw32.xchg_u align offset` (threads)
+    I64AtomicRmw32XchgU = 0x47,
+    /// `FE 48 i32.atomic.rmw.cmpxchg align offset` (threads)
+    I32AtomicRmwCmpxchg = 0x48,
+    /// `FE 49 i64.atomic.rmw.cmpxchg align offset` (threads)
+    I64AtomicRmwCmpxchg = 0x49,
+    /// `FE 4A i32.atomic.rmw8.cmpxchg_u align offset` (threads)
+    I32AtomicRmw8CmpxchgU = 0x4A,
+    /// `FE 4B i32.atomic.rmw16.cmpxchg_u align offset` (threads)
+    I32AtomicRmw16CmpxchgU = 0x4B,
+    /// `FE 4C i64.atomic.rmw8.cmpxchg_u align offset` (threads)
+    I64AtomicRmw8CmpxchgU = 0x4C,
+    /// `FE 4D i64.atomic.rmw16.cmpxchg_u align offset` (threads)
+    I64AtomicRmw16CmpxchgU = 0x4D,
+    /// `FE 4E i64.atomic.rmw32.cmpxchg_u align offset` (threads)
+    I64AtomicRmw32CmpxchgU = 0x4E,
+}
+
+/// SIMD instructions with the `FD` prefix
+#[non_exhaustive]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum WasmOpcodeFD {
+    /// `FD 00 v128.load align offset` (simd)
+    V128Load = 0x00,
+    /// `FD 01 v128.load8x8_s align offset` (simd)
+    V128Load8x8S = 0x01,
+    /// `FD 02 v128.load8x8_u align offset` (simd)
+    V128Load8x8U = 0x02,
+    /// `FD 03 v128.load16x4_s align offset` (simd)
+    V128Load16x4S = 0x03,
+    /// `FD 04 v128.load16x4_u align offset` (simd)
+    V128Load16x4U = 0x04,
+    /// `FD 05 v128.load32x2_s align offset` (simd)
+    V128Load32x2S = 0x05,
+    /// `FD 06 v128.load32x2_u align offset` (simd)
+    V128Load32x2U = 0x06,
+    /// `FD 07 v128.load8_splat align offset` (simd)
+    V128Load8Splat = 0x07,
+    /// `FD 08 v128.load16_splat align offset` (simd)
+    V128Load16Splat = 0x08,
+    /// `FD 09 v128.load32_splat align offset` (simd)
+    V128Load32Splat = 0x09,
+    /// `FD 0A v128.load64_splat align offset` (simd)
+    V128Load64Splat = 0x0A,
+    /// `FD 0B v128.store align offset` (simd)
+    V128Store = 0x0B,
+    /// `FD 0C v128.const i128` (simd)
+    V128Const = 0x0C,
+    /// `FD 0D i8x16.shuffle laneidx*16` (simd)
+    I8x16Shuffle = 0x0D,
+    /// `FD 0E i8x16.swizzle` (simd)
+    I8x16Swizzle = 0x0E,
+    /// `FD 0F i8x16.splat` (simd)
+    I8x16Splat = 0x0F,
+    /// `FD 10 i16x8.splat` (simd)
+    I16x8Splat = 0x10,
+    /// `FD 11 i32x4.splat` (simd)
+    I32x4Splat = 0x11,
+    /// `FD 12 i64x2.splat` (simd)
+    I64x2Splat = 0x12,
+    /// `FD 13 f32x4.splat` (simd)
+    F32x4Splat = 0x13,
+    /// `FD 14 f64x2.splat` (simd)
+    F64x2Splat = 0x14,
+    /// `FD 15 i8x16.extract_lane_s laneidx` (simd)
+    I8x16ExtractLaneS = 0x15,
+    /// `FD 16 i8x16.extract_lane_u laneidx` (simd)
+    I8x16ExtractLaneU = 0x16,
+    /// `FD 17 i8x16.replace_lane laneidx` (simd)
+    I8x16ReplaceLane = 0x17,
+    /// `FD 18 i16x8.extract_lane_s laneidx` (simd)
+    I16x8ExtractLaneS = 0x18,
+    /// `FD 19 i16x8.extract_lane_u laneidx` (simd)
+    I16x8ExtractLaneU = 0x19,
+    /// `FD 1A i16x8.replace_lane laneidx` (simd)
+    I16x8ReplaceLane = 0x1A,
+    /// `FD 1B i32x4.extract_lane laneidx` (simd)
+    I32x4ExtractLane = 0x1B,
+    /// `FD 1C i32x4.replace_lane laneidx` (simd)
+    I32x4ReplaceLane = 0x1C,
+    /// `FD 1D i64x2.extract_lane laneidx` (simd)
+    I64x2ExtractLane = 0x1D,
+    /// `FD 1E i64x2.replace_lane laneidx` (simd)
+    I64x2ReplaceLane = 0x1E,
+    /// `FD 1F f32x4.extract_lane laneidx` (simd)
+    F32x4ExtractLane = 0x1F,
+    /// `FD 20 f32x4.replace_lane laneidx` (simd)
+    F32x4ReplaceLane = 0x20,
+    /// `FD 21 f64x2.extract_lane laneidx` (simd)
+    F64x2ExtractLane = 0x21,
+    /// `FD 22 f64x2.replace_lane laneidx` (simd)
+    F64x2ReplaceLane = 0x22,
+    /// `FD 23 i8x16.eq` (simd)
+    I8x16Eq = 0x23,
+    /// `FD 24 i8x16.ne` (simd)
+    I8x16Ne = 0x24,
+    /// `FD 25 i8x16.lt_s` (simd)
+    I8x16LtS = 0x25,
+    /// `FD 26 i8x16.lt_u` (simd)
+    I8x16LtU = 0x26,
+    /// `FD 27 i8x16.gt_s` (simd)
+    I8x16GtS = 0x27,
+    /// `FD 28 i8x16.gt_u` (simd)
+    I8x16GtU = 0x28,
+    /// `FD 29 i8x16.le_s` (simd)
+    I8x16LeS = 0x29,
+    /// `FD 2A i8x16.le_u` (simd)
+    I8x16LeU = 0x2A,
+    /// `FD 2B i8x16.ge_s` (simd)
+    I8x16GeS = 0x2B,
+    /// `FD 2C i8x16.ge_u` (simd)
+    I8x16GeU = 0x2C,
+    /// `FD 2D i16x8.eq` (simd)
+    I16x8Eq = 0x2D,
+    /// `FD 2E i16x8.ne` (simd)
+    I16x8Ne = 0x2E,
+    /// `FD 2F i16x8.lt_s` (simd)
+    I16x8LtS = 0x2F,
+    /// `FD 30 i16x8.lt_u` (simd)
+    I16x8LtU = 0x30,
+    /// `FD 31 i16x8.gt_s` (simd)
+    I16x8GtS = 0x31,
+    /// `FD 32 i16x8.gt_u` (simd)
+    I16x8GtU = 0x32,
+    /// `FD 33 i16x8.le_s` (simd)
+    I16x8LeS = 0x33,
+    /// `FD 34 i16x8.le_u` (simd)
+    I16x8LeU = 0x34,
+    /// `FD 35 i16x8.ge_s` (simd)
+    I16x8GeS = 0x35,
+    /// `FD 36 i16x8.ge_u` (simd)
+    I16x8GeU = 0x36,
+    /// `FD 37 i32x4.eq` (simd)
+    I32x4Eq = 0x37,
+    /// `FD 38 i32x4.ne` (simd)
+    I32x4Ne = 0x38,
+    /// `FD 39 i32x4.lt_s` (simd)
+    I32x4LtS = 0x39,
+    /// `FD 3A i32x4.lt_u` (simd)
+    I32x4LtU = 0x3A,
+    /// `FD 3B i32x4.gt_s` (simd)
+    I32x4GtS = 0x3B,
+    /// `FD 3C i32x4.gt_u` (simd)
+    I32x4GtU = 0x3C,
+    /// `FD 3D i32x4.le_s` (simd)
+    I32x4LeS = 0x3D,
+    /// `FD 3E i32x4.le_u` (simd)
+    I32x4LeU = 0x3E,
+    /// `FD 3F i32x4.ge_s` (simd)
+    I32x4GeS = 0x3F,
+    /// `FD 40 i32x4.ge_u` (simd)
+    I32x4GeU = 0x40,
+    /// `FD 41 f32x4.eq` (simd)
+    F32x4Eq = 0x41,
+    /// `FD 42 f32x4.ne` (simd)
+    F32x4Ne = 0x42,
+    /// `FD 43 f32x4.lt` (simd)
+    F32x4Lt = 0x43,
+    /// `FD 44 f32x4.gt` (simd)
+    F32x4Gt = 0x44,
+    /// `FD 45 f32x4.le` (simd)
+    F32x4Le = 0x45,
+    /// `FD 46 f32x4.ge` (simd)
+    F32x4Ge = 0x46,
+    /// `FD 47 f64x2.eq` (simd)
+    F64x2Eq = 0x47,
+    /// `FD 48 f64x2.ne` (simd)
+    F64x2Ne = 0x48,
+    /// `FD 49 f64x2.lt` (simd)
+    F64x2Lt = 0x49,
+    /// `FD 4A f64x2.gt` (simd)
+    F64x2Gt = 0x4A,
+    /// `FD 4B f64x2.le` (simd)
+    F64x2Le = 0x4B,
+    /// `FD 4C f64x2.ge` (simd)
+    F64x2Ge = 0x4C,
+    /// `FD 4D v128.not` (simd)
+    V128Not = 0x4D,
+    /// `FD 4E v128.and` (simd)
+    V128And = 0x4E,
+    /// `FD 4F v128.andnot` (simd)
+    V128Andnot = 0x4F,
+    /// `FD 50 v128.or` (simd)
+    V128Or = 0x50,
+    /// `FD 51 v128.xor` (simd)
+    V128Xor = 0x51,
+    /// `FD 52 v128.bitselect` (simd)
+    V128Bitselect = 0x52,
+    /// `FD 53 v128.any_true` (simd)
+    V128AnyTrue = 0x53,
+    /// `FD 54 v128.load8_lane align offset laneidx` (simd)
+    V128Load8Lane = 0x54,
+    /// `FD 55 v128.load16_lane align offset laneidx` (simd)
+    V128Load16Lane = 0x55,
+    /// `FD 56 v128.load32_lane align offset laneidx` (simd)
+    V128Load32Lane = 0x56,
+    /// `FD 57 v128.load64_lane align offset laneidx` (simd)
+    V128Load64Lane = 0x57,
+    /// `FD 58 v128.store8_lane align offset laneidx` (simd)
+    V128Store8Lane = 0x58,
+    /// `FD 59 v128.store16_lane align offset laneidx` (simd)
+    V128Store16Lane = 0x59,
+    /// `FD 5A v128.store32_lane align offset laneidx` (simd)
+    V128Store32Lane = 0x5A,
+    /// `FD 5B v128.store64_lane align offset laneidx` (simd)
+    V128Store64Lane = 0x5B,
+    /// `FD 5C v128.load32_zero align offset` (simd)
+    V128Load32Zero = 0x5C,
+    /// `FD 5D v128.load64_zero align offset` (simd)
+    V128Load64Zero = 0x5D,
+    /// `FD 5E f32x4.demote_f64x2_zero` (simd)
+    F32x4DemoteF64x2Zero = 0x5E,
+    /// `FD 5F f64x2.promote_low_f32x4` (simd)
+    F64x2PromoteLowF32x4 = 0x5F,
+    /// `FD 60 i8x16.abs` (simd)
+    I8x16Abs = 0x60,
+    /// `FD 61 i8x16.neg` (simd)
+    I8x16Neg = 0x61,
+    /// `FD 62 i8x16.popcnt` (simd)
+    I8x16Popcnt = 0x62,
+    /// `FD 63 i8x16.all_true` (simd)
+    I8x16AllTrue = 0x63,
+    /// `FD 64 i8x16.bitmask` (simd)
+    I8x16Bitmask = 0x64,
+    /// `FD 65 i8x16.narrow_i16x8_s` (simd)
+    I8x16NarrowI16x8S = 0x65,
+    /// `FD 66 i8x16.narrow_i16x8_u` (simd)
+    I8x16NarrowI16x8U = 0x66,
+    /// `FD 67 f32x4.ceil` (simd)
+    F32x4Ceil = 0x67,
+    /// `FD 68 f32x4.floor` (simd)
+    F32x4Floor = 0x68,
+    /// `FD 69 f32x4.trunc` (simd)
+    F32x4Trunc = 0x69,
+    /// `FD 6A f32x4.nearest` (simd)
+    F32x4Nearest = 0x6A,
+    /// `FD 6B i8x16.shl` (simd)
+    I8x16Shl = 0x6B,
+    /// `FD 6C i8x16.shr_s` (simd)
+    I8x16ShrS = 0x6C,
+    /// `FD 6D i8x16.shr_u` (simd)
+    I8x16ShrU = 0x6D,
+    /// `FD 6E i8x16.add` (simd)
+    I8x16Add = 0x6E,
+    /// `FD 6F i8x16.add_sat_s` (simd)
+    I8x16AddSatS = 0x6F,
+    /// `FD 70 i8x16.add_sat_u` (simd)
+    I8x16AddSatU = 0x70,
+    /// `FD 71 i8x16.sub` (simd)
+    I8x16Sub = 0x71,
+    /// `FD 72 i8x16.sub_sat_s` (simd)
+    I8x16SubSatS = 0x72,
+    /// `FD 73 i8x16.sub_sat_u` (simd)
+    I8x16SubSatU = 0x73,
+    /// `FD 74 f64x2.ceil` (simd)
+    F64x2Ceil = 0x74,
+    /// `FD 75 f64x2.floor` (simd)
+    F64x2Floor = 0x75,
+    /// `FD 76 i8x16.min_s` (simd)
+    I8x16MinS = 0x76,
+    /// `FD 77 i8x16.min_u` (simd)
+    I8x16MinU = 0x77,
+    /// `FD 78 i8x16.max_s` (simd)
+    I8x16MaxS = 0x78,
+    /// `FD 79 i8x16.max_u` (simd)
+    I8x16MaxU = 0x79,
+    /// `FD 7A f64x2.trunc` (simd)
+    F64x2Trunc = 0x7A,
+    /// `FD 7B i8x16.avgr_u` (simd)
+    I8x16AvgrU = 0x7B,
+    /// `FD 7C i16x8.extadd_pairwise_i8x16_s` (simd)
+    I16x8ExtaddPairwiseI8x16S = 0x7C,
+    /// `FD 7D i16x8.extadd_pairwise_i8x16_u` (simd)
+    I16x8ExtaddPairwiseI8x16U = 0x7D,
+    /// `FD 7E i32x4.extadd_pairwise_i16x8_s` (simd)
+    I32x4ExtaddPairwiseI16x8S = 0x7E,
+    /// `FD 7F i32x4.extadd_pairwise_i16x8_u` (simd)
+    I32x4ExtaddPairwiseI16x8U = 0x7F,
+    /// `FD 80 i16x8.abs` (simd)
+    I16x8Abs = 0x80,
+    /// `FD 81 i16x8.neg` (simd)
+    I16x8Neg = 0x81,
+    /// `FD 82 i16x8.q15mulr_sat_s` (simd)
+    I16x8Q15mulrSatS = 0x82,
+    /// `FD 83 i16x8.all_true` (simd)
+    I16x8AllTrue = 0x83,
+    /// `FD 84 i16x8.bitmask` (simd)
+    I16x8Bitmask = 0x84,
+    /// `FD 85 i16x8.narrow_i32x4_s` (simd)
+    I16x8NarrowI32x4S = 0x85,
+    /// `FD 86 i16x8.narrow_i32x4_u` (simd)
+    I16x8NarrowI32x4U = 0x86,
+    /// `FD 87 i16x8.extend_low_i8x16_s` (simd)
+    I16x8ExtendLowI8x16S = 0x87,
+    /// `FD 88 i16x8.extend_high_i8x16_s` (simd)
+    I16x8ExtendHighI8x16S = 0x88,
+    /// `FD 89 i16x8.extend_low_i8x16_u` (simd)
+    I16x8ExtendLowI8x16U = 0x89,
+    /// `FD 8A i16x8.extend_high_i8x16_u` (simd)
+    I16x8ExtendHighI8x16U = 0x8A,
+    /// `FD 8B i16x8.shl` (simd)
+    I16x8Shl = 0x8B,
+    /// `FD 8C i16x8.shr_s` (simd)
+    I16x8ShrS = 0x8C,
+    /// `FD 8D i16x8.shr_u` (simd)
+    I16x8ShrU = 0x8D,
+    /// `FD 8E i16x8.add` (simd)
+    I16x8Add = 0x8E,
+    /// `FD 8F i16x8.add_sat_s` (simd)
+    I16x8AddSatS = 0x8F,
+    /// `FD 90 i16x8.add_sat_u` (simd)
+    I16x8AddSatU = 0x90,
+    /// `FD 91 i16x8.sub` (simd)
+    I16x8Sub = 0x91,
+    /// `FD 92 i16x8.sub_sat_s` (simd)
+    I16x8SubSatS = 0x92,
+    /// `FD 93 i16x8.sub_sat_u` (simd)
+    I16x8SubSatU = 0x93,
+    /// `FD 94 f64x2.nearest` (simd)
+    F64x2Nearest = 0x94,
+    /// `FD 95 i16x8.mul` (simd)
+    I16x8Mul = 0x95,
+    /// `FD 96 i16x8.min_s` (simd)
+    I16x8MinS = 0x96,
+    /// `FD 97 i16x8.min_u` (simd)
+    I16x8MinU = 0x97,
+    /// `FD 98 i16x8.max_s` (simd)
+    I16x8MaxS = 0x98,
+    /// `FD 99 i16x8.max_u` (simd)
+    I16x8MaxU = 0x99,
+    /// `FD 9B i16x8.avgr_u` (simd)
+    I16x8AvgrU = 0x9B,
+    /// `FD 9C i16x8.extmul_low_i8x16_s` (simd)
+    I16x8ExtmulLowI8x16S = 0x9C,
+    /// `FD 9D i16x8.extmul_high_i8x16_s` (simd)
+    I16x8ExtmulHighI8x16S = 0x9D,
+    /// `FD 9E i16x8.extmul_low_i8x16_u` (simd)
+    I16x8ExtmulLowI8x16U = 0x9E,
+    /// `FD 9F i16x8.extmul_high_i8x16_u` (simd)
+    I16x8ExtmulHighI8x16U = 0x9F,
+    /// `FD A0 i32x4.abs` (simd)
+    I32x4Abs = 0xA0,
+    /// `FD A1 i32x4.neg` (simd)
+    I32x4Neg = 0xA1,
+    /// `FD A3 i32x4.all_true` (simd)
+    I32x4AllTrue = 0xA3,
+    /// `FD A4 i32x4.bitmask` (simd)
+    I32x4Bitmask = 0xA4,
+    /// `FD A7 i32x4.extend_low_i16x8_s` (simd)
+    I32x4ExtendLowI16x8S = 0xA7,
+    /// `FD A8 i32x4.extend_high_i16x8_s` (simd)
+    I32x4ExtendHighI16x8S = 0xA8,
+    /// `FD A9 i32x4.extend_low_i16x8_u` (simd)
+    I32x4ExtendLowI16x8U = 0xA9,
+    /// `FD AA i32x4.extend_high_i16x8_u` (simd)
+    I32x4ExtendHighI16x8U = 0xAA,
+    /// `FD AB i32x4.shl` (simd)
+    I32x4Shl = 0xAB,
+    /// `FD AC i32x4.shr_s` (simd)
+    I32x4ShrS = 0xAC,
+    /// `FD AD i32x4.shr_u` (simd)
+    I32x4ShrU = 0xAD,
+    /// `FD AE i32x4.add` (simd)
+    I32x4Add = 0xAE,
+    /// `FD B1 i32x4.sub` (simd)
+    I32x4Sub = 0xB1,
+    /// `FD B5 i32x4.mul` (simd)
+    I32x4Mul = 0xB5,
+    /// `FD B6 i32x4.min_s` (simd)
+    I32x4MinS = 0xB6,
+    /// `FD B7 i32x4.min_u` (simd)
+    I32x4MinU = 0xB7,
+    /// `FD B8 i32x4.max_s` (simd)
+    I32x4MaxS = 0xB8,
+    /// `FD B9 i32x4.max_u` (simd)
+    I32x4MaxU = 0xB9,
+    /// `FD BA i32x4.dot_i16x8_s` (simd)
+    I32x4DotI16x8S = 0xBA,
+    /// `FD BC i32x4.extmul_low_i16x8_s` (simd)
+    I32x4ExtmulLowI16x8S = 0xBC,
+    /// `FD BD i32x4.extmul_high_i16x8_s` (simd)
+    I32x4ExtmulHighI16x8S = 0xBD,
+    /// `FD BE i32x4.extmul_low_i16x8_u` (simd)
+    I32x4ExtmulLowI16x8U = 0xBE,
+    /// `FD BF i32x4.extmul_high_i16x8_u` (simd)
+    I32x4ExtmulHighI16x8U = 0xBF,
+    /// `FD C0 i64x2.abs` (simd)
+    I64x2Abs = 0xC0,
+    /// `FD C1 i64x2.neg` (simd)
+    I64x2Neg = 0xC1,
+    /// `FD C3 i64x2.all_true` (simd)
+    I64x2AllTrue = 0xC3,
+    /// `FD C4 i64x2.bitmask` (simd)
+    I64x2Bitmask = 0xC4,
+    /// `FD C7 i64x2.extend_low_i32x4_s` (simd)
+    I64x2ExtendLowI32x4S = 0xC7,
+    /// `FD C8 i64x2.extend_high_i32x4_s` (simd)
+    I64x2ExtendHighI32x4S = 0xC8,
+    /// `FD C9 i64x2.extend_low_i32x4_u` (simd)
+    I64x2ExtendLowI32x4U = 0xC9,
+    /// `FD CA i64x2.extend_high_i32x4_u` (simd)
+    I64x2ExtendHighI32x4U = 0xCA,
+    /// `FD CB i64x2.shl` (simd)
+    I64x2Shl = 0xCB,
+    /// `FD CC i64x2.shr_s` (simd)
+    I64x2ShrS = 0xCC,
+    /// `FD CD i64x2.shr_u` (simd)
+    I64x2ShrU = 0xCD,
+    /// `FD CE i64x2.add` (simd)
+    I64x2Add = 0xCE,
+    /// `FD D1 i64x2.sub` (simd)
+    I64x2Sub = 0xD1,
+    /// `FD D5 i64x2.mul` (simd)
+    I64x2Mul = 0xD5,
+    /// `FD D6 i64x2.eq` (simd)
+    I64x2Eq = 0xD6,
+    /// `FD D7 i64x2.ne` (simd)
+    I64x2Ne = 0xD7,
+    /// `FD D8 i64x2.lt_s` (simd)
+    I64x2LtS = 0xD8,
+    /// `FD D9 i64x2.gt_s` (simd)
+    I64x2GtS = 0xD9,
+    /// `FD DA i64x2.le_s` (simd)
+    I64x2LeS = 0xDA,
+    /// `FD DB i64x2.ge_s` (simd)
+    I64x2GeS = 0xDB,
+    /// `FD DC i64x2.extmul_low_i32x4_s` (simd)
+    I64x2ExtmulLowI32x4S = 0xDC,
+    /// `FD DD i64x2.extmul_high_i32x4_s` (simd)
+    I64x2ExtmulHighI32x4S = 0xDD,
+    /// `FD DE i64x2.extmul_low_i32x4_u` (simd)
+    I64x2ExtmulLowI32x4U = 0xDE,
+    /// `FD DF i64x2.extmul_high_i32x4_u` (simd)
+    I64x2ExtmulHighI32x4U = 0xDF,
+    /// `FD E0 f32x4.abs` (simd)
+    F32x4Abs = 0xE0,
+    /// `FD E1 f32x4.neg` (simd)
+    F32x4Neg = 0xE1,
+    /// `FD E3 f32x4.sqrt` (simd)
+    F32x4Sqrt = 0xE3,
+    /// `FD E4 f32x4.add` (simd)
+    F32x4Add = 0xE4,
+    /// `FD E5 f32x4.sub` (simd)
+    F32x4Sub = 0xE5,
+    /// `FD E6 f32x4.mul` (simd)
+    F32x4Mul = 0xE6,
+    /// `FD E7 f32x4.div` (simd)
+    F32x4Div = 0xE7,
+    /// `FD E8 f32x4.min` (simd)
+    F32x4Min = 0xE8,
+    /// `FD E9 f32x4.max` (simd)
+    F32x4Max = 0xE9,
+    /// `FD EA f32x4.pmin` (simd)
+    F32x4Pmin = 0xEA,
+    /// `FD EB f32x4.pmax` (simd)
+    F32x4Pmax = 0xEB,
+    /// `FD EC f64x2.abs` (simd)
+    F64x2Abs = 0xEC,
+    /// `FD ED f64x2.neg` (simd)
+    F64x2Neg = 0xED,
+    /// `FD EF f64x2.sqrt` (simd)
+    F64x2Sqrt = 0xEF,
+    /// `FD F0 f64x2.add` (simd)
+    F64x2Add = 0xF0,
+    /// `FD F1 f64x2.sub` (simd)
+    F64x2Sub = 0xF1,
+    /// `FD F2 f64x2.mul` (simd)
+    F64x2Mul = 0xF2,
+    /// `FD F3 f64x2.div` (simd)
+    F64x2Div = 0xF3,
+    /// `FD F4 f64x2.min` (simd)
+    F64x2Min = 0xF4,
+    /// `FD F5 f64x2.max` (simd)
+    F64x2Max = 0xF5,
+    /// `FD F6 f64x2.pmin` (simd)
+    F64x2Pmin = 0xF6,
+    /// `FD F7 f64x2.pmax` (simd)
+    F64x2Pmax = 0xF7,
+    /// `FD F8 i32x4.trunc_sat_f32x4_s` (simd)
+    I32x4TruncSatF32x4S = 0xF8,
+    /// `FD F9 i32x4.trunc_sat_f32x4_u` (simd)
+    I32x4TruncSatF32x4U = 0xF9,
+    /// `FD FA f32x4.convert_i32x4_s` (simd)
+    F32x4ConvertI32x4S = 0xFA,
+    /// `FD FB f32x4.convert_i32x4_u` (simd)
+    F32x4ConvertI32x4U = 0xFB,
+    /// `FD FC i32x4.trunc_sat_f64x2_s_zero` (simd)
+    I32x4TruncSatF64x2SZero = 0xFC,
+    /// `FD FD i32x4.trunc_sat_f64x2_u_zero` (simd)
+    I32x4TruncSatF64x2UZero = 0xFD,
+    /// `FD FE f64x2.convert_low_i32x4_s` (simd)
+    F64x2ConvertLowI32x4S = 0xFE,
+    /// `FD FF f64x2.convert_low_i32x4_u` (simd)
+    F64x2ConvertLowI32x4U = 0xFF,
 }
 
 #[non_exhaustive]
@@ -378,10 +1068,15 @@ pub enum WasmOperandType {
     Global,
     Memory,
     MemSize,
+    Select,
+    Table,
+    RefType,
+    Func,
     I32,
     I64,
     F32,
     F64,
+    Tag,
 }
 
 #[non_exhaustive]
@@ -393,6 +1088,12 @@ pub enum WasmProposalType {
     MvpF32,
     MvpF64,
     SignExtend,
+    NonTrappingFloatToInt,
+    BulkMemory,
+    ReferenceTypes,
+    Threads,
+    ExceptionHandling,
+    Simd,
 }
 
 impl WasmOpcode {
@@ -404,6 +1105,10 @@ impl WasmOpcode {
             0x03 => Some(Self::Loop),
             0x04 => Some(Self::If),
             0x05 => Some(Self::Else),
+            0x06 => Some(Self::Try),
+            0x07 => Some(Self::Catch),
+            0x08 => Some(Self::Throw),
+            0x09 => Some(Self::Rethrow),
             0x0B => Some(Self::End),
             0x0C => Some(Self::Br),
             0x0D => Some(Self::BrIf),
@@ -413,13 +1118,18 @@ impl WasmOpcode {
             0x11 => Some(Self::CallIndirect),
             0x12 => Some(Self::ReturnCall),
             0x13 => Some(Self::ReturnCallIndirect),
+            0x18 => Some(Self::Delegate),
+            0x19 => Some(Self::CatchAll),
             0x1A => Some(Self::Drop),
             0x1B => Some(Self::Select),
+            0x1C => Some(Self::SelectT),
             0x20 => Some(Self::LocalGet),
             0x21 => Some(Self::LocalSet),
             0x22 => Some(Self::LocalTee),
             0x23 => Some(Self::GlobalGet),
             0x24 => Some(Self::GlobalSet),
+            0x25 => Some(Self::TableGet),
+            0x26 => Some(Self::TableSet),
             0x28 => Some(Self::I32Load),
             0x29 => Some(Self::I64Load),
             0x2A => Some(Self::F32Load),
@@ -577,6 +1287,12 @@ impl WasmOpcode {
             0xC2 => Some(Self::I64Extend8S),
             0xC3 => Some(Self::I64Extend16S),
             0xC4 => Some(Self::I64Extend32S),
+            0xD0 => Some(Self::RefNull),
+            0xD1 => Some(Self::RefIsNull),
+            0xD2 => Some(Self::RefFunc),
+            0xFC => Some(Self::PrefixFC),
+            0xFD => Some(Self::PrefixFD),
+            0xFE => Some(Self::PrefixFE),
             _ => None,
         }
     }
@@ -589,6 +1305,10 @@ impl WasmOpcode {
             Self::Loop => "loop",
             Self::If => "if",
             Self::Else => "else",
+            Self::Try => "try",
+            Self::Catch => "catch",
+            Self::Throw => "throw",
+            Self::Rethrow => "rethrow",
             Self::End => "end",
             Self::Br => "br",
             Self::BrIf => "br_if",
@@ -598,13 +1318,18 @@ impl WasmOpcode {
             Self::CallIndirect => "call_indirect",
             Self::ReturnCall => "return_call",
             Self::ReturnCallIndirect => "return_call_indirect",
+            Self::Delegate => "delegate",
+            Self::CatchAll => "catch_all",
             Self::Drop => "drop",
             Self::Select => "select",
+            Self::SelectT => "select",
             Self::LocalGet => "local.get",
             Self::LocalSet => "local.set",
             Self::LocalTee => "local.tee",
             Self::GlobalGet => "global.get",
             Self::GlobalSet => "global.set",
+            Self::TableGet => "table.get",
+            Self::TableSet => "table.set",
             Self::I32Load => "i32.load",
             Self::I64Load => "i64.load",
             Self::F32Load => "f32.load",
@@ -762,6 +1487,12 @@ impl WasmOpcode {
             Self::I64Extend8S => "i64.extend8_s",
             Self::I64Extend16S => "i64.extend16_s",
             Self::I64Extend32S => "i64.extend32_s",
+            Self::RefNull => "ref.null",
+            Self::RefIsNull => "ref.is_null",
+            Self::RefFunc => "ref.func",
+            Self::PrefixFC => "(prefix_fc)",
+            Self::PrefixFD => "(prefix_fd)",
+            Self::PrefixFE => "(prefix_fe)",
         }
     }
 
@@ -771,6 +1502,10 @@ impl WasmOpcode {
             Self::Loop => WasmOperandType::Block,
             Self::If => WasmOperandType::Block,
             Self::Else => WasmOperandType::Else,
+            Self::Try => WasmOperandType::Block,
+            Self::Catch => WasmOperandType::Tag,
+            Self::Throw => WasmOperandType::Tag,
+            Self::Rethrow => WasmOperandType::Br,
             Self::End => WasmOperandType::End,
             Self::Br => WasmOperandType::Br,
             Self::BrIf => WasmOperandType::Br,
@@ -779,11 +1514,18 @@ impl WasmOpcode {
             Self::CallIndirect => WasmOperandType::CallIndirect,
             Self::ReturnCall => WasmOperandType::Call,
             Self::ReturnCallIndirect => WasmOperandType::CallIndirect,
+            Self::Delegate => WasmOperandType::Br,
+            Self::CatchAll => WasmOperandType::Else,
             Self::LocalGet => WasmOperandType::Local,
             Self::LocalSet => WasmOperandType::Local,
             Self::LocalTee => WasmOperandType::Local,
             Self::GlobalGet => WasmOperandType::Global,
             Self::GlobalSet => WasmOperandType::Global,
+            Self::SelectT => WasmOperandType::Select,
+            Self::TableGet => WasmOperandType::Table,
+            Self::TableSet => WasmOperandType::Table,
+            Self::RefNull => WasmOperandType::RefType,
+            Self::RefFunc => WasmOperandType::Func,
             Self::I32Load => WasmOperandType::Memory,
             Self::I64Load => WasmOperandType::Memory,
             Self::F32Load => WasmOperandType::Memory,
@@ -819,8 +1561,14 @@ impl WasmOpcode {
 
     pub const fn proposal_type(&self) -> WasmProposalType {
         match *self {
+            Self::Try => WasmProposalType::ExceptionHandling,
+            Self::Catch => WasmProposalType::ExceptionHandling,
+            Self::Throw => WasmProposalType::ExceptionHandling,
+            Self::Rethrow => WasmProposalType::ExceptionHandling,
             Self::ReturnCall => WasmProposalType::TailCall,
             Self::ReturnCallIndirect => WasmProposalType::TailCall,
+            Self::Delegate => WasmProposalType::ExceptionHandling,
+            Self::CatchAll => WasmProposalType::ExceptionHandling,
             Self::I64Load => WasmProposalType::MvpI64,
             Self::F32Load => WasmProposalType::MvpF32,
             Self::F64Load => WasmProposalType::MvpF64,
@@ -938,7 +1686,720 @@ impl WasmOpcode {
             Self::I64Extend8S => WasmProposalType::SignExtend,
             Self::I64Extend16S => WasmProposalType::SignExtend,
             Self::I64Extend32S => WasmProposalType::SignExtend,
+            Self::SelectT => WasmProposalType::ReferenceTypes,
+            Self::TableGet => WasmProposalType::ReferenceTypes,
+            Self::TableSet => WasmProposalType::ReferenceTypes,
+            Self::RefNull => WasmProposalType::ReferenceTypes,
+            Self::RefIsNull => WasmProposalType::ReferenceTypes,
+            Self::RefFunc => WasmProposalType::ReferenceTypes,
+            Self::PrefixFD => WasmProposalType::Simd,
+            Self::PrefixFE => WasmProposalType::Threads,
             _ => WasmProposalType::Mvp,
         }
     }
 }
+
+impl WasmOpcodeFC {
+    pub const fn new(value: u32) -> Option<Self> {
+        match value {
+            0x00 => Some(Self::I32TruncSatF32S),
+            0x01 => Some(Self::I32TruncSatF32U),
+            0x02 => Some(Self::I32TruncSatF64S),
+            0x03 => Some(Self::I32TruncSatF64U),
+            0x04 => Some(Self::I64TruncSatF32S),
+            0x05 => Some(Self::I64TruncSatF32U),
+            0x06 => Some(Self::I64TruncSatF64S),
+            0x07 => Some(Self::I64TruncSatF64U),
+            0x08 => Some(Self::MemoryInit),
+            0x09 => Some(Self::DataDrop),
+            0x0A => Some(Self::MemoryCopy),
+            0x0B => Some(Self::MemoryFill),
+            0x0C => Some(Self::TableInit),
+            0x0D => Some(Self::ElemDrop),
+            0x0E => Some(Self::TableCopy),
+            0x0F => Some(Self::TableGrow),
+            0x10 => Some(Self::TableSize),
+            0x11 => Some(Self::TableFill),
+            _ => None,
+        }
+    }
+
+    pub const fn to_str(&self) -> &str {
+        match *self {
+            Self::I32TruncSatF32S => "i32.trunc_sat_f32_s",
+            Self::I32TruncSatF32U => "i32.trunc_sat_f32_u",
+            Self::I32TruncSatF64S => "i32.trunc_sat_f64_s",
+            Self::I32TruncSatF64U => "i32.trunc_sat_f64_u",
+            Self::I64TruncSatF32S => "i64.trunc_sat_f32_s",
+            Self::I64TruncSatF32U => "i64.trunc_sat_f32_u",
+            Self::I64TruncSatF64S => "i64.trunc_sat_f64_s",
+            Self::I64TruncSatF64U => "i64.trunc_sat_f64_u",
+            Self::MemoryInit => "memory.init",
+            Self::DataDrop => "data.drop",
+            Self::MemoryCopy => "memory.copy",
+            Self::MemoryFill => "memory.fill",
+            Self::TableInit => "table.init",
+            Self::ElemDrop => "elem.drop",
+            Self::TableCopy => "table.copy",
+            Self::TableGrow => "table.grow",
+            Self::TableSize => "table.size",
+            Self::TableFill => "table.fill",
+        }
+    }
+
+    pub const fn proposal_type(&self) -> WasmProposalType {
+        match *self {
+            Self::MemoryInit => WasmProposalType::BulkMemory,
+            Self::DataDrop => WasmProposalType::BulkMemory,
+            Self::MemoryCopy => WasmProposalType::BulkMemory,
+            Self::MemoryFill => WasmProposalType::BulkMemory,
+            Self::TableInit => WasmProposalType::BulkMemory,
+            Self::ElemDrop => WasmProposalType::BulkMemory,
+            Self::TableCopy => WasmProposalType::BulkMemory,
+            Self::TableGrow => WasmProposalType::ReferenceTypes,
+            Self::TableSize => WasmProposalType::ReferenceTypes,
+            Self::TableFill => WasmProposalType::ReferenceTypes,
+            _ => WasmProposalType::NonTrappingFloatToInt,
+        }
+    }
+}
+
+impl WasmOpcodeFE {
+    pub const fn new(value: u32) -> Option<Self> {
+        match value {
+            0x00 => Some(Self::MemoryAtomicNotify),
+            0x01 => Some(Self::MemoryAtomicWait32),
+            0x02 => Some(Self::MemoryAtomicWait64),
+            0x03 => Some(Self::AtomicFence),
+            0x10 => Some(Self::I32AtomicLoad),
+            0x11 => Some(Self::I64AtomicLoad),
+            0x12 => Some(Self::I32AtomicLoad8U),
+            0x13 => Some(Self::I32AtomicLoad16U),
+            0x14 => Some(Self::I64AtomicLoad8U),
+            0x15 => Some(Self::I64AtomicLoad16U),
+            0x16 => Some(Self::I64AtomicLoad32U),
+            0x17 => Some(Self::I32AtomicStore),
+            0x18 => Some(Self::I64AtomicStore),
+            0x19 => Some(Self::I32AtomicStore8),
+            0x1A => Some(Self::I32AtomicStore16),
+            0x1B => Some(Self::I64AtomicStore8),
+            0x1C => Some(Self::I64AtomicStore16),
+            0x1D => Some(Self::I64AtomicStore32),
+            0x1E => Some(Self::I32AtomicRmwAdd),
+            0x1F => Some(Self::I64AtomicRmwAdd),
+            0x20 => Some(Self::I32AtomicRmw8AddU),
+            0x21 => Some(Self::I32AtomicRmw16AddU),
+            0x22 => Some(Self::I64AtomicRmw8AddU),
+            0x23 => Some(Self::I64AtomicRmw16AddU),
+            0x24 => Some(Self::I64AtomicRmw32AddU),
+            0x25 => Some(Self::I32AtomicRmwSub),
+            0x26 => Some(Self::I64AtomicRmwSub),
+            0x27 => Some(Self::I32AtomicRmw8SubU),
+            0x28 => Some(Self::I32AtomicRmw16SubU),
+            0x29 => Some(Self::I64AtomicRmw8SubU),
+            0x2A => Some(Self::I64AtomicRmw16SubU),
+            0x2B => Some(Self::I64AtomicRmw32SubU),
+            0x2C => Some(Self::I32AtomicRmwAnd),
+            0x2D => Some(Self::I64AtomicRmwAnd),
+            0x2E => Some(Self::I32AtomicRmw8AndU),
+            0x2F => Some(Self::I32AtomicRmw16AndU),
+            0x30 => Some(Self::I64AtomicRmw8AndU),
+            0x31 => Some(Self::I64AtomicRmw16AndU),
+            0x32 => Some(Self::I64AtomicRmw32AndU),
+            0x33 => Some(Self::I32AtomicRmwOr),
+            0x34 => Some(Self::I64AtomicRmwOr),
+            0x35 => Some(Self::I32AtomicRmw8OrU),
+            0x36 => Some(Self::I32AtomicRmw16OrU),
+            0x37 => Some(Self::I64AtomicRmw8OrU),
+            0x38 => Some(Self::I64AtomicRmw16OrU),
+            0x39 => Some(Self::I64AtomicRmw32OrU),
+            0x3A => Some(Self::I32AtomicRmwXor),
+            0x3B => Some(Self::I64AtomicRmwXor),
+            0x3C => Some(Self::I32AtomicRmw8XorU),
+            0x3D => Some(Self::I32AtomicRmw16XorU),
+            0x3E => Some(Self::I64AtomicRmw8XorU),
+            0x3F => Some(Self::I64AtomicRmw16XorU),
+            0x40 => Some(Self::I64AtomicRmw32XorU),
+            0x41 => Some(Self::I32AtomicRmwXchg),
+            0x42 => Some(Self::I64AtomicRmwXchg),
+            0x43 => Some(Self::I32AtomicRmw8XchgU),
+            0x44 => Some(Self::I32AtomicRmw16XchgU),
+            0x45 => Some(Self::I64AtomicRmw8XchgU),
+            0x46 => Some(Self::I64AtomicRmw16XchgU),
+            0x47 => Some(Self::I64AtomicRmw32XchgU),
+            0x48 => Some(Self::I32AtomicRmwCmpxchg),
+            0x49 => Some(Self::I64AtomicRmwCmpxchg),
+            0x4A => Some(Self::I32AtomicRmw8CmpxchgU),
+            0x4B => Some(Self::I32AtomicRmw16CmpxchgU),
+            0x4C => Some(Self::I64AtomicRmw8CmpxchgU),
+            0x4D => Some(Self::I64AtomicRmw16CmpxchgU),
+            0x4E => Some(Self::I64AtomicRmw32CmpxchgU),
+            _ => None,
+        }
+    }
+
+    pub const fn to_str(&self) -> &str {
+        match *self {
+            Self::MemoryAtomicNotify => "memory.atomic.notify",
+            Self::MemoryAtomicWait32 => "memory.atomic.wait32",
+            Self::MemoryAtomicWait64 => "memory.atomic.wait64",
+            Self::AtomicFence => "atomic.fence",
+            Self::I32AtomicLoad => "i32.atomic.load",
+            Self::I64AtomicLoad => "i64.atomic.load",
+            Self::I32AtomicLoad8U => "i32.atomic.load8_u",
+            Self::I32AtomicLoad16U => "i32.atomic.load16_u",
+            Self::I64AtomicLoad8U => "i64.atomic.load8_u",
+            Self::I64AtomicLoad16U => "i64.atomic.load16_u",
+            Self::I64AtomicLoad32U => "i64.atomic.load32_u",
+            Self::I32AtomicStore => "i32.atomic.store",
+            Self::I64AtomicStore => "i64.atomic.store",
+            Self::I32AtomicStore8 => "i32.atomic.store8",
+            Self::I32AtomicStore16 => "i32.atomic.store16",
+            Self::I64AtomicStore8 => "i64.atomic.store8",
+            Self::I64AtomicStore16 => "i64.atomic.store16",
+            Self::I64AtomicStore32 => "i64.atomic.store32",
+            Self::I32AtomicRmwAdd => "i32.atomic.rmw.add",
+            Self::I64AtomicRmwAdd => "i64.atomic.rmw.add",
+            Self::I32AtomicRmw8AddU => "i32.atomic.rmw8.add_u",
+            Self::I32AtomicRmw16AddU => "i32.atomic.rmw16.add_u",
+            Self::I64AtomicRmw8AddU => "i64.atomic.rmw8.add_u",
+            Self::I64AtomicRmw16AddU => "i64.atomic.rmw16.add_u",
+            Self::I64AtomicRmw32AddU => "i64.atomic.rmw32.add_u",
+            Self::I32AtomicRmwSub => "i32.atomic.rmw.sub",
+            Self::I64AtomicRmwSub => "i64.atomic.rmw.sub",
+            Self::I32AtomicRmw8SubU => "i32.atomic.rmw8.sub_u",
+            Self::I32AtomicRmw16SubU => "i32.atomic.rmw16.sub_u",
+            Self::I64AtomicRmw8SubU => "i64.atomic.rmw8.sub_u",
+            Self::I64AtomicRmw16SubU => "i64.atomic.rmw16.sub_u",
+            Self::I64AtomicRmw32SubU => "i64.atomic.rmw32.sub_u",
+            Self::I32AtomicRmwAnd => "i32.atomic.rmw.and",
+            Self::I64AtomicRmwAnd => "i64.atomic.rmw.and",
+            Self::I32AtomicRmw8AndU => "i32.atomic.rmw8.and_u",
+            Self::I32AtomicRmw16AndU => "i32.atomic.rmw16.and_u",
+            Self::I64AtomicRmw8AndU => "i64.atomic.rmw8.and_u",
+            Self::I64AtomicRmw16AndU => "i64.atomic.rmw16.and_u",
+            Self::I64AtomicRmw32AndU => "i64.atomic.rmw32.and_u",
+            Self::I32AtomicRmwOr => "i32.atomic.rmw.or",
+            Self::I64AtomicRmwOr => "i64.atomic.rmw.or",
+            Self::I32AtomicRmw8OrU => "i32.atomic.rmw8.or_u",
+            Self::I32AtomicRmw16OrU => "i32.atomic.rmw16.or_u",
+            Self::I64AtomicRmw8OrU => "i64.atomic.rmw8.or_u",
+            Self::I64AtomicRmw16OrU => "i64.atomic.rmw16.or_u",
+            Self::I64AtomicRmw32OrU => "i64.atomic.rmw32.or_u",
+            Self::I32AtomicRmwXor => "i32.atomic.rmw.xor",
+            Self::I64AtomicRmwXor => "i64.atomic.rmw.xor",
+            Self::I32AtomicRmw8XorU => "i32.atomic.rmw8.xor_u",
+            Self::I32AtomicRmw16XorU => "i32.atomic.rmw16.xor_u",
+            Self::I64AtomicRmw8XorU => "i64.atomic.rmw8.xor_u",
+            Self::I64AtomicRmw16XorU => "i64.atomic.rmw16.xor_u",
+            Self::I64AtomicRmw32XorU => "i64.atomic.rmw32.xor_u",
+            Self::I32AtomicRmwXchg => "i32.atomic.rmw.xchg",
+            Self::I64AtomicRmwXchg => "i64.atomic.rmw.xchg",
+            Self::I32AtomicRmw8XchgU => "i32.atomic.rmw8.xchg_u",
+            Self::I32AtomicRmw16XchgU => "i32.atomic.rmw16.xchg_u",
+            Self::I64AtomicRmw8XchgU => "i64.atomic.rmw8.xchg_u",
+            Self::I64AtomicRmw16XchgU => "i64.atomic.rmw16.xchg_u",
+            Self::I64AtomicRmw32XchgU => "i64.atomic.rmw32.xchg_u",
+            Self::I32AtomicRmwCmpxchg => "i32.atomic.rmw.cmpxchg",
+            Self::I64AtomicRmwCmpxchg => "i64.atomic.rmw.cmpxchg",
+            Self::I32AtomicRmw8CmpxchgU => "i32.atomic.rmw8.cmpxchg_u",
+            Self::I32AtomicRmw16CmpxchgU => "i32.atomic.rmw16.cmpxchg_u",
+            Self::I64AtomicRmw8CmpxchgU => "i64.atomic.rmw8.cmpxchg_u",
+            Self::I64AtomicRmw16CmpxchgU => "i64.atomic.rmw16.cmpxchg_u",
+            Self::I64AtomicRmw32CmpxchgU => "i64.atomic.rmw32.cmpxchg_u",
+        }
+    }
+
+    pub const fn proposal_type(&self) -> WasmProposalType {
+        WasmProposalType::Threads
+    }
+}
+
+impl WasmOpcodeFD {
+    pub const fn new(value: u32) -> Option<Self> {
+        match value {
+            0x00 => Some(Self::V128Load),
+            0x01 => Some(Self::V128Load8x8S),
+            0x02 => Some(Self::V128Load8x8U),
+            0x03 => Some(Self::V128Load16x4S),
+            0x04 => Some(Self::V128Load16x4U),
+            0x05 => Some(Self::V128Load32x2S),
+            0x06 => Some(Self::V128Load32x2U),
+            0x07 => Some(Self::V128Load8Splat),
+            0x08 => Some(Self::V128Load16Splat),
+            0x09 => Some(Self::V128Load32Splat),
+            0x0A => Some(Self::V128Load64Splat),
+            0x0B => Some(Self::V128Store),
+            0x0C => Some(Self::V128Const),
+            0x0D => Some(Self::I8x16Shuffle),
+            0x0E => Some(Self::I8x16Swizzle),
+            0x0F => Some(Self::I8x16Splat),
+            0x10 => Some(Self::I16x8Splat),
+            0x11 => Some(Self::I32x4Splat),
+            0x12 => Some(Self::I64x2Splat),
+            0x13 => Some(Self::F32x4Splat),
+            0x14 => Some(Self::F64x2Splat),
+            0x15 => Some(Self::I8x16ExtractLaneS),
+            0x16 => Some(Self::I8x16ExtractLaneU),
+            0x17 => Some(Self::I8x16ReplaceLane),
+            0x18 => Some(Self::I16x8ExtractLaneS),
+            0x19 => Some(Self::I16x8ExtractLaneU),
+            0x1A => Some(Self::I16x8ReplaceLane),
+            0x1B => Some(Self::I32x4ExtractLane),
+            0x1C => Some(Self::I32x4ReplaceLane),
+            0x1D => Some(Self::I64x2ExtractLane),
+            0x1E => Some(Self::I64x2ReplaceLane),
+            0x1F => Some(Self::F32x4ExtractLane),
+            0x20 => Some(Self::F32x4ReplaceLane),
+            0x21 => Some(Self::F64x2ExtractLane),
+            0x22 => Some(Self::F64x2ReplaceLane),
+            0x23 => Some(Self::I8x16Eq),
+            0x24 => Some(Self::I8x16Ne),
+            0x25 => Some(Self::I8x16LtS),
+            0x26 => Some(Self::I8x16LtU),
+            0x27 => Some(Self::I8x16GtS),
+            0x28 => Some(Self::I8x16GtU),
+            0x29 => Some(Self::I8x16LeS),
+            0x2A => Some(Self::I8x16LeU),
+            0x2B => Some(Self::I8x16GeS),
+            0x2C => Some(Self::I8x16GeU),
+            0x2D => Some(Self::I16x8Eq),
+            0x2E => Some(Self::I16x8Ne),
+            0x2F => Some(Self::I16x8LtS),
+            0x30 => Some(Self::I16x8LtU),
+            0x31 => Some(Self::I16x8GtS),
+            0x32 => Some(Self::I16x8GtU),
+            0x33 => Some(Self::I16x8LeS),
+            0x34 => Some(Self::I16x8LeU),
+            0x35 => Some(Self::I16x8GeS),
+            0x36 => Some(Self::I16x8GeU),
+            0x37 => Some(Self::I32x4Eq),
+            0x38 => Some(Self::I32x4Ne),
+            0x39 => Some(Self::I32x4LtS),
+            0x3A => Some(Self::I32x4LtU),
+            0x3B => Some(Self::I32x4GtS),
+            0x3C => Some(Self::I32x4GtU),
+            0x3D => Some(Self::I32x4LeS),
+            0x3E => Some(Self::I32x4LeU),
+            0x3F => Some(Self::I32x4GeS),
+            0x40 => Some(Self::I32x4GeU),
+            0x41 => Some(Self::F32x4Eq),
+            0x42 => Some(Self::F32x4Ne),
+            0x43 => Some(Self::F32x4Lt),
+            0x44 => Some(Self::F32x4Gt),
+            0x45 => Some(Self::F32x4Le),
+            0x46 => Some(Self::F32x4Ge),
+            0x47 => Some(Self::F64x2Eq),
+            0x48 => Some(Self::F64x2Ne),
+            0x49 => Some(Self::F64x2Lt),
+            0x4A => Some(Self::F64x2Gt),
+            0x4B => Some(Self::F64x2Le),
+            0x4C => Some(Self::F64x2Ge),
+            0x4D => Some(Self::V128Not),
+            0x4E => Some(Self::V128And),
+            0x4F => Some(Self::V128Andnot),
+            0x50 => Some(Self::V128Or),
+            0x51 => Some(Self::V128Xor),
+            0x52 => Some(Self::V128Bitselect),
+            0x53 => Some(Self::V128AnyTrue),
+            0x54 => Some(Self::V128Load8Lane),
+            0x55 => Some(Self::V128Load16Lane),
+            0x56 => Some(Self::V128Load32Lane),
+            0x57 => Some(Self::V128Load64Lane),
+            0x58 => Some(Self::V128Store8Lane),
+            0x59 => Some(Self::V128Store16Lane),
+            0x5A => Some(Self::V128Store32Lane),
+            0x5B => Some(Self::V128Store64Lane),
+            0x5C => Some(Self::V128Load32Zero),
+            0x5D => Some(Self::V128Load64Zero),
+            0x5E => Some(Self::F32x4DemoteF64x2Zero),
+            0x5F => Some(Self::F64x2PromoteLowF32x4),
+            0x60 => Some(Self::I8x16Abs),
+            0x61 => Some(Self::I8x16Neg),
+            0x62 => Some(Self::I8x16Popcnt),
+            0x63 => Some(Self::I8x16AllTrue),
+            0x64 => Some(Self::I8x16Bitmask),
+            0x65 => Some(Self::I8x16NarrowI16x8S),
+            0x66 => Some(Self::I8x16NarrowI16x8U),
+            0x67 => Some(Self::F32x4Ceil),
+            0x68 => Some(Self::F32x4Floor),
+            0x69 => Some(Self::F32x4Trunc),
+            0x6A => Some(Self::F32x4Nearest),
+            0x6B => Some(Self::I8x16Shl),
+            0x6C => Some(Self::I8x16ShrS),
+            0x6D => Some(Self::I8x16ShrU),
+            0x6E => Some(Self::I8x16Add),
+            0x6F => Some(Self::I8x16AddSatS),
+            0x70 => Some(Self::I8x16AddSatU),
+            0x71 => Some(Self::I8x16Sub),
+            0x72 => Some(Self::I8x16SubSatS),
+            0x73 => Some(Self::I8x16SubSatU),
+            0x74 => Some(Self::F64x2Ceil),
+            0x75 => Some(Self::F64x2Floor),
+            0x76 => Some(Self::I8x16MinS),
+            0x77 => Some(Self::I8x16MinU),
+            0x78 => Some(Self::I8x16MaxS),
+            0x79 => Some(Self::I8x16MaxU),
+            0x7A => Some(Self::F64x2Trunc),
+            0x7B => Some(Self::I8x16AvgrU),
+            0x7C => Some(Self::I16x8ExtaddPairwiseI8x16S),
+            0x7D => Some(Self::I16x8ExtaddPairwiseI8x16U),
+            0x7E => Some(Self::I32x4ExtaddPairwiseI16x8S),
+            0x7F => Some(Self::I32x4ExtaddPairwiseI16x8U),
+            0x80 => Some(Self::I16x8Abs),
+            0x81 => Some(Self::I16x8Neg),
+            0x82 => Some(Self::I16x8Q15mulrSatS),
+            0x83 => Some(Self::I16x8AllTrue),
+            0x84 => Some(Self::I16x8Bitmask),
+            0x85 => Some(Self::I16x8NarrowI32x4S),
+            0x86 => Some(Self::I16x8NarrowI32x4U),
+            0x87 => Some(Self::I16x8ExtendLowI8x16S),
+            0x88 => Some(Self::I16x8ExtendHighI8x16S),
+            0x89 => Some(Self::I16x8ExtendLowI8x16U),
+            0x8A => Some(Self::I16x8ExtendHighI8x16U),
+            0x8B => Some(Self::I16x8Shl),
+            0x8C => Some(Self::I16x8ShrS),
+            0x8D => Some(Self::I16x8ShrU),
+            0x8E => Some(Self::I16x8Add),
+            0x8F => Some(Self::I16x8AddSatS),
+            0x90 => Some(Self::I16x8AddSatU),
+            0x91 => Some(Self::I16x8Sub),
+            0x92 => Some(Self::I16x8SubSatS),
+            0x93 => Some(Self::I16x8SubSatU),
+            0x94 => Some(Self::F64x2Nearest),
+            0x95 => Some(Self::I16x8Mul),
+            0x96 => Some(Self::I16x8MinS),
+            0x97 => Some(Self::I16x8MinU),
+            0x98 => Some(Self::I16x8MaxS),
+            0x99 => Some(Self::I16x8MaxU),
+            0x9B => Some(Self::I16x8AvgrU),
+            0x9C => Some(Self::I16x8ExtmulLowI8x16S),
+            0x9D => Some(Self::I16x8ExtmulHighI8x16S),
+            0x9E => Some(Self::I16x8ExtmulLowI8x16U),
+            0x9F => Some(Self::I16x8ExtmulHighI8x16U),
+            0xA0 => Some(Self::I32x4Abs),
+            0xA1 => Some(Self::I32x4Neg),
+            0xA3 => Some(Self::I32x4AllTrue),
+            0xA4 => Some(Self::I32x4Bitmask),
+            0xA7 => Some(Self::I32x4ExtendLowI16x8S),
+            0xA8 => Some(Self::I32x4ExtendHighI16x8S),
+            0xA9 => Some(Self::I32x4ExtendLowI16x8U),
+            0xAA => Some(Self::I32x4ExtendHighI16x8U),
+            0xAB => Some(Self::I32x4Shl),
+            0xAC => Some(Self::I32x4ShrS),
+            0xAD => Some(Self::I32x4ShrU),
+            0xAE => Some(Self::I32x4Add),
+            0xB1 => Some(Self::I32x4Sub),
+            0xB5 => Some(Self::I32x4Mul),
+            0xB6 => Some(Self::I32x4MinS),
+            0xB7 => Some(Self::I32x4MinU),
+            0xB8 => Some(Self::I32x4MaxS),
+            0xB9 => Some(Self::I32x4MaxU),
+            0xBA => Some(Self::I32x4DotI16x8S),
+            0xBC => Some(Self::I32x4ExtmulLowI16x8S),
+            0xBD => Some(Self::I32x4ExtmulHighI16x8S),
+            0xBE => Some(Self::I32x4ExtmulLowI16x8U),
+            0xBF => Some(Self::I32x4ExtmulHighI16x8U),
+            0xC0 => Some(Self::I64x2Abs),
+            0xC1 => Some(Self::I64x2Neg),
+            0xC3 => Some(Self::I64x2AllTrue),
+            0xC4 => Some(Self::I64x2Bitmask),
+            0xC7 => Some(Self::I64x2ExtendLowI32x4S),
+            0xC8 => Some(Self::I64x2ExtendHighI32x4S),
+            0xC9 => Some(Self::I64x2ExtendLowI32x4U),
+            0xCA => Some(Self::I64x2ExtendHighI32x4U),
+            0xCB => Some(Self::I64x2Shl),
+            0xCC => Some(Self::I64x2ShrS),
+            0xCD => Some(Self::I64x2ShrU),
+            0xCE => Some(Self::I64x2Add),
+            0xD1 => Some(Self::I64x2Sub),
+            0xD5 => Some(Self::I64x2Mul),
+            0xD6 => Some(Self::I64x2Eq),
+            0xD7 => Some(Self::I64x2Ne),
+            0xD8 => Some(Self::I64x2LtS),
+            0xD9 => Some(Self::I64x2GtS),
+            0xDA => Some(Self::I64x2LeS),
+            0xDB => Some(Self::I64x2GeS),
+            0xDC => Some(Self::I64x2ExtmulLowI32x4S),
+            0xDD => Some(Self::I64x2ExtmulHighI32x4S),
+            0xDE => Some(Self::I64x2ExtmulLowI32x4U),
+            0xDF => Some(Self::I64x2ExtmulHighI32x4U),
+            0xE0 => Some(Self::F32x4Abs),
+            0xE1 => Some(Self::F32x4Neg),
+            0xE3 => Some(Self::F32x4Sqrt),
+            0xE4 => Some(Self::F32x4Add),
+            0xE5 => Some(Self::F32x4Sub),
+            0xE6 => Some(Self::F32x4Mul),
+            0xE7 => Some(Self::F32x4Div),
+            0xE8 => Some(Self::F32x4Min),
+            0xE9 => Some(Self::F32x4Max),
+            0xEA => Some(Self::F32x4Pmin),
+            0xEB => Some(Self::F32x4Pmax),
+            0xEC => Some(Self::F64x2Abs),
+            0xED => Some(Self::F64x2Neg),
+            0xEF => Some(Self::F64x2Sqrt),
+            0xF0 => Some(Self::F64x2Add),
+            0xF1 => Some(Self::F64x2Sub),
+            0xF2 => Some(Self::F64x2Mul),
+            0xF3 => Some(Self::F64x2Div),
+            0xF4 => Some(Self::F64x2Min),
+            0xF5 => Some(Self::F64x2Max),
+            0xF6 => Some(Self::F64x2Pmin),
+            0xF7 => Some(Self::F64x2Pmax),
+            0xF8 => Some(Self::I32x4TruncSatF32x4S),
+            0xF9 => Some(Self::I32x4TruncSatF32x4U),
+            0xFA => Some(Self::F32x4ConvertI32x4S),
+            0xFB => Some(Self::F32x4ConvertI32x4U),
+            0xFC => Some(Self::I32x4TruncSatF64x2SZero),
+            0xFD => Some(Self::I32x4TruncSatF64x2UZero),
+            0xFE => Some(Self::F64x2ConvertLowI32x4S),
+            0xFF => Some(Self::F64x2ConvertLowI32x4U),
+            _ => None,
+        }
+    }
+
+    pub const fn to_str(&self) -> &str {
+        match *self {
+            Self::V128Load => "v128.load",
+            Self::V128Load8x8S => "v128.load8x8_s",
+            Self::V128Load8x8U => "v128.load8x8_u",
+            Self::V128Load16x4S => "v128.load16x4_s",
+            Self::V128Load16x4U => "v128.load16x4_u",
+            Self::V128Load32x2S => "v128.load32x2_s",
+            Self::V128Load32x2U => "v128.load32x2_u",
+            Self::V128Load8Splat => "v128.load8_splat",
+            Self::V128Load16Splat => "v128.load16_splat",
+            Self::V128Load32Splat => "v128.load32_splat",
+            Self::V128Load64Splat => "v128.load64_splat",
+            Self::V128Store => "v128.store",
+            Self::V128Const => "v128.const",
+            Self::I8x16Shuffle => "i8x16.shuffle",
+            Self::I8x16Swizzle => "i8x16.swizzle",
+            Self::I8x16Splat => "i8x16.splat",
+            Self::I16x8Splat => "i16x8.splat",
+            Self::I32x4Splat => "i32x4.splat",
+            Self::I64x2Splat => "i64x2.splat",
+            Self::F32x4Splat => "f32x4.splat",
+            Self::F64x2Splat => "f64x2.splat",
+            Self::I8x16ExtractLaneS => "i8x16.extract_lane_s",
+            Self::I8x16ExtractLaneU => "i8x16.extract_lane_u",
+            Self::I8x16ReplaceLane => "i8x16.replace_lane",
+            Self::I16x8ExtractLaneS => "i16x8.extract_lane_s",
+            Self::I16x8ExtractLaneU => "i16x8.extract_lane_u",
+            Self::I16x8ReplaceLane => "i16x8.replace_lane",
+            Self::I32x4ExtractLane => "i32x4.extract_lane",
+            Self::I32x4ReplaceLane => "i32x4.replace_lane",
+            Self::I64x2ExtractLane => "i64x2.extract_lane",
+            Self::I64x2ReplaceLane => "i64x2.replace_lane",
+            Self::F32x4ExtractLane => "f32x4.extract_lane",
+            Self::F32x4ReplaceLane => "f32x4.replace_lane",
+            Self::F64x2ExtractLane => "f64x2.extract_lane",
+            Self::F64x2ReplaceLane => "f64x2.replace_lane",
+            Self::I8x16Eq => "i8x16.eq",
+            Self::I8x16Ne => "i8x16.ne",
+            Self::I8x16LtS => "i8x16.lt_s",
+            Self::I8x16LtU => "i8x16.lt_u",
+            Self::I8x16GtS => "i8x16.gt_s",
+            Self::I8x16GtU => "i8x16.gt_u",
+            Self::I8x16LeS => "i8x16.le_s",
+            Self::I8x16LeU => "i8x16.le_u",
+            Self::I8x16GeS => "i8x16.ge_s",
+            Self::I8x16GeU => "i8x16.ge_u",
+            Self::I16x8Eq => "i16x8.eq",
+            Self::I16x8Ne => "i16x8.ne",
+            Self::I16x8LtS => "i16x8.lt_s",
+            Self::I16x8LtU => "i16x8.lt_u",
+            Self::I16x8GtS => "i16x8.gt_s",
+            Self::I16x8GtU => "i16x8.gt_u",
+            Self::I16x8LeS => "i16x8.le_s",
+            Self::I16x8LeU => "i16x8.le_u",
+            Self::I16x8GeS => "i16x8.ge_s",
+            Self::I16x8GeU => "i16x8.ge_u",
+            Self::I32x4Eq => "i32x4.eq",
+            Self::I32x4Ne => "i32x4.ne",
+            Self::I32x4LtS => "i32x4.lt_s",
+            Self::I32x4LtU => "i32x4.lt_u",
+            Self::I32x4GtS => "i32x4.gt_s",
+            Self::I32x4GtU => "i32x4.gt_u",
+            Self::I32x4LeS => "i32x4.le_s",
+            Self::I32x4LeU => "i32x4.le_u",
+            Self::I32x4GeS => "i32x4.ge_s",
+            Self::I32x4GeU => "i32x4.ge_u",
+            Self::F32x4Eq => "f32x4.eq",
+            Self::F32x4Ne => "f32x4.ne",
+            Self::F32x4Lt => "f32x4.lt",
+            Self::F32x4Gt => "f32x4.gt",
+            Self::F32x4Le => "f32x4.le",
+            Self::F32x4Ge => "f32x4.ge",
+            Self::F64x2Eq => "f64x2.eq",
+            Self::F64x2Ne => "f64x2.ne",
+            Self::F64x2Lt => "f64x2.lt",
+            Self::F64x2Gt => "f64x2.gt",
+            Self::F64x2Le => "f64x2.le",
+            Self::F64x2Ge => "f64x2.ge",
+            Self::V128Not => "v128.not",
+            Self::V128And => "v128.and",
+            Self::V128Andnot => "v128.andnot",
+            Self::V128Or => "v128.or",
+            Self::V128Xor => "v128.xor",
+            Self::V128Bitselect => "v128.bitselect",
+            Self::V128AnyTrue => "v128.any_true",
+            Self::V128Load8Lane => "v128.load8_lane",
+            Self::V128Load16Lane => "v128.load16_lane",
+            Self::V128Load32Lane => "v128.load32_lane",
+            Self::V128Load64Lane => "v128.load64_lane",
+            Self::V128Store8Lane => "v128.store8_lane",
+            Self::V128Store16Lane => "v128.store16_lane",
+            Self::V128Store32Lane => "v128.store32_lane",
+            Self::V128Store64Lane => "v128.store64_lane",
+            Self::V128Load32Zero => "v128.load32_zero",
+            Self::V128Load64Zero => "v128.load64_zero",
+            Self::F32x4DemoteF64x2Zero => "f32x4.demote_f64x2_zero",
+            Self::F64x2PromoteLowF32x4 => "f64x2.promote_low_f32x4",
+            Self::I8x16Abs => "i8x16.abs",
+            Self::I8x16Neg => "i8x16.neg",
+            Self::I8x16Popcnt => "i8x16.popcnt",
+            Self::I8x16AllTrue => "i8x16.all_true",
+            Self::I8x16Bitmask => "i8x16.bitmask",
+            Self::I8x16NarrowI16x8S => "i8x16.narrow_i16x8_s",
+            Self::I8x16NarrowI16x8U => "i8x16.narrow_i16x8_u",
+            Self::F32x4Ceil => "f32x4.ceil",
+            Self::F32x4Floor => "f32x4.floor",
+            Self::F32x4Trunc => "f32x4.trunc",
+            Self::F32x4Nearest => "f32x4.nearest",
+            Self::I8x16Shl => "i8x16.shl",
+            Self::I8x16ShrS => "i8x16.shr_s",
+            Self::I8x16ShrU => "i8x16.shr_u",
+            Self::I8x16Add => "i8x16.add",
+            Self::I8x16AddSatS => "i8x16.add_sat_s",
+            Self::I8x16AddSatU => "i8x16.add_sat_u",
+            Self::I8x16Sub => "i8x16.sub",
+            Self::I8x16SubSatS => "i8x16.sub_sat_s",
+            Self::I8x16SubSatU => "i8x16.sub_sat_u",
+            Self::F64x2Ceil => "f64x2.ceil",
+            Self::F64x2Floor => "f64x2.floor",
+            Self::I8x16MinS => "i8x16.min_s",
+            Self::I8x16MinU => "i8x16.min_u",
+            Self::I8x16MaxS => "i8x16.max_s",
+            Self::I8x16MaxU => "i8x16.max_u",
+            Self::F64x2Trunc => "f64x2.trunc",
+            Self::I8x16AvgrU => "i8x16.avgr_u",
+            Self::I16x8ExtaddPairwiseI8x16S => "i16x8.extadd_pairwise_i8x16_s",
+            Self::I16x8ExtaddPairwiseI8x16U => "i16x8.extadd_pairwise_i8x16_u",
+            Self::I32x4ExtaddPairwiseI16x8S => "i32x4.extadd_pairwise_i16x8_s",
+            Self::I32x4ExtaddPairwiseI16x8U => "i32x4.extadd_pairwise_i16x8_u",
+            Self::I16x8Abs => "i16x8.abs",
+            Self::I16x8Neg => "i16x8.neg",
+            Self::I16x8Q15mulrSatS => "i16x8.q15mulr_sat_s",
+            Self::I16x8AllTrue => "i16x8.all_true",
+            Self::I16x8Bitmask => "i16x8.bitmask",
+            Self::I16x8NarrowI32x4S => "i16x8.narrow_i32x4_s",
+            Self::I16x8NarrowI32x4U => "i16x8.narrow_i32x4_u",
+            Self::I16x8ExtendLowI8x16S => "i16x8.extend_low_i8x16_s",
+            Self::I16x8ExtendHighI8x16S => "i16x8.extend_high_i8x16_s",
+            Self::I16x8ExtendLowI8x16U => "i16x8.extend_low_i8x16_u",
+            Self::I16x8ExtendHighI8x16U => "i16x8.extend_high_i8x16_u",
+            Self::I16x8Shl => "i16x8.shl",
+            Self::I16x8ShrS => "i16x8.shr_s",
+            Self::I16x8ShrU => "i16x8.shr_u",
+            Self::I16x8Add => "i16x8.add",
+            Self::I16x8AddSatS => "i16x8.add_sat_s",
+            Self::I16x8AddSatU => "i16x8.add_sat_u",
+            Self::I16x8Sub => "i16x8.sub",
+            Self::I16x8SubSatS => "i16x8.sub_sat_s",
+            Self::I16x8SubSatU => "i16x8.sub_sat_u",
+            Self::F64x2Nearest => "f64x2.nearest",
+            Self::I16x8Mul => "i16x8.mul",
+            Self::I16x8MinS => "i16x8.min_s",
+            Self::I16x8MinU => "i16x8.min_u",
+            Self::I16x8MaxS => "i16x8.max_s",
+            Self::I16x8MaxU => "i16x8.max_u",
+            Self::I16x8AvgrU => "i16x8.avgr_u",
+            Self::I16x8ExtmulLowI8x16S => "i16x8.extmul_low_i8x16_s",
+            Self::I16x8ExtmulHighI8x16S => "i16x8.extmul_high_i8x16_s",
+            Self::I16x8ExtmulLowI8x16U => "i16x8.extmul_low_i8x16_u",
+            Self::I16x8ExtmulHighI8x16U => "i16x8.extmul_high_i8x16_u",
+            Self::I32x4Abs => "i32x4.abs",
+            Self::I32x4Neg => "i32x4.neg",
+            Self::I32x4AllTrue => "i32x4.all_true",
+            Self::I32x4Bitmask => "i32x4.bitmask",
+            Self::I32x4ExtendLowI16x8S => "i32x4.extend_low_i16x8_s",
+            Self::I32x4ExtendHighI16x8S => "i32x4.extend_high_i16x8_s",
+            Self::I32x4ExtendLowI16x8U => "i32x4.extend_low_i16x8_u",
+            Self::I32x4ExtendHighI16x8U => "i32x4.extend_high_i16x8_u",
+            Self::I32x4Shl => "i32x4.shl",
+            Self::I32x4ShrS => "i32x4.shr_s",
+            Self::I32x4ShrU => "i32x4.shr_u",
+            Self::I32x4Add => "i32x4.add",
+            Self::I32x4Sub => "i32x4.sub",
+            Self::I32x4Mul => "i32x4.mul",
+            Self::I32x4MinS => "i32x4.min_s",
+            Self::I32x4MinU => "i32x4.min_u",
+            Self::I32x4MaxS => "i32x4.max_s",
+            Self::I32x4MaxU => "i32x4.max_u",
+            Self::I32x4DotI16x8S => "i32x4.dot_i16x8_s",
+            Self::I32x4ExtmulLowI16x8S => "i32x4.extmul_low_i16x8_s",
+            Self::I32x4ExtmulHighI16x8S => "i32x4.extmul_high_i16x8_s",
+            Self::I32x4ExtmulLowI16x8U => "i32x4.extmul_low_i16x8_u",
+            Self::I32x4ExtmulHighI16x8U => "i32x4.extmul_high_i16x8_u",
+            Self::I64x2Abs => "i64x2.abs",
+            Self::I64x2Neg => "i64x2.neg",
+            Self::I64x2AllTrue => "i64x2.all_true",
+            Self::I64x2Bitmask => "i64x2.bitmask",
+            Self::I64x2ExtendLowI32x4S => "i64x2.extend_low_i32x4_s",
+            Self::I64x2ExtendHighI32x4S => "i64x2.extend_high_i32x4_s",
+            Self::I64x2ExtendLowI32x4U => "i64x2.extend_low_i32x4_u",
+            Self::I64x2ExtendHighI32x4U => "i64x2.extend_high_i32x4_u",
+            Self::I64x2Shl => "i64x2.shl",
+            Self::I64x2ShrS => "i64x2.shr_s",
+            Self::I64x2ShrU => "i64x2.shr_u",
+            Self::I64x2Add => "i64x2.add",
+            Self::I64x2Sub => "i64x2.sub",
+            Self::I64x2Mul => "i64x2.mul",
+            Self::I64x2Eq => "i64x2.eq",
+            Self::I64x2Ne => "i64x2.ne",
+            Self::I64x2LtS => "i64x2.lt_s",
+            Self::I64x2GtS => "i64x2.gt_s",
+            Self::I64x2LeS => "i64x2.le_s",
+            Self::I64x2GeS => "i64x2.ge_s",
+            Self::I64x2ExtmulLowI32x4S => "i64x2.extmul_low_i32x4_s",
+            Self::I64x2ExtmulHighI32x4S => "i64x2.extmul_high_i32x4_s",
+            Self::I64x2ExtmulLowI32x4U => "i64x2.extmul_low_i32x4_u",
+            Self::I64x2ExtmulHighI32x4U => "i64x2.extmul_high_i32x4_u",
+            Self::F32x4Abs => "f32x4.abs",
+            Self::F32x4Neg => "f32x4.neg",
+            Self::F32x4Sqrt => "f32x4.sqrt",
+            Self::F32x4Add => "f32x4.add",
+            Self::F32x4Sub => "f32x4.sub",
+            Self::F32x4Mul => "f32x4.mul",
+            Self::F32x4Div => "f32x4.div",
+            Self::F32x4Min => "f32x4.min",
+            Self::F32x4Max => "f32x4.max",
+            Self::F32x4Pmin => "f32x4.pmin",
+            Self::F32x4Pmax => "f32x4.pmax",
+            Self::F64x2Abs => "f64x2.abs",
+            Self::F64x2Neg => "f64x2.neg",
+            Self::F64x2Sqrt => "f64x2.sqrt",
+            Self::F64x2Add => "f64x2.add",
+            Self::F64x2Sub => "f64x2.sub",
+            Self::F64x2Mul => "f64x2.mul",
+            Self::F64x2Div => "f64x2.div",
+            Self::F64x2Min => "f64x2.min",
+            Self::F64x2Max => "f64x2.max",
+            Self::F64x2Pmin => "f64x2.pmin",
+            Self::F64x2Pmax => "f64x2.pmax",
+            Self::I32x4TruncSatF32x4S => "i32x4.trunc_sat_f32x4_s",
+            Self::I32x4TruncSatF32x4U => "i32x4.trunc_sat_f32x4_u",
+            Self::F32x4ConvertI32x4S => "f32x4.convert_i32x4_s",
+            Self::F32x4ConvertI32x4U => "f32x4.convert_i32x4_u",
+            Self::I32x4TruncSatF64x2SZero => "i32x4.trunc_sat_f64x2_s_zero",
+            Self::I32x4TruncSatF64x2UZero => "i32x4.trunc_sat_f64x2_u_zero",
+            Self::F64x2ConvertLowI32x4S => "f64x2.convert_low_i32x4_s",
+            Self::F64x2ConvertLowI32x4U => "f64x2.convert_low_i32x4_u",
+        }
+    }
+
+    pub const fn proposal_type(&self) -> WasmProposalType {
+        WasmProposalType::Simd
+    }
+}
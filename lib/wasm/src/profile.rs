@@ -0,0 +1,169 @@
+//! Per-function profiler of the interpreter
+
+use crate::*;
+use alloc::{boxed::Box, vec::Vec};
+use core::{cmp::Reverse, fmt, sync::atomic::*};
+
+/// Returns the monotonic time in nanoseconds, which is provided by the host.
+pub type WasmProfileClock = fn() -> u64;
+
+/// Counts the calls, the retired intermediate codes and the time spent in each function.
+///
+/// The counters are shared by all threads running the module.
+/// Functions compiled by the JIT are not counted, so a profiled module stays on the interpreter.
+pub struct WasmProfiler {
+    clock: Option<WasmProfileClock>,
+    counters: Box<[WasmProfileCounter]>,
+}
+
+#[derive(Default)]
+struct WasmProfileCounter {
+    calls: AtomicU64,
+    instructions: AtomicU64,
+    total_time: AtomicU64,
+    self_time: AtomicU64,
+}
+
+impl WasmProfiler {
+    /// Creates a profiler of the module with the specified number of functions.
+    ///
+    /// Without a clock, only the calls and the instructions are counted.
+    pub fn new(n_functions: usize, clock: Option<WasmProfileClock>) -> Self {
+        let mut counters = Vec::with_capacity(n_functions);
+        counters.resize_with(n_functions, WasmProfileCounter::default);
+        Self {
+            clock,
+            counters: counters.into_boxed_slice(),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn now(&self) -> u64 {
+        self.clock.map(|clock| clock()).unwrap_or(0)
+    }
+
+    /// Records a call of the function.
+    ///
+    /// `self_time` is the elapsed time without the time spent in the callees.
+    pub(crate) fn record(
+        &self,
+        func_index: usize,
+        instructions: u64,
+        elapsed: u64,
+        self_time: u64,
+    ) {
+        let counter = match self.counters.get(func_index) {
+            Some(v) => v,
+            None => return,
+        };
+        counter.calls.fetch_add(1, Ordering::Relaxed);
+        counter
+            .instructions
+            .fetch_add(instructions, Ordering::Relaxed);
+        counter.total_time.fetch_add(elapsed, Ordering::Relaxed);
+        counter.self_time.fetch_add(self_time, Ordering::Relaxed);
+    }
+
+    /// Returns whether the time is measured
+    #[inline]
+    pub fn has_clock(&self) -> bool {
+        self.clock.is_some()
+    }
+
+    /// Returns the entries of the functions that have been called, sorted by cost.
+    ///
+    /// The cost is the self time if the time is measured, or the number of the instructions otherwise.
+    pub fn entries(&self) -> Vec<WasmProfileEntry> {
+        let mut entries = self
+            .counters
+            .iter()
+            .enumerate()
+            .filter_map(|(function, counter)| {
+                let calls = counter.calls.load(Ordering::Relaxed);
+                (calls > 0).then(|| WasmProfileEntry {
+                    function,
+                    calls,
+                    instructions: counter.instructions.load(Ordering::Relaxed),
+                    total_time: counter.total_time.load(Ordering::Relaxed),
+                    self_time: counter.self_time.load(Ordering::Relaxed),
+                })
+            })
+            .collect::<Vec<_>>();
+        if self.has_clock() {
+            entries.sort_by_key(|v| Reverse(v.self_time));
+        } else {
+            entries.sort_by_key(|v| Reverse(v.instructions));
+        }
+        entries
+    }
+
+    /// Returns the report that can be printed with the names of the module
+    #[inline]
+    pub fn report<'a>(&self, module: &'a WasmModule) -> WasmProfileReport<'a> {
+        WasmProfileReport {
+            module,
+            has_clock: self.has_clock(),
+            entries: self.entries(),
+        }
+    }
+}
+
+/// Profile of a function
+#[derive(Debug, Clone, Copy)]
+pub struct WasmProfileEntry {
+    pub function: usize,
+    pub calls: u64,
+    /// Number of the intermediate codes retired, which is zero for the imported functions.
+    pub instructions: u64,
+    /// Nanoseconds including the callees, recursive calls are counted more than once.
+    pub total_time: u64,
+    /// Nanoseconds excluding the callees
+    pub self_time: u64,
+}
+
+/// Printable report of the profiler
+pub struct WasmProfileReport<'a> {
+    module: &'a WasmModule,
+    has_clock: bool,
+    entries: Vec<WasmProfileEntry>,
+}
+
+impl WasmProfileReport<'_> {
+    #[inline]
+    pub fn entries(&self) -> &[WasmProfileEntry] {
+        self.entries.as_slice()
+    }
+}
+
+impl fmt::Display for WasmProfileReport<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.has_clock {
+            writeln!(
+                f,
+                "{:>10} {:>12} {:>10} {:>10}  function",
+                "calls", "instructions", "self(us)", "total(us)"
+            )?;
+        } else {
+            writeln!(f, "{:>10} {:>12}  function", "calls", "instructions")?;
+        }
+        for entry in &self.entries {
+            if self.has_clock {
+                write!(
+                    f,
+                    "{:>10} {:>12} {:>10} {:>10}  ",
+                    entry.calls,
+                    entry.instructions,
+                    entry.self_time / 1000,
+                    entry.total_time / 1000
+                )?;
+            } else {
+                write!(f, "{:>10} {:>12}  ", entry.calls, entry.instructions)?;
+            }
+            match self.module.func_name(entry.function) {
+                Some(name) => writeln!(f, "{} (function {})", name, entry.function)?,
+                None => writeln!(f, "function {}", entry.function)?,
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,8 +1,11 @@
 // test
 
 use crate::{
+    opcode::WasmOpcode,
     wasmintr::{WasmInterpreter, WasmInvocation},
-    Leb128Stream, WasmCodeBlock, WasmLoader, WasmModule, WasmRuntimeErrorType, WasmValType,
+    wat::WasmDisassembler,
+    Leb128Stream, WasmCodeBlock, WasmDecodeErrorType, WasmGlobal, WasmLimit, WasmLoader,
+    WasmMemory, WasmModule, WasmRuntimeErrorType, WasmTable, WasmValType, WasmValue,
 };
 
 #[test]
@@ -678,3 +681,1628 @@ fn global() {
 
     assert_eq!(module.global(0).unwrap().value().get_i32().unwrap(), 1368);
 }
+
+#[test]
+fn float_arithmetic() {
+    // (f32.sub (f32.mul (local.get 0) (local.get 1)) (f32.const 0.5))
+    let slice = [
+        0, 0x20, 0, 0x20, 1, 0x94, 0x43, 0x00, 0x00, 0x00, 0x3F, 0x93, 0x0B,
+    ];
+    let param_types = [WasmValType::F32, WasmValType::F32];
+    let result_types = [WasmValType::F32];
+    let mut stream = Leb128Stream::from_slice(&slice);
+    let module = WasmModule::new();
+    let info =
+        WasmCodeBlock::generate(0, &mut stream, &param_types, &result_types, &module).unwrap();
+    let mut interp = WasmInterpreter::new(&module);
+
+    let params = [1.5f32.into(), 3.0f32.into()];
+    let result = interp
+        .invoke(0, &info, &params, &result_types)
+        .unwrap()
+        .unwrap()
+        .get_f32()
+        .unwrap();
+    assert_eq!(result, 4.0);
+
+    // (f64.div (local.get 0) (local.get 1))
+    let slice = [0, 0x20, 0, 0x20, 1, 0xA3, 0x0B];
+    let param_types = [WasmValType::F64, WasmValType::F64];
+    let result_types = [WasmValType::F64];
+    let mut stream = Leb128Stream::from_slice(&slice);
+    let info =
+        WasmCodeBlock::generate(0, &mut stream, &param_types, &result_types, &module).unwrap();
+
+    let params = [1.0f64.into(), 8.0f64.into()];
+    let result = interp
+        .invoke(0, &info, &params, &result_types)
+        .unwrap()
+        .unwrap()
+        .get_f64()
+        .unwrap();
+    assert_eq!(result, 0.125);
+
+    // NaN results are canonicalized
+    let params = [0.0f64.into(), 0.0f64.into()];
+    let result = interp
+        .invoke(0, &info, &params, &result_types)
+        .unwrap()
+        .unwrap()
+        .get_f64()
+        .unwrap();
+    assert_eq!(
+        result.to_bits() & 0x7FFF_FFFF_FFFF_FFFF,
+        0x7FF8_0000_0000_0000
+    );
+}
+
+#[test]
+fn float_rounding() {
+    // (f64.nearest (local.get 0))
+    let slice = [0, 0x20, 0, 0x9E, 0x0B];
+    let param_types = [WasmValType::F64];
+    let result_types = [WasmValType::F64];
+    let mut stream = Leb128Stream::from_slice(&slice);
+    let module = WasmModule::new();
+    let info =
+        WasmCodeBlock::generate(0, &mut stream, &param_types, &result_types, &module).unwrap();
+    let mut interp = WasmInterpreter::new(&module);
+
+    for (param, expected) in [(2.5, 2.0), (3.5, 4.0), (-0.5, -0.0), (-1.7, -2.0)].iter() {
+        let params = [(*param as f64).into()];
+        let result = interp
+            .invoke(0, &info, &params, &result_types)
+            .unwrap()
+            .unwrap()
+            .get_f64()
+            .unwrap();
+        assert_eq!(result.to_bits(), (*expected as f64).to_bits());
+    }
+
+    // (f32.min (local.get 0) (local.get 1))
+    let slice = [0, 0x20, 0, 0x20, 1, 0x96, 0x0B];
+    let param_types = [WasmValType::F32, WasmValType::F32];
+    let result_types = [WasmValType::F32];
+    let mut stream = Leb128Stream::from_slice(&slice);
+    let info =
+        WasmCodeBlock::generate(0, &mut stream, &param_types, &result_types, &module).unwrap();
+
+    let params = [0.0f32.into(), (-0.0f32).into()];
+    let result = interp
+        .invoke(0, &info, &params, &result_types)
+        .unwrap()
+        .unwrap()
+        .get_f32()
+        .unwrap();
+    assert_eq!(result.to_bits(), (-0.0f32).to_bits());
+
+    let params = [f32::NAN.into(), 1.0f32.into()];
+    let result = interp
+        .invoke(0, &info, &params, &result_types)
+        .unwrap()
+        .unwrap()
+        .get_f32()
+        .unwrap();
+    assert!(result.is_nan());
+}
+
+#[test]
+fn float_conversion() {
+    // (i32.trunc_f32_s (local.get 0))
+    let slice = [0, 0x20, 0, 0xA8, 0x0B];
+    let param_types = [WasmValType::F32];
+    let result_types = [WasmValType::I32];
+    let mut stream = Leb128Stream::from_slice(&slice);
+    let module = WasmModule::new();
+    let info =
+        WasmCodeBlock::generate(0, &mut stream, &param_types, &result_types, &module).unwrap();
+    let mut interp = WasmInterpreter::new(&module);
+
+    let params = [(-123.75f32).into()];
+    let result = interp
+        .invoke(0, &info, &params, &result_types)
+        .unwrap()
+        .unwrap()
+        .get_i32()
+        .unwrap();
+    assert_eq!(result, -123);
+
+    let params = [2147483648.0f32.into()];
+    let result = interp.invoke(0, &info, &params, &result_types);
+    assert_eq!(
+        result.unwrap_err().kind(),
+        WasmRuntimeErrorType::IntegerOverflow
+    );
+
+    let params = [f32::NAN.into()];
+    let result = interp.invoke(0, &info, &params, &result_types);
+    assert_eq!(
+        result.unwrap_err().kind(),
+        WasmRuntimeErrorType::InvalidConversionToInteger
+    );
+
+    // (f64.convert_i64_u (local.get 0))
+    let slice = [0, 0x20, 0, 0xBA, 0x0B];
+    let param_types = [WasmValType::I64];
+    let result_types = [WasmValType::F64];
+    let mut stream = Leb128Stream::from_slice(&slice);
+    let info =
+        WasmCodeBlock::generate(0, &mut stream, &param_types, &result_types, &module).unwrap();
+
+    let params = [0xFFFF_FFFF_FFFF_FFFFu64.into()];
+    let result = interp
+        .invoke(0, &info, &params, &result_types)
+        .unwrap()
+        .unwrap()
+        .get_f64()
+        .unwrap();
+    assert_eq!(result, 18446744073709551616.0);
+
+    // (i32.reinterpret_f32 (f32.demote_f64 (local.get 0)))
+    let slice = [0, 0x20, 0, 0xB6, 0xBC, 0x0B];
+    let param_types = [WasmValType::F64];
+    let result_types = [WasmValType::I32];
+    let mut stream = Leb128Stream::from_slice(&slice);
+    let info =
+        WasmCodeBlock::generate(0, &mut stream, &param_types, &result_types, &module).unwrap();
+
+    let params = [1.0f64.into()];
+    let result = interp
+        .invoke(0, &info, &params, &result_types)
+        .unwrap()
+        .unwrap()
+        .get_u32()
+        .unwrap();
+    assert_eq!(result, 0x3F80_0000);
+}
+
+#[test]
+fn trunc_sat() {
+    // (i32.trunc_sat_f32_s (local.get 0))
+    let slice = [0, 0x20, 0, 0xFC, 0x00, 0x0B];
+    let param_types = [WasmValType::F32];
+    let result_types = [WasmValType::I32];
+    let mut stream = Leb128Stream::from_slice(&slice);
+    let module = WasmModule::new();
+    let info =
+        WasmCodeBlock::generate(0, &mut stream, &param_types, &result_types, &module).unwrap();
+    let mut interp = WasmInterpreter::new(&module);
+
+    for (param, expected) in [
+        (-123.75f32, -123),
+        (3.0e9, i32::MAX),
+        (-3.0e9, i32::MIN),
+        (f32::NAN, 0),
+    ]
+    .iter()
+    {
+        let params = [(*param).into()];
+        let result = interp
+            .invoke(0, &info, &params, &result_types)
+            .unwrap()
+            .unwrap()
+            .get_i32()
+            .unwrap();
+        assert_eq!(result, *expected);
+    }
+
+    // (i64.trunc_sat_f64_u (local.get 0))
+    let slice = [0, 0x20, 0, 0xFC, 0x07, 0x0B];
+    let param_types = [WasmValType::F64];
+    let result_types = [WasmValType::I64];
+    let mut stream = Leb128Stream::from_slice(&slice);
+    let info =
+        WasmCodeBlock::generate(0, &mut stream, &param_types, &result_types, &module).unwrap();
+
+    for (param, expected) in [(-1.0f64, 0), (1.0e20, u64::MAX), (12345.9, 12345)].iter() {
+        let params = [(*param).into()];
+        let result = interp
+            .invoke(0, &info, &params, &result_types)
+            .unwrap()
+            .unwrap()
+            .get_u64()
+            .unwrap();
+        assert_eq!(result, *expected);
+    }
+}
+
+#[test]
+fn bulk_memory() {
+    // (memory 1)
+    // (data "hello")
+    // (func
+    //   (memory.init 0 (i32.const 16) (i32.const 0) (i32.const 5))
+    //   (data.drop 0)
+    //   (memory.copy (i32.const 32) (i32.const 16) (i32.const 5))
+    //   (memory.fill (i32.const 16) (i32.const 42) (i32.const 2))
+    // )
+    let slice = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x04, 0x01, 0x60, 0x00, 0x00, 0x03,
+        0x02, 0x01, 0x00, 0x05, 0x03, 0x01, 0x00, 0x01, 0x0c, 0x01, 0x01, 0x0a, 0x24, 0x01, 0x22,
+        0x00, 0x41, 0x10, 0x41, 0x00, 0x41, 0x05, 0xfc, 0x08, 0x00, 0x00, 0xfc, 0x09, 0x00, 0x41,
+        0x20, 0x41, 0x10, 0x41, 0x05, 0xfc, 0x0a, 0x00, 0x00, 0x41, 0x10, 0x41, 0x2a, 0x41, 0x02,
+        0xfc, 0x0b, 0x00, 0x0b, 0x0b, 0x08, 0x01, 0x01, 0x05, 0x68, 0x65, 0x6c, 0x6c, 0x6f,
+    ];
+    let module = WasmLoader::instantiate(&slice, |_, _, _| unreachable!()).unwrap();
+    let runnable = module.func_by_index(0).unwrap();
+
+    let result = runnable.invoke(&[]).unwrap();
+    assert!(result.is_none());
+
+    let memory = module.memory(0).unwrap();
+    assert_eq!(memory.read_bytes(16, 5).unwrap(), b"**llo");
+    assert_eq!(memory.read_bytes(32, 5).unwrap(), b"hello");
+
+    // The segment has already been dropped
+    let result = runnable.invoke(&[]);
+    assert_eq!(
+        result.unwrap_err().kind(),
+        WasmRuntimeErrorType::OutOfBounds
+    );
+}
+
+#[test]
+fn if_else() {
+    // (if (result i32) (local.get 0) (then (i32.const 10)) (else (i32.const 20)))
+    let slice = [0, 0x20, 0, 0x04, 0x7F, 0x41, 10, 0x05, 0x41, 20, 0x0B, 0x0B];
+    let param_types = [WasmValType::I32];
+    let result_types = [WasmValType::I32];
+    let mut stream = Leb128Stream::from_slice(&slice);
+    let module = WasmModule::new();
+    let info =
+        WasmCodeBlock::generate(0, &mut stream, &param_types, &result_types, &module).unwrap();
+    let mut interp = WasmInterpreter::new(&module);
+
+    let params = [1.into()];
+    let result = interp
+        .invoke(0, &info, &params, &result_types)
+        .unwrap()
+        .unwrap()
+        .get_i32()
+        .unwrap();
+    assert_eq!(result, 10);
+
+    let params = [0.into()];
+    let result = interp
+        .invoke(0, &info, &params, &result_types)
+        .unwrap()
+        .unwrap()
+        .get_i32()
+        .unwrap();
+    assert_eq!(result, 20);
+}
+
+#[test]
+fn sign_extend() {
+    // (i32.extend8_s (local.get 0))
+    let slice = [0, 0x20, 0, 0xC0, 0x0B];
+    let param_types = [WasmValType::I32];
+    let result_types = [WasmValType::I32];
+    let mut stream = Leb128Stream::from_slice(&slice);
+    let module = WasmModule::new();
+    let info =
+        WasmCodeBlock::generate(0, &mut stream, &param_types, &result_types, &module).unwrap();
+    let mut interp = WasmInterpreter::new(&module);
+
+    let params = [0x1280.into()];
+    let result = interp
+        .invoke(0, &info, &params, &result_types)
+        .unwrap()
+        .unwrap()
+        .get_i32()
+        .unwrap();
+    assert_eq!(result, -128);
+
+    // (i64.extend32_s (local.get 0))
+    let slice = [0, 0x20, 0, 0xC4, 0x0B];
+    let param_types = [WasmValType::I64];
+    let result_types = [WasmValType::I64];
+    let mut stream = Leb128Stream::from_slice(&slice);
+    let info =
+        WasmCodeBlock::generate(0, &mut stream, &param_types, &result_types, &module).unwrap();
+
+    let params = [0x1_8000_0000u64.into()];
+    let result = interp
+        .invoke(0, &info, &params, &result_types)
+        .unwrap()
+        .unwrap()
+        .get_i64()
+        .unwrap();
+    assert_eq!(result, -0x8000_0000);
+}
+
+#[test]
+fn multi_value() {
+    // (type $t0 (func (param i32 i32) (result i32 i32)))
+    // (type $t1 (func (param i32) (result i32 i32)))
+    // (type $t2 (func (param i32) (result i32)))
+    // (func $swap (type $t0) (local.get 1) (local.get 0))
+    // (func (type $t1) (local.get 0) (i32.const 1) (block (type $t0) (call $swap)))
+    // (func (type $t2) (block (result i32) (i32.const 99) (local.get 0) (br 0)))
+    // (func (type $t2)
+    //   (local.get 0)
+    //   (loop (type $t2)
+    //     (i32.const 1) (i32.sub) (local.tee 0) (local.get 0) (br_if 0)))
+    // (func (type $t2)
+    //   (block (result i32)
+    //     (i32.const 7) (local.get 0) (local.get 0) (br_if 0) (drop)))
+    let slice = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x13, 0x03, 0x60, 0x02, 0x7f, 0x7f,
+        0x02, 0x7f, 0x7f, 0x60, 0x01, 0x7f, 0x02, 0x7f, 0x7f, 0x60, 0x01, 0x7f, 0x01, 0x7f, 0x03,
+        0x06, 0x05, 0x00, 0x01, 0x02, 0x02, 0x02, 0x0a, 0x41, 0x05, 0x06, 0x00, 0x20, 0x01, 0x20,
+        0x00, 0x0b, 0x0b, 0x00, 0x20, 0x00, 0x41, 0x01, 0x02, 0x00, 0x10, 0x00, 0x0b, 0x0b, 0x0c,
+        0x00, 0x02, 0x7f, 0x41, 0xe3, 0x00, 0x20, 0x00, 0x0c, 0x00, 0x0b, 0x0b, 0x10, 0x00, 0x20,
+        0x00, 0x03, 0x02, 0x41, 0x01, 0x6b, 0x22, 0x00, 0x20, 0x00, 0x0d, 0x00, 0x0b, 0x0b, 0x0e,
+        0x00, 0x02, 0x7f, 0x41, 0x07, 0x20, 0x00, 0x20, 0x00, 0x0d, 0x00, 0x1a, 0x0b, 0x0b,
+    ];
+    let module = WasmLoader::instantiate(&slice, |_, _, _| unreachable!()).unwrap();
+
+    let result = module
+        .func_by_index(0)
+        .unwrap()
+        .invoke_multi(&[123.into(), 456.into()])
+        .unwrap();
+    assert_eq!(result.len(), 2);
+    assert_eq!(result[0].get_i32().unwrap(), 456);
+    assert_eq!(result[1].get_i32().unwrap(), 123);
+
+    let result = module
+        .func_by_index(1)
+        .unwrap()
+        .invoke_multi(&[789.into()])
+        .unwrap();
+    assert_eq!(result.len(), 2);
+    assert_eq!(result[0].get_i32().unwrap(), 1);
+    assert_eq!(result[1].get_i32().unwrap(), 789);
+
+    let result = module
+        .func_by_index(2)
+        .unwrap()
+        .invoke(&[42.into()])
+        .unwrap()
+        .unwrap()
+        .get_i32()
+        .unwrap();
+    assert_eq!(result, 42);
+
+    let result = module
+        .func_by_index(3)
+        .unwrap()
+        .invoke(&[10.into()])
+        .unwrap()
+        .unwrap()
+        .get_i32()
+        .unwrap();
+    assert_eq!(result, 0);
+
+    let runnable = module.func_by_index(4).unwrap();
+    let result = runnable
+        .invoke(&[5.into()])
+        .unwrap()
+        .unwrap()
+        .get_i32()
+        .unwrap();
+    assert_eq!(result, 5);
+    let result = runnable
+        .invoke(&[0.into()])
+        .unwrap()
+        .unwrap()
+        .get_i32()
+        .unwrap();
+    assert_eq!(result, 7);
+}
+
+#[test]
+fn validation() {
+    // (func (param i32) (result i32) (local.get 0) (i64.const 1) (i32.add))
+    let slice = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x06, 0x01, 0x60, 0x01, 0x7f, 0x01,
+        0x7f, 0x03, 0x02, 0x01, 0x00, 0x0a, 0x09, 0x01, 0x07, 0x00, 0x20, 0x00, 0x42, 0x01, 0x6a,
+        0x0b,
+    ];
+    let err = WasmLoader::instantiate(&slice, |_, _, _| unreachable!())
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), WasmDecodeErrorType::TypeMismatch);
+    assert_eq!(err.function(), Some(0));
+    assert_eq!(err.position(), 5);
+    assert_eq!(err.opcode(), Some(WasmOpcode::I32Add));
+
+    // (memory 1) (func (param i32) (result i32) (i32.load align=8 (local.get 0)))
+    let slice = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x06, 0x01, 0x60, 0x01, 0x7f, 0x01,
+        0x7f, 0x03, 0x02, 0x01, 0x00, 0x05, 0x03, 0x01, 0x00, 0x01, 0x0a, 0x09, 0x01, 0x07, 0x00,
+        0x20, 0x00, 0x28, 0x03, 0x00, 0x0b,
+    ];
+    let err = WasmLoader::instantiate(&slice, |_, _, _| unreachable!())
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), WasmDecodeErrorType::InvalidAlignment);
+    assert_eq!(err.position(), 3);
+
+    // (func (param i32) (result i32))
+    let slice = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x06, 0x01, 0x60, 0x01, 0x7f, 0x01,
+        0x7f, 0x03, 0x02, 0x01, 0x00, 0x0a, 0x04, 0x01, 0x02, 0x00, 0x0b,
+    ];
+    let err = WasmLoader::instantiate(&slice, |_, _, _| unreachable!())
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), WasmDecodeErrorType::OutOfStack);
+    assert_eq!(err.opcode(), Some(WasmOpcode::End));
+
+    // The stack is polymorphic after unreachable
+    // (func (param i32) (result i32) (unreachable) (i32.add))
+    let slice = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x06, 0x01, 0x60, 0x01, 0x7f, 0x01,
+        0x7f, 0x03, 0x02, 0x01, 0x00, 0x0a, 0x06, 0x01, 0x04, 0x00, 0x00, 0x6a, 0x0b,
+    ];
+    let module = WasmLoader::instantiate(&slice, |_, _, _| unreachable!()).unwrap();
+    let err = module
+        .func_by_index(0)
+        .unwrap()
+        .invoke(&[1.into()])
+        .unwrap_err();
+    assert_eq!(err.kind(), WasmRuntimeErrorType::Unreachable);
+}
+
+#[test]
+fn duplicate_export_and_start() {
+    // (func) (export "a" (func 0)) (export "b" (func 0))
+    let slice = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x04, 0x01, 0x60, 0x00, 0x00, 0x03,
+        0x02, 0x01, 0x00, 0x07, 0x09, 0x02, 0x01, 0x61, 0x00, 0x00, 0x01, 0x62, 0x00, 0x00, 0x0a,
+        0x04, 0x01, 0x02, 0x00, 0x0b,
+    ];
+    WasmLoader::instantiate(&slice, |_, _, _| unreachable!()).unwrap();
+
+    // (func) (export "a" (func 0)) (export "a" (func 0))
+    let slice = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x04, 0x01, 0x60, 0x00, 0x00, 0x03,
+        0x02, 0x01, 0x00, 0x07, 0x09, 0x02, 0x01, 0x61, 0x00, 0x00, 0x01, 0x61, 0x00, 0x00, 0x0a,
+        0x04, 0x01, 0x02, 0x00, 0x0b,
+    ];
+    let err = WasmLoader::instantiate(&slice, |_, _, _| unreachable!())
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), WasmDecodeErrorType::DuplicateExport);
+
+    // (func) (start 0) followed by another start section
+    let slice = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x04, 0x01, 0x60, 0x00, 0x00, 0x03,
+        0x02, 0x01, 0x00, 0x08, 0x01, 0x00, 0x08, 0x01, 0x00, 0x0a, 0x04, 0x01, 0x02, 0x00, 0x0b,
+    ];
+    let err = WasmLoader::instantiate(&slice, |_, _, _| unreachable!())
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), WasmDecodeErrorType::DuplicateSection);
+    assert_eq!(err.position(), 21);
+}
+
+#[test]
+fn memory_grow_limit() {
+    // (module
+    //   (memory 1 3)
+    //   (func (param i32) (result i32) (memory.grow (local.get 0))))
+    let slice = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x06, 0x01, 0x60, 0x01, 0x7f, 0x01,
+        0x7f, 0x03, 0x02, 0x01, 0x00, 0x05, 0x04, 0x01, 0x01, 0x01, 0x03, 0x0a, 0x08, 0x01, 0x06,
+        0x00, 0x20, 0x00, 0x40, 0x00, 0x0b,
+    ];
+    let grow = |module: &WasmModule, delta: i32| {
+        module
+            .func_by_index(0)
+            .unwrap()
+            .invoke(&[delta.into()])
+            .unwrap()
+            .unwrap()
+            .get_i32()
+            .unwrap()
+    };
+
+    // Up to the maximum
+    let module = WasmLoader::instantiate(&slice, |_, _, _| unreachable!()).unwrap();
+    assert_eq!(grow(&module, 1), 1);
+    assert_eq!(grow(&module, 2), -1);
+    assert_eq!(grow(&module, 1), 2);
+    assert_eq!(grow(&module, 1), -1);
+    assert_eq!(module.memory(0).unwrap().size(), 3);
+
+    // Up to the quota of the host
+    let mut module = WasmLoader::instantiate(&slice, |_, _, _| unreachable!()).unwrap();
+    module.set_grow_handler(Some(|_, _, requested| requested <= 2));
+    assert_eq!(grow(&module, 0), 1);
+    assert_eq!(grow(&module, 1), 1);
+    assert_eq!(grow(&module, 1), -1);
+    assert_eq!(module.memory(0).unwrap().size(), 2);
+}
+
+#[test]
+fn import_memory_table_global() {
+    // (import "env" "mem" (memory 1))
+    // (import "env" "t" (table 1 funcref))
+    // (import "env" "g" (global (mut i32)))
+    // (elem (i32.const 0) 1)
+    // (func (result i32)
+    //   (global.set 0 (i32.add (global.get 0) (i32.load (i32.const 0))))
+    //   (global.get 0))
+    // (func (result i32)
+    //   (i32.store (i32.const 4) (i32.const 1234))
+    //   (i32.load offset=4 (i32.const 0)))
+    let slice = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f,
+        0x02, 0x1f, 0x03, 0x03, 0x65, 0x6e, 0x76, 0x03, 0x6d, 0x65, 0x6d, 0x02, 0x00, 0x01, 0x03,
+        0x65, 0x6e, 0x76, 0x01, 0x74, 0x01, 0x70, 0x00, 0x01, 0x03, 0x65, 0x6e, 0x76, 0x01, 0x67,
+        0x03, 0x7f, 0x01, 0x03, 0x03, 0x02, 0x00, 0x00, 0x09, 0x07, 0x01, 0x00, 0x41, 0x00, 0x0b,
+        0x01, 0x01, 0x0a, 0x20, 0x02, 0x0e, 0x00, 0x23, 0x00, 0x41, 0x00, 0x28, 0x02, 0x00, 0x6a,
+        0x24, 0x00, 0x23, 0x00, 0x0b, 0x0f, 0x00, 0x41, 0x04, 0x41, 0xd2, 0x09, 0x36, 0x02, 0x00,
+        0x41, 0x00, 0x28, 0x02, 0x04, 0x0b,
+    ];
+
+    let err = WasmLoader::new()
+        .load(&slice, |_, _, _| unreachable!())
+        .unwrap_err();
+    assert_eq!(err.kind(), WasmDecodeErrorType::NoModule);
+    assert!(WasmLoader::imports_module(&slice, "env"));
+    assert!(!WasmLoader::imports_module(
+        &slice,
+        "wasi_snapshot_preview1"
+    ));
+
+    let memory = WasmMemory::new(WasmLimit::new(1, 1));
+    let mut table = WasmTable::new(WasmLimit::new(1, 1));
+    let global = WasmGlobal::new(WasmValue::I32(5), true);
+    memory.write_u32(0, 10).unwrap();
+
+    let mut loader = WasmLoader::new();
+    loader.define_memory("env", "mem", memory.share());
+    loader.define_table("env", "t", table.share());
+    loader.define_global("env", "g", global.share());
+    loader.load(&slice, |_, _, _| unreachable!()).unwrap();
+    let module = loader.into_module();
+
+    assert_eq!(table.table()[0], Some(1));
+
+    let result = module
+        .func_by_index(0)
+        .unwrap()
+        .invoke(&[])
+        .unwrap()
+        .unwrap()
+        .get_i32()
+        .unwrap();
+    assert_eq!(result, 15);
+    assert_eq!(global.value().get_i32().unwrap(), 15);
+
+    let result = module
+        .func_by_index(1)
+        .unwrap()
+        .invoke(&[])
+        .unwrap()
+        .unwrap()
+        .get_i32()
+        .unwrap();
+    assert_eq!(result, 1234);
+    assert_eq!(memory.read_u32(4).unwrap(), 1234);
+}
+
+#[test]
+fn reference_types() {
+    // (table $a 2 funcref)
+    // (table $b 4 8 funcref)
+    // (table $e 1 externref)
+    // (elem (table $b) (i32.const 0) func $double $square)
+    // (elem $p funcref (ref.func $double) (ref.null func) (ref.func $square))
+    // (elem declare func 4)
+    // (func $double (param i32) (result i32) (i32.mul (local.get 0) (i32.const 2)))
+    // (func $square (param i32) (result i32) (i32.mul (local.get 0) (local.get 0)))
+    // (func (param i32 i32) (result i32)
+    //   (call_indirect $b (type 0) (local.get 1) (local.get 0)))
+    // (func (result i32)
+    //   (i32.add (i32.mul (ref.is_null (ref.null func)) (i32.const 10))
+    //     (ref.is_null (ref.func $double))))
+    // (func (result i32)
+    //   (i32.add (i32.mul (table.grow $b (ref.null func) (i32.const 2)) (i32.const 100))
+    //     (table.size $b)))
+    // (func (result i32) (table.grow $b (ref.null func) (i32.const 10)))
+    // (func (result i32)
+    //   (table.init $a $p (i32.const 0) (i32.const 1) (i32.const 2))
+    //   (elem.drop $p)
+    //   (i32.add (i32.mul (ref.is_null (table.get $a (i32.const 0))) (i32.const 10))
+    //     (call_indirect $a (type 0) (i32.const 5) (i32.const 1))))
+    // (func (param i32 i32) (result i32)
+    //   (call_indirect $a (type 0) (local.get 1) (local.get 0)))
+    // (func (result i32) (table.init $a $p (i32.const 0) (i32.const 0) (i32.const 1)) (i32.const 0))
+    // (func (result i32)
+    //   (table.copy $b $a (i32.const 2) (i32.const 0) (i32.const 2))
+    //   (table.fill $a (i32.const 0) (ref.func $double) (i32.const 1))
+    //   (table.set $b (i32.const 0) (table.get $b (i32.const 1)))
+    //   (call_indirect $b (type 0) (i32.const 3) (i32.const 0)))
+    // (func (param externref) (result externref)
+    //   (table.set $e (i32.const 0) (local.get 0))
+    //   (select (result externref) (table.get $e (i32.const 0)) (ref.null extern) (i32.const 1)))
+    let slice = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x15, 0x04, 0x60, 0x01, 0x7f, 0x01,
+        0x7f, 0x60, 0x00, 0x01, 0x7f, 0x60, 0x01, 0x6f, 0x01, 0x6f, 0x60, 0x02, 0x7f, 0x7f, 0x01,
+        0x7f, 0x03, 0x0c, 0x0b, 0x00, 0x00, 0x03, 0x01, 0x01, 0x01, 0x01, 0x03, 0x01, 0x01, 0x02,
+        0x04, 0x0b, 0x03, 0x70, 0x00, 0x02, 0x70, 0x01, 0x04, 0x08, 0x6f, 0x00, 0x01, 0x09, 0x1a,
+        0x03, 0x02, 0x01, 0x41, 0x00, 0x0b, 0x00, 0x02, 0x00, 0x01, 0x05, 0x70, 0x03, 0xd2, 0x00,
+        0x0b, 0xd0, 0x70, 0x0b, 0xd2, 0x01, 0x0b, 0x03, 0x00, 0x01, 0x04, 0x0a, 0xb6, 0x01, 0x0b,
+        0x07, 0x00, 0x20, 0x00, 0x41, 0x02, 0x6c, 0x0b, 0x07, 0x00, 0x20, 0x00, 0x20, 0x00, 0x6c,
+        0x0b, 0x09, 0x00, 0x20, 0x01, 0x20, 0x00, 0x11, 0x00, 0x01, 0x0b, 0x0c, 0x00, 0xd0, 0x70,
+        0xd1, 0x41, 0x0a, 0x6c, 0xd2, 0x00, 0xd1, 0x6a, 0x0b, 0x11, 0x00, 0xd0, 0x70, 0x41, 0x02,
+        0xfc, 0x0f, 0x01, 0x41, 0xe4, 0x00, 0x6c, 0xfc, 0x10, 0x01, 0x6a, 0x0b, 0x09, 0x00, 0xd0,
+        0x70, 0x41, 0x0a, 0xfc, 0x0f, 0x01, 0x0b, 0x1f, 0x00, 0x41, 0x00, 0x41, 0x01, 0x41, 0x02,
+        0xfc, 0x0c, 0x01, 0x00, 0xfc, 0x0d, 0x01, 0x41, 0x00, 0x25, 0x00, 0xd1, 0x41, 0x0a, 0x6c,
+        0x41, 0x05, 0x41, 0x01, 0x11, 0x00, 0x00, 0x6a, 0x0b, 0x09, 0x00, 0x20, 0x01, 0x20, 0x00,
+        0x11, 0x00, 0x00, 0x0b, 0x0e, 0x00, 0x41, 0x00, 0x41, 0x00, 0x41, 0x01, 0xfc, 0x0c, 0x01,
+        0x00, 0x41, 0x00, 0x0b, 0x24, 0x00, 0x41, 0x02, 0x41, 0x00, 0x41, 0x02, 0xfc, 0x0e, 0x01,
+        0x00, 0x41, 0x00, 0xd2, 0x00, 0x41, 0x01, 0xfc, 0x11, 0x00, 0x41, 0x00, 0x41, 0x01, 0x25,
+        0x01, 0x26, 0x01, 0x41, 0x03, 0x41, 0x00, 0x11, 0x00, 0x01, 0x0b, 0x13, 0x00, 0x41, 0x00,
+        0x20, 0x00, 0x26, 0x02, 0x41, 0x00, 0x25, 0x02, 0xd0, 0x6f, 0x41, 0x01, 0x1c, 0x01, 0x6f,
+        0x0b,
+    ];
+    let module = WasmLoader::instantiate(&slice, |_, _, _| unreachable!()).unwrap();
+
+    let call = |index: usize, params: &[WasmValue]| {
+        module
+            .func_by_index(index)
+            .unwrap()
+            .invoke(params)
+            .map(|v| v.unwrap())
+    };
+
+    // active segment and call_indirect on the second table
+    assert_eq!(
+        call(2, &[0.into(), 7.into()]).unwrap().get_i32().unwrap(),
+        14
+    );
+    assert_eq!(
+        call(2, &[1.into(), 7.into()]).unwrap().get_i32().unwrap(),
+        49
+    );
+    let err = call(2, &[2.into(), 7.into()]).unwrap_err();
+    assert_eq!(err.kind(), WasmRuntimeErrorType::NoMethod);
+
+    // ref.null, ref.is_null, ref.func
+    assert_eq!(call(3, &[]).unwrap().get_i32().unwrap(), 10);
+
+    // table.grow, table.size
+    assert_eq!(call(4, &[]).unwrap().get_i32().unwrap(), 406);
+    assert_eq!(call(5, &[]).unwrap().get_i32().unwrap(), -1);
+    assert_eq!(module.table(1).unwrap().len(), 6);
+
+    // table.init, elem.drop, table.get
+    let err = call(7, &[0.into(), 5.into()]).unwrap_err();
+    assert_eq!(err.kind(), WasmRuntimeErrorType::NoMethod);
+    assert_eq!(call(6, &[]).unwrap().get_i32().unwrap(), 35);
+    let err = call(8, &[]).unwrap_err();
+    assert_eq!(err.kind(), WasmRuntimeErrorType::OutOfBounds);
+
+    // table.copy, table.fill, table.set
+    assert_eq!(call(9, &[]).unwrap().get_i32().unwrap(), 9);
+    let table = module.table(1).unwrap();
+    assert_eq!(table.get(0).unwrap(), Some(1));
+    assert_eq!(table.get(2).unwrap(), None);
+    assert_eq!(table.get(3).unwrap(), Some(1));
+    assert_eq!(module.table(0).unwrap().get(0).unwrap(), Some(0));
+    assert_eq!(
+        call(7, &[0.into(), 5.into()]).unwrap().get_i32().unwrap(),
+        10
+    );
+
+    // externref
+    let result = call(10, &[WasmValue::ExternRef(Some(42))]).unwrap();
+    assert_eq!(result.get_externref().unwrap(), Some(42));
+    let result = call(10, &[WasmValue::ExternRef(None)]).unwrap();
+    assert_eq!(result.get_externref().unwrap(), None);
+    assert_eq!(module.table(2).unwrap().get(0).unwrap(), None);
+    assert!(call(10, &[WasmValue::I32(1)]).is_err());
+}
+
+#[test]
+fn threads() {
+    // (memory 1 1 shared)
+    // (func (param i32 i32) (result i32) (i32.atomic.rmw.add (local.get 0) (local.get 1)))
+    // (func (param i32 i32 i32) (result i32)
+    //   (i32.atomic.rmw.cmpxchg (local.get 0) (local.get 1) (local.get 2)))
+    // (func (param i32) (result i64) (i64.atomic.load (local.get 0)))
+    // (func (param i32 i64) (result i64) (i64.atomic.rmw8.sub_u (local.get 0) (local.get 1)))
+    // (func (param i32 i32 i64) (result i32)
+    //   (memory.atomic.wait32 (local.get 0) (local.get 1) (local.get 2)))
+    // (func (param i32 i32) (result i32) (memory.atomic.notify (local.get 0) (local.get 1)))
+    // (func (param i32 i32) (i32.atomic.store16 (local.get 0) (local.get 1)) (atomic.fence))
+    let slice = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x25, 0x06, 0x60, 0x02, 0x7f, 0x7f,
+        0x01, 0x7f, 0x60, 0x03, 0x7f, 0x7f, 0x7f, 0x01, 0x7f, 0x60, 0x01, 0x7f, 0x01, 0x7e, 0x60,
+        0x02, 0x7f, 0x7e, 0x01, 0x7e, 0x60, 0x03, 0x7f, 0x7f, 0x7e, 0x01, 0x7f, 0x60, 0x02, 0x7f,
+        0x7f, 0x00, 0x03, 0x08, 0x07, 0x00, 0x01, 0x02, 0x03, 0x04, 0x00, 0x05, 0x05, 0x04, 0x01,
+        0x03, 0x01, 0x01, 0x0a, 0x53, 0x07, 0x0a, 0x00, 0x20, 0x00, 0x20, 0x01, 0xfe, 0x1e, 0x02,
+        0x00, 0x0b, 0x0c, 0x00, 0x20, 0x00, 0x20, 0x01, 0x20, 0x02, 0xfe, 0x48, 0x02, 0x00, 0x0b,
+        0x08, 0x00, 0x20, 0x00, 0xfe, 0x11, 0x03, 0x00, 0x0b, 0x0a, 0x00, 0x20, 0x00, 0x20, 0x01,
+        0xfe, 0x29, 0x00, 0x00, 0x0b, 0x0c, 0x00, 0x20, 0x00, 0x20, 0x01, 0x20, 0x02, 0xfe, 0x01,
+        0x02, 0x00, 0x0b, 0x0a, 0x00, 0x20, 0x00, 0x20, 0x01, 0xfe, 0x00, 0x02, 0x00, 0x0b, 0x0d,
+        0x00, 0x20, 0x00, 0x20, 0x01, 0xfe, 0x1a, 0x01, 0x00, 0xfe, 0x03, 0x00, 0x0b,
+    ];
+    let mut module = WasmLoader::instantiate(&slice, |_, _, _| unreachable!()).unwrap();
+    assert!(module.memory(0).unwrap().is_shared());
+
+    let call = |module: &WasmModule, index: usize, params: &[WasmValue]| {
+        module
+            .func_by_index(index)
+            .unwrap()
+            .invoke(params)
+            .map(|v| v.unwrap())
+    };
+
+    // read-modify-write returns the old value
+    assert_eq!(
+        call(&module, 0, &[0.into(), 5.into()])
+            .unwrap()
+            .get_i32()
+            .unwrap(),
+        0
+    );
+    assert_eq!(
+        call(&module, 0, &[0.into(), 3.into()])
+            .unwrap()
+            .get_i32()
+            .unwrap(),
+        5
+    );
+    assert_eq!(module.memory(0).unwrap().read_u32(0).unwrap(), 8);
+    let params = [0.into(), 8.into(), 100.into()];
+    assert_eq!(call(&module, 1, &params).unwrap().get_i32().unwrap(), 8);
+    let params = [0.into(), 8.into(), 1.into()];
+    assert_eq!(call(&module, 1, &params).unwrap().get_i32().unwrap(), 100);
+    assert_eq!(module.memory(0).unwrap().read_u32(0).unwrap(), 100);
+
+    // narrow widths are wrapped and zero-extended
+    let params = [8.into(), 0x12345.into()];
+    module.func_by_index(6).unwrap().invoke(&params).unwrap();
+    assert_eq!(
+        call(&module, 2, &[8.into()]).unwrap().get_i64().unwrap(),
+        0x2345
+    );
+    let params = [8.into(), WasmValue::I64(0x46)];
+    assert_eq!(call(&module, 3, &params).unwrap().get_i64().unwrap(), 0x45);
+    assert_eq!(module.memory(0).unwrap().read_u16(8).unwrap(), 0x23ff);
+
+    // traps
+    let err = call(&module, 0, &[2.into(), 1.into()]).unwrap_err();
+    assert_eq!(err.kind(), WasmRuntimeErrorType::UnalignedAtomic);
+    let err = call(&module, 0, &[65536.into(), 1.into()]).unwrap_err();
+    assert_eq!(err.kind(), WasmRuntimeErrorType::OutOfBounds);
+
+    // wait and notify without the handlers
+    let params = [0.into(), 999.into(), WasmValue::I64(-1)];
+    assert_eq!(call(&module, 4, &params).unwrap().get_i32().unwrap(), 1);
+    let params = [0.into(), 100.into(), WasmValue::I64(1000)];
+    assert_eq!(call(&module, 4, &params).unwrap().get_i32().unwrap(), 2);
+    let params = [0.into(), 100.into(), WasmValue::I64(-1)];
+    let err = call(&module, 4, &params).unwrap_err();
+    assert_eq!(err.kind(), WasmRuntimeErrorType::NotSupprted);
+    assert_eq!(
+        call(&module, 5, &[0.into(), 1.into()])
+            .unwrap()
+            .get_i32()
+            .unwrap(),
+        0
+    );
+
+    // wait and notify with the handlers
+    module.set_atomic_handlers(
+        Some(|_, wait| {
+            assert!(wait.is_expected());
+            assert_eq!(wait.timeout(), None);
+            Ok(0)
+        }),
+        Some(|_, _, count| Ok(count)),
+    );
+    let params = [0.into(), 100.into(), WasmValue::I64(-1)];
+    assert_eq!(call(&module, 4, &params).unwrap().get_i32().unwrap(), 0);
+    assert_eq!(
+        call(&module, 5, &[0.into(), 3.into()])
+            .unwrap()
+            .get_i32()
+            .unwrap(),
+        3
+    );
+
+    // another instance for a thread shares the memory and the handlers
+    let thread = module.instantiate_thread().unwrap();
+    assert_eq!(
+        call(&thread, 0, &[0.into(), 1.into()])
+            .unwrap()
+            .get_i32()
+            .unwrap(),
+        100
+    );
+    assert_eq!(module.memory(0).unwrap().read_u32(0).unwrap(), 101);
+    let params = [0.into(), 101.into(), WasmValue::I64(-1)];
+    assert_eq!(call(&thread, 4, &params).unwrap().get_i32().unwrap(), 0);
+
+    // (memory 1 1 shared) (func (param i32) (result i32) (i32.atomic.load align=1 (local.get 0)))
+    let slice = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x06, 0x01, 0x60, 0x01, 0x7f, 0x01,
+        0x7f, 0x03, 0x02, 0x01, 0x00, 0x05, 0x04, 0x01, 0x03, 0x01, 0x01, 0x0a, 0x0a, 0x01, 0x07,
+        0x00, 0x20, 0x00, 0xfe, 0x10, 0x00, 0x00, 0x0b,
+    ];
+    let err = WasmLoader::instantiate(&slice, |_, _, _| unreachable!())
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), WasmDecodeErrorType::InvalidAlignment);
+}
+
+#[test]
+fn shared_memory() {
+    // the reserved size never moves
+    let memory = WasmMemory::new(WasmLimit::new_shared(1, 3));
+    let handle = memory.share();
+    let base = memory.read_bytes(0, 1).unwrap().as_ptr();
+    assert_eq!(memory.grow(1), 1);
+    assert_eq!(handle.size(), 2);
+    assert_eq!(handle.grow(2), -1);
+    assert_eq!(handle.grow(1), 2);
+    assert_eq!(memory.size(), 3);
+    assert_eq!(memory.read_bytes(0, 1).unwrap().as_ptr(), base);
+    assert_eq!(memory.read_u8(3 * WasmMemory::PAGE_SIZE - 1).unwrap(), 0);
+
+    // the alignment of atomic accesses is checked with the effective address
+    assert!(memory.atomic_load(8, 8).is_ok());
+    assert_eq!(
+        memory.atomic_load(4, 8).unwrap_err(),
+        WasmRuntimeErrorType::UnalignedAtomic
+    );
+
+    // a memory that is not shared cannot be accessed by other threads
+    // (memory 1)
+    let slice = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x05, 0x03, 0x01, 0x00, 0x01,
+    ];
+    let module = WasmLoader::instantiate(&slice, |_, _, _| unreachable!()).unwrap();
+    assert!(module.instantiate_thread().is_none());
+    assert_eq!(module.memory(0).unwrap().grow(1), 1);
+    assert_eq!(module.memory(0).unwrap().size(), 2);
+
+    // the reservation of a shared memory is bounded by the quota
+    // (memory 1 65536 shared)
+    let slice = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x05, 0x06, 0x01, 0x03, 0x01, 0x80, 0x80,
+        0x04,
+    ];
+    let mut loader = WasmLoader::new();
+    loader.set_memory_quota(Some(3 * WasmMemory::PAGE_SIZE));
+    loader.load(&slice, |_, _, _| unreachable!()).unwrap();
+    let memory = loader.module().memory(0).unwrap();
+    assert_eq!(memory.capacity(), 3 * WasmMemory::PAGE_SIZE);
+    assert_eq!(memory.grow(2), 1);
+    assert_eq!(memory.grow(1), -1);
+
+    // a memory whose initial size exceeds the quota is rejected
+    // (memory 4)
+    let slice = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x05, 0x03, 0x01, 0x00, 0x04,
+    ];
+    let mut loader = WasmLoader::new();
+    loader.set_memory_quota(Some(3 * WasmMemory::PAGE_SIZE));
+    assert_eq!(
+        loader
+            .load(&slice, |_, _, _| unreachable!())
+            .unwrap_err()
+            .kind(),
+        WasmDecodeErrorType::OutOfMemory
+    );
+}
+
+#[test]
+fn tail_call() {
+    // (type $t1 (func (param i32) (result i32)))
+    // (table 1 funcref) (elem (i32.const 0) $count)
+    // (func $fact (param i64 i64) (result i64)
+    //   (if (result i64) (i64.eqz (local.get 0))
+    //     (then (local.get 1))
+    //     (else
+    //       (return_call $fact
+    //         (i64.sub (local.get 0) (i64.const 1)) (i64.mul (local.get 0) (local.get 1))))))
+    // (func $count (type $t1)
+    //   (if (result i32) (i32.eqz (local.get 0))
+    //     (then (i32.const 42))
+    //     (else
+    //       (return_call_indirect (type $t1)
+    //         (i32.sub (local.get 0) (i32.const 1)) (i32.const 0)))))
+    let slice = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x0c, 0x02, 0x60, 0x02, 0x7e, 0x7e,
+        0x01, 0x7e, 0x60, 0x01, 0x7f, 0x01, 0x7f, 0x03, 0x03, 0x02, 0x00, 0x01, 0x04, 0x04, 0x01,
+        0x70, 0x00, 0x01, 0x09, 0x07, 0x01, 0x00, 0x41, 0x00, 0x0b, 0x01, 0x01, 0x0a, 0x2f, 0x02,
+        0x17, 0x00, 0x20, 0x00, 0x50, 0x04, 0x7e, 0x20, 0x01, 0x05, 0x20, 0x00, 0x42, 0x01, 0x7d,
+        0x20, 0x00, 0x20, 0x01, 0x7e, 0x12, 0x00, 0x0b, 0x0b, 0x15, 0x00, 0x20, 0x00, 0x45, 0x04,
+        0x7f, 0x41, 0x2a, 0x05, 0x20, 0x00, 0x41, 0x01, 0x6b, 0x41, 0x00, 0x13, 0x01, 0x00, 0x0b,
+        0x0b,
+    ];
+    let module = WasmLoader::instantiate(&slice, |_, _, _| unreachable!()).unwrap();
+
+    let result = module
+        .func_by_index(0)
+        .unwrap()
+        .invoke(&[20i64.into(), 1i64.into()])
+        .unwrap()
+        .unwrap()
+        .get_i64()
+        .unwrap();
+    assert_eq!(result, 2_432_902_008_176_640_000);
+
+    // Deep enough to overflow the stack heap unless the frames are reused
+    let result = module
+        .func_by_index(1)
+        .unwrap()
+        .invoke(&[1_000_000.into()])
+        .unwrap()
+        .unwrap()
+        .get_i32()
+        .unwrap();
+    assert_eq!(result, 42);
+
+    // (func (result i32) (return_call 1)) (func (result i64) (i64.const 0))
+    let slice = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x09, 0x02, 0x60, 0x00, 0x01, 0x7f,
+        0x60, 0x00, 0x01, 0x7e, 0x03, 0x03, 0x02, 0x00, 0x01, 0x0a, 0x0b, 0x02, 0x04, 0x00, 0x12,
+        0x01, 0x0b, 0x04, 0x00, 0x42, 0x00, 0x0b,
+    ];
+    let err = WasmLoader::instantiate(&slice, |_, _, _| unreachable!())
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), WasmDecodeErrorType::TypeMismatch);
+}
+
+#[test]
+fn exception_handling() {
+    // (tag $e0 (param i32)) (tag $e1 (param i32))
+    // (export "e0" (tag $e0))
+    // (func $thrower (param i32)
+    //   (if (i32.gt_s (local.get 0) (i32.const 0)) (then (throw $e0 (local.get 0))))
+    //   (throw $e1 (local.get 0)))
+    // (func (param i32) (result i32)
+    //   (try (result i32)
+    //     (do (call $thrower (local.get 0)) (i32.const 0))
+    //     (catch $e0 (i32.mul (i32.const 2)))
+    //     (catch_all (i32.const -1))))
+    // (func (param i32) (result i32)
+    //   (try (result i32)
+    //     (do
+    //       (try (do (call $thrower (local.get 0))) (catch $e0 (drop) (rethrow 0)))
+    //       (i32.const 0))
+    //     (catch $e0 (i32.add (i32.const 1)))))
+    // (func (param i32) (result i32)
+    //   (try (result i32)
+    //     (do (try (do (throw $e0 (local.get 0))) (delegate 0)) (i32.const 0))
+    //     (catch $e0 (i32.add (i32.const 100)))))
+    // (func (param i32) (result i32)
+    //   (try (result i32) (do (call $thrower (local.get 0)) (i32.const 0)) (catch $e0)))
+    let slice = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x0a, 0x02, 0x60, 0x01, 0x7f, 0x00,
+        0x60, 0x01, 0x7f, 0x01, 0x7f, 0x03, 0x06, 0x05, 0x00, 0x01, 0x01, 0x01, 0x01, 0x0d, 0x05,
+        0x02, 0x00, 0x00, 0x00, 0x00, 0x07, 0x06, 0x01, 0x02, 0x65, 0x30, 0x04, 0x00, 0x0a, 0x65,
+        0x05, 0x12, 0x00, 0x20, 0x00, 0x41, 0x00, 0x4a, 0x04, 0x40, 0x20, 0x00, 0x08, 0x00, 0x0b,
+        0x20, 0x00, 0x08, 0x01, 0x0b, 0x13, 0x00, 0x06, 0x7f, 0x20, 0x00, 0x10, 0x00, 0x41, 0x00,
+        0x07, 0x00, 0x41, 0x02, 0x6c, 0x19, 0x41, 0x7f, 0x0b, 0x0b, 0x18, 0x00, 0x06, 0x7f, 0x06,
+        0x40, 0x20, 0x00, 0x10, 0x00, 0x07, 0x00, 0x1a, 0x09, 0x00, 0x0b, 0x41, 0x00, 0x07, 0x00,
+        0x41, 0x01, 0x6a, 0x0b, 0x0b, 0x15, 0x00, 0x06, 0x7f, 0x06, 0x40, 0x20, 0x00, 0x08, 0x00,
+        0x18, 0x00, 0x41, 0x00, 0x07, 0x00, 0x41, 0xe4, 0x00, 0x6a, 0x0b, 0x0b, 0x0d, 0x00, 0x06,
+        0x7f, 0x20, 0x00, 0x10, 0x00, 0x41, 0x00, 0x07, 0x00, 0x0b, 0x0b,
+    ];
+    let module = WasmLoader::instantiate(&slice, |_, _, _| unreachable!()).unwrap();
+    assert_eq!(module.tags(), &[0, 0]);
+
+    let call = |index: usize, param: i32| {
+        module
+            .func_by_index(index)
+            .unwrap()
+            .invoke(&[param.into()])
+            .map(|v| v.unwrap().get_i32().unwrap())
+    };
+
+    // catch and catch_all across the call
+    assert_eq!(call(1, 5).unwrap(), 10);
+    assert_eq!(call(1, -3).unwrap(), -1);
+
+    // rethrow to the outer try-block
+    assert_eq!(call(2, 5).unwrap(), 6);
+    assert_eq!(
+        call(2, -1).err().unwrap().kind(),
+        WasmRuntimeErrorType::Exception
+    );
+
+    // delegate to the outer try-block
+    assert_eq!(call(3, 7).unwrap(), 107);
+
+    // an exception that does not match any catch-clause goes through the function
+    assert_eq!(call(4, 3).unwrap(), 3);
+    let err = call(4, -2).err().unwrap();
+    assert_eq!(err.kind(), WasmRuntimeErrorType::Exception);
+    assert_eq!(err.function(), 0);
+
+    let wat = alloc::format!("{}", WasmDisassembler::new(&module));
+    assert!(wat.contains("\n  (tag (;1;) (type 0))\n"), "{}", wat);
+    assert!(wat.contains("\n  (export \"e0\" (tag 0))\n"), "{}", wat);
+    let expected = [
+        "    try (result i32)                       ;; +1",
+        "      try                                  ;; +3",
+        "        local.get 0                        ;; +5",
+        "        throw 0                            ;; +7",
+        "      delegate 0                           ;; +9",
+        "      i32.const 0                          ;; +b",
+        "    catch 0                                ;; +d",
+        "      i32.const 100                        ;; +f",
+        "      i32.add                              ;; +12",
+        "    end                                    ;; +13",
+    ];
+    let mut lines = wat.lines().skip_while(|v| !v.starts_with("  (func (;3;)"));
+    assert!(lines.next().is_some(), "{}", wat);
+    assert!(
+        lines.take(expected.len()).eq(expected.iter().copied()),
+        "{}",
+        wat
+    );
+
+    // The handlers are kept in the precompiled image
+    let digest = WasmLoader::digest(&slice);
+    let image = module.to_image(digest).unwrap();
+    let restored = WasmLoader::instantiate_image(&image, digest, |_, _, _| unreachable!()).unwrap();
+    let result = restored
+        .func_by_index(2)
+        .unwrap()
+        .invoke(&[5.into()])
+        .unwrap()
+        .unwrap()
+        .get_i32()
+        .unwrap();
+    assert_eq!(result, 6);
+}
+
+#[test]
+fn simd() {
+    // (memory 1)
+    // (func $dot (param i32 i32) (result i32) (local v128)
+    //   (local.set 2 (i32x4.mul (v128.load (local.get 0)) (v128.load (local.get 1))))
+    //   (i32.add
+    //     (i32.add (i32x4.extract_lane 0 (local.get 2)) (i32x4.extract_lane 1 (local.get 2)))
+    //     (i32.add (i32x4.extract_lane 2 (local.get 2)) (i32x4.extract_lane 3 (local.get 2)))))
+    // (func $reverse (param i32)
+    //   (v128.store (local.get 0)
+    //     (i8x16.shuffle 15 14 13 12 11 10 9 8 7 6 5 4 3 2 1 0
+    //       (v128.load (local.get 0)) (v128.const i32x4 0 0 0 0))))
+    // (func $greater (param i32) (result i32)
+    //   (i8x16.bitmask
+    //     (i8x16.lt_s (i8x16.splat (local.get 0))
+    //       (v128.const i8x16 0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15))))
+    // (func $echo (param v128) (result v128) (local.get 0))
+    let slice = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x15, 0x04, 0x60, 0x02, 0x7f, 0x7f,
+        0x01, 0x7f, 0x60, 0x01, 0x7f, 0x00, 0x60, 0x01, 0x7f, 0x01, 0x7f, 0x60, 0x01, 0x7b, 0x01,
+        0x7b, 0x03, 0x05, 0x04, 0x00, 0x01, 0x02, 0x03, 0x05, 0x03, 0x01, 0x00, 0x01, 0x0a, 0x83,
+        0x01, 0x04, 0x2c, 0x01, 0x01, 0x7b, 0x20, 0x00, 0xfd, 0x00, 0x04, 0x00, 0x20, 0x01, 0xfd,
+        0x00, 0x04, 0x00, 0xfd, 0xb5, 0x01, 0x21, 0x02, 0x20, 0x02, 0xfd, 0x1b, 0x00, 0x20, 0x02,
+        0xfd, 0x1b, 0x01, 0x6a, 0x20, 0x02, 0xfd, 0x1b, 0x02, 0x20, 0x02, 0xfd, 0x1b, 0x03, 0x6a,
+        0x6a, 0x0b, 0x32, 0x00, 0x20, 0x00, 0x20, 0x00, 0xfd, 0x00, 0x04, 0x00, 0xfd, 0x0c, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0xfd, 0x0d, 0x0f, 0x0e, 0x0d, 0x0c, 0x0b, 0x0a, 0x09, 0x08, 0x07, 0x06, 0x05, 0x04, 0x03,
+        0x02, 0x01, 0x00, 0xfd, 0x0b, 0x04, 0x00, 0x0b, 0x1c, 0x00, 0x20, 0x00, 0xfd, 0x0f, 0xfd,
+        0x0c, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d,
+        0x0e, 0x0f, 0xfd, 0x25, 0xfd, 0x64, 0x0b, 0x04, 0x00, 0x20, 0x00, 0x0b,
+    ];
+    let module = WasmLoader::instantiate(&slice, |_, _, _| unreachable!()).unwrap();
+    let memory = module.memory(0).unwrap();
+    let call = |index: usize, params: &[WasmValue]| {
+        module
+            .func_by_index(index)
+            .unwrap()
+            .invoke(params)
+            .map(|v| v.unwrap())
+    };
+
+    for i in 0..8 {
+        memory.write_u32(i * 4, i as u32 + 1).unwrap();
+    }
+    let result = call(0, &[0.into(), 16.into()]).unwrap().get_i32().unwrap();
+    // 1 * 5 + 2 * 6 + 3 * 7 + 4 * 8
+    assert_eq!(result, 70);
+    let err = call(0, &[0.into(), 65530.into()]).unwrap_err();
+    assert_eq!(err.kind(), WasmRuntimeErrorType::OutOfBounds);
+
+    memory.write_slice(0x100, b"0123456789abcdef").unwrap();
+    module
+        .func_by_index(1)
+        .unwrap()
+        .invoke(&[0x100.into()])
+        .unwrap();
+    assert_eq!(memory.read_bytes(0x100, 16).unwrap(), b"fedcba9876543210");
+
+    assert_eq!(call(2, &[5.into()]).unwrap().get_i32().unwrap(), 0xFFC0);
+    assert_eq!(call(2, &[(-1).into()]).unwrap().get_i32().unwrap(), 0xFFFF);
+
+    let value = 0x0123_4567_89AB_CDEF_FEDC_BA98_7654_3210u128;
+    let result = call(3, &[value.into()]).unwrap().get_v128().unwrap();
+    assert_eq!(result, value);
+    let err = call(3, &[1.into()]).unwrap_err();
+    assert_eq!(err.kind(), WasmRuntimeErrorType::InvalidParameter);
+
+    // The functions that hold v128 values are never compiled to the native code
+    let code_block = |index: usize| module.functions()[index].code_block().unwrap();
+    assert!(code_block(0).uses_v128());
+    assert!(code_block(3).uses_v128());
+
+    let wat = alloc::format!("{}", WasmDisassembler::new(&module));
+    assert!(wat.contains(" i32x4.extract_lane 3 "), "{}", wat);
+    assert!(
+        wat.contains(" v128.const i32x4 0x03020100 0x07060504 0x0b0a0908 0x0f0e0d0c "),
+        "{}",
+        wat
+    );
+    assert!(
+        wat.contains(" i8x16.shuffle 15 14 13 12 11 10 9 8 7 6 5 4 3 2 1 0 "),
+        "{}",
+        wat
+    );
+
+    // (func (result i32) (i32x4.extract_lane 4 (v128.const i32x4 0 0 0 0)))
+    let slice = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f,
+        0x03, 0x02, 0x01, 0x00, 0x0a, 0x19, 0x01, 0x17, 0x00, 0xfd, 0x0c, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xfd, 0x1b, 0x04,
+        0x0b,
+    ];
+    let err = WasmLoader::instantiate(&slice, |_, _, _| unreachable!())
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), WasmDecodeErrorType::InvalidParameter);
+}
+
+#[test]
+fn fuel() {
+    // (func (param i32) (result i32) (local i32)
+    //   (loop
+    //     (br_if 0 (i32.ne (local.tee 1 (i32.add (local.get 1) (i32.const 1))) (local.get 0))))
+    //   (local.get 1))
+    let slice = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x06, 0x01, 0x60, 0x01, 0x7f, 0x01,
+        0x7f, 0x03, 0x02, 0x01, 0x00, 0x0a, 0x17, 0x01, 0x15, 0x01, 0x01, 0x7f, 0x03, 0x40, 0x20,
+        0x01, 0x41, 0x01, 0x6a, 0x22, 0x01, 0x20, 0x00, 0x47, 0x0d, 0x00, 0x0b, 0x20, 0x01, 0x0b,
+    ];
+    let mut module = WasmLoader::instantiate(&slice, |_, _, _| unreachable!()).unwrap();
+
+    let count = |module: &WasmModule, n: i32| {
+        module
+            .func_by_index(0)
+            .unwrap()
+            .invoke(&[WasmValue::I32(n)])
+            .map(|v| v.unwrap().get_i32().unwrap())
+    };
+
+    assert_eq!(count(&module, 1000).unwrap(), 1000);
+
+    module.set_fuel(Some(100));
+    let err = count(&module, 1000).unwrap_err();
+    assert_eq!(err.kind(), WasmRuntimeErrorType::OutOfFuel);
+    assert_eq!(err.opcode(), WasmOpcode::BrIf);
+    assert_eq!(module.fuel(), Some(0));
+
+    module.add_fuel(1000);
+    assert_eq!(count(&module, 500).unwrap(), 500);
+    assert_eq!(module.fuel(), Some(501));
+
+    // refill
+    module.set_fuel(Some(10));
+    module.set_fuel_handler(Some(|module| match module.fuel() {
+        Some(0) => Ok(10),
+        _ => unreachable!(),
+    }));
+    assert_eq!(count(&module, 1000).unwrap(), 1000);
+
+    // abort
+    module.set_fuel(Some(10));
+    module.set_fuel_handler(Some(|_| Err(WasmRuntimeErrorType::Exit)));
+    let err = count(&module, 1000).unwrap_err();
+    assert_eq!(err.kind(), WasmRuntimeErrorType::Exit);
+
+    module.set_fuel(None);
+    assert_eq!(count(&module, 1000).unwrap(), 1000);
+}
+
+#[test]
+fn debug_handler() {
+    // (func (param i32) (result i32) (local i32)
+    //   (loop
+    //     (br_if 0 (i32.ne (local.tee 1 (i32.add (local.get 1) (i32.const 1))) (local.get 0))))
+    //   (local.get 1))
+    let slice = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x06, 0x01, 0x60, 0x01, 0x7f, 0x01,
+        0x7f, 0x03, 0x02, 0x01, 0x00, 0x0a, 0x17, 0x01, 0x15, 0x01, 0x01, 0x7f, 0x03, 0x40, 0x20,
+        0x01, 0x41, 0x01, 0x6a, 0x22, 0x01, 0x20, 0x00, 0x47, 0x0d, 0x00, 0x0b, 0x20, 0x01, 0x0b,
+    ];
+    let module = WasmLoader::instantiate(&slice, |_, _, _| unreachable!()).unwrap();
+
+    let count = |module: &WasmModule, n: i32| {
+        module
+            .func_by_index(0)
+            .unwrap()
+            .invoke(&[WasmValue::I32(n)])
+            .map(|v| v.unwrap().get_i32().unwrap())
+    };
+
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    static ENTRIES: AtomicUsize = AtomicUsize::new(0);
+    static STEPS: AtomicUsize = AtomicUsize::new(0);
+
+    // breaks at the third br_if
+    module.set_debug_handler(Some(|frame| {
+        STEPS.fetch_add(1, Ordering::Relaxed);
+        if frame.is_entry() {
+            ENTRIES.fetch_add(1, Ordering::Relaxed);
+            assert_eq!(frame.function(), 0);
+            assert_eq!(frame.local_types(), &[WasmValType::I32, WasmValType::I32]);
+            assert_eq!(frame.local(0).unwrap().get_i32().unwrap(), 1000);
+            assert!(frame.local(2).is_none());
+        }
+        if frame.opcode() == Some(WasmOpcode::BrIf) {
+            assert!(frame.stack().is_empty());
+            if frame.local(1).unwrap().get_i32().unwrap() == 3 {
+                return Err(WasmRuntimeErrorType::Exit);
+            }
+        }
+        Ok(())
+    }));
+    let err = count(&module, 1000).unwrap_err();
+    assert_eq!(err.kind(), WasmRuntimeErrorType::Exit);
+    assert_eq!(err.opcode(), WasmOpcode::BrIf);
+    assert_eq!(ENTRIES.load(Ordering::Relaxed), 1);
+    assert!(STEPS.load(Ordering::Relaxed) > 3);
+
+    module.set_debug_handler(None);
+    assert_eq!(count(&module, 1000).unwrap(), 1000);
+}
+
+#[test]
+fn name_section() {
+    // (module $app
+    //   (func $main (call $crash))
+    //   (func $crash (unreachable)))
+    let slice = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x04, 0x01, 0x60, 0x00, 0x00, 0x03,
+        0x03, 0x02, 0x00, 0x00, 0x0a, 0x0a, 0x02, 0x04, 0x00, 0x10, 0x01, 0x0b, 0x03, 0x00, 0x00,
+        0x0b, 0x00, 0x1b, 0x04, 0x6e, 0x61, 0x6d, 0x65, 0x00, 0x04, 0x03, 0x61, 0x70, 0x70, 0x01,
+        0x0e, 0x02, 0x00, 0x04, 0x6d, 0x61, 0x69, 0x6e, 0x01, 0x05, 0x63, 0x72, 0x61, 0x73, 0x68,
+    ];
+    let module = WasmLoader::instantiate(&slice, |_, _, _| unreachable!()).unwrap();
+
+    let names = module.names().unwrap();
+    assert_eq!(names.module(), Some("app"));
+    assert_eq!(module.func_name(0), Some("main"));
+    assert_eq!(module.func_name(1), Some("crash"));
+    assert_eq!(module.func_name(2), None);
+
+    let err = module.func_by_index(0).unwrap().invoke(&[]).unwrap_err();
+    assert_eq!(err.kind(), WasmRuntimeErrorType::Unreachable);
+    let backtrace = err.backtrace().collect::<alloc::vec::Vec<_>>();
+    assert_eq!(backtrace.len(), 2);
+    assert_eq!(backtrace[0].function(), 1);
+    assert_eq!(backtrace[0].position(), 1);
+    assert_eq!(backtrace[1].function(), 0);
+    assert_eq!(backtrace[1].position(), 1);
+}
+
+#[test]
+fn disassemble() {
+    // (module $app
+    //   (memory 1)
+    //   (global (mut i32) (i32.const 16))
+    //   (func $abs (param i32) (result i32)
+    //     (if (result i32) (i32.lt_s (local.get 0) (i32.const 0))
+    //       (then (i32.sub (i32.const 0) (local.get 0)))
+    //       (else (local.get 0))))
+    //   (export "abs" (func $abs))
+    //   (data (i32.const 8) "hi\00"))
+    let slice = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x06, 0x01, 0x60, 0x01, 0x7f, 0x01,
+        0x7f, 0x03, 0x02, 0x01, 0x00, 0x05, 0x03, 0x01, 0x00, 0x01, 0x06, 0x06, 0x01, 0x7f, 0x01,
+        0x41, 0x10, 0x0b, 0x07, 0x07, 0x01, 0x03, 0x61, 0x62, 0x73, 0x00, 0x00, 0x0a, 0x14, 0x01,
+        0x12, 0x00, 0x20, 0x00, 0x41, 0x00, 0x48, 0x04, 0x7f, 0x41, 0x00, 0x20, 0x00, 0x6b, 0x05,
+        0x20, 0x00, 0x0b, 0x0b, 0x0b, 0x09, 0x01, 0x00, 0x41, 0x08, 0x0b, 0x03, 0x68, 0x69, 0x00,
+        0x00, 0x13, 0x04, 0x6e, 0x61, 0x6d, 0x65, 0x00, 0x04, 0x03, 0x61, 0x70, 0x70, 0x01, 0x06,
+        0x01, 0x00, 0x03, 0x61, 0x62, 0x73,
+    ];
+    let module = WasmLoader::instantiate(&slice, |_, _, _| unreachable!()).unwrap();
+
+    let wat = alloc::format!("{}", WasmDisassembler::new(&module));
+    let expected = [
+        "(module $app",
+        "  (type (;0;) (func (param i32) (result i32)))",
+        "  (func $abs (;0;) (type 0) (param i32) (result i32)",
+        "    local.get 0                            ;; +1",
+        "    i32.const 0                            ;; +3",
+        "    i32.lt_s                               ;; +5",
+        "    if (result i32)                        ;; +6",
+        "      i32.const 0                          ;; +8",
+        "      local.get 0                          ;; +a",
+        "      i32.sub                              ;; +c",
+        "    else                                   ;; +d",
+        "      local.get 0                          ;; +e",
+        "    end                                    ;; +10",
+        "  )",
+        "  (memory (;0;) 1)",
+        "  (global (;0;) (mut i32) (i32.const 16))",
+        "  (export \"abs\" (func $abs))",
+        "  (data (;0;) (i32.const 8) \"hi\\00\")",
+        ")",
+    ];
+    assert!(wat.lines().eq(expected.iter().copied()), "{}", wat);
+}
+
+#[test]
+fn precompiled_image() {
+    // Same module as `disassemble`
+    let slice = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x06, 0x01, 0x60, 0x01, 0x7f, 0x01,
+        0x7f, 0x03, 0x02, 0x01, 0x00, 0x05, 0x03, 0x01, 0x00, 0x01, 0x06, 0x06, 0x01, 0x7f, 0x01,
+        0x41, 0x10, 0x0b, 0x07, 0x07, 0x01, 0x03, 0x61, 0x62, 0x73, 0x00, 0x00, 0x0a, 0x14, 0x01,
+        0x12, 0x00, 0x20, 0x00, 0x41, 0x00, 0x48, 0x04, 0x7f, 0x41, 0x00, 0x20, 0x00, 0x6b, 0x05,
+        0x20, 0x00, 0x0b, 0x0b, 0x0b, 0x09, 0x01, 0x00, 0x41, 0x08, 0x0b, 0x03, 0x68, 0x69, 0x00,
+        0x00, 0x13, 0x04, 0x6e, 0x61, 0x6d, 0x65, 0x00, 0x04, 0x03, 0x61, 0x70, 0x70, 0x01, 0x06,
+        0x01, 0x00, 0x03, 0x61, 0x62, 0x73,
+    ];
+    let digest = WasmLoader::digest(&slice);
+    let module = WasmLoader::instantiate(&slice, |_, _, _| unreachable!()).unwrap();
+    let image = module.to_image(digest).unwrap();
+    assert_eq!(WasmLoader::identity_image(&image), Some(digest));
+
+    let restored = WasmLoader::instantiate_image(&image, digest, |_, _, _| unreachable!()).unwrap();
+    assert_eq!(
+        alloc::format!("{}", WasmDisassembler::new(&restored)),
+        alloc::format!("{}", WasmDisassembler::new(&module))
+    );
+    let code_block = restored.functions()[0].code_block().unwrap();
+    assert_eq!(
+        code_block.intermediate_codes().len(),
+        module.functions()[0]
+            .code_block()
+            .unwrap()
+            .intermediate_codes()
+            .len()
+    );
+
+    let runnable = restored.func("abs").unwrap();
+    let result = runnable
+        .invoke(&[(-123).into()])
+        .unwrap()
+        .unwrap()
+        .get_i32()
+        .unwrap();
+    assert_eq!(result, 123);
+
+    let memory = restored.memory(0).unwrap();
+    assert_eq!(memory.read_u8(8).unwrap(), b'h');
+    assert_eq!(memory.read_u8(9).unwrap(), b'i');
+
+    // An image of another binary is rejected
+    let err = WasmLoader::instantiate_image(&image, digest ^ 1, |_, _, _| unreachable!())
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), WasmDecodeErrorType::BadExecutable);
+}
+
+#[test]
+fn profiler() {
+    // (module
+    //   (import "env" "host" (func $host (param i32) (result i32)))
+    //   (func $leaf (param i32) (result i32) (i32.add (local.get 0) (i32.const 1)))
+    //   (func $main (result i32) (call $leaf (call $host (call $leaf (i32.const 1)))))
+    //   (export "main" (func $main)))
+    let slice = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x0a, 0x02, 0x60, 0x01, 0x7f, 0x01,
+        0x7f, 0x60, 0x00, 0x01, 0x7f, 0x02, 0x0c, 0x01, 0x03, 0x65, 0x6e, 0x76, 0x04, 0x68, 0x6f,
+        0x73, 0x74, 0x00, 0x00, 0x03, 0x03, 0x02, 0x00, 0x01, 0x07, 0x08, 0x01, 0x04, 0x6d, 0x61,
+        0x69, 0x6e, 0x00, 0x02, 0x0a, 0x14, 0x02, 0x07, 0x00, 0x20, 0x00, 0x41, 0x01, 0x6a, 0x0b,
+        0x0a, 0x00, 0x41, 0x01, 0x10, 0x01, 0x10, 0x00, 0x10, 0x01, 0x0b,
+    ];
+    use core::sync::atomic::{AtomicU64, Ordering};
+
+    // Advances 10 units each time it is read
+    static CLOCK: AtomicU64 = AtomicU64::new(0);
+
+    let mut module = WasmLoader::instantiate(&slice, |_, _, _| {
+        Ok(|_, args| Ok(WasmValue::from(args[0].get_i32().unwrap() * 2)))
+    })
+    .unwrap();
+    module.enable_profiler(Some(|| CLOCK.fetch_add(10, Ordering::SeqCst)));
+
+    let result = module
+        .func("main")
+        .unwrap()
+        .invoke(&[])
+        .unwrap()
+        .unwrap()
+        .get_i32()
+        .unwrap();
+    assert_eq!(result, 5);
+
+    let profiler = module.profiler().unwrap();
+    let entries = profiler.entries();
+    // Sorted by the self time
+    assert_eq!(
+        entries
+            .iter()
+            .map(|v| v.function)
+            .collect::<alloc::vec::Vec<_>>(),
+        [2, 1, 0]
+    );
+
+    let main = entries[0];
+    assert_eq!(main.calls, 1);
+    assert_eq!(main.total_time, 70);
+    assert_eq!(main.self_time, 40);
+
+    let leaf = entries[1];
+    let leaf_codes = module.functions()[1]
+        .code_block()
+        .unwrap()
+        .intermediate_codes()
+        .len() as u64;
+    assert_eq!(leaf.calls, 2);
+    assert_eq!(leaf.instructions, leaf_codes * 2);
+    assert_eq!(leaf.self_time, 20);
+
+    let host = entries[2];
+    assert_eq!(host.calls, 1);
+    assert_eq!(host.instructions, 0);
+    assert_eq!(host.self_time, 10);
+
+    let report = alloc::format!("{}", profiler.report(&module));
+    assert_eq!(report.lines().count(), 4);
+}
+
+#[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+struct TestJitAllocator;
+
+#[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+impl crate::wasmjit::WasmJitAllocator for TestJitAllocator {
+    fn alloc_code(&self, code: &[u8]) -> Option<core::ptr::NonNull<u8>> {
+        extern "C" {
+            fn mmap(addr: *mut u8, len: usize, prot: i32, flags: i32, fd: i32, off: i64)
+                -> *mut u8;
+            fn mprotect(addr: *mut u8, len: usize, prot: i32) -> i32;
+        }
+        const PROT_READ: i32 = 1;
+        const PROT_WRITE: i32 = 2;
+        const PROT_EXEC: i32 = 4;
+        const MAP_PRIVATE: i32 = 2;
+        const MAP_ANONYMOUS: i32 = 0x20;
+        unsafe {
+            let ptr = mmap(
+                core::ptr::null_mut(),
+                code.len(),
+                PROT_READ | PROT_WRITE,
+                MAP_PRIVATE | MAP_ANONYMOUS,
+                -1,
+                0,
+            );
+            if ptr as isize == -1 {
+                return None;
+            }
+            ptr.copy_from_nonoverlapping(code.as_ptr(), code.len());
+            mprotect(ptr, code.len(), PROT_READ | PROT_EXEC);
+            core::ptr::NonNull::new(ptr)
+        }
+    }
+
+    unsafe fn free_code(&self, ptr: core::ptr::NonNull<u8>, len: usize) {
+        extern "C" {
+            fn munmap(addr: *mut u8, len: usize) -> i32;
+        }
+        munmap(ptr.as_ptr(), len);
+    }
+}
+
+#[test]
+#[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+fn jit() {
+    // (memory 1)
+    // (func $fib (param i32) (result i32)
+    //   (if (i32.lt_u (local.get 0) (i32.const 2)) (then (return (local.get 0))))
+    //   (i32.add
+    //     (call $fib (i32.sub (local.get 0) (i32.const 1)))
+    //     (call $fib (i32.sub (local.get 0) (i32.const 2)))))
+    // (func (param i32 i32) (result i32) (i32.div_s (local.get 0) (local.get 1)))
+    // (func (param i32) (result i32)
+    //   (i32.store (local.get 0) (i32.const 0x12345678))
+    //   (i32.load8_u offset=1 (local.get 0)))
+    // (func (param i32) (result i32)
+    //   (block (block (block (br_table 0 1 2 (local.get 0)))
+    //     (return (i32.const 10))) (return (i32.const 11)))
+    //   (i32.const 12))
+    // (func (param i32) (result i64) (local i64)
+    //   (block (loop
+    //     (br_if 1 (i32.eqz (local.get 0)))
+    //     (local.set 1 (i64.add (local.get 1) (i64.extend_i32_u (local.get 0))))
+    //     (local.set 0 (i32.sub (local.get 0) (i32.const 1)))
+    //     (br 0)))
+    //   (local.get 1))
+    // (func (param i32 i32) (result i32)
+    //   (i32.add (i32.clz (local.get 0)) (i32.mul (i32.ctz (local.get 1)) (i32.const 100))))
+    let slice = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x11, 0x03, 0x60, 0x01, 0x7f, 0x01,
+        0x7f, 0x60, 0x02, 0x7f, 0x7f, 0x01, 0x7f, 0x60, 0x01, 0x7f, 0x01, 0x7e, 0x03, 0x07, 0x06,
+        0x00, 0x01, 0x00, 0x00, 0x02, 0x01, 0x05, 0x03, 0x01, 0x00, 0x01, 0x0a, 0x85, 0x01, 0x06,
+        0x1c, 0x00, 0x20, 0x00, 0x41, 0x02, 0x49, 0x04, 0x40, 0x20, 0x00, 0x0f, 0x0b, 0x20, 0x00,
+        0x41, 0x01, 0x6b, 0x10, 0x00, 0x20, 0x00, 0x41, 0x02, 0x6b, 0x10, 0x00, 0x6a, 0x0b, 0x07,
+        0x00, 0x20, 0x00, 0x20, 0x01, 0x6d, 0x0b, 0x12, 0x00, 0x20, 0x00, 0x41, 0xf8, 0xac, 0xd1,
+        0x91, 0x01, 0x36, 0x02, 0x00, 0x20, 0x00, 0x2d, 0x00, 0x01, 0x0b, 0x1a, 0x00, 0x02, 0x40,
+        0x02, 0x40, 0x02, 0x40, 0x20, 0x00, 0x0e, 0x02, 0x00, 0x01, 0x02, 0x0b, 0x41, 0x0a, 0x0f,
+        0x0b, 0x41, 0x0b, 0x0f, 0x0b, 0x41, 0x0c, 0x0b, 0x22, 0x01, 0x01, 0x7e, 0x02, 0x40, 0x03,
+        0x40, 0x20, 0x00, 0x45, 0x0d, 0x01, 0x20, 0x01, 0x20, 0x00, 0xad, 0x7c, 0x21, 0x01, 0x20,
+        0x00, 0x41, 0x01, 0x6b, 0x21, 0x00, 0x0c, 0x00, 0x0b, 0x0b, 0x20, 0x01, 0x0b, 0x0d, 0x00,
+        0x20, 0x00, 0x67, 0x20, 0x01, 0x68, 0x41, 0xe4, 0x00, 0x6c, 0x6a, 0x0b,
+    ];
+    let mut module = WasmLoader::instantiate(&slice, |_, _, _| unreachable!()).unwrap();
+    let interp = WasmLoader::instantiate(&slice, |_, _, _| unreachable!()).unwrap();
+    assert_eq!(module.compile_jit(&TestJitAllocator), 6);
+
+    let call = |module: &WasmModule, index: usize, params: &[WasmValue]| {
+        module
+            .func_by_index(index)
+            .unwrap()
+            .invoke(params)
+            .map(|v| v.unwrap())
+    };
+    let cases: &[(usize, &[WasmValue])] = &[
+        (0, &[WasmValue::I32(0)]),
+        (0, &[WasmValue::I32(1)]),
+        (0, &[WasmValue::I32(20)]),
+        (1, &[WasmValue::I32(7), WasmValue::I32(-2)]),
+        (1, &[WasmValue::I32(i32::MIN), WasmValue::I32(-1)]),
+        (2, &[WasmValue::I32(0)]),
+        (2, &[WasmValue::I32(65532)]),
+        (3, &[WasmValue::I32(0)]),
+        (3, &[WasmValue::I32(1)]),
+        (3, &[WasmValue::I32(2)]),
+        (3, &[WasmValue::I32(100)]),
+        (4, &[WasmValue::I32(100)]),
+        (5, &[WasmValue::I32(1), WasmValue::I32(8)]),
+        (5, &[WasmValue::I32(0), WasmValue::I32(0)]),
+    ];
+    for (index, params) in cases {
+        let expected = call(&interp, *index, params).unwrap();
+        let result = call(&module, *index, params).unwrap();
+        assert_eq!(
+            alloc::format!("{:?}", result),
+            alloc::format!("{:?}", expected)
+        );
+    }
+
+    assert_eq!(
+        call(&module, 0, &[20.into()]).unwrap().get_i32().unwrap(),
+        6765
+    );
+    assert_eq!(
+        call(&module, 2, &[0.into()]).unwrap().get_i32().unwrap(),
+        0x56
+    );
+    assert_eq!(
+        call(&module, 4, &[100.into()]).unwrap().get_i64().unwrap(),
+        5050
+    );
+    assert_eq!(
+        call(&module, 5, &[1.into(), 8.into()])
+            .unwrap()
+            .get_i32()
+            .unwrap(),
+        331
+    );
+
+    let err = call(&module, 1, &[1.into(), 0.into()]).unwrap_err();
+    assert_eq!(err.kind(), WasmRuntimeErrorType::DivideByZero);
+    assert_eq!(err.opcode(), WasmOpcode::I32DivS);
+    let err = call(&module, 2, &[65535.into()]).unwrap_err();
+    assert_eq!(err.kind(), WasmRuntimeErrorType::OutOfBounds);
+    assert_eq!(err.opcode(), WasmOpcode::I32Store);
+}
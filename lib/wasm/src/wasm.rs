@@ -1,23 +1,56 @@
 //! WebAssembly Runtime Library
 
-use crate::{intcode::*, opcode::*, wasmintr::*, *};
-use alloc::{boxed::Box, string::*, vec::Vec};
+#[cfg(target_arch = "x86_64")]
+use crate::wasmjit::*;
+use crate::{intcode::*, opcode::*, profile::*, wasmintr::*, *};
+use alloc::{boxed::Box, rc::Rc, string::*, sync::Arc, vec, vec::Vec};
 use bitflags::*;
 use byteorder::*;
 use core::{
-    cell::{RefCell, UnsafeCell},
-    fmt,
+    alloc::Layout,
+    cell::{Cell, RefCell, UnsafeCell},
+    fmt, mem,
     ops::*,
     slice, str,
+    sync::atomic::*,
+    time::Duration,
 };
 
+mod image;
+
 /// WebAssembly loader
 pub struct WasmLoader {
     module: WasmModule,
+    externals: Vec<(String, String, WasmExternal)>,
+    memory_quota: Option<usize>,
+}
+
+/// Host-provided objects that can be bound to the imports of a module
+pub enum WasmExternal {
+    Memory(WasmMemory),
+    Table(WasmTable),
+    Global(WasmGlobal),
 }
 
 pub type WasmDynFunc = fn(&WasmModule, &[WasmValue]) -> Result<WasmValue, WasmRuntimeErrorType>;
 
+/// Called when the fuel runs out, returns the amount of fuel to refill or an error to abort.
+pub type WasmFuelHandler = fn(&WasmModule) -> Result<u64, WasmRuntimeErrorType>;
+
+/// Called by `memory.atomic.wait32` and `memory.atomic.wait64` on a shared memory,
+/// returns 0 if the thread was woken, 1 if the value was not expected, or 2 if it timed out.
+pub type WasmWaitHandler = fn(&WasmModule, &WasmAtomicWait) -> Result<u32, WasmRuntimeErrorType>;
+
+/// Called by `memory.atomic.notify` on a shared memory with the address and the maximum number of waiters to wake,
+/// returns the number of woken waiters.
+pub type WasmNotifyHandler = fn(&WasmModule, usize, u32) -> Result<u32, WasmRuntimeErrorType>;
+
+/// Called by `memory.grow` with the current and the requested number of pages, returns whether to allow it.
+pub type WasmGrowHandler = fn(&WasmModule, u32, u32) -> bool;
+
+/// Called by the interpreter before each instruction while it is set, returns an error to abort the execution.
+pub type WasmDebugHandler = fn(&WasmDebugFrame) -> Result<(), WasmRuntimeErrorType>;
+
 impl WasmLoader {
     /// Minimal valid module size, Magic(4) + Version(4) + Empty sections(0) = 8
     const MINIMAL_MOD_SIZE: usize = 8;
@@ -30,6 +63,81 @@ impl WasmLoader {
     pub const fn new() -> Self {
         Self {
             module: WasmModule::new(),
+            externals: Vec::new(),
+            memory_quota: None,
+        }
+    }
+
+    /// Limits the bytes that the memories of the module can reserve when they are created.
+    ///
+    /// A memory whose initial size does not fit in the quota is rejected as `OutOfMemory`,
+    /// and a shared memory reserves no more than the rest of the quota.
+    #[inline]
+    pub fn set_memory_quota(&mut self, quota: Option<usize>) {
+        self.memory_quota = quota;
+    }
+
+    /// Creates a memory within the quota left by the other memories
+    fn new_memory(
+        memories: &[WasmMemory],
+        quota: Option<usize>,
+        limit: WasmLimit,
+    ) -> Result<WasmMemory, WasmDecodeErrorType> {
+        let quota = match quota {
+            Some(v) => v,
+            None => return Ok(WasmMemory::new(limit)),
+        };
+        let rest = memories
+            .iter()
+            .fold(quota, |acc, v| acc.saturating_sub(v.capacity()));
+        if limit.min as usize * WasmMemory::PAGE_SIZE > rest {
+            return Err(WasmDecodeErrorType::OutOfMemory);
+        }
+        Ok(WasmMemory::with_quota(limit, Some(rest)))
+    }
+
+    /// Binds a memory to the import with the specified name.
+    ///
+    /// The memory is shared with the module, not copied.
+    #[inline]
+    pub fn define_memory(&mut self, mod_name: &str, name: &str, memory: WasmMemory) {
+        self.define(mod_name, name, WasmExternal::Memory(memory));
+    }
+
+    /// Binds a table to the import with the specified name.
+    ///
+    /// The elements of a `funcref` table are function indexes of the importing module,
+    /// and the elements of an `externref` table are opaque handles defined by the host.
+    #[inline]
+    pub fn define_table(&mut self, mod_name: &str, name: &str, table: WasmTable) {
+        self.define(mod_name, name, WasmExternal::Table(table));
+    }
+
+    /// Binds a global variable to the import with the specified name.
+    #[inline]
+    pub fn define_global(&mut self, mod_name: &str, name: &str, global: WasmGlobal) {
+        self.define(mod_name, name, WasmExternal::Global(global));
+    }
+
+    fn define(&mut self, mod_name: &str, name: &str, external: WasmExternal) {
+        self.externals
+            .push((mod_name.to_string(), name.to_string(), external));
+    }
+
+    fn external(&self, import: &WasmImport) -> Result<&WasmExternal, WasmDecodeErrorType> {
+        let mut has_module = false;
+        for (mod_name, name, external) in &self.externals {
+            if mod_name == import.mod_name() {
+                if name == import.name() {
+                    return Ok(external);
+                }
+                has_module = true;
+            }
+        }
+        if has_module {
+            Err(WasmDecodeErrorType::NoMethod)
+        } else {
+            Err(WasmDecodeErrorType::NoModule)
         }
     }
 
@@ -41,8 +149,34 @@ impl WasmLoader {
             && LE::read_u32(&blob[4..8]) == Self::VER_CURRENT
     }
 
+    /// Returns whether the module imports anything from the specified module, without loading it.
+    pub fn imports_module(blob: &[u8], mod_name: &str) -> bool {
+        if !Self::identity(blob) {
+            return false;
+        }
+        let mut blob = Leb128Stream::from_slice(&blob[8..]);
+        while let Ok(Some(mut section)) = blob.next_section() {
+            if section.section_type != WasmSectionType::Import {
+                continue;
+            }
+            let n_items = match section.stream.read_unsigned() {
+                Ok(v) => v as usize,
+                Err(_) => return false,
+            };
+            for _ in 0..n_items {
+                match WasmImport::from_stream(&mut section.stream) {
+                    Ok(import) if import.mod_name() == mod_name => return true,
+                    Ok(_) => (),
+                    Err(_) => return false,
+                }
+            }
+            return false;
+        }
+        false
+    }
+
     /// Instantiate wasm modules from slice
-    pub fn instantiate<F>(blob: &[u8], resolver: F) -> Result<WasmModule, WasmDecodeErrorType>
+    pub fn instantiate<F>(blob: &[u8], resolver: F) -> Result<WasmModule, WasmDecodeError>
     where
         F: FnMut(&str, &str, &WasmType) -> Result<WasmDynFunc, WasmDecodeErrorType> + Copy,
     {
@@ -50,19 +184,28 @@ impl WasmLoader {
             let mut loader = Self::new();
             loader.load(blob, resolver).map(|_| loader.module)
         } else {
-            return Err(WasmDecodeErrorType::BadExecutable);
+            return Err(WasmDecodeErrorType::BadExecutable.into());
         }
     }
 
     /// Load wasm from slice
-    pub fn load<F>(&mut self, blob: &[u8], resolver: F) -> Result<(), WasmDecodeErrorType>
+    pub fn load<F>(&mut self, blob: &[u8], resolver: F) -> Result<(), WasmDecodeError>
     where
         F: FnMut(&str, &str, &WasmType) -> Result<WasmDynFunc, WasmDecodeErrorType> + Copy,
     {
+        if !Self::identity(blob) {
+            return Err(WasmDecodeErrorType::BadExecutable.into());
+        }
         let mut blob = Leb128Stream::from_slice(&blob[8..]);
-        while let Some(section) = blob.next_section()? {
+        loop {
+            let section_position = 8 + blob.position();
+            let section = match blob.next_section() {
+                Ok(Some(v)) => v,
+                Ok(None) => break,
+                Err(err) => return Err(WasmDecodeError::new(err, None, section_position, None)),
+            };
             match section.section_type {
-                WasmSectionType::Custom => Ok(()),
+                WasmSectionType::Custom => self.parse_sec_custom(section),
                 WasmSectionType::Type => self.parse_sec_type(section),
                 WasmSectionType::Import => self.parse_sec_import(section, resolver),
                 WasmSectionType::Table => self.parse_sec_table(section),
@@ -70,13 +213,22 @@ impl WasmLoader {
                 WasmSectionType::Element => self.parse_sec_elem(section),
                 WasmSectionType::Function => self.parse_sec_func(section),
                 WasmSectionType::Export => self.parse_sec_export(section),
-                WasmSectionType::Code => self.parse_sec_code(section),
+                WasmSectionType::Code => {
+                    self.parse_sec_code(section)?;
+                    continue;
+                }
                 WasmSectionType::Data => self.parse_sec_data(section),
                 WasmSectionType::Start => self.parse_sec_start(section),
                 WasmSectionType::Global => self.parse_sec_global(section),
-            }?;
+                WasmSectionType::DataCount => self.parse_sec_data_count(section),
+                WasmSectionType::Tag => self.parse_sec_tag(section),
+            }
+            .map_err(|err| WasmDecodeError::new(err, None, section_position, None))?;
         }
 
+        self.validate()
+            .map_err(|err| WasmDecodeError::new(err, None, 0, None))?;
+
         self.module.types.shrink_to_fit();
         self.module.imports.shrink_to_fit();
         self.module.functions.shrink_to_fit();
@@ -84,6 +236,9 @@ impl WasmLoader {
         self.module.memories.shrink_to_fit();
         self.module.exports.shrink_to_fit();
         self.module.globals.shrink_to_fit();
+        self.module.tags.shrink_to_fit();
+        self.module.elements.shrink_to_fit();
+        self.module.data.shrink_to_fit();
 
         Ok(())
     }
@@ -100,6 +255,15 @@ impl WasmLoader {
         self.module
     }
 
+    /// Parse custom sections
+    fn parse_sec_custom(&mut self, mut section: WasmSection) -> Result<(), WasmDecodeErrorType> {
+        if section.stream.get_string()? == WasmName::SECTION_NAME {
+            // A malformed "name" section is not an error, it is just ignored.
+            self.module.names = WasmName::from_stream(&mut section.stream).ok();
+        }
+        Ok(())
+    }
+
     /// Parse "type" section
     fn parse_sec_type(&mut self, mut section: WasmSection) -> Result<(), WasmDecodeErrorType> {
         let n_items = section.stream.read_unsigned()? as usize;
@@ -139,9 +303,39 @@ impl WasmLoader {
                     ));
                     self.module.n_ext_func += 1;
                 }
-                WasmImportIndex::Memory(memtype) => {
-                    // TODO: import memory
-                    self.module.memories.push(WasmMemory::new(memtype));
+                WasmImportIndex::Memory(limit) => {
+                    let memory = match self.external(&import)? {
+                        WasmExternal::Memory(v) => v,
+                        _ => return Err(WasmDecodeErrorType::IncompatibleImport),
+                    };
+                    if memory.size() < limit.min() as i32 || memory.is_shared() != limit.is_shared()
+                    {
+                        return Err(WasmDecodeErrorType::IncompatibleImport);
+                    }
+                    let memory = memory.share();
+                    self.module.memories.push(memory);
+                }
+                WasmImportIndex::Table(ref_type, limit) => {
+                    let table = match self.external(&import)? {
+                        WasmExternal::Table(v) => v,
+                        _ => return Err(WasmDecodeErrorType::IncompatibleImport),
+                    };
+                    if table.ref_type() != ref_type || table.len() < limit.min() as usize {
+                        return Err(WasmDecodeErrorType::IncompatibleImport);
+                    }
+                    let table = table.share();
+                    self.module.tables.push(table);
+                }
+                WasmImportIndex::Global(val_type, is_mutable) => {
+                    let global = match self.external(&import)? {
+                        WasmExternal::Global(v) => v,
+                        _ => return Err(WasmDecodeErrorType::IncompatibleImport),
+                    };
+                    if global.val_type() != val_type || global.is_mutable() != is_mutable {
+                        return Err(WasmDecodeErrorType::IncompatibleImport);
+                    }
+                    let global = global.share();
+                    self.module.globals.push(global);
                 }
             }
             self.module.imports.push(import);
@@ -152,7 +346,7 @@ impl WasmLoader {
     /// Parse "func" section
     fn parse_sec_func(&mut self, mut section: WasmSection) -> Result<(), WasmDecodeErrorType> {
         let n_items = section.stream.read_unsigned()? as usize;
-        let base_index = self.module.imports.len();
+        let base_index = self.module.n_ext_func;
         for index in 0..n_items {
             let type_index = section.stream.read_unsigned()? as usize;
             let func_type = self
@@ -174,11 +368,15 @@ impl WasmLoader {
         let n_items = section.stream.read_unsigned()? as usize;
         for i in 0..n_items {
             let export = WasmExport::from_stream(&mut section.stream)?;
+            if self.module.exports.iter().any(|v| v.name == export.name) {
+                return Err(WasmDecodeErrorType::DuplicateExport);
+            }
             if let WasmExportIndex::Function(index) = export.index {
                 self.module
                     .functions
                     .get_mut(index)
-                    .map(|v| v.origin = WasmFunctionOrigin::Export(i));
+                    .ok_or(WasmDecodeErrorType::InvalidFunction)?
+                    .origin = WasmFunctionOrigin::Export(i);
             }
             self.module.exports.push(export);
         }
@@ -190,7 +388,8 @@ impl WasmLoader {
         let n_items = section.stream.read_unsigned()?;
         for _ in 0..n_items {
             let limit = WasmLimit::from_stream(&mut section.stream)?;
-            self.module.memories.push(WasmMemory::new(limit));
+            let memory = Self::new_memory(&self.module.memories, self.memory_quota, limit)?;
+            self.module.memories.push(memory);
         }
         Ok(())
     }
@@ -209,71 +408,217 @@ impl WasmLoader {
     fn parse_sec_elem(&mut self, mut section: WasmSection) -> Result<(), WasmDecodeErrorType> {
         let n_items = section.stream.read_unsigned()?;
         for _ in 0..n_items {
-            let tabidx = section.stream.read_unsigned()? as usize;
-            let offset = self.eval_offset(&mut section.stream)? as usize;
+            // bit 0: passive or declarative, bit 1: explicit table index or declarative, bit 2: expressions
+            let flags = section.stream.read_unsigned()?;
+            if flags > 7 {
+                return Err(WasmDecodeErrorType::UnexpectedToken);
+            }
+            let active = match flags & 3 {
+                0 => Some((0, self.eval_offset(&mut section.stream)?)),
+                2 => {
+                    let tabidx = section.stream.read_unsigned()? as usize;
+                    Some((tabidx, self.eval_offset(&mut section.stream)?))
+                }
+                _ => None,
+            };
+            let is_expr = (flags & 4) != 0;
+            let ref_type = if (flags & 3) == 0 {
+                WasmValType::FuncRef
+            } else if is_expr {
+                WasmValType::ref_type_from_stream(&mut section.stream)?
+            } else {
+                match section.stream.read_byte()? {
+                    0 => WasmValType::FuncRef,
+                    _ => return Err(WasmDecodeErrorType::UnexpectedToken),
+                }
+            };
+
             let n_elements = section.stream.read_unsigned()? as usize;
-            let table = self
-                .module
-                .tables
-                .get_mut(tabidx)
-                .ok_or(WasmDecodeErrorType::InvalidParameter)?;
-            for i in offset..offset + n_elements {
-                let elem = section.stream.read_unsigned()? as usize;
-                table.table.get_mut(i).map(|v| *v = elem);
+            let mut items = Vec::with_capacity(n_elements);
+            for _ in 0..n_elements {
+                let item = if is_expr {
+                    let value = self.eval_expr(&mut section.stream)?;
+                    match (ref_type, value) {
+                        (WasmValType::FuncRef, WasmValue::FuncRef(v)) => v,
+                        (WasmValType::ExternRef, WasmValue::ExternRef(v)) => v,
+                        _ => return Err(WasmDecodeErrorType::TypeMismatch),
+                    }
+                } else {
+                    let index = section.stream.read_unsigned()? as usize;
+                    if index >= self.module.functions.len() {
+                        return Err(WasmDecodeErrorType::InvalidFunction);
+                    }
+                    Some(index)
+                };
+                items.push(item);
+            }
+
+            let element = match active {
+                Some((tabidx, offset)) => {
+                    let table = self
+                        .module
+                        .tables
+                        .get(tabidx)
+                        .ok_or(WasmDecodeErrorType::InvalidParameter)?;
+                    if table.ref_type() != ref_type {
+                        return Err(WasmDecodeErrorType::TypeMismatch);
+                    }
+                    table
+                        .init(offset, &items)
+                        .map_err(|_| WasmDecodeErrorType::InvalidTable)?;
+                    // Active segments are dropped once they are applied
+                    WasmElement::new(ref_type, Vec::new())
+                }
+                None if (flags & 3) == 3 => {
+                    // Declarative segments are only used for validation
+                    WasmElement::new(ref_type, Vec::new())
+                }
+                None => WasmElement::new(ref_type, items),
+            };
+            self.module.elements.push(element);
+        }
+        Ok(())
+    }
+
+    /// Checks the consistency of the whole module after all sections are loaded
+    fn validate(&self) -> Result<(), WasmDecodeErrorType> {
+        for func in &self.module.functions[self.module.n_ext_func..] {
+            if func.code_block.is_none() {
+                return Err(WasmDecodeErrorType::InvalidFunction);
             }
         }
         Ok(())
     }
 
     /// Parse "code" section
-    fn parse_sec_code(&mut self, mut section: WasmSection) -> Result<(), WasmDecodeErrorType> {
+    fn parse_sec_code(&mut self, mut section: WasmSection) -> Result<(), WasmDecodeError> {
         let n_items = section.stream.read_unsigned()? as usize;
+        if n_items != self.module.functions.len() - self.module.n_ext_func {
+            return Err(WasmDecodeErrorType::InvalidFunction.into());
+        }
         for i in 0..n_items {
             let index = i + self.module.n_ext_func;
             let module = &mut self.module;
-            let func_def = module
-                .functions
-                .get(index)
-                .ok_or(WasmDecodeErrorType::InvalidParameter)?;
-            let blob = section.stream.read_bytes().unwrap();
+            let func_def = &module.functions[index];
+            let blob = section
+                .stream
+                .read_bytes()
+                .map_err(|err| WasmDecodeError::new(err, Some(index), 0, None))?;
             let mut stream = Leb128Stream::from_slice(blob);
-            let body = WasmCodeBlock::generate(
+            let mut body = WasmCodeBlock::generate(
                 index,
                 &mut stream,
                 func_def.param_types(),
                 func_def.result_types(),
                 module,
             )?;
+            body.source = blob.into();
 
-            self.module.functions[index].code_block = Some(body);
+            self.module.functions[index].code_block = Some(Arc::new(body));
         }
         Ok(())
     }
 
     /// Parse "data" section
     fn parse_sec_data(&mut self, mut section: WasmSection) -> Result<(), WasmDecodeErrorType> {
-        let n_items = section.stream.read_unsigned()?;
+        let n_items = section.stream.read_unsigned()? as usize;
+        if let Some(data_count) = self.module.data_count {
+            if n_items != data_count {
+                return Err(WasmDecodeErrorType::InvalidParameter);
+            }
+        }
         for _ in 0..n_items {
-            let memidx = section.stream.read_unsigned()? as usize;
-            let offset = self.eval_offset(&mut section.stream)?;
-            let src = section.stream.read_bytes()?;
-            let memory = self
-                .module
-                .memories
-                .get_mut(memidx)
-                .ok_or(WasmDecodeErrorType::InvalidParameter)?;
-            memory.write_slice(offset, src).unwrap();
+            let data = match section.stream.read_unsigned()? {
+                0 => {
+                    let offset = self.eval_offset(&mut section.stream)?;
+                    let src = section.stream.read_bytes()?;
+                    self.write_active_data(0, offset, src)?;
+                    // Active segments are dropped once they are applied
+                    WasmData::active(0, offset, src.len())
+                }
+                1 => {
+                    let src = section.stream.read_bytes()?;
+                    WasmData::new(src.to_vec())
+                }
+                2 => {
+                    let memidx = section.stream.read_unsigned()? as usize;
+                    let offset = self.eval_offset(&mut section.stream)?;
+                    let src = section.stream.read_bytes()?;
+                    self.write_active_data(memidx, offset, src)?;
+                    WasmData::active(memidx, offset, src.len())
+                }
+                _ => return Err(WasmDecodeErrorType::UnexpectedToken),
+            };
+            self.module.data.push(data);
         }
         Ok(())
     }
 
+    fn write_active_data(
+        &mut self,
+        memidx: usize,
+        offset: usize,
+        src: &[u8],
+    ) -> Result<(), WasmDecodeErrorType> {
+        let memory = self
+            .module
+            .memories
+            .get_mut(memidx)
+            .ok_or(WasmDecodeErrorType::InvalidParameter)?;
+        memory.write_slice(offset, src).unwrap();
+        Ok(())
+    }
+
+    /// Parse "data count" section
+    fn parse_sec_data_count(
+        &mut self,
+        mut section: WasmSection,
+    ) -> Result<(), WasmDecodeErrorType> {
+        let count = section.stream.read_unsigned()? as usize;
+        self.module.data_count = Some(count);
+        Ok(())
+    }
+
     /// Parse "start" section
     fn parse_sec_start(&mut self, mut section: WasmSection) -> Result<(), WasmDecodeErrorType> {
+        if self.module.start.is_some() {
+            return Err(WasmDecodeErrorType::DuplicateSection);
+        }
         let index = section.stream.read_unsigned()? as usize;
+        let func = self
+            .module
+            .functions
+            .get(index)
+            .ok_or(WasmDecodeErrorType::InvalidFunction)?;
+        if !func.param_types().is_empty() || !func.result_types().is_empty() {
+            return Err(WasmDecodeErrorType::InvalidFunction);
+        }
         self.module.start = Some(index);
         Ok(())
     }
 
+    /// Parse "tag" section
+    fn parse_sec_tag(&mut self, mut section: WasmSection) -> Result<(), WasmDecodeErrorType> {
+        let n_items = section.stream.read_unsigned()? as usize;
+        for _ in 0..n_items {
+            // The attribute is always zero, which means an exception
+            if section.stream.read_byte()? != 0 {
+                return Err(WasmDecodeErrorType::UnexpectedToken);
+            }
+            let type_index = section.stream.read_unsigned()? as usize;
+            let func_type = self
+                .module
+                .types
+                .get(type_index)
+                .ok_or(WasmDecodeErrorType::InvalidType)?;
+            if !func_type.result_types().is_empty() {
+                return Err(WasmDecodeErrorType::TypeMismatch);
+            }
+            self.module.tags.push(type_index);
+        }
+        Ok(())
+    }
+
     /// Parse "global" section
     fn parse_sec_global(&mut self, mut section: WasmSection) -> Result<(), WasmDecodeErrorType> {
         let n_items = section.stream.read_unsigned()? as usize;
@@ -320,6 +665,57 @@ impl WasmLoader {
                         _ => Err(WasmDecodeErrorType::UnexpectedToken),
                     })
                 }),
+                Some(WasmOpcode::F32Const) => stream.read_f32().and_then(|r| {
+                    stream.read_byte().and_then(|v| match WasmOpcode::new(v) {
+                        Some(WasmOpcode::End) => Ok(WasmValue::F32(r)),
+                        _ => Err(WasmDecodeErrorType::UnexpectedToken),
+                    })
+                }),
+                Some(WasmOpcode::F64Const) => stream.read_f64().and_then(|r| {
+                    stream.read_byte().and_then(|v| match WasmOpcode::new(v) {
+                        Some(WasmOpcode::End) => Ok(WasmValue::F64(r)),
+                        _ => Err(WasmDecodeErrorType::UnexpectedToken),
+                    })
+                }),
+                Some(WasmOpcode::PrefixFD) => match stream.read_unsigned()? as u32 {
+                    v if WasmOpcodeFD::new(v) == Some(WasmOpcodeFD::V128Const) => {
+                        let r = stream.read_v128()?;
+                        stream.read_byte().and_then(|v| match WasmOpcode::new(v) {
+                            Some(WasmOpcode::End) => Ok(WasmValue::V128(r)),
+                            _ => Err(WasmDecodeErrorType::UnexpectedToken),
+                        })
+                    }
+                    _ => Err(WasmDecodeErrorType::UnexpectedToken),
+                },
+                Some(WasmOpcode::RefNull) => {
+                    let ref_type = WasmValType::ref_type_from_stream(stream)?;
+                    stream.read_byte().and_then(|v| match WasmOpcode::new(v) {
+                        Some(WasmOpcode::End) => Ok(WasmValue::default_for(ref_type)),
+                        _ => Err(WasmDecodeErrorType::UnexpectedToken),
+                    })
+                }
+                Some(WasmOpcode::RefFunc) => stream.read_unsigned().and_then(|r| {
+                    let index = r as usize;
+                    if index >= self.module.functions.len() {
+                        return Err(WasmDecodeErrorType::InvalidFunction);
+                    }
+                    stream.read_byte().and_then(|v| match WasmOpcode::new(v) {
+                        Some(WasmOpcode::End) => Ok(WasmValue::FuncRef(Some(index))),
+                        _ => Err(WasmDecodeErrorType::UnexpectedToken),
+                    })
+                }),
+                Some(WasmOpcode::GlobalGet) => stream.read_unsigned().and_then(|r| {
+                    let global = self
+                        .module
+                        .globals
+                        .get(r as usize)
+                        .ok_or(WasmDecodeErrorType::InvalidGlobal)?;
+                    let value = *global.value();
+                    stream.read_byte().and_then(|v| match WasmOpcode::new(v) {
+                        Some(WasmOpcode::End) => Ok(value),
+                        _ => Err(WasmDecodeErrorType::UnexpectedToken),
+                    })
+                }),
                 _ => Err(WasmDecodeErrorType::UnexpectedToken),
             })
     }
@@ -335,7 +731,19 @@ pub struct WasmModule {
     functions: Vec<WasmFunction>,
     start: Option<usize>,
     globals: Vec<WasmGlobal>,
+    tags: Vec<usize>,
+    elements: Vec<WasmElement>,
+    data: Vec<WasmData>,
+    data_count: Option<usize>,
     n_ext_func: usize,
+    fuel: Cell<Option<u64>>,
+    fuel_handler: Option<WasmFuelHandler>,
+    wait_handler: Option<WasmWaitHandler>,
+    notify_handler: Option<WasmNotifyHandler>,
+    grow_handler: Option<WasmGrowHandler>,
+    debug_handler: Cell<Option<WasmDebugHandler>>,
+    profiler: Option<Arc<WasmProfiler>>,
+    names: Option<WasmName>,
 }
 
 impl WasmModule {
@@ -350,7 +758,19 @@ impl WasmModule {
             functions: Vec::new(),
             start: None,
             globals: Vec::new(),
+            tags: Vec::new(),
+            elements: Vec::new(),
+            data: Vec::new(),
+            data_count: None,
             n_ext_func: 0,
+            fuel: Cell::new(None),
+            fuel_handler: None,
+            wait_handler: None,
+            notify_handler: None,
+            grow_handler: None,
+            debug_handler: Cell::new(None),
+            profiler: None,
+            names: None,
         }
     }
 
@@ -404,12 +824,25 @@ impl WasmModule {
         self.tables.as_mut_slice()
     }
 
+    #[inline]
+    pub fn table(&self, index: usize) -> Option<&WasmTable> {
+        self.tables.get(index)
+    }
+
     #[inline]
     pub fn elem_by_index(&self, index: usize) -> Option<&WasmFunction> {
+        self.elem_by_table(0, index)
+    }
+
+    /// Returns the function referenced by the element of the specified table
+    #[inline]
+    pub fn elem_by_table(&self, table_index: usize, index: usize) -> Option<&WasmFunction> {
         self.tables
-            .get(0)
-            .and_then(|v| v.table.get(index))
-            .and_then(|v| self.functions.get(*v))
+            .get(table_index)
+            .filter(|v| v.ref_type() == WasmValType::FuncRef)
+            .and_then(|v| v.elements().get(index).copied())
+            .flatten()
+            .and_then(|v| self.functions.get(v))
     }
 
     #[inline]
@@ -417,6 +850,18 @@ impl WasmModule {
         self.functions.as_slice()
     }
 
+    /// Returns the type indexes of the exception tags
+    #[inline]
+    pub fn tags(&self) -> &[usize] {
+        self.tags.as_slice()
+    }
+
+    /// Returns the parameter types of the exception tag
+    #[inline]
+    pub fn tag_type(&self, index: usize) -> Option<&WasmType> {
+        self.tags.get(index).and_then(|v| self.types.get(*v))
+    }
+
     #[inline]
     pub fn func_by_index(&self, index: usize) -> Result<WasmRunnable, WasmRuntimeErrorType> {
         self.functions
@@ -445,6 +890,90 @@ impl WasmModule {
         Err(WasmRuntimeErrorType::NoMethod)
     }
 
+    /// Compiles the functions of this module into native code.
+    ///
+    /// Functions that use instructions not supported by the JIT compiler keep running on the interpreter.
+    /// Returns the number of compiled functions.
+    #[cfg(target_arch = "x86_64")]
+    pub fn compile_jit(&mut self, allocator: &'static dyn WasmJitAllocator) -> usize {
+        let mut count = 0;
+        for function in self.functions.iter_mut() {
+            // The code shared with other instances is not compiled again
+            let code_block = match function.code_block.as_mut().and_then(Arc::get_mut) {
+                Some(v) => v,
+                None => continue,
+            };
+            if code_block.jit_code.is_some() {
+                count += 1;
+                continue;
+            }
+            code_block.jit_code = WasmJitCompiler::compile(code_block)
+                .and_then(|code| WasmJitCode::new(&code, allocator));
+            if code_block.jit_code.is_some() {
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// Creates another instance of this module for a new thread.
+    ///
+    /// The new instance shares the code, the profiler and the memories with this instance,
+    /// and starts with copies of the current globals, tables and segments.
+    /// Returns `None` if the module has a memory that is not shared, since it cannot be accessed by other threads.
+    pub fn instantiate_thread(&self) -> Option<Self> {
+        if self.memories.iter().any(|v| !v.is_shared()) {
+            return None;
+        }
+        Some(Self {
+            types: self.types.clone(),
+            imports: self.imports.clone(),
+            exports: self.exports.clone(),
+            memories: self.memories.iter().map(|v| v.share()).collect(),
+            tables: self.tables.iter().map(|v| v.duplicate()).collect(),
+            functions: self.functions.clone(),
+            start: self.start,
+            globals: self.globals.iter().map(|v| v.duplicate()).collect(),
+            tags: self.tags.clone(),
+            elements: self.elements.iter().map(|v| v.duplicate()).collect(),
+            data: self.data.iter().map(|v| v.duplicate()).collect(),
+            data_count: self.data_count,
+            n_ext_func: self.n_ext_func,
+            fuel: Cell::new(self.fuel.get()),
+            fuel_handler: self.fuel_handler,
+            wait_handler: self.wait_handler,
+            notify_handler: self.notify_handler,
+            grow_handler: self.grow_handler,
+            debug_handler: Cell::new(self.debug_handler.get()),
+            profiler: self.profiler.clone(),
+            names: self.names.clone(),
+        })
+    }
+
+    /// Get a reference to the exported memory with the specified name
+    pub fn exported_memory(&self, name: &str) -> Option<&WasmMemory> {
+        self.exports.iter().find_map(|export| match export.index {
+            WasmExportIndex::Memory(v) if export.name == name => self.memories.get(v),
+            _ => None,
+        })
+    }
+
+    /// Get a reference to the exported table with the specified name
+    pub fn exported_table(&self, name: &str) -> Option<&WasmTable> {
+        self.exports.iter().find_map(|export| match export.index {
+            WasmExportIndex::Table(v) if export.name == name => self.tables.get(v),
+            _ => None,
+        })
+    }
+
+    /// Get a reference to the exported global variable with the specified name
+    pub fn exported_global(&self, name: &str) -> Option<&WasmGlobal> {
+        self.exports.iter().find_map(|export| match export.index {
+            WasmExportIndex::Global(v) if export.name == name => self.globals.get(v),
+            _ => None,
+        })
+    }
+
     #[inline]
     pub fn globals(&self) -> &[WasmGlobal] {
         self.globals.as_slice()
@@ -454,6 +983,195 @@ impl WasmModule {
     pub fn global(&self, index: usize) -> Option<&WasmGlobal> {
         self.globals.get(index)
     }
+
+    /// Returns the contents of the "name" section, if any.
+    #[inline]
+    pub const fn names(&self) -> Option<&WasmName> {
+        self.names.as_ref()
+    }
+
+    /// Returns the name of the function with the specified index.
+    ///
+    /// The "name" section is preferred, otherwise the exported name is used.
+    pub fn func_name(&self, index: usize) -> Option<&str> {
+        self.names
+            .as_ref()
+            .and_then(|v| v.func_by_index(index))
+            .or_else(|| {
+                self.exports.iter().find_map(|export| match export.index {
+                    WasmExportIndex::Function(v) if v == index => Some(export.name()),
+                    _ => None,
+                })
+            })
+    }
+
+    /// Returns the remaining fuel, or `None` if the fuel is not metered.
+    #[inline]
+    pub fn fuel(&self) -> Option<u64> {
+        self.fuel.get()
+    }
+
+    /// Sets the fuel, or `None` to disable metering.
+    ///
+    /// One unit of fuel is charged for each backward branch and each function call.
+    /// When it runs out, the fuel handler is called if it exists,
+    /// otherwise the execution is aborted with `WasmRuntimeErrorType::OutOfFuel`.
+    #[inline]
+    pub fn set_fuel(&self, fuel: Option<u64>) {
+        self.fuel.set(fuel);
+    }
+
+    /// Adds fuel to the remaining fuel, and enables metering if it was disabled.
+    #[inline]
+    pub fn add_fuel(&self, fuel: u64) {
+        self.fuel
+            .set(Some(self.fuel.get().unwrap_or(0).saturating_add(fuel)));
+    }
+
+    /// Sets the handler that is called when the fuel runs out.
+    ///
+    /// The handler can refill the fuel to resume the execution, or return an error to abort it.
+    #[inline]
+    pub fn set_fuel_handler(&mut self, handler: Option<WasmFuelHandler>) {
+        self.fuel_handler = handler;
+    }
+
+    /// Charges one unit of fuel.
+    #[inline]
+    pub(crate) fn consume_fuel(&self) -> Result<(), WasmRuntimeErrorType> {
+        match self.fuel.get() {
+            None => Ok(()),
+            Some(0) => {
+                let handler = self.fuel_handler.ok_or(WasmRuntimeErrorType::OutOfFuel)?;
+                match handler(self)? {
+                    0 => Err(WasmRuntimeErrorType::OutOfFuel),
+                    fuel => {
+                        self.fuel.set(Some(fuel - 1));
+                        Ok(())
+                    }
+                }
+            }
+            Some(fuel) => {
+                self.fuel.set(Some(fuel - 1));
+                Ok(())
+            }
+        }
+    }
+
+    /// Sets the handlers of `memory.atomic.wait` and `memory.atomic.notify` on shared memories.
+    ///
+    /// Without them, nobody is woken by `memory.atomic.notify`,
+    /// and `memory.atomic.wait` returns "timed-out" if the timeout is specified or traps otherwise.
+    #[inline]
+    pub fn set_atomic_handlers(
+        &mut self,
+        wait: Option<WasmWaitHandler>,
+        notify: Option<WasmNotifyHandler>,
+    ) {
+        self.wait_handler = wait;
+        self.notify_handler = notify;
+    }
+
+    /// Sets the handler that decides whether `memory.grow` is allowed, in addition to the maximum of the memory.
+    ///
+    /// Without it, the memory can grow up to its maximum as long as the allocation succeeds.
+    #[inline]
+    pub fn set_grow_handler(&mut self, handler: Option<WasmGrowHandler>) {
+        self.grow_handler = handler;
+    }
+
+    /// Grows the memory with the specified index as `memory.grow` does, asking the grow handler if it is set.
+    ///
+    /// The host should use this instead of [WasmMemory::grow] so that the same policy is applied.
+    pub fn grow_memory(&self, index: usize, delta: i32) -> i32 {
+        let memory = match self.memories.get(index) {
+            Some(v) => v,
+            None => return -1,
+        };
+        if delta > 0 {
+            if let Some(handler) = self.grow_handler {
+                let current = memory.size() as u32;
+                let requested = current.saturating_add(delta as u32);
+                if !handler(self, current, requested) {
+                    return -1;
+                }
+            }
+        }
+        memory.grow(delta)
+    }
+
+    /// Returns the debug handler if it is set.
+    #[inline]
+    pub fn debug_handler(&self) -> Option<WasmDebugHandler> {
+        self.debug_handler.get()
+    }
+
+    /// Sets the handler that is called before each instruction, or `None` to remove it.
+    ///
+    /// It can be changed while the module is running, even from the handler itself.
+    /// Functions compiled by the JIT don't call the handler,
+    /// so the interpreter is used instead while it is set, except for functions already running in the native code.
+    #[inline]
+    pub fn set_debug_handler(&self, handler: Option<WasmDebugHandler>) {
+        self.debug_handler.set(handler);
+    }
+
+    /// Returns the profiler if it is enabled.
+    #[inline]
+    pub fn profiler(&self) -> Option<&WasmProfiler> {
+        self.profiler.as_deref()
+    }
+
+    /// Enables the profiler with the clock to measure the time, or without it to count only.
+    ///
+    /// Functions compiled by the JIT are not profiled, so the interpreter is used instead while it is enabled.
+    #[inline]
+    pub fn enable_profiler(&mut self, clock: Option<WasmProfileClock>) {
+        self.profiler = Some(Arc::new(WasmProfiler::new(self.functions.len(), clock)));
+    }
+
+    /// memory.atomic.wait
+    pub(crate) fn atomic_wait(&self, wait: &WasmAtomicWait) -> Result<u32, WasmRuntimeErrorType> {
+        if !wait.is_expected() {
+            return Ok(1);
+        }
+        match self.wait_handler {
+            Some(handler) => handler(self, wait),
+            None => match wait.timeout() {
+                Some(_) => Ok(2),
+                None => Err(WasmRuntimeErrorType::NotSupprted),
+            },
+        }
+    }
+
+    /// memory.atomic.notify
+    #[inline]
+    pub(crate) fn atomic_notify(
+        &self,
+        address: usize,
+        count: u32,
+    ) -> Result<u32, WasmRuntimeErrorType> {
+        match self.notify_handler {
+            Some(handler) => handler(self, address, count),
+            None => Ok(0),
+        }
+    }
+
+    /// Returns the number of data segments declared in the "data count" section
+    #[inline]
+    pub const fn data_count(&self) -> Option<usize> {
+        self.data_count
+    }
+
+    #[inline]
+    pub fn data(&self, index: usize) -> Option<&WasmData> {
+        self.data.get(index)
+    }
+
+    #[inline]
+    pub fn element(&self, index: usize) -> Option<&WasmElement> {
+        self.elements.get(index)
+    }
 }
 
 /// Stream encoded with LEB128
@@ -580,6 +1298,24 @@ impl Leb128Stream<'_> {
         }
     }
 
+    /// Reads a 32-bit floating point number from a stream
+    #[inline]
+    pub fn read_f32(&mut self) -> Result<f32, WasmDecodeErrorType> {
+        self.get_bytes(4).map(|v| f32::from_bits(LE::read_u32(v)))
+    }
+
+    /// Reads a 64-bit floating point number from a stream
+    #[inline]
+    pub fn read_f64(&mut self) -> Result<f64, WasmDecodeErrorType> {
+        self.get_bytes(8).map(|v| f64::from_bits(LE::read_u64(v)))
+    }
+
+    /// Reads the 128-bit immediate of `v128.const` and `i8x16.shuffle`
+    #[inline]
+    pub fn read_v128(&mut self) -> Result<u128, WasmDecodeErrorType> {
+        self.get_bytes(16).map(LE::read_u128)
+    }
+
     /// Reads the UTF-8 encoded string from the stream
     #[inline]
     pub fn get_string(&mut self) -> Result<&str, WasmDecodeErrorType> {
@@ -632,6 +1368,26 @@ impl WasmMemArg {
     pub const fn offset_by(&self, base: u32) -> usize {
         (self.offset as u64 + base as u64) as usize
     }
+
+    /// Checks that the alignment does not exceed the natural alignment (in log2)
+    #[inline]
+    pub const fn check_alignment(self, natural: u32) -> Result<Self, WasmDecodeErrorType> {
+        if self.align <= natural {
+            Ok(self)
+        } else {
+            Err(WasmDecodeErrorType::InvalidAlignment)
+        }
+    }
+
+    /// Checks that the alignment is exactly the natural alignment, as required by the atomic instructions
+    #[inline]
+    pub const fn check_atomic_alignment(self, natural: u32) -> Result<Self, WasmDecodeErrorType> {
+        if self.align == natural {
+            Ok(self)
+        } else {
+            Err(WasmDecodeErrorType::InvalidAlignment)
+        }
+    }
 }
 
 /// WebAssembly section stream
@@ -656,6 +1412,8 @@ enum WasmSectionType {
     Element,
     Code,
     Data,
+    DataCount,
+    Tag,
 }
 
 impl From<u8> for WasmSectionType {
@@ -673,6 +1431,8 @@ impl From<u8> for WasmSectionType {
             9 => WasmSectionType::Element,
             10 => WasmSectionType::Code,
             11 => WasmSectionType::Data,
+            12 => WasmSectionType::DataCount,
+            13 => WasmSectionType::Tag,
             _ => WasmSectionType::Custom,
         }
     }
@@ -686,22 +1446,47 @@ pub enum WasmValType {
     I64 = 0x7E,
     F32 = 0x7D,
     F64 = 0x7C,
+    /// A 128-bit vector of packed integer or floating-point data
+    V128 = 0x7B,
+    /// A reference to a function
+    FuncRef = 0x70,
+    /// An opaque reference to a host object
+    ExternRef = 0x6F,
 }
 
 impl WasmValType {
-    const fn from_u64(v: u64) -> Result<Self, WasmDecodeErrorType> {
+    pub(crate) const fn from_u64(v: u64) -> Result<Self, WasmDecodeErrorType> {
         match v {
             0x7F => Ok(WasmValType::I32),
             0x7E => Ok(WasmValType::I64),
             0x7D => Ok(WasmValType::F32),
             0x7C => Ok(WasmValType::F64),
+            0x7B => Ok(WasmValType::V128),
+            0x70 => Ok(WasmValType::FuncRef),
+            0x6F => Ok(WasmValType::ExternRef),
             _ => Err(WasmDecodeErrorType::UnexpectedToken),
         }
     }
-}
 
-impl fmt::Display for WasmValType {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    /// Reads a reference type, which is the element type of tables
+    pub(crate) fn ref_type_from_stream(
+        stream: &mut Leb128Stream,
+    ) -> Result<Self, WasmDecodeErrorType> {
+        match stream.read_byte().and_then(|v| Self::from_u64(v as u64))? {
+            v if v.is_ref() => Ok(v),
+            _ => Err(WasmDecodeErrorType::UnexpectedToken),
+        }
+    }
+
+    /// Returns whether this type is a reference type
+    #[inline]
+    pub const fn is_ref(&self) -> bool {
+        matches!(*self, WasmValType::FuncRef | WasmValType::ExternRef)
+    }
+}
+
+impl fmt::Display for WasmValType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
             "{}",
@@ -710,20 +1495,27 @@ impl fmt::Display for WasmValType {
                 WasmValType::I64 => "i64",
                 WasmValType::F32 => "f32",
                 WasmValType::F64 => "f64",
+                WasmValType::V128 => "v128",
+                WasmValType::FuncRef => "funcref",
+                WasmValType::ExternRef => "externref",
             }
         )
     }
 }
 
 /// WebAssembly block types
-#[repr(isize)]
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum WasmBlockType {
-    Empty = -64,
-    I32 = -1,
-    I64 = -2,
-    F32 = -3,
-    F64 = -4,
+    Empty,
+    I32,
+    I64,
+    F32,
+    F64,
+    V128,
+    FuncRef,
+    ExternRef,
+    /// A function type in the "type" section, which may have multiple params and results.
+    Type(usize),
 }
 
 impl WasmBlockType {
@@ -734,17 +1526,32 @@ impl WasmBlockType {
             -2 => Ok(Self::I64),
             -3 => Ok(Self::F32),
             -4 => Ok(Self::F64),
+            -5 => Ok(Self::V128),
+            -16 => Ok(Self::FuncRef),
+            -17 => Ok(Self::ExternRef),
+            _ if v >= 0 => Ok(Self::Type(v as usize)),
             _ => Err(WasmDecodeErrorType::InvalidParameter),
         }
     }
 
-    pub const fn into_type(self) -> Option<WasmValType> {
-        match self {
-            WasmBlockType::Empty => None,
-            WasmBlockType::I32 => Some(WasmValType::I32),
-            WasmBlockType::I64 => Some(WasmValType::I64),
-            WasmBlockType::F32 => Some(WasmValType::F32),
-            WasmBlockType::F64 => Some(WasmValType::F64),
+    /// Returns the types of the params and results of this block.
+    pub fn signature<'a>(
+        &self,
+        module: &'a WasmModule,
+    ) -> Result<(&'a [WasmValType], &'a [WasmValType]), WasmDecodeErrorType> {
+        match *self {
+            WasmBlockType::Empty => Ok((&[], &[])),
+            WasmBlockType::I32 => Ok((&[], &[WasmValType::I32])),
+            WasmBlockType::I64 => Ok((&[], &[WasmValType::I64])),
+            WasmBlockType::F32 => Ok((&[], &[WasmValType::F32])),
+            WasmBlockType::F64 => Ok((&[], &[WasmValType::F64])),
+            WasmBlockType::V128 => Ok((&[], &[WasmValType::V128])),
+            WasmBlockType::FuncRef => Ok((&[], &[WasmValType::FuncRef])),
+            WasmBlockType::ExternRef => Ok((&[], &[WasmValType::ExternRef])),
+            WasmBlockType::Type(index) => module
+                .type_by_ref(index)
+                .map(|v| (v.param_types(), v.result_types()))
+                .ok_or(WasmDecodeErrorType::InvalidType),
         }
     }
 }
@@ -754,41 +1561,197 @@ impl WasmBlockType {
 pub struct WasmLimit {
     min: u32,
     max: u32,
+    shared: bool,
 }
 
 impl WasmLimit {
+    #[inline]
+    pub const fn new(min: u32, max: u32) -> Self {
+        Self {
+            min,
+            max,
+            shared: false,
+        }
+    }
+
+    /// Creates a limit of a shared memory, which always has the maximum.
+    #[inline]
+    pub const fn new_shared(min: u32, max: u32) -> Self {
+        Self {
+            min,
+            max,
+            shared: true,
+        }
+    }
+
     #[inline]
     fn from_stream(stream: &mut Leb128Stream) -> Result<Self, WasmDecodeErrorType> {
         match stream.read_unsigned() {
-            Ok(0) => stream.read_unsigned().map(|min| Self {
-                min: min as u32,
-                max: min as u32,
-            }),
+            Ok(0) => stream
+                .read_unsigned()
+                .map(|min| Self::new(min as u32, u32::MAX)),
             Ok(1) => {
                 let min = stream.read_unsigned()? as u32;
                 let max = stream.read_unsigned()? as u32;
-                Ok(Self { min, max })
+                Ok(Self::new(min, max))
+            }
+            Ok(3) => {
+                let min = stream.read_unsigned()? as u32;
+                let max = stream.read_unsigned()? as u32;
+                Ok(Self::new_shared(min, max))
             }
             Err(err) => Err(err),
             _ => Err(WasmDecodeErrorType::UnexpectedToken),
         }
     }
 
+    /// Reads the limit of a table, which cannot be shared
+    #[inline]
+    fn table_from_stream(stream: &mut Leb128Stream) -> Result<Self, WasmDecodeErrorType> {
+        match Self::from_stream(stream)? {
+            v if v.is_shared() => Err(WasmDecodeErrorType::UnexpectedToken),
+            v => Ok(v),
+        }
+    }
+
     #[inline]
     pub const fn min(&self) -> u32 {
         self.min
     }
 
+    /// Returns the maximum, which is `u32::MAX` if it is not specified.
     #[inline]
     pub const fn max(&self) -> u32 {
         self.max
     }
+
+    #[inline]
+    pub const fn is_shared(&self) -> bool {
+        self.shared
+    }
 }
 
 /// WebAssembly memory object
+///
+/// The contents are reference counted, so that the host, another module or another thread can share it via [WasmMemory::share].
+///
+/// A shared memory reserves its maximum size in advance,
+/// so that the contents never move while other threads are accessing them.
 pub struct WasmMemory {
     limit: WasmLimit,
-    data: UnsafeCell<Vec<u8>>,
+    data: Arc<WasmMemoryData>,
+}
+
+/// The contents of a memory
+///
+/// The buffer is aligned to [WasmMemoryData::ALIGN],
+/// so that the naturally aligned addresses in the memory are also aligned in the host.
+/// Only a memory that is not shared moves its buffer when it grows, and it is used by one thread at a time.
+struct WasmMemoryData {
+    base: AtomicPtr<u8>,
+    size: AtomicUsize,
+    capacity: AtomicUsize,
+}
+
+impl WasmMemoryData {
+    const ALIGN: usize = 16;
+
+    /// Allocates the buffer of `size` bytes, reserving `capacity` bytes if possible.
+    fn new(size: usize, capacity: usize) -> Self {
+        let (base, capacity) = match Self::alloc(capacity) {
+            Some(base) => (base, capacity),
+            None => match Self::alloc(size) {
+                Some(base) => (base, size),
+                None => alloc::alloc::handle_alloc_error(Self::layout(size)),
+            },
+        };
+        Self {
+            base: AtomicPtr::new(base),
+            size: AtomicUsize::new(size),
+            capacity: AtomicUsize::new(capacity),
+        }
+    }
+
+    #[inline]
+    fn layout(capacity: usize) -> Layout {
+        Layout::from_size_align(capacity, Self::ALIGN).unwrap()
+    }
+
+    /// Allocates the zero-filled buffer, an empty buffer is not allocated.
+    fn alloc(capacity: usize) -> Option<*mut u8> {
+        if capacity == 0 {
+            return Some(Self::ALIGN as *mut u8);
+        }
+        let layout = Layout::from_size_align(capacity, Self::ALIGN).ok()?;
+        let ptr = unsafe { alloc::alloc::alloc_zeroed(layout) };
+        if ptr.is_null() {
+            None
+        } else {
+            Some(ptr)
+        }
+    }
+
+    #[inline]
+    fn base(&self) -> *mut u8 {
+        self.base.load(Ordering::Relaxed)
+    }
+
+    #[inline]
+    fn size(&self) -> usize {
+        self.size.load(Ordering::Acquire)
+    }
+
+    #[inline]
+    fn capacity(&self) -> usize {
+        self.capacity.load(Ordering::Relaxed)
+    }
+
+    /// Extends the size within the capacity, returns the previous size.
+    fn grow_within(&self, additional: usize) -> Option<usize> {
+        let capacity = self.capacity.load(Ordering::Relaxed);
+        self.size
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |size| {
+                size.checked_add(additional).filter(|v| *v <= capacity)
+            })
+            .ok()
+    }
+
+    /// Reallocates the buffer to extend the size, returns the previous size.
+    ///
+    /// The caller must ensure that no other thread is accessing the buffer.
+    unsafe fn grow_realloc(&self, additional: usize) -> Option<usize> {
+        let old_size = self.size();
+        let new_size = old_size.checked_add(additional)?;
+        let capacity = self.capacity.load(Ordering::Relaxed);
+        if new_size > capacity {
+            Layout::from_size_align(new_size, Self::ALIGN).ok()?;
+            let base = if capacity == 0 {
+                Self::alloc(new_size)?
+            } else {
+                let ptr = alloc::alloc::realloc(self.base(), Self::layout(capacity), new_size);
+                if ptr.is_null() {
+                    return None;
+                }
+                ptr.add(capacity).write_bytes(0, new_size - capacity);
+                ptr
+            };
+            self.base.store(base, Ordering::Relaxed);
+            self.capacity.store(new_size, Ordering::Relaxed);
+        }
+        self.size.store(new_size, Ordering::Release);
+        Some(old_size)
+    }
+}
+
+impl Drop for WasmMemoryData {
+    fn drop(&mut self) {
+        let capacity = *self.capacity.get_mut();
+        if capacity > 0 {
+            unsafe {
+                alloc::alloc::dealloc(*self.base.get_mut(), Self::layout(capacity));
+            }
+        }
+    }
 }
 
 impl WasmMemory {
@@ -796,14 +1759,38 @@ impl WasmMemory {
     /// which is defined to be the constant 65536 – abbreviated 64Ki.
     pub const PAGE_SIZE: usize = 65536;
 
+    /// The maximum number of pages in the 32-bit address space
+    pub const MAX_PAGES: usize = 0x10000;
+
     #[inline]
     pub fn new(limit: WasmLimit) -> Self {
+        Self::with_quota(limit, None)
+    }
+
+    /// Creates a memory, of which a shared one reserves no more than `quota` bytes to grow.
+    pub fn with_quota(limit: WasmLimit, quota: Option<usize>) -> Self {
         let size = limit.min as usize * Self::PAGE_SIZE;
-        let mut data = Vec::with_capacity(size);
-        data.resize(size, 0);
+        let capacity = if limit.is_shared() {
+            let max = usize::min(limit.max as usize, Self::MAX_PAGES) * Self::PAGE_SIZE;
+            match quota {
+                Some(quota) => usize::max(size, usize::min(max, quota & !(Self::PAGE_SIZE - 1))),
+                None => max,
+            }
+        } else {
+            size
+        };
         Self {
             limit,
-            data: UnsafeCell::new(data),
+            data: Arc::new(WasmMemoryData::new(size, capacity)),
+        }
+    }
+
+    /// Returns a new handle that refers to the same memory
+    #[inline]
+    pub fn share(&self) -> Self {
+        Self {
+            limit: self.limit,
+            data: self.data.clone(),
         }
     }
 
@@ -812,38 +1799,63 @@ impl WasmMemory {
         self.limit
     }
 
+    #[inline]
+    pub const fn is_shared(&self) -> bool {
+        self.limit.is_shared()
+    }
+
     #[inline]
     fn memory(&self) -> &[u8] {
-        unsafe { &*self.data.get() }
+        unsafe { slice::from_raw_parts(self.data.base(), self.data.size()) }
     }
 
     #[inline]
+    #[allow(clippy::mut_from_ref)]
     fn memory_mut(&self) -> &mut [u8] {
-        unsafe { &mut *self.data.get() }
+        unsafe { slice::from_raw_parts_mut(self.data.base(), self.data.size()) }
+    }
+
+    /// Returns the base address and the size of the memory for the native code.
+    #[cfg(target_arch = "x86_64")]
+    #[inline]
+    pub(crate) fn raw_parts(&self) -> (*mut u8, usize) {
+        (self.data.base(), self.data.size())
+    }
+
+    /// Returns the bytes allocated for the memory, which a shared memory reserves to grow.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.data.capacity()
     }
 
     /// memory.size
     #[inline]
     pub fn size(&self) -> i32 {
-        let memory = self.memory();
-        (memory.len() / Self::PAGE_SIZE) as i32
+        (self.data.size() / Self::PAGE_SIZE) as i32
     }
 
     /// memory.grow
+    ///
+    /// Returns the previous number of pages, or -1 if it would exceed the maximum or the allocation fails.
+    /// A shared memory cannot grow beyond the size reserved when it was created.
     pub fn grow(&self, delta: i32) -> i32 {
-        let memory = unsafe { &mut *self.data.get() };
-        let old_size = memory.len();
-        if delta > 0 {
-            let additional = delta as usize * Self::PAGE_SIZE;
-            if memory.try_reserve_exact(additional).is_err() {
-                return -1;
-            }
-            memory.resize(old_size + additional, 0);
-            (old_size / Self::PAGE_SIZE) as i32
-        } else if delta == 0 {
-            (old_size / Self::PAGE_SIZE) as i32
+        if delta < 0 {
+            return -1;
+        }
+        let max_pages = usize::min(self.limit.max as usize, Self::MAX_PAGES);
+        let additional = delta as usize * Self::PAGE_SIZE;
+        let old_size = if self.is_shared() {
+            // The capacity is limited to the maximum
+            self.data.grow_within(additional)
+        } else if self.data.size() / Self::PAGE_SIZE + delta as usize > max_pages {
+            None
         } else {
-            -1
+            // A memory that is not shared is never accessed by other threads
+            unsafe { self.data.grow_realloc(additional) }
+        };
+        match old_size {
+            Some(v) => (v / Self::PAGE_SIZE) as i32,
+            None => -1,
         }
     }
 
@@ -917,6 +1929,43 @@ impl WasmMemory {
         }
     }
 
+    /// memory.fill
+    pub fn fill(&self, offset: usize, val: u8, count: usize) -> Result<(), WasmRuntimeErrorType> {
+        let memory = self.memory_mut();
+        match offset.checked_add(count) {
+            Some(end) if end <= memory.len() => {
+                memory[offset..end].fill(val);
+                Ok(())
+            }
+            _ => Err(WasmRuntimeErrorType::OutOfBounds),
+        }
+    }
+
+    /// memory.copy
+    pub fn copy(&self, dest: usize, src: usize, count: usize) -> Result<(), WasmRuntimeErrorType> {
+        let memory = self.memory_mut();
+        let limit = memory.len();
+        match (dest.checked_add(count), src.checked_add(count)) {
+            (Some(dest_end), Some(src_end)) if dest_end <= limit && src_end <= limit => {
+                memory.copy_within(src..src_end, dest);
+                Ok(())
+            }
+            _ => Err(WasmRuntimeErrorType::OutOfBounds),
+        }
+    }
+
+    /// memory.init
+    pub fn init(&self, dest: usize, src: &[u8]) -> Result<(), WasmRuntimeErrorType> {
+        let memory = self.memory_mut();
+        match dest.checked_add(src.len()) {
+            Some(end) if end <= memory.len() => {
+                memory[dest..end].copy_from_slice(src);
+                Ok(())
+            }
+            _ => Err(WasmRuntimeErrorType::OutOfBounds),
+        }
+    }
+
     #[inline]
     pub fn read_u8(&self, offset: usize) -> Result<u8, WasmRuntimeErrorType> {
         let slice = self.memory();
@@ -1003,127 +2052,473 @@ impl WasmMemory {
             Err(WasmRuntimeErrorType::OutOfBounds)
         }
     }
+
+    #[inline]
+    pub fn read_u128(&self, offset: usize) -> Result<u128, WasmRuntimeErrorType> {
+        let slice = self.memory();
+        let limit = slice.len();
+        if offset + 15 < limit {
+            Ok(LE::read_u128(&slice[offset..offset + 16]))
+        } else {
+            Err(WasmRuntimeErrorType::OutOfBounds)
+        }
+    }
+
+    #[inline]
+    pub fn write_u128(&self, offset: usize, val: u128) -> Result<(), WasmRuntimeErrorType> {
+        let slice = self.memory_mut();
+        let limit = slice.len();
+        if offset + 15 < limit {
+            LE::write_u128(&mut slice[offset..offset + 16], val);
+            Ok(())
+        } else {
+            Err(WasmRuntimeErrorType::OutOfBounds)
+        }
+    }
+
+    /// Returns the atomic object at the specified offset, which must be naturally aligned.
+    ///
+    /// `T` is one of `AtomicU8`, `AtomicU16`, `AtomicU32` or `AtomicU64`.
+    #[inline]
+    pub(crate) fn atomic<T>(&self, offset: usize) -> Result<&T, WasmRuntimeErrorType> {
+        let size = mem::size_of::<T>();
+        let memory = self.memory();
+        if offset >= memory.len() || memory.len() - offset < size {
+            return Err(WasmRuntimeErrorType::OutOfBounds);
+        }
+        if offset & (size - 1) != 0 {
+            return Err(WasmRuntimeErrorType::UnalignedAtomic);
+        }
+        // The buffer is aligned, so the naturally aligned offset is also aligned in the host
+        Ok(unsafe { &*(memory.as_ptr().add(offset) as *const T) })
+    }
+
+    /// Loads the value of the width in bytes atomically, the value is zero-extended.
+    pub(crate) fn atomic_load(
+        &self,
+        offset: usize,
+        width: usize,
+    ) -> Result<u64, WasmRuntimeErrorType> {
+        let order = Ordering::SeqCst;
+        Ok(match width {
+            1 => self.atomic::<AtomicU8>(offset)?.load(order) as u64,
+            2 => self.atomic::<AtomicU16>(offset)?.load(order) as u64,
+            4 => self.atomic::<AtomicU32>(offset)?.load(order) as u64,
+            _ => self.atomic::<AtomicU64>(offset)?.load(order),
+        })
+    }
+
+    /// Stores the value of the width in bytes atomically, the value is wrapped.
+    pub(crate) fn atomic_store(
+        &self,
+        offset: usize,
+        width: usize,
+        val: u64,
+    ) -> Result<(), WasmRuntimeErrorType> {
+        let order = Ordering::SeqCst;
+        match width {
+            1 => self.atomic::<AtomicU8>(offset)?.store(val as u8, order),
+            2 => self.atomic::<AtomicU16>(offset)?.store(val as u16, order),
+            4 => self.atomic::<AtomicU32>(offset)?.store(val as u32, order),
+            _ => self.atomic::<AtomicU64>(offset)?.store(val, order),
+        }
+        Ok(())
+    }
+
+    /// Performs the read-modify-write operation atomically, returns the old value.
+    pub(crate) fn atomic_rmw(
+        &self,
+        offset: usize,
+        width: usize,
+        op: WasmAtomicRmwOp,
+        val: u64,
+    ) -> Result<u64, WasmRuntimeErrorType> {
+        let order = Ordering::SeqCst;
+        Ok(match width {
+            1 => self
+                .atomic::<AtomicU8>(offset)?
+                .fetch_update(order, order, |v| Some(op.apply(v as u64, val) as u8))
+                .unwrap_or_else(|v| v) as u64,
+            2 => self
+                .atomic::<AtomicU16>(offset)?
+                .fetch_update(order, order, |v| Some(op.apply(v as u64, val) as u16))
+                .unwrap_or_else(|v| v) as u64,
+            4 => self
+                .atomic::<AtomicU32>(offset)?
+                .fetch_update(order, order, |v| Some(op.apply(v as u64, val) as u32))
+                .unwrap_or_else(|v| v) as u64,
+            _ => self
+                .atomic::<AtomicU64>(offset)?
+                .fetch_update(order, order, |v| Some(op.apply(v, val)))
+                .unwrap_or_else(|v| v),
+        })
+    }
+
+    /// Replaces the value if it equals the expected value atomically, returns the old value.
+    ///
+    /// The expected value and the replacement are wrapped to the width.
+    pub(crate) fn atomic_cmpxchg(
+        &self,
+        offset: usize,
+        width: usize,
+        expected: u64,
+        replacement: u64,
+    ) -> Result<u64, WasmRuntimeErrorType> {
+        let order = Ordering::SeqCst;
+        Ok(match width {
+            1 => self
+                .atomic::<AtomicU8>(offset)?
+                .compare_exchange(expected as u8, replacement as u8, order, order)
+                .unwrap_or_else(|v| v) as u64,
+            2 => self
+                .atomic::<AtomicU16>(offset)?
+                .compare_exchange(expected as u16, replacement as u16, order, order)
+                .unwrap_or_else(|v| v) as u64,
+            4 => self
+                .atomic::<AtomicU32>(offset)?
+                .compare_exchange(expected as u32, replacement as u32, order, order)
+                .unwrap_or_else(|v| v) as u64,
+            _ => self
+                .atomic::<AtomicU64>(offset)?
+                .compare_exchange(expected, replacement, order, order)
+                .unwrap_or_else(|v| v),
+        })
+    }
 }
 
-/// WebAssembly table object
-pub struct WasmTable {
-    limit: WasmLimit,
-    table: Vec<usize>,
+/// The parameters of `memory.atomic.wait32` and `memory.atomic.wait64`
+pub struct WasmAtomicWait<'a> {
+    memory: &'a WasmMemory,
+    offset: usize,
+    expected: u64,
+    is_64bit: bool,
+    timeout: Option<Duration>,
 }
 
-impl WasmTable {
+impl<'a> WasmAtomicWait<'a> {
     #[inline]
-    fn from_stream(stream: &mut Leb128Stream) -> Result<Self, WasmDecodeErrorType> {
-        match stream.read_unsigned() {
-            Ok(0x70) => (),
-            Err(err) => return Err(err),
-            _ => return Err(WasmDecodeErrorType::UnexpectedToken),
-        };
-        WasmLimit::from_stream(stream).map(|limit| {
-            let size = limit.min as usize;
-            let mut table = Vec::with_capacity(size);
-            table.resize(size, 0);
-            Self { limit, table }
-        })
+    pub(crate) const fn new(
+        memory: &'a WasmMemory,
+        offset: usize,
+        expected: u64,
+        is_64bit: bool,
+        timeout: Option<Duration>,
+    ) -> Self {
+        Self {
+            memory,
+            offset,
+            expected,
+            is_64bit,
+            timeout,
+        }
     }
 
+    /// Returns the host address of the value,
+    /// which identifies the same location even if the memory is shared by other modules.
     #[inline]
-    pub const fn limit(&self) -> WasmLimit {
-        self.limit
+    pub fn address(&self) -> usize {
+        self.memory.memory().as_ptr() as usize + self.offset
+    }
+
+    /// Returns the timeout, or `None` to wait forever.
+    #[inline]
+    pub const fn timeout(&self) -> Option<Duration> {
+        self.timeout
     }
 
+    /// Loads the value atomically and returns whether it is the expected value.
+    ///
+    /// The handler has to check it again while holding its queue of waiters, so that no notification is lost.
     #[inline]
-    pub fn table(&mut self) -> &mut [usize] {
-        self.table.as_mut_slice()
+    pub fn is_expected(&self) -> bool {
+        if self.is_64bit {
+            self.memory
+                .atomic::<AtomicU64>(self.offset)
+                .map(|v| v.load(Ordering::SeqCst) == self.expected)
+                .unwrap_or(false)
+        } else {
+            self.memory
+                .atomic::<AtomicU32>(self.offset)
+                .map(|v| v.load(Ordering::SeqCst) == self.expected as u32)
+                .unwrap_or(false)
+        }
     }
 }
 
-/// A type that represents the type of WebAssembly function.
-///
-/// There are two types of functions in WebAssembly: those that are imported from external modules and those that have bytecode in the same module.
+/// WebAssembly table object
 ///
-/// It appears as the third section (`0x03`) in the WebAssembly binary.
-pub struct WasmFunction {
-    index: usize,
-    type_index: usize,
-    func_type: WasmType,
-    origin: WasmFunctionOrigin,
-    code_block: Option<WasmCodeBlock>,
-    dlink: Option<WasmDynFunc>,
+/// Each element is a reference of the element type of the table, or `None` for the null reference.
+/// The elements of a `funcref` table are function indexes of the module.
+pub struct WasmTable {
+    ref_type: WasmValType,
+    limit: WasmLimit,
+    table: Rc<UnsafeCell<Vec<Option<usize>>>>,
 }
 
-impl WasmFunction {
+impl WasmTable {
     #[inline]
-    fn from_import(
-        type_index: usize,
-        func_type: &WasmType,
-        index: usize,
-        dlink: WasmDynFunc,
-    ) -> Self {
+    fn from_stream(stream: &mut Leb128Stream) -> Result<Self, WasmDecodeErrorType> {
+        let ref_type = WasmValType::ref_type_from_stream(stream)?;
+        WasmLimit::table_from_stream(stream).map(|limit| Self::with_type(ref_type, limit))
+    }
+
+    /// Creates a new `funcref` table
+    #[inline]
+    pub fn new(limit: WasmLimit) -> Self {
+        Self::with_type(WasmValType::FuncRef, limit)
+    }
+
+    /// Creates a new table with the specified element type
+    #[inline]
+    pub fn with_type(ref_type: WasmValType, limit: WasmLimit) -> Self {
+        let size = limit.min as usize;
+        let mut table = Vec::with_capacity(size);
+        table.resize(size, None);
         Self {
-            index,
-            type_index,
-            func_type: func_type.clone(),
-            origin: WasmFunctionOrigin::Import(index),
-            code_block: None,
-            dlink: Some(dlink),
+            ref_type,
+            limit,
+            table: Rc::new(UnsafeCell::new(table)),
         }
     }
 
+    /// Returns a new handle that refers to the same table
     #[inline]
-    fn internal(index: usize, type_index: usize, func_type: &WasmType) -> Self {
+    pub fn share(&self) -> Self {
         Self {
-            index,
-            type_index,
-            func_type: func_type.clone(),
-            origin: WasmFunctionOrigin::Internal,
-            code_block: None,
-            dlink: None,
+            ref_type: self.ref_type,
+            limit: self.limit,
+            table: self.table.clone(),
         }
     }
 
+    /// Returns a new table with a copy of the elements
     #[inline]
-    pub const fn index(&self) -> usize {
-        self.index
+    fn duplicate(&self) -> Self {
+        Self {
+            ref_type: self.ref_type,
+            limit: self.limit,
+            table: Rc::new(UnsafeCell::new(self.elements().to_vec())),
+        }
     }
 
+    /// Returns the element type of this table
     #[inline]
-    pub const fn type_index(&self) -> usize {
-        self.type_index
+    pub const fn ref_type(&self) -> WasmValType {
+        self.ref_type
     }
 
     #[inline]
-    pub const fn param_types(&self) -> &[WasmValType] {
-        &self.func_type.param_types
+    pub const fn limit(&self) -> WasmLimit {
+        self.limit
     }
 
     #[inline]
-    pub const fn result_types(&self) -> &[WasmValType] {
-        &self.func_type.result_types
+    pub fn len(&self) -> usize {
+        self.elements().len()
     }
 
     #[inline]
-    pub const fn origin(&self) -> WasmFunctionOrigin {
-        self.origin
+    pub fn is_empty(&self) -> bool {
+        self.elements().is_empty()
     }
 
     #[inline]
-    pub const fn code_block(&self) -> Option<&WasmCodeBlock> {
-        self.code_block.as_ref()
+    fn elements(&self) -> &[Option<usize>] {
+        unsafe { &*self.table.get() }
     }
 
     #[inline]
-    pub fn dlink(&self) -> Option<WasmDynFunc> {
-        self.dlink
+    #[allow(clippy::mut_from_ref)]
+    fn elements_mut(&self) -> &mut Vec<Option<usize>> {
+        unsafe { &mut *self.table.get() }
     }
-}
 
-#[derive(Debug, Copy, Clone)]
-pub enum WasmFunctionOrigin {
-    Internal,
-    Export(usize),
-    Import(usize),
-}
+    #[inline]
+    pub fn table(&mut self) -> &mut [Option<usize>] {
+        self.elements_mut().as_mut_slice()
+    }
+
+    /// table.get
+    #[inline]
+    pub fn get(&self, index: usize) -> Result<Option<usize>, WasmRuntimeErrorType> {
+        self.elements()
+            .get(index)
+            .copied()
+            .ok_or(WasmRuntimeErrorType::OutOfBounds)
+    }
+
+    /// table.set
+    #[inline]
+    pub fn set(&self, index: usize, value: Option<usize>) -> Result<(), WasmRuntimeErrorType> {
+        self.elements_mut()
+            .get_mut(index)
+            .map(|v| *v = value)
+            .ok_or(WasmRuntimeErrorType::OutOfBounds)
+    }
+
+    /// table.grow
+    ///
+    /// Returns the previous size, or `-1` if the table cannot grow.
+    pub fn grow(&self, delta: u32, value: Option<usize>) -> i32 {
+        let table = self.elements_mut();
+        let old_size = table.len();
+        match old_size.checked_add(delta as usize) {
+            Some(new_size) if new_size <= self.limit.max as usize => {
+                if table.try_reserve_exact(delta as usize).is_err() {
+                    return -1;
+                }
+                table.resize(new_size, value);
+                old_size as i32
+            }
+            _ => -1,
+        }
+    }
+
+    /// table.fill
+    pub fn fill(
+        &self,
+        offset: usize,
+        value: Option<usize>,
+        count: usize,
+    ) -> Result<(), WasmRuntimeErrorType> {
+        let table = self.elements_mut();
+        match offset.checked_add(count) {
+            Some(end) if end <= table.len() => {
+                table[offset..end].fill(value);
+                Ok(())
+            }
+            _ => Err(WasmRuntimeErrorType::OutOfBounds),
+        }
+    }
+
+    /// table.copy
+    ///
+    /// `src_table` may refer to the same table as `self`.
+    pub fn copy(
+        &self,
+        dest: usize,
+        src_table: &WasmTable,
+        src: usize,
+        count: usize,
+    ) -> Result<(), WasmRuntimeErrorType> {
+        let dest_len = self.len();
+        let src_len = src_table.len();
+        match (dest.checked_add(count), src.checked_add(count)) {
+            (Some(dest_end), Some(src_end)) if dest_end <= dest_len && src_end <= src_len => {
+                if Rc::ptr_eq(&self.table, &src_table.table) {
+                    self.elements_mut().copy_within(src..src_end, dest);
+                } else {
+                    self.elements_mut()[dest..dest_end]
+                        .copy_from_slice(&src_table.elements()[src..src_end]);
+                }
+                Ok(())
+            }
+            _ => Err(WasmRuntimeErrorType::OutOfBounds),
+        }
+    }
+
+    /// table.init
+    pub fn init(&self, dest: usize, src: &[Option<usize>]) -> Result<(), WasmRuntimeErrorType> {
+        let table = self.elements_mut();
+        match dest.checked_add(src.len()) {
+            Some(end) if end <= table.len() => {
+                table[dest..end].copy_from_slice(src);
+                Ok(())
+            }
+            _ => Err(WasmRuntimeErrorType::OutOfBounds),
+        }
+    }
+}
+
+/// A type that represents the type of WebAssembly function.
+///
+/// There are two types of functions in WebAssembly: those that are imported from external modules and those that have bytecode in the same module.
+///
+/// It appears as the third section (`0x03`) in the WebAssembly binary.
+///
+/// The code block is shared by the instances of the module created for other threads.
+#[derive(Clone)]
+pub struct WasmFunction {
+    index: usize,
+    type_index: usize,
+    func_type: WasmType,
+    origin: WasmFunctionOrigin,
+    code_block: Option<Arc<WasmCodeBlock>>,
+    dlink: Option<WasmDynFunc>,
+}
+
+impl WasmFunction {
+    #[inline]
+    fn from_import(
+        type_index: usize,
+        func_type: &WasmType,
+        index: usize,
+        dlink: WasmDynFunc,
+    ) -> Self {
+        Self {
+            index,
+            type_index,
+            func_type: func_type.clone(),
+            origin: WasmFunctionOrigin::Import(index),
+            code_block: None,
+            dlink: Some(dlink),
+        }
+    }
+
+    #[inline]
+    fn internal(index: usize, type_index: usize, func_type: &WasmType) -> Self {
+        Self {
+            index,
+            type_index,
+            func_type: func_type.clone(),
+            origin: WasmFunctionOrigin::Internal,
+            code_block: None,
+            dlink: None,
+        }
+    }
+
+    #[inline]
+    pub const fn index(&self) -> usize {
+        self.index
+    }
+
+    #[inline]
+    pub const fn type_index(&self) -> usize {
+        self.type_index
+    }
+
+    #[inline]
+    pub const fn param_types(&self) -> &[WasmValType] {
+        &self.func_type.param_types
+    }
+
+    #[inline]
+    pub const fn result_types(&self) -> &[WasmValType] {
+        &self.func_type.result_types
+    }
+
+    #[inline]
+    pub const fn origin(&self) -> WasmFunctionOrigin {
+        self.origin
+    }
+
+    #[inline]
+    pub fn code_block(&self) -> Option<&WasmCodeBlock> {
+        self.code_block.as_deref()
+    }
+
+    #[inline]
+    pub fn dlink(&self) -> Option<WasmDynFunc> {
+        self.dlink
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum WasmFunctionOrigin {
+    Internal,
+    Export(usize),
+    Import(usize),
+}
 
 /// A type that holds the signature of a function that combines a list of argument types with a list of return types.
 ///
@@ -1239,9 +2634,9 @@ impl WasmImport {
 #[derive(Debug, Copy, Clone)]
 pub enum WasmImportIndex {
     Type(usize),
-    // Table(usize),
+    Table(WasmValType, WasmLimit),
     Memory(WasmLimit),
-    // Global(usize),
+    Global(WasmValType, bool),
 }
 
 impl WasmImportIndex {
@@ -1249,15 +2644,29 @@ impl WasmImportIndex {
     fn from_stream(mut stream: &mut Leb128Stream) -> Result<Self, WasmDecodeErrorType> {
         stream.read_unsigned().and_then(|v| match v {
             0 => stream.read_unsigned().map(|v| Self::Type(v as usize)),
-            // 1 => stream.read_unsigned().map(|v| Self::Table(v as usize)),
+            1 => {
+                let ref_type = WasmValType::ref_type_from_stream(stream)?;
+                WasmLimit::table_from_stream(&mut stream).map(|v| Self::Table(ref_type, v))
+            }
             2 => WasmLimit::from_stream(&mut stream).map(|v| Self::Memory(v)),
-            // 3 => stream.read_unsigned().map(|v| Self::Global(v as usize)),
+            3 => {
+                let val_type = stream
+                    .read_byte()
+                    .and_then(|v| WasmValType::from_u64(v as u64))?;
+                let is_mutable = match stream.read_byte()? {
+                    0 => false,
+                    1 => true,
+                    _ => return Err(WasmDecodeErrorType::UnexpectedToken),
+                };
+                Ok(Self::Global(val_type, is_mutable))
+            }
             _ => Err(WasmDecodeErrorType::UnexpectedToken),
         })
     }
 }
 
 /// WebAssembly export object
+#[derive(Clone)]
 pub struct WasmExport {
     name: String,
     index: WasmExportIndex,
@@ -1282,12 +2691,77 @@ impl WasmExport {
     }
 }
 
+/// Contents of the "name" custom section
+#[derive(Clone)]
+pub struct WasmName {
+    module: Option<String>,
+    functions: Vec<(usize, String)>,
+}
+
+impl WasmName {
+    pub const SECTION_NAME: &'static str = "name";
+
+    const NAME_MODULE: u8 = 0;
+    const NAME_FUNCTION: u8 = 1;
+
+    fn from_stream(stream: &mut Leb128Stream) -> Result<Self, WasmDecodeErrorType> {
+        let mut module = None;
+        let mut functions = Vec::new();
+
+        while !stream.is_eof() {
+            let name_id = stream.read_byte()?;
+            let blob = stream.read_bytes()?;
+            let mut stream = Leb128Stream::from_slice(blob);
+            match name_id {
+                Self::NAME_MODULE => {
+                    module = Some(stream.get_string()?.to_string());
+                }
+                Self::NAME_FUNCTION => {
+                    let n_items = stream.read_unsigned()? as usize;
+                    for _ in 0..n_items {
+                        let index = stream.read_unsigned()? as usize;
+                        let name = stream.get_string()?.to_string();
+                        functions.push((index, name));
+                    }
+                }
+                // Local names and others are not used
+                _ => (),
+            }
+        }
+        functions.sort_by_key(|v| v.0);
+
+        Ok(Self { module, functions })
+    }
+
+    /// Returns the name of the module
+    #[inline]
+    pub fn module(&self) -> Option<&str> {
+        self.module.as_deref()
+    }
+
+    /// Returns the list of function names sorted by index
+    #[inline]
+    pub fn functions(&self) -> &[(usize, String)] {
+        self.functions.as_slice()
+    }
+
+    /// Returns the name of the function with the specified index
+    #[inline]
+    pub fn func_by_index(&self, index: usize) -> Option<&str> {
+        self.functions
+            .binary_search_by_key(&index, |v| v.0)
+            .ok()
+            .map(|v| self.functions[v].1.as_str())
+    }
+}
+
 #[derive(Debug, Copy, Clone)]
 pub enum WasmExportIndex {
     Function(usize),
     Table(usize),
     Memory(usize),
     Global(usize),
+    Tag(usize),
 }
 
 impl WasmExportIndex {
@@ -1298,6 +2772,7 @@ impl WasmExportIndex {
             1 => stream.read_unsigned().map(|v| Self::Table(v as usize)),
             2 => stream.read_unsigned().map(|v| Self::Memory(v as usize)),
             3 => stream.read_unsigned().map(|v| Self::Global(v as usize)),
+            4 => stream.read_unsigned().map(|v| Self::Tag(v as usize)),
             _ => Err(WasmDecodeErrorType::UnexpectedToken),
         })
     }
@@ -1313,6 +2788,10 @@ pub enum WasmDecodeErrorType {
     InvalidType,
     InvalidGlobal,
     InvalidLocal,
+    InvalidFunction,
+    InvalidTable,
+    InvalidData,
+    InvalidAlignment,
     OutOfStack,
     OutOfBranch,
     OutOfMemory,
@@ -1325,6 +2804,88 @@ pub enum WasmDecodeErrorType {
     NotSupprted,
     BadExecutable,
     ExceededBytecode,
+    IncompatibleImport,
+    DuplicateExport,
+    DuplicateSection,
+}
+
+/// A decode error with the location where the problem was found
+///
+/// `function` is the index of the function whose body failed to validate, if any.
+/// `position` is relative to the function body in that case, otherwise it is the file offset of the section.
+pub struct WasmDecodeError {
+    kind: WasmDecodeErrorType,
+    function: Option<usize>,
+    position: usize,
+    opcode: Option<WasmOpcode>,
+}
+
+impl WasmDecodeError {
+    #[inline]
+    pub const fn new(
+        kind: WasmDecodeErrorType,
+        function: Option<usize>,
+        position: usize,
+        opcode: Option<WasmOpcode>,
+    ) -> Self {
+        Self {
+            kind,
+            function,
+            position,
+            opcode,
+        }
+    }
+
+    #[inline]
+    pub const fn kind(&self) -> WasmDecodeErrorType {
+        self.kind
+    }
+
+    #[inline]
+    pub const fn function(&self) -> Option<usize> {
+        self.function
+    }
+
+    #[inline]
+    pub const fn position(&self) -> usize {
+        self.position
+    }
+
+    #[inline]
+    pub const fn opcode(&self) -> Option<WasmOpcode> {
+        self.opcode
+    }
+}
+
+impl From<WasmDecodeErrorType> for WasmDecodeError {
+    #[inline]
+    fn from(kind: WasmDecodeErrorType) -> Self {
+        Self::new(kind, None, 0, None)
+    }
+}
+
+impl fmt::Debug for WasmDecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.function(), self.opcode()) {
+            (Some(function), Some(opcode)) => write!(
+                f,
+                "{:?} (function {} position {:x} bytecode {:02x} {})",
+                self.kind(),
+                function,
+                self.position(),
+                opcode as usize,
+                opcode.to_str(),
+            ),
+            (Some(function), None) => write!(
+                f,
+                "{:?} (function {} position {:x})",
+                self.kind(),
+                function,
+                self.position(),
+            ),
+            (None, _) => write!(f, "{:?} (position {:x})", self.kind(), self.position()),
+        }
+    }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -1339,7 +2900,15 @@ pub enum WasmRuntimeErrorType {
     OutOfMemory,
     NoMethod,
     DivideByZero,
+    IntegerOverflow,
+    InvalidConversionToInteger,
     TypeMismatch,
+    /// The fuel has run out
+    OutOfFuel,
+    /// The address of an atomic instruction is not naturally aligned
+    UnalignedAtomic,
+    /// An exception thrown by `throw` or `rethrow` has not been caught
+    Exception,
 }
 
 /// A type that holds a WebAssembly primitive value with a type information tag.
@@ -1349,6 +2918,12 @@ pub enum WasmValue {
     I64(i64),
     F32(f32),
     F64(f64),
+    /// A 128-bit vector, with lane 0 in the least significant bits
+    V128(u128),
+    /// A function index, or `None` for the null reference
+    FuncRef(Option<usize>),
+    /// A host-defined opaque handle, or `None` for the null reference
+    ExternRef(Option<usize>),
 }
 
 impl WasmValue {
@@ -1359,6 +2934,9 @@ impl WasmValue {
             WasmValType::I64 => Self::I64(0),
             WasmValType::F32 => Self::F32(0.0),
             WasmValType::F64 => Self::F64(0.0),
+            WasmValType::V128 => Self::V128(0),
+            WasmValType::FuncRef => Self::FuncRef(None),
+            WasmValType::ExternRef => Self::ExternRef(None),
         }
     }
 
@@ -1369,6 +2947,9 @@ impl WasmValue {
             WasmValue::I64(_) => WasmValType::I64,
             WasmValue::F32(_) => WasmValType::F32,
             WasmValue::F64(_) => WasmValType::F64,
+            WasmValue::V128(_) => WasmValType::V128,
+            WasmValue::FuncRef(_) => WasmValType::FuncRef,
+            WasmValue::ExternRef(_) => WasmValType::ExternRef,
         }
     }
 
@@ -1379,6 +2960,9 @@ impl WasmValue {
             (Self::I64(_), WasmValType::I64) => true,
             (Self::F32(_), WasmValType::F32) => true,
             (Self::F64(_), WasmValType::F64) => true,
+            (Self::V128(_), WasmValType::V128) => true,
+            (Self::FuncRef(_), WasmValType::FuncRef) => true,
+            (Self::ExternRef(_), WasmValType::ExternRef) => true,
             _ => false,
         }
     }
@@ -1415,6 +2999,46 @@ impl WasmValue {
         }
     }
 
+    #[inline]
+    pub const fn get_f32(self) -> Result<f32, WasmRuntimeErrorType> {
+        match self {
+            Self::F32(a) => Ok(a),
+            _ => return Err(WasmRuntimeErrorType::TypeMismatch),
+        }
+    }
+
+    #[inline]
+    pub const fn get_f64(self) -> Result<f64, WasmRuntimeErrorType> {
+        match self {
+            Self::F64(a) => Ok(a),
+            _ => return Err(WasmRuntimeErrorType::TypeMismatch),
+        }
+    }
+
+    #[inline]
+    pub const fn get_v128(self) -> Result<u128, WasmRuntimeErrorType> {
+        match self {
+            Self::V128(a) => Ok(a),
+            _ => Err(WasmRuntimeErrorType::TypeMismatch),
+        }
+    }
+
+    #[inline]
+    pub const fn get_funcref(self) -> Result<Option<usize>, WasmRuntimeErrorType> {
+        match self {
+            Self::FuncRef(a) => Ok(a),
+            _ => return Err(WasmRuntimeErrorType::TypeMismatch),
+        }
+    }
+
+    #[inline]
+    pub const fn get_externref(self) -> Result<Option<usize>, WasmRuntimeErrorType> {
+        match self {
+            Self::ExternRef(a) => Ok(a),
+            _ => return Err(WasmRuntimeErrorType::TypeMismatch),
+        }
+    }
+
     #[inline]
     pub fn map_i32<F>(self, f: F) -> Result<WasmValue, WasmRuntimeErrorType>
     where
@@ -1480,6 +3104,13 @@ impl From<f64> for WasmValue {
     }
 }
 
+impl From<u128> for WasmValue {
+    #[inline]
+    fn from(v: u128) -> Self {
+        Self::V128(v)
+    }
+}
+
 impl From<bool> for WasmValue {
     #[inline]
     fn from(v: bool) -> Self {
@@ -1492,29 +3123,49 @@ impl fmt::Display for WasmValue {
         match *self {
             Self::I32(v) => write!(f, "{}", v),
             Self::I64(v) => write!(f, "{}", v),
-            Self::F32(_) => write!(f, "(#!F32)"),
-            Self::F64(_) => write!(f, "(#!F64)"),
+            Self::F32(v) => write!(f, "{}", v),
+            Self::F64(v) => write!(f, "{}", v),
+            Self::V128(v) => write!(f, "(v128.const i64x2 {} {})", v as u64, (v >> 64) as u64),
+            Self::FuncRef(Some(v)) => write!(f, "(ref.func {})", v),
+            Self::ExternRef(Some(v)) => write!(f, "(ref.extern {})", v),
+            Self::FuncRef(None) => write!(f, "(ref.null func)"),
+            Self::ExternRef(None) => write!(f, "(ref.null extern)"),
         }
     }
 }
 
 /// WebAssembly global variables
 pub struct WasmGlobal {
-    value: UnsafeCell<WasmValue>,
+    value: Rc<UnsafeCell<WasmValue>>,
     is_mutable: bool,
 }
 
 impl WasmGlobal {
     #[inline]
-    pub const fn new(val: WasmValue, is_mutable: bool) -> Self {
+    pub fn new(val: WasmValue, is_mutable: bool) -> Self {
         Self {
-            value: UnsafeCell::new(val),
+            value: Rc::new(UnsafeCell::new(val)),
             is_mutable,
         }
     }
 
+    /// Returns a new handle that refers to the same variable
     #[inline]
-    pub const fn val_type(&self) -> WasmValType {
+    pub fn share(&self) -> Self {
+        Self {
+            value: self.value.clone(),
+            is_mutable: self.is_mutable,
+        }
+    }
+
+    /// Returns a new variable with the current value
+    #[inline]
+    fn duplicate(&self) -> Self {
+        Self::new(*self.value(), self.is_mutable)
+    }
+
+    #[inline]
+    pub fn val_type(&self) -> WasmValType {
         self.value().val_type()
     }
 
@@ -1524,7 +3175,7 @@ impl WasmGlobal {
     }
 
     #[inline]
-    pub const fn value(&self) -> &WasmValue {
+    pub fn value(&self) -> &WasmValue {
         unsafe { &*self.value.get() }
     }
 
@@ -1538,45 +3189,210 @@ impl WasmGlobal {
     }
 }
 
-/// WebAssembly code block
-pub struct WasmCodeBlock {
-    func_index: usize,
-    local_types: Box<[WasmValType]>,
-    max_stack: usize,
-    flags: WasmBlockFlag,
-    int_codes: Box<[WasmImc]>,
-    ext_params: Box<[usize]>,
+/// WebAssembly data segment
+pub struct WasmData {
+    mode: WasmDataMode,
+    data: UnsafeCell<Vec<u8>>,
 }
 
-bitflags! {
-    pub struct WasmBlockFlag: usize {
-        const LEAF_FUNCTION     = 0b0000_0000_0000_0001;
-    }
+/// Where a data segment is placed
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum WasmDataMode {
+    Passive,
+    /// Copied into the memory when the module is instantiated, the contents are not kept in the segment.
+    Active {
+        memory: usize,
+        offset: usize,
+        len: usize,
+    },
 }
 
-impl WasmCodeBlock {
+impl WasmData {
+    /// Creates a passive data segment
     #[inline]
-    pub const fn func_index(&self) -> usize {
-        self.func_index
+    pub const fn new(data: Vec<u8>) -> Self {
+        Self {
+            mode: WasmDataMode::Passive,
+            data: UnsafeCell::new(data),
+        }
     }
 
     #[inline]
-    pub const fn local_types(&self) -> &[WasmValType] {
-        &self.local_types
+    const fn active(memory: usize, offset: usize, len: usize) -> Self {
+        Self {
+            mode: WasmDataMode::Active {
+                memory,
+                offset,
+                len,
+            },
+            data: UnsafeCell::new(Vec::new()),
+        }
     }
 
-    /// Returns the maximum size of the value stack.
     #[inline]
-    pub const fn max_value_stack(&self) -> usize {
-        self.max_stack
+    fn duplicate(&self) -> Self {
+        Self {
+            mode: self.mode,
+            data: UnsafeCell::new(self.bytes().to_vec()),
+        }
     }
 
-    /// Returns whether or not this function block does not call any other functions.
+    #[inline]
+    pub const fn mode(&self) -> WasmDataMode {
+        self.mode
+    }
+
+    #[inline]
+    pub fn bytes(&self) -> &[u8] {
+        unsafe { &*self.data.get() }
+    }
+
+    /// data.drop
+    #[inline]
+    pub fn discard(&self) {
+        let data = unsafe { &mut *self.data.get() };
+        *data = Vec::new();
+    }
+}
+
+/// WebAssembly element segment
+pub struct WasmElement {
+    ref_type: WasmValType,
+    items: UnsafeCell<Vec<Option<usize>>>,
+}
+
+impl WasmElement {
+    #[inline]
+    pub const fn new(ref_type: WasmValType, items: Vec<Option<usize>>) -> Self {
+        Self {
+            ref_type,
+            items: UnsafeCell::new(items),
+        }
+    }
+
+    #[inline]
+    fn duplicate(&self) -> Self {
+        Self::new(self.ref_type, self.items().to_vec())
+    }
+
+    #[inline]
+    pub const fn ref_type(&self) -> WasmValType {
+        self.ref_type
+    }
+
+    #[inline]
+    pub fn items(&self) -> &[Option<usize>] {
+        unsafe { &*self.items.get() }
+    }
+
+    /// elem.drop
+    #[inline]
+    pub fn discard(&self) {
+        let items = unsafe { &mut *self.items.get() };
+        *items = Vec::new();
+    }
+}
+
+/// WebAssembly code block
+pub struct WasmCodeBlock {
+    func_index: usize,
+    local_types: Box<[WasmValType]>,
+    max_stack: usize,
+    flags: WasmBlockFlag,
+    int_codes: Box<[WasmImc]>,
+    ext_params: Box<[usize]>,
+    handlers: Box<[WasmExceptionHandler]>,
+    source: Box<[u8]>,
+    #[cfg(target_arch = "x86_64")]
+    jit_code: Option<WasmJitCode>,
+}
+
+/// Exception handler of a try-block
+///
+/// The positions are indexes of the intermediate codes.
+#[derive(Debug, Clone)]
+pub struct WasmExceptionHandler {
+    start: usize,
+    end: usize,
+    stack_level: usize,
+    catches: Box<[(Option<usize>, usize)]>,
+    delegate: Option<WasmDelegateTarget>,
+}
+
+impl WasmExceptionHandler {
+    /// Returns the range of the try-clause
+    #[inline]
+    pub const fn range(&self) -> (usize, usize) {
+        (self.start, self.end)
+    }
+
+    /// Returns the level of the value stack where the values of the caught exception are placed.
+    #[inline]
+    pub const fn stack_level(&self) -> usize {
+        self.stack_level
+    }
+
+    /// Returns the tags and the positions of the catch-clauses, the tag of `catch_all` is `None`.
+    #[inline]
+    pub const fn catches(&self) -> &[(Option<usize>, usize)] {
+        &self.catches
+    }
+
+    /// Returns where the exception is delegated to if this is `try` ... `delegate`.
+    #[inline]
+    pub const fn delegate(&self) -> Option<WasmDelegateTarget> {
+        self.delegate
+    }
+}
+
+/// Where `delegate` forwards an exception
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WasmDelegateTarget {
+    /// Throws it to the caller
+    Caller,
+    /// Handles it with the handler of the try-block
+    Handler(usize),
+    /// Handles it with the handlers that cover the range of the block
+    Block(usize, usize),
+}
+
+bitflags! {
+    pub struct WasmBlockFlag: usize {
+        const LEAF_FUNCTION     = 0b0000_0000_0000_0001;
+        /// Some values are `v128`, which do not fit in the native code
+        const USES_V128         = 0b0000_0000_0000_0010;
+    }
+}
+
+impl WasmCodeBlock {
+    #[inline]
+    pub const fn func_index(&self) -> usize {
+        self.func_index
+    }
+
+    #[inline]
+    pub const fn local_types(&self) -> &[WasmValType] {
+        &self.local_types
+    }
+
+    /// Returns the maximum size of the value stack.
+    #[inline]
+    pub const fn max_value_stack(&self) -> usize {
+        self.max_stack
+    }
+
+    /// Returns whether or not this function block does not call any other functions.
     #[inline]
     pub fn is_leaf(&self) -> bool {
         self.flags.contains(WasmBlockFlag::LEAF_FUNCTION)
     }
 
+    /// Returns whether or not any local variables or operands of this function block are `v128`.
+    #[inline]
+    pub fn uses_v128(&self) -> bool {
+        self.flags.contains(WasmBlockFlag::USES_V128)
+    }
+
     /// Returns an intermediate code block.
     #[inline]
     pub const fn intermediate_codes(&self) -> &[WasmImc] {
@@ -1588,6 +3404,38 @@ impl WasmCodeBlock {
         &self.ext_params
     }
 
+    /// Returns the exception handlers of the try-blocks, from inner to outer.
+    #[inline]
+    pub const fn exception_handlers(&self) -> &[WasmExceptionHandler] {
+        &self.handlers
+    }
+
+    /// Returns the index of the innermost exception handler that covers the range of intermediate codes,
+    /// searching from the specified index.
+    pub fn find_exception_handler(&self, from: usize, start: usize, end: usize) -> Option<usize> {
+        self.handlers
+            .iter()
+            .enumerate()
+            .skip(from)
+            .find(|(_, handler)| handler.start <= start && end <= handler.end)
+            .map(|(index, _)| index)
+    }
+
+    /// Returns the original bytecode of the function body, including the declarations of the local variables.
+    ///
+    /// The positions of the intermediate codes are offsets in it.
+    #[inline]
+    pub const fn source(&self) -> &[u8] {
+        &self.source
+    }
+
+    /// Returns the native code if this function has been compiled.
+    #[cfg(target_arch = "x86_64")]
+    #[inline]
+    pub const fn jit_code(&self) -> Option<&WasmJitCode> {
+        self.jit_code.as_ref()
+    }
+
     /// Analyzes the WebAssembly bytecode stream to generate intermediate code blocks.
     pub fn generate(
         func_index: usize,
@@ -1595,6 +3443,33 @@ impl WasmCodeBlock {
         param_types: &[WasmValType],
         result_types: &[WasmValType],
         module: &WasmModule,
+    ) -> Result<Self, WasmDecodeError> {
+        let mut current_position = None;
+        Self::generate_imc(
+            func_index,
+            stream,
+            param_types,
+            result_types,
+            module,
+            &mut current_position,
+        )
+        .map_err(|kind| match current_position {
+            Some(position) => {
+                stream.set_position(position);
+                let opcode = stream.read_opcode().ok();
+                WasmDecodeError::new(kind, Some(func_index), position, opcode)
+            }
+            None => WasmDecodeError::new(kind, Some(func_index), 0, None),
+        })
+    }
+
+    fn generate_imc(
+        func_index: usize,
+        stream: &mut Leb128Stream,
+        param_types: &[WasmValType],
+        result_types: &[WasmValType],
+        module: &WasmModule,
+        current_position: &mut Option<usize>,
     ) -> Result<Self, WasmDecodeErrorType> {
         let n_local_types = stream.read_unsigned()? as usize;
         let mut local_types = Vec::with_capacity(n_local_types);
@@ -1615,18 +3490,20 @@ impl WasmCodeBlock {
 
         let mut blocks = Vec::new();
         let mut block_stack = Vec::new();
-        let mut value_stack = Vec::new();
+        let mut value_stack = WasmValidationStack::new();
         let mut max_stack = 0;
         let mut max_block_level = 0;
         let mut flags = WasmBlockFlag::LEAF_FUNCTION;
 
         let mut int_codes: Vec<WasmImc> = Vec::new();
         let mut ext_params = Vec::new();
+        let mut handlers: Vec<WasmHandlerContext> = Vec::new();
 
         loop {
             max_stack = usize::max(max_stack, value_stack.len());
             max_block_level = usize::max(max_block_level, block_stack.len());
             let position = stream.position();
+            *current_position = Some(position);
             let opcode = stream.read_opcode()?;
             // let old_values = value_stack.clone();
 
@@ -1634,61 +3511,73 @@ impl WasmCodeBlock {
                 WasmProposalType::Mvp => {}
                 WasmProposalType::MvpI64 => {}
                 WasmProposalType::SignExtend => {}
-                #[cfg(feature = "float")]
-                WasmProposalType::MvpF32 | WasmProposalType::MvpF64 => {}
+                WasmProposalType::MvpF32 => {}
+                WasmProposalType::MvpF64 => {}
+                WasmProposalType::ReferenceTypes => {}
+                WasmProposalType::Threads => {}
+                WasmProposalType::TailCall => {}
+                WasmProposalType::ExceptionHandling => {}
+                WasmProposalType::Simd => {}
                 _ => return Err(WasmDecodeErrorType::NotSupprted),
             }
 
             match opcode {
                 WasmOpcode::Unreachable => {
-                    int_codes.push(WasmIntMnemonic::Unreachable.into());
+                    int_codes.push(WasmImc::new(
+                        position,
+                        opcode,
+                        WasmIntMnemonic::Unreachable,
+                        value_stack.len(),
+                        0,
+                    ));
+                    value_stack.set_unreachable();
                 }
 
                 WasmOpcode::Nop => (),
 
-                WasmOpcode::Block => {
+                WasmOpcode::Block | WasmOpcode::Loop | WasmOpcode::If | WasmOpcode::Try => {
+                    if opcode == WasmOpcode::If {
+                        let cc = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
+                        if cc != WasmValType::I32 {
+                            return Err(WasmDecodeErrorType::TypeMismatch);
+                        }
+                    }
                     let target = blocks.len();
                     let block_type = stream
                         .read_signed()
                         .and_then(|v| WasmBlockType::from_i64(v))?;
+                    let (block_params, _) = block_type.signature(module)?;
+                    value_stack.pop_types(block_params)?;
+                    let stack_level = value_stack.len();
+                    value_stack.enter(stack_level);
+                    value_stack.extend_from_slice(block_params);
+                    let inst_type = match opcode {
+                        WasmOpcode::Loop => BlockInstType::Loop,
+                        WasmOpcode::If => BlockInstType::If,
+                        WasmOpcode::Try => BlockInstType::Try,
+                        _ => BlockInstType::Block,
+                    };
                     let block = RefCell::new(WasmBlockContext {
-                        inst_type: BlockInstType::Block,
+                        inst_type,
                         block_type,
-                        stack_level: value_stack.len(),
+                        stack_level,
+                        has_else: false,
+                        handler: None,
                         start_position: 0,
                         end_position: 0,
                         else_position: 0,
                     });
                     block_stack.push(target);
                     blocks.push(block);
-                    if block_type == WasmBlockType::Empty {
+                    if inst_type == BlockInstType::If {
                         int_codes.push(WasmImc::new(
                             position,
                             opcode,
-                            WasmIntMnemonic::Block,
+                            WasmIntMnemonic::If,
                             value_stack.len(),
                             target as u64,
                         ));
                     } else {
-                        int_codes.push(WasmIntMnemonic::Undefined.into());
-                    }
-                }
-                WasmOpcode::Loop => {
-                    let target = blocks.len();
-                    let block_type = stream
-                        .read_signed()
-                        .and_then(|v| WasmBlockType::from_i64(v))?;
-                    let block = RefCell::new(WasmBlockContext {
-                        inst_type: BlockInstType::Loop,
-                        block_type,
-                        stack_level: value_stack.len(),
-                        start_position: 0,
-                        end_position: 0,
-                        else_position: 0,
-                    });
-                    block_stack.push(target);
-                    blocks.push(block);
-                    if block_type == WasmBlockType::Empty {
                         int_codes.push(WasmImc::new(
                             position,
                             opcode,
@@ -1696,43 +3585,130 @@ impl WasmCodeBlock {
                             value_stack.len(),
                             target as u64,
                         ));
-                    } else {
-                        int_codes.push(WasmIntMnemonic::Undefined.into());
                     }
                 }
-                WasmOpcode::If => {
-                    let cc = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
-                    if cc != WasmValType::I32 {
-                        return Err(WasmDecodeErrorType::TypeMismatch);
-                    }
-                    let block_type = stream
-                        .read_signed()
-                        .and_then(|v| WasmBlockType::from_i64(v))?;
-                    let block = RefCell::new(WasmBlockContext {
-                        inst_type: BlockInstType::If,
-                        block_type,
-                        stack_level: value_stack.len(),
-                        start_position: 0,
-                        end_position: 0,
-                        else_position: 0,
-                    });
-                    block_stack.push(blocks.len());
-                    blocks.push(block);
-                    int_codes.push(WasmIntMnemonic::Undefined.into());
-                }
                 WasmOpcode::Else => {
-                    let block_ref = block_stack
+                    let block_ref = *block_stack
                         .last()
                         .ok_or(WasmDecodeErrorType::ElseWithoutIf)?;
-                    let block = blocks.get(*block_ref).unwrap().borrow();
-                    if block.inst_type != BlockInstType::If {
+                    let mut block = blocks.get(block_ref).unwrap().borrow_mut();
+                    if block.inst_type != BlockInstType::If || block.has_else {
                         return Err(WasmDecodeErrorType::ElseWithoutIf);
                     }
-                    let n_drops = value_stack.len() - block.stack_level;
-                    for _ in 0..n_drops {
-                        value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
-                    }
-                    int_codes.push(WasmIntMnemonic::Undefined.into());
+                    block.has_else = true;
+                    let (block_params, block_results) = block.block_type.signature(module)?;
+                    value_stack.pop_types(block_results)?;
+                    value_stack.check_frame_end()?;
+                    value_stack.reset_frame();
+                    value_stack.extend_from_slice(block_params);
+                    // The then-clause jumps over the else-clause to the end of the block
+                    int_codes.push(WasmImc::new(
+                        position,
+                        opcode,
+                        WasmIntMnemonic::Br,
+                        value_stack.len(),
+                        block_ref as u64,
+                    ));
+                    int_codes.push(WasmImc::new(
+                        position,
+                        opcode,
+                        WasmIntMnemonic::Else,
+                        value_stack.len(),
+                        block_ref as u64,
+                    ));
+                }
+                WasmOpcode::Catch | WasmOpcode::CatchAll => {
+                    let block_ref = *block_stack
+                        .last()
+                        .ok_or(WasmDecodeErrorType::BlockMismatch)?;
+                    let mut block = blocks.get(block_ref).unwrap().borrow_mut();
+                    // No more catch-clauses can follow the catch_all-clause
+                    if block.inst_type != BlockInstType::Try || block.has_else {
+                        return Err(WasmDecodeErrorType::BlockMismatch);
+                    }
+                    let tag = if opcode == WasmOpcode::Catch {
+                        let tag = stream.read_unsigned()? as usize;
+                        if tag >= module.tags.len() {
+                            return Err(WasmDecodeErrorType::InvalidParameter);
+                        }
+                        Some(tag)
+                    } else {
+                        block.has_else = true;
+                        None
+                    };
+                    let (_, block_results) = block.block_type.signature(module)?;
+                    value_stack.pop_types(block_results)?;
+                    value_stack.check_frame_end()?;
+                    value_stack.reset_frame();
+                    if let Some(tag) = tag {
+                        let tag_type = module
+                            .tag_type(tag)
+                            .ok_or(WasmDecodeErrorType::InvalidType)?;
+                        value_stack.extend_from_slice(tag_type.param_types());
+                    }
+                    let handler = *block.handler.get_or_insert_with(|| {
+                        handlers.push(WasmHandlerContext {
+                            block: block_ref,
+                            end_position: 0,
+                            catches: Vec::new(),
+                            delegate: None,
+                        });
+                        handlers.len() - 1
+                    });
+                    let clause = handlers[handler].catches.len();
+                    handlers[handler].catches.push((tag, 0));
+                    // The previous clause jumps over this catch-clause to the end of the block
+                    int_codes.push(WasmImc::new(
+                        position,
+                        opcode,
+                        WasmIntMnemonic::Br,
+                        block.stack_level,
+                        block_ref as u64,
+                    ));
+                    int_codes.push(WasmImc::new(
+                        position,
+                        opcode,
+                        WasmIntMnemonic::Catch,
+                        value_stack.len(),
+                        handler as u64 | (clause as u64) << 32,
+                    ));
+                }
+                WasmOpcode::Delegate => {
+                    let br = stream.read_unsigned()? as usize;
+                    let block_ref = block_stack
+                        .pop()
+                        .ok_or(WasmDecodeErrorType::BlockMismatch)?;
+                    let mut block = blocks.get(block_ref).unwrap().borrow_mut();
+                    if block.inst_type != BlockInstType::Try || block.handler.is_some() {
+                        return Err(WasmDecodeErrorType::BlockMismatch);
+                    }
+                    // The label is counted from the block enclosing this try-block,
+                    // and the outermost label delegates the exception to the caller.
+                    let target = match block_stack.len().checked_sub(br + 1) {
+                        Some(v) => Some(block_stack[v]),
+                        None if br == block_stack.len() => None,
+                        None => return Err(WasmDecodeErrorType::OutOfBranch),
+                    };
+                    handlers.push(WasmHandlerContext {
+                        block: block_ref,
+                        end_position: 0,
+                        catches: Vec::new(),
+                        delegate: Some(target),
+                    });
+                    block.handler = Some(handlers.len() - 1);
+                    let (_, block_results) = block.block_type.signature(module)?;
+                    value_stack.pop_types(block_results)?;
+                    value_stack.check_frame_end()?;
+                    value_stack.leave();
+                    value_stack.truncate(block.stack_level);
+                    value_stack.extend_from_slice(block_results);
+                    int_codes.push(WasmImc::new(
+                        position,
+                        opcode,
+                        WasmIntMnemonic::End,
+                        value_stack.len(),
+                        block_ref as u64,
+                    ));
                 }
                 WasmOpcode::End => {
                     if block_stack.len() > 0 {
@@ -1740,13 +3716,18 @@ impl WasmCodeBlock {
                             .pop()
                             .ok_or(WasmDecodeErrorType::BlockMismatch)?;
                         let block = blocks.get(block_ref).unwrap().borrow();
-                        let n_drops = value_stack.len() - block.stack_level;
-                        for _ in 0..n_drops {
-                            value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
+                        let (block_params, block_results) = block.block_type.signature(module)?;
+                        if block.inst_type == BlockInstType::If
+                            && !block.has_else
+                            && block_params != block_results
+                        {
+                            return Err(WasmDecodeErrorType::TypeMismatch);
                         }
-                        block.block_type.into_type().map(|v| {
-                            value_stack.push(v);
-                        });
+                        value_stack.pop_types(block_results)?;
+                        value_stack.check_frame_end()?;
+                        value_stack.leave();
+                        value_stack.truncate(block.stack_level);
+                        value_stack.extend_from_slice(block_results);
                         int_codes.push(WasmImc::new(
                             position,
                             opcode,
@@ -1754,63 +3735,124 @@ impl WasmCodeBlock {
                             value_stack.len(),
                             block_ref as u64,
                         ));
-                    // TODO: type check
                     } else {
+                        value_stack.pop_types(result_types)?;
+                        value_stack.check_frame_end()?;
                         int_codes.push(WasmImc::new(
                             position,
                             opcode,
                             WasmIntMnemonic::Return,
-                            value_stack.len() - 1,
+                            value_stack.len(),
                             0,
                         ));
+                        if !stream.is_eof() {
+                            return Err(WasmDecodeErrorType::ExceededBytecode);
+                        }
                         break;
                     }
                 }
 
                 WasmOpcode::Br => {
                     let br = stream.read_unsigned()? as usize;
-                    let target = block_stack
-                        .get(block_stack.len() - br - 1)
+                    let target = *block_stack
+                        .len()
+                        .checked_sub(br + 1)
+                        .and_then(|v| block_stack.get(v))
                         .ok_or(WasmDecodeErrorType::OutOfBranch)?;
-                    int_codes.push(WasmImc::new(
-                        position,
-                        opcode,
-                        WasmIntMnemonic::Br,
-                        value_stack.len(),
-                        *target as u64,
-                    ));
+                    let block = blocks[target].borrow();
+                    let label_types = block.label_types(module)?;
+                    let arity = label_types.len();
+                    value_stack.pop_types(label_types)?;
+                    let source = value_stack.len();
+                    if source == block.stack_level || arity == 0 {
+                        int_codes.push(WasmImc::new(
+                            position,
+                            opcode,
+                            WasmIntMnemonic::Br,
+                            source,
+                            target as u64,
+                        ));
+                    } else {
+                        let param_position = ext_params.len();
+                        ext_params.push(target);
+                        ext_params.push(block.stack_level);
+                        ext_params.push(arity);
+                        int_codes.push(WasmImc::new(
+                            position,
+                            opcode,
+                            WasmIntMnemonic::BrUnwind,
+                            source,
+                            param_position as u64,
+                        ));
+                    }
+                    value_stack.set_unreachable();
                 }
                 WasmOpcode::BrIf => {
                     let br = stream.read_unsigned()? as usize;
-                    let target = block_stack
-                        .get(block_stack.len() - br - 1)
+                    let target = *block_stack
+                        .len()
+                        .checked_sub(br + 1)
+                        .and_then(|v| block_stack.get(v))
                         .ok_or(WasmDecodeErrorType::OutOfBranch)?;
                     let cc = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
-                    int_codes.push(WasmImc::new(
-                        position,
-                        opcode,
-                        WasmIntMnemonic::BrIf,
-                        value_stack.len(),
-                        *target as u64,
-                    ));
                     if cc != WasmValType::I32 {
                         return Err(WasmDecodeErrorType::TypeMismatch);
                     }
+                    let block = blocks[target].borrow();
+                    let label_types = block.label_types(module)?;
+                    let arity = label_types.len();
+                    value_stack.pop_types(label_types)?;
+                    let source = value_stack.len();
+                    value_stack.extend_from_slice(label_types);
+                    if source == block.stack_level || arity == 0 {
+                        int_codes.push(WasmImc::new(
+                            position,
+                            opcode,
+                            WasmIntMnemonic::BrIf,
+                            value_stack.len(),
+                            target as u64,
+                        ));
+                    } else {
+                        let param_position = ext_params.len();
+                        ext_params.push(target);
+                        ext_params.push(block.stack_level);
+                        ext_params.push(arity);
+                        int_codes.push(WasmImc::new(
+                            position,
+                            opcode,
+                            WasmIntMnemonic::BrIfUnwind,
+                            value_stack.len(),
+                            param_position as u64,
+                        ));
+                    }
                 }
                 WasmOpcode::BrTable => {
                     let table_len = 1 + stream.read_unsigned()? as usize;
+                    let cc = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
+                    if cc != WasmValType::I32 {
+                        return Err(WasmDecodeErrorType::TypeMismatch);
+                    }
                     let param_position = ext_params.len();
                     ext_params.push(table_len);
+                    let mut default_arity = None;
                     for _ in 0..table_len {
                         let br = stream.read_unsigned()? as usize;
-                        let target = block_stack
-                            .get(block_stack.len() - br - 1)
+                        let target = *block_stack
+                            .len()
+                            .checked_sub(br + 1)
+                            .and_then(|v| block_stack.get(v))
                             .ok_or(WasmDecodeErrorType::OutOfBranch)?;
-                        ext_params.push(*target);
-                    }
-                    let cc = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
-                    if cc != WasmValType::I32 {
-                        return Err(WasmDecodeErrorType::TypeMismatch);
+                        let block = blocks[target].borrow();
+                        let label_types = block.label_types(module)?;
+                        let arity = label_types.len();
+                        if *default_arity.get_or_insert(arity) != arity {
+                            return Err(WasmDecodeErrorType::TypeMismatch);
+                        }
+                        value_stack.pop_types(label_types)?;
+                        value_stack.extend_from_slice(label_types);
+                        ext_params.push(target);
+                        ext_params.push(block.stack_level);
+                        ext_params.push(arity);
                     }
                     int_codes.push(WasmImc::new(
                         position,
@@ -1819,17 +3861,19 @@ impl WasmCodeBlock {
                         value_stack.len(),
                         param_position as u64,
                     ));
+                    value_stack.set_unreachable();
                 }
 
                 WasmOpcode::Return => {
+                    value_stack.pop_types(result_types)?;
                     int_codes.push(WasmImc::new(
                         position,
                         opcode,
                         WasmIntMnemonic::Return,
-                        value_stack.len() - 1,
+                        value_stack.len(),
                         0,
                     ));
-                    // TODO: type check
+                    value_stack.set_unreachable();
                 }
 
                 WasmOpcode::Call => {
@@ -1838,51 +3882,132 @@ impl WasmCodeBlock {
                     let function = module
                         .functions
                         .get(func_index)
-                        .ok_or(WasmDecodeErrorType::InvalidParameter)?;
+                        .ok_or(WasmDecodeErrorType::InvalidFunction)?;
+                    value_stack.pop_types(function.param_types())?;
                     int_codes.push(WasmImc::new(
                         position,
                         opcode,
                         WasmIntMnemonic::Call,
-                        value_stack.len(),
+                        value_stack.len() + function.param_types().len(),
                         func_index as u64,
                     ));
-                    // TODO: type check
-                    for _param in function.param_types() {
-                        value_stack.pop();
-                    }
-                    for result in function.result_types() {
-                        value_stack.push(result.clone());
-                    }
+                    value_stack.extend_from_slice(function.result_types());
                 }
                 WasmOpcode::CallIndirect => {
                     flags.remove(WasmBlockFlag::LEAF_FUNCTION);
                     let type_ref = stream.read_unsigned()? as usize;
-                    let _reserved = stream.read_unsigned()? as usize;
+                    let table_ref = stream.read_unsigned()? as usize;
+                    match module.table(table_ref) {
+                        Some(v) if v.ref_type() == WasmValType::FuncRef => {}
+                        Some(_) => return Err(WasmDecodeErrorType::TypeMismatch),
+                        None => return Err(WasmDecodeErrorType::InvalidTable),
+                    }
                     let func_type = module
                         .type_by_ref(type_ref)
-                        .ok_or(WasmDecodeErrorType::InvalidParameter)?;
+                        .ok_or(WasmDecodeErrorType::InvalidType)?;
                     let index = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
                     if index != WasmValType::I32 {
                         return Err(WasmDecodeErrorType::TypeMismatch);
                     }
+                    value_stack.pop_types(func_type.param_types())?;
                     int_codes.push(WasmImc::new(
                         position,
                         opcode,
                         WasmIntMnemonic::CallIndirect,
-                        value_stack.len(),
-                        type_ref as u64,
+                        value_stack.len() + func_type.param_types().len(),
+                        type_ref as u64 | (table_ref as u64) << 32,
+                    ));
+                    value_stack.extend_from_slice(func_type.result_types());
+                }
+
+                WasmOpcode::ReturnCall => {
+                    flags.remove(WasmBlockFlag::LEAF_FUNCTION);
+                    let func_index = stream.read_unsigned()? as usize;
+                    let function = module
+                        .functions
+                        .get(func_index)
+                        .ok_or(WasmDecodeErrorType::InvalidFunction)?;
+                    if function.result_types() != result_types {
+                        return Err(WasmDecodeErrorType::TypeMismatch);
+                    }
+                    value_stack.pop_types(function.param_types())?;
+                    int_codes.push(WasmImc::new(
+                        position,
+                        opcode,
+                        WasmIntMnemonic::ReturnCall,
+                        value_stack.len() + function.param_types().len(),
+                        func_index as u64,
                     ));
-                    // TODO: type check
-                    for _param in func_type.param_types() {
-                        value_stack.pop();
+                    value_stack.set_unreachable();
+                }
+                WasmOpcode::ReturnCallIndirect => {
+                    flags.remove(WasmBlockFlag::LEAF_FUNCTION);
+                    let type_ref = stream.read_unsigned()? as usize;
+                    let table_ref = stream.read_unsigned()? as usize;
+                    match module.table(table_ref) {
+                        Some(v) if v.ref_type() == WasmValType::FuncRef => {}
+                        Some(_) => return Err(WasmDecodeErrorType::TypeMismatch),
+                        None => return Err(WasmDecodeErrorType::InvalidTable),
                     }
-                    for result in func_type.result_types() {
-                        value_stack.push(result.clone());
+                    let func_type = module
+                        .type_by_ref(type_ref)
+                        .ok_or(WasmDecodeErrorType::InvalidType)?;
+                    if func_type.result_types() != result_types {
+                        return Err(WasmDecodeErrorType::TypeMismatch);
+                    }
+                    let index = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
+                    if index != WasmValType::I32 {
+                        return Err(WasmDecodeErrorType::TypeMismatch);
                     }
+                    value_stack.pop_types(func_type.param_types())?;
+                    int_codes.push(WasmImc::new(
+                        position,
+                        opcode,
+                        WasmIntMnemonic::ReturnCallIndirect,
+                        value_stack.len() + func_type.param_types().len(),
+                        type_ref as u64 | (table_ref as u64) << 32,
+                    ));
+                    value_stack.set_unreachable();
+                }
+
+                WasmOpcode::Throw => {
+                    let tag = stream.read_unsigned()? as usize;
+                    let tag_type = module
+                        .tag_type(tag)
+                        .ok_or(WasmDecodeErrorType::InvalidParameter)?;
+                    value_stack.pop_types(tag_type.param_types())?;
+                    int_codes.push(WasmImc::new(
+                        position,
+                        opcode,
+                        WasmIntMnemonic::Throw,
+                        value_stack.len(),
+                        tag as u64,
+                    ));
+                    value_stack.set_unreachable();
+                }
+                WasmOpcode::Rethrow => {
+                    let br = stream.read_unsigned()? as usize;
+                    let target = *block_stack
+                        .len()
+                        .checked_sub(br + 1)
+                        .and_then(|v| block_stack.get(v))
+                        .ok_or(WasmDecodeErrorType::OutOfBranch)?;
+                    // The label must refer to a catch-clause
+                    let block = blocks[target].borrow();
+                    let handler = match (block.inst_type, block.handler) {
+                        (BlockInstType::Try, Some(v)) => v,
+                        _ => return Err(WasmDecodeErrorType::OutOfBranch),
+                    };
+                    int_codes.push(WasmImc::new(
+                        position,
+                        opcode,
+                        WasmIntMnemonic::Rethrow,
+                        value_stack.len(),
+                        handler as u64,
+                    ));
+                    value_stack.set_unreachable();
                 }
 
-                // WasmOpcode::ReturnCall
-                // WasmOpcode::ReturnCallIndirect
                 WasmOpcode::Drop => {
                     value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
                 }
@@ -1894,6 +4019,15 @@ impl WasmCodeBlock {
                     if a != b || cc != WasmValType::I32 {
                         return Err(WasmDecodeErrorType::TypeMismatch);
                     }
+                    // Untyped select only accepts numeric types
+                    match (a, b) {
+                        (WasmStackType::Known(v), _) | (_, WasmStackType::Known(v))
+                            if v.is_ref() =>
+                        {
+                            return Err(WasmDecodeErrorType::TypeMismatch)
+                        }
+                        _ => {}
+                    }
                     int_codes.push(WasmImc::new(
                         position,
                         opcode,
@@ -1904,6 +4038,29 @@ impl WasmCodeBlock {
                     value_stack.push(a);
                 }
 
+                WasmOpcode::SelectT => {
+                    if stream.read_unsigned()? != 1 {
+                        return Err(WasmDecodeErrorType::InvalidParameter);
+                    }
+                    let val_type = stream
+                        .read_byte()
+                        .and_then(|v| WasmValType::from_u64(v as u64))?;
+                    let cc = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
+                    let b = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
+                    let a = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
+                    if a != val_type || b != val_type || cc != WasmValType::I32 {
+                        return Err(WasmDecodeErrorType::TypeMismatch);
+                    }
+                    int_codes.push(WasmImc::new(
+                        position,
+                        opcode,
+                        WasmIntMnemonic::Select,
+                        value_stack.len(),
+                        0,
+                    ));
+                    value_stack.push(val_type);
+                }
+
                 WasmOpcode::LocalGet => {
                     let local_ref = stream.read_unsigned()? as usize;
                     let val = *local_var_types
@@ -1988,11 +4145,48 @@ impl WasmCodeBlock {
                     ));
                 }
 
+                WasmOpcode::TableGet => {
+                    let table_ref = stream.read_unsigned()? as usize;
+                    let table = module
+                        .table(table_ref)
+                        .ok_or(WasmDecodeErrorType::InvalidTable)?;
+                    let index = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
+                    if index != WasmValType::I32 {
+                        return Err(WasmDecodeErrorType::TypeMismatch);
+                    }
+                    int_codes.push(WasmImc::new(
+                        position,
+                        opcode,
+                        WasmIntMnemonic::TableGet,
+                        value_stack.len(),
+                        table_ref as u64,
+                    ));
+                    value_stack.push(table.ref_type());
+                }
+                WasmOpcode::TableSet => {
+                    let table_ref = stream.read_unsigned()? as usize;
+                    let table = module
+                        .table(table_ref)
+                        .ok_or(WasmDecodeErrorType::InvalidTable)?;
+                    let val = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
+                    let index = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
+                    if val != table.ref_type() || index != WasmValType::I32 {
+                        return Err(WasmDecodeErrorType::TypeMismatch);
+                    }
+                    int_codes.push(WasmImc::new(
+                        position,
+                        opcode,
+                        WasmIntMnemonic::TableSet,
+                        value_stack.len(),
+                        table_ref as u64,
+                    ));
+                }
+
                 WasmOpcode::I32Load => {
                     if !module.has_memory() {
                         return Err(WasmDecodeErrorType::OutOfMemory);
                     }
-                    let arg = stream.read_memarg()?;
+                    let arg = stream.read_memarg()?.check_alignment(2)?;
                     let a = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
                     if a != WasmValType::I32 {
                         return Err(WasmDecodeErrorType::TypeMismatch);
@@ -2010,7 +4204,7 @@ impl WasmCodeBlock {
                     if !module.has_memory() {
                         return Err(WasmDecodeErrorType::OutOfMemory);
                     }
-                    let arg = stream.read_memarg()?;
+                    let arg = stream.read_memarg()?.check_alignment(0)?;
                     let a = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
                     if a != WasmValType::I32 {
                         return Err(WasmDecodeErrorType::TypeMismatch);
@@ -2028,7 +4222,7 @@ impl WasmCodeBlock {
                     if !module.has_memory() {
                         return Err(WasmDecodeErrorType::OutOfMemory);
                     }
-                    let arg = stream.read_memarg()?;
+                    let arg = stream.read_memarg()?.check_alignment(0)?;
                     let a = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
                     if a != WasmValType::I32 {
                         return Err(WasmDecodeErrorType::TypeMismatch);
@@ -2046,7 +4240,7 @@ impl WasmCodeBlock {
                     if !module.has_memory() {
                         return Err(WasmDecodeErrorType::OutOfMemory);
                     }
-                    let arg = stream.read_memarg()?;
+                    let arg = stream.read_memarg()?.check_alignment(1)?;
                     let a = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
                     if a != WasmValType::I32 {
                         return Err(WasmDecodeErrorType::TypeMismatch);
@@ -2064,7 +4258,7 @@ impl WasmCodeBlock {
                     if !module.has_memory() {
                         return Err(WasmDecodeErrorType::OutOfMemory);
                     }
-                    let arg = stream.read_memarg()?;
+                    let arg = stream.read_memarg()?.check_alignment(1)?;
                     let a = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
                     if a != WasmValType::I32 {
                         return Err(WasmDecodeErrorType::TypeMismatch);
@@ -2083,7 +4277,7 @@ impl WasmCodeBlock {
                     if !module.has_memory() {
                         return Err(WasmDecodeErrorType::OutOfMemory);
                     }
-                    let arg = stream.read_memarg()?;
+                    let arg = stream.read_memarg()?.check_alignment(3)?;
                     let a = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
                     if a != WasmValType::I32 {
                         return Err(WasmDecodeErrorType::TypeMismatch);
@@ -2101,7 +4295,7 @@ impl WasmCodeBlock {
                     if !module.has_memory() {
                         return Err(WasmDecodeErrorType::OutOfMemory);
                     }
-                    let arg = stream.read_memarg()?;
+                    let arg = stream.read_memarg()?.check_alignment(0)?;
                     let a = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
                     if a != WasmValType::I32 {
                         return Err(WasmDecodeErrorType::TypeMismatch);
@@ -2119,7 +4313,7 @@ impl WasmCodeBlock {
                     if !module.has_memory() {
                         return Err(WasmDecodeErrorType::OutOfMemory);
                     }
-                    let arg = stream.read_memarg()?;
+                    let arg = stream.read_memarg()?.check_alignment(0)?;
                     let a = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
                     if a != WasmValType::I32 {
                         return Err(WasmDecodeErrorType::TypeMismatch);
@@ -2137,7 +4331,7 @@ impl WasmCodeBlock {
                     if !module.has_memory() {
                         return Err(WasmDecodeErrorType::OutOfMemory);
                     }
-                    let arg = stream.read_memarg()?;
+                    let arg = stream.read_memarg()?.check_alignment(1)?;
                     let a = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
                     if a != WasmValType::I32 {
                         return Err(WasmDecodeErrorType::TypeMismatch);
@@ -2155,7 +4349,7 @@ impl WasmCodeBlock {
                     if !module.has_memory() {
                         return Err(WasmDecodeErrorType::OutOfMemory);
                     }
-                    let arg = stream.read_memarg()?;
+                    let arg = stream.read_memarg()?.check_alignment(1)?;
                     let a = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
                     if a != WasmValType::I32 {
                         return Err(WasmDecodeErrorType::TypeMismatch);
@@ -2173,7 +4367,7 @@ impl WasmCodeBlock {
                     if !module.has_memory() {
                         return Err(WasmDecodeErrorType::OutOfMemory);
                     }
-                    let arg = stream.read_memarg()?;
+                    let arg = stream.read_memarg()?.check_alignment(2)?;
                     let a = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
                     if a != WasmValType::I32 {
                         return Err(WasmDecodeErrorType::TypeMismatch);
@@ -2191,7 +4385,7 @@ impl WasmCodeBlock {
                     if !module.has_memory() {
                         return Err(WasmDecodeErrorType::OutOfMemory);
                     }
-                    let arg = stream.read_memarg()?;
+                    let arg = stream.read_memarg()?.check_alignment(2)?;
                     let a = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
                     if a != WasmValType::I32 {
                         return Err(WasmDecodeErrorType::TypeMismatch);
@@ -2210,7 +4404,7 @@ impl WasmCodeBlock {
                     if !module.has_memory() {
                         return Err(WasmDecodeErrorType::OutOfMemory);
                     }
-                    let arg = stream.read_memarg()?;
+                    let arg = stream.read_memarg()?.check_alignment(2)?;
                     let d = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
                     let i = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
                     if i != d && i != WasmValType::I32 {
@@ -2228,7 +4422,7 @@ impl WasmCodeBlock {
                     if !module.has_memory() {
                         return Err(WasmDecodeErrorType::OutOfMemory);
                     }
-                    let arg = stream.read_memarg()?;
+                    let arg = stream.read_memarg()?.check_alignment(0)?;
                     let d = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
                     let i = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
                     if i != d && i != WasmValType::I32 {
@@ -2246,7 +4440,7 @@ impl WasmCodeBlock {
                     if !module.has_memory() {
                         return Err(WasmDecodeErrorType::OutOfMemory);
                     }
-                    let arg = stream.read_memarg()?;
+                    let arg = stream.read_memarg()?.check_alignment(1)?;
                     let d = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
                     let i = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
                     if i != d && i != WasmValType::I32 {
@@ -2265,7 +4459,7 @@ impl WasmCodeBlock {
                     if !module.has_memory() {
                         return Err(WasmDecodeErrorType::OutOfMemory);
                     }
-                    let arg = stream.read_memarg()?;
+                    let arg = stream.read_memarg()?.check_alignment(3)?;
                     let d = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
                     let i = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
                     if i != WasmValType::I32 && d != WasmValType::I64 {
@@ -2283,7 +4477,7 @@ impl WasmCodeBlock {
                     if !module.has_memory() {
                         return Err(WasmDecodeErrorType::OutOfMemory);
                     }
-                    let arg = stream.read_memarg()?;
+                    let arg = stream.read_memarg()?.check_alignment(0)?;
                     let d = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
                     let i = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
                     if i != WasmValType::I32 && d != WasmValType::I64 {
@@ -2301,7 +4495,7 @@ impl WasmCodeBlock {
                     if !module.has_memory() {
                         return Err(WasmDecodeErrorType::OutOfMemory);
                     }
-                    let arg = stream.read_memarg()?;
+                    let arg = stream.read_memarg()?.check_alignment(1)?;
                     let d = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
                     let i = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
                     if i != WasmValType::I32 && d != WasmValType::I64 {
@@ -2319,7 +4513,7 @@ impl WasmCodeBlock {
                     if !module.has_memory() {
                         return Err(WasmDecodeErrorType::OutOfMemory);
                     }
-                    let arg = stream.read_memarg()?;
+                    let arg = stream.read_memarg()?.check_alignment(2)?;
                     let d = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
                     let i = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
                     if i != WasmValType::I32 && d != WasmValType::I64 {
@@ -2334,45 +4528,81 @@ impl WasmCodeBlock {
                     ));
                 }
 
-                #[cfg(feature = "float")]
                 WasmOpcode::F32Load => {
-                    let _ = code_block.read_memarg()?;
+                    if !module.has_memory() {
+                        return Err(WasmDecodeErrorType::OutOfMemory);
+                    }
+                    let arg = stream.read_memarg()?.check_alignment(2)?;
                     let a = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
                     if a != WasmValType::I32 {
                         return Err(WasmDecodeErrorType::TypeMismatch);
                     }
+                    int_codes.push(WasmImc::new(
+                        position,
+                        opcode,
+                        WasmIntMnemonic::F32Load,
+                        value_stack.len(),
+                        arg.offset as u64,
+                    ));
                     value_stack.push(WasmValType::F32);
                 }
-                #[cfg(feature = "float")]
                 WasmOpcode::F64Load => {
-                    let _ = code_block.read_memarg()?;
+                    if !module.has_memory() {
+                        return Err(WasmDecodeErrorType::OutOfMemory);
+                    }
+                    let arg = stream.read_memarg()?.check_alignment(3)?;
                     let a = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
                     if a != WasmValType::I32 {
                         return Err(WasmDecodeErrorType::TypeMismatch);
                     }
+                    int_codes.push(WasmImc::new(
+                        position,
+                        opcode,
+                        WasmIntMnemonic::F64Load,
+                        value_stack.len(),
+                        arg.offset as u64,
+                    ));
                     value_stack.push(WasmValType::F64);
                 }
-                #[cfg(feature = "float")]
                 WasmOpcode::F32Store => {
-                    let _ = code_block.read_memarg()?;
+                    if !module.has_memory() {
+                        return Err(WasmDecodeErrorType::OutOfMemory);
+                    }
+                    let arg = stream.read_memarg()?.check_alignment(2)?;
                     let d = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
                     let i = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
-                    if i != WasmValType::I32 && d != WasmValType::F32 {
+                    if i != WasmValType::I32 || d != WasmValType::F32 {
                         return Err(WasmDecodeErrorType::TypeMismatch);
                     }
+                    int_codes.push(WasmImc::new(
+                        position,
+                        opcode,
+                        WasmIntMnemonic::F32Store,
+                        value_stack.len(),
+                        arg.offset as u64,
+                    ));
                 }
-                #[cfg(feature = "float")]
                 WasmOpcode::F64Store => {
-                    let _ = code_block.read_memarg()?;
+                    if !module.has_memory() {
+                        return Err(WasmDecodeErrorType::OutOfMemory);
+                    }
+                    let arg = stream.read_memarg()?.check_alignment(3)?;
                     let d = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
                     let i = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
-                    if i != WasmValType::I32 && d != WasmValType::F64 {
+                    if i != WasmValType::I32 || d != WasmValType::F64 {
                         return Err(WasmDecodeErrorType::TypeMismatch);
                     }
+                    int_codes.push(WasmImc::new(
+                        position,
+                        opcode,
+                        WasmIntMnemonic::F64Store,
+                        value_stack.len(),
+                        arg.offset as u64,
+                    ));
                 }
 
                 WasmOpcode::MemorySize => {
-                    let index = stream.read_unsigned()? as usize;
+                    let index = stream.read_byte()? as usize;
                     if index >= module.memories.len() {
                         return Err(WasmDecodeErrorType::OutOfMemory);
                     }
@@ -2387,21 +4617,21 @@ impl WasmCodeBlock {
                 }
 
                 WasmOpcode::MemoryGrow => {
-                    let index = stream.read_unsigned()? as usize;
+                    let index = stream.read_byte()? as usize;
                     if index >= module.memories.len() {
                         return Err(WasmDecodeErrorType::OutOfMemory);
                     }
+                    let a = *value_stack.last().ok_or(WasmDecodeErrorType::OutOfStack)?;
+                    if a != WasmValType::I32 {
+                        return Err(WasmDecodeErrorType::TypeMismatch);
+                    }
                     int_codes.push(WasmImc::new(
                         position,
                         opcode,
                         WasmIntMnemonic::MemoryGrow,
-                        value_stack.len(),
+                        value_stack.len() - 1,
                         0,
                     ));
-                    let a = *value_stack.last().ok_or(WasmDecodeErrorType::OutOfStack)?;
-                    if a != WasmValType::I32 {
-                        return Err(WasmDecodeErrorType::TypeMismatch);
-                    }
                 }
 
                 WasmOpcode::I32Const => {
@@ -2429,14 +4659,26 @@ impl WasmCodeBlock {
                     ));
                     value_stack.push(WasmValType::I64);
                 }
-                #[cfg(feature = "float")]
                 WasmOpcode::F32Const => {
-                    let _ = code_block.get_bytes(4)?;
+                    let val = stream.read_f32()?;
+                    int_codes.push(WasmImc::new(
+                        position,
+                        opcode,
+                        WasmIntMnemonic::F32Const,
+                        value_stack.len(),
+                        val.to_bits() as u64,
+                    ));
                     value_stack.push(WasmValType::F32);
                 }
-                #[cfg(feature = "float")]
                 WasmOpcode::F64Const => {
-                    let _ = code_block.get_bytes(8)?;
+                    let val = stream.read_f64()?;
+                    int_codes.push(WasmImc::new(
+                        position,
+                        opcode,
+                        WasmIntMnemonic::F64Const,
+                        value_stack.len(),
+                        val.to_bits(),
+                    ));
                     value_stack.push(WasmValType::F64);
                 }
 
@@ -3387,20 +5629,7 @@ impl WasmCodeBlock {
                     value_stack.push(WasmValType::I64);
                 }
 
-                // [f32] -> [i32]
-                #[cfg(feature = "float")]
-                WasmOpcode::I32TruncF32S
-                | WasmOpcode::I32TruncF32U
-                | WasmOpcode::I32ReinterpretF32 => {
-                    let a = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
-                    if a != WasmValType::F32 {
-                        return Err(WasmDecodeErrorType::TypeMismatch);
-                    }
-                    value_stack.push(WasmValType::I32);
-                }
-
                 // [f32, f32] -> [i32]
-                #[cfg(feature = "float")]
                 WasmOpcode::F32Eq
                 | WasmOpcode::F32Ne
                 | WasmOpcode::F32Lt
@@ -3412,11 +5641,25 @@ impl WasmCodeBlock {
                     if a != b || a != WasmValType::F32 {
                         return Err(WasmDecodeErrorType::TypeMismatch);
                     }
+                    let mnemonic = match opcode {
+                        WasmOpcode::F32Eq => WasmIntMnemonic::F32Eq,
+                        WasmOpcode::F32Ne => WasmIntMnemonic::F32Ne,
+                        WasmOpcode::F32Lt => WasmIntMnemonic::F32Lt,
+                        WasmOpcode::F32Gt => WasmIntMnemonic::F32Gt,
+                        WasmOpcode::F32Le => WasmIntMnemonic::F32Le,
+                        _ => WasmIntMnemonic::F32Ge,
+                    };
+                    int_codes.push(WasmImc::new(
+                        position,
+                        opcode,
+                        mnemonic,
+                        value_stack.len(),
+                        0,
+                    ));
                     value_stack.push(WasmValType::I32);
                 }
 
                 // [f32] -> [f32]
-                #[cfg(feature = "float")]
                 WasmOpcode::F32Abs
                 | WasmOpcode::F32Neg
                 | WasmOpcode::F32Ceil
@@ -3425,13 +5668,28 @@ impl WasmCodeBlock {
                 | WasmOpcode::F32Nearest
                 | WasmOpcode::F32Sqrt => {
                     let a = *value_stack.last().ok_or(WasmDecodeErrorType::OutOfStack)?;
-                    if a != WasmValType::I32 {
+                    if a != WasmValType::F32 {
                         return Err(WasmDecodeErrorType::TypeMismatch);
                     }
+                    let mnemonic = match opcode {
+                        WasmOpcode::F32Abs => WasmIntMnemonic::F32Abs,
+                        WasmOpcode::F32Neg => WasmIntMnemonic::F32Neg,
+                        WasmOpcode::F32Ceil => WasmIntMnemonic::F32Ceil,
+                        WasmOpcode::F32Floor => WasmIntMnemonic::F32Floor,
+                        WasmOpcode::F32Trunc => WasmIntMnemonic::F32Trunc,
+                        WasmOpcode::F32Nearest => WasmIntMnemonic::F32Nearest,
+                        _ => WasmIntMnemonic::F32Sqrt,
+                    };
+                    int_codes.push(WasmImc::new(
+                        position,
+                        opcode,
+                        mnemonic,
+                        value_stack.len() - 1,
+                        0,
+                    ));
                 }
 
                 // [f32, f32] -> [f32]
-                #[cfg(feature = "float")]
                 WasmOpcode::F32Add
                 | WasmOpcode::F32Sub
                 | WasmOpcode::F32Mul
@@ -3444,34 +5702,25 @@ impl WasmCodeBlock {
                     if a != b || a != WasmValType::F32 {
                         return Err(WasmDecodeErrorType::TypeMismatch);
                     }
-                }
-
-                // [f64] -> [i32]
-                #[cfg(feature = "float")]
-                WasmOpcode::I32TruncF64S | WasmOpcode::I32TruncF64U => {
-                    let a = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
-                    if a != WasmValType::F64 {
-                        return Err(WasmDecodeErrorType::TypeMismatch);
-                    }
-                    value_stack.push(WasmValType::I32);
-                }
-
-                // [f64] -> [i64]
-                #[cfg(feature = "float")]
-                WasmOpcode::I64TruncF32S
-                | WasmOpcode::I64TruncF32U
-                | WasmOpcode::I64TruncF64S
-                | WasmOpcode::I64TruncF64U
-                | WasmOpcode::I64ReinterpretF64 => {
-                    let a = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
-                    if a != WasmValType::F64 {
-                        return Err(WasmDecodeErrorType::TypeMismatch);
-                    }
-                    value_stack.push(WasmValType::I32);
+                    let mnemonic = match opcode {
+                        WasmOpcode::F32Add => WasmIntMnemonic::F32Add,
+                        WasmOpcode::F32Sub => WasmIntMnemonic::F32Sub,
+                        WasmOpcode::F32Mul => WasmIntMnemonic::F32Mul,
+                        WasmOpcode::F32Div => WasmIntMnemonic::F32Div,
+                        WasmOpcode::F32Min => WasmIntMnemonic::F32Min,
+                        WasmOpcode::F32Max => WasmIntMnemonic::F32Max,
+                        _ => WasmIntMnemonic::F32Copysign,
+                    };
+                    int_codes.push(WasmImc::new(
+                        position,
+                        opcode,
+                        mnemonic,
+                        value_stack.len() - 1,
+                        0,
+                    ));
                 }
 
                 // [f64, f64] -> [i32]
-                #[cfg(feature = "float")]
                 WasmOpcode::F64Eq
                 | WasmOpcode::F64Ne
                 | WasmOpcode::F64Lt
@@ -3483,11 +5732,25 @@ impl WasmCodeBlock {
                     if a != b || a != WasmValType::F64 {
                         return Err(WasmDecodeErrorType::TypeMismatch);
                     }
+                    let mnemonic = match opcode {
+                        WasmOpcode::F64Eq => WasmIntMnemonic::F64Eq,
+                        WasmOpcode::F64Ne => WasmIntMnemonic::F64Ne,
+                        WasmOpcode::F64Lt => WasmIntMnemonic::F64Lt,
+                        WasmOpcode::F64Gt => WasmIntMnemonic::F64Gt,
+                        WasmOpcode::F64Le => WasmIntMnemonic::F64Le,
+                        _ => WasmIntMnemonic::F64Ge,
+                    };
+                    int_codes.push(WasmImc::new(
+                        position,
+                        opcode,
+                        mnemonic,
+                        value_stack.len(),
+                        0,
+                    ));
                     value_stack.push(WasmValType::I32);
                 }
 
                 // [f64] -> [f64]
-                #[cfg(feature = "float")]
                 WasmOpcode::F64Abs
                 | WasmOpcode::F64Neg
                 | WasmOpcode::F64Ceil
@@ -3499,10 +5762,25 @@ impl WasmCodeBlock {
                     if a != WasmValType::F64 {
                         return Err(WasmDecodeErrorType::TypeMismatch);
                     }
+                    let mnemonic = match opcode {
+                        WasmOpcode::F64Abs => WasmIntMnemonic::F64Abs,
+                        WasmOpcode::F64Neg => WasmIntMnemonic::F64Neg,
+                        WasmOpcode::F64Ceil => WasmIntMnemonic::F64Ceil,
+                        WasmOpcode::F64Floor => WasmIntMnemonic::F64Floor,
+                        WasmOpcode::F64Trunc => WasmIntMnemonic::F64Trunc,
+                        WasmOpcode::F64Nearest => WasmIntMnemonic::F64Nearest,
+                        _ => WasmIntMnemonic::F64Sqrt,
+                    };
+                    int_codes.push(WasmImc::new(
+                        position,
+                        opcode,
+                        mnemonic,
+                        value_stack.len() - 1,
+                        0,
+                    ));
                 }
 
                 // [f64, f64] -> [f64]
-                #[cfg(feature = "float")]
                 WasmOpcode::F64Add
                 | WasmOpcode::F64Sub
                 | WasmOpcode::F64Mul
@@ -3515,70 +5793,712 @@ impl WasmCodeBlock {
                     if a != b || a != WasmValType::F64 {
                         return Err(WasmDecodeErrorType::TypeMismatch);
                     }
+                    let mnemonic = match opcode {
+                        WasmOpcode::F64Add => WasmIntMnemonic::F64Add,
+                        WasmOpcode::F64Sub => WasmIntMnemonic::F64Sub,
+                        WasmOpcode::F64Mul => WasmIntMnemonic::F64Mul,
+                        WasmOpcode::F64Div => WasmIntMnemonic::F64Div,
+                        WasmOpcode::F64Min => WasmIntMnemonic::F64Min,
+                        WasmOpcode::F64Max => WasmIntMnemonic::F64Max,
+                        _ => WasmIntMnemonic::F64Copysign,
+                    };
+                    int_codes.push(WasmImc::new(
+                        position,
+                        opcode,
+                        mnemonic,
+                        value_stack.len() - 1,
+                        0,
+                    ));
                 }
 
-                // [i32] -> [f32]
-                #[cfg(feature = "float")]
-                WasmOpcode::F32ConvertI32S
+                // conversion operators
+                WasmOpcode::I32TruncF32S
+                | WasmOpcode::I32TruncF32U
+                | WasmOpcode::I32TruncF64S
+                | WasmOpcode::I32TruncF64U
+                | WasmOpcode::I64TruncF32S
+                | WasmOpcode::I64TruncF32U
+                | WasmOpcode::I64TruncF64S
+                | WasmOpcode::I64TruncF64U
+                | WasmOpcode::F32ConvertI32S
                 | WasmOpcode::F32ConvertI32U
-                | WasmOpcode::F32ReinterpretI32 => {
+                | WasmOpcode::F32ConvertI64S
+                | WasmOpcode::F32ConvertI64U
+                | WasmOpcode::F32DemoteF64
+                | WasmOpcode::F64ConvertI32S
+                | WasmOpcode::F64ConvertI32U
+                | WasmOpcode::F64ConvertI64S
+                | WasmOpcode::F64ConvertI64U
+                | WasmOpcode::F64PromoteF32
+                | WasmOpcode::I32ReinterpretF32
+                | WasmOpcode::I64ReinterpretF64
+                | WasmOpcode::F32ReinterpretI32
+                | WasmOpcode::F64ReinterpretI64 => {
+                    use WasmValType::*;
+                    let (mnemonic, from, to) = match opcode {
+                        WasmOpcode::I32TruncF32S => (WasmIntMnemonic::I32TruncF32S, F32, I32),
+                        WasmOpcode::I32TruncF32U => (WasmIntMnemonic::I32TruncF32U, F32, I32),
+                        WasmOpcode::I32TruncF64S => (WasmIntMnemonic::I32TruncF64S, F64, I32),
+                        WasmOpcode::I32TruncF64U => (WasmIntMnemonic::I32TruncF64U, F64, I32),
+                        WasmOpcode::I64TruncF32S => (WasmIntMnemonic::I64TruncF32S, F32, I64),
+                        WasmOpcode::I64TruncF32U => (WasmIntMnemonic::I64TruncF32U, F32, I64),
+                        WasmOpcode::I64TruncF64S => (WasmIntMnemonic::I64TruncF64S, F64, I64),
+                        WasmOpcode::I64TruncF64U => (WasmIntMnemonic::I64TruncF64U, F64, I64),
+                        WasmOpcode::F32ConvertI32S => (WasmIntMnemonic::F32ConvertI32S, I32, F32),
+                        WasmOpcode::F32ConvertI32U => (WasmIntMnemonic::F32ConvertI32U, I32, F32),
+                        WasmOpcode::F32ConvertI64S => (WasmIntMnemonic::F32ConvertI64S, I64, F32),
+                        WasmOpcode::F32ConvertI64U => (WasmIntMnemonic::F32ConvertI64U, I64, F32),
+                        WasmOpcode::F32DemoteF64 => (WasmIntMnemonic::F32DemoteF64, F64, F32),
+                        WasmOpcode::F64ConvertI32S => (WasmIntMnemonic::F64ConvertI32S, I32, F64),
+                        WasmOpcode::F64ConvertI32U => (WasmIntMnemonic::F64ConvertI32U, I32, F64),
+                        WasmOpcode::F64ConvertI64S => (WasmIntMnemonic::F64ConvertI64S, I64, F64),
+                        WasmOpcode::F64ConvertI64U => (WasmIntMnemonic::F64ConvertI64U, I64, F64),
+                        WasmOpcode::F64PromoteF32 => (WasmIntMnemonic::F64PromoteF32, F32, F64),
+                        WasmOpcode::I32ReinterpretF32 => {
+                            (WasmIntMnemonic::I32ReinterpretF32, F32, I32)
+                        }
+                        WasmOpcode::I64ReinterpretF64 => {
+                            (WasmIntMnemonic::I64ReinterpretF64, F64, I64)
+                        }
+                        WasmOpcode::F32ReinterpretI32 => {
+                            (WasmIntMnemonic::F32ReinterpretI32, I32, F32)
+                        }
+                        _ => (WasmIntMnemonic::F64ReinterpretI64, I64, F64),
+                    };
                     let a = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
-                    if a != WasmValType::I32 {
+                    if a != from {
                         return Err(WasmDecodeErrorType::TypeMismatch);
                     }
-                    value_stack.push(WasmValType::F32);
+                    int_codes.push(WasmImc::new(
+                        position,
+                        opcode,
+                        mnemonic,
+                        value_stack.len(),
+                        0,
+                    ));
+                    value_stack.push(to);
                 }
 
-                // [i64] -> [f64]
-                #[cfg(feature = "float")]
-                WasmOpcode::F32ConvertI64S | WasmOpcode::F32ConvertI64U => {
-                    let a = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
-                    if a != WasmValType::I64 {
-                        return Err(WasmDecodeErrorType::TypeMismatch);
-                    }
-                    value_stack.push(WasmValType::F32);
+                WasmOpcode::RefNull => {
+                    let ref_type = WasmValType::ref_type_from_stream(stream)?;
+                    int_codes.push(WasmImc::new(
+                        position,
+                        opcode,
+                        WasmIntMnemonic::RefNull,
+                        value_stack.len(),
+                        0,
+                    ));
+                    value_stack.push(ref_type);
                 }
-
-                // [f64] -> [f32]
-                #[cfg(feature = "float")]
-                WasmOpcode::F32DemoteF64 => {
+                WasmOpcode::RefIsNull => {
                     let a = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
-                    if a != WasmValType::F64 {
-                        return Err(WasmDecodeErrorType::TypeMismatch);
+                    match a {
+                        WasmStackType::Known(v) if !v.is_ref() => {
+                            return Err(WasmDecodeErrorType::TypeMismatch)
+                        }
+                        _ => {}
                     }
-                    value_stack.push(WasmValType::F32);
+                    int_codes.push(WasmImc::new(
+                        position,
+                        opcode,
+                        WasmIntMnemonic::RefIsNull,
+                        value_stack.len(),
+                        0,
+                    ));
+                    value_stack.push(WasmValType::I32);
                 }
+                WasmOpcode::RefFunc => {
+                    let func_ref = stream.read_unsigned()? as usize;
+                    if func_ref >= module.functions.len() {
+                        return Err(WasmDecodeErrorType::InvalidFunction);
+                    }
+                    int_codes.push(WasmImc::new(
+                        position,
+                        opcode,
+                        WasmIntMnemonic::RefFunc,
+                        value_stack.len(),
+                        func_ref as u64,
+                    ));
+                    value_stack.push(WasmValType::FuncRef);
+                }
+
+                WasmOpcode::PrefixFC => {
+                    let opcode_fc = stream.read_unsigned().and_then(|v| {
+                        WasmOpcodeFC::new(v as u32).ok_or(WasmDecodeErrorType::InvalidBytecode)
+                    })?;
+                    match opcode_fc {
+                        WasmOpcodeFC::I32TruncSatF32S
+                        | WasmOpcodeFC::I32TruncSatF32U
+                        | WasmOpcodeFC::I32TruncSatF64S
+                        | WasmOpcodeFC::I32TruncSatF64U
+                        | WasmOpcodeFC::I64TruncSatF32S
+                        | WasmOpcodeFC::I64TruncSatF32U
+                        | WasmOpcodeFC::I64TruncSatF64S
+                        | WasmOpcodeFC::I64TruncSatF64U => {
+                            use WasmValType::*;
+                            let (mnemonic, from, to) = match opcode_fc {
+                                WasmOpcodeFC::I32TruncSatF32S => {
+                                    (WasmIntMnemonic::I32TruncSatF32S, F32, I32)
+                                }
+                                WasmOpcodeFC::I32TruncSatF32U => {
+                                    (WasmIntMnemonic::I32TruncSatF32U, F32, I32)
+                                }
+                                WasmOpcodeFC::I32TruncSatF64S => {
+                                    (WasmIntMnemonic::I32TruncSatF64S, F64, I32)
+                                }
+                                WasmOpcodeFC::I32TruncSatF64U => {
+                                    (WasmIntMnemonic::I32TruncSatF64U, F64, I32)
+                                }
+                                WasmOpcodeFC::I64TruncSatF32S => {
+                                    (WasmIntMnemonic::I64TruncSatF32S, F32, I64)
+                                }
+                                WasmOpcodeFC::I64TruncSatF32U => {
+                                    (WasmIntMnemonic::I64TruncSatF32U, F32, I64)
+                                }
+                                WasmOpcodeFC::I64TruncSatF64S => {
+                                    (WasmIntMnemonic::I64TruncSatF64S, F64, I64)
+                                }
+                                _ => (WasmIntMnemonic::I64TruncSatF64U, F64, I64),
+                            };
+                            let a = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
+                            if a != from {
+                                return Err(WasmDecodeErrorType::TypeMismatch);
+                            }
+                            int_codes.push(WasmImc::new(
+                                position,
+                                opcode,
+                                mnemonic,
+                                value_stack.len(),
+                                0,
+                            ));
+                            value_stack.push(to);
+                        }
 
-                // [i32] -> [f64]
-                #[cfg(feature = "float")]
-                WasmOpcode::F64ConvertI32S | WasmOpcode::F64ConvertI32U => {
-                    let a = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
-                    if a != WasmValType::I32 {
-                        return Err(WasmDecodeErrorType::TypeMismatch);
+                        WasmOpcodeFC::MemoryInit => {
+                            let index = stream.read_unsigned()? as usize;
+                            if stream.read_byte()? != 0 {
+                                return Err(WasmDecodeErrorType::InvalidParameter);
+                            }
+                            if !module.has_memory() {
+                                return Err(WasmDecodeErrorType::OutOfMemory);
+                            }
+                            match module.data_count() {
+                                Some(v) if index < v => {}
+                                _ => return Err(WasmDecodeErrorType::InvalidParameter),
+                            }
+                            let n = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
+                            let s = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
+                            let d = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
+                            if n != WasmValType::I32 || s != n || d != n {
+                                return Err(WasmDecodeErrorType::TypeMismatch);
+                            }
+                            int_codes.push(WasmImc::new(
+                                position,
+                                opcode,
+                                WasmIntMnemonic::MemoryInit,
+                                value_stack.len(),
+                                index as u64,
+                            ));
+                        }
+                        WasmOpcodeFC::DataDrop => {
+                            let index = stream.read_unsigned()? as usize;
+                            match module.data_count() {
+                                Some(v) if index < v => {}
+                                _ => return Err(WasmDecodeErrorType::InvalidParameter),
+                            }
+                            int_codes.push(WasmImc::new(
+                                position,
+                                opcode,
+                                WasmIntMnemonic::DataDrop,
+                                value_stack.len(),
+                                index as u64,
+                            ));
+                        }
+                        WasmOpcodeFC::MemoryCopy => {
+                            if stream.read_byte()? != 0 || stream.read_byte()? != 0 {
+                                return Err(WasmDecodeErrorType::InvalidParameter);
+                            }
+                            if !module.has_memory() {
+                                return Err(WasmDecodeErrorType::OutOfMemory);
+                            }
+                            let n = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
+                            let s = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
+                            let d = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
+                            if n != WasmValType::I32 || s != n || d != n {
+                                return Err(WasmDecodeErrorType::TypeMismatch);
+                            }
+                            int_codes.push(WasmImc::new(
+                                position,
+                                opcode,
+                                WasmIntMnemonic::MemoryCopy,
+                                value_stack.len(),
+                                0,
+                            ));
+                        }
+                        WasmOpcodeFC::MemoryFill => {
+                            if stream.read_byte()? != 0 {
+                                return Err(WasmDecodeErrorType::InvalidParameter);
+                            }
+                            if !module.has_memory() {
+                                return Err(WasmDecodeErrorType::OutOfMemory);
+                            }
+                            let n = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
+                            let val = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
+                            let d = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
+                            if n != WasmValType::I32 || val != n || d != n {
+                                return Err(WasmDecodeErrorType::TypeMismatch);
+                            }
+                            int_codes.push(WasmImc::new(
+                                position,
+                                opcode,
+                                WasmIntMnemonic::MemoryFill,
+                                value_stack.len(),
+                                0,
+                            ));
+                        }
+
+                        WasmOpcodeFC::TableInit => {
+                            let elem_ref = stream.read_unsigned()? as usize;
+                            let table_ref = stream.read_unsigned()? as usize;
+                            let elem = module
+                                .element(elem_ref)
+                                .ok_or(WasmDecodeErrorType::InvalidParameter)?;
+                            let table = module
+                                .table(table_ref)
+                                .ok_or(WasmDecodeErrorType::InvalidTable)?;
+                            if elem.ref_type() != table.ref_type() {
+                                return Err(WasmDecodeErrorType::TypeMismatch);
+                            }
+                            let n = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
+                            let s = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
+                            let d = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
+                            if n != WasmValType::I32 || s != n || d != n {
+                                return Err(WasmDecodeErrorType::TypeMismatch);
+                            }
+                            int_codes.push(WasmImc::new(
+                                position,
+                                opcode,
+                                WasmIntMnemonic::TableInit,
+                                value_stack.len(),
+                                elem_ref as u64 | (table_ref as u64) << 32,
+                            ));
+                        }
+                        WasmOpcodeFC::ElemDrop => {
+                            let elem_ref = stream.read_unsigned()? as usize;
+                            if module.element(elem_ref).is_none() {
+                                return Err(WasmDecodeErrorType::InvalidParameter);
+                            }
+                            int_codes.push(WasmImc::new(
+                                position,
+                                opcode,
+                                WasmIntMnemonic::ElemDrop,
+                                value_stack.len(),
+                                elem_ref as u64,
+                            ));
+                        }
+                        WasmOpcodeFC::TableCopy => {
+                            let dest_ref = stream.read_unsigned()? as usize;
+                            let src_ref = stream.read_unsigned()? as usize;
+                            let dest = module
+                                .table(dest_ref)
+                                .ok_or(WasmDecodeErrorType::InvalidTable)?;
+                            let src = module
+                                .table(src_ref)
+                                .ok_or(WasmDecodeErrorType::InvalidTable)?;
+                            if dest.ref_type() != src.ref_type() {
+                                return Err(WasmDecodeErrorType::TypeMismatch);
+                            }
+                            let n = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
+                            let s = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
+                            let d = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
+                            if n != WasmValType::I32 || s != n || d != n {
+                                return Err(WasmDecodeErrorType::TypeMismatch);
+                            }
+                            int_codes.push(WasmImc::new(
+                                position,
+                                opcode,
+                                WasmIntMnemonic::TableCopy,
+                                value_stack.len(),
+                                dest_ref as u64 | (src_ref as u64) << 32,
+                            ));
+                        }
+                        WasmOpcodeFC::TableGrow => {
+                            let table_ref = stream.read_unsigned()? as usize;
+                            let table = module
+                                .table(table_ref)
+                                .ok_or(WasmDecodeErrorType::InvalidTable)?;
+                            let n = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
+                            let val = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
+                            if n != WasmValType::I32 || val != table.ref_type() {
+                                return Err(WasmDecodeErrorType::TypeMismatch);
+                            }
+                            int_codes.push(WasmImc::new(
+                                position,
+                                opcode,
+                                WasmIntMnemonic::TableGrow,
+                                value_stack.len(),
+                                table_ref as u64,
+                            ));
+                            value_stack.push(WasmValType::I32);
+                        }
+                        WasmOpcodeFC::TableSize => {
+                            let table_ref = stream.read_unsigned()? as usize;
+                            if module.table(table_ref).is_none() {
+                                return Err(WasmDecodeErrorType::InvalidTable);
+                            }
+                            int_codes.push(WasmImc::new(
+                                position,
+                                opcode,
+                                WasmIntMnemonic::TableSize,
+                                value_stack.len(),
+                                table_ref as u64,
+                            ));
+                            value_stack.push(WasmValType::I32);
+                        }
+                        WasmOpcodeFC::TableFill => {
+                            let table_ref = stream.read_unsigned()? as usize;
+                            let table = module
+                                .table(table_ref)
+                                .ok_or(WasmDecodeErrorType::InvalidTable)?;
+                            let n = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
+                            let val = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
+                            let d = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
+                            if n != WasmValType::I32 || val != table.ref_type() || d != n {
+                                return Err(WasmDecodeErrorType::TypeMismatch);
+                            }
+                            int_codes.push(WasmImc::new(
+                                position,
+                                opcode,
+                                WasmIntMnemonic::TableFill,
+                                value_stack.len(),
+                                table_ref as u64,
+                            ));
+                        }
                     }
-                    value_stack.push(WasmValType::F64);
                 }
 
-                // [i64] -> [f64]
-                #[cfg(feature = "float")]
-                WasmOpcode::F64ConvertI64S
-                | WasmOpcode::F64ConvertI64U
-                | WasmOpcode::F64ReinterpretI64 => {
-                    let a = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
-                    if a != WasmValType::I64 {
-                        return Err(WasmDecodeErrorType::TypeMismatch);
+                WasmOpcode::PrefixFD => {
+                    let opcode_fd = stream.read_unsigned().and_then(|v| {
+                        WasmOpcodeFD::new(v as u32).ok_or(WasmDecodeErrorType::InvalidBytecode)
+                    })?;
+                    use WasmOpcodeFD::*;
+                    use WasmValType::{F32, F64, I32, I64, V128};
+                    match opcode_fd {
+                        V128Load | V128Load8x8S | V128Load8x8U | V128Load16x4S | V128Load16x4U
+                        | V128Load32x2S | V128Load32x2U | V128Load8Splat | V128Load16Splat
+                        | V128Load32Splat | V128Load64Splat | V128Load32Zero | V128Load64Zero
+                        | V128Store => {
+                            if !module.has_memory() {
+                                return Err(WasmDecodeErrorType::OutOfMemory);
+                            }
+                            let natural = match opcode_fd {
+                                V128Load | V128Store => 4,
+                                V128Load8Splat => 0,
+                                V128Load16Splat => 1,
+                                V128Load32Splat | V128Load32Zero => 2,
+                                _ => 3,
+                            };
+                            let arg = stream.read_memarg()?.check_alignment(natural)?;
+                            let mnemonic = if opcode_fd == V128Store {
+                                value_stack.pop_types(&[I32, V128])?;
+                                WasmIntMnemonic::V128Store
+                            } else {
+                                value_stack.pop_types(&[I32])?;
+                                WasmIntMnemonic::V128Load
+                            };
+                            int_codes.push(WasmImc::new(
+                                position,
+                                opcode,
+                                mnemonic,
+                                value_stack.len(),
+                                arg.offset as u64 | (opcode_fd as u64) << 32,
+                            ));
+                            if mnemonic == WasmIntMnemonic::V128Load {
+                                value_stack.push(V128);
+                            }
+                        }
+
+                        V128Load8Lane | V128Load16Lane | V128Load32Lane | V128Load64Lane
+                        | V128Store8Lane | V128Store16Lane | V128Store32Lane | V128Store64Lane => {
+                            if !module.has_memory() {
+                                return Err(WasmDecodeErrorType::OutOfMemory);
+                            }
+                            // The lane loads and stores are ordered by width
+                            let natural = (opcode_fd as u32 - V128Load8Lane as u32) & 3;
+                            let arg = stream.read_memarg()?.check_alignment(natural)?;
+                            let lane = stream.read_byte()? as u64;
+                            if lane >= 16 >> natural {
+                                return Err(WasmDecodeErrorType::InvalidParameter);
+                            }
+                            value_stack.pop_types(&[I32, V128])?;
+                            let mnemonic = if opcode_fd < V128Store8Lane {
+                                WasmIntMnemonic::V128LoadLane
+                            } else {
+                                WasmIntMnemonic::V128StoreLane
+                            };
+                            int_codes.push(WasmImc::new(
+                                position,
+                                opcode,
+                                mnemonic,
+                                value_stack.len(),
+                                arg.offset as u64 | (opcode_fd as u64) << 32 | lane << 48,
+                            ));
+                            if mnemonic == WasmIntMnemonic::V128LoadLane {
+                                value_stack.push(V128);
+                            }
+                        }
+
+                        V128Const | I8x16Shuffle => {
+                            let imm = stream.read_v128()?;
+                            let mnemonic = if opcode_fd == V128Const {
+                                WasmIntMnemonic::V128Const
+                            } else {
+                                if imm.to_le_bytes().iter().any(|lane| *lane >= 32) {
+                                    return Err(WasmDecodeErrorType::InvalidParameter);
+                                }
+                                value_stack.pop_types(&[V128, V128])?;
+                                WasmIntMnemonic::I8x16Shuffle
+                            };
+                            let param_position = ext_params.len();
+                            for i in 0..4 {
+                                ext_params.push((imm >> (i * 32)) as u32 as usize);
+                            }
+                            int_codes.push(WasmImc::new(
+                                position,
+                                opcode,
+                                mnemonic,
+                                value_stack.len(),
+                                param_position as u64,
+                            ));
+                            value_stack.push(V128);
+                        }
+
+                        _ => {
+                            // (params, result, number of lanes of the lane index)
+                            let (params, result, lanes): (&[WasmValType], _, _) = match opcode_fd {
+                                I8x16Splat | I16x8Splat | I32x4Splat => (&[I32], V128, 0),
+                                I64x2Splat => (&[I64], V128, 0),
+                                F32x4Splat => (&[F32], V128, 0),
+                                F64x2Splat => (&[F64], V128, 0),
+                                I8x16ExtractLaneS | I8x16ExtractLaneU => (&[V128], I32, 16),
+                                I16x8ExtractLaneS | I16x8ExtractLaneU => (&[V128], I32, 8),
+                                I32x4ExtractLane => (&[V128], I32, 4),
+                                I64x2ExtractLane => (&[V128], I64, 2),
+                                F32x4ExtractLane => (&[V128], F32, 4),
+                                F64x2ExtractLane => (&[V128], F64, 2),
+                                I8x16ReplaceLane => (&[V128, I32], V128, 16),
+                                I16x8ReplaceLane => (&[V128, I32], V128, 8),
+                                I32x4ReplaceLane => (&[V128, I32], V128, 4),
+                                I64x2ReplaceLane => (&[V128, I64], V128, 2),
+                                F32x4ReplaceLane => (&[V128, F32], V128, 4),
+                                F64x2ReplaceLane => (&[V128, F64], V128, 2),
+
+                                V128AnyTrue | I8x16AllTrue | I8x16Bitmask | I16x8AllTrue
+                                | I16x8Bitmask | I32x4AllTrue | I32x4Bitmask | I64x2AllTrue
+                                | I64x2Bitmask => (&[V128], I32, 0),
+
+                                I8x16Shl | I8x16ShrS | I8x16ShrU | I16x8Shl | I16x8ShrS
+                                | I16x8ShrU | I32x4Shl | I32x4ShrS | I32x4ShrU | I64x2Shl
+                                | I64x2ShrS | I64x2ShrU => (&[V128, I32], V128, 0),
+
+                                V128Bitselect => (&[V128, V128, V128], V128, 0),
+
+                                V128Not
+                                | F32x4DemoteF64x2Zero
+                                | F64x2PromoteLowF32x4
+                                | I8x16Abs
+                                | I8x16Neg
+                                | I8x16Popcnt
+                                | F32x4Ceil
+                                | F32x4Floor
+                                | F32x4Trunc
+                                | F32x4Nearest
+                                | F64x2Ceil
+                                | F64x2Floor
+                                | F64x2Trunc
+                                | F64x2Nearest
+                                | I16x8ExtaddPairwiseI8x16S
+                                | I16x8ExtaddPairwiseI8x16U
+                                | I32x4ExtaddPairwiseI16x8S
+                                | I32x4ExtaddPairwiseI16x8U
+                                | I16x8Abs
+                                | I16x8Neg
+                                | I16x8ExtendLowI8x16S
+                                | I16x8ExtendHighI8x16S
+                                | I16x8ExtendLowI8x16U
+                                | I16x8ExtendHighI8x16U
+                                | I32x4Abs
+                                | I32x4Neg
+                                | I32x4ExtendLowI16x8S
+                                | I32x4ExtendHighI16x8S
+                                | I32x4ExtendLowI16x8U
+                                | I32x4ExtendHighI16x8U
+                                | I64x2Abs
+                                | I64x2Neg
+                                | I64x2ExtendLowI32x4S
+                                | I64x2ExtendHighI32x4S
+                                | I64x2ExtendLowI32x4U
+                                | I64x2ExtendHighI32x4U
+                                | F32x4Abs
+                                | F32x4Neg
+                                | F32x4Sqrt
+                                | F64x2Abs
+                                | F64x2Neg
+                                | F64x2Sqrt
+                                | I32x4TruncSatF32x4S
+                                | I32x4TruncSatF32x4U
+                                | F32x4ConvertI32x4S
+                                | F32x4ConvertI32x4U
+                                | I32x4TruncSatF64x2SZero
+                                | I32x4TruncSatF64x2UZero
+                                | F64x2ConvertLowI32x4S
+                                | F64x2ConvertLowI32x4U => (&[V128], V128, 0),
+
+                                _ => (&[V128, V128], V128, 0),
+                            };
+                            let lane = if lanes > 0 {
+                                let lane = stream.read_byte()? as u64;
+                                if lane >= lanes {
+                                    return Err(WasmDecodeErrorType::InvalidParameter);
+                                }
+                                lane
+                            } else {
+                                0
+                            };
+                            value_stack.pop_types(params)?;
+                            int_codes.push(WasmImc::new(
+                                position,
+                                opcode,
+                                WasmIntMnemonic::Simd,
+                                value_stack.len(),
+                                opcode_fd as u64 | lane << 32,
+                            ));
+                            value_stack.push(result);
+                        }
                     }
-                    value_stack.push(WasmValType::F64);
                 }
 
-                // [f32] -> [f64]
-                #[cfg(feature = "float")]
-                WasmOpcode::F64PromoteF32 => {
-                    let a = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
-                    if a != WasmValType::F32 {
-                        return Err(WasmDecodeErrorType::TypeMismatch);
+                WasmOpcode::PrefixFE => {
+                    let opcode_fe = stream.read_unsigned().and_then(|v| {
+                        WasmOpcodeFE::new(v as u32).ok_or(WasmDecodeErrorType::InvalidBytecode)
+                    })?;
+                    if !module.has_memory() {
+                        return Err(WasmDecodeErrorType::OutOfMemory);
+                    }
+                    if opcode_fe == WasmOpcodeFE::AtomicFence {
+                        if stream.read_byte()? != 0 {
+                            return Err(WasmDecodeErrorType::InvalidParameter);
+                        }
+                        int_codes.push(WasmImc::new(
+                            position,
+                            opcode,
+                            WasmIntMnemonic::AtomicFence,
+                            value_stack.len(),
+                            0,
+                        ));
+                    } else {
+                        use WasmIntMnemonic::*;
+                        use WasmValType::{I32, I64};
+                        let mut rmw_op = 0;
+                        // (mnemonic, natural alignment, params, result)
+                        let (mnemonic, natural, params, result): (_, _, &[WasmValType], _) =
+                            match opcode_fe {
+                                WasmOpcodeFE::MemoryAtomicNotify => {
+                                    (MemoryAtomicNotify, 2, &[I32, I32], Some(I32))
+                                }
+                                WasmOpcodeFE::MemoryAtomicWait32 => {
+                                    (MemoryAtomicWait32, 2, &[I32, I32, I64], Some(I32))
+                                }
+                                WasmOpcodeFE::MemoryAtomicWait64 => {
+                                    (MemoryAtomicWait64, 3, &[I32, I64, I64], Some(I32))
+                                }
+                                WasmOpcodeFE::I32AtomicLoad => (AtomicLoad32, 2, &[I32], Some(I32)),
+                                WasmOpcodeFE::I64AtomicLoad => (AtomicLoad64, 3, &[I32], Some(I64)),
+                                WasmOpcodeFE::I32AtomicLoad8U => {
+                                    (AtomicLoad8, 0, &[I32], Some(I32))
+                                }
+                                WasmOpcodeFE::I32AtomicLoad16U => {
+                                    (AtomicLoad16, 1, &[I32], Some(I32))
+                                }
+                                WasmOpcodeFE::I64AtomicLoad8U => {
+                                    (AtomicLoad8, 0, &[I32], Some(I64))
+                                }
+                                WasmOpcodeFE::I64AtomicLoad16U => {
+                                    (AtomicLoad16, 1, &[I32], Some(I64))
+                                }
+                                WasmOpcodeFE::I64AtomicLoad32U => {
+                                    (AtomicLoad32, 2, &[I32], Some(I64))
+                                }
+                                WasmOpcodeFE::I32AtomicStore => {
+                                    (AtomicStore32, 2, &[I32, I32], None)
+                                }
+                                WasmOpcodeFE::I64AtomicStore => {
+                                    (AtomicStore64, 3, &[I32, I64], None)
+                                }
+                                WasmOpcodeFE::I32AtomicStore8 => {
+                                    (AtomicStore8, 0, &[I32, I32], None)
+                                }
+                                WasmOpcodeFE::I32AtomicStore16 => {
+                                    (AtomicStore16, 1, &[I32, I32], None)
+                                }
+                                WasmOpcodeFE::I64AtomicStore8 => {
+                                    (AtomicStore8, 0, &[I32, I64], None)
+                                }
+                                WasmOpcodeFE::I64AtomicStore16 => {
+                                    (AtomicStore16, 1, &[I32, I64], None)
+                                }
+                                WasmOpcodeFE::I64AtomicStore32 => {
+                                    (AtomicStore32, 2, &[I32, I64], None)
+                                }
+                                _ => {
+                                    // The read-modify-write instructions are in groups of seven widths,
+                                    // ordered by `add`, `sub`, `and`, `or`, `xor`, `xchg` and `cmpxchg`.
+                                    let index =
+                                        opcode_fe as u32 - WasmOpcodeFE::I32AtomicRmwAdd as u32;
+                                    let group = index / 7;
+                                    let is_cmpxchg = group == 6;
+                                    rmw_op = group as u64;
+                                    let (natural, val_type) = match index % 7 {
+                                        0 => (2, I32),
+                                        1 => (3, I64),
+                                        2 => (0, I32),
+                                        3 => (1, I32),
+                                        4 => (0, I64),
+                                        5 => (1, I64),
+                                        _ => (2, I64),
+                                    };
+                                    let mnemonic = match (is_cmpxchg, natural) {
+                                        (false, 0) => AtomicRmw8,
+                                        (false, 1) => AtomicRmw16,
+                                        (false, 2) => AtomicRmw32,
+                                        (false, _) => AtomicRmw64,
+                                        (true, 0) => AtomicCmpxchg8,
+                                        (true, 1) => AtomicCmpxchg16,
+                                        (true, 2) => AtomicCmpxchg32,
+                                        (true, _) => AtomicCmpxchg64,
+                                    };
+                                    let params: &[WasmValType] = match (is_cmpxchg, val_type) {
+                                        (false, I32) => &[I32, I32],
+                                        (false, _) => &[I32, I64],
+                                        (true, I32) => &[I32, I32, I32],
+                                        (true, _) => &[I32, I64, I64],
+                                    };
+                                    (mnemonic, natural, params, Some(val_type))
+                                }
+                            };
+                        let arg = stream.read_memarg()?.check_atomic_alignment(natural)?;
+                        value_stack.pop_types(params)?;
+                        int_codes.push(WasmImc::new(
+                            position,
+                            opcode,
+                            mnemonic,
+                            value_stack.len(),
+                            arg.offset as u64 | rmw_op << 32,
+                        ));
+                        if let Some(result) = result {
+                            value_stack.push(result);
+                        }
                     }
-                    value_stack.push(WasmValType::F64);
                 }
 
                 #[allow(unreachable_patterns)]
@@ -3586,23 +6506,6 @@ impl WasmCodeBlock {
             }
         }
 
-        if result_types.len() > 0 {
-            if result_types.len() != value_stack.len() {
-                return Err(WasmDecodeErrorType::TypeMismatch);
-            }
-
-            for result_type in result_types {
-                let val = value_stack.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
-                if *result_type != val {
-                    return Err(WasmDecodeErrorType::TypeMismatch);
-                }
-            }
-        } else {
-            if value_stack.len() > 0 {
-                return Err(WasmDecodeErrorType::InvalidStackLevel);
-            }
-        }
-
         macro_rules! fused_const_opr {
             ( $array:ident, $index:expr, $opr:expr ) => {
                 let next = $index + 1;
@@ -3620,6 +6523,8 @@ impl WasmCodeBlock {
             };
         }
 
+        *current_position = None;
+
         // fused instructions
         if int_codes.len() > 2 {
             let limit = int_codes.len() - 1;
@@ -3684,11 +6589,25 @@ impl WasmCodeBlock {
                     let ref mut block = blocks[target].borrow_mut();
                     block.start_position = actual_len;
                 }
+                Else => {
+                    let target = code.param1() as usize;
+                    let ref mut block = blocks[target].borrow_mut();
+                    block.else_position = actual_len;
+                }
                 End => {
                     let target = code.param1() as usize;
                     let ref mut block = blocks[target].borrow_mut();
                     block.end_position = actual_len;
                 }
+                Catch => {
+                    let handler = &mut handlers[code.param1() as u32 as usize];
+                    let clause = (code.param1() >> 32) as usize;
+                    // The first catch-clause ends the try-clause
+                    if clause == 0 {
+                        handler.end_position = actual_len;
+                    }
+                    handler.catches[clause].1 = actual_len;
+                }
                 _ => {
                     int_codes[actual_len] = code;
                     actual_len += 1;
@@ -3710,11 +6629,28 @@ impl WasmCodeBlock {
                 code.set_param1(block.borrow().preferred_target() as u64);
             } else {
                 match code.mnemonic() {
+                    If => {
+                        let target = code.param1() as usize;
+                        let block = blocks.get(target).ok_or(WasmDecodeErrorType::OutOfBranch)?;
+                        let block = block.borrow();
+                        let else_position = if block.else_position > 0 {
+                            block.else_position
+                        } else {
+                            block.end_position
+                        };
+                        code.set_param1(else_position as u64);
+                    }
+                    BrUnwind | BrIfUnwind => {
+                        let index = code.param1() as usize;
+                        let target = ext_params[index];
+                        let block = blocks.get(target).ok_or(WasmDecodeErrorType::OutOfBranch)?;
+                        ext_params[index] = block.borrow().preferred_target();
+                    }
                     BrTable => {
                         let table_position = code.param1() as usize;
                         let table_len = ext_params[table_position];
                         for i in 0..table_len {
-                            let index = table_position + i + 1;
+                            let index = table_position + i * 3 + 1;
                             let target = ext_params[index];
                             let block =
                                 blocks.get(target).ok_or(WasmDecodeErrorType::OutOfBranch)?;
@@ -3726,6 +6662,43 @@ impl WasmCodeBlock {
             }
         }
 
+        let handlers = handlers
+            .into_iter()
+            .map(|handler| {
+                let block = blocks[handler.block].borrow();
+                let delegate = handler.delegate.map(|target| match target {
+                    Some(target) => {
+                        let target = blocks[target].borrow();
+                        match target.handler {
+                            Some(index) if target.inst_type == BlockInstType::Try => {
+                                WasmDelegateTarget::Handler(index)
+                            }
+                            _ => WasmDelegateTarget::Block(
+                                target.start_position,
+                                target.end_position,
+                            ),
+                        }
+                    }
+                    None => WasmDelegateTarget::Caller,
+                });
+                WasmExceptionHandler {
+                    start: block.start_position,
+                    end: if delegate.is_some() {
+                        block.end_position
+                    } else {
+                        handler.end_position
+                    },
+                    stack_level: block.stack_level,
+                    catches: handler.catches.into_boxed_slice(),
+                    delegate,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        if value_stack.has_v128 || local_var_types.contains(&WasmValType::V128) {
+            flags.insert(WasmBlockFlag::USES_V128);
+        }
+
         Ok(Self {
             func_index,
             local_types: local_var_types.into_boxed_slice(),
@@ -3733,6 +6706,10 @@ impl WasmCodeBlock {
             flags,
             int_codes: int_codes.into_boxed_slice(),
             ext_params: ext_params.into_boxed_slice(),
+            handlers: handlers.into_boxed_slice(),
+            source: Box::new([]),
+            #[cfg(target_arch = "x86_64")]
+            jit_code: None,
         })
     }
 }
@@ -3743,6 +6720,7 @@ pub enum BlockInstType {
     Block,
     Loop,
     If,
+    Try,
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -3750,6 +6728,10 @@ struct WasmBlockContext {
     pub inst_type: BlockInstType,
     pub block_type: WasmBlockType,
     pub stack_level: usize,
+    /// `else` or `catch_all` has appeared
+    pub has_else: bool,
+    /// Index of the exception handler, which is made when the try-clause ends
+    pub handler: Option<usize>,
     pub start_position: usize,
     pub end_position: usize,
     pub else_position: usize,
@@ -3764,6 +6746,194 @@ impl WasmBlockContext {
             self.end_position
         }
     }
+
+    /// Returns the types of values carried by a branch to this block.
+    #[inline]
+    pub fn label_types<'a>(
+        &self,
+        module: &'a WasmModule,
+    ) -> Result<&'a [WasmValType], WasmDecodeErrorType> {
+        let (params, results) = self.block_type.signature(module)?;
+        if self.inst_type == BlockInstType::Loop {
+            Ok(params)
+        } else {
+            Ok(results)
+        }
+    }
+}
+
+/// Exception handler being generated
+struct WasmHandlerContext {
+    block: usize,
+    end_position: usize,
+    /// Tags and positions of the catch-clauses
+    catches: Vec<(Option<usize>, usize)>,
+    /// The block that `delegate` refers to, or `None` for the caller
+    delegate: Option<Option<usize>>,
+}
+
+/// A type of operand in the validation stack
+#[derive(Debug, Copy, Clone)]
+enum WasmStackType {
+    Known(WasmValType),
+    /// Operands popped in unreachable code, which match any type
+    Unknown,
+}
+
+impl From<WasmValType> for WasmStackType {
+    #[inline]
+    fn from(v: WasmValType) -> Self {
+        Self::Known(v)
+    }
+}
+
+impl PartialEq for WasmStackType {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        match (*self, *other) {
+            (Self::Known(lhs), Self::Known(rhs)) => lhs == rhs,
+            _ => true,
+        }
+    }
+}
+
+impl PartialEq<WasmValType> for WasmStackType {
+    #[inline]
+    fn eq(&self, other: &WasmValType) -> bool {
+        match *self {
+            Self::Known(lhs) => lhs == *other,
+            Self::Unknown => true,
+        }
+    }
+}
+
+/// Operand stack for validation
+///
+/// After an unconditional branch, the rest of the block is unreachable and
+/// the stack becomes polymorphic, so any number of operands of any type can be popped.
+struct WasmValidationStack {
+    stack: Vec<WasmStackType>,
+    /// height and reachability of each control frame
+    frames: Vec<(usize, bool)>,
+    /// whether or not a `v128` has ever been pushed
+    has_v128: bool,
+}
+
+impl WasmValidationStack {
+    #[inline]
+    fn new() -> Self {
+        Self {
+            stack: Vec::new(),
+            frames: vec![(0, false)],
+            has_v128: false,
+        }
+    }
+
+    #[inline]
+    fn current_frame(&self) -> (usize, bool) {
+        *self.frames.last().unwrap()
+    }
+
+    #[inline]
+    fn len(&self) -> usize {
+        self.stack.len()
+    }
+
+    #[inline]
+    fn push<T: Into<WasmStackType>>(&mut self, val: T) {
+        let val = val.into();
+        if let WasmStackType::Known(WasmValType::V128) = val {
+            self.has_v128 = true;
+        }
+        self.stack.push(val);
+    }
+
+    fn pop(&mut self) -> Option<WasmStackType> {
+        let (height, unreachable) = self.current_frame();
+        if self.stack.len() > height {
+            self.stack.pop()
+        } else if unreachable {
+            Some(WasmStackType::Unknown)
+        } else {
+            None
+        }
+    }
+
+    fn last(&mut self) -> Option<&WasmStackType> {
+        let (height, unreachable) = self.current_frame();
+        if self.stack.len() <= height {
+            if unreachable {
+                self.stack.push(WasmStackType::Unknown);
+            } else {
+                return None;
+            }
+        }
+        self.stack.last()
+    }
+
+    #[inline]
+    fn truncate(&mut self, len: usize) {
+        self.stack.truncate(len);
+    }
+
+    #[inline]
+    fn extend_from_slice(&mut self, types: &[WasmValType]) {
+        for val_type in types {
+            self.push(*val_type);
+        }
+    }
+
+    /// Pops the operands with checking their types
+    fn pop_types(&mut self, types: &[WasmValType]) -> Result<(), WasmDecodeErrorType> {
+        for val_type in types.iter().rev() {
+            let val = self.pop().ok_or(WasmDecodeErrorType::OutOfStack)?;
+            if val != *val_type {
+                return Err(WasmDecodeErrorType::TypeMismatch);
+            }
+        }
+        Ok(())
+    }
+
+    /// Enters a new control frame
+    #[inline]
+    fn enter(&mut self, height: usize) {
+        self.frames.push((height, false));
+    }
+
+    /// Leaves the current control frame
+    #[inline]
+    fn leave(&mut self) {
+        if self.frames.len() > 1 {
+            self.frames.pop();
+        }
+    }
+
+    /// Checks that the current frame has no extra operands
+    #[inline]
+    fn check_frame_end(&self) -> Result<(), WasmDecodeErrorType> {
+        let (height, _) = self.current_frame();
+        if self.stack.len() == height {
+            Ok(())
+        } else {
+            Err(WasmDecodeErrorType::InvalidStackLevel)
+        }
+    }
+
+    /// Marks the rest of the current frame as unreachable
+    #[inline]
+    fn set_unreachable(&mut self) {
+        let (height, _) = self.current_frame();
+        self.stack.truncate(height);
+        self.frames.last_mut().unwrap().1 = true;
+    }
+
+    /// Resets the current frame for the else-clause
+    #[inline]
+    fn reset_frame(&mut self) {
+        let (height, _) = self.current_frame();
+        self.stack.truncate(height);
+        self.frames.last_mut().unwrap().1 = false;
+    }
 }
 
 #[derive(Copy, Clone)]
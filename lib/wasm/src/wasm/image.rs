@@ -0,0 +1,626 @@
+//! Precompiled image of WebAssembly modules
+//!
+//! An image keeps the intermediate codes of a loaded module along with its types, tables and data layout,
+//! so that loading it skips the validation and the code generation of every function body.
+//!
+//! The header is stamped with the version of the format, the number of the intermediate mnemonics
+//! and the digest of the source binary, and any mismatch makes the image rejected.
+//! The rest is encoded with unsigned LEB128.
+
+use super::*;
+
+/// Magic number of the precompiled image
+const IMAGE_MAGIC: u32 = 0x6D69_7700;
+/// Version of the image format, which must be updated when the layout changes.
+const IMAGE_VERSION: u16 = 2;
+/// Magic(4) + Version(2) + Mnemonics(2) + Digest(8)
+const IMAGE_HEADER_SIZE: usize = 16;
+
+impl WasmLoader {
+    /// Returns the digest of the binary to stamp and look up its image.
+    ///
+    /// This is 64-bit FNV-1a, which is not a cryptographic hash,
+    /// so a cache of images must also compare the binary before it uses the image.
+    pub fn digest(blob: &[u8]) -> u64 {
+        const FNV_OFFSET_BASIS: u64 = 0xCBF2_9CE4_8422_2325;
+        const FNV_PRIME: u64 = 0x0000_0100_0000_01B3;
+        blob.iter().fold(FNV_OFFSET_BASIS, |acc, v| {
+            (acc ^ *v as u64).wrapping_mul(FNV_PRIME)
+        })
+    }
+
+    /// Identify the image format and returns the digest of the source binary
+    pub fn identity_image(image: &[u8]) -> Option<u64> {
+        if image.len() >= IMAGE_HEADER_SIZE
+            && LE::read_u32(&image[0..4]) == IMAGE_MAGIC
+            && LE::read_u16(&image[4..6]) == IMAGE_VERSION
+            && LE::read_u16(&image[6..8]) == WasmIntMnemonic::LAST as u16
+        {
+            Some(LE::read_u64(&image[8..16]))
+        } else {
+            None
+        }
+    }
+
+    /// Instantiate wasm modules from the precompiled image
+    pub fn instantiate_image<F>(
+        image: &[u8],
+        digest: u64,
+        resolver: F,
+    ) -> Result<WasmModule, WasmDecodeError>
+    where
+        F: FnMut(&str, &str, &WasmType) -> Result<WasmDynFunc, WasmDecodeErrorType> + Copy,
+    {
+        let mut loader = Self::new();
+        loader
+            .load_image(image, digest, resolver)
+            .map(|_| loader.module)
+    }
+
+    /// Load wasm from the precompiled image made by [WasmModule::to_image].
+    ///
+    /// `digest` is the [WasmLoader::digest] of the binary to be loaded,
+    /// the image is rejected as `BadExecutable` if it was made from another binary or by another version.
+    /// The imported functions are resolved again since they cannot be saved.
+    pub fn load_image<F>(
+        &mut self,
+        image: &[u8],
+        digest: u64,
+        resolver: F,
+    ) -> Result<(), WasmDecodeError>
+    where
+        F: FnMut(&str, &str, &WasmType) -> Result<WasmDynFunc, WasmDecodeErrorType> + Copy,
+    {
+        if Self::identity_image(image) != Some(digest) {
+            return Err(WasmDecodeErrorType::BadExecutable.into());
+        }
+        let mut stream = Leb128Stream::from_slice(&image[IMAGE_HEADER_SIZE..]);
+        self.parse_image(&mut stream, resolver)
+            .map_err(|err| WasmDecodeError::new(err, None, stream.position(), None))?;
+
+        self.validate()
+            .map_err(|err| WasmDecodeError::new(err, None, 0, None))?;
+
+        Ok(())
+    }
+
+    fn parse_image<F>(
+        &mut self,
+        stream: &mut Leb128Stream,
+        mut resolver: F,
+    ) -> Result<(), WasmDecodeErrorType>
+    where
+        F: FnMut(&str, &str, &WasmType) -> Result<WasmDynFunc, WasmDecodeErrorType> + Copy,
+    {
+        let memory_quota = self.memory_quota;
+        let module = &mut self.module;
+
+        let n_items = stream.read_unsigned()? as usize;
+        for _ in 0..n_items {
+            let param_types = stream.read_val_types()?;
+            let result_types = stream.read_val_types()?;
+            module.types.push(WasmType {
+                param_types,
+                result_types,
+            });
+        }
+
+        let n_items = stream.read_unsigned()? as usize;
+        for _ in 0..n_items {
+            let mod_name = stream.get_string()?.to_string();
+            let name = stream.get_string()?.to_string();
+            let type_index = stream.read_unsigned()? as usize;
+            let func_type = module
+                .types
+                .get(type_index)
+                .ok_or(WasmDecodeErrorType::InvalidType)?;
+            let dlink = resolver(&mod_name, &name, func_type)?;
+            module.functions.push(WasmFunction::from_import(
+                type_index,
+                func_type,
+                module.n_ext_func,
+                dlink,
+            ));
+            module.imports.push(WasmImport {
+                mod_name,
+                name,
+                index: WasmImportIndex::Type(type_index),
+                func_ref: module.n_ext_func,
+            });
+            module.n_ext_func += 1;
+        }
+
+        let n_items = stream.read_unsigned()? as usize;
+        for index in module.n_ext_func..module.n_ext_func + n_items {
+            let type_index = stream.read_unsigned()? as usize;
+            let func_type = module
+                .types
+                .get(type_index)
+                .ok_or(WasmDecodeErrorType::InvalidType)?;
+            let mut function = WasmFunction::internal(index, type_index, func_type);
+            function.code_block = Some(Arc::new(WasmCodeBlock::from_image(index, stream)?));
+            module.functions.push(function);
+        }
+        for function in module.functions.iter_mut() {
+            if let Some(index) = stream.read_ref()? {
+                function.origin = WasmFunctionOrigin::Export(index);
+            }
+        }
+
+        let n_items = stream.read_unsigned()? as usize;
+        for _ in 0..n_items {
+            let ref_type = stream.read_val_type()?;
+            let limit = stream.read_limit()?;
+            let n_elements = stream.read_unsigned()? as usize;
+            let mut elements = Vec::with_capacity(n_elements);
+            for _ in 0..n_elements {
+                elements.push(stream.read_ref()?);
+            }
+            module.tables.push(WasmTable {
+                ref_type,
+                limit,
+                table: Rc::new(UnsafeCell::new(elements)),
+            });
+        }
+
+        let n_items = stream.read_unsigned()? as usize;
+        for _ in 0..n_items {
+            let limit = stream.read_limit()?;
+            let memory = Self::new_memory(&module.memories, memory_quota, limit)?;
+            module.memories.push(memory);
+        }
+
+        let n_items = stream.read_unsigned()? as usize;
+        for _ in 0..n_items {
+            let is_mutable = stream.read_byte()? != 0;
+            let value = stream.read_value()?;
+            module.globals.push(WasmGlobal::new(value, is_mutable));
+        }
+
+        let n_items = stream.read_unsigned()? as usize;
+        for _ in 0..n_items {
+            module.tags.push(stream.read_unsigned()? as usize);
+        }
+
+        let n_items = stream.read_unsigned()? as usize;
+        for _ in 0..n_items {
+            let ref_type = stream.read_val_type()?;
+            let n_elements = stream.read_unsigned()? as usize;
+            let mut items = Vec::with_capacity(n_elements);
+            for _ in 0..n_elements {
+                items.push(stream.read_ref()?);
+            }
+            module.elements.push(WasmElement::new(ref_type, items));
+        }
+
+        let n_items = stream.read_unsigned()? as usize;
+        for _ in 0..n_items {
+            let data = match stream.read_byte()? {
+                0 => WasmData::new(stream.read_bytes()?.to_vec()),
+                1 => {
+                    let memidx = stream.read_unsigned()? as usize;
+                    let offset = stream.read_unsigned()? as usize;
+                    let src = stream.read_bytes()?;
+                    module
+                        .memories
+                        .get(memidx)
+                        .ok_or(WasmDecodeErrorType::InvalidParameter)?
+                        .write_slice(offset, src)
+                        .map_err(|_| WasmDecodeErrorType::InvalidData)?;
+                    WasmData::active(memidx, offset, src.len())
+                }
+                _ => return Err(WasmDecodeErrorType::UnexpectedToken),
+            };
+            module.data.push(data);
+        }
+
+        let n_items = stream.read_unsigned()? as usize;
+        for _ in 0..n_items {
+            let name = stream.get_string()?.to_string();
+            let index = stream.read_unsigned()? as usize;
+            let index = match stream.read_byte()? {
+                0 => WasmExportIndex::Function(index),
+                1 => WasmExportIndex::Table(index),
+                2 => WasmExportIndex::Memory(index),
+                3 => WasmExportIndex::Global(index),
+                4 => WasmExportIndex::Tag(index),
+                _ => return Err(WasmDecodeErrorType::UnexpectedToken),
+            };
+            module.exports.push(WasmExport { name, index });
+        }
+
+        module.start = stream.read_ref()?;
+        module.data_count = stream.read_ref()?;
+
+        if stream.read_byte()? != 0 {
+            let name = stream.get_string()?;
+            let name_module = if name.is_empty() {
+                None
+            } else {
+                Some(name.to_string())
+            };
+            let n_items = stream.read_unsigned()? as usize;
+            let mut functions = Vec::with_capacity(n_items);
+            for _ in 0..n_items {
+                let index = stream.read_unsigned()? as usize;
+                let name = stream.get_string()?.to_string();
+                functions.push((index, name));
+            }
+            module.names = Some(WasmName {
+                module: name_module,
+                functions,
+            });
+        }
+
+        if stream.is_eof() {
+            Ok(())
+        } else {
+            Err(WasmDecodeErrorType::UnexpectedToken)
+        }
+    }
+}
+
+impl WasmModule {
+    /// Makes the precompiled image of this module, stamped with the [WasmLoader::digest] of its binary.
+    ///
+    /// The contents of the memories, tables and globals are saved as they are now,
+    /// so this must be called on a freshly loaded module before any function is invoked.
+    /// Modules that import memories, tables or globals are not supported,
+    /// since their contents belong to the host.
+    pub fn to_image(&self, digest: u64) -> Result<Vec<u8>, WasmDecodeErrorType> {
+        let mut image = Vec::new();
+        image.extend_from_slice(&IMAGE_MAGIC.to_le_bytes());
+        image.extend_from_slice(&IMAGE_VERSION.to_le_bytes());
+        image.extend_from_slice(&(WasmIntMnemonic::LAST as u16).to_le_bytes());
+        image.extend_from_slice(&digest.to_le_bytes());
+
+        write_unsigned(&mut image, self.types.len() as u64);
+        for func_type in &self.types {
+            write_val_types(&mut image, func_type.param_types());
+            write_val_types(&mut image, func_type.result_types());
+        }
+
+        write_unsigned(&mut image, self.imports.len() as u64);
+        for import in &self.imports {
+            let type_index = match import.index() {
+                WasmImportIndex::Type(v) => v,
+                _ => return Err(WasmDecodeErrorType::NotSupprted),
+            };
+            write_bytes(&mut image, import.mod_name().as_bytes());
+            write_bytes(&mut image, import.name().as_bytes());
+            write_unsigned(&mut image, type_index as u64);
+        }
+
+        let functions = &self.functions[self.n_ext_func..];
+        write_unsigned(&mut image, functions.len() as u64);
+        for function in functions {
+            write_unsigned(&mut image, function.type_index() as u64);
+            function
+                .code_block()
+                .ok_or(WasmDecodeErrorType::InvalidFunction)?
+                .write_image(&mut image);
+        }
+        for function in &self.functions {
+            let export = match function.origin() {
+                WasmFunctionOrigin::Export(v) => Some(v),
+                _ => None,
+            };
+            write_ref(&mut image, export);
+        }
+
+        write_unsigned(&mut image, self.tables.len() as u64);
+        for table in &self.tables {
+            image.push(table.ref_type() as u8);
+            write_limit(&mut image, table.limit());
+            write_unsigned(&mut image, table.len() as u64);
+            for element in table.elements() {
+                write_ref(&mut image, *element);
+            }
+        }
+
+        write_unsigned(&mut image, self.memories.len() as u64);
+        for memory in &self.memories {
+            write_limit(&mut image, memory.limit());
+        }
+
+        write_unsigned(&mut image, self.globals.len() as u64);
+        for global in &self.globals {
+            image.push(global.is_mutable() as u8);
+            write_value(&mut image, global.value());
+        }
+
+        write_unsigned(&mut image, self.tags.len() as u64);
+        for tag in &self.tags {
+            write_unsigned(&mut image, *tag as u64);
+        }
+
+        write_unsigned(&mut image, self.elements.len() as u64);
+        for element in &self.elements {
+            image.push(element.ref_type() as u8);
+            write_unsigned(&mut image, element.items().len() as u64);
+            for item in element.items() {
+                write_ref(&mut image, *item);
+            }
+        }
+
+        write_unsigned(&mut image, self.data.len() as u64);
+        for data in &self.data {
+            match data.mode() {
+                WasmDataMode::Passive => {
+                    image.push(0);
+                    write_bytes(&mut image, data.bytes());
+                }
+                WasmDataMode::Active {
+                    memory,
+                    offset,
+                    len,
+                } => {
+                    // Active segments are not kept, so the bytes are read back from the memory.
+                    let src = self
+                        .memories
+                        .get(memory)
+                        .and_then(|v| v.memory().get(offset..offset + len))
+                        .ok_or(WasmDecodeErrorType::InvalidData)?;
+                    image.push(1);
+                    write_unsigned(&mut image, memory as u64);
+                    write_unsigned(&mut image, offset as u64);
+                    write_bytes(&mut image, src);
+                }
+            }
+        }
+
+        write_unsigned(&mut image, self.exports.len() as u64);
+        for export in &self.exports {
+            write_bytes(&mut image, export.name().as_bytes());
+            let (index, kind) = match export.index() {
+                WasmExportIndex::Function(v) => (v, 0),
+                WasmExportIndex::Table(v) => (v, 1),
+                WasmExportIndex::Memory(v) => (v, 2),
+                WasmExportIndex::Global(v) => (v, 3),
+                WasmExportIndex::Tag(v) => (v, 4),
+            };
+            write_unsigned(&mut image, index as u64);
+            image.push(kind);
+        }
+
+        write_ref(&mut image, self.start);
+        write_ref(&mut image, self.data_count);
+
+        match self.names.as_ref() {
+            Some(names) => {
+                image.push(1);
+                write_bytes(&mut image, names.module().unwrap_or("").as_bytes());
+                write_unsigned(&mut image, names.functions().len() as u64);
+                for (index, name) in names.functions() {
+                    write_unsigned(&mut image, *index as u64);
+                    write_bytes(&mut image, name.as_bytes());
+                }
+            }
+            None => image.push(0),
+        }
+
+        Ok(image)
+    }
+}
+
+impl WasmCodeBlock {
+    fn from_image(
+        func_index: usize,
+        stream: &mut Leb128Stream,
+    ) -> Result<Self, WasmDecodeErrorType> {
+        let local_types = stream.read_val_types()?;
+        let max_stack = stream.read_unsigned()? as usize;
+        let flags = WasmBlockFlag::from_bits_truncate(stream.read_unsigned()? as usize);
+
+        let n_codes = stream.read_unsigned()? as usize;
+        let mut int_codes = Vec::with_capacity(n_codes);
+        for _ in 0..n_codes {
+            let source = stream.read_unsigned()? as u32;
+            let mnemonic = WasmIntMnemonic::from_u16(stream.read_unsigned()? as u16)
+                .ok_or(WasmDecodeErrorType::InvalidBytecode)?;
+            let stack_level = stream.read_unsigned()? as usize;
+            let param1 = stream.read_unsigned()?;
+            int_codes.push(WasmImc {
+                source,
+                mnemonic,
+                stack_level,
+                param1,
+            });
+        }
+
+        let n_params = stream.read_unsigned()? as usize;
+        let mut ext_params = Vec::with_capacity(n_params);
+        for _ in 0..n_params {
+            ext_params.push(stream.read_unsigned()? as usize);
+        }
+
+        let n_handlers = stream.read_unsigned()? as usize;
+        let mut handlers = Vec::with_capacity(n_handlers);
+        for _ in 0..n_handlers {
+            let start = stream.read_unsigned()? as usize;
+            let end = stream.read_unsigned()? as usize;
+            let stack_level = stream.read_unsigned()? as usize;
+            let n_catches = stream.read_unsigned()? as usize;
+            let mut catches = Vec::with_capacity(n_catches);
+            for _ in 0..n_catches {
+                let tag = stream.read_ref()?;
+                let target = stream.read_unsigned()? as usize;
+                catches.push((tag, target));
+            }
+            let delegate = match stream.read_byte()? {
+                0 => None,
+                1 => Some(WasmDelegateTarget::Caller),
+                2 => Some(WasmDelegateTarget::Handler(stream.read_unsigned()? as usize)),
+                3 => {
+                    let start = stream.read_unsigned()? as usize;
+                    let end = stream.read_unsigned()? as usize;
+                    Some(WasmDelegateTarget::Block(start, end))
+                }
+                _ => return Err(WasmDecodeErrorType::UnexpectedToken),
+            };
+            handlers.push(WasmExceptionHandler {
+                start,
+                end,
+                stack_level,
+                catches: catches.into_boxed_slice(),
+                delegate,
+            });
+        }
+
+        let source = stream.read_bytes()?.into();
+
+        Ok(Self {
+            func_index,
+            local_types,
+            max_stack,
+            flags,
+            int_codes: int_codes.into_boxed_slice(),
+            ext_params: ext_params.into_boxed_slice(),
+            handlers: handlers.into_boxed_slice(),
+            source,
+            #[cfg(target_arch = "x86_64")]
+            jit_code: None,
+        })
+    }
+
+    fn write_image(&self, image: &mut Vec<u8>) {
+        write_val_types(image, self.local_types());
+        write_unsigned(image, self.max_value_stack() as u64);
+        write_unsigned(image, self.flags.bits() as u64);
+
+        write_unsigned(image, self.int_codes.len() as u64);
+        for code in self.intermediate_codes() {
+            write_unsigned(image, code.source as u64);
+            write_unsigned(image, code.mnemonic() as u64);
+            write_unsigned(image, code.stack_level() as u64);
+            write_unsigned(image, code.param1());
+        }
+
+        write_unsigned(image, self.ext_params.len() as u64);
+        for param in self.ext_params() {
+            write_unsigned(image, *param as u64);
+        }
+
+        write_unsigned(image, self.handlers.len() as u64);
+        for handler in self.exception_handlers() {
+            write_unsigned(image, handler.start as u64);
+            write_unsigned(image, handler.end as u64);
+            write_unsigned(image, handler.stack_level as u64);
+            write_unsigned(image, handler.catches.len() as u64);
+            for (tag, target) in handler.catches() {
+                write_ref(image, *tag);
+                write_unsigned(image, *target as u64);
+            }
+            match handler.delegate {
+                None => image.push(0),
+                Some(WasmDelegateTarget::Caller) => image.push(1),
+                Some(WasmDelegateTarget::Handler(index)) => {
+                    image.push(2);
+                    write_unsigned(image, index as u64);
+                }
+                Some(WasmDelegateTarget::Block(start, end)) => {
+                    image.push(3);
+                    write_unsigned(image, start as u64);
+                    write_unsigned(image, end as u64);
+                }
+            }
+        }
+
+        write_bytes(image, self.source());
+    }
+}
+
+impl Leb128Stream<'_> {
+    fn read_val_type(&mut self) -> Result<WasmValType, WasmDecodeErrorType> {
+        self.read_byte()
+            .and_then(|v| WasmValType::from_u64(v as u64))
+    }
+
+    fn read_val_types(&mut self) -> Result<Box<[WasmValType]>, WasmDecodeErrorType> {
+        let n_items = self.read_unsigned()? as usize;
+        let mut vec = Vec::with_capacity(n_items);
+        for _ in 0..n_items {
+            vec.push(self.read_val_type()?);
+        }
+        Ok(vec.into_boxed_slice())
+    }
+
+    fn read_limit(&mut self) -> Result<WasmLimit, WasmDecodeErrorType> {
+        let min = self.read_unsigned()? as u32;
+        let max = self.read_unsigned()? as u32;
+        let shared = self.read_byte()? != 0;
+        Ok(WasmLimit { min, max, shared })
+    }
+
+    /// Reads a reference or an optional index, which is encoded as zero for `None` or the value plus one.
+    fn read_ref(&mut self) -> Result<Option<usize>, WasmDecodeErrorType> {
+        self.read_unsigned()
+            .map(|v| v.checked_sub(1).map(|v| v as usize))
+    }
+
+    fn read_value(&mut self) -> Result<WasmValue, WasmDecodeErrorType> {
+        let val_type = self.read_val_type()?;
+        Ok(match val_type {
+            WasmValType::I32 => WasmValue::I32(self.read_unsigned()? as u32 as i32),
+            WasmValType::I64 => WasmValue::I64(self.read_unsigned()? as i64),
+            WasmValType::F32 => WasmValue::F32(f32::from_bits(self.read_unsigned()? as u32)),
+            WasmValType::F64 => WasmValue::F64(f64::from_bits(self.read_unsigned()?)),
+            WasmValType::V128 => {
+                let lo = self.read_unsigned()? as u128;
+                let hi = self.read_unsigned()? as u128;
+                WasmValue::V128(lo | hi << 64)
+            }
+            WasmValType::FuncRef => WasmValue::FuncRef(self.read_ref()?),
+            WasmValType::ExternRef => WasmValue::ExternRef(self.read_ref()?),
+        })
+    }
+}
+
+fn write_unsigned(image: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let d = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            image.push(d);
+            break;
+        }
+        image.push(d | 0x80);
+    }
+}
+
+fn write_bytes(image: &mut Vec<u8>, bytes: &[u8]) {
+    write_unsigned(image, bytes.len() as u64);
+    image.extend_from_slice(bytes);
+}
+
+fn write_val_types(image: &mut Vec<u8>, val_types: &[WasmValType]) {
+    write_unsigned(image, val_types.len() as u64);
+    for val_type in val_types {
+        image.push(*val_type as u8);
+    }
+}
+
+fn write_limit(image: &mut Vec<u8>, limit: WasmLimit) {
+    write_unsigned(image, limit.min() as u64);
+    write_unsigned(image, limit.max() as u64);
+    image.push(limit.is_shared() as u8);
+}
+
+fn write_ref(image: &mut Vec<u8>, value: Option<usize>) {
+    write_unsigned(image, value.map(|v| v as u64 + 1).unwrap_or(0));
+}
+
+fn write_value(image: &mut Vec<u8>, value: &WasmValue) {
+    image.push(value.val_type() as u8);
+    match *value {
+        WasmValue::I32(v) => write_unsigned(image, v as u32 as u64),
+        WasmValue::I64(v) => write_unsigned(image, v as u64),
+        WasmValue::F32(v) => write_unsigned(image, v.to_bits() as u64),
+        WasmValue::F64(v) => write_unsigned(image, v.to_bits()),
+        WasmValue::V128(v) => {
+            write_unsigned(image, v as u64);
+            write_unsigned(image, (v >> 64) as u64);
+        }
+        WasmValue::FuncRef(v) | WasmValue::ExternRef(v) => write_ref(image, v),
+    }
+}
@@ -1,9 +1,17 @@
 //! WebAssembly Intermediate Code Interpreter
 
 use super::{intcode::*, stack::*, wasm::*};
-use crate::opcode::WasmOpcode;
+use crate::opcode::{WasmOpcode, WasmOpcodeFD};
+#[cfg(target_arch = "x86_64")]
+use crate::wasmjit::*;
 use alloc::vec::Vec;
-use core::fmt;
+use core::{
+    fmt, mem,
+    sync::atomic::{fence, AtomicU32, Ordering},
+    time::Duration,
+};
+
+mod simd;
 
 type StackType = usize;
 
@@ -89,6 +97,21 @@ impl From<WasmIntMnemonic> for WasmImc {
 pub struct WasmInterpreter<'a> {
     module: &'a WasmModule,
     func_index: usize,
+    /// Number of the intermediate codes retired in the current function, for the profiler
+    retired: u64,
+    /// Time spent in the callees of the current function, for the profiler
+    child_time: u64,
+    /// Parameters of the pending tail call
+    tail_params: Vec<WasmStackValue>,
+    /// The exception being thrown
+    exception: Option<WasmException>,
+}
+
+/// An exception thrown by `throw`, with the values of its tag
+#[derive(Clone)]
+struct WasmException {
+    tag: usize,
+    values: Vec<WasmStackValue>,
 }
 
 impl<'a> WasmInterpreter<'a> {
@@ -97,6 +120,10 @@ impl<'a> WasmInterpreter<'a> {
         Self {
             module,
             func_index: 0,
+            retired: 0,
+            child_time: 0,
+            tail_params: Vec::new(),
+            exception: None,
         }
     }
 }
@@ -109,6 +136,18 @@ impl WasmInterpreter<'_> {
         locals: &[WasmStackValue],
         result_types: &[WasmValType],
     ) -> Result<Option<WasmValue>, WasmRuntimeError> {
+        self.invoke_multi(func_index, code_block, locals, result_types)
+            .map(|v| v.first().map(|v| *v))
+    }
+
+    /// Invokes the function and returns all of its results.
+    pub fn invoke_multi(
+        &mut self,
+        func_index: usize,
+        code_block: &WasmCodeBlock,
+        locals: &[WasmStackValue],
+        result_types: &[WasmValType],
+    ) -> Result<Vec<WasmValue>, WasmRuntimeError> {
         let mut heap = StackHeap::with_capacity(0x10000);
 
         let mut locals = {
@@ -119,7 +158,32 @@ impl WasmInterpreter<'_> {
 
         self.func_index = func_index;
 
-        self.interpret(code_block, &mut locals, result_types, &mut heap)
+        let mut results = Vec::new();
+        results.resize(result_types.len(), WasmStackValue::zero());
+        self.interpret(code_block, &mut locals, results.as_mut_slice(), &mut heap)?;
+
+        Ok(results
+            .iter()
+            .zip(result_types.iter())
+            .map(|(value, val_type)| value.get_by_type(*val_type))
+            .collect())
+    }
+
+    /// Moves to the branch target, charging fuel if it is a backward branch.
+    #[inline]
+    fn branch(
+        &self,
+        codes: &mut WasmIntermediateCodeStream,
+        target: usize,
+        code: &WasmImc,
+    ) -> Result<(), WasmRuntimeError> {
+        if target < codes.position() {
+            self.module
+                .consume_fuel()
+                .map_err(|kind| self.error(kind, code))?;
+        }
+        codes.set_position(target);
+        Ok(())
     }
 
     #[inline]
@@ -129,16 +193,122 @@ impl WasmInterpreter<'_> {
             function: self.func_index,
             position: code.source_position(),
             opcode: code.opcode().unwrap_or(WasmOpcode::Unreachable),
+            callers: Vec::new(),
         }
     }
 
+    #[inline]
+    fn simd_opcode(&self, value: u64, code: &WasmImc) -> Result<WasmOpcodeFD, WasmRuntimeError> {
+        WasmOpcodeFD::new(value as u32)
+            .ok_or_else(|| self.error(WasmRuntimeErrorType::NotSupprted, code))
+    }
+
+    /// Reads the 128-bit immediate that is stored as four 32-bit words.
+    #[inline]
+    fn v128_param(ext_params: &[usize], position: usize) -> u128 {
+        ext_params[position..position + 4]
+            .iter()
+            .rev()
+            .fold(0, |acc, v| acc << 32 | *v as u32 as u128)
+    }
+
+    #[inline]
+    fn table(&self, index: usize, code: &WasmImc) -> Result<&WasmTable, WasmRuntimeError> {
+        self.module
+            .table(index)
+            .ok_or_else(|| self.error(WasmRuntimeErrorType::OutOfBounds, code))
+    }
+
+    /// Interprets the function and the functions that it calls in tail position.
+    ///
+    /// A tail call replaces the frame of the caller instead of nesting,
+    /// so a chain of tail calls runs in constant space.
     fn interpret(
         &mut self,
         code_block: &WasmCodeBlock,
         locals: &mut [WasmStackValue],
-        result_types: &[WasmValType],
+        results: &mut [WasmStackValue],
         heap: &mut StackHeap,
-    ) -> Result<Option<WasmValue>, WasmRuntimeError> {
+    ) -> Result<(), WasmRuntimeError> {
+        let module = self.module;
+        let mut tail_call = self.interpret_frame(code_block, locals, results, heap)?;
+        while let Some(func_index) = tail_call {
+            let target = &module.functions()[func_index];
+            let code_block = match target.code_block() {
+                Some(v) => v,
+                None => break,
+            };
+            tail_call = heap.snapshot(|heap| {
+                let mut locals =
+                    heap.alloc_stack(self.tail_params.len() + code_block.local_types().len());
+                locals.extend_from_slice(&self.tail_params);
+                for _ in code_block.local_types() {
+                    let _ = locals.push(WasmStackValue::zero());
+                }
+
+                self.func_index = func_index;
+                self.interpret_frame(code_block, locals.as_mut_slice(), results, heap)
+            })?;
+        }
+        Ok(())
+    }
+
+    fn interpret_frame(
+        &mut self,
+        code_block: &WasmCodeBlock,
+        locals: &mut [WasmStackValue],
+        results: &mut [WasmStackValue],
+        heap: &mut StackHeap,
+    ) -> Result<Option<usize>, WasmRuntimeError> {
+        let profiler = match self.module.profiler() {
+            Some(v) => v,
+            None => return self.interpret_body(code_block, locals, results, heap),
+        };
+
+        let func_index = self.func_index;
+        let outer_retired = mem::replace(&mut self.retired, 0);
+        let outer_child_time = mem::replace(&mut self.child_time, 0);
+        let start = profiler.now();
+
+        let result = self.interpret_body(code_block, locals, results, heap);
+
+        let elapsed = profiler.now().wrapping_sub(start);
+        profiler.record(
+            func_index,
+            self.retired,
+            elapsed,
+            elapsed.saturating_sub(self.child_time),
+        );
+        self.retired = outer_retired;
+        self.child_time = outer_child_time + elapsed;
+
+        result
+    }
+
+    /// Interprets the function body.
+    ///
+    /// Returns the index of the function to be called in tail position, whose parameters are in `tail_params`.
+    fn interpret_body(
+        &mut self,
+        code_block: &WasmCodeBlock,
+        locals: &mut [WasmStackValue],
+        results: &mut [WasmStackValue],
+        heap: &mut StackHeap,
+    ) -> Result<Option<usize>, WasmRuntimeError> {
+        // The native code does not charge fuel, call the debug handler nor count for the profiler,
+        // so metered, debugged or profiled modules stay on the interpreter.
+        #[cfg(target_arch = "x86_64")]
+        if let Some(jit_code) = code_block.jit_code() {
+            if self.module.fuel().is_none()
+                && self.module.debug_handler().is_none()
+                && self.module.profiler().is_none()
+            {
+                return self
+                    .run_jit(jit_code, code_block, locals, results, heap)
+                    .map(|_| None);
+            }
+        }
+
         let mut codes = WasmIntermediateCodeStream::from_codes(code_block.intermediate_codes());
 
         let value_stack = heap.alloc(code_block.max_value_stack());
@@ -148,11 +318,33 @@ impl WasmInterpreter<'_> {
 
         let mut result_stack_level = 0;
 
+        // Exceptions caught by each handler, for rethrow
+        let mut caught = Vec::new();
+        caught.resize(code_block.exception_handlers().len(), None);
+        let func_index = self.func_index;
+
         // let mut last_code = WasmImc::from_mnemonic(WasmIntMnemonic::Unreachable);
 
         let memory = unsafe { self.module.memory_unchecked(0) };
 
+        let mut is_entry = true;
+
         while let Some(code) = codes.fetch() {
+            if let Some(handler) = self.module.debug_handler() {
+                let frame = WasmDebugFrame {
+                    module: self.module,
+                    func_index: self.func_index,
+                    code,
+                    is_entry,
+                    local_types: code_block.local_types(),
+                    locals,
+                    stack: &value_stack[..code.stack_level()],
+                };
+                handler(&frame).map_err(|kind| self.error(kind, code))?;
+            }
+            is_entry = false;
+            self.retired += 1;
+
             match code.mnemonic() {
                 WasmIntMnemonic::Unreachable => {
                     return Err(self.error(WasmRuntimeErrorType::Unreachable, code))
@@ -163,14 +355,37 @@ impl WasmInterpreter<'_> {
 
                 WasmIntMnemonic::Br => {
                     let br = code.param1() as usize;
-                    codes.set_position(br);
+                    self.branch(&mut codes, br, code)?;
                 }
 
                 WasmIntMnemonic::BrIf => {
                     let cc = value_stack[code.stack_level()].get_bool();
                     if cc {
                         let br = code.param1() as usize;
-                        codes.set_position(br);
+                        self.branch(&mut codes, br, code)?;
+                    }
+                }
+                WasmIntMnemonic::BrUnwind => {
+                    let ext_params = code_block.ext_params();
+                    let param_position = code.param1() as usize;
+                    let target = ext_params[param_position];
+                    let dest = ext_params[param_position + 1];
+                    let arity = ext_params[param_position + 2];
+                    let source = code.stack_level();
+                    value_stack.copy_within(source..source + arity, dest);
+                    self.branch(&mut codes, target, code)?;
+                }
+                WasmIntMnemonic::BrIfUnwind => {
+                    let cc = value_stack[code.stack_level()].get_bool();
+                    if cc {
+                        let ext_params = code_block.ext_params();
+                        let param_position = code.param1() as usize;
+                        let target = ext_params[param_position];
+                        let dest = ext_params[param_position + 1];
+                        let arity = ext_params[param_position + 2];
+                        let source = code.stack_level() - arity;
+                        value_stack.copy_within(source..source + arity, dest);
+                        self.branch(&mut codes, target, code)?;
                     }
                 }
                 WasmIntMnemonic::BrTable => {
@@ -181,8 +396,22 @@ impl WasmInterpreter<'_> {
                     if index >= table_len {
                         index = table_len;
                     }
-                    let target = ext_params[table_position + index + 1];
-                    codes.set_position(target);
+                    let entry = table_position + index * 3 + 1;
+                    let target = ext_params[entry];
+                    let dest = ext_params[entry + 1];
+                    let arity = ext_params[entry + 2];
+                    let source = code.stack_level() - arity;
+                    if source != dest {
+                        value_stack.copy_within(source..source + arity, dest);
+                    }
+                    self.branch(&mut codes, target, code)?;
+                }
+                WasmIntMnemonic::If => {
+                    let cc = value_stack[code.stack_level()].get_bool();
+                    if !cc {
+                        let br = code.param1() as usize;
+                        self.branch(&mut codes, br, code)?;
+                    }
                 }
 
                 WasmIntMnemonic::Return => {
@@ -197,20 +426,71 @@ impl WasmInterpreter<'_> {
                             .functions()
                             .get_unchecked(code.param1() as usize)
                     };
-                    self.call(func, code, value_stack, heap)?;
+                    if let Err(err) = self.call(func, code, value_stack, heap) {
+                        self.func_index = func_index;
+                        self.catch(err, code_block, &mut codes, value_stack, &mut caught)?;
+                    }
                 }
                 WasmIntMnemonic::CallIndirect => {
-                    let type_index = code.param1() as usize;
+                    let type_index = code.param1() as u32 as usize;
+                    let table_index = (code.param1() >> 32) as usize;
+                    let index =
+                        unsafe { value_stack.get_unchecked(code.stack_level()).get_u32() as usize };
+                    let func = self
+                        .module
+                        .elem_by_table(table_index, index)
+                        .ok_or(self.error(WasmRuntimeErrorType::NoMethod, code))?;
+                    if func.type_index() != type_index {
+                        return Err(self.error(WasmRuntimeErrorType::TypeMismatch, code));
+                    }
+                    if let Err(err) = self.call(func, code, value_stack, heap) {
+                        self.func_index = func_index;
+                        self.catch(err, code_block, &mut codes, value_stack, &mut caught)?;
+                    }
+                }
+
+                WasmIntMnemonic::ReturnCall => {
+                    let func = unsafe {
+                        self.module
+                            .functions()
+                            .get_unchecked(code.param1() as usize)
+                    };
+                    return self.tail_call(func, code, value_stack, results, heap);
+                }
+                WasmIntMnemonic::ReturnCallIndirect => {
+                    let type_index = code.param1() as u32 as usize;
+                    let table_index = (code.param1() >> 32) as usize;
                     let index =
-                        unsafe { value_stack.get_unchecked(code.stack_level()).get_i32() as usize };
+                        unsafe { value_stack.get_unchecked(code.stack_level()).get_u32() as usize };
                     let func = self
                         .module
-                        .elem_by_index(index)
+                        .elem_by_table(table_index, index)
                         .ok_or(self.error(WasmRuntimeErrorType::NoMethod, code))?;
                     if func.type_index() != type_index {
                         return Err(self.error(WasmRuntimeErrorType::TypeMismatch, code));
                     }
-                    self.call(func, code, value_stack, heap)?;
+                    return self.tail_call(func, code, value_stack, results, heap);
+                }
+
+                WasmIntMnemonic::Throw => {
+                    let tag = code.param1() as usize;
+                    let stack_level = code.stack_level();
+                    let n_values = self
+                        .module
+                        .tag_type(tag)
+                        .map(|v| v.param_types().len())
+                        .unwrap_or(0);
+                    self.exception = Some(WasmException {
+                        tag,
+                        values: value_stack[stack_level..stack_level + n_values].to_vec(),
+                    });
+                    let err = self.error(WasmRuntimeErrorType::Exception, code);
+                    self.catch(err, code_block, &mut codes, value_stack, &mut caught)?;
+                }
+                WasmIntMnemonic::Rethrow => {
+                    self.exception = caught[code.param1() as usize].clone();
+                    let err = self.error(WasmRuntimeErrorType::Exception, code);
+                    self.catch(err, code_block, &mut codes, value_stack, &mut caught)?;
                 }
 
                 WasmIntMnemonic::Select => {
@@ -422,13 +702,68 @@ impl WasmInterpreter<'_> {
                     }
                 }
 
+                WasmIntMnemonic::F32Load => {
+                    let var = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    let offset = code.param1() as usize + var.get_u32() as usize;
+                    *var = match memory
+                        .read_u32(offset)
+                        .map(|v| WasmStackValue::from_f32(f32::from_bits(v)))
+                    {
+                        Ok(v) => v,
+                        Err(e) => return Err(self.error(e, code)),
+                    };
+                }
+                WasmIntMnemonic::F64Load => {
+                    let var = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    let offset = code.param1() as usize + var.get_u32() as usize;
+                    *var = match memory
+                        .read_u64(offset)
+                        .map(|v| WasmStackValue::from_f64(f64::from_bits(v)))
+                    {
+                        Ok(v) => v,
+                        Err(e) => return Err(self.error(e, code)),
+                    };
+                }
+                WasmIntMnemonic::F32Store => {
+                    let stack_level = code.stack_level();
+                    let index =
+                        unsafe { value_stack.get_unchecked(stack_level).get_u32() as usize };
+                    let data = unsafe {
+                        value_stack
+                            .get_unchecked(stack_level + 1)
+                            .get_f32()
+                            .to_bits()
+                    };
+                    let offset = code.param1() as usize + index;
+                    match memory.write_u32(offset, data) {
+                        Ok(_) => {}
+                        Err(e) => return Err(self.error(e, code)),
+                    }
+                }
+                WasmIntMnemonic::F64Store => {
+                    let stack_level = code.stack_level();
+                    let index =
+                        unsafe { value_stack.get_unchecked(stack_level).get_u32() as usize };
+                    let data = unsafe {
+                        value_stack
+                            .get_unchecked(stack_level + 1)
+                            .get_f64()
+                            .to_bits()
+                    };
+                    let offset = code.param1() as usize + index;
+                    match memory.write_u64(offset, data) {
+                        Ok(_) => {}
+                        Err(e) => return Err(self.error(e, code)),
+                    }
+                }
+
                 WasmIntMnemonic::MemorySize => {
                     let ref_a = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
                     *ref_a = WasmStackValue::from(memory.size());
                 }
                 WasmIntMnemonic::MemoryGrow => {
                     let ref_a = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
-                    *ref_a = WasmStackValue::from(memory.grow(ref_a.get_i32()));
+                    *ref_a = WasmStackValue::from(self.module.grow_memory(0, ref_a.get_i32()));
                 }
 
                 WasmIntMnemonic::I32Const => {
@@ -439,6 +774,14 @@ impl WasmInterpreter<'_> {
                     let ref_a = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
                     *ref_a = WasmStackValue::from_u64(code.param1());
                 }
+                WasmIntMnemonic::F32Const => {
+                    let ref_a = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    *ref_a = WasmStackValue::from_u64(code.param1() as u32 as u64);
+                }
+                WasmIntMnemonic::F64Const => {
+                    let ref_a = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    *ref_a = WasmStackValue::from_u64(code.param1());
+                }
 
                 WasmIntMnemonic::I32Eqz => {
                     let var = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
@@ -833,137 +1176,1178 @@ impl WasmInterpreter<'_> {
                     *var = WasmStackValue::from_i32(var.get_i16() as i32);
                 }
 
-                WasmIntMnemonic::FusedI32AddI => {
-                    let lhs = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
-                    lhs.map_i32(|lhs| lhs.wrapping_add(code.param1() as i32));
+                WasmIntMnemonic::F32Eq => {
+                    let stack_level = code.stack_level();
+                    let rhs = unsafe { value_stack.get_unchecked(stack_level + 1).get_f32() };
+                    let lhs = unsafe { value_stack.get_unchecked_mut(stack_level) };
+                    *lhs = WasmStackValue::from_bool(lhs.get_f32() == rhs);
                 }
-                WasmIntMnemonic::FusedI32SubI => {
-                    let lhs = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
-                    lhs.map_i32(|lhs| lhs.wrapping_sub(code.param1() as i32));
+                WasmIntMnemonic::F32Ne => {
+                    let stack_level = code.stack_level();
+                    let rhs = unsafe { value_stack.get_unchecked(stack_level + 1).get_f32() };
+                    let lhs = unsafe { value_stack.get_unchecked_mut(stack_level) };
+                    *lhs = WasmStackValue::from_bool(lhs.get_f32() != rhs);
                 }
-                WasmIntMnemonic::FusedI32AndI => {
-                    let lhs = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
-                    lhs.map_i32(|lhs| lhs & code.param1() as i32);
+                WasmIntMnemonic::F32Lt => {
+                    let stack_level = code.stack_level();
+                    let rhs = unsafe { value_stack.get_unchecked(stack_level + 1).get_f32() };
+                    let lhs = unsafe { value_stack.get_unchecked_mut(stack_level) };
+                    *lhs = WasmStackValue::from_bool(lhs.get_f32() < rhs);
                 }
-                WasmIntMnemonic::FusedI32OrI => {
-                    let lhs = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
-                    lhs.map_i32(|lhs| lhs | code.param1() as i32);
+                WasmIntMnemonic::F32Gt => {
+                    let stack_level = code.stack_level();
+                    let rhs = unsafe { value_stack.get_unchecked(stack_level + 1).get_f32() };
+                    let lhs = unsafe { value_stack.get_unchecked_mut(stack_level) };
+                    *lhs = WasmStackValue::from_bool(lhs.get_f32() > rhs);
                 }
-                WasmIntMnemonic::FusedI32XorI => {
-                    let lhs = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
-                    lhs.map_i32(|lhs| lhs ^ code.param1() as i32);
+                WasmIntMnemonic::F32Le => {
+                    let stack_level = code.stack_level();
+                    let rhs = unsafe { value_stack.get_unchecked(stack_level + 1).get_f32() };
+                    let lhs = unsafe { value_stack.get_unchecked_mut(stack_level) };
+                    *lhs = WasmStackValue::from_bool(lhs.get_f32() <= rhs);
                 }
-                WasmIntMnemonic::FusedI32ShlI => {
-                    let lhs = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
-                    lhs.map_u32(|lhs| lhs << (code.param1() as u32));
+                WasmIntMnemonic::F32Ge => {
+                    let stack_level = code.stack_level();
+                    let rhs = unsafe { value_stack.get_unchecked(stack_level + 1).get_f32() };
+                    let lhs = unsafe { value_stack.get_unchecked_mut(stack_level) };
+                    *lhs = WasmStackValue::from_bool(lhs.get_f32() >= rhs);
                 }
-                WasmIntMnemonic::FusedI32ShrUI => {
-                    let lhs = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
-                    lhs.map_u32(|lhs| lhs >> (code.param1() as u32));
+
+                WasmIntMnemonic::F32Abs => {
+                    let var = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    var.map_f32(|v| f32::from_bits(v.to_bits() & !(1 << 31)));
                 }
-                WasmIntMnemonic::FusedI32ShrSI => {
-                    let lhs = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
-                    lhs.map_i32(|lhs| lhs >> (code.param1() as i32));
+                WasmIntMnemonic::F32Neg => {
+                    let var = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    var.map_f32(|v| f32::from_bits(v.to_bits() ^ (1 << 31)));
                 }
-
-                WasmIntMnemonic::FusedI64AddI => {
-                    let lhs = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
-                    lhs.map_i64(|lhs| lhs.wrapping_add(code.param1() as i64));
+                WasmIntMnemonic::F32Ceil => {
+                    let var = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    var.map_f32(|v| canonicalize_f32(libm::ceilf(v)));
                 }
-                WasmIntMnemonic::FusedI64SubI => {
-                    let lhs = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
-                    lhs.map_i64(|lhs| lhs.wrapping_sub(code.param1() as i64));
+                WasmIntMnemonic::F32Floor => {
+                    let var = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    var.map_f32(|v| canonicalize_f32(libm::floorf(v)));
                 }
-
-                WasmIntMnemonic::FusedI32BrZ => {
-                    let cc =
-                        unsafe { value_stack.get_unchecked_mut(code.stack_level()).get_i32() == 0 };
-                    if cc {
-                        let br = code.param1() as usize;
-                        codes.set_position(br);
-                    }
+                WasmIntMnemonic::F32Trunc => {
+                    let var = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    var.map_f32(|v| canonicalize_f32(libm::truncf(v)));
                 }
-                WasmIntMnemonic::FusedI64BrZ => {
-                    let cc =
-                        unsafe { value_stack.get_unchecked_mut(code.stack_level()).get_i64() == 0 };
-                    if cc {
-                        let br = code.param1() as usize;
-                        codes.set_position(br);
-                    }
+                WasmIntMnemonic::F32Nearest => {
+                    let var = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    var.map_f32(|v| canonicalize_f32(libm::rintf(v)));
                 }
-
-                _ => return Err(self.error(WasmRuntimeErrorType::NotSupprted, code)),
-            }
-        }
-        if let Some(result_type) = result_types.first() {
-            let val = unsafe { value_stack.get_unchecked(result_stack_level) };
-            match result_type {
-                WasmValType::I32 => Ok(Some(WasmValue::I32(val.get_i32()))),
-                WasmValType::I64 => Ok(Some(WasmValue::I64(val.get_i64()))),
-                // WasmValType::F32 => {}
-                // WasmValType::F64 => {}
-                _ => unreachable!(),
-            }
-        } else {
-            Ok(None)
-        }
-    }
-
-    #[inline]
-    fn call(
-        &mut self,
-        target: &WasmFunction,
-        code: &WasmImc,
-        value_stack: &mut [WasmStackValue],
-        heap: &mut StackHeap,
-    ) -> Result<(), WasmRuntimeError> {
-        let stack_pointer = code.stack_level();
-        let current_function = self.func_index;
-        let module = self.module;
-        let result_types = target.result_types();
-
-        let param_len = target.param_types().len();
-        // if stack_pointer < param_len {
-        //     return Err(self.error(WasmRuntimeError::InternalInconsistency, code));
-        // }
-
-        if let Some(code_block) = target.code_block() {
-            heap.snapshot(|heap| {
-                let mut locals = heap.alloc_stack(param_len + code_block.local_types().len());
-                let stack_under = stack_pointer - param_len;
-
-                locals.extend_from_slice(&value_stack[stack_under..stack_under + param_len]);
-                for _ in code_block.local_types() {
-                    let _ = locals.push(WasmStackValue::zero());
+                WasmIntMnemonic::F32Sqrt => {
+                    let var = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    var.map_f32(|v| canonicalize_f32(libm::sqrtf(v)));
                 }
 
-                self.func_index = target.index();
-
-                self.interpret(code_block, locals.as_mut_slice(), result_types, heap)
-                    .and_then(|v| {
-                        if let Some(result) = v {
-                            let var = unsafe { value_stack.get_unchecked_mut(stack_under) };
-                            *var = WasmStackValue::from(result);
-                        }
-                        self.func_index = current_function;
-                        Ok(())
-                    })
-            })
-        } else if let Some(dlink) = target.dlink() {
-            heap.snapshot(|heap| {
-                let mut locals = heap.alloc_stack(param_len);
-                let stack_under = stack_pointer - param_len;
-                let params = &value_stack[stack_under..stack_under + param_len];
-                for (index, val_type) in target.param_types().iter().enumerate() {
-                    let _ = locals.push(params[index].get_by_type(*val_type));
+                WasmIntMnemonic::F32Add => {
+                    let stack_level = code.stack_level();
+                    let rhs = unsafe { value_stack.get_unchecked(stack_level + 1).get_f32() };
+                    let lhs = unsafe { value_stack.get_unchecked_mut(stack_level) };
+                    lhs.map_f32(|lhs| canonicalize_f32(lhs + rhs));
                 }
-
-                let result = match dlink(module, locals.as_slice()) {
-                    Ok(v) => v,
-                    Err(e) => return Err(self.error(e, code)),
-                };
-
-                if let Some(t) = result_types.first() {
+                WasmIntMnemonic::F32Sub => {
+                    let stack_level = code.stack_level();
+                    let rhs = unsafe { value_stack.get_unchecked(stack_level + 1).get_f32() };
+                    let lhs = unsafe { value_stack.get_unchecked_mut(stack_level) };
+                    lhs.map_f32(|lhs| canonicalize_f32(lhs - rhs));
+                }
+                WasmIntMnemonic::F32Mul => {
+                    let stack_level = code.stack_level();
+                    let rhs = unsafe { value_stack.get_unchecked(stack_level + 1).get_f32() };
+                    let lhs = unsafe { value_stack.get_unchecked_mut(stack_level) };
+                    lhs.map_f32(|lhs| canonicalize_f32(lhs * rhs));
+                }
+                WasmIntMnemonic::F32Div => {
+                    let stack_level = code.stack_level();
+                    let rhs = unsafe { value_stack.get_unchecked(stack_level + 1).get_f32() };
+                    let lhs = unsafe { value_stack.get_unchecked_mut(stack_level) };
+                    lhs.map_f32(|lhs| canonicalize_f32(lhs / rhs));
+                }
+                WasmIntMnemonic::F32Min => {
+                    let stack_level = code.stack_level();
+                    let rhs = unsafe { value_stack.get_unchecked(stack_level + 1).get_f32() };
+                    let lhs = unsafe { value_stack.get_unchecked_mut(stack_level) };
+                    lhs.map_f32(|lhs| min_f32(lhs, rhs));
+                }
+                WasmIntMnemonic::F32Max => {
+                    let stack_level = code.stack_level();
+                    let rhs = unsafe { value_stack.get_unchecked(stack_level + 1).get_f32() };
+                    let lhs = unsafe { value_stack.get_unchecked_mut(stack_level) };
+                    lhs.map_f32(|lhs| max_f32(lhs, rhs));
+                }
+                WasmIntMnemonic::F32Copysign => {
+                    let stack_level = code.stack_level();
+                    let rhs = unsafe { value_stack.get_unchecked(stack_level + 1).get_f32() };
+                    let lhs = unsafe { value_stack.get_unchecked_mut(stack_level) };
+                    lhs.map_f32(|lhs| libm::copysignf(lhs, rhs));
+                }
+
+                WasmIntMnemonic::F64Eq => {
+                    let stack_level = code.stack_level();
+                    let rhs = unsafe { value_stack.get_unchecked(stack_level + 1).get_f64() };
+                    let lhs = unsafe { value_stack.get_unchecked_mut(stack_level) };
+                    *lhs = WasmStackValue::from_bool(lhs.get_f64() == rhs);
+                }
+                WasmIntMnemonic::F64Ne => {
+                    let stack_level = code.stack_level();
+                    let rhs = unsafe { value_stack.get_unchecked(stack_level + 1).get_f64() };
+                    let lhs = unsafe { value_stack.get_unchecked_mut(stack_level) };
+                    *lhs = WasmStackValue::from_bool(lhs.get_f64() != rhs);
+                }
+                WasmIntMnemonic::F64Lt => {
+                    let stack_level = code.stack_level();
+                    let rhs = unsafe { value_stack.get_unchecked(stack_level + 1).get_f64() };
+                    let lhs = unsafe { value_stack.get_unchecked_mut(stack_level) };
+                    *lhs = WasmStackValue::from_bool(lhs.get_f64() < rhs);
+                }
+                WasmIntMnemonic::F64Gt => {
+                    let stack_level = code.stack_level();
+                    let rhs = unsafe { value_stack.get_unchecked(stack_level + 1).get_f64() };
+                    let lhs = unsafe { value_stack.get_unchecked_mut(stack_level) };
+                    *lhs = WasmStackValue::from_bool(lhs.get_f64() > rhs);
+                }
+                WasmIntMnemonic::F64Le => {
+                    let stack_level = code.stack_level();
+                    let rhs = unsafe { value_stack.get_unchecked(stack_level + 1).get_f64() };
+                    let lhs = unsafe { value_stack.get_unchecked_mut(stack_level) };
+                    *lhs = WasmStackValue::from_bool(lhs.get_f64() <= rhs);
+                }
+                WasmIntMnemonic::F64Ge => {
+                    let stack_level = code.stack_level();
+                    let rhs = unsafe { value_stack.get_unchecked(stack_level + 1).get_f64() };
+                    let lhs = unsafe { value_stack.get_unchecked_mut(stack_level) };
+                    *lhs = WasmStackValue::from_bool(lhs.get_f64() >= rhs);
+                }
+
+                WasmIntMnemonic::F64Abs => {
+                    let var = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    var.map_f64(|v| f64::from_bits(v.to_bits() & !(1 << 63)));
+                }
+                WasmIntMnemonic::F64Neg => {
+                    let var = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    var.map_f64(|v| f64::from_bits(v.to_bits() ^ (1 << 63)));
+                }
+                WasmIntMnemonic::F64Ceil => {
+                    let var = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    var.map_f64(|v| canonicalize_f64(libm::ceil(v)));
+                }
+                WasmIntMnemonic::F64Floor => {
+                    let var = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    var.map_f64(|v| canonicalize_f64(libm::floor(v)));
+                }
+                WasmIntMnemonic::F64Trunc => {
+                    let var = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    var.map_f64(|v| canonicalize_f64(libm::trunc(v)));
+                }
+                WasmIntMnemonic::F64Nearest => {
+                    let var = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    var.map_f64(|v| canonicalize_f64(libm::rint(v)));
+                }
+                WasmIntMnemonic::F64Sqrt => {
+                    let var = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    var.map_f64(|v| canonicalize_f64(libm::sqrt(v)));
+                }
+
+                WasmIntMnemonic::F64Add => {
+                    let stack_level = code.stack_level();
+                    let rhs = unsafe { value_stack.get_unchecked(stack_level + 1).get_f64() };
+                    let lhs = unsafe { value_stack.get_unchecked_mut(stack_level) };
+                    lhs.map_f64(|lhs| canonicalize_f64(lhs + rhs));
+                }
+                WasmIntMnemonic::F64Sub => {
+                    let stack_level = code.stack_level();
+                    let rhs = unsafe { value_stack.get_unchecked(stack_level + 1).get_f64() };
+                    let lhs = unsafe { value_stack.get_unchecked_mut(stack_level) };
+                    lhs.map_f64(|lhs| canonicalize_f64(lhs - rhs));
+                }
+                WasmIntMnemonic::F64Mul => {
+                    let stack_level = code.stack_level();
+                    let rhs = unsafe { value_stack.get_unchecked(stack_level + 1).get_f64() };
+                    let lhs = unsafe { value_stack.get_unchecked_mut(stack_level) };
+                    lhs.map_f64(|lhs| canonicalize_f64(lhs * rhs));
+                }
+                WasmIntMnemonic::F64Div => {
+                    let stack_level = code.stack_level();
+                    let rhs = unsafe { value_stack.get_unchecked(stack_level + 1).get_f64() };
+                    let lhs = unsafe { value_stack.get_unchecked_mut(stack_level) };
+                    lhs.map_f64(|lhs| canonicalize_f64(lhs / rhs));
+                }
+                WasmIntMnemonic::F64Min => {
+                    let stack_level = code.stack_level();
+                    let rhs = unsafe { value_stack.get_unchecked(stack_level + 1).get_f64() };
+                    let lhs = unsafe { value_stack.get_unchecked_mut(stack_level) };
+                    lhs.map_f64(|lhs| min_f64(lhs, rhs));
+                }
+                WasmIntMnemonic::F64Max => {
+                    let stack_level = code.stack_level();
+                    let rhs = unsafe { value_stack.get_unchecked(stack_level + 1).get_f64() };
+                    let lhs = unsafe { value_stack.get_unchecked_mut(stack_level) };
+                    lhs.map_f64(|lhs| max_f64(lhs, rhs));
+                }
+                WasmIntMnemonic::F64Copysign => {
+                    let stack_level = code.stack_level();
+                    let rhs = unsafe { value_stack.get_unchecked(stack_level + 1).get_f64() };
+                    let lhs = unsafe { value_stack.get_unchecked_mut(stack_level) };
+                    lhs.map_f64(|lhs| libm::copysign(lhs, rhs));
+                }
+
+                WasmIntMnemonic::I32TruncF32S => {
+                    let var = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    let val = var.get_f32();
+                    if val.is_nan() {
+                        return Err(
+                            self.error(WasmRuntimeErrorType::InvalidConversionToInteger, code)
+                        );
+                    }
+                    if !(val >= -2147483648.0 && val < 2147483648.0) {
+                        return Err(self.error(WasmRuntimeErrorType::IntegerOverflow, code));
+                    }
+                    *var = WasmStackValue::from_i32(val as i32);
+                }
+                WasmIntMnemonic::I32TruncF32U => {
+                    let var = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    let val = var.get_f32();
+                    if val.is_nan() {
+                        return Err(
+                            self.error(WasmRuntimeErrorType::InvalidConversionToInteger, code)
+                        );
+                    }
+                    if !(val > -1.0 && val < 4294967296.0) {
+                        return Err(self.error(WasmRuntimeErrorType::IntegerOverflow, code));
+                    }
+                    *var = WasmStackValue::from_u32(val as u32);
+                }
+                WasmIntMnemonic::I32TruncF64S => {
+                    let var = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    let val = var.get_f64();
+                    if val.is_nan() {
+                        return Err(
+                            self.error(WasmRuntimeErrorType::InvalidConversionToInteger, code)
+                        );
+                    }
+                    if !(val > -2147483649.0 && val < 2147483648.0) {
+                        return Err(self.error(WasmRuntimeErrorType::IntegerOverflow, code));
+                    }
+                    *var = WasmStackValue::from_i32(val as i32);
+                }
+                WasmIntMnemonic::I32TruncF64U => {
+                    let var = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    let val = var.get_f64();
+                    if val.is_nan() {
+                        return Err(
+                            self.error(WasmRuntimeErrorType::InvalidConversionToInteger, code)
+                        );
+                    }
+                    if !(val > -1.0 && val < 4294967296.0) {
+                        return Err(self.error(WasmRuntimeErrorType::IntegerOverflow, code));
+                    }
+                    *var = WasmStackValue::from_u32(val as u32);
+                }
+                WasmIntMnemonic::I64TruncF32S => {
+                    let var = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    let val = var.get_f32();
+                    if val.is_nan() {
+                        return Err(
+                            self.error(WasmRuntimeErrorType::InvalidConversionToInteger, code)
+                        );
+                    }
+                    if !(val >= -9223372036854775808.0 && val < 9223372036854775808.0) {
+                        return Err(self.error(WasmRuntimeErrorType::IntegerOverflow, code));
+                    }
+                    *var = WasmStackValue::from_i64(val as i64);
+                }
+                WasmIntMnemonic::I64TruncF32U => {
+                    let var = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    let val = var.get_f32();
+                    if val.is_nan() {
+                        return Err(
+                            self.error(WasmRuntimeErrorType::InvalidConversionToInteger, code)
+                        );
+                    }
+                    if !(val > -1.0 && val < 18446744073709551616.0) {
+                        return Err(self.error(WasmRuntimeErrorType::IntegerOverflow, code));
+                    }
+                    *var = WasmStackValue::from_u64(val as u64);
+                }
+                WasmIntMnemonic::I64TruncF64S => {
+                    let var = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    let val = var.get_f64();
+                    if val.is_nan() {
+                        return Err(
+                            self.error(WasmRuntimeErrorType::InvalidConversionToInteger, code)
+                        );
+                    }
+                    if !(val >= -9223372036854775808.0 && val < 9223372036854775808.0) {
+                        return Err(self.error(WasmRuntimeErrorType::IntegerOverflow, code));
+                    }
+                    *var = WasmStackValue::from_i64(val as i64);
+                }
+                WasmIntMnemonic::I64TruncF64U => {
+                    let var = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    let val = var.get_f64();
+                    if val.is_nan() {
+                        return Err(
+                            self.error(WasmRuntimeErrorType::InvalidConversionToInteger, code)
+                        );
+                    }
+                    if !(val > -1.0 && val < 18446744073709551616.0) {
+                        return Err(self.error(WasmRuntimeErrorType::IntegerOverflow, code));
+                    }
+                    *var = WasmStackValue::from_u64(val as u64);
+                }
+                WasmIntMnemonic::F32ConvertI32S => {
+                    let var = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    *var = WasmStackValue::from_f32(var.get_i32() as f32);
+                }
+                WasmIntMnemonic::F32ConvertI32U => {
+                    let var = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    *var = WasmStackValue::from_f32(var.get_u32() as f32);
+                }
+                WasmIntMnemonic::F32ConvertI64S => {
+                    let var = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    *var = WasmStackValue::from_f32(var.get_i64() as f32);
+                }
+                WasmIntMnemonic::F32ConvertI64U => {
+                    let var = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    *var = WasmStackValue::from_f32(var.get_u64() as f32);
+                }
+                WasmIntMnemonic::F32DemoteF64 => {
+                    let var = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    *var = WasmStackValue::from_f32(canonicalize_f32(var.get_f64() as f32));
+                }
+                WasmIntMnemonic::F64ConvertI32S => {
+                    let var = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    *var = WasmStackValue::from_f64(var.get_i32() as f64);
+                }
+                WasmIntMnemonic::F64ConvertI32U => {
+                    let var = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    *var = WasmStackValue::from_f64(var.get_u32() as f64);
+                }
+                WasmIntMnemonic::F64ConvertI64S => {
+                    let var = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    *var = WasmStackValue::from_f64(var.get_i64() as f64);
+                }
+                WasmIntMnemonic::F64ConvertI64U => {
+                    let var = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    *var = WasmStackValue::from_f64(var.get_u64() as f64);
+                }
+                WasmIntMnemonic::F64PromoteF32 => {
+                    let var = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    *var = WasmStackValue::from_f64(canonicalize_f64(var.get_f32() as f64));
+                }
+                WasmIntMnemonic::I32ReinterpretF32 | WasmIntMnemonic::F32ReinterpretI32 => {
+                    // The 32-bit payload is shared by both types,
+                    // only the upper half needs to be cleared.
+                    let var = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    *var = WasmStackValue::from_u64(var.get_u32() as u64);
+                }
+                WasmIntMnemonic::I64ReinterpretF64 | WasmIntMnemonic::F64ReinterpretI64 => {
+                    // Nothing to do
+                }
+
+                // Rust's `as` conversions saturate and map NaN to zero
+                WasmIntMnemonic::I32TruncSatF32S => {
+                    let var = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    *var = WasmStackValue::from_i32(var.get_f32() as i32);
+                }
+                WasmIntMnemonic::I32TruncSatF32U => {
+                    let var = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    *var = WasmStackValue::from_u32(var.get_f32() as u32);
+                }
+                WasmIntMnemonic::I32TruncSatF64S => {
+                    let var = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    *var = WasmStackValue::from_i32(var.get_f64() as i32);
+                }
+                WasmIntMnemonic::I32TruncSatF64U => {
+                    let var = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    *var = WasmStackValue::from_u32(var.get_f64() as u32);
+                }
+                WasmIntMnemonic::I64TruncSatF32S => {
+                    let var = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    *var = WasmStackValue::from_i64(var.get_f32() as i64);
+                }
+                WasmIntMnemonic::I64TruncSatF32U => {
+                    let var = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    *var = WasmStackValue::from_u64(var.get_f32() as u64);
+                }
+                WasmIntMnemonic::I64TruncSatF64S => {
+                    let var = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    *var = WasmStackValue::from_i64(var.get_f64() as i64);
+                }
+                WasmIntMnemonic::I64TruncSatF64U => {
+                    let var = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    *var = WasmStackValue::from_u64(var.get_f64() as u64);
+                }
+
+                WasmIntMnemonic::MemoryInit => {
+                    let stack_level = code.stack_level();
+                    let dest = unsafe { value_stack.get_unchecked(stack_level).get_u32() as usize };
+                    let src =
+                        unsafe { value_stack.get_unchecked(stack_level + 1).get_u32() as usize };
+                    let count =
+                        unsafe { value_stack.get_unchecked(stack_level + 2).get_u32() as usize };
+                    let data = match self.module.data(code.param1() as usize) {
+                        Some(v) => v.bytes(),
+                        None => return Err(self.error(WasmRuntimeErrorType::OutOfBounds, code)),
+                    };
+                    let src = match src
+                        .checked_add(count)
+                        .and_then(|src_end| data.get(src..src_end))
+                    {
+                        Some(v) => v,
+                        None => return Err(self.error(WasmRuntimeErrorType::OutOfBounds, code)),
+                    };
+                    match memory.init(dest, src) {
+                        Ok(_) => {}
+                        Err(e) => return Err(self.error(e, code)),
+                    }
+                }
+                WasmIntMnemonic::DataDrop => {
+                    if let Some(data) = self.module.data(code.param1() as usize) {
+                        data.discard();
+                    }
+                }
+                WasmIntMnemonic::MemoryCopy => {
+                    let stack_level = code.stack_level();
+                    let dest = unsafe { value_stack.get_unchecked(stack_level).get_u32() as usize };
+                    let src =
+                        unsafe { value_stack.get_unchecked(stack_level + 1).get_u32() as usize };
+                    let count =
+                        unsafe { value_stack.get_unchecked(stack_level + 2).get_u32() as usize };
+                    match memory.copy(dest, src, count) {
+                        Ok(_) => {}
+                        Err(e) => return Err(self.error(e, code)),
+                    }
+                }
+                WasmIntMnemonic::MemoryFill => {
+                    let stack_level = code.stack_level();
+                    let dest = unsafe { value_stack.get_unchecked(stack_level).get_u32() as usize };
+                    let val = unsafe { value_stack.get_unchecked(stack_level + 1).get_u8() };
+                    let count =
+                        unsafe { value_stack.get_unchecked(stack_level + 2).get_u32() as usize };
+                    match memory.fill(dest, val, count) {
+                        Ok(_) => {}
+                        Err(e) => return Err(self.error(e, code)),
+                    }
+                }
+
+                WasmIntMnemonic::RefNull => {
+                    let ref_a = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    *ref_a = WasmStackValue::from_ref(None);
+                }
+                WasmIntMnemonic::RefIsNull => {
+                    let ref_a = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    *ref_a = WasmStackValue::from_bool(ref_a.get_ref().is_none());
+                }
+                WasmIntMnemonic::RefFunc => {
+                    let ref_a = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    *ref_a = WasmStackValue::from_ref(Some(code.param1() as usize));
+                }
+
+                WasmIntMnemonic::TableGet => {
+                    let table = self.table(code.param1() as usize, code)?;
+                    let ref_a = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    match table.get(ref_a.get_u32() as usize) {
+                        Ok(v) => *ref_a = WasmStackValue::from_ref(v),
+                        Err(e) => return Err(self.error(e, code)),
+                    }
+                }
+                WasmIntMnemonic::TableSet => {
+                    let stack_level = code.stack_level();
+                    let table = self.table(code.param1() as usize, code)?;
+                    let index =
+                        unsafe { value_stack.get_unchecked(stack_level).get_u32() as usize };
+                    let val = unsafe { value_stack.get_unchecked(stack_level + 1).get_ref() };
+                    match table.set(index, val) {
+                        Ok(_) => {}
+                        Err(e) => return Err(self.error(e, code)),
+                    }
+                }
+                WasmIntMnemonic::TableInit => {
+                    let stack_level = code.stack_level();
+                    let dest = unsafe { value_stack.get_unchecked(stack_level).get_u32() as usize };
+                    let src =
+                        unsafe { value_stack.get_unchecked(stack_level + 1).get_u32() as usize };
+                    let count =
+                        unsafe { value_stack.get_unchecked(stack_level + 2).get_u32() as usize };
+                    let table = self.table((code.param1() >> 32) as usize, code)?;
+                    let items = match self.module.element(code.param1() as u32 as usize) {
+                        Some(v) => v.items(),
+                        None => return Err(self.error(WasmRuntimeErrorType::OutOfBounds, code)),
+                    };
+                    let src = match src
+                        .checked_add(count)
+                        .and_then(|src_end| items.get(src..src_end))
+                    {
+                        Some(v) => v,
+                        None => return Err(self.error(WasmRuntimeErrorType::OutOfBounds, code)),
+                    };
+                    match table.init(dest, src) {
+                        Ok(_) => {}
+                        Err(e) => return Err(self.error(e, code)),
+                    }
+                }
+                WasmIntMnemonic::ElemDrop => {
+                    if let Some(elem) = self.module.element(code.param1() as usize) {
+                        elem.discard();
+                    }
+                }
+                WasmIntMnemonic::TableCopy => {
+                    let stack_level = code.stack_level();
+                    let dest = unsafe { value_stack.get_unchecked(stack_level).get_u32() as usize };
+                    let src =
+                        unsafe { value_stack.get_unchecked(stack_level + 1).get_u32() as usize };
+                    let count =
+                        unsafe { value_stack.get_unchecked(stack_level + 2).get_u32() as usize };
+                    let dest_table = self.table(code.param1() as u32 as usize, code)?;
+                    let src_table = self.table((code.param1() >> 32) as usize, code)?;
+                    match dest_table.copy(dest, src_table, src, count) {
+                        Ok(_) => {}
+                        Err(e) => return Err(self.error(e, code)),
+                    }
+                }
+                WasmIntMnemonic::TableGrow => {
+                    let stack_level = code.stack_level();
+                    let table = self.table(code.param1() as usize, code)?;
+                    let val = unsafe { value_stack.get_unchecked(stack_level).get_ref() };
+                    let delta = unsafe { value_stack.get_unchecked(stack_level + 1).get_u32() };
+                    let ref_a = unsafe { value_stack.get_unchecked_mut(stack_level) };
+                    *ref_a = WasmStackValue::from_i32(table.grow(delta, val));
+                }
+                WasmIntMnemonic::TableSize => {
+                    let table = self.table(code.param1() as usize, code)?;
+                    let ref_a = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    *ref_a = WasmStackValue::from_u32(table.len() as u32);
+                }
+                WasmIntMnemonic::TableFill => {
+                    let stack_level = code.stack_level();
+                    let table = self.table(code.param1() as usize, code)?;
+                    let dest = unsafe { value_stack.get_unchecked(stack_level).get_u32() as usize };
+                    let val = unsafe { value_stack.get_unchecked(stack_level + 1).get_ref() };
+                    let count =
+                        unsafe { value_stack.get_unchecked(stack_level + 2).get_u32() as usize };
+                    match table.fill(dest, val, count) {
+                        Ok(_) => {}
+                        Err(e) => return Err(self.error(e, code)),
+                    }
+                }
+
+                WasmIntMnemonic::MemoryAtomicNotify => {
+                    let stack_level = code.stack_level();
+                    let index =
+                        unsafe { value_stack.get_unchecked(stack_level).get_u32() as usize };
+                    let count = unsafe { value_stack.get_unchecked(stack_level + 1).get_u32() };
+                    let offset = code.param1() as u32 as usize + index;
+                    let address = match memory.atomic::<AtomicU32>(offset) {
+                        Ok(v) => v as *const _ as usize,
+                        Err(e) => return Err(self.error(e, code)),
+                    };
+                    let woken = if memory.is_shared() {
+                        match self.module.atomic_notify(address, count) {
+                            Ok(v) => v,
+                            Err(e) => return Err(self.error(e, code)),
+                        }
+                    } else {
+                        0
+                    };
+                    let ref_a = unsafe { value_stack.get_unchecked_mut(stack_level) };
+                    *ref_a = WasmStackValue::from_u32(woken);
+                }
+                WasmIntMnemonic::MemoryAtomicWait32 | WasmIntMnemonic::MemoryAtomicWait64 => {
+                    let stack_level = code.stack_level();
+                    let is_64bit = code.mnemonic() == WasmIntMnemonic::MemoryAtomicWait64;
+                    let index =
+                        unsafe { value_stack.get_unchecked(stack_level).get_u32() as usize };
+                    let expected = unsafe { value_stack.get_unchecked(stack_level + 1).get_u64() };
+                    let timeout = unsafe { value_stack.get_unchecked(stack_level + 2).get_i64() };
+                    let offset = code.param1() as u32 as usize + index;
+                    let width = if is_64bit { 8 } else { 4 };
+                    if let Err(e) = memory.atomic_load(offset, width) {
+                        return Err(self.error(e, code));
+                    }
+                    if !memory.is_shared() {
+                        return Err(self.error(WasmRuntimeErrorType::NotSupprted, code));
+                    }
+                    let timeout = if timeout < 0 {
+                        None
+                    } else {
+                        Some(Duration::from_nanos(timeout as u64))
+                    };
+                    let wait = WasmAtomicWait::new(memory, offset, expected, is_64bit, timeout);
+                    let result = match self.module.atomic_wait(&wait) {
+                        Ok(v) => v,
+                        Err(e) => return Err(self.error(e, code)),
+                    };
+                    let ref_a = unsafe { value_stack.get_unchecked_mut(stack_level) };
+                    *ref_a = WasmStackValue::from_u32(result);
+                }
+                WasmIntMnemonic::AtomicFence => {
+                    fence(Ordering::SeqCst);
+                }
+
+                WasmIntMnemonic::AtomicLoad8 => {
+                    let var = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    let offset = code.param1() as u32 as usize + var.get_u32() as usize;
+                    *var = match memory.atomic_load(offset, 1) {
+                        Ok(v) => WasmStackValue::from_u64(v),
+                        Err(e) => return Err(self.error(e, code)),
+                    };
+                }
+                WasmIntMnemonic::AtomicLoad16 => {
+                    let var = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    let offset = code.param1() as u32 as usize + var.get_u32() as usize;
+                    *var = match memory.atomic_load(offset, 2) {
+                        Ok(v) => WasmStackValue::from_u64(v),
+                        Err(e) => return Err(self.error(e, code)),
+                    };
+                }
+                WasmIntMnemonic::AtomicLoad32 => {
+                    let var = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    let offset = code.param1() as u32 as usize + var.get_u32() as usize;
+                    *var = match memory.atomic_load(offset, 4) {
+                        Ok(v) => WasmStackValue::from_u64(v),
+                        Err(e) => return Err(self.error(e, code)),
+                    };
+                }
+                WasmIntMnemonic::AtomicLoad64 => {
+                    let var = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    let offset = code.param1() as u32 as usize + var.get_u32() as usize;
+                    *var = match memory.atomic_load(offset, 8) {
+                        Ok(v) => WasmStackValue::from_u64(v),
+                        Err(e) => return Err(self.error(e, code)),
+                    };
+                }
+                WasmIntMnemonic::AtomicStore8 => {
+                    let stack_level = code.stack_level();
+                    let index =
+                        unsafe { value_stack.get_unchecked(stack_level).get_u32() as usize };
+                    let data = unsafe { value_stack.get_unchecked(stack_level + 1).get_u64() };
+                    let offset = code.param1() as u32 as usize + index;
+                    if let Err(e) = memory.atomic_store(offset, 1, data) {
+                        return Err(self.error(e, code));
+                    }
+                }
+                WasmIntMnemonic::AtomicStore16 => {
+                    let stack_level = code.stack_level();
+                    let index =
+                        unsafe { value_stack.get_unchecked(stack_level).get_u32() as usize };
+                    let data = unsafe { value_stack.get_unchecked(stack_level + 1).get_u64() };
+                    let offset = code.param1() as u32 as usize + index;
+                    if let Err(e) = memory.atomic_store(offset, 2, data) {
+                        return Err(self.error(e, code));
+                    }
+                }
+                WasmIntMnemonic::AtomicStore32 => {
+                    let stack_level = code.stack_level();
+                    let index =
+                        unsafe { value_stack.get_unchecked(stack_level).get_u32() as usize };
+                    let data = unsafe { value_stack.get_unchecked(stack_level + 1).get_u64() };
+                    let offset = code.param1() as u32 as usize + index;
+                    if let Err(e) = memory.atomic_store(offset, 4, data) {
+                        return Err(self.error(e, code));
+                    }
+                }
+                WasmIntMnemonic::AtomicStore64 => {
+                    let stack_level = code.stack_level();
+                    let index =
+                        unsafe { value_stack.get_unchecked(stack_level).get_u32() as usize };
+                    let data = unsafe { value_stack.get_unchecked(stack_level + 1).get_u64() };
+                    let offset = code.param1() as u32 as usize + index;
+                    if let Err(e) = memory.atomic_store(offset, 8, data) {
+                        return Err(self.error(e, code));
+                    }
+                }
+                WasmIntMnemonic::AtomicRmw8 => {
+                    let stack_level = code.stack_level();
+                    let op = WasmAtomicRmwOp::from_u64(code.param1() >> 32);
+                    let index =
+                        unsafe { value_stack.get_unchecked(stack_level).get_u32() as usize };
+                    let data = unsafe { value_stack.get_unchecked(stack_level + 1).get_u64() };
+                    let offset = code.param1() as u32 as usize + index;
+                    let ref_a = unsafe { value_stack.get_unchecked_mut(stack_level) };
+                    *ref_a = match memory.atomic_rmw(offset, 1, op, data) {
+                        Ok(v) => WasmStackValue::from_u64(v),
+                        Err(e) => return Err(self.error(e, code)),
+                    };
+                }
+                WasmIntMnemonic::AtomicRmw16 => {
+                    let stack_level = code.stack_level();
+                    let op = WasmAtomicRmwOp::from_u64(code.param1() >> 32);
+                    let index =
+                        unsafe { value_stack.get_unchecked(stack_level).get_u32() as usize };
+                    let data = unsafe { value_stack.get_unchecked(stack_level + 1).get_u64() };
+                    let offset = code.param1() as u32 as usize + index;
+                    let ref_a = unsafe { value_stack.get_unchecked_mut(stack_level) };
+                    *ref_a = match memory.atomic_rmw(offset, 2, op, data) {
+                        Ok(v) => WasmStackValue::from_u64(v),
+                        Err(e) => return Err(self.error(e, code)),
+                    };
+                }
+                WasmIntMnemonic::AtomicRmw32 => {
+                    let stack_level = code.stack_level();
+                    let op = WasmAtomicRmwOp::from_u64(code.param1() >> 32);
+                    let index =
+                        unsafe { value_stack.get_unchecked(stack_level).get_u32() as usize };
+                    let data = unsafe { value_stack.get_unchecked(stack_level + 1).get_u64() };
+                    let offset = code.param1() as u32 as usize + index;
+                    let ref_a = unsafe { value_stack.get_unchecked_mut(stack_level) };
+                    *ref_a = match memory.atomic_rmw(offset, 4, op, data) {
+                        Ok(v) => WasmStackValue::from_u64(v),
+                        Err(e) => return Err(self.error(e, code)),
+                    };
+                }
+                WasmIntMnemonic::AtomicRmw64 => {
+                    let stack_level = code.stack_level();
+                    let op = WasmAtomicRmwOp::from_u64(code.param1() >> 32);
+                    let index =
+                        unsafe { value_stack.get_unchecked(stack_level).get_u32() as usize };
+                    let data = unsafe { value_stack.get_unchecked(stack_level + 1).get_u64() };
+                    let offset = code.param1() as u32 as usize + index;
+                    let ref_a = unsafe { value_stack.get_unchecked_mut(stack_level) };
+                    *ref_a = match memory.atomic_rmw(offset, 8, op, data) {
+                        Ok(v) => WasmStackValue::from_u64(v),
+                        Err(e) => return Err(self.error(e, code)),
+                    };
+                }
+                WasmIntMnemonic::AtomicCmpxchg8 => {
+                    let stack_level = code.stack_level();
+                    let index =
+                        unsafe { value_stack.get_unchecked(stack_level).get_u32() as usize };
+                    let expected = unsafe { value_stack.get_unchecked(stack_level + 1).get_u64() };
+                    let replacement =
+                        unsafe { value_stack.get_unchecked(stack_level + 2).get_u64() };
+                    let offset = code.param1() as u32 as usize + index;
+                    let ref_a = unsafe { value_stack.get_unchecked_mut(stack_level) };
+                    *ref_a = match memory.atomic_cmpxchg(offset, 1, expected, replacement) {
+                        Ok(v) => WasmStackValue::from_u64(v),
+                        Err(e) => return Err(self.error(e, code)),
+                    };
+                }
+                WasmIntMnemonic::AtomicCmpxchg16 => {
+                    let stack_level = code.stack_level();
+                    let index =
+                        unsafe { value_stack.get_unchecked(stack_level).get_u32() as usize };
+                    let expected = unsafe { value_stack.get_unchecked(stack_level + 1).get_u64() };
+                    let replacement =
+                        unsafe { value_stack.get_unchecked(stack_level + 2).get_u64() };
+                    let offset = code.param1() as u32 as usize + index;
+                    let ref_a = unsafe { value_stack.get_unchecked_mut(stack_level) };
+                    *ref_a = match memory.atomic_cmpxchg(offset, 2, expected, replacement) {
+                        Ok(v) => WasmStackValue::from_u64(v),
+                        Err(e) => return Err(self.error(e, code)),
+                    };
+                }
+                WasmIntMnemonic::AtomicCmpxchg32 => {
+                    let stack_level = code.stack_level();
+                    let index =
+                        unsafe { value_stack.get_unchecked(stack_level).get_u32() as usize };
+                    let expected = unsafe { value_stack.get_unchecked(stack_level + 1).get_u64() };
+                    let replacement =
+                        unsafe { value_stack.get_unchecked(stack_level + 2).get_u64() };
+                    let offset = code.param1() as u32 as usize + index;
+                    let ref_a = unsafe { value_stack.get_unchecked_mut(stack_level) };
+                    *ref_a = match memory.atomic_cmpxchg(offset, 4, expected, replacement) {
+                        Ok(v) => WasmStackValue::from_u64(v),
+                        Err(e) => return Err(self.error(e, code)),
+                    };
+                }
+                WasmIntMnemonic::AtomicCmpxchg64 => {
+                    let stack_level = code.stack_level();
+                    let index =
+                        unsafe { value_stack.get_unchecked(stack_level).get_u32() as usize };
+                    let expected = unsafe { value_stack.get_unchecked(stack_level + 1).get_u64() };
+                    let replacement =
+                        unsafe { value_stack.get_unchecked(stack_level + 2).get_u64() };
+                    let offset = code.param1() as u32 as usize + index;
+                    let ref_a = unsafe { value_stack.get_unchecked_mut(stack_level) };
+                    *ref_a = match memory.atomic_cmpxchg(offset, 8, expected, replacement) {
+                        Ok(v) => WasmStackValue::from_u64(v),
+                        Err(e) => return Err(self.error(e, code)),
+                    };
+                }
+
+                WasmIntMnemonic::V128Load => {
+                    let op = self.simd_opcode(code.param1() >> 32, code)?;
+                    let var = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    let offset = code.param1() as u32 as usize + var.get_u32() as usize;
+                    *var = match simd::load(op, memory, offset) {
+                        Ok(v) => WasmStackValue::from_v128(v),
+                        Err(e) => return Err(self.error(e, code)),
+                    };
+                }
+                WasmIntMnemonic::V128Store => {
+                    let stack_level = code.stack_level();
+                    let index =
+                        unsafe { value_stack.get_unchecked(stack_level).get_u32() as usize };
+                    let data = unsafe { value_stack.get_unchecked(stack_level + 1).get_v128() };
+                    let offset = code.param1() as u32 as usize + index;
+                    if let Err(e) = memory.write_u128(offset, data) {
+                        return Err(self.error(e, code));
+                    }
+                }
+                WasmIntMnemonic::V128LoadLane => {
+                    let stack_level = code.stack_level();
+                    let op = self.simd_opcode((code.param1() >> 32) as u16 as u64, code)?;
+                    let lane = (code.param1() >> 48) as usize;
+                    let index =
+                        unsafe { value_stack.get_unchecked(stack_level).get_u32() as usize };
+                    let data = unsafe { value_stack.get_unchecked(stack_level + 1).get_v128() };
+                    let offset = code.param1() as u32 as usize + index;
+                    let ref_a = unsafe { value_stack.get_unchecked_mut(stack_level) };
+                    *ref_a = match simd::load_lane(op, memory, offset, data, lane) {
+                        Ok(v) => WasmStackValue::from_v128(v),
+                        Err(e) => return Err(self.error(e, code)),
+                    };
+                }
+                WasmIntMnemonic::V128StoreLane => {
+                    let stack_level = code.stack_level();
+                    let op = self.simd_opcode((code.param1() >> 32) as u16 as u64, code)?;
+                    let lane = (code.param1() >> 48) as usize;
+                    let index =
+                        unsafe { value_stack.get_unchecked(stack_level).get_u32() as usize };
+                    let data = unsafe { value_stack.get_unchecked(stack_level + 1).get_v128() };
+                    let offset = code.param1() as u32 as usize + index;
+                    if let Err(e) = simd::store_lane(op, memory, offset, data, lane) {
+                        return Err(self.error(e, code));
+                    }
+                }
+                WasmIntMnemonic::V128Const => {
+                    let imm = Self::v128_param(code_block.ext_params(), code.param1() as usize);
+                    let ref_a = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    *ref_a = WasmStackValue::from_v128(imm);
+                }
+                WasmIntMnemonic::I8x16Shuffle => {
+                    let stack_level = code.stack_level();
+                    let lanes = Self::v128_param(code_block.ext_params(), code.param1() as usize);
+                    let a = unsafe { value_stack.get_unchecked(stack_level).get_v128() };
+                    let b = unsafe { value_stack.get_unchecked(stack_level + 1).get_v128() };
+                    let ref_a = unsafe { value_stack.get_unchecked_mut(stack_level) };
+                    *ref_a = WasmStackValue::from_v128(simd::shuffle(a, b, lanes));
+                }
+                WasmIntMnemonic::Simd => {
+                    let op = self.simd_opcode(code.param1() as u32 as u64, code)?;
+                    let lane = (code.param1() >> 32) as usize;
+                    simd::exec(op, lane, &mut value_stack[code.stack_level()..]);
+                }
+
+                WasmIntMnemonic::FusedI32AddI => {
+                    let lhs = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    lhs.map_i32(|lhs| lhs.wrapping_add(code.param1() as i32));
+                }
+                WasmIntMnemonic::FusedI32SubI => {
+                    let lhs = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    lhs.map_i32(|lhs| lhs.wrapping_sub(code.param1() as i32));
+                }
+                WasmIntMnemonic::FusedI32AndI => {
+                    let lhs = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    lhs.map_i32(|lhs| lhs & code.param1() as i32);
+                }
+                WasmIntMnemonic::FusedI32OrI => {
+                    let lhs = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    lhs.map_i32(|lhs| lhs | code.param1() as i32);
+                }
+                WasmIntMnemonic::FusedI32XorI => {
+                    let lhs = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    lhs.map_i32(|lhs| lhs ^ code.param1() as i32);
+                }
+                WasmIntMnemonic::FusedI32ShlI => {
+                    let lhs = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    lhs.map_u32(|lhs| lhs << (code.param1() as u32));
+                }
+                WasmIntMnemonic::FusedI32ShrUI => {
+                    let lhs = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    lhs.map_u32(|lhs| lhs >> (code.param1() as u32));
+                }
+                WasmIntMnemonic::FusedI32ShrSI => {
+                    let lhs = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    lhs.map_i32(|lhs| lhs >> (code.param1() as i32));
+                }
+
+                WasmIntMnemonic::FusedI64AddI => {
+                    let lhs = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    lhs.map_i64(|lhs| lhs.wrapping_add(code.param1() as i64));
+                }
+                WasmIntMnemonic::FusedI64SubI => {
+                    let lhs = unsafe { value_stack.get_unchecked_mut(code.stack_level()) };
+                    lhs.map_i64(|lhs| lhs.wrapping_sub(code.param1() as i64));
+                }
+
+                WasmIntMnemonic::FusedI32BrZ => {
+                    let cc =
+                        unsafe { value_stack.get_unchecked_mut(code.stack_level()).get_i32() == 0 };
+                    if cc {
+                        let br = code.param1() as usize;
+                        self.branch(&mut codes, br, code)?;
+                    }
+                }
+                WasmIntMnemonic::FusedI64BrZ => {
+                    let cc =
+                        unsafe { value_stack.get_unchecked_mut(code.stack_level()).get_i64() == 0 };
+                    if cc {
+                        let br = code.param1() as usize;
+                        self.branch(&mut codes, br, code)?;
+                    }
+                }
+
+                _ => return Err(self.error(WasmRuntimeErrorType::NotSupprted, code)),
+            }
+        }
+        let n_results = results.len();
+        results.copy_from_slice(&value_stack[result_stack_level..result_stack_level + n_results]);
+        Ok(None)
+    }
+
+    /// Moves to the catch-clause that handles the exception thrown by the last fetched code,
+    /// or returns the error if no handler in this function handles it.
+    fn catch(
+        &mut self,
+        err: WasmRuntimeError,
+        code_block: &WasmCodeBlock,
+        codes: &mut WasmIntermediateCodeStream,
+        value_stack: &mut [WasmStackValue],
+        caught: &mut [Option<WasmException>],
+    ) -> Result<(), WasmRuntimeError> {
+        if err.kind() != WasmRuntimeErrorType::Exception {
+            return Err(err);
+        }
+        let tag = match self.exception.as_ref() {
+            Some(v) => v.tag,
+            None => return Err(err),
+        };
+        let handlers = code_block.exception_handlers();
+        let position = codes.position() - 1;
+        let mut next = code_block.find_exception_handler(0, position, position + 1);
+        while let Some(index) = next {
+            let handler = &handlers[index];
+            let (start, end) = handler.range();
+            if let Some(delegate) = handler.delegate() {
+                next = match delegate {
+                    WasmDelegateTarget::Caller => None,
+                    WasmDelegateTarget::Handler(v) => Some(v),
+                    WasmDelegateTarget::Block(start, end) => {
+                        code_block.find_exception_handler(index + 1, start, end)
+                    }
+                };
+                continue;
+            }
+            for (catch_tag, target) in handler.catches() {
+                if catch_tag.map(|v| v == tag).unwrap_or(true) {
+                    let exception = self.exception.take();
+                    if let (Some(exception), Some(_)) = (exception.as_ref(), catch_tag) {
+                        let stack_level = handler.stack_level();
+                        value_stack[stack_level..stack_level + exception.values.len()]
+                            .copy_from_slice(&exception.values);
+                    }
+                    caught[index] = exception;
+                    codes.set_position(*target);
+                    return Ok(());
+                }
+            }
+            next = code_block.find_exception_handler(index + 1, start, end);
+        }
+        Err(err)
+    }
+
+    /// Calls the function in tail position.
+    ///
+    /// The parameters of a function with a code block are moved to `tail_params`,
+    /// and its index is returned to be called after this frame is discarded.
+    /// A host function is just called since it does not grow the value stack.
+    fn tail_call(
+        &mut self,
+        target: &WasmFunction,
+        code: &WasmImc,
+        value_stack: &mut [WasmStackValue],
+        results: &mut [WasmStackValue],
+        heap: &mut StackHeap,
+    ) -> Result<Option<usize>, WasmRuntimeError> {
+        let stack_pointer = code.stack_level();
+        let stack_under = stack_pointer - target.param_types().len();
+        if target.code_block().is_some() {
+            self.module
+                .consume_fuel()
+                .map_err(|kind| self.error(kind, code))?;
+            self.tail_params.clear();
+            self.tail_params
+                .extend_from_slice(&value_stack[stack_under..stack_pointer]);
+            Ok(Some(target.index()))
+        } else {
+            self.call(target, code, value_stack, heap)?;
+            let n_results = results.len();
+            results.copy_from_slice(&value_stack[stack_under..stack_under + n_results]);
+            Ok(None)
+        }
+    }
+
+    /// Runs the native code generated by the JIT compiler.
+    #[cfg(target_arch = "x86_64")]
+    fn run_jit(
+        &mut self,
+        jit_code: &WasmJitCode,
+        code_block: &WasmCodeBlock,
+        locals: &mut [WasmStackValue],
+        results: &mut [WasmStackValue],
+        heap: &mut StackHeap,
+    ) -> Result<(), WasmRuntimeError> {
+        let value_stack = heap.alloc(code_block.max_value_stack());
+        for value in value_stack.iter_mut() {
+            *value = WasmStackValue::zero();
+        }
+
+        let (memory_base, memory_size) = Self::jit_memory(self.module);
+        let mut env = WasmJitEnv {
+            interp: self,
+            heap,
+            code_block,
+            stack_len: value_stack.len(),
+            error: None,
+        };
+        let mut context = WasmJitContext {
+            memory_base,
+            memory_size,
+            trap_position: 0,
+            result_level: 0,
+            call: Self::jit_call,
+            env: &mut env as *mut WasmJitEnv as *mut _,
+        };
+
+        let status = unsafe {
+            (jit_code.entry())(&mut context, locals.as_mut_ptr(), value_stack.as_mut_ptr())
+        };
+        match status {
+            STATUS_SUCCESS => {
+                let result_stack_level = context.result_level;
+                let n_results = results.len();
+                results.copy_from_slice(
+                    &value_stack[result_stack_level..result_stack_level + n_results],
+                );
+                Ok(())
+            }
+            STATUS_CALL_ERROR => Err(env
+                .error
+                .take()
+                .unwrap_or(WasmRuntimeErrorType::InvalidParameter.into())),
+            _ => {
+                let code = &code_block.intermediate_codes()[context.trap_position];
+                Err(env.interp.error(trap_kind(status), code))
+            }
+        }
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[inline]
+    fn jit_memory(module: &WasmModule) -> (*mut u8, usize) {
+        module
+            .memory(0)
+            .map(|v| v.raw_parts())
+            .unwrap_or((core::ptr::null_mut(), 0))
+    }
+
+    /// Calls a function on behalf of the native code.
+    #[cfg(target_arch = "x86_64")]
+    unsafe extern "sysv64" fn jit_call(
+        context: *mut WasmJitContext,
+        index: usize,
+        value_stack: *mut WasmStackValue,
+    ) -> u32 {
+        let context = &mut *context;
+        let env = &mut *(context.env as *mut WasmJitEnv);
+        let code = &env.code_block.intermediate_codes()[index];
+        let value_stack = core::slice::from_raw_parts_mut(value_stack, env.stack_len);
+        let module = env.interp.module;
+        let func = module.functions().get_unchecked(code.param1() as usize);
+
+        let result = env.interp.call(func, code, value_stack, env.heap);
+
+        let (memory_base, memory_size) = Self::jit_memory(module);
+        context.memory_base = memory_base;
+        context.memory_size = memory_size;
+
+        match result {
+            Ok(_) => STATUS_SUCCESS,
+            Err(err) => {
+                env.error = Some(err);
+                STATUS_CALL_ERROR
+            }
+        }
+    }
+
+    #[inline]
+    fn call(
+        &mut self,
+        target: &WasmFunction,
+        code: &WasmImc,
+        value_stack: &mut [WasmStackValue],
+        heap: &mut StackHeap,
+    ) -> Result<(), WasmRuntimeError> {
+        self.module
+            .consume_fuel()
+            .map_err(|kind| self.error(kind, code))?;
+
+        let stack_pointer = code.stack_level();
+        let current_function = self.func_index;
+        let module = self.module;
+        let result_types = target.result_types();
+
+        let param_len = target.param_types().len();
+        // if stack_pointer < param_len {
+        //     return Err(self.error(WasmRuntimeError::InternalInconsistency, code));
+        // }
+
+        if let Some(code_block) = target.code_block() {
+            heap.snapshot(|heap| {
+                let mut locals = heap.alloc_stack(param_len + code_block.local_types().len());
+                let stack_under = stack_pointer - param_len;
+
+                locals.extend_from_slice(&value_stack[stack_under..stack_under + param_len]);
+                for _ in code_block.local_types() {
+                    let _ = locals.push(WasmStackValue::zero());
+                }
+
+                self.func_index = target.index();
+
+                let results = &mut value_stack[stack_under..stack_under + result_types.len()];
+                self.interpret(code_block, locals.as_mut_slice(), results, heap)
+                    .map(|_| {
+                        self.func_index = current_function;
+                    })
+            })
+            .map_err(|mut err| {
+                err.callers.push(WasmStackFrame::new(
+                    current_function,
+                    code.source_position(),
+                ));
+                err
+            })
+        } else if let Some(dlink) = target.dlink() {
+            heap.snapshot(|heap| {
+                let mut locals = heap.alloc_stack(param_len);
+                let stack_under = stack_pointer - param_len;
+                let params = &value_stack[stack_under..stack_under + param_len];
+                for (index, val_type) in target.param_types().iter().enumerate() {
+                    let _ = locals.push(params[index].get_by_type(*val_type));
+                }
+
+                let result = match module.profiler() {
+                    Some(profiler) => {
+                        let start = profiler.now();
+                        let result = dlink(module, locals.as_slice());
+                        let elapsed = profiler.now().wrapping_sub(start);
+                        profiler.record(target.index(), 0, elapsed, elapsed);
+                        self.child_time += elapsed;
+                        result
+                    }
+                    None => dlink(module, locals.as_slice()),
+                };
+                let result = match result {
+                    Ok(v) => v,
+                    Err(e) => return Err(self.error(e, code)),
+                };
+
+                if let Some(t) = result_types.first() {
                     if result.is_valid_type(*t) {
                         let var = match value_stack.get_mut(stack_under) {
                             Some(v) => v,
@@ -984,6 +2368,92 @@ impl WasmInterpreter<'_> {
     }
 }
 
+/// Replaces any NaN with the canonical NaN of the same sign.
+#[inline]
+fn canonicalize_f32(v: f32) -> f32 {
+    if v.is_nan() {
+        f32::from_bits(0x7FC0_0000 | (v.to_bits() & 0x8000_0000))
+    } else {
+        v
+    }
+}
+
+/// Replaces any NaN with the canonical NaN of the same sign.
+#[inline]
+fn canonicalize_f64(v: f64) -> f64 {
+    if v.is_nan() {
+        f64::from_bits(0x7FF8_0000_0000_0000 | (v.to_bits() & 0x8000_0000_0000_0000))
+    } else {
+        v
+    }
+}
+
+/// `f32.min`: propagates NaN and treats `-0.0` as less than `+0.0`
+#[inline]
+fn min_f32(lhs: f32, rhs: f32) -> f32 {
+    if lhs.is_nan() || rhs.is_nan() {
+        canonicalize_f32(lhs + rhs)
+    } else if lhs == rhs {
+        f32::from_bits(lhs.to_bits() | rhs.to_bits())
+    } else if lhs < rhs {
+        lhs
+    } else {
+        rhs
+    }
+}
+
+/// `f32.max`: propagates NaN and treats `+0.0` as greater than `-0.0`
+#[inline]
+fn max_f32(lhs: f32, rhs: f32) -> f32 {
+    if lhs.is_nan() || rhs.is_nan() {
+        canonicalize_f32(lhs + rhs)
+    } else if lhs == rhs {
+        f32::from_bits(lhs.to_bits() & rhs.to_bits())
+    } else if lhs > rhs {
+        lhs
+    } else {
+        rhs
+    }
+}
+
+/// `f64.min`: propagates NaN and treats `-0.0` as less than `+0.0`
+#[inline]
+fn min_f64(lhs: f64, rhs: f64) -> f64 {
+    if lhs.is_nan() || rhs.is_nan() {
+        canonicalize_f64(lhs + rhs)
+    } else if lhs == rhs {
+        f64::from_bits(lhs.to_bits() | rhs.to_bits())
+    } else if lhs < rhs {
+        lhs
+    } else {
+        rhs
+    }
+}
+
+/// `f64.max`: propagates NaN and treats `+0.0` as greater than `-0.0`
+#[inline]
+fn max_f64(lhs: f64, rhs: f64) -> f64 {
+    if lhs.is_nan() || rhs.is_nan() {
+        canonicalize_f64(lhs + rhs)
+    } else if lhs == rhs {
+        f64::from_bits(lhs.to_bits() & rhs.to_bits())
+    } else if lhs > rhs {
+        lhs
+    } else {
+        rhs
+    }
+}
+
+/// Runtime state shared with the callback from the native code
+#[cfg(target_arch = "x86_64")]
+struct WasmJitEnv<'a, 'b> {
+    interp: &'a mut WasmInterpreter<'b>,
+    heap: &'a mut StackHeap,
+    code_block: &'a WasmCodeBlock,
+    stack_len: usize,
+    error: Option<WasmRuntimeError>,
+}
+
 struct WasmIntermediateCodeStream<'a> {
     codes: &'a [WasmImc],
     position: usize,
@@ -996,16 +2466,15 @@ impl<'a> WasmIntermediateCodeStream<'a> {
     }
 }
 
-impl WasmIntermediateCodeStream<'_> {
+impl<'a> WasmIntermediateCodeStream<'a> {
     #[inline]
-    fn fetch(&mut self) -> Option<&WasmImc> {
+    fn fetch(&mut self) -> Option<&'a WasmImc> {
         self.codes.get(self.position).map(|v| {
             self.position += 1;
             v
         })
     }
 
-    #[allow(dead_code)]
     #[inline]
     const fn position(&self) -> usize {
         self.position
@@ -1019,10 +2488,17 @@ impl WasmIntermediateCodeStream<'_> {
 
 pub trait WasmInvocation {
     fn invoke(&self, params: &[WasmValue]) -> Result<Option<WasmValue>, WasmRuntimeError>;
+
+    /// Invokes the function and returns all of its results.
+    fn invoke_multi(&self, params: &[WasmValue]) -> Result<Vec<WasmValue>, WasmRuntimeError>;
 }
 
 impl WasmInvocation for WasmRunnable<'_> {
     fn invoke(&self, params: &[WasmValue]) -> Result<Option<WasmValue>, WasmRuntimeError> {
+        self.invoke_multi(params).map(|v| v.first().map(|v| *v))
+    }
+
+    fn invoke_multi(&self, params: &[WasmValue]) -> Result<Vec<WasmValue>, WasmRuntimeError> {
         let function = self.function();
         let code_block = function
             .code_block()
@@ -1046,7 +2522,7 @@ impl WasmInvocation for WasmRunnable<'_> {
         let result_types = function.result_types();
 
         let mut interp = WasmInterpreter::new(self.module());
-        interp.invoke(
+        interp.invoke_multi(
             function.index(),
             code_block,
             locals.as_slice(),
@@ -1060,6 +2536,7 @@ pub struct WasmRuntimeError {
     function: usize,
     position: usize,
     opcode: WasmOpcode,
+    callers: Vec<WasmStackFrame>,
 }
 
 impl WasmRuntimeError {
@@ -1082,6 +2559,13 @@ impl WasmRuntimeError {
     pub const fn opcode(&self) -> WasmOpcode {
         self.opcode
     }
+
+    /// Returns the call stack at the time of the error, starting with the function where it occurred.
+    pub fn backtrace(&self) -> impl Iterator<Item = WasmStackFrame> + '_ {
+        Some(WasmStackFrame::new(self.function, self.position))
+            .into_iter()
+            .chain(self.callers.iter().copied())
+    }
 }
 
 impl From<WasmRuntimeErrorType> for WasmRuntimeError {
@@ -1092,10 +2576,101 @@ impl From<WasmRuntimeErrorType> for WasmRuntimeError {
             function: 0,
             position: 0,
             opcode: WasmOpcode::Unreachable,
+            callers: Vec::new(),
         }
     }
 }
 
+/// A frame of the call stack
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct WasmStackFrame {
+    function: usize,
+    position: usize,
+}
+
+impl WasmStackFrame {
+    #[inline]
+    pub const fn new(function: usize, position: usize) -> Self {
+        Self { function, position }
+    }
+
+    /// Returns the index of the function
+    #[inline]
+    pub const fn function(&self) -> usize {
+        self.function
+    }
+
+    /// Returns the position in the function body
+    #[inline]
+    pub const fn position(&self) -> usize {
+        self.position
+    }
+}
+
+/// The state of the interpreter passed to the debug handler, just before an instruction is executed
+pub struct WasmDebugFrame<'a> {
+    module: &'a WasmModule,
+    func_index: usize,
+    code: &'a WasmImc,
+    is_entry: bool,
+    local_types: &'a [WasmValType],
+    locals: &'a [WasmStackValue],
+    stack: &'a [WasmStackValue],
+}
+
+impl WasmDebugFrame<'_> {
+    #[inline]
+    pub const fn module(&self) -> &WasmModule {
+        self.module
+    }
+
+    /// Returns the index of the function
+    #[inline]
+    pub const fn function(&self) -> usize {
+        self.func_index
+    }
+
+    /// Returns the position of the instruction in the function body, same as `WasmRuntimeError::position`
+    #[inline]
+    pub const fn position(&self) -> usize {
+        self.code.source_position()
+    }
+
+    #[inline]
+    pub const fn opcode(&self) -> Option<WasmOpcode> {
+        self.code.opcode()
+    }
+
+    /// Returns whether the instruction is the first one executed in this call of the function
+    #[inline]
+    pub const fn is_entry(&self) -> bool {
+        self.is_entry
+    }
+
+    /// Returns the types of the local variables, including the parameters
+    #[inline]
+    pub const fn local_types(&self) -> &[WasmValType] {
+        self.local_types
+    }
+
+    /// Returns the value of the local variable
+    #[inline]
+    pub fn local(&self, index: usize) -> Option<WasmValue> {
+        self.local_types
+            .get(index)
+            .zip(self.locals.get(index))
+            .map(|(val_type, value)| value.get_by_type(*val_type))
+    }
+
+    /// Returns the value stack of the function, without the operands of the instruction.
+    ///
+    /// Since the values on the stack are untyped, the caller needs to know how to interpret them.
+    #[inline]
+    pub const fn stack(&self) -> &[WasmStackValue] {
+        self.stack
+    }
+}
+
 impl fmt::Debug for WasmRuntimeError {
     #[inline]
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -1123,12 +2698,14 @@ pub union WasmStackValue {
     u64: u64,
     f32: f32,
     f64: f64,
+    /// `v128` as two 64-bit halves, so that the alignment is the same as the other types
+    v128: [u64; 2],
 }
 
 impl WasmStackValue {
     #[inline]
     pub const fn zero() -> Self {
-        Self { u64: 0 }
+        Self { v128: [0; 2] }
     }
 
     #[inline]
@@ -1160,6 +2737,34 @@ impl WasmStackValue {
         Self { u64: v }
     }
 
+    /// Encodes a reference, the null reference is zero so that zero-initialized values are null.
+    #[inline]
+    pub const fn from_ref(v: Option<usize>) -> Self {
+        match v {
+            Some(v) => Self::from_u64(v as u64 + 1),
+            None => Self::from_u64(0),
+        }
+    }
+
+    #[inline]
+    pub fn from_f32(v: f32) -> Self {
+        Self {
+            u64: v.to_bits() as u64,
+        }
+    }
+
+    #[inline]
+    pub fn from_f64(v: f64) -> Self {
+        Self { f64: v }
+    }
+
+    #[inline]
+    pub const fn from_v128(v: u128) -> Self {
+        Self {
+            v128: [v as u64, (v >> 64) as u64],
+        }
+    }
+
     #[inline]
     pub fn get_bool(&self) -> bool {
         unsafe { self.i32 != 0 }
@@ -1185,6 +2790,14 @@ impl WasmStackValue {
         unsafe { self.u64 }
     }
 
+    #[inline]
+    pub fn get_ref(&self) -> Option<usize> {
+        match self.get_u64() {
+            0 => None,
+            v => Some(v as usize - 1),
+        }
+    }
+
     #[inline]
     pub fn get_f32(&self) -> f32 {
         unsafe { self.f32 }
@@ -1195,6 +2808,12 @@ impl WasmStackValue {
         unsafe { self.f64 }
     }
 
+    #[inline]
+    pub fn get_v128(&self) -> u128 {
+        let [lo, hi] = unsafe { self.v128 };
+        lo as u128 | (hi as u128) << 64
+    }
+
     #[inline]
     pub fn get_i8(&self) -> i8 {
         unsafe { self.u32 as i8 }
@@ -1255,15 +2874,37 @@ impl WasmStackValue {
         self.u64 = f(val);
     }
 
+    /// Retrieves the value held by the instance as a value of type `f32` and re-stores the value processed by the closure.
+    #[inline]
+    pub fn map_f32<F>(&mut self, f: F)
+    where
+        F: FnOnce(f32) -> f32,
+    {
+        let val = unsafe { self.f32 };
+        *self = Self::from_f32(f(val));
+    }
+
+    /// Retrieves the value held by the instance as a value of type `f64` and re-stores the value processed by the closure.
+    #[inline]
+    pub fn map_f64<F>(&mut self, f: F)
+    where
+        F: FnOnce(f64) -> f64,
+    {
+        let val = unsafe { self.f64 };
+        self.f64 = f(val);
+    }
+
     /// Converts the value held by the instance to the `WasmValue` type as a value of the specified type.
     #[inline]
     pub fn get_by_type(&self, val_type: WasmValType) -> WasmValue {
         match val_type {
             WasmValType::I32 => WasmValue::I32(self.get_i32()),
             WasmValType::I64 => WasmValue::I64(self.get_i64()),
-            // WasmValType::F32 => {}
-            // WasmValType::F64 => {}
-            _ => todo!(),
+            WasmValType::F32 => WasmValue::F32(self.get_f32()),
+            WasmValType::F64 => WasmValue::F64(self.get_f64()),
+            WasmValType::V128 => WasmValue::V128(self.get_v128()),
+            WasmValType::FuncRef => WasmValue::FuncRef(self.get_ref()),
+            WasmValType::ExternRef => WasmValue::ExternRef(self.get_ref()),
         }
     }
 }
@@ -1303,13 +2944,38 @@ impl From<i64> for WasmStackValue {
     }
 }
 
+impl From<f32> for WasmStackValue {
+    #[inline]
+    fn from(v: f32) -> Self {
+        Self::from_f32(v)
+    }
+}
+
+impl From<f64> for WasmStackValue {
+    #[inline]
+    fn from(v: f64) -> Self {
+        Self::from_f64(v)
+    }
+}
+
+impl From<u128> for WasmStackValue {
+    #[inline]
+    fn from(v: u128) -> Self {
+        Self::from_v128(v)
+    }
+}
+
 impl From<WasmValue> for WasmStackValue {
     #[inline]
     fn from(v: WasmValue) -> Self {
         match v {
             WasmValue::I32(v) => Self::from_i64(v as i64),
             WasmValue::I64(v) => Self::from_i64(v),
-            _ => todo!(),
+            WasmValue::F32(v) => Self::from_f32(v),
+            WasmValue::F64(v) => Self::from_f64(v),
+            WasmValue::V128(v) => Self::from_v128(v),
+            WasmValue::FuncRef(v) => Self::from_ref(v),
+            WasmValue::ExternRef(v) => Self::from_ref(v),
         }
     }
 }
@@ -0,0 +1,642 @@
+//! Scalar implementation of the SIMD instructions
+
+use super::WasmStackValue;
+use super::{canonicalize_f32, canonicalize_f64, max_f32, max_f64, min_f32, min_f64};
+use crate::{opcode::WasmOpcodeFD, WasmMemory, WasmRuntimeErrorType};
+use core::convert::TryInto;
+
+/// A vector that is split into lanes, lane 0 is the least significant.
+trait Lanes: Copy {
+    fn split(v: u128) -> Self;
+
+    fn join(self) -> u128;
+}
+
+macro_rules! impl_lanes {
+    ( $( $t:ty, $n:expr; )* ) => {
+        $(
+            impl Lanes for [$t; $n] {
+                #[inline]
+                fn split(v: u128) -> Self {
+                    let bytes = v.to_le_bytes();
+                    let mut lanes = [<$t>::default(); $n];
+                    for (lane, chunk) in lanes.iter_mut().zip(bytes.chunks_exact(16 / $n)) {
+                        *lane = <$t>::from_le_bytes(chunk.try_into().unwrap());
+                    }
+                    lanes
+                }
+
+                #[inline]
+                fn join(self) -> u128 {
+                    let mut bytes = [0; 16];
+                    for (chunk, lane) in bytes.chunks_exact_mut(16 / $n).zip(self.iter()) {
+                        chunk.copy_from_slice(&lane.to_le_bytes());
+                    }
+                    u128::from_le_bytes(bytes)
+                }
+            }
+        )*
+    };
+}
+
+impl_lanes! {
+    i8, 16;
+    u8, 16;
+    i16, 8;
+    u16, 8;
+    i32, 4;
+    u32, 4;
+    i64, 2;
+    u64, 2;
+    f32, 4;
+    f64, 2;
+}
+
+/// Executes the SIMD instruction that is not a memory access, the result is stored in the first operand.
+pub(super) fn exec(op: WasmOpcodeFD, lane: usize, operands: &mut [WasmStackValue]) {
+    use WasmOpcodeFD::*;
+    let v = |index: usize| operands[index].get_v128();
+    let result: WasmStackValue = match op {
+        I8x16Splat => [operands[0].get_i8(); 16].join().into(),
+        I16x8Splat => [operands[0].get_i16(); 8].join().into(),
+        I32x4Splat => [operands[0].get_i32(); 4].join().into(),
+        I64x2Splat => [operands[0].get_i64(); 2].join().into(),
+        F32x4Splat => [operands[0].get_f32(); 4].join().into(),
+        F64x2Splat => [operands[0].get_f64(); 2].join().into(),
+
+        I8x16ExtractLaneS => (<[i8; 16]>::split(v(0))[lane] as i32).into(),
+        I8x16ExtractLaneU => (<[u8; 16]>::split(v(0))[lane] as u32).into(),
+        I16x8ExtractLaneS => (<[i16; 8]>::split(v(0))[lane] as i32).into(),
+        I16x8ExtractLaneU => (<[u16; 8]>::split(v(0))[lane] as u32).into(),
+        I32x4ExtractLane => <[i32; 4]>::split(v(0))[lane].into(),
+        I64x2ExtractLane => <[i64; 2]>::split(v(0))[lane].into(),
+        F32x4ExtractLane => <[f32; 4]>::split(v(0))[lane].into(),
+        F64x2ExtractLane => <[f64; 2]>::split(v(0))[lane].into(),
+
+        I8x16ReplaceLane => replace::<u8, 16>(v(0), lane, operands[1].get_u8()).into(),
+        I16x8ReplaceLane => replace::<u16, 8>(v(0), lane, operands[1].get_u16()).into(),
+        I32x4ReplaceLane => replace::<u32, 4>(v(0), lane, operands[1].get_u32()).into(),
+        I64x2ReplaceLane => replace::<u64, 2>(v(0), lane, operands[1].get_u64()).into(),
+        F32x4ReplaceLane => replace::<f32, 4>(v(0), lane, operands[1].get_f32()).into(),
+        F64x2ReplaceLane => replace::<f64, 2>(v(0), lane, operands[1].get_f64()).into(),
+
+        I8x16Swizzle => {
+            let a = <[u8; 16]>::split(v(0));
+            <[u8; 16]>::split(v(1))
+                .map(|i| a.get(i as usize).copied().unwrap_or(0))
+                .join()
+                .into()
+        }
+
+        I8x16Eq => cmp::<i8, 16>(v(0), v(1), |a, b| a == b).into(),
+        I8x16Ne => cmp::<i8, 16>(v(0), v(1), |a, b| a != b).into(),
+        I8x16LtS => cmp::<i8, 16>(v(0), v(1), |a, b| a < b).into(),
+        I8x16LtU => cmp::<u8, 16>(v(0), v(1), |a, b| a < b).into(),
+        I8x16GtS => cmp::<i8, 16>(v(0), v(1), |a, b| a > b).into(),
+        I8x16GtU => cmp::<u8, 16>(v(0), v(1), |a, b| a > b).into(),
+        I8x16LeS => cmp::<i8, 16>(v(0), v(1), |a, b| a <= b).into(),
+        I8x16LeU => cmp::<u8, 16>(v(0), v(1), |a, b| a <= b).into(),
+        I8x16GeS => cmp::<i8, 16>(v(0), v(1), |a, b| a >= b).into(),
+        I8x16GeU => cmp::<u8, 16>(v(0), v(1), |a, b| a >= b).into(),
+        I16x8Eq => cmp::<i16, 8>(v(0), v(1), |a, b| a == b).into(),
+        I16x8Ne => cmp::<i16, 8>(v(0), v(1), |a, b| a != b).into(),
+        I16x8LtS => cmp::<i16, 8>(v(0), v(1), |a, b| a < b).into(),
+        I16x8LtU => cmp::<u16, 8>(v(0), v(1), |a, b| a < b).into(),
+        I16x8GtS => cmp::<i16, 8>(v(0), v(1), |a, b| a > b).into(),
+        I16x8GtU => cmp::<u16, 8>(v(0), v(1), |a, b| a > b).into(),
+        I16x8LeS => cmp::<i16, 8>(v(0), v(1), |a, b| a <= b).into(),
+        I16x8LeU => cmp::<u16, 8>(v(0), v(1), |a, b| a <= b).into(),
+        I16x8GeS => cmp::<i16, 8>(v(0), v(1), |a, b| a >= b).into(),
+        I16x8GeU => cmp::<u16, 8>(v(0), v(1), |a, b| a >= b).into(),
+        I32x4Eq => cmp::<i32, 4>(v(0), v(1), |a, b| a == b).into(),
+        I32x4Ne => cmp::<i32, 4>(v(0), v(1), |a, b| a != b).into(),
+        I32x4LtS => cmp::<i32, 4>(v(0), v(1), |a, b| a < b).into(),
+        I32x4LtU => cmp::<u32, 4>(v(0), v(1), |a, b| a < b).into(),
+        I32x4GtS => cmp::<i32, 4>(v(0), v(1), |a, b| a > b).into(),
+        I32x4GtU => cmp::<u32, 4>(v(0), v(1), |a, b| a > b).into(),
+        I32x4LeS => cmp::<i32, 4>(v(0), v(1), |a, b| a <= b).into(),
+        I32x4LeU => cmp::<u32, 4>(v(0), v(1), |a, b| a <= b).into(),
+        I32x4GeS => cmp::<i32, 4>(v(0), v(1), |a, b| a >= b).into(),
+        I32x4GeU => cmp::<u32, 4>(v(0), v(1), |a, b| a >= b).into(),
+        I64x2Eq => cmp::<i64, 2>(v(0), v(1), |a, b| a == b).into(),
+        I64x2Ne => cmp::<i64, 2>(v(0), v(1), |a, b| a != b).into(),
+        I64x2LtS => cmp::<i64, 2>(v(0), v(1), |a, b| a < b).into(),
+        I64x2GtS => cmp::<i64, 2>(v(0), v(1), |a, b| a > b).into(),
+        I64x2LeS => cmp::<i64, 2>(v(0), v(1), |a, b| a <= b).into(),
+        I64x2GeS => cmp::<i64, 2>(v(0), v(1), |a, b| a >= b).into(),
+        F32x4Eq => cmp::<f32, 4>(v(0), v(1), |a, b| a == b).into(),
+        F32x4Ne => cmp::<f32, 4>(v(0), v(1), |a, b| a != b).into(),
+        F32x4Lt => cmp::<f32, 4>(v(0), v(1), |a, b| a < b).into(),
+        F32x4Gt => cmp::<f32, 4>(v(0), v(1), |a, b| a > b).into(),
+        F32x4Le => cmp::<f32, 4>(v(0), v(1), |a, b| a <= b).into(),
+        F32x4Ge => cmp::<f32, 4>(v(0), v(1), |a, b| a >= b).into(),
+        F64x2Eq => cmp::<f64, 2>(v(0), v(1), |a, b| a == b).into(),
+        F64x2Ne => cmp::<f64, 2>(v(0), v(1), |a, b| a != b).into(),
+        F64x2Lt => cmp::<f64, 2>(v(0), v(1), |a, b| a < b).into(),
+        F64x2Gt => cmp::<f64, 2>(v(0), v(1), |a, b| a > b).into(),
+        F64x2Le => cmp::<f64, 2>(v(0), v(1), |a, b| a <= b).into(),
+        F64x2Ge => cmp::<f64, 2>(v(0), v(1), |a, b| a >= b).into(),
+
+        V128Not => (!v(0)).into(),
+        V128And => (v(0) & v(1)).into(),
+        V128Andnot => (v(0) & !v(1)).into(),
+        V128Or => (v(0) | v(1)).into(),
+        V128Xor => (v(0) ^ v(1)).into(),
+        V128Bitselect => {
+            let c = v(2);
+            (v(0) & c | v(1) & !c).into()
+        }
+        V128AnyTrue => (v(0) != 0).into(),
+
+        I8x16AllTrue => all_true::<u8, 16>(v(0)).into(),
+        I16x8AllTrue => all_true::<u16, 8>(v(0)).into(),
+        I32x4AllTrue => all_true::<u32, 4>(v(0)).into(),
+        I64x2AllTrue => all_true::<u64, 2>(v(0)).into(),
+        I8x16Bitmask => bitmask::<i8, 16>(v(0), |a| a < 0).into(),
+        I16x8Bitmask => bitmask::<i16, 8>(v(0), |a| a < 0).into(),
+        I32x4Bitmask => bitmask::<i32, 4>(v(0), |a| a < 0).into(),
+        I64x2Bitmask => bitmask::<i64, 2>(v(0), |a| a < 0).into(),
+
+        I8x16Shl => {
+            let s = operands[1].get_u32();
+            map::<u8, 16>(v(0), |a| a.wrapping_shl(s)).into()
+        }
+        I8x16ShrS => {
+            let s = operands[1].get_u32();
+            map::<i8, 16>(v(0), |a| a.wrapping_shr(s)).into()
+        }
+        I8x16ShrU => {
+            let s = operands[1].get_u32();
+            map::<u8, 16>(v(0), |a| a.wrapping_shr(s)).into()
+        }
+        I16x8Shl => {
+            let s = operands[1].get_u32();
+            map::<u16, 8>(v(0), |a| a.wrapping_shl(s)).into()
+        }
+        I16x8ShrS => {
+            let s = operands[1].get_u32();
+            map::<i16, 8>(v(0), |a| a.wrapping_shr(s)).into()
+        }
+        I16x8ShrU => {
+            let s = operands[1].get_u32();
+            map::<u16, 8>(v(0), |a| a.wrapping_shr(s)).into()
+        }
+        I32x4Shl => {
+            let s = operands[1].get_u32();
+            map::<u32, 4>(v(0), |a| a.wrapping_shl(s)).into()
+        }
+        I32x4ShrS => {
+            let s = operands[1].get_u32();
+            map::<i32, 4>(v(0), |a| a.wrapping_shr(s)).into()
+        }
+        I32x4ShrU => {
+            let s = operands[1].get_u32();
+            map::<u32, 4>(v(0), |a| a.wrapping_shr(s)).into()
+        }
+        I64x2Shl => {
+            let s = operands[1].get_u32();
+            map::<u64, 2>(v(0), |a| a.wrapping_shl(s)).into()
+        }
+        I64x2ShrS => {
+            let s = operands[1].get_u32();
+            map::<i64, 2>(v(0), |a| a.wrapping_shr(s)).into()
+        }
+        I64x2ShrU => {
+            let s = operands[1].get_u32();
+            map::<u64, 2>(v(0), |a| a.wrapping_shr(s)).into()
+        }
+
+        I8x16Abs => map::<i8, 16>(v(0), i8::wrapping_abs).into(),
+        I8x16Neg => map::<i8, 16>(v(0), i8::wrapping_neg).into(),
+        I8x16Popcnt => map::<u8, 16>(v(0), |a| a.count_ones() as u8).into(),
+        I8x16Add => zip::<u8, 16>(v(0), v(1), u8::wrapping_add).into(),
+        I8x16AddSatS => zip::<i8, 16>(v(0), v(1), i8::saturating_add).into(),
+        I8x16AddSatU => zip::<u8, 16>(v(0), v(1), u8::saturating_add).into(),
+        I8x16Sub => zip::<u8, 16>(v(0), v(1), u8::wrapping_sub).into(),
+        I8x16SubSatS => zip::<i8, 16>(v(0), v(1), i8::saturating_sub).into(),
+        I8x16SubSatU => zip::<u8, 16>(v(0), v(1), u8::saturating_sub).into(),
+        I8x16MinS => zip::<i8, 16>(v(0), v(1), Ord::min).into(),
+        I8x16MinU => zip::<u8, 16>(v(0), v(1), Ord::min).into(),
+        I8x16MaxS => zip::<i8, 16>(v(0), v(1), Ord::max).into(),
+        I8x16MaxU => zip::<u8, 16>(v(0), v(1), Ord::max).into(),
+        I8x16AvgrU => {
+            zip::<u8, 16>(v(0), v(1), |a, b| ((a as u16 + b as u16 + 1) >> 1) as u8).into()
+        }
+        I8x16NarrowI16x8S => narrow::<i16, i8, 8, 16>(v(0), v(1), |a| {
+            a.clamp(i8::MIN as i16, i8::MAX as i16) as i8
+        })
+        .into(),
+        I8x16NarrowI16x8U => {
+            narrow::<i16, u8, 8, 16>(v(0), v(1), |a| a.clamp(0, u8::MAX as i16) as u8).into()
+        }
+
+        I16x8Abs => map::<i16, 8>(v(0), i16::wrapping_abs).into(),
+        I16x8Neg => map::<i16, 8>(v(0), i16::wrapping_neg).into(),
+        I16x8Q15mulrSatS => zip::<i16, 8>(v(0), v(1), |a, b| {
+            ((a as i32 * b as i32 + 0x4000) >> 15).clamp(i16::MIN as i32, i16::MAX as i32) as i16
+        })
+        .into(),
+        I16x8Add => zip::<u16, 8>(v(0), v(1), u16::wrapping_add).into(),
+        I16x8AddSatS => zip::<i16, 8>(v(0), v(1), i16::saturating_add).into(),
+        I16x8AddSatU => zip::<u16, 8>(v(0), v(1), u16::saturating_add).into(),
+        I16x8Sub => zip::<u16, 8>(v(0), v(1), u16::wrapping_sub).into(),
+        I16x8SubSatS => zip::<i16, 8>(v(0), v(1), i16::saturating_sub).into(),
+        I16x8SubSatU => zip::<u16, 8>(v(0), v(1), u16::saturating_sub).into(),
+        I16x8Mul => zip::<u16, 8>(v(0), v(1), u16::wrapping_mul).into(),
+        I16x8MinS => zip::<i16, 8>(v(0), v(1), Ord::min).into(),
+        I16x8MinU => zip::<u16, 8>(v(0), v(1), Ord::min).into(),
+        I16x8MaxS => zip::<i16, 8>(v(0), v(1), Ord::max).into(),
+        I16x8MaxU => zip::<u16, 8>(v(0), v(1), Ord::max).into(),
+        I16x8AvgrU => {
+            zip::<u16, 8>(v(0), v(1), |a, b| ((a as u32 + b as u32 + 1) >> 1) as u16).into()
+        }
+        I16x8NarrowI32x4S => narrow::<i32, i16, 4, 8>(v(0), v(1), |a| {
+            a.clamp(i16::MIN as i32, i16::MAX as i32) as i16
+        })
+        .into(),
+        I16x8NarrowI32x4U => {
+            narrow::<i32, u16, 4, 8>(v(0), v(1), |a| a.clamp(0, u16::MAX as i32) as u16).into()
+        }
+        I16x8ExtendLowI8x16S => extend::<i8, i16, 16, 8>(v(0), false, |a| a as i16).into(),
+        I16x8ExtendHighI8x16S => extend::<i8, i16, 16, 8>(v(0), true, |a| a as i16).into(),
+        I16x8ExtendLowI8x16U => extend::<u8, u16, 16, 8>(v(0), false, |a| a as u16).into(),
+        I16x8ExtendHighI8x16U => extend::<u8, u16, 16, 8>(v(0), true, |a| a as u16).into(),
+        I16x8ExtaddPairwiseI8x16S => {
+            pairwise::<i8, i16, 16, 8>(v(0), |a, b| a as i16 + b as i16).into()
+        }
+        I16x8ExtaddPairwiseI8x16U => {
+            pairwise::<u8, u16, 16, 8>(v(0), |a, b| a as u16 + b as u16).into()
+        }
+        I16x8ExtmulLowI8x16S => {
+            extmul::<i8, i16, 16, 8>(v(0), v(1), false, |a, b| a as i16 * b as i16).into()
+        }
+        I16x8ExtmulHighI8x16S => {
+            extmul::<i8, i16, 16, 8>(v(0), v(1), true, |a, b| a as i16 * b as i16).into()
+        }
+        I16x8ExtmulLowI8x16U => {
+            extmul::<u8, u16, 16, 8>(v(0), v(1), false, |a, b| a as u16 * b as u16).into()
+        }
+        I16x8ExtmulHighI8x16U => {
+            extmul::<u8, u16, 16, 8>(v(0), v(1), true, |a, b| a as u16 * b as u16).into()
+        }
+
+        I32x4Abs => map::<i32, 4>(v(0), i32::wrapping_abs).into(),
+        I32x4Neg => map::<i32, 4>(v(0), i32::wrapping_neg).into(),
+        I32x4Add => zip::<u32, 4>(v(0), v(1), u32::wrapping_add).into(),
+        I32x4Sub => zip::<u32, 4>(v(0), v(1), u32::wrapping_sub).into(),
+        I32x4Mul => zip::<u32, 4>(v(0), v(1), u32::wrapping_mul).into(),
+        I32x4MinS => zip::<i32, 4>(v(0), v(1), Ord::min).into(),
+        I32x4MinU => zip::<u32, 4>(v(0), v(1), Ord::min).into(),
+        I32x4MaxS => zip::<i32, 4>(v(0), v(1), Ord::max).into(),
+        I32x4MaxU => zip::<u32, 4>(v(0), v(1), Ord::max).into(),
+        I32x4DotI16x8S => {
+            let b = <[i16; 8]>::split(v(1));
+            let mut products = <[i16; 8]>::split(v(0)).map(|a| a as i32);
+            for (a, b) in products.iter_mut().zip(b.iter()) {
+                *a *= *b as i32;
+            }
+            let mut result = [0i32; 4];
+            for (result, pair) in result.iter_mut().zip(products.chunks_exact(2)) {
+                *result = pair[0].wrapping_add(pair[1]);
+            }
+            result.join().into()
+        }
+        I32x4ExtendLowI16x8S => extend::<i16, i32, 8, 4>(v(0), false, |a| a as i32).into(),
+        I32x4ExtendHighI16x8S => extend::<i16, i32, 8, 4>(v(0), true, |a| a as i32).into(),
+        I32x4ExtendLowI16x8U => extend::<u16, u32, 8, 4>(v(0), false, |a| a as u32).into(),
+        I32x4ExtendHighI16x8U => extend::<u16, u32, 8, 4>(v(0), true, |a| a as u32).into(),
+        I32x4ExtaddPairwiseI16x8S => {
+            pairwise::<i16, i32, 8, 4>(v(0), |a, b| a as i32 + b as i32).into()
+        }
+        I32x4ExtaddPairwiseI16x8U => {
+            pairwise::<u16, u32, 8, 4>(v(0), |a, b| a as u32 + b as u32).into()
+        }
+        I32x4ExtmulLowI16x8S => {
+            extmul::<i16, i32, 8, 4>(v(0), v(1), false, |a, b| a as i32 * b as i32).into()
+        }
+        I32x4ExtmulHighI16x8S => {
+            extmul::<i16, i32, 8, 4>(v(0), v(1), true, |a, b| a as i32 * b as i32).into()
+        }
+        I32x4ExtmulLowI16x8U => {
+            extmul::<u16, u32, 8, 4>(v(0), v(1), false, |a, b| a as u32 * b as u32).into()
+        }
+        I32x4ExtmulHighI16x8U => {
+            extmul::<u16, u32, 8, 4>(v(0), v(1), true, |a, b| a as u32 * b as u32).into()
+        }
+
+        I64x2Abs => map::<i64, 2>(v(0), i64::wrapping_abs).into(),
+        I64x2Neg => map::<i64, 2>(v(0), i64::wrapping_neg).into(),
+        I64x2Add => zip::<u64, 2>(v(0), v(1), u64::wrapping_add).into(),
+        I64x2Sub => zip::<u64, 2>(v(0), v(1), u64::wrapping_sub).into(),
+        I64x2Mul => zip::<u64, 2>(v(0), v(1), u64::wrapping_mul).into(),
+        I64x2ExtendLowI32x4S => extend::<i32, i64, 4, 2>(v(0), false, |a| a as i64).into(),
+        I64x2ExtendHighI32x4S => extend::<i32, i64, 4, 2>(v(0), true, |a| a as i64).into(),
+        I64x2ExtendLowI32x4U => extend::<u32, u64, 4, 2>(v(0), false, |a| a as u64).into(),
+        I64x2ExtendHighI32x4U => extend::<u32, u64, 4, 2>(v(0), true, |a| a as u64).into(),
+        I64x2ExtmulLowI32x4S => {
+            extmul::<i32, i64, 4, 2>(v(0), v(1), false, |a, b| a as i64 * b as i64).into()
+        }
+        I64x2ExtmulHighI32x4S => {
+            extmul::<i32, i64, 4, 2>(v(0), v(1), true, |a, b| a as i64 * b as i64).into()
+        }
+        I64x2ExtmulLowI32x4U => {
+            extmul::<u32, u64, 4, 2>(v(0), v(1), false, |a, b| a as u64 * b as u64).into()
+        }
+        I64x2ExtmulHighI32x4U => {
+            extmul::<u32, u64, 4, 2>(v(0), v(1), true, |a, b| a as u64 * b as u64).into()
+        }
+
+        F32x4Ceil => map::<f32, 4>(v(0), |a| canonicalize_f32(libm::ceilf(a))).into(),
+        F32x4Floor => map::<f32, 4>(v(0), |a| canonicalize_f32(libm::floorf(a))).into(),
+        F32x4Trunc => map::<f32, 4>(v(0), |a| canonicalize_f32(libm::truncf(a))).into(),
+        F32x4Nearest => map::<f32, 4>(v(0), |a| canonicalize_f32(libm::rintf(a))).into(),
+        F32x4Abs => map::<u32, 4>(v(0), |a| a & 0x7FFF_FFFF).into(),
+        F32x4Neg => map::<u32, 4>(v(0), |a| a ^ 0x8000_0000).into(),
+        F32x4Sqrt => map::<f32, 4>(v(0), |a| canonicalize_f32(libm::sqrtf(a))).into(),
+        F32x4Add => zip::<f32, 4>(v(0), v(1), |a, b| a + b).into(),
+        F32x4Sub => zip::<f32, 4>(v(0), v(1), |a, b| a - b).into(),
+        F32x4Mul => zip::<f32, 4>(v(0), v(1), |a, b| a * b).into(),
+        F32x4Div => zip::<f32, 4>(v(0), v(1), |a, b| a / b).into(),
+        F32x4Min => zip::<f32, 4>(v(0), v(1), min_f32).into(),
+        F32x4Max => zip::<f32, 4>(v(0), v(1), max_f32).into(),
+        F32x4Pmin => zip::<f32, 4>(v(0), v(1), |a, b| if b < a { b } else { a }).into(),
+        F32x4Pmax => zip::<f32, 4>(v(0), v(1), |a, b| if a < b { b } else { a }).into(),
+
+        F64x2Ceil => map::<f64, 2>(v(0), |a| canonicalize_f64(libm::ceil(a))).into(),
+        F64x2Floor => map::<f64, 2>(v(0), |a| canonicalize_f64(libm::floor(a))).into(),
+        F64x2Trunc => map::<f64, 2>(v(0), |a| canonicalize_f64(libm::trunc(a))).into(),
+        F64x2Nearest => map::<f64, 2>(v(0), |a| canonicalize_f64(libm::rint(a))).into(),
+        F64x2Abs => map::<u64, 2>(v(0), |a| a & 0x7FFF_FFFF_FFFF_FFFF).into(),
+        F64x2Neg => map::<u64, 2>(v(0), |a| a ^ 0x8000_0000_0000_0000).into(),
+        F64x2Sqrt => map::<f64, 2>(v(0), |a| canonicalize_f64(libm::sqrt(a))).into(),
+        F64x2Add => zip::<f64, 2>(v(0), v(1), |a, b| a + b).into(),
+        F64x2Sub => zip::<f64, 2>(v(0), v(1), |a, b| a - b).into(),
+        F64x2Mul => zip::<f64, 2>(v(0), v(1), |a, b| a * b).into(),
+        F64x2Div => zip::<f64, 2>(v(0), v(1), |a, b| a / b).into(),
+        F64x2Min => zip::<f64, 2>(v(0), v(1), min_f64).into(),
+        F64x2Max => zip::<f64, 2>(v(0), v(1), max_f64).into(),
+        F64x2Pmin => zip::<f64, 2>(v(0), v(1), |a, b| if b < a { b } else { a }).into(),
+        F64x2Pmax => zip::<f64, 2>(v(0), v(1), |a, b| if a < b { b } else { a }).into(),
+
+        // The float-to-int casts of Rust saturate and convert NaN to zero
+        I32x4TruncSatF32x4S => convert::<f32, i32, 4>(v(0), |a| a as i32).into(),
+        I32x4TruncSatF32x4U => convert::<f32, u32, 4>(v(0), |a| a as u32).into(),
+        F32x4ConvertI32x4S => convert::<i32, f32, 4>(v(0), |a| a as f32).into(),
+        F32x4ConvertI32x4U => convert::<u32, f32, 4>(v(0), |a| a as f32).into(),
+        I32x4TruncSatF64x2SZero => narrow::<f64, i32, 2, 4>(v(0), 0, |a| a as i32).into(),
+        I32x4TruncSatF64x2UZero => narrow::<f64, u32, 2, 4>(v(0), 0, |a| a as u32).into(),
+        F64x2ConvertLowI32x4S => extend::<i32, f64, 4, 2>(v(0), false, |a| a as f64).into(),
+        F64x2ConvertLowI32x4U => extend::<u32, f64, 4, 2>(v(0), false, |a| a as f64).into(),
+        F32x4DemoteF64x2Zero => narrow::<f64, f32, 2, 4>(v(0), 0, |a| a as f32).into(),
+        F64x2PromoteLowF32x4 => extend::<f32, f64, 4, 2>(v(0), false, |a| a as f64).into(),
+
+        // Memory accesses, constants and shuffles have their own intermediate codes
+        _ => unreachable!(),
+    };
+    operands[0] = result;
+}
+
+/// Shuffles the lanes of the two vectors, each byte of `lanes` is an index of the 32 lanes.
+pub(super) fn shuffle(a: u128, b: u128, lanes: u128) -> u128 {
+    let a = <[u8; 16]>::split(a);
+    let b = <[u8; 16]>::split(b);
+    <[u8; 16]>::split(lanes)
+        .map(|i| {
+            let i = i as usize;
+            if i < 16 {
+                a[i]
+            } else {
+                b[i - 16]
+            }
+        })
+        .join()
+}
+
+/// Loads a vector from the memory, extending, splatting or zero-filling it.
+pub(super) fn load(
+    op: WasmOpcodeFD,
+    memory: &WasmMemory,
+    offset: usize,
+) -> Result<u128, WasmRuntimeErrorType> {
+    use WasmOpcodeFD::*;
+    Ok(match op {
+        V128Load => memory.read_u128(offset)?,
+        V128Load8x8S => {
+            extend::<i8, i16, 16, 8>(memory.read_u64(offset)? as u128, false, |a| a as i16)
+        }
+        V128Load8x8U => {
+            extend::<u8, u16, 16, 8>(memory.read_u64(offset)? as u128, false, |a| a as u16)
+        }
+        V128Load16x4S => {
+            extend::<i16, i32, 8, 4>(memory.read_u64(offset)? as u128, false, |a| a as i32)
+        }
+        V128Load16x4U => {
+            extend::<u16, u32, 8, 4>(memory.read_u64(offset)? as u128, false, |a| a as u32)
+        }
+        V128Load32x2S => {
+            extend::<i32, i64, 4, 2>(memory.read_u64(offset)? as u128, false, |a| a as i64)
+        }
+        V128Load32x2U => {
+            extend::<u32, u64, 4, 2>(memory.read_u64(offset)? as u128, false, |a| a as u64)
+        }
+        V128Load8Splat => [memory.read_u8(offset)?; 16].join(),
+        V128Load16Splat => [memory.read_u16(offset)?; 8].join(),
+        V128Load32Splat => [memory.read_u32(offset)?; 4].join(),
+        V128Load64Splat => [memory.read_u64(offset)?; 2].join(),
+        V128Load32Zero => memory.read_u32(offset)? as u128,
+        V128Load64Zero => memory.read_u64(offset)? as u128,
+        _ => return Err(WasmRuntimeErrorType::NotSupprted),
+    })
+}
+
+/// Loads a lane of the vector from the memory.
+pub(super) fn load_lane(
+    op: WasmOpcodeFD,
+    memory: &WasmMemory,
+    offset: usize,
+    v: u128,
+    lane: usize,
+) -> Result<u128, WasmRuntimeErrorType> {
+    use WasmOpcodeFD::*;
+    Ok(match op {
+        V128Load8Lane => replace::<u8, 16>(v, lane, memory.read_u8(offset)?),
+        V128Load16Lane => replace::<u16, 8>(v, lane, memory.read_u16(offset)?),
+        V128Load32Lane => replace::<u32, 4>(v, lane, memory.read_u32(offset)?),
+        V128Load64Lane => replace::<u64, 2>(v, lane, memory.read_u64(offset)?),
+        _ => return Err(WasmRuntimeErrorType::NotSupprted),
+    })
+}
+
+/// Stores a lane of the vector to the memory.
+pub(super) fn store_lane(
+    op: WasmOpcodeFD,
+    memory: &WasmMemory,
+    offset: usize,
+    v: u128,
+    lane: usize,
+) -> Result<(), WasmRuntimeErrorType> {
+    use WasmOpcodeFD::*;
+    match op {
+        V128Store8Lane => memory.write_u8(offset, <[u8; 16]>::split(v)[lane]),
+        V128Store16Lane => memory.write_u16(offset, <[u16; 8]>::split(v)[lane]),
+        V128Store32Lane => memory.write_u32(offset, <[u32; 4]>::split(v)[lane]),
+        V128Store64Lane => memory.write_u64(offset, <[u64; 2]>::split(v)[lane]),
+        _ => Err(WasmRuntimeErrorType::NotSupprted),
+    }
+}
+
+#[inline]
+fn replace<T: Copy, const N: usize>(v: u128, lane: usize, value: T) -> u128
+where
+    [T; N]: Lanes,
+{
+    let mut lanes = <[T; N]>::split(v);
+    lanes[lane] = value;
+    lanes.join()
+}
+
+#[inline]
+fn map<T: Copy, const N: usize>(a: u128, f: impl Fn(T) -> T) -> u128
+where
+    [T; N]: Lanes,
+{
+    <[T; N]>::split(a).map(f).join()
+}
+
+#[inline]
+fn zip<T: Copy, const N: usize>(a: u128, b: u128, f: impl Fn(T, T) -> T) -> u128
+where
+    [T; N]: Lanes,
+{
+    let mut lanes = <[T; N]>::split(a);
+    for (a, b) in lanes.iter_mut().zip(<[T; N]>::split(b).iter()) {
+        *a = f(*a, *b);
+    }
+    lanes.join()
+}
+
+/// Compares each lane, the lanes of the result are all ones if true or zero if false.
+#[inline]
+fn cmp<T: Copy, const N: usize>(a: u128, b: u128, f: impl Fn(T, T) -> bool) -> u128
+where
+    [T; N]: Lanes,
+{
+    let width = 128 / N;
+    let mask = u128::MAX >> (128 - width);
+    <[T; N]>::split(a)
+        .iter()
+        .zip(<[T; N]>::split(b).iter())
+        .enumerate()
+        .filter(|(_, (a, b))| f(**a, **b))
+        .fold(0, |acc, (i, _)| acc | mask << (i * width))
+}
+
+#[inline]
+fn all_true<T: Copy + PartialEq + Default, const N: usize>(a: u128) -> bool
+where
+    [T; N]: Lanes,
+{
+    <[T; N]>::split(a).iter().all(|a| *a != T::default())
+}
+
+#[inline]
+fn bitmask<T: Copy, const N: usize>(a: u128, f: impl Fn(T) -> bool) -> u32
+where
+    [T; N]: Lanes,
+{
+    <[T; N]>::split(a)
+        .iter()
+        .enumerate()
+        .filter(|(_, a)| f(**a))
+        .fold(0, |acc, (i, _)| acc | 1 << i)
+}
+
+/// Converts each lane to the type of the same width.
+#[inline]
+fn convert<T: Copy, U: Copy, const N: usize>(a: u128, f: impl Fn(T) -> U) -> u128
+where
+    [T; N]: Lanes,
+    [U; N]: Lanes,
+{
+    <[T; N]>::split(a).map(f).join()
+}
+
+/// Converts the lower or upper half of the lanes to the type of the double width.
+#[inline]
+fn extend<T: Copy, U: Copy, const N: usize, const M: usize>(
+    a: u128,
+    high: bool,
+    f: impl Fn(T) -> U,
+) -> u128
+where
+    [T; N]: Lanes,
+    [U; M]: Lanes,
+{
+    let a = <[T; N]>::split(a);
+    let base = if high { M } else { 0 };
+    let mut lanes = <[U; M]>::split(0);
+    for (lane, a) in lanes.iter_mut().zip(a[base..].iter()) {
+        *lane = f(*a);
+    }
+    lanes.join()
+}
+
+/// Multiplies the lower or upper half of the lanes to the type of the double width.
+#[inline]
+fn extmul<T: Copy, U: Copy, const N: usize, const M: usize>(
+    a: u128,
+    b: u128,
+    high: bool,
+    f: impl Fn(T, T) -> U,
+) -> u128
+where
+    [T; N]: Lanes,
+    [U; M]: Lanes,
+{
+    let a = <[T; N]>::split(a);
+    let b = <[T; N]>::split(b);
+    let base = if high { M } else { 0 };
+    let mut lanes = <[U; M]>::split(0);
+    for (lane, (a, b)) in lanes.iter_mut().zip(a[base..].iter().zip(b[base..].iter())) {
+        *lane = f(*a, *b);
+    }
+    lanes.join()
+}
+
+/// Adds the adjacent pairs of the lanes to the type of the double width.
+#[inline]
+fn pairwise<T: Copy, U: Copy, const N: usize, const M: usize>(
+    a: u128,
+    f: impl Fn(T, T) -> U,
+) -> u128
+where
+    [T; N]: Lanes,
+    [U; M]: Lanes,
+{
+    let a = <[T; N]>::split(a);
+    let mut lanes = <[U; M]>::split(0);
+    for (lane, pair) in lanes.iter_mut().zip(a.chunks_exact(2)) {
+        *lane = f(pair[0], pair[1]);
+    }
+    lanes.join()
+}
+
+/// Converts the lanes of the two vectors to the type of the half width, `a` to the lower half and `b` to the upper half.
+#[inline]
+fn narrow<T: Copy, U: Copy, const N: usize, const M: usize>(
+    a: u128,
+    b: u128,
+    f: impl Fn(T) -> U,
+) -> u128
+where
+    [T; N]: Lanes,
+    [U; M]: Lanes,
+{
+    let a = <[T; N]>::split(a);
+    let b = <[T; N]>::split(b);
+    let mut lanes = <[U; M]>::split(0);
+    for (lane, v) in lanes.iter_mut().zip(a.iter().chain(b.iter())) {
+        *lane = f(*v);
+    }
+    lanes.join()
+}
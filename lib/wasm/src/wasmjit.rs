@@ -0,0 +1,889 @@
+//! Baseline JIT compiler for x86-64
+//!
+//! Translates the intermediate code of a function into x86-64 machine code.
+//! The generated code keeps the same value stack layout as the interpreter,
+//! so that both can call each other freely.
+//! Functions that use instructions not supported by this compiler remain in the interpreter.
+
+use super::{intcode::*, wasm::*, wasmintr::*};
+use alloc::vec::Vec;
+use core::{
+    ffi::c_void,
+    mem::{size_of, transmute},
+    ptr::NonNull,
+};
+
+/// Executable memory for the JIT compiled code
+///
+/// The code may be released by any thread that runs the module.
+pub trait WasmJitAllocator: Sync {
+    /// Allocates executable memory and copies the code into it.
+    fn alloc_code(&self, code: &[u8]) -> Option<NonNull<u8>>;
+
+    /// Releases the memory allocated by `alloc_code`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` and `len` must be the ones passed to or returned by `alloc_code`.
+    unsafe fn free_code(&self, ptr: NonNull<u8>, len: usize);
+}
+
+/// Native code of a function
+pub struct WasmJitCode {
+    entry: NonNull<u8>,
+    len: usize,
+    allocator: &'static dyn WasmJitAllocator,
+}
+
+impl WasmJitCode {
+    #[inline]
+    pub(crate) fn new(code: &[u8], allocator: &'static dyn WasmJitAllocator) -> Option<Self> {
+        allocator.alloc_code(code).map(|entry| Self {
+            entry,
+            len: code.len(),
+            allocator,
+        })
+    }
+
+    /// Returns the size of the native code
+    #[inline]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    #[inline]
+    pub(crate) fn entry(&self) -> WasmJitEntry {
+        unsafe { transmute(self.entry.as_ptr()) }
+    }
+}
+
+// The native code is never modified after it is allocated
+unsafe impl Send for WasmJitCode {}
+
+unsafe impl Sync for WasmJitCode {}
+
+impl Drop for WasmJitCode {
+    fn drop(&mut self) {
+        unsafe {
+            self.allocator.free_code(self.entry, self.len);
+        }
+    }
+}
+
+/// Entry point of the native code `(context, locals, value_stack) -> status`
+pub(crate) type WasmJitEntry =
+    unsafe extern "sysv64" fn(*mut WasmJitContext, *mut WasmStackValue, *mut WasmStackValue) -> u32;
+
+/// Function calls from the native code `(context, code_index, value_stack) -> status`
+pub(crate) type WasmJitCallback =
+    unsafe extern "sysv64" fn(*mut WasmJitContext, usize, *mut WasmStackValue) -> u32;
+
+/// Shared data between the native code and the runtime
+#[repr(C)]
+pub(crate) struct WasmJitContext {
+    pub memory_base: *mut u8,
+    pub memory_size: usize,
+    pub trap_position: usize,
+    pub result_level: usize,
+    pub call: WasmJitCallback,
+    pub env: *mut c_void,
+}
+
+impl WasmJitContext {
+    const MEMORY_BASE: i32 = 0;
+    const MEMORY_SIZE: i32 = 8;
+    const TRAP_POSITION: i32 = 16;
+    const RESULT_LEVEL: i32 = 24;
+    const CALL: i32 = 32;
+}
+
+/// The function returned normally
+pub(crate) const STATUS_SUCCESS: u32 = 0;
+/// The called function failed, the error is held by the callback
+pub(crate) const STATUS_CALL_ERROR: u32 = 1;
+/// Traps are reported as `STATUS_TRAP_BASE + index of TRAP_KINDS`
+const STATUS_TRAP_BASE: u32 = 2;
+
+const TRAP_KINDS: [WasmRuntimeErrorType; 3] = [
+    WasmRuntimeErrorType::Unreachable,
+    WasmRuntimeErrorType::OutOfBounds,
+    WasmRuntimeErrorType::DivideByZero,
+];
+
+/// Returns the kind of trap that corresponds to the status
+#[inline]
+pub(crate) fn trap_kind(status: u32) -> WasmRuntimeErrorType {
+    status
+        .checked_sub(STATUS_TRAP_BASE)
+        .and_then(|v| TRAP_KINDS.get(v as usize))
+        .copied()
+        .unwrap_or(WasmRuntimeErrorType::InvalidParameter)
+}
+
+#[inline]
+fn trap_status(kind: WasmRuntimeErrorType) -> u32 {
+    TRAP_KINDS
+        .iter()
+        .position(|v| *v == kind)
+        .map(|v| v as u32 + STATUS_TRAP_BASE)
+        .unwrap()
+}
+
+const RAX: u8 = 0;
+const RCX: u8 = 1;
+const RDX: u8 = 2;
+const RBX: u8 = 3;
+const RSI: u8 = 6;
+const RDI: u8 = 7;
+/// Base of the value stack
+const R12: u8 = 12;
+/// Base of the linear memory
+const R13: u8 = 13;
+/// Size of the linear memory
+const R14: u8 = 14;
+/// Context
+const R15: u8 = 15;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(dead_code)]
+enum Cond {
+    O = 0x0,
+    B = 0x2,
+    AE = 0x3,
+    E = 0x4,
+    NE = 0x5,
+    BE = 0x6,
+    A = 0x7,
+    L = 0xC,
+    GE = 0xD,
+    LE = 0xE,
+    G = 0xF,
+}
+
+/// Baseline JIT compiler for x86-64
+pub struct WasmJitCompiler<'a> {
+    code_block: &'a WasmCodeBlock,
+    buf: Vec<u8>,
+    /// Offsets of each intermediate code
+    labels: Vec<usize>,
+    /// (position of rel32, index of intermediate code)
+    branches: Vec<(usize, usize)>,
+    /// (position of rel32, index of intermediate code, kind)
+    traps: Vec<(usize, usize, WasmRuntimeErrorType)>,
+    /// position of rel32 to the epilogue
+    exits: Vec<usize>,
+}
+
+impl<'a> WasmJitCompiler<'a> {
+    /// Compiles the code block into x86-64 machine code.
+    ///
+    /// Returns `None` if the code block contains instructions that are not supported.
+    pub fn compile(code_block: &'a WasmCodeBlock) -> Option<Vec<u8>> {
+        let codes = code_block.intermediate_codes();
+        if !codes.iter().all(|v| Self::is_supported(v.mnemonic())) {
+            return None;
+        }
+        // Exceptions thrown by the callees are caught by the interpreter
+        if !code_block.exception_handlers().is_empty() {
+            return None;
+        }
+        // The slots are moved in 64 bits, which cannot carry `v128`
+        if code_block.uses_v128() {
+            return None;
+        }
+
+        let mut compiler = Self {
+            code_block,
+            buf: Vec::with_capacity(codes.len() * 16),
+            labels: Vec::with_capacity(codes.len() + 1),
+            branches: Vec::new(),
+            traps: Vec::new(),
+            exits: Vec::new(),
+        };
+        compiler.prologue();
+        for (index, code) in codes.iter().enumerate() {
+            compiler.labels.push(compiler.buf.len());
+            compiler.emit(index, code);
+        }
+
+        // The interpreter returns no results when it runs past the end of the codes
+        compiler.labels.push(compiler.buf.len());
+        compiler.store_imm32(true, R15, WasmJitContext::RESULT_LEVEL, 0);
+        compiler.op_reg(false, &[0x31], RAX, RAX);
+        compiler.jmp_exit();
+
+        compiler.trap_stubs();
+        compiler.epilogue();
+
+        for (position, target) in compiler.branches.clone() {
+            let target = compiler.labels[target];
+            compiler.patch32(position, target);
+        }
+
+        Some(compiler.buf)
+    }
+
+    /// Returns whether the compiler supports the mnemonic
+    pub fn is_supported(mnemonic: WasmIntMnemonic) -> bool {
+        use WasmIntMnemonic::*;
+        match mnemonic {
+            Unreachable | Br | BrIf | BrUnwind | BrIfUnwind | If | FusedI32BrZ | FusedI64BrZ
+            | BrTable | Return | Call | Select | LocalGet | LocalSet | LocalTee => true,
+
+            I32Load | I32Load8S | I32Load8U | I32Load16S | I32Load16U | I32Store | I32Store8
+            | I32Store16 | I64Load | I64Load8S | I64Load8U | I64Load16S | I64Load16U
+            | I64Load32S | I64Load32U | I64Store | I64Store8 | I64Store16 | I64Store32
+            | F32Load | F64Load | F32Store | F64Store | MemorySize => true,
+
+            I32Const | I64Const | F32Const | F64Const => true,
+
+            I32Eqz | I32Eq | I32Ne | I32LtS | I32LtU | I32GtS | I32GtU | I32LeS | I32LeU
+            | I32GeS | I32GeU | I32Clz | I32Ctz | I32Add | I32Sub | I32Mul | I32DivS | I32DivU
+            | I32RemS | I32RemU | I32And | I32Or | I32Xor | I32Shl | I32ShrS | I32ShrU
+            | I32Rotl | I32Rotr => true,
+
+            I64Eqz | I64Eq | I64Ne | I64LtS | I64LtU | I64GtS | I64GtU | I64LeS | I64LeU
+            | I64GeS | I64GeU | I64Clz | I64Ctz | I64Add | I64Sub | I64Mul | I64DivS | I64DivU
+            | I64RemS | I64RemU | I64And | I64Or | I64Xor | I64Shl | I64ShrS | I64ShrU
+            | I64Rotl | I64Rotr => true,
+
+            I64Extend8S | I64Extend16S | I64Extend32S | I64ExtendI32S | I64ExtendI32U
+            | I32WrapI64 | I32Extend8S | I32Extend16S => true,
+
+            I32ReinterpretF32 | I64ReinterpretF64 | F32ReinterpretI32 | F64ReinterpretI64 => true,
+
+            FusedI32AddI | FusedI32SubI | FusedI64AddI | FusedI64SubI | FusedI32AndI
+            | FusedI32OrI | FusedI32XorI | FusedI32ShlI | FusedI32ShrSI | FusedI32ShrUI => true,
+
+            _ => false,
+        }
+    }
+
+    #[inline]
+    const fn slot(level: usize) -> i32 {
+        (level * size_of::<WasmStackValue>()) as i32
+    }
+
+    fn emit(&mut self, index: usize, code: &WasmImc) {
+        use WasmIntMnemonic::*;
+        let level = code.stack_level();
+        let a = Self::slot(level);
+        let b = Self::slot(level + 1);
+        let param1 = code.param1();
+
+        match code.mnemonic() {
+            Unreachable => self.jmp_trap(index, WasmRuntimeErrorType::Unreachable),
+
+            Br => self.jmp_label(param1 as usize),
+            BrIf => {
+                self.load(false, RAX, R12, a);
+                self.op_reg(false, &[0x85], RAX, RAX);
+                self.jcc_label(Cond::NE, param1 as usize);
+            }
+            If | FusedI32BrZ => {
+                self.load(false, RAX, R12, a);
+                self.op_reg(false, &[0x85], RAX, RAX);
+                self.jcc_label(Cond::E, param1 as usize);
+            }
+            FusedI64BrZ => {
+                self.load(true, RAX, R12, a);
+                self.op_reg(true, &[0x85], RAX, RAX);
+                self.jcc_label(Cond::E, param1 as usize);
+            }
+            BrUnwind => {
+                let (target, dest, arity) = self.branch_entry(param1 as usize);
+                self.copy_slots(level, dest, arity);
+                self.jmp_label(target);
+            }
+            BrIfUnwind => {
+                let (target, dest, arity) = self.branch_entry(param1 as usize);
+                self.load(false, RAX, R12, a);
+                self.op_reg(false, &[0x85], RAX, RAX);
+                let skip = self.jcc32_forward(Cond::E);
+                self.copy_slots(level - arity, dest, arity);
+                self.jmp_label(target);
+                self.patch32_here(skip);
+            }
+            BrTable => {
+                let table_position = param1 as usize;
+                let table_len = self.code_block.ext_params()[table_position];
+                self.load(false, RAX, R12, a);
+                for i in 0..table_len {
+                    let (target, dest, arity) = self.branch_entry(table_position + i * 3 + 1);
+                    let skip = if i + 1 < table_len {
+                        // cmp eax, imm32
+                        self.buf.push(0x3D);
+                        self.imm32(i as u32);
+                        Some(self.jcc32_forward(Cond::NE))
+                    } else {
+                        None
+                    };
+                    self.copy_slots(level - arity, dest, arity);
+                    self.jmp_label(target);
+                    if let Some(skip) = skip {
+                        self.patch32_here(skip);
+                    }
+                }
+            }
+
+            Return => {
+                self.store_imm32(true, R15, WasmJitContext::RESULT_LEVEL, level as u32);
+                self.op_reg(false, &[0x31], RAX, RAX);
+                self.jmp_exit();
+            }
+            Call => {
+                self.op_reg(true, &[0x89], R15, RDI);
+                self.mov_imm32(RSI, index as u32);
+                self.op_reg(true, &[0x89], R12, RDX);
+                self.op_mem(false, &[0xFF], 2, R15, WasmJitContext::CALL);
+                self.op_reg(false, &[0x85], RAX, RAX);
+                self.jcc_exit(Cond::NE);
+                // The linear memory may have been grown by the callee
+                self.load(true, R13, R15, WasmJitContext::MEMORY_BASE);
+                self.load(true, R14, R15, WasmJitContext::MEMORY_SIZE);
+            }
+            Select => {
+                self.load(false, RAX, R12, Self::slot(level + 2));
+                self.op_reg(false, &[0x85], RAX, RAX);
+                let skip = self.jcc8_forward(Cond::NE);
+                self.load(true, RAX, R12, b);
+                self.store(true, RAX, R12, a);
+                self.patch8_here(skip);
+            }
+
+            LocalGet => {
+                self.load(true, RAX, RBX, Self::slot(param1 as usize));
+                self.store(true, RAX, R12, a);
+            }
+            LocalSet | LocalTee => {
+                self.load(true, RAX, R12, a);
+                self.store(true, RAX, RBX, Self::slot(param1 as usize));
+            }
+
+            I32Load | F32Load => {
+                self.memory_address(index, level, param1, 4);
+                self.load(false, RAX, RAX, 0);
+                self.store(false, RAX, R12, a);
+            }
+            I32Load8S => {
+                self.memory_address(index, level, param1, 1);
+                self.op_mem(false, &[0x0F, 0xBE], RAX, RAX, 0);
+                self.store(false, RAX, R12, a);
+            }
+            I32Load8U => {
+                self.memory_address(index, level, param1, 1);
+                self.op_mem(false, &[0x0F, 0xB6], RAX, RAX, 0);
+                self.store(false, RAX, R12, a);
+            }
+            I32Load16S => {
+                self.memory_address(index, level, param1, 2);
+                self.op_mem(false, &[0x0F, 0xBF], RAX, RAX, 0);
+                self.store(false, RAX, R12, a);
+            }
+            I32Load16U => {
+                self.memory_address(index, level, param1, 2);
+                self.op_mem(false, &[0x0F, 0xB7], RAX, RAX, 0);
+                self.store(false, RAX, R12, a);
+            }
+            I64Load | F64Load => {
+                self.memory_address(index, level, param1, 8);
+                self.load(true, RAX, RAX, 0);
+                self.store(true, RAX, R12, a);
+            }
+            I64Load8S => {
+                self.memory_address(index, level, param1, 1);
+                self.op_mem(true, &[0x0F, 0xBE], RAX, RAX, 0);
+                self.store(true, RAX, R12, a);
+            }
+            I64Load8U => {
+                self.memory_address(index, level, param1, 1);
+                self.op_mem(false, &[0x0F, 0xB6], RAX, RAX, 0);
+                self.store(true, RAX, R12, a);
+            }
+            I64Load16S => {
+                self.memory_address(index, level, param1, 2);
+                self.op_mem(true, &[0x0F, 0xBF], RAX, RAX, 0);
+                self.store(true, RAX, R12, a);
+            }
+            I64Load16U => {
+                self.memory_address(index, level, param1, 2);
+                self.op_mem(false, &[0x0F, 0xB7], RAX, RAX, 0);
+                self.store(true, RAX, R12, a);
+            }
+            I64Load32S => {
+                self.memory_address(index, level, param1, 4);
+                self.op_mem(true, &[0x63], RAX, RAX, 0);
+                self.store(true, RAX, R12, a);
+            }
+            I64Load32U => {
+                self.memory_address(index, level, param1, 4);
+                self.load(false, RAX, RAX, 0);
+                self.store(true, RAX, R12, a);
+            }
+
+            I32Store | I64Store32 | F32Store => {
+                self.memory_address(index, level, param1, 4);
+                self.load(false, RCX, R12, b);
+                self.store(false, RCX, RAX, 0);
+            }
+            I32Store8 | I64Store8 => {
+                self.memory_address(index, level, param1, 1);
+                self.load(false, RCX, R12, b);
+                self.op_mem(false, &[0x88], RCX, RAX, 0);
+            }
+            I32Store16 | I64Store16 => {
+                self.memory_address(index, level, param1, 2);
+                self.load(false, RCX, R12, b);
+                self.buf.push(0x66);
+                self.op_mem(false, &[0x89], RCX, RAX, 0);
+            }
+            I64Store | F64Store => {
+                self.memory_address(index, level, param1, 8);
+                self.load(true, RCX, R12, b);
+                self.store(true, RCX, RAX, 0);
+            }
+
+            MemorySize => {
+                self.op_reg(true, &[0x89], R14, RAX);
+                self.op_reg(true, &[0xC1], 5, RAX);
+                self.buf.push(16);
+                self.store(false, RAX, R12, a);
+            }
+
+            I32Const => self.store_imm32(false, R12, a, param1 as u32),
+            I64Const | F64Const => {
+                self.mov_imm64(RAX, param1);
+                self.store(true, RAX, R12, a);
+            }
+            F32Const => {
+                self.mov_imm32(RAX, param1 as u32);
+                self.store(true, RAX, R12, a);
+            }
+
+            I32Eqz => self.eqz(false, a),
+            I64Eqz => self.eqz(true, a),
+
+            I32Eq => self.compare(false, Cond::E, a, b),
+            I32Ne => self.compare(false, Cond::NE, a, b),
+            I32LtS => self.compare(false, Cond::L, a, b),
+            I32LtU => self.compare(false, Cond::B, a, b),
+            I32GtS => self.compare(false, Cond::G, a, b),
+            I32GtU => self.compare(false, Cond::A, a, b),
+            I32LeS => self.compare(false, Cond::LE, a, b),
+            I32LeU => self.compare(false, Cond::BE, a, b),
+            I32GeS => self.compare(false, Cond::GE, a, b),
+            I32GeU => self.compare(false, Cond::AE, a, b),
+            I64Eq => self.compare(true, Cond::E, a, b),
+            I64Ne => self.compare(true, Cond::NE, a, b),
+            I64LtS => self.compare(true, Cond::L, a, b),
+            I64LtU => self.compare(true, Cond::B, a, b),
+            I64GtS => self.compare(true, Cond::G, a, b),
+            I64GtU => self.compare(true, Cond::A, a, b),
+            I64LeS => self.compare(true, Cond::LE, a, b),
+            I64LeU => self.compare(true, Cond::BE, a, b),
+            I64GeS => self.compare(true, Cond::GE, a, b),
+            I64GeU => self.compare(true, Cond::AE, a, b),
+
+            I32Clz => self.count_zeros(false, true, a),
+            I32Ctz => self.count_zeros(false, false, a),
+            I64Clz => self.count_zeros(true, true, a),
+            I64Ctz => self.count_zeros(true, false, a),
+
+            I32Add => self.binary(false, &[0x03], a, b),
+            I32Sub => self.binary(false, &[0x2B], a, b),
+            I32Mul => self.binary(false, &[0x0F, 0xAF], a, b),
+            I32And => self.binary(false, &[0x23], a, b),
+            I32Or => self.binary(false, &[0x0B], a, b),
+            I32Xor => self.binary(false, &[0x33], a, b),
+            I64Add => self.binary(true, &[0x03], a, b),
+            I64Sub => self.binary(true, &[0x2B], a, b),
+            I64Mul => self.binary(true, &[0x0F, 0xAF], a, b),
+            I64And => self.binary(true, &[0x23], a, b),
+            I64Or => self.binary(true, &[0x0B], a, b),
+            I64Xor => self.binary(true, &[0x33], a, b),
+
+            I32Shl => self.shift(false, 4, a, b),
+            I32ShrS => self.shift(false, 7, a, b),
+            I32ShrU => self.shift(false, 5, a, b),
+            I32Rotl => self.shift(false, 0, a, b),
+            I32Rotr => self.shift(false, 1, a, b),
+            I64Shl => self.shift(true, 4, a, b),
+            I64ShrS => self.shift(true, 7, a, b),
+            I64ShrU => self.shift(true, 5, a, b),
+            I64Rotl => self.shift(true, 0, a, b),
+            I64Rotr => self.shift(true, 1, a, b),
+
+            I32DivS => self.divide(index, false, true, false, a, b),
+            I32DivU => self.divide(index, false, false, false, a, b),
+            I32RemS => self.divide(index, false, true, true, a, b),
+            I32RemU => self.divide(index, false, false, true, a, b),
+            I64DivS => self.divide(index, true, true, false, a, b),
+            I64DivU => self.divide(index, true, false, false, a, b),
+            I64RemS => self.divide(index, true, true, true, a, b),
+            I64RemU => self.divide(index, true, false, true, a, b),
+
+            I32Extend8S => {
+                self.op_mem(false, &[0x0F, 0xBE], RAX, R12, a);
+                self.store(false, RAX, R12, a);
+            }
+            I32Extend16S => {
+                self.op_mem(false, &[0x0F, 0xBF], RAX, R12, a);
+                self.store(false, RAX, R12, a);
+            }
+            I64Extend8S => {
+                self.op_mem(true, &[0x0F, 0xBE], RAX, R12, a);
+                self.store(true, RAX, R12, a);
+            }
+            I64Extend16S => {
+                self.op_mem(true, &[0x0F, 0xBF], RAX, R12, a);
+                self.store(true, RAX, R12, a);
+            }
+            I64Extend32S | I64ExtendI32S => {
+                self.op_mem(true, &[0x63], RAX, R12, a);
+                self.store(true, RAX, R12, a);
+            }
+            I64ExtendI32U | I32ReinterpretF32 | F32ReinterpretI32 => {
+                self.load(false, RAX, R12, a);
+                self.store(true, RAX, R12, a);
+            }
+            // The lower half of the slot is already the result
+            I32WrapI64 | I64ReinterpretF64 | F64ReinterpretI64 => (),
+
+            FusedI32AddI => self.op_mem_imm32(false, 0, a, param1 as u32),
+            FusedI32SubI => self.op_mem_imm32(false, 5, a, param1 as u32),
+            FusedI32AndI => self.op_mem_imm32(false, 4, a, param1 as u32),
+            FusedI32OrI => self.op_mem_imm32(false, 1, a, param1 as u32),
+            FusedI32XorI => self.op_mem_imm32(false, 6, a, param1 as u32),
+            FusedI32ShlI => self.shift_imm(4, a, param1 as u8),
+            FusedI32ShrSI => self.shift_imm(7, a, param1 as u8),
+            FusedI32ShrUI => self.shift_imm(5, a, param1 as u8),
+            FusedI64AddI | FusedI64SubI => {
+                let (digit, opcode) = if code.mnemonic() == FusedI64AddI {
+                    (0, 0x01)
+                } else {
+                    (5, 0x29)
+                };
+                let imm = param1 as i64;
+                if imm == imm as i32 as i64 {
+                    self.op_mem_imm32(true, digit, a, imm as u32);
+                } else {
+                    self.mov_imm64(RAX, param1);
+                    self.op_mem(true, &[opcode], RAX, R12, a);
+                }
+            }
+
+            _ => unreachable!(),
+        }
+    }
+
+    /// Returns (target, dest_level, arity) of the branch parameter
+    #[inline]
+    fn branch_entry(&self, position: usize) -> (usize, usize, usize) {
+        let ext_params = self.code_block.ext_params();
+        (
+            ext_params[position],
+            ext_params[position + 1],
+            ext_params[position + 2],
+        )
+    }
+
+    fn copy_slots(&mut self, source: usize, dest: usize, arity: usize) {
+        if source == dest {
+            return;
+        }
+        for i in 0..arity {
+            self.load(true, RCX, R12, Self::slot(source + i));
+            self.store(true, RCX, R12, Self::slot(dest + i));
+        }
+    }
+
+    /// Calculates the address of the linear memory into RAX, or traps if it is out of bounds.
+    fn memory_address(&mut self, index: usize, level: usize, offset: u64, size: i32) {
+        self.load(false, RAX, R12, Self::slot(level));
+        if offset > 0 {
+            if offset <= i32::MAX as u64 {
+                // add rax, imm32
+                self.rex(true, 0, RAX);
+                self.buf.push(0x05);
+                self.imm32(offset as u32);
+            } else {
+                self.mov_imm64(RCX, offset);
+                self.op_reg(true, &[0x01], RCX, RAX);
+            }
+        }
+        // lea rdx, [rax + size]
+        self.op_mem(true, &[0x8D], RDX, RAX, size);
+        // cmp rdx, r14
+        self.op_reg(true, &[0x39], R14, RDX);
+        self.jcc_trap(Cond::A, index, WasmRuntimeErrorType::OutOfBounds);
+        // add rax, r13
+        self.op_reg(true, &[0x01], R13, RAX);
+    }
+
+    fn eqz(&mut self, w: bool, a: i32) {
+        // cmp [r12 + a], 0
+        self.op_mem(w, &[0x83], 7, R12, a);
+        self.buf.push(0);
+        self.setcc(Cond::E);
+        self.store(false, RAX, R12, a);
+    }
+
+    fn compare(&mut self, w: bool, cond: Cond, a: i32, b: i32) {
+        self.load(w, RAX, R12, a);
+        self.op_mem(w, &[0x3B], RAX, R12, b);
+        self.setcc(cond);
+        self.store(false, RAX, R12, a);
+    }
+
+    fn binary(&mut self, w: bool, opcode: &[u8], a: i32, b: i32) {
+        self.load(w, RAX, R12, a);
+        self.op_mem(w, opcode, RAX, R12, b);
+        self.store(w, RAX, R12, a);
+    }
+
+    fn shift(&mut self, w: bool, digit: u8, a: i32, b: i32) {
+        self.load(w, RAX, R12, a);
+        self.load(false, RCX, R12, b);
+        self.op_reg(w, &[0xD3], digit, RAX);
+        self.store(w, RAX, R12, a);
+    }
+
+    fn shift_imm(&mut self, digit: u8, a: i32, imm: u8) {
+        self.op_mem(false, &[0xC1], digit, R12, a);
+        self.buf.push(imm);
+    }
+
+    fn count_zeros(&mut self, w: bool, leading: bool, a: i32) {
+        let bits = if w { 64 } else { 32 };
+        if leading {
+            // clz = (bits - 1) - bsr(x), bsr(0) is treated as -1
+            self.op_reg(true, &[0xC7], 0, RCX);
+            self.imm32(u32::MAX);
+            self.op_mem(w, &[0x0F, 0xBD], RAX, R12, a);
+            self.op_reg(w, &[0x0F, 0x44], RAX, RCX);
+            self.op_reg(w, &[0xF7], 3, RAX);
+            self.op_reg(w, &[0x83], 0, RAX);
+            self.buf.push(bits - 1);
+        } else {
+            self.mov_imm32(RCX, bits as u32);
+            self.op_mem(w, &[0x0F, 0xBC], RAX, R12, a);
+            self.op_reg(w, &[0x0F, 0x44], RAX, RCX);
+        }
+        self.store(w, RAX, R12, a);
+    }
+
+    /// Division that behaves like `wrapping_div` and `wrapping_rem` of the interpreter
+    fn divide(&mut self, index: usize, w: bool, signed: bool, rem: bool, a: i32, b: i32) {
+        self.load(w, RCX, R12, b);
+        self.op_reg(w, &[0x85], RCX, RCX);
+        self.jcc_trap(Cond::E, index, WasmRuntimeErrorType::DivideByZero);
+        self.load(w, RAX, R12, a);
+        if signed {
+            // MIN / -1 overflows on x86
+            self.op_reg(w, &[0x83], 7, RCX);
+            self.buf.push(0xFF);
+            let normal = self.jcc8_forward(Cond::NE);
+            if rem {
+                self.op_reg(false, &[0x31], RAX, RAX);
+            } else {
+                self.op_reg(w, &[0xF7], 3, RAX);
+            }
+            let done = self.jmp8_forward();
+            self.patch8_here(normal);
+            if w {
+                self.buf.push(0x48);
+            }
+            self.buf.push(0x99);
+            self.op_reg(w, &[0xF7], 7, RCX);
+            if rem {
+                self.op_reg(w, &[0x89], RDX, RAX);
+            }
+            self.patch8_here(done);
+        } else {
+            self.op_reg(false, &[0x31], RDX, RDX);
+            self.op_reg(w, &[0xF7], 6, RCX);
+            if rem {
+                self.op_reg(w, &[0x89], RDX, RAX);
+            }
+        }
+        self.store(w, RAX, R12, a);
+    }
+
+    fn prologue(&mut self) {
+        self.buf.push(0x53);
+        self.buf
+            .extend_from_slice(&[0x41, 0x54, 0x41, 0x55, 0x41, 0x56, 0x41, 0x57]);
+        self.op_reg(true, &[0x89], RDI, R15);
+        self.op_reg(true, &[0x89], RSI, RBX);
+        self.op_reg(true, &[0x89], RDX, R12);
+        self.load(true, R13, R15, WasmJitContext::MEMORY_BASE);
+        self.load(true, R14, R15, WasmJitContext::MEMORY_SIZE);
+    }
+
+    fn trap_stubs(&mut self) {
+        for (position, index, kind) in core::mem::take(&mut self.traps) {
+            let here = self.buf.len();
+            self.patch32(position, here);
+            self.store_imm32(true, R15, WasmJitContext::TRAP_POSITION, index as u32);
+            self.mov_imm32(RAX, trap_status(kind));
+            self.jmp_exit();
+        }
+    }
+
+    fn epilogue(&mut self) {
+        let here = self.buf.len();
+        for position in core::mem::take(&mut self.exits) {
+            self.patch32(position, here);
+        }
+        self.buf
+            .extend_from_slice(&[0x41, 0x5F, 0x41, 0x5E, 0x41, 0x5D, 0x41, 0x5C, 0x5B, 0xC3]);
+    }
+
+    #[inline]
+    fn imm32(&mut self, imm: u32) {
+        self.buf.extend_from_slice(&imm.to_le_bytes());
+    }
+
+    #[inline]
+    fn rex(&mut self, w: bool, reg: u8, rm: u8) {
+        let rex = 0x40 | ((w as u8) << 3) | ((reg >> 3) << 2) | (rm >> 3);
+        if rex != 0x40 {
+            self.buf.push(rex);
+        }
+    }
+
+    /// op reg, [base + disp32]
+    fn op_mem(&mut self, w: bool, opcode: &[u8], reg: u8, base: u8, disp: i32) {
+        self.rex(w, reg, base);
+        self.buf.extend_from_slice(opcode);
+        self.buf.push(0x80 | ((reg & 7) << 3) | (base & 7));
+        if base & 7 == 4 {
+            self.buf.push(0x24);
+        }
+        self.imm32(disp as u32);
+    }
+
+    /// op rm, reg
+    fn op_reg(&mut self, w: bool, opcode: &[u8], reg: u8, rm: u8) {
+        self.rex(w, reg, rm);
+        self.buf.extend_from_slice(opcode);
+        self.buf.push(0xC0 | ((reg & 7) << 3) | (rm & 7));
+    }
+
+    /// op [r12 + disp32], imm32
+    fn op_mem_imm32(&mut self, w: bool, digit: u8, disp: i32, imm: u32) {
+        self.op_mem(w, &[0x81], digit, R12, disp);
+        self.imm32(imm);
+    }
+
+    #[inline]
+    fn load(&mut self, w: bool, reg: u8, base: u8, disp: i32) {
+        self.op_mem(w, &[0x8B], reg, base, disp);
+    }
+
+    #[inline]
+    fn store(&mut self, w: bool, reg: u8, base: u8, disp: i32) {
+        self.op_mem(w, &[0x89], reg, base, disp);
+    }
+
+    #[inline]
+    fn store_imm32(&mut self, w: bool, base: u8, disp: i32, imm: u32) {
+        self.op_mem(w, &[0xC7], 0, base, disp);
+        self.imm32(imm);
+    }
+
+    #[inline]
+    fn mov_imm32(&mut self, reg: u8, imm: u32) {
+        self.rex(false, 0, reg);
+        self.buf.push(0xB8 | (reg & 7));
+        self.imm32(imm);
+    }
+
+    #[inline]
+    fn mov_imm64(&mut self, reg: u8, imm: u64) {
+        self.rex(true, 0, reg);
+        self.buf.push(0xB8 | (reg & 7));
+        self.buf.extend_from_slice(&imm.to_le_bytes());
+    }
+
+    /// setcc al; movzx eax, al
+    #[inline]
+    fn setcc(&mut self, cond: Cond) {
+        self.buf
+            .extend_from_slice(&[0x0F, 0x90 | cond as u8, 0xC0, 0x0F, 0xB6, 0xC0]);
+    }
+
+    fn jmp_label(&mut self, target: usize) {
+        self.buf.push(0xE9);
+        self.branches.push((self.buf.len(), target));
+        self.imm32(0);
+    }
+
+    fn jcc_label(&mut self, cond: Cond, target: usize) {
+        self.buf.extend_from_slice(&[0x0F, 0x80 | cond as u8]);
+        self.branches.push((self.buf.len(), target));
+        self.imm32(0);
+    }
+
+    fn jmp_trap(&mut self, index: usize, kind: WasmRuntimeErrorType) {
+        self.buf.push(0xE9);
+        self.traps.push((self.buf.len(), index, kind));
+        self.imm32(0);
+    }
+
+    fn jcc_trap(&mut self, cond: Cond, index: usize, kind: WasmRuntimeErrorType) {
+        self.buf.extend_from_slice(&[0x0F, 0x80 | cond as u8]);
+        self.traps.push((self.buf.len(), index, kind));
+        self.imm32(0);
+    }
+
+    fn jmp_exit(&mut self) {
+        self.buf.push(0xE9);
+        self.exits.push(self.buf.len());
+        self.imm32(0);
+    }
+
+    fn jcc_exit(&mut self, cond: Cond) {
+        self.buf.extend_from_slice(&[0x0F, 0x80 | cond as u8]);
+        self.exits.push(self.buf.len());
+        self.imm32(0);
+    }
+
+    fn jcc32_forward(&mut self, cond: Cond) -> usize {
+        self.buf.extend_from_slice(&[0x0F, 0x80 | cond as u8]);
+        let position = self.buf.len();
+        self.imm32(0);
+        position
+    }
+
+    fn jcc8_forward(&mut self, cond: Cond) -> usize {
+        self.buf.extend_from_slice(&[0x70 | cond as u8, 0]);
+        self.buf.len() - 1
+    }
+
+    fn jmp8_forward(&mut self) -> usize {
+        self.buf.extend_from_slice(&[0xEB, 0]);
+        self.buf.len() - 1
+    }
+
+    fn patch8_here(&mut self, position: usize) {
+        let rel = self.buf.len() - (position + 1);
+        debug_assert!(rel < 0x80);
+        self.buf[position] = rel as u8;
+    }
+
+    fn patch32_here(&mut self, position: usize) {
+        let here = self.buf.len();
+        self.patch32(position, here);
+    }
+
+    fn patch32(&mut self, position: usize, target: usize) {
+        let rel = target as isize - (position + 4) as isize;
+        self.buf[position..position + 4].copy_from_slice(&(rel as i32).to_le_bytes());
+    }
+}
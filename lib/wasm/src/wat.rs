@@ -0,0 +1,665 @@
+//! WebAssembly Text Format
+
+use crate::{opcode::*, *};
+use alloc::string::String;
+use core::fmt::{self, Write};
+
+/// Writes a loaded module in the WebAssembly text format
+///
+/// Function bodies are decoded from their original bytecode,
+/// and active data segments show the current contents of the memory they were copied into.
+/// Each instruction is followed by its position in the function body, as shown in the backtraces.
+pub struct WasmDisassembler<'a> {
+    module: &'a WasmModule,
+}
+
+impl<'a> WasmDisassembler<'a> {
+    /// Column where the positions of the instructions are written
+    const POSITION_COLUMN: usize = 40;
+
+    #[inline]
+    pub const fn new(module: &'a WasmModule) -> Self {
+        Self { module }
+    }
+}
+
+impl WasmDisassembler<'_> {
+    fn write_types(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, func_type) in self.module.types().iter().enumerate() {
+            writeln!(f, "  (type (;{};) (func{}))", index, func_type)?;
+        }
+        Ok(())
+    }
+
+    fn write_imports(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut n_funcs = 0;
+        let mut n_tables = 0;
+        let mut n_memories = 0;
+        let mut n_globals = 0;
+        for import in self.module.imports() {
+            write!(
+                f,
+                "  (import \"{}\" \"{}\" ",
+                import.mod_name(),
+                import.name()
+            )?;
+            match import.index() {
+                WasmImportIndex::Type(type_index) => {
+                    write!(f, "(func")?;
+                    self.write_func_id(f, n_funcs)?;
+                    write!(f, " (type {}))", type_index)?;
+                    n_funcs += 1;
+                }
+                WasmImportIndex::Table(ref_type, limit) => {
+                    write!(f, "(table (;{};)", n_tables)?;
+                    Self::write_limit(f, limit)?;
+                    write!(f, " {})", ref_type)?;
+                    n_tables += 1;
+                }
+                WasmImportIndex::Memory(limit) => {
+                    write!(f, "(memory (;{};)", n_memories)?;
+                    Self::write_limit(f, limit)?;
+                    write!(f, ")")?;
+                    n_memories += 1;
+                }
+                WasmImportIndex::Global(val_type, is_mutable) => {
+                    write!(f, "(global (;{};) ", n_globals)?;
+                    Self::write_global_type(f, val_type, is_mutable)?;
+                    write!(f, ")")?;
+                    n_globals += 1;
+                }
+            }
+            writeln!(f, ")")?;
+        }
+        Ok(())
+    }
+
+    fn write_functions(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for function in self.module.functions() {
+            let code_block = match function.code_block() {
+                Some(v) => v,
+                None => continue,
+            };
+            write!(f, "  (func")?;
+            self.write_func_id(f, function.index())?;
+            let func_type = self.module.type_by_ref(function.type_index());
+            write!(f, " (type {})", function.type_index())?;
+            if let Some(func_type) = func_type {
+                write!(f, "{}", func_type)?;
+            }
+            writeln!(f)?;
+
+            let n_params = function.param_types().len();
+            let local_types = code_block.local_types().get(n_params..).unwrap_or(&[]);
+            if !local_types.is_empty() {
+                write!(f, "    (local")?;
+                for local_type in local_types {
+                    write!(f, " {}", local_type)?;
+                }
+                writeln!(f, ")")?;
+            }
+
+            self.write_body(f, code_block.source())?;
+            writeln!(f, "  )")?;
+        }
+        Ok(())
+    }
+
+    fn write_body(&self, f: &mut fmt::Formatter<'_>, source: &[u8]) -> fmt::Result {
+        let mut stream = Leb128Stream::from_slice(source);
+        if Self::skip_locals(&mut stream).is_err() {
+            return writeln!(f, "    ;; no bytecode");
+        }
+
+        let mut level = 0;
+        let mut line = String::new();
+        while !stream.is_eof() {
+            let position = stream.position();
+            let opcode = match stream.read_opcode() {
+                Ok(v) => v,
+                Err(_) => return writeln!(f, "    ;; invalid bytecode at +{:x}", position),
+            };
+            match opcode {
+                WasmOpcode::End if level == 0 => break,
+                WasmOpcode::End | WasmOpcode::Delegate => level = usize::saturating_sub(level, 1),
+                _ => (),
+            }
+
+            line.clear();
+            let indent = if matches!(
+                opcode,
+                WasmOpcode::Else | WasmOpcode::Catch | WasmOpcode::CatchAll
+            ) {
+                usize::saturating_sub(level, 1)
+            } else {
+                level
+            };
+            for _ in 0..=indent {
+                line.push_str("  ");
+            }
+            if self
+                .write_instruction(&mut line, &mut stream, opcode)
+                .is_err()
+            {
+                return writeln!(f, "    ;; invalid bytecode at +{:x}", position);
+            }
+            writeln!(
+                f,
+                "  {:<width$} ;; +{:x}",
+                line,
+                position,
+                width = Self::POSITION_COLUMN
+            )?;
+
+            if opcode.operand_type() == WasmOperandType::Block {
+                level += 1;
+            }
+        }
+        Ok(())
+    }
+
+    fn skip_locals(stream: &mut Leb128Stream) -> Result<(), WasmDecodeErrorType> {
+        let n_local_types = stream.read_unsigned()?;
+        for _ in 0..n_local_types {
+            stream.read_unsigned()?;
+            stream.read_unsigned()?;
+        }
+        Ok(())
+    }
+
+    /// Writes the mnemonic and the operands of an instruction
+    fn write_instruction(
+        &self,
+        line: &mut String,
+        stream: &mut Leb128Stream,
+        opcode: WasmOpcode,
+    ) -> Result<(), WasmDecodeErrorType> {
+        match opcode {
+            WasmOpcode::PrefixFC => return self.write_instruction_fc(line, stream),
+            WasmOpcode::PrefixFD => return Self::write_instruction_fd(line, stream),
+            WasmOpcode::PrefixFE => return Self::write_instruction_fe(line, stream),
+            _ => (),
+        }
+        line.push_str(opcode.to_str());
+
+        match opcode.operand_type() {
+            WasmOperandType::Implied | WasmOperandType::Else | WasmOperandType::End => (),
+            WasmOperandType::Block => match WasmBlockType::from_i64(stream.read_signed()?)? {
+                WasmBlockType::Empty => (),
+                WasmBlockType::I32 => line.push_str(" (result i32)"),
+                WasmBlockType::I64 => line.push_str(" (result i64)"),
+                WasmBlockType::F32 => line.push_str(" (result f32)"),
+                WasmBlockType::F64 => line.push_str(" (result f64)"),
+                WasmBlockType::V128 => line.push_str(" (result v128)"),
+                WasmBlockType::FuncRef => line.push_str(" (result funcref)"),
+                WasmBlockType::ExternRef => line.push_str(" (result externref)"),
+                WasmBlockType::Type(index) => write!(line, " (type {})", index).unwrap(),
+            },
+            WasmOperandType::Br
+            | WasmOperandType::Local
+            | WasmOperandType::Global
+            | WasmOperandType::Table
+            | WasmOperandType::Tag => {
+                write!(line, " {}", stream.read_unsigned()?).unwrap();
+            }
+            WasmOperandType::BrTable => {
+                let n_labels = stream.read_unsigned()?;
+                for _ in 0..=n_labels {
+                    write!(line, " {}", stream.read_unsigned()?).unwrap();
+                }
+            }
+            WasmOperandType::Call | WasmOperandType::Func => {
+                let index = stream.read_unsigned()? as usize;
+                self.write_func_ref(line, index);
+            }
+            WasmOperandType::CallIndirect => {
+                let type_index = stream.read_unsigned()?;
+                let table_index = stream.read_unsigned()?;
+                if table_index != 0 {
+                    write!(line, " {}", table_index).unwrap();
+                }
+                write!(line, " (type {})", type_index).unwrap();
+            }
+            WasmOperandType::Memory => {
+                let memarg = stream.read_memarg()?;
+                Self::write_memarg(line, memarg);
+            }
+            WasmOperandType::MemSize => {
+                stream.read_byte()?;
+            }
+            WasmOperandType::Select => {
+                let n_types = stream.read_unsigned()?;
+                line.push_str(" (result");
+                for _ in 0..n_types {
+                    let val_type = stream
+                        .read_byte()
+                        .and_then(|v| WasmValType::from_u64(v as u64))?;
+                    write!(line, " {}", val_type).unwrap();
+                }
+                line.push(')');
+            }
+            WasmOperandType::RefType => match WasmValType::ref_type_from_stream(stream)? {
+                WasmValType::ExternRef => line.push_str(" extern"),
+                _ => line.push_str(" func"),
+            },
+            WasmOperandType::I32 => {
+                write!(line, " {}", stream.read_signed()? as i32).unwrap();
+            }
+            WasmOperandType::I64 => {
+                write!(line, " {}", stream.read_signed()?).unwrap();
+            }
+            WasmOperandType::F32 => {
+                let v = stream.read_f32()?;
+                line.push(' ');
+                Self::write_float(line, v, v.is_nan(), v.is_infinite(), v.is_sign_negative());
+            }
+            WasmOperandType::F64 => {
+                let v = stream.read_f64()?;
+                line.push(' ');
+                Self::write_float(line, v, v.is_nan(), v.is_infinite(), v.is_sign_negative());
+            }
+        }
+        Ok(())
+    }
+
+    fn write_instruction_fc(
+        &self,
+        line: &mut String,
+        stream: &mut Leb128Stream,
+    ) -> Result<(), WasmDecodeErrorType> {
+        let opcode = stream.read_unsigned().and_then(|v| {
+            WasmOpcodeFC::new(v as u32).ok_or(WasmDecodeErrorType::InvalidBytecode)
+        })?;
+        line.push_str(opcode.to_str());
+        match opcode {
+            WasmOpcodeFC::MemoryInit => {
+                write!(line, " {}", stream.read_unsigned()?).unwrap();
+                stream.read_byte()?;
+            }
+            WasmOpcodeFC::DataDrop | WasmOpcodeFC::ElemDrop => {
+                write!(line, " {}", stream.read_unsigned()?).unwrap();
+            }
+            WasmOpcodeFC::MemoryCopy => {
+                stream.read_byte()?;
+                stream.read_byte()?;
+            }
+            WasmOpcodeFC::MemoryFill => {
+                stream.read_byte()?;
+            }
+            WasmOpcodeFC::TableInit => {
+                let elem_index = stream.read_unsigned()?;
+                let table_index = stream.read_unsigned()?;
+                write!(line, " {} {}", table_index, elem_index).unwrap();
+            }
+            WasmOpcodeFC::TableCopy => {
+                let dest = stream.read_unsigned()?;
+                let src = stream.read_unsigned()?;
+                write!(line, " {} {}", dest, src).unwrap();
+            }
+            WasmOpcodeFC::TableGrow | WasmOpcodeFC::TableSize | WasmOpcodeFC::TableFill => {
+                write!(line, " {}", stream.read_unsigned()?).unwrap();
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    fn write_instruction_fd(
+        line: &mut String,
+        stream: &mut Leb128Stream,
+    ) -> Result<(), WasmDecodeErrorType> {
+        let opcode = stream.read_unsigned().and_then(|v| {
+            WasmOpcodeFD::new(v as u32).ok_or(WasmDecodeErrorType::InvalidBytecode)
+        })?;
+        line.push_str(opcode.to_str());
+        match opcode {
+            WasmOpcodeFD::V128Load
+            | WasmOpcodeFD::V128Load8x8S
+            | WasmOpcodeFD::V128Load8x8U
+            | WasmOpcodeFD::V128Load16x4S
+            | WasmOpcodeFD::V128Load16x4U
+            | WasmOpcodeFD::V128Load32x2S
+            | WasmOpcodeFD::V128Load32x2U
+            | WasmOpcodeFD::V128Load8Splat
+            | WasmOpcodeFD::V128Load16Splat
+            | WasmOpcodeFD::V128Load32Splat
+            | WasmOpcodeFD::V128Load64Splat
+            | WasmOpcodeFD::V128Load32Zero
+            | WasmOpcodeFD::V128Load64Zero
+            | WasmOpcodeFD::V128Store => {
+                let memarg = stream.read_memarg()?;
+                Self::write_memarg(line, memarg);
+            }
+            WasmOpcodeFD::V128Load8Lane
+            | WasmOpcodeFD::V128Load16Lane
+            | WasmOpcodeFD::V128Load32Lane
+            | WasmOpcodeFD::V128Load64Lane
+            | WasmOpcodeFD::V128Store8Lane
+            | WasmOpcodeFD::V128Store16Lane
+            | WasmOpcodeFD::V128Store32Lane
+            | WasmOpcodeFD::V128Store64Lane => {
+                let memarg = stream.read_memarg()?;
+                Self::write_memarg(line, memarg);
+                write!(line, " {}", stream.read_byte()?).unwrap();
+            }
+            WasmOpcodeFD::V128Const => {
+                line.push(' ');
+                Self::write_v128(line, stream.read_v128()?);
+            }
+            WasmOpcodeFD::I8x16Shuffle => {
+                for lane in stream.get_bytes(16)? {
+                    write!(line, " {}", lane).unwrap();
+                }
+            }
+            WasmOpcodeFD::I8x16ExtractLaneS
+            | WasmOpcodeFD::I8x16ExtractLaneU
+            | WasmOpcodeFD::I8x16ReplaceLane
+            | WasmOpcodeFD::I16x8ExtractLaneS
+            | WasmOpcodeFD::I16x8ExtractLaneU
+            | WasmOpcodeFD::I16x8ReplaceLane
+            | WasmOpcodeFD::I32x4ExtractLane
+            | WasmOpcodeFD::I32x4ReplaceLane
+            | WasmOpcodeFD::I64x2ExtractLane
+            | WasmOpcodeFD::I64x2ReplaceLane
+            | WasmOpcodeFD::F32x4ExtractLane
+            | WasmOpcodeFD::F32x4ReplaceLane
+            | WasmOpcodeFD::F64x2ExtractLane
+            | WasmOpcodeFD::F64x2ReplaceLane => {
+                write!(line, " {}", stream.read_byte()?).unwrap();
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    /// Writes the operand of `v128.const` as four 32-bit lanes
+    fn write_v128(line: &mut String, v: u128) {
+        line.push_str("i32x4");
+        for i in 0..4 {
+            write!(line, " 0x{:08x}", (v >> (i * 32)) as u32).unwrap();
+        }
+    }
+
+    fn write_instruction_fe(
+        line: &mut String,
+        stream: &mut Leb128Stream,
+    ) -> Result<(), WasmDecodeErrorType> {
+        let opcode = stream.read_unsigned().and_then(|v| {
+            WasmOpcodeFE::new(v as u32).ok_or(WasmDecodeErrorType::InvalidBytecode)
+        })?;
+        line.push_str(opcode.to_str());
+        match opcode {
+            WasmOpcodeFE::AtomicFence => {
+                stream.read_byte()?;
+            }
+            _ => {
+                let memarg = stream.read_memarg()?;
+                Self::write_memarg(line, memarg);
+            }
+        }
+        Ok(())
+    }
+
+    fn write_memarg(line: &mut String, memarg: WasmMemArg) {
+        if memarg.offset != 0 {
+            write!(line, " offset={}", memarg.offset).unwrap();
+        }
+        write!(line, " align={}", 1u64 << memarg.align.min(63)).unwrap();
+    }
+
+    /// Writes a float in the notation of the text format, which differs from Rust in NaN and infinity
+    fn write_float<T: fmt::Display>(
+        line: &mut String,
+        value: T,
+        is_nan: bool,
+        is_infinite: bool,
+        is_negative: bool,
+    ) {
+        if is_nan {
+            line.push_str(if is_negative { "-nan" } else { "nan" });
+        } else if is_infinite {
+            line.push_str(if is_negative { "-inf" } else { "inf" });
+        } else {
+            write!(line, "{}", value).unwrap();
+        }
+    }
+
+    fn write_tables(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let n_imports = self
+            .module
+            .imports()
+            .iter()
+            .filter(|v| matches!(v.index(), WasmImportIndex::Table(_, _)))
+            .count();
+        let mut index = n_imports;
+        while let Some(table) = self.module.table(index) {
+            write!(f, "  (table (;{};)", index)?;
+            Self::write_limit(f, table.limit())?;
+            writeln!(f, " {})", table.ref_type())?;
+            index += 1;
+        }
+        Ok(())
+    }
+
+    fn write_memories(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let n_imports = self
+            .module
+            .imports()
+            .iter()
+            .filter(|v| matches!(v.index(), WasmImportIndex::Memory(_)))
+            .count();
+        for (index, memory) in self.module.memories().iter().enumerate().skip(n_imports) {
+            write!(f, "  (memory (;{};)", index)?;
+            Self::write_limit(f, memory.limit())?;
+            writeln!(f, ")")?;
+        }
+        Ok(())
+    }
+
+    /// Writes the tags of the exception handling proposal
+    fn write_tags(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, type_index) in self.module.tags().iter().enumerate() {
+            writeln!(f, "  (tag (;{};) (type {}))", index, type_index)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the globals with their current values
+    fn write_globals(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let n_imports = self
+            .module
+            .imports()
+            .iter()
+            .filter(|v| matches!(v.index(), WasmImportIndex::Global(_, _)))
+            .count();
+        for (index, global) in self.module.globals().iter().enumerate().skip(n_imports) {
+            write!(f, "  (global (;{};) ", index)?;
+            Self::write_global_type(f, global.val_type(), global.is_mutable())?;
+            let mut line = String::new();
+            match *global.value() {
+                WasmValue::I32(v) => write!(line, "i32.const {}", v).unwrap(),
+                WasmValue::I64(v) => write!(line, "i64.const {}", v).unwrap(),
+                WasmValue::F32(v) => {
+                    line.push_str("f32.const ");
+                    Self::write_float(
+                        &mut line,
+                        v,
+                        v.is_nan(),
+                        v.is_infinite(),
+                        v.is_sign_negative(),
+                    );
+                }
+                WasmValue::F64(v) => {
+                    line.push_str("f64.const ");
+                    Self::write_float(
+                        &mut line,
+                        v,
+                        v.is_nan(),
+                        v.is_infinite(),
+                        v.is_sign_negative(),
+                    );
+                }
+                WasmValue::V128(v) => {
+                    line.push_str("v128.const ");
+                    Self::write_v128(&mut line, v);
+                }
+                WasmValue::FuncRef(Some(v)) => {
+                    line.push_str("ref.func");
+                    self.write_func_ref(&mut line, v);
+                }
+                WasmValue::FuncRef(None) => line.push_str("ref.null func"),
+                WasmValue::ExternRef(Some(v)) => write!(line, "ref.extern {}", v).unwrap(),
+                WasmValue::ExternRef(None) => line.push_str("ref.null extern"),
+            }
+            writeln!(f, " ({}))", line)?;
+        }
+        Ok(())
+    }
+
+    fn write_exports(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for export in self.module.exports() {
+            write!(f, "  (export \"{}\" ", export.name())?;
+            match export.index() {
+                WasmExportIndex::Function(v) => {
+                    let mut line = String::from("(func");
+                    self.write_func_ref(&mut line, v);
+                    write!(f, "{})", line)?
+                }
+                WasmExportIndex::Table(v) => write!(f, "(table {})", v)?,
+                WasmExportIndex::Memory(v) => write!(f, "(memory {})", v)?,
+                WasmExportIndex::Global(v) => write!(f, "(global {})", v)?,
+                WasmExportIndex::Tag(v) => write!(f, "(tag {})", v)?,
+            }
+            writeln!(f, ")")?;
+        }
+        Ok(())
+    }
+
+    fn write_data(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut index = 0;
+        while let Some(data) = self.module.data(index) {
+            write!(f, "  (data (;{};)", index)?;
+            match data.mode() {
+                WasmDataMode::Passive => {
+                    Self::write_string(f, data.bytes())?;
+                }
+                WasmDataMode::Active {
+                    memory,
+                    offset,
+                    len,
+                } => {
+                    if memory != 0 {
+                        write!(f, " (memory {})", memory)?;
+                    }
+                    write!(f, " (i32.const {})", offset as i32)?;
+                    match self
+                        .module
+                        .memory(memory)
+                        .and_then(|v| v.read_bytes(offset, len).ok())
+                    {
+                        Some(bytes) => Self::write_string(f, bytes)?,
+                        None => write!(f, " \"\"")?,
+                    }
+                }
+            }
+            writeln!(f, ")")?;
+            index += 1;
+        }
+        Ok(())
+    }
+
+    fn write_string(f: &mut fmt::Formatter<'_>, bytes: &[u8]) -> fmt::Result {
+        write!(f, " \"")?;
+        for byte in bytes {
+            match *byte {
+                b'"' | b'\\' => write!(f, "\\{}", *byte as char)?,
+                0x20..=0x7E => write!(f, "{}", *byte as char)?,
+                _ => write!(f, "\\{:02x}", byte)?,
+            }
+        }
+        write!(f, "\"")
+    }
+
+    fn write_limit(f: &mut fmt::Formatter<'_>, limit: WasmLimit) -> fmt::Result {
+        write!(f, " {}", limit.min())?;
+        if limit.max() != u32::MAX {
+            write!(f, " {}", limit.max())?;
+        }
+        if limit.is_shared() {
+            write!(f, " shared")?;
+        }
+        Ok(())
+    }
+
+    fn write_global_type(
+        f: &mut fmt::Formatter<'_>,
+        val_type: WasmValType,
+        is_mutable: bool,
+    ) -> fmt::Result {
+        if is_mutable {
+            write!(f, "(mut {})", val_type)
+        } else {
+            write!(f, "{}", val_type)
+        }
+    }
+
+    /// Writes the name and the index of the function in a definition
+    fn write_func_id(&self, f: &mut fmt::Formatter<'_>, index: usize) -> fmt::Result {
+        if let Some(name) = self.func_id(index) {
+            write!(f, " ${}", name)?;
+        }
+        write!(f, " (;{};)", index)
+    }
+
+    /// Writes a reference to the function as an operand
+    fn write_func_ref(&self, line: &mut String, index: usize) {
+        match self.func_id(index) {
+            Some(name) => write!(line, " ${}", name).unwrap(),
+            None => write!(line, " {}", index).unwrap(),
+        }
+    }
+
+    /// Returns the name of the function if it can be used as an identifier
+    fn func_id(&self, index: usize) -> Option<&str> {
+        self.module
+            .names()
+            .and_then(|v| v.func_by_index(index))
+            .filter(|v| !v.is_empty() && v.bytes().all(Self::is_idchar))
+    }
+
+    #[inline]
+    const fn is_idchar(c: u8) -> bool {
+        match c {
+            b' ' | b'"' | b',' | b';' | b'(' | b')' | b'[' | b']' | b'{' | b'}' => false,
+            0x21..=0x7E => true,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for WasmDisassembler<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "(module")?;
+        if let Some(name) = self.module.names().and_then(|v| v.module()) {
+            if !name.is_empty() && name.bytes().all(Self::is_idchar) {
+                write!(f, " ${}", name)?;
+            }
+        }
+        writeln!(f)?;
+
+        self.write_types(f)?;
+        self.write_imports(f)?;
+        self.write_functions(f)?;
+        self.write_tables(f)?;
+        self.write_memories(f)?;
+        self.write_tags(f)?;
+        self.write_globals(f)?;
+        self.write_exports(f)?;
+        if let Ok(start) = self.module.entry_point() {
+            writeln!(f, "  (start {})", start.function().index())?;
+        }
+        self.write_data(f)?;
+
+        writeln!(f, ")")
+    }
+}
@@ -0,0 +1,25 @@
+(module
+  (import "spectest" "print_i32" (func $print (param i32)))
+  (global (export "g") i32 (i32.const 42))
+  (memory 1)
+  (func (export "add") (param i32 i32) (result i32) (i32.add (local.get 0) (local.get 1)))
+  (func (export "div") (param i32 i32) (result i32) (i32.div_s (local.get 0) (local.get 1)))
+  (func (export "nan") (result f32) (f32.div (f32.const 0) (f32.const 0)))
+  (func (export "two") (result i32 i64) (i32.const 1) (i64.const 2))
+  (func (export "p") (call $print (i32.const 1)))
+)
+(assert_return (invoke "add" (i32.const 1) (i32.const 2)) (i32.const 3))
+(assert_trap (invoke "div" (i32.const 1) (i32.const 0)) "integer divide by zero")
+(assert_return (invoke "nan") (f32.const nan:canonical))
+(assert_return (invoke "two") (i32.const 1) (i64.const 2))
+(assert_return (get "g") (i32.const 42))
+(invoke "p")
+(assert_invalid (module (func (result i32) (i64.const 0))) "type mismatch")
+(assert_malformed (module binary "\00asm\01\00\00\00\01") "unexpected end")
+(assert_malformed (module quote "(func") "unexpected")
+(assert_exhaustion (invoke "p") "call stack exhausted")
+(assert_unlinkable (module (import "nowhere" "f" (func))) "unknown import")
+(module $M (memory (export "mem") 1))
+(register "M" $M)
+(module (import "M" "mem" (memory 1)) (data (i32.const 0) "a") (func (export "ld") (result i32) (i32.load8_u (i32.const 0))))
+(assert_return (invoke "ld") (i32.const 97))
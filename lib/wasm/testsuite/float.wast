@@ -0,0 +1,34 @@
+(module
+  (func (export "add") (param f32 f32) (result f32) (f32.add (local.get 0) (local.get 1)))
+  (func (export "min") (param f64 f64) (result f64) (f64.min (local.get 0) (local.get 1)))
+  (func (export "nearest") (param f32) (result f32) (f32.nearest (local.get 0)))
+  (func (export "trunc") (param f32) (result i32) (i32.trunc_f32_s (local.get 0)))
+  (func (export "truncu64") (param f64) (result i64) (i64.trunc_f64_u (local.get 0)))
+  (func (export "conv") (param i64) (result f32) (f32.convert_i64_u (local.get 0)))
+  (func (export "reint") (param i32) (result f32) (f32.reinterpret_i32 (local.get 0)))
+  (func (export "sat") (param f32) (result i32) (i32.trunc_sat_f32_s (local.get 0)))
+  (func (export "satu") (param f64) (result i64) (i64.trunc_sat_f64_u (local.get 0)))
+  (func (export "demote") (param f64) (result f32) (f32.demote_f64 (local.get 0)))
+  (func (export "cmp") (param f64 f64) (result i32) (f64.lt (local.get 0) (local.get 1)))
+  (func (export "sel") (param f32 f32 i32) (result f32) (select (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "sel64") (param f64 f64 i32) (result f64) (select (local.get 0) (local.get 1) (local.get 2)))
+)
+(assert_return (invoke "add" (f32.const 1.5) (f32.const 2.25)) (f32.const 3.75))
+(assert_return (invoke "min" (f64.const -0) (f64.const 0)) (f64.const -0))
+(assert_return (invoke "min" (f64.const nan) (f64.const 0)) (f64.const nan:canonical))
+(assert_return (invoke "nearest" (f32.const 2.5)) (f32.const 2))
+(assert_return (invoke "nearest" (f32.const -0.5)) (f32.const -0))
+(assert_trap (invoke "trunc" (f32.const nan)) "invalid conversion to integer")
+(assert_trap (invoke "trunc" (f32.const 2147483648)) "integer overflow")
+(assert_return (invoke "trunc" (f32.const -2147483648)) (i32.const -2147483648))
+(assert_return (invoke "truncu64" (f64.const -0.9)) (i64.const 0))
+(assert_return (invoke "conv" (i64.const -1)) (f32.const 18446744073709551616))
+(assert_return (invoke "conv" (i64.const 0x20000020000001)) (f32.const 0x1.000002p+53))
+(assert_return (invoke "reint" (i32.const 0x7fa00000)) (f32.const nan:0x200000))
+(assert_return (invoke "sat" (f32.const nan)) (i32.const 0))
+(assert_return (invoke "sat" (f32.const -1e10)) (i32.const -2147483648))
+(assert_return (invoke "satu" (f64.const 1e30)) (i64.const -1))
+(assert_return (invoke "demote" (f64.const 1e300)) (f32.const inf))
+(assert_return (invoke "cmp" (f64.const nan) (f64.const 1)) (i32.const 0))
+(assert_return (invoke "sel" (f32.const 1) (f32.const 2) (i32.const 0)) (f32.const 2))
+(assert_return (invoke "sel64" (f64.const 1) (f64.const 2) (i32.const 1)) (f64.const 1))
@@ -0,0 +1,71 @@
+(module
+  (memory 1)
+  (data (i32.const 0) "hello")
+  (data $p "PASSIVE")
+  (func (export "swap") (param i32 i32) (result i32 i32) (local.get 1) (local.get 0))
+  (func (export "blk") (result i32 i32)
+    (i32.const 1) (i32.const 2)
+    (block (param i32 i32) (result i32 i32)
+      (i32.add) (i32.const 10)))
+  (func (export "loopp") (param i32) (result i32)
+    (i32.const 0) (local.get 0)
+    (loop (param i32 i32) (result i32)
+      ;; acc n
+      (local.set 0)
+      (local.get 0) (i32.add)
+      (local.get 0) (i32.const 1) (i32.sub) (local.tee 0)
+      (if (param i32) (result i32) (then (local.get 0) (br 1)) (else))))
+  (func (export "brif") (param i32) (result i32 i64)
+    (block (result i32 i64)
+      (i32.const 7) (i64.const 8) (local.get 0) (br_if 0)
+      (drop) (drop) (i32.const 1) (i64.const 2)))
+  (func (export "ifelse") (param i32) (result i32)
+    (if (result i32) (local.get 0) (then (i32.const 11)) (else (i32.const 22))))
+  (func (export "ext") (param i32) (result i32) (i32.extend8_s (local.get 0)))
+  (func (export "ext64") (param i64) (result i64) (i64.extend32_s (local.get 0)))
+  (func (export "copy") (result i32)
+    (memory.copy (i32.const 10) (i32.const 0) (i32.const 5))
+    (i32.load8_u (i32.const 14)))
+  (func (export "fill") (result i32)
+    (memory.fill (i32.const 100) (i32.const 0x41) (i32.const 3))
+    (i32.load8_u (i32.const 102)))
+  (func (export "init") (result i32)
+    (memory.init $p (i32.const 200) (i32.const 1) (i32.const 3))
+    (i32.load8_u (i32.const 202)))
+  (func (export "drop") (data.drop $p))
+  (func (export "overlap") (result i32)
+    (memory.copy (i32.const 1) (i32.const 0) (i32.const 4))
+    (i32.load8_u (i32.const 4)))
+  (func (export "oob") (memory.fill (i32.const 65530) (i32.const 0) (i32.const 10)))
+  (func (export "brtable") (param i32) (result i32 i32)
+    (block (result i32 i32)
+      (block (result i32 i32)
+        (i32.const 1) (i32.const 2) (local.get 0) (br_table 0 1))
+      (drop) (drop) (i32.const 3) (i32.const 4)))
+  (func $fib (export "fib") (param i32) (result i32)
+    (if (result i32) (i32.lt_s (local.get 0) (i32.const 2)) (then (local.get 0))
+      (else (i32.add (call $fib (i32.sub (local.get 0) (i32.const 1))) (call $fib (i32.sub (local.get 0) (i32.const 2)))))))
+  (func $pair (param i32) (result i32 i32) (local.get 0) (i32.const 5))
+  (func (export "callmv") (result i32) (call $pair (i32.const 3)) (i32.sub))
+)
+(assert_return (invoke "swap" (i32.const 1) (i32.const 2)) (i32.const 2) (i32.const 1))
+(assert_return (invoke "blk") (i32.const 3) (i32.const 10))
+(assert_return (invoke "loopp" (i32.const 4)) (i32.const 10))
+(assert_return (invoke "brif" (i32.const 1)) (i32.const 7) (i64.const 8))
+(assert_return (invoke "brif" (i32.const 0)) (i32.const 1) (i64.const 2))
+(assert_return (invoke "ifelse" (i32.const 0)) (i32.const 22))
+(assert_return (invoke "ifelse" (i32.const 5)) (i32.const 11))
+(assert_return (invoke "ext" (i32.const 0x80)) (i32.const -128))
+(assert_return (invoke "ext64" (i64.const 0x80000000)) (i64.const -2147483648))
+(assert_return (invoke "copy") (i32.const 0x6f))
+(assert_return (invoke "fill") (i32.const 0x41))
+(assert_return (invoke "init") (i32.const 0x53))
+(assert_return (invoke "drop"))
+(assert_trap (invoke "init") "out of bounds")
+(assert_return (invoke "overlap") (i32.const 0x6c))
+(assert_trap (invoke "oob") "out of bounds")
+(assert_return (invoke "brtable" (i32.const 0)) (i32.const 3) (i32.const 4))
+(assert_return (invoke "brtable" (i32.const 1)) (i32.const 1) (i32.const 2))
+(assert_return (invoke "brtable" (i32.const 9)) (i32.const 1) (i32.const 2))
+(assert_return (invoke "fib" (i32.const 15)) (i32.const 610))
+(assert_return (invoke "callmv") (i32.const -2))
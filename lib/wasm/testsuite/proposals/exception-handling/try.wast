@@ -0,0 +1,43 @@
+(module
+  (tag $e (param i32))
+  (tag $e2)
+  (func $throw (param i32) (throw $e (local.get 0)))
+  (func (export "catch") (param i32) (result i32)
+    try (result i32)
+      (call $throw (local.get 0)) (i32.const 0)
+    catch $e
+      (i32.add (i32.const 1))
+    end)
+  (func (export "catchall") (result i32)
+    try (result i32) (throw $e2) (i32.const 0) catch_all (i32.const 9) end)
+  (func (export "uncaught") (throw $e2))
+  (func (export "rethrow") (result i32)
+    try (result i32)
+      try (throw $e (i32.const 5)) catch $e drop rethrow 0 end
+      (i32.const 0)
+    catch $e
+    end)
+  (func (export "nested") (param i32) (result i32)
+    (local i32)
+    try try (throw $e2) catch $e drop end
+    catch $e2 (local.set 1 (i32.const 3)) end
+    (local.get 1))
+  (func (export "delegate") (result i32)
+    try (result i32)
+      try (throw $e (i32.const 4)) delegate 0
+      (i32.const 0)
+    catch $e
+    end)
+  (func (export "stack") (param i32) (result i32)
+    (i32.const 100)
+    try (result i32) (i32.const 1) (i32.const 2) (call $throw (local.get 0)) drop drop (i32.const 0)
+    catch $e end
+    i32.add)
+)
+(assert_return (invoke "catch" (i32.const 41)) (i32.const 42))
+(assert_return (invoke "catchall") (i32.const 9))
+(assert_exception (invoke "uncaught"))
+(assert_return (invoke "rethrow") (i32.const 5))
+(assert_return (invoke "nested" (i32.const 0)) (i32.const 3))
+(assert_return (invoke "delegate") (i32.const 4))
+(assert_return (invoke "stack" (i32.const 5)) (i32.const 105))
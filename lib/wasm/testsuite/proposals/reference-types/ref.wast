@@ -0,0 +1,47 @@
+(module
+  (type $t (func (result i32)))
+  (table $t0 2 funcref)
+  (table $t1 3 10 externref)
+  (func $a (result i32) (i32.const 10))
+  (func $b (result i32) (i32.const 20))
+  (elem (table $t0) (i32.const 0) func $a $b)
+  (elem $e funcref (ref.func $b) (ref.func $a))
+  (elem declare func $a)
+  (func (export "call") (param i32) (result i32) (call_indirect $t0 (type $t) (local.get 0)))
+  (func (export "isnull") (param externref) (result i32) (ref.is_null (local.get 0)))
+  (func (export "set") (param i32 externref) (table.set $t1 (local.get 0) (local.get 1)))
+  (func (export "get") (param i32) (result externref) (table.get $t1 (local.get 0)))
+  (func (export "grow") (param i32) (result i32) (table.grow $t1 (ref.null extern) (local.get 0)))
+  (func (export "size") (result i32) (table.size $t1))
+  (func (export "init") (table.init $t0 $e (i32.const 0) (i32.const 0) (i32.const 2)))
+  (func (export "dropit") (elem.drop $e))
+  (func (export "fill") (param i32 externref i32) (table.fill $t1 (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "copy") (table.copy $t0 $t0 (i32.const 1) (i32.const 0) (i32.const 1)))
+  (func (export "reffunc") (result i32) (ref.is_null (ref.func $a)))
+  (func (export "nullcall") (table.set $t0 (i32.const 1) (ref.null func)))
+)
+(assert_return (invoke "call" (i32.const 0)) (i32.const 10))
+(assert_return (invoke "call" (i32.const 1)) (i32.const 20))
+(assert_trap (invoke "call" (i32.const 2)) "undefined element")
+(assert_return (invoke "isnull" (ref.null extern)) (i32.const 1))
+(assert_return (invoke "isnull" (ref.extern 1)) (i32.const 0))
+(assert_return (invoke "set" (i32.const 2) (ref.extern 7)))
+(assert_return (invoke "get" (i32.const 2)) (ref.extern 7))
+(assert_return (invoke "get" (i32.const 0)) (ref.null extern))
+(assert_trap (invoke "get" (i32.const 3)) "out of bounds")
+(assert_return (invoke "grow" (i32.const 2)) (i32.const 3))
+(assert_return (invoke "size") (i32.const 5))
+(assert_return (invoke "grow" (i32.const 6)) (i32.const -1))
+(assert_return (invoke "init"))
+(assert_return (invoke "call" (i32.const 0)) (i32.const 20))
+(assert_return (invoke "call" (i32.const 1)) (i32.const 10))
+(assert_return (invoke "dropit"))
+(assert_trap (invoke "init") "out of bounds")
+(assert_return (invoke "fill" (i32.const 1) (ref.extern 5) (i32.const 3)))
+(assert_return (invoke "get" (i32.const 3)) (ref.extern 5))
+(assert_trap (invoke "fill" (i32.const 4) (ref.extern 5) (i32.const 3)) "out of bounds")
+(assert_return (invoke "copy"))
+(assert_return (invoke "call" (i32.const 1)) (i32.const 20))
+(assert_return (invoke "reffunc") (i32.const 0))
+(assert_return (invoke "nullcall"))
+(assert_trap (invoke "call" (i32.const 1)) "uninitialized element")
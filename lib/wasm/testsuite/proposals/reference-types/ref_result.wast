@@ -0,0 +1,12 @@
+(module
+  (table $t 2 externref)
+  (func (export "set") (param externref) (table.set $t (i32.const 0) (local.get 0)))
+  (func (export "get") (result externref) (table.get $t (i32.const 0)))
+  (func (export "null") (result funcref) (ref.null func))
+  (func (export "f") (result funcref) (ref.func 0))
+  (elem declare func 0)
+)
+(invoke "set" (ref.extern 7))
+(assert_return (invoke "get") (ref.extern 7))
+(assert_return (invoke "null") (ref.null func))
+(assert_return (invoke "f") (ref.func))
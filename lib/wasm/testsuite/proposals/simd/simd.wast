@@ -0,0 +1,57 @@
+(module
+  (memory 1)
+  (func (export "add") (param v128 v128) (result v128) (i32x4.add (local.get 0) (local.get 1)))
+  (func (export "shuf") (param v128 v128) (result v128) (i8x16.shuffle 0 16 1 17 2 18 3 19 4 20 5 21 6 22 7 23 (local.get 0) (local.get 1)))
+  (func (export "swz") (param v128 v128) (result v128) (i8x16.swizzle (local.get 0) (local.get 1)))
+  (func (export "extract") (param v128) (result i32) (i8x16.extract_lane_s 15 (local.get 0)))
+  (func (export "fmin") (param v128 v128) (result v128) (f32x4.min (local.get 0) (local.get 1)))
+  (func (export "satadd") (param v128 v128) (result v128) (i8x16.add_sat_u (local.get 0) (local.get 1)))
+  (func (export "ld") (result v128) (v128.store (i32.const 16) (v128.const i64x2 0x0102030405060708 0x1112131415161718)) (v128.load (i32.const 16)))
+  (func (export "ldsplat") (result v128) (i32.store8 (i32.const 0) (i32.const 0xab)) (v128.load8_splat (i32.const 0)))
+  (func (export "lane") (param v128) (result v128) (i64x2.replace_lane 1 (local.get 0) (i64.const -1)))
+  (func (export "bitmask") (param v128) (result i32) (i8x16.bitmask (local.get 0)))
+  (func (export "anytrue") (param v128) (result i32) (v128.any_true (local.get 0)))
+  (func (export "narrow") (param v128 v128) (result v128) (i8x16.narrow_i16x8_s (local.get 0) (local.get 1)))
+  (func (export "trunc") (param v128) (result v128) (i32x4.trunc_sat_f32x4_s (local.get 0)))
+  (func (export "dot") (param v128 v128) (result v128) (i32x4.dot_i16x8_s (local.get 0) (local.get 1)))
+  (func (export "q15") (param v128 v128) (result v128) (i16x8.q15mulr_sat_s (local.get 0) (local.get 1)))
+  (func (export "popcnt") (param v128) (result v128) (i8x16.popcnt (local.get 0)))
+  (func (export "shl") (param v128 i32) (result v128) (i16x8.shl (local.get 0) (local.get 1)))
+  (func (export "bsel") (param v128 v128 v128) (result v128) (v128.bitselect (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "sel") (param v128 v128 i32) (result v128) (select (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "loc") (result v128) (local v128) (local.set 0 (v128.const i32x4 1 2 3 4)) (local.get 0))
+  (func (export "blk") (param v128) (result v128) (block (result v128) (local.get 0) (br 0)))
+  (func (export "ldlane") (param v128) (result v128) (i32.store (i32.const 40) (i32.const 99)) (v128.load32_lane 2 (i32.const 40) (local.get 0)))
+  (func (export "ext") (param v128) (result v128) (i32x4.extend_high_i16x8_u (local.get 0)))
+  (func (export "fnearest") (param v128) (result v128) (f64x2.nearest (local.get 0)))
+  (global $g (mut v128) (v128.const i32x4 0 0 0 0))
+  (func (export "glob") (param v128) (result v128) (global.set $g (local.get 0)) (global.get $g))
+  (func $id (param v128) (result v128) (local.get 0))
+  (func (export "callv") (param v128) (result v128) (call $id (local.get 0)))
+)
+(assert_return (invoke "add" (v128.const i32x4 1 2 3 0xffffffff) (v128.const i32x4 10 20 30 1)) (v128.const i32x4 11 22 33 0))
+(assert_return (invoke "shuf" (v128.const i8x16 0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15) (v128.const i8x16 16 17 18 19 20 21 22 23 24 25 26 27 28 29 30 31)) (v128.const i8x16 0 16 1 17 2 18 3 19 4 20 5 21 6 22 7 23))
+(assert_return (invoke "swz" (v128.const i8x16 0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15) (v128.const i8x16 15 16 255 0 1 1 1 1 1 1 1 1 1 1 1 1)) (v128.const i8x16 15 0 0 0 1 1 1 1 1 1 1 1 1 1 1 1))
+(assert_return (invoke "extract" (v128.const i8x16 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0x80)) (i32.const -128))
+(assert_return (invoke "fmin" (v128.const f32x4 0 -0 nan 1) (v128.const f32x4 -0 0 1 2)) (v128.const f32x4 -0 -0 nan:canonical 1))
+(assert_return (invoke "satadd" (v128.const i8x16 250 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0) (v128.const i8x16 10 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0)) (v128.const i8x16 255 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0))
+(assert_return (invoke "ld") (v128.const i64x2 0x0102030405060708 0x1112131415161718))
+(assert_return (invoke "ldsplat") (v128.const i8x16 0xab 0xab 0xab 0xab 0xab 0xab 0xab 0xab 0xab 0xab 0xab 0xab 0xab 0xab 0xab 0xab))
+(assert_return (invoke "lane" (v128.const i64x2 5 6)) (v128.const i64x2 5 -1))
+(assert_return (invoke "bitmask" (v128.const i8x16 0x80 0 0x80 0 0 0 0 0 0 0 0 0 0 0 0 0xff)) (i32.const 0x8005))
+(assert_return (invoke "anytrue" (v128.const i64x2 0 0)) (i32.const 0))
+(assert_return (invoke "narrow" (v128.const i16x8 300 -300 5 0 0 0 0 0) (v128.const i16x8 0 0 0 0 0 0 0 -1)) (v128.const i8x16 127 -128 5 0 0 0 0 0 0 0 0 0 0 0 0 -1))
+(assert_return (invoke "trunc" (v128.const f32x4 nan 1e10 -1e10 -1.5)) (v128.const i32x4 0 0x7fffffff 0x80000000 -1))
+(assert_return (invoke "dot" (v128.const i16x8 1 2 3 4 -32768 -32768 0 0) (v128.const i16x8 5 6 7 8 -32768 -32768 0 0)) (v128.const i32x4 17 53 0x80000000 0))
+(assert_return (invoke "q15" (v128.const i16x8 -32768 16384 0 0 0 0 0 0) (v128.const i16x8 -32768 16384 0 0 0 0 0 0)) (v128.const i16x8 32767 8192 0 0 0 0 0 0))
+(assert_return (invoke "popcnt" (v128.const i8x16 0xff 1 3 0 0 0 0 0 0 0 0 0 0 0 0 0)) (v128.const i8x16 8 1 2 0 0 0 0 0 0 0 0 0 0 0 0 0))
+(assert_return (invoke "shl" (v128.const i16x8 1 1 1 1 1 1 1 1) (i32.const 17)) (v128.const i16x8 2 2 2 2 2 2 2 2))
+(assert_return (invoke "bsel" (v128.const i64x2 -1 -1) (v128.const i64x2 0 0) (v128.const i64x2 0xff 0)) (v128.const i64x2 0xff 0))
+(assert_return (invoke "sel" (v128.const i64x2 1 2) (v128.const i64x2 3 4) (i32.const 0)) (v128.const i64x2 3 4))
+(assert_return (invoke "loc") (v128.const i32x4 1 2 3 4))
+(assert_return (invoke "blk" (v128.const i32x4 5 6 7 8)) (v128.const i32x4 5 6 7 8))
+(assert_return (invoke "ldlane" (v128.const i32x4 1 2 3 4)) (v128.const i32x4 1 2 99 4))
+(assert_return (invoke "ext" (v128.const i16x8 0 0 0 0 -1 2 3 4)) (v128.const i32x4 65535 2 3 4))
+(assert_return (invoke "fnearest" (v128.const f64x2 2.5 -3.5)) (v128.const f64x2 2 -4))
+(assert_return (invoke "glob" (v128.const i32x4 9 8 7 6)) (v128.const i32x4 9 8 7 6))
+(assert_return (invoke "callv" (v128.const i32x4 9 8 7 6)) (v128.const i32x4 9 8 7 6))
@@ -0,0 +1,22 @@
+(module
+  (type $ii (func (param i64 i64) (result i64)))
+  (table 1 funcref)
+  (elem (i32.const 0) $fac)
+  (func $fac (param i64 i64) (result i64)
+    (if (result i64) (i64.eqz (local.get 0)) (then (local.get 1))
+      (else (return_call $fac (i64.sub (local.get 0) (i64.const 1)) (i64.mul (local.get 0) (local.get 1))))))
+  (func (export "fac") (param i64) (result i64) (call $fac (local.get 0) (i64.const 1)))
+  (func $count (param i32) (result i32)
+    (if (result i32) (i32.eqz (local.get 0)) (then (i32.const 42))
+      (else (return_call_indirect (type $cnt) (i32.sub (local.get 0) (i32.const 1)) (i32.const 0)))))
+  (type $cnt (func (param i32) (result i32)))
+  (func $cnt0 (param i32) (result i32) (local.get 0))
+  (func (export "deep") (param i32) (result i32) (call $loop (local.get 0)))
+  (func $loop (param i32) (result i32)
+    (if (result i32) (i32.eqz (local.get 0)) (then (i32.const 7))
+      (else (return_call $loop (i32.sub (local.get 0) (i32.const 1))))))
+  (func (export "badind") (result i64) (return_call_indirect (type $ii) (i64.const 1) (i64.const 2) (i32.const 5)))
+)
+(assert_return (invoke "fac" (i64.const 20)) (i64.const 2432902008176640000))
+(assert_return (invoke "deep" (i32.const 1000000)) (i32.const 7))
+(assert_trap (invoke "badind") "undefined element")
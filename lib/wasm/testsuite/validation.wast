@@ -0,0 +1,23 @@
+(assert_invalid (module (func (result i32) (i64.const 1))) "type mismatch")
+(assert_invalid (module (func (local.get 0) (drop))) "unknown local")
+(assert_invalid (module (func (global.get 0) (drop))) "unknown global")
+(assert_invalid (module (func (call 5))) "unknown function")
+(assert_invalid (module (memory 1) (func (i32.load align=8 (i32.const 0)) (drop))) "alignment")
+(assert_invalid (module (func (br 1))) "unknown label")
+(assert_invalid (module (func (result i32) (block (result i32) (i32.const 1) (i32.const 2)))) "type mismatch")
+(assert_invalid (module (global i32 (i32.const 0)) (func (global.set 0 (i32.const 1)))) "immutable")
+(assert_invalid (module (func (i32.const 1) (i32.const 2) (drop))) "type mismatch")
+(assert_invalid (module (func (result i32) (unreachable) (i64.const 0))) "type mismatch")
+(assert_invalid (module (func (i32.load (i32.const 0)) (drop))) "unknown memory")
+(assert_invalid (module (func (select (i32.const 0) (i64.const 0) (i32.const 0)) (drop))) "type mismatch")
+(assert_invalid (module (func (if (i32.const 1) (then (i32.const 1))))) "type mismatch")
+(assert_invalid (module (func (result i32) (if (result i32) (i32.const 1) (then (i32.const 1))))) "type mismatch")
+(assert_invalid (module (table 1 funcref) (func (call_indirect (type 9) (i32.const 0)))) "unknown type")
+(assert_invalid (module (func $f) (start $f) (start $f)) "multiple start")
+(assert_invalid (module (func (param i32) (result i32) (local.get 0)) (start 0)) "start function")
+(assert_invalid (module (export "a" (func 0)) (export "a" (func 0)) (func)) "duplicate export")
+(assert_invalid (module (func (result i32) (br_table 0 1 (i32.const 0) (i32.const 0)))) "type mismatch")
+(module (func (result i32) (unreachable) (i32.add)))
+(module (func (result i32) (block (result i32) (br 0 (i32.const 1)) (i64.const 0) (drop))))
+(module (func (unreachable) (select) (drop)))
+(module (func (result f64) (unreachable) (select)))
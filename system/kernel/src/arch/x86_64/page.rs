@@ -24,7 +24,9 @@ impl PageManager {
     const PAGE_KERNEL_PREFIX: usize = 0xFFFF_0000_0000_0000;
     const PAGE_RECURSIVE: usize = 0x1FE;
     const PAGE_DIRECT_MAP: usize = 0x180;
+    const PAGE_KERNEL_ALIAS: usize = 0x1FC;
     const DIRECT_BASE: usize = Self::PAGE_KERNEL_PREFIX | (Self::PAGE_DIRECT_MAP << 39);
+    const ALIAS_BASE: usize = Self::PAGE_KERNEL_PREFIX | (Self::PAGE_KERNEL_ALIAS << 39);
 
     #[inline]
     pub unsafe fn init(_info: &BootInfo) {
@@ -109,7 +111,21 @@ impl PageManager {
                 );
                 va
             }
-            // Kernel(_, _, _) => todo!(),
+            Kernel(base, len, attr) => {
+                // FFFF_FE00_0000_0000 - FFFF_FE7F_FFFF_FFFF KERNEL ALIAS AREA
+                let len = match NonZeroUsize::new(len) {
+                    Some(v) => v,
+                    None => return 0,
+                };
+                let pa = base as PhysicalAddress;
+                let va = Self::ALIAS_BASE + base;
+                Self::map(
+                    va,
+                    len,
+                    PageTableEntry::new(pa, PageAttributes::from(attr) | PageAttributes::GLOBAL),
+                );
+                va
+            }
             _ => todo!(),
         }
     }
@@ -152,6 +168,31 @@ impl PageManager {
         }
     }
 
+    /// Removes the 4K page mappings in the range; the page tables themselves are kept.
+    #[inline]
+    pub unsafe fn unmap(va: usize, len: usize) {
+        let mask_4k = Self::PAGE_SIZE_MIN - 1;
+        let count = ((len + mask_4k) & !mask_4k) / Self::PAGE_SIZE_MIN;
+        let mut va = va & !mask_4k;
+        for _ in 0..count {
+            let present = [PageLevel::Level4, PageLevel::Level3, PageLevel::Level2]
+                .iter()
+                .all(|level| level.pte_of(va).read_volatile().is_present());
+            if present
+                && !PageLevel::Level2
+                    .pte_of(va)
+                    .read_volatile()
+                    .contains(PageAttributes::LARGE)
+            {
+                PageLevel::Level1
+                    .pte_of(va)
+                    .write_volatile(PageTableEntry::empty());
+                Self::invalidate_tlb(va);
+            }
+            va += Self::PAGE_SIZE_MIN;
+        }
+    }
+
     #[inline]
     unsafe fn map_table_if_needed(va: usize, level: PageLevel, template: PageTableEntry) {
         let pte = level.pte_of(va);
@@ -194,6 +235,11 @@ impl PageManager {
         Self::DIRECT_BASE + pa as usize
     }
 
+    #[inline]
+    pub const fn kernel_alias_to_physical(va: usize) -> PhysicalAddress {
+        (va - Self::ALIAS_BASE) as PhysicalAddress
+    }
+
     #[inline]
     pub fn broadcast_invalidate_tlb() -> Result<(), ()> {
         unsafe {
@@ -1,8 +1,10 @@
 //! Peripheral Component Interconnect Bus
 
 use crate::arch::cpu::*;
+use crate::mem::mmio::Mmio;
 use crate::system::System;
-use alloc::{boxed::Box, vec::Vec};
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
+use bitflags::*;
 use core::fmt;
 // use num_derive::FromPrimitive;
 // use num_traits::FromPrimitive;
@@ -83,7 +85,7 @@ pub trait PciImpl {
 }
 
 pub trait PciDriverRegistrar {
-    fn instantiate(&self, device: &PciDevice) -> Option<usize>;
+    fn instantiate(&self, device: &PciDevice) -> Option<Arc<dyn PciDriver>>;
 }
 
 pub trait PciDriver {
@@ -95,6 +97,7 @@ static mut PCI: Pci = Pci::new();
 pub struct Pci {
     devices: Vec<PciDevice>,
     registrars: Vec<Box<dyn PciDriverRegistrar>>,
+    drivers: Vec<Arc<dyn PciDriver>>,
 }
 
 impl Pci {
@@ -102,6 +105,7 @@ impl Pci {
         Self {
             devices: Vec::new(),
             registrars: Vec::new(),
+            drivers: Vec::new(),
         }
     }
 
@@ -114,6 +118,12 @@ impl Pci {
         let shared = Self::shared();
 
         // shared.registrars.push(super::xhci::XhciRegistrar::init());
+        shared
+            .registrars
+            .push(crate::dev::ahci::AhciRegistrar::init());
+        shared
+            .registrars
+            .push(crate::dev::virtio::blk::VirtioBlkRegistrar::init());
 
         let cpu = System::current_processor();
         let bus = 0;
@@ -123,19 +133,25 @@ impl Pci {
             }
         }
 
-        // for device in &shared.devices {
-        //     for registrar in &shared.registrars {
-        //         match registrar.instantiate(&device) {
-        //             Some(_) => {}
-        //             None => {}
-        //         }
-        //     }
-        // }
+        for device in &shared.devices {
+            for device in core::iter::once(device).chain(device.functions()) {
+                for registrar in &shared.registrars {
+                    if let Some(driver) = registrar.instantiate(device) {
+                        shared.drivers.push(driver);
+                        break;
+                    }
+                }
+            }
+        }
     }
 
     pub fn devices() -> &'static [PciDevice] {
         Self::shared().devices.as_slice()
     }
+
+    pub fn drivers() -> &'static [Arc<dyn PciDriver>] {
+        Self::shared().drivers.as_slice()
+    }
 }
 
 #[repr(transparent)]
@@ -287,6 +303,103 @@ impl PciDevice {
     pub fn capabilities(&self) -> &[(PciCapabilityId, u8)] {
         self.capabilities.as_ref()
     }
+
+    /// Returns the BAR at the specified index (0 to 5) of this device.
+    pub unsafe fn bar_at(&self, index: u8) -> Option<PciBar> {
+        let mut bar = PciBar::from_raw(self.read_register(4 + index) as u64);
+        if bar.bar_type() == PciBarType::Mmio64 {
+            let high = self.read_register(5 + index) as u64;
+            bar = PciBar::from_raw(bar.0 | (high << 32));
+        }
+        self.bars
+            .iter()
+            .find(|v| v.bar_type() == bar.bar_type() && v.base() == bar.base())
+            .map(|v| *v)
+    }
+
+    /// Reads a register in the configuration space of this device.
+    #[inline]
+    pub unsafe fn read_register(&self, register: u8) -> u32 {
+        System::current_processor().read_pci(self.addr.register(register))
+    }
+
+    /// Writes a register in the configuration space of this device.
+    #[inline]
+    pub unsafe fn write_register(&self, register: u8, value: u32) {
+        System::current_processor().write_pci(self.addr.register(register), value)
+    }
+
+    /// Sets the bits of the command register.
+    #[inline]
+    pub unsafe fn set_command(&self, command: PciCommand) {
+        // The upper half is the status register and writing 1 clears its bits
+        let value = self.read_register(1) & 0xFFFF;
+        self.write_register(1, value | command.bits() as u32);
+    }
+
+    /// Routes the interrupt of this device to the handler using MSI.
+    pub unsafe fn register_msi(&self, f: fn()) -> Result<(), ()> {
+        let register = self
+            .capabilities()
+            .iter()
+            .find(|v| v.0 == PciCapabilityId::MSI)
+            .map(|v| v.1)
+            .ok_or(())?;
+        let (addr, data) = Cpu::register_msi(f)?;
+
+        let control = self.read_register(register) >> 16;
+        let is_64bit = (control & 0x0080) != 0;
+        self.write_register(register + 1, addr as u32);
+        if is_64bit {
+            self.write_register(register + 2, (addr >> 32) as u32);
+            self.write_register(register + 3, data as u32);
+        } else {
+            self.write_register(register + 2, data as u32);
+        }
+
+        // Single message, enabled
+        let control = (control & !0x0070) | 0x0001;
+        let head = self.read_register(register) & 0xFFFF;
+        self.write_register(register, head | (control << 16));
+        self.set_command(PciCommand::INTX_DISABLE);
+        Ok(())
+    }
+
+    /// Routes the interrupt of this device to the handler using the first entry of the MSI-X table.
+    pub unsafe fn register_msix(&self, f: fn()) -> Result<(), ()> {
+        let register = self
+            .capabilities()
+            .iter()
+            .find(|v| v.0 == PciCapabilityId::MSI_X)
+            .map(|v| v.1)
+            .ok_or(())?;
+        let table = self.read_register(register + 1);
+        let bar = self.bar_at((table & 0x07) as u8).ok_or(())?;
+        let mmio = Mmio::from_bar(bar).ok_or(())?;
+        let offset = (table & !0x07) as usize;
+        let (addr, data) = Cpu::register_msi(f)?;
+
+        mmio.write_u32(offset, addr as u32);
+        mmio.write_u32(offset + 4, (addr >> 32) as u32);
+        mmio.write_u32(offset + 8, data as u32);
+        mmio.write_u32(offset + 12, 0);
+
+        // Enabled, not masked
+        let head = self.read_register(register);
+        self.write_register(register, (head & !0x4000_0000) | 0x8000_0000);
+        self.set_command(PciCommand::INTX_DISABLE);
+        Ok(())
+    }
+}
+
+bitflags! {
+    /// PCI Command Register
+    pub struct PciCommand: u16 {
+        const IO_SPACE      = 0x0001;
+        const MEM_SPACE     = 0x0002;
+        const BUS_MASTER    = 0x0004;
+        const INTX_DISABLE  = 0x0400;
+    }
 }
 
 /// PCI Base Address Register
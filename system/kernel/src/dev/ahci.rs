@@ -0,0 +1,600 @@
+// Serial ATA AHCI Driver
+
+use super::block::*;
+use crate::arch::cpu::Cpu;
+use crate::arch::page::{PageManager, PhysicalAddress};
+use crate::bus::pci::*;
+use crate::mem::{mmio::Mmio, MemoryManager};
+use crate::sync::spinlock::{SpinLoopWait, Spinlock};
+use crate::task::scheduler::Timer;
+use alloc::{boxed::Box, format, sync::Arc, vec::Vec};
+use core::{
+    alloc::Layout, num::NonZeroUsize, ptr::null_mut, slice, sync::atomic::*, time::Duration,
+};
+use megstd::io;
+
+const MAX_CONTROLLERS: usize = 4;
+
+/// Controllers that the MSI handler looks for the interrupt sources
+static CONTROLLERS: [AtomicPtr<Ahci>; MAX_CONTROLLERS] = [NULL_CONTROLLER; MAX_CONTROLLERS];
+const NULL_CONTROLLER: AtomicPtr<Ahci> = AtomicPtr::new(null_mut());
+
+static NEXT_DISK_INDEX: AtomicUsize = AtomicUsize::new(0);
+
+pub struct AhciRegistrar();
+
+impl AhciRegistrar {
+    const PREFERRED_CLASS: PciClass = PciClass::code(0x01).sub(0x06).interface(0x01);
+
+    pub fn init() -> Box<dyn PciDriverRegistrar> {
+        Box::new(Self())
+    }
+}
+
+impl PciDriverRegistrar for AhciRegistrar {
+    fn instantiate(&self, device: &PciDevice) -> Option<Arc<dyn PciDriver>> {
+        if device.class_code().matches(Self::PREFERRED_CLASS) {
+            unsafe { Ahci::new(device).map(|v| v as Arc<dyn PciDriver>) }
+        } else {
+            None
+        }
+    }
+}
+
+/// AHCI Host Bus Adapter
+pub struct Ahci {
+    mmio: Mmio,
+    ports: Vec<Arc<AhciPort>>,
+}
+
+impl Ahci {
+    const REG_CAP: usize = 0x00;
+    const REG_GHC: usize = 0x04;
+    const REG_IS: usize = 0x08;
+    const REG_PI: usize = 0x0C;
+    const REG_CAP2: usize = 0x24;
+    const REG_BOHC: usize = 0x28;
+
+    const CAP_S64A: u32 = 0x8000_0000;
+    const CAP2_BOH: u32 = 0x0000_0001;
+    const GHC_AE: u32 = 0x8000_0000;
+    const GHC_IE: u32 = 0x0000_0002;
+    const BOHC_BOS: u32 = 0x0000_0001;
+    const BOHC_OOS: u32 = 0x0000_0002;
+
+    unsafe fn new(device: &PciDevice) -> Option<Arc<Self>> {
+        // ABAR is BAR5
+        let mmio = Mmio::from_bar(device.bar_at(5)?)?;
+        device.set_command(PciCommand::MEM_SPACE | PciCommand::BUS_MASTER);
+
+        // Take the ownership from the firmware
+        if (mmio.read_u32(Self::REG_CAP2) & Self::CAP2_BOH) != 0 {
+            let bohc = mmio.read_u32(Self::REG_BOHC);
+            mmio.write_u32(Self::REG_BOHC, bohc | Self::BOHC_OOS);
+            wait_for(Duration::from_millis(2000), || {
+                (mmio.read_u32(Self::REG_BOHC) & Self::BOHC_BOS) == 0
+            });
+        }
+
+        mmio.write_u32(Self::REG_GHC, Self::GHC_AE);
+        let cap = mmio.read_u32(Self::REG_CAP);
+        let is_64bit = (cap & Self::CAP_S64A) != 0;
+        let n_slots = usize::min(((cap >> 8) & 0x1F) as usize + 1, AhciPort::MAX_SLOTS);
+        let ports_implemented = mmio.read_u32(Self::REG_PI);
+
+        let mut ports = Vec::new();
+        for index in 0..32 {
+            if (ports_implemented & (1 << index)) != 0 {
+                if let Some(port) = AhciPort::new(mmio, index, n_slots, is_64bit) {
+                    ports.push(Arc::new(port));
+                }
+            }
+        }
+
+        let ahci = Arc::new(Self { mmio, ports });
+
+        let ptr = Arc::as_ptr(&ahci) as *mut Ahci;
+        let is_registered = CONTROLLERS.iter().any(|v| {
+            v.compare_exchange(null_mut(), ptr, Ordering::SeqCst, Ordering::Relaxed)
+                .is_ok()
+        });
+        if is_registered && device.register_msi(Self::handle_msi).is_ok() {
+            mmio.write_u32(Self::REG_IS, u32::MAX);
+            mmio.write_u32(Self::REG_GHC, Self::GHC_AE | Self::GHC_IE);
+            for port in &ahci.ports {
+                port.enable_interrupt();
+            }
+        }
+
+        for port in &ahci.ports {
+            let name = format!("sata{}", NEXT_DISK_INDEX.fetch_add(1, Ordering::SeqCst));
+            BlockDeviceManager::register(name, port.clone());
+        }
+
+        Some(ahci)
+    }
+
+    /// MSI handler shared by all controllers
+    fn handle_msi() {
+        for controller in &CONTROLLERS {
+            let ptr = controller.load(Ordering::SeqCst);
+            if let Some(ahci) = unsafe { ptr.as_ref() } {
+                unsafe { ahci.handle_irq() };
+            }
+        }
+    }
+
+    unsafe fn handle_irq(&self) {
+        let is = self.mmio.read_u32(Self::REG_IS);
+        if is == 0 {
+            return;
+        }
+        for port in &self.ports {
+            if (is & (1 << port.index)) != 0 {
+                port.update();
+            }
+        }
+        self.mmio.write_u32(Self::REG_IS, is);
+    }
+}
+
+impl PciDriver for Ahci {
+    fn name<'a>(&self) -> &'a str {
+        "AHCI"
+    }
+}
+
+/// A port of the HBA that a SATA disk is attached to
+struct AhciPort {
+    mmio: Mmio,
+    index: usize,
+    /// Command list, received FIS and command tables
+    mem: Mmio,
+    /// A bounce buffer for each slot
+    buffers: usize,
+    buffers_pa: PhysicalAddress,
+    n_slots: usize,
+    has_interrupt: AtomicBool,
+    slots: BlockSlots,
+    lock: Spinlock,
+    sector_size: usize,
+    total_sectors: u64,
+}
+
+impl AhciPort {
+    const MAX_SLOTS: usize = 8;
+    const BUFFER_SIZE: usize = 0x1_0000;
+
+    const OFFSET_RECEIVED_FIS: usize = 0x400;
+    const OFFSET_COMMAND_TABLES: usize = 0x800;
+    const COMMAND_TABLE_SIZE: usize = 0x100;
+    const MEM_SIZE: usize =
+        Self::OFFSET_COMMAND_TABLES + Self::COMMAND_TABLE_SIZE * Self::MAX_SLOTS;
+
+    const REG_CLB: usize = 0x00;
+    const REG_FB: usize = 0x08;
+    const REG_IS: usize = 0x10;
+    const REG_IE: usize = 0x14;
+    const REG_CMD: usize = 0x18;
+    const REG_TFD: usize = 0x20;
+    const REG_SIG: usize = 0x24;
+    const REG_SSTS: usize = 0x28;
+    const REG_SERR: usize = 0x30;
+    const REG_CI: usize = 0x38;
+
+    const CMD_ST: u32 = 0x0000_0001;
+    const CMD_FRE: u32 = 0x0000_0010;
+    const CMD_FR: u32 = 0x0000_4000;
+    const CMD_CR: u32 = 0x0000_8000;
+    const TFD_ERR: u32 = 0x01;
+    const TFD_DRQ: u32 = 0x08;
+    const TFD_BSY: u32 = 0x80;
+    /// Device to host register FIS interrupt
+    const IS_DHRS: u32 = 0x0000_0001;
+    /// Task file error, host bus data error, host bus fatal error and interface fatal error
+    const IS_ERRORS: u32 = 0x7800_0000;
+
+    const SIG_ATA: u32 = 0x0000_0101;
+    const SSTS_DET_PRESENT: u32 = 0x3;
+
+    const ATA_READ_DMA_EXT: u8 = 0x25;
+    const ATA_WRITE_DMA_EXT: u8 = 0x35;
+    const ATA_IDENTIFY_DEVICE: u8 = 0xEC;
+
+    unsafe fn new(hba: Mmio, index: usize, n_slots: usize, is_64bit: bool) -> Option<Self> {
+        let mmio = Mmio::from_virt(NonZeroUsize::new(hba.base() + 0x100 + index * 0x80)?, 0x80);
+        if (mmio.read_u32(Self::REG_SSTS) & 0x0F) != Self::SSTS_DET_PRESENT
+            || mmio.read_u32(Self::REG_SIG) != Self::SIG_ATA
+        {
+            return None;
+        }
+
+        let page_size = MemoryManager::PAGE_SIZE_MIN;
+        let buffers_size = Self::BUFFER_SIZE * n_slots;
+        let mem_layout = Layout::from_size_align(Self::MEM_SIZE, page_size).ok()?;
+        let buffers_layout = Layout::from_size_align(buffers_size, page_size).ok()?;
+        let mem_pa = MemoryManager::pg_alloc(mem_layout)?.get() as PhysicalAddress;
+        let buffers_pa = match MemoryManager::pg_alloc(buffers_layout) {
+            Some(v) => v.get() as PhysicalAddress,
+            None => {
+                Self::dealloc(mem_pa, mem_layout);
+                return None;
+            }
+        };
+        let free_memory = || {
+            Self::dealloc(mem_pa, mem_layout);
+            Self::dealloc(buffers_pa, buffers_layout);
+        };
+        let limit = u32::MAX as PhysicalAddress;
+        if !is_64bit && (mem_pa > limit || buffers_pa + buffers_size as PhysicalAddress > limit) {
+            free_memory();
+            return None;
+        }
+        let mem = match NonZeroUsize::new(PageManager::direct_map(mem_pa)) {
+            Some(v) => Mmio::from_virt(v, Self::MEM_SIZE),
+            None => {
+                free_memory();
+                return None;
+            }
+        };
+        (mem.base() as *mut u8).write_bytes(0, Self::MEM_SIZE);
+
+        let mut port = Self {
+            mmio,
+            index,
+            mem,
+            buffers: PageManager::direct_map(buffers_pa),
+            buffers_pa,
+            n_slots,
+            has_interrupt: AtomicBool::new(false),
+            slots: BlockSlots::new(n_slots),
+            lock: Spinlock::new(),
+            sector_size: 512,
+            total_sectors: 0,
+        };
+
+        if !port.stop() {
+            // The HBA does not know the memory yet
+            free_memory();
+            return None;
+        }
+        for slot in 0..n_slots {
+            let header = port.command_header(slot);
+            header.ctba =
+                mem_pa + (Self::OFFSET_COMMAND_TABLES + Self::COMMAND_TABLE_SIZE * slot) as u64;
+        }
+        port.write_reg_u64(Self::REG_CLB, mem_pa);
+        port.write_reg_u64(Self::REG_FB, mem_pa + Self::OFFSET_RECEIVED_FIS as u64);
+        port.start();
+
+        if port.identify().is_none() {
+            // The memory is leaked if the HBA does not stop, as it may still write to it
+            if port.stop() {
+                free_memory();
+            }
+            return None;
+        }
+        Some(port)
+    }
+
+    #[inline]
+    unsafe fn dealloc(pa: PhysicalAddress, layout: Layout) {
+        if let Some(base) = NonZeroUsize::new(pa as usize) {
+            let _ = MemoryManager::pg_dealloc(base, layout);
+        }
+    }
+
+    #[inline]
+    unsafe fn read_reg(&self, register: usize) -> u32 {
+        self.mmio.read_u32(register)
+    }
+
+    #[inline]
+    unsafe fn write_reg(&self, register: usize, value: u32) {
+        self.mmio.write_u32(register, value)
+    }
+
+    #[inline]
+    unsafe fn write_reg_u64(&self, register: usize, value: u64) {
+        self.write_reg(register, value as u32);
+        self.write_reg(register + 4, (value >> 32) as u32);
+    }
+
+    /// Stops the command list and FIS receive engines
+    unsafe fn stop(&self) -> bool {
+        let cmd = self.read_reg(Self::REG_CMD);
+        self.write_reg(Self::REG_CMD, cmd & !Self::CMD_ST);
+        if !wait_for(Duration::from_millis(500), || {
+            (self.read_reg(Self::REG_CMD) & Self::CMD_CR) == 0
+        }) {
+            return false;
+        }
+        let cmd = self.read_reg(Self::REG_CMD);
+        self.write_reg(Self::REG_CMD, cmd & !Self::CMD_FRE);
+        wait_for(Duration::from_millis(500), || {
+            (self.read_reg(Self::REG_CMD) & Self::CMD_FR) == 0
+        })
+    }
+
+    /// Clears the errors and starts the engines
+    unsafe fn start(&self) {
+        self.write_reg(Self::REG_SERR, u32::MAX);
+        self.write_reg(Self::REG_IS, u32::MAX);
+        let cmd = self.read_reg(Self::REG_CMD);
+        self.write_reg(Self::REG_CMD, cmd | Self::CMD_FRE);
+        wait_for(Duration::from_millis(1000), || {
+            (self.read_reg(Self::REG_TFD) & (Self::TFD_BSY | Self::TFD_DRQ)) == 0
+        });
+        let cmd = self.read_reg(Self::REG_CMD);
+        self.write_reg(Self::REG_CMD, cmd | Self::CMD_ST);
+    }
+
+    unsafe fn enable_interrupt(&self) {
+        self.has_interrupt.store(true, Ordering::SeqCst);
+        self.write_reg(Self::REG_IS, u32::MAX);
+        self.write_reg(Self::REG_IE, Self::IS_DHRS | Self::IS_ERRORS);
+    }
+
+    /// Identifies the device by polling, as interrupts are not available yet
+    unsafe fn identify(&mut self) -> Option<()> {
+        let slot = self.slots.try_alloc()?;
+        let index = slot.index();
+        self.issue(index, Self::ATA_IDENTIFY_DEVICE, 0, 0, 512, false);
+        wait_for(Duration::from_millis(1000), || {
+            (self.read_reg(Self::REG_CI) & (1 << index)) == 0
+                || (self.read_reg(Self::REG_IS) & Self::IS_ERRORS) != 0
+        });
+        let is_ok = (self.read_reg(Self::REG_CI) & (1 << index)) == 0
+            && (self.read_reg(Self::REG_TFD) & Self::TFD_ERR) == 0;
+        self.slots.complete(index, is_ok);
+        drop(slot);
+        if !is_ok {
+            let _ = self.stop();
+            return None;
+        }
+
+        let mut data = [0u8; 512];
+        data.copy_from_slice(&self.buffer(index)[..512]);
+        let word = |index: usize| u16::from_le_bytes([data[index * 2], data[index * 2 + 1]]);
+
+        // Only the devices that support 48-bit addresses are supported
+        if (word(83) & 0x0400) == 0 {
+            let _ = self.stop();
+            return None;
+        }
+        self.total_sectors = (0..4).fold(0, |acc, i| acc | ((word(100 + i) as u64) << (i * 16)));
+        let w106 = word(106);
+        if (w106 & 0xC000) == 0x4000 && (w106 & 0x1000) != 0 {
+            let words = word(117) as usize | ((word(118) as usize) << 16);
+            self.sector_size = words * 2;
+        }
+        if self.sector_size == 0 || Self::BUFFER_SIZE % self.sector_size != 0 {
+            let _ = self.stop();
+            return None;
+        }
+        Some(())
+    }
+
+    #[inline]
+    unsafe fn command_header(&self, slot: usize) -> &mut AhciCommandHeader {
+        self.mem.transmute_mut(slot * 32)
+    }
+
+    #[inline]
+    unsafe fn command_table(&self, slot: usize) -> &mut AhciCommandTable {
+        self.mem
+            .transmute_mut(Self::OFFSET_COMMAND_TABLES + Self::COMMAND_TABLE_SIZE * slot)
+    }
+
+    #[inline]
+    unsafe fn buffer(&self, slot: usize) -> &mut [u8] {
+        slice::from_raw_parts_mut(
+            (self.buffers + Self::BUFFER_SIZE * slot) as *mut u8,
+            Self::BUFFER_SIZE,
+        )
+    }
+
+    /// Builds the command in the slot and issues it
+    unsafe fn issue(
+        &self,
+        slot: usize,
+        command: u8,
+        lba: u64,
+        count: usize,
+        len: usize,
+        is_write: bool,
+    ) {
+        let header = self.command_header(slot);
+        header.flags = (AhciCommandTable::CFIS_LEN / 4) as u16 | if is_write { 0x40 } else { 0 };
+        header.prdtl = if len > 0 { 1 } else { 0 };
+        header.prdbc = 0;
+
+        let table = self.command_table(slot);
+        table.cfis = [0; 64];
+        let fis = &mut table.cfis;
+        fis[0] = 0x27; // Register FIS - host to device
+        fis[1] = 0x80; // Command
+        fis[2] = command;
+        fis[4] = lba as u8;
+        fis[5] = (lba >> 8) as u8;
+        fis[6] = (lba >> 16) as u8;
+        fis[7] = 0x40; // LBA mode
+        fis[8] = (lba >> 24) as u8;
+        fis[9] = (lba >> 32) as u8;
+        fis[10] = (lba >> 40) as u8;
+        fis[12] = count as u8;
+        fis[13] = (count >> 8) as u8;
+        table.prdt = AhciPrd {
+            dba: self.buffers_pa + (Self::BUFFER_SIZE * slot) as u64,
+            reserved: 0,
+            dbc: (len as u32).wrapping_sub(1) & 0x003F_FFFF,
+        };
+
+        Cpu::without_interrupts(|| {
+            self.lock.synchronized(|| {
+                self.slots.issue(slot);
+                self.write_reg(Self::REG_CI, 1 << slot);
+            })
+        });
+    }
+
+    /// Completes the finished commands and recovers from errors
+    unsafe fn update(&self) {
+        Cpu::without_interrupts(|| {
+            self.lock.synchronized(|| {
+                let is = self.read_reg(Self::REG_IS);
+                self.write_reg(Self::REG_IS, is);
+                let issued = self.slots.issued();
+                let mut running = self.read_reg(Self::REG_CI);
+                let mut failed = 0;
+                if (is & Self::IS_ERRORS) != 0 {
+                    failed = issued & running;
+                    if self.stop() {
+                        self.start();
+                    }
+                    running = 0;
+                }
+
+                let done = issued & !running;
+                for slot in 0..self.n_slots {
+                    let bit = 1 << slot;
+                    if (done & bit) != 0 {
+                        self.slots.complete(slot, (failed & bit) == 0);
+                    }
+                }
+            })
+        })
+    }
+
+    /// Returns the function that completes the commands if interrupts are not available
+    fn poller(&self) -> Option<impl Fn() + '_> {
+        (!self.has_interrupt.load(Ordering::SeqCst)).then(move || move || unsafe { self.update() })
+    }
+
+    fn check_range(&self, lba: u64, len: usize) -> io::Result<usize> {
+        let count = len / self.sector_size;
+        if len % self.sector_size != 0 {
+            return Err(io::ErrorKind::InvalidInput.into());
+        }
+        match lba.checked_add(count as u64) {
+            Some(end) if end <= self.total_sectors => Ok(count),
+            _ => Err(io::ErrorKind::InvalidInput.into()),
+        }
+    }
+
+    async fn read(&self, mut lba: u64, buf: &mut [u8]) -> io::Result<()> {
+        self.check_range(lba, buf.len())?;
+        for chunk in buf.chunks_mut(Self::BUFFER_SIZE) {
+            let count = chunk.len() / self.sector_size;
+            let slot = self.slots.alloc().await;
+            unsafe {
+                self.issue(
+                    slot.index(),
+                    Self::ATA_READ_DMA_EXT,
+                    lba,
+                    count,
+                    chunk.len(),
+                    false,
+                );
+            }
+            slot.wait(self.poller()).await?;
+            chunk.copy_from_slice(unsafe { &self.buffer(slot.index())[..chunk.len()] });
+            lba += count as u64;
+        }
+        Ok(())
+    }
+
+    async fn write(&self, mut lba: u64, buf: &[u8]) -> io::Result<()> {
+        self.check_range(lba, buf.len())?;
+        for chunk in buf.chunks(Self::BUFFER_SIZE) {
+            let count = chunk.len() / self.sector_size;
+            let slot = self.slots.alloc().await;
+            unsafe {
+                self.buffer(slot.index())[..chunk.len()].copy_from_slice(chunk);
+                self.issue(
+                    slot.index(),
+                    Self::ATA_WRITE_DMA_EXT,
+                    lba,
+                    count,
+                    chunk.len(),
+                    true,
+                );
+            }
+            slot.wait(self.poller()).await?;
+            lba += count as u64;
+        }
+        Ok(())
+    }
+}
+
+impl BlockDevice for AhciPort {
+    #[inline]
+    fn sector_size(&self) -> usize {
+        self.sector_size
+    }
+
+    #[inline]
+    fn total_sectors(&self) -> u64 {
+        self.total_sectors
+    }
+
+    fn read_sectors_async<'a>(&'a self, lba: u64, buf: &'a mut [u8]) -> BlockFuture<'a> {
+        Box::pin(self.read(lba, buf))
+    }
+
+    fn write_sectors_async<'a>(&'a self, lba: u64, buf: &'a [u8]) -> BlockFuture<'a> {
+        Box::pin(self.write(lba, buf))
+    }
+}
+
+#[repr(C)]
+#[allow(dead_code)]
+struct AhciCommandHeader {
+    flags: u16,
+    /// Physical region descriptor table length
+    prdtl: u16,
+    /// Physical region descriptor byte count
+    prdbc: u32,
+    /// Command table base address
+    ctba: u64,
+    _reserved: [u32; 4],
+}
+
+#[repr(C)]
+#[allow(dead_code)]
+struct AhciCommandTable {
+    cfis: [u8; 64],
+    acmd: [u8; 16],
+    _reserved: [u8; 48],
+    prdt: AhciPrd,
+}
+
+impl AhciCommandTable {
+    /// Length of the register FIS
+    const CFIS_LEN: usize = 20;
+}
+
+/// Physical region descriptor
+#[repr(C)]
+#[allow(dead_code)]
+struct AhciPrd {
+    dba: u64,
+    reserved: u32,
+    dbc: u32,
+}
+
+/// Spins until the condition is met or the time is up
+fn wait_for<F>(duration: Duration, mut f: F) -> bool
+where
+    F: FnMut() -> bool,
+{
+    let mut spin_loop = SpinLoopWait::new();
+    let deadline = Timer::new(duration);
+    while deadline.until() {
+        if f() {
+            return true;
+        }
+        spin_loop.wait();
+    }
+    f()
+}
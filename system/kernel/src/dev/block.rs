@@ -0,0 +1,570 @@
+// Block Device
+
+use crate::sync::Mutex;
+use crate::task::executor::Executor;
+use alloc::{boxed::Box, collections::BTreeMap, format, string::String, sync::Arc, vec::Vec};
+use byteorder::*;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicU32, Ordering};
+use core::task::{Context, Poll};
+use futures_util::task::AtomicWaker;
+use megstd::io;
+
+/// The result of an asynchronous sector transfer
+pub type BlockFuture<'a> = Pin<Box<dyn Future<Output = io::Result<()>> + 'a>>;
+
+/// A device that is read and written in units of fixed-size sectors
+pub trait BlockDevice {
+    /// Returns the size of a sector in bytes
+    fn sector_size(&self) -> usize;
+
+    /// Returns the total number of sectors
+    fn total_sectors(&self) -> u64;
+
+    #[inline]
+    fn is_read_only(&self) -> bool {
+        false
+    }
+
+    /// Reads the sectors starting at `lba`; the length of `buf` must be a multiple of the sector size
+    fn read_sectors_async<'a>(&'a self, lba: u64, buf: &'a mut [u8]) -> BlockFuture<'a>;
+
+    /// Writes the sectors starting at `lba`; the length of `buf` must be a multiple of the sector size
+    fn write_sectors_async<'a>(&'a self, lba: u64, buf: &'a [u8]) -> BlockFuture<'a>;
+
+    /// Reads the sectors and blocks the current thread until the transfer is complete
+    #[inline]
+    fn read_sectors(&self, lba: u64, buf: &mut [u8]) -> io::Result<()> {
+        Executor::block_on(self.read_sectors_async(lba, buf))
+    }
+
+    /// Writes the sectors and blocks the current thread until the transfer is complete
+    #[inline]
+    fn write_sectors(&self, lba: u64, buf: &[u8]) -> io::Result<()> {
+        Executor::block_on(self.write_sectors_async(lba, buf))
+    }
+}
+
+/// A range of sectors on another block device, such as a partition
+pub struct BlockPartition {
+    dev: Arc<dyn BlockDevice>,
+    start: u64,
+    len: u64,
+}
+
+impl BlockPartition {
+    const MBR_SIZE: usize = 512;
+    const MBR_SIGNATURE: [u8; 2] = [0x55, 0xAA];
+    const MBR_OFFSET_ENTRIES: usize = 446;
+    const MBR_ENTRY_SIZE: usize = 16;
+    const MBR_N_ENTRIES: usize = 4;
+    const MBR_TYPE_GPT_PROTECTIVE: u8 = 0xEE;
+    const MBR_TYPES_EXTENDED: [u8; 3] = [0x05, 0x0F, 0x85];
+
+    const GPT_SIGNATURE: &'static [u8; 8] = b"EFI PART";
+    const GPT_MIN_ENTRY_SIZE: usize = 128;
+    const GPT_MAX_ENTRIES: usize = 256;
+
+    #[inline]
+    pub fn new(dev: Arc<dyn BlockDevice>, start: u64, len: u64) -> Option<Self> {
+        let end = start.checked_add(len)?;
+        (end <= dev.total_sectors()).then(|| Self { dev, start, len })
+    }
+
+    /// Finds the partitions in the GPT or the MBR of the device and returns them with their numbers
+    ///
+    /// Logical partitions in an extended partition of the MBR are not supported.
+    pub fn scan(dev: &Arc<dyn BlockDevice>) -> Vec<(usize, Self)> {
+        let sector_size = dev.sector_size();
+        if sector_size < Self::MBR_SIZE {
+            return Vec::new();
+        }
+        let mut mbr = Vec::new();
+        mbr.resize(sector_size, 0);
+        if dev.read_sectors(0, &mut mbr).is_err()
+            || mbr[Self::MBR_SIZE - 2..Self::MBR_SIZE] != Self::MBR_SIGNATURE
+        {
+            return Vec::new();
+        }
+
+        let entries = (0..Self::MBR_N_ENTRIES).map(|index| {
+            let entry = &mbr[Self::MBR_OFFSET_ENTRIES + Self::MBR_ENTRY_SIZE * index..]
+                [..Self::MBR_ENTRY_SIZE];
+            (
+                index + 1,
+                entry[4],
+                LE::read_u32(&entry[8..12]) as u64,
+                LE::read_u32(&entry[12..16]) as u64,
+            )
+        });
+        if entries
+            .clone()
+            .any(|(_, v, _, _)| v == Self::MBR_TYPE_GPT_PROTECTIVE)
+        {
+            return Self::scan_gpt(dev).unwrap_or_default();
+        }
+        entries
+            .filter(|(_, v, _, len)| *v != 0 && *len != 0 && !Self::MBR_TYPES_EXTENDED.contains(v))
+            .filter_map(|(number, _, start, len)| {
+                Self::new(dev.clone(), start, len).map(|v| (number, v))
+            })
+            .collect()
+    }
+
+    fn scan_gpt(dev: &Arc<dyn BlockDevice>) -> Option<Vec<(usize, Self)>> {
+        let sector_size = dev.sector_size();
+        let mut header = Vec::new();
+        header.resize(sector_size, 0);
+        dev.read_sectors(1, &mut header).ok()?;
+        if &header[0..8] != Self::GPT_SIGNATURE {
+            return None;
+        }
+        let entries_lba = LE::read_u64(&header[72..80]);
+        let n_entries = usize::min(
+            LE::read_u32(&header[80..84]) as usize,
+            Self::GPT_MAX_ENTRIES,
+        );
+        let entry_size = LE::read_u32(&header[84..88]) as usize;
+        if entry_size < Self::GPT_MIN_ENTRY_SIZE || entry_size > sector_size {
+            return None;
+        }
+
+        let n_sectors = (n_entries * entry_size + sector_size - 1) / sector_size;
+        let mut entries = Vec::new();
+        entries.resize(n_sectors * sector_size, 0);
+        dev.read_sectors(entries_lba, &mut entries).ok()?;
+        let result = entries
+            .chunks_exact(entry_size)
+            .take(n_entries)
+            .enumerate()
+            // An entry whose type is all zero is unused
+            .filter(|(_, entry)| entry[0..16].iter().any(|v| *v != 0))
+            .filter_map(|(index, entry)| {
+                let first = LE::read_u64(&entry[32..40]);
+                let last = LE::read_u64(&entry[40..48]);
+                let len = last.checked_sub(first)?.checked_add(1)?;
+                Self::new(dev.clone(), first, len).map(|v| (index + 1, v))
+            })
+            .collect();
+        Some(result)
+    }
+
+    #[inline]
+    fn check_range(&self, lba: u64, len: usize) -> io::Result<u64> {
+        let count = (len / self.sector_size()) as u64;
+        match lba.checked_add(count) {
+            Some(end) if end <= self.len => Ok(self.start + lba),
+            _ => Err(io::ErrorKind::InvalidInput.into()),
+        }
+    }
+}
+
+impl BlockDevice for BlockPartition {
+    #[inline]
+    fn sector_size(&self) -> usize {
+        self.dev.sector_size()
+    }
+
+    #[inline]
+    fn total_sectors(&self) -> u64 {
+        self.len
+    }
+
+    #[inline]
+    fn is_read_only(&self) -> bool {
+        self.dev.is_read_only()
+    }
+
+    fn read_sectors_async<'a>(&'a self, lba: u64, buf: &'a mut [u8]) -> BlockFuture<'a> {
+        match self.check_range(lba, buf.len()) {
+            Ok(lba) => self.dev.read_sectors_async(lba, buf),
+            Err(err) => Box::pin(async { Err(err) }),
+        }
+    }
+
+    fn write_sectors_async<'a>(&'a self, lba: u64, buf: &'a [u8]) -> BlockFuture<'a> {
+        match self.check_range(lba, buf.len()) {
+            Ok(lba) => self.dev.write_sectors_async(lba, buf),
+            Err(err) => Box::pin(async { Err(err) }),
+        }
+    }
+}
+
+/// A write-through cache that keeps recently used sectors of another block device
+pub struct BlockCache {
+    dev: Arc<dyn BlockDevice>,
+    capacity: usize,
+    inner: Mutex<BlockCacheInner>,
+}
+
+struct BlockCacheInner {
+    /// Sector data and the tick of the last use, by LBA
+    sectors: BTreeMap<u64, (u64, Box<[u8]>)>,
+    tick: u64,
+    /// Incremented on every write so that a read racing with it does not cache stale data
+    generation: u64,
+}
+
+impl BlockCache {
+    pub const DEFAULT_CAPACITY: usize = 256;
+
+    #[inline]
+    pub fn new(dev: Arc<dyn BlockDevice>, capacity: usize) -> Self {
+        Self {
+            dev,
+            capacity,
+            inner: Mutex::new(BlockCacheInner {
+                sectors: BTreeMap::new(),
+                tick: 0,
+                generation: 0,
+            }),
+        }
+    }
+
+    async fn read(&self, lba: u64, buf: &mut [u8]) -> io::Result<()> {
+        let sector_size = self.sector_size();
+        let count = buf.len() / sector_size;
+        let mut index = 0;
+        while index < count {
+            let generation = {
+                let mut inner = self.inner.lock().unwrap();
+                if inner.get(
+                    lba + index as u64,
+                    &mut buf[index * sector_size..][..sector_size],
+                ) {
+                    index += 1;
+                    continue;
+                }
+                inner.generation
+            };
+
+            // Read the run of missing sectors at once
+            let mut end = index + 1;
+            {
+                let inner = self.inner.lock().unwrap();
+                while end < count && !inner.sectors.contains_key(&(lba + end as u64)) {
+                    end += 1;
+                }
+            }
+            let run = &mut buf[index * sector_size..end * sector_size];
+            self.dev.read_sectors_async(lba + index as u64, run).await?;
+
+            let mut inner = self.inner.lock().unwrap();
+            if inner.generation == generation && end - index <= self.capacity {
+                for (i, chunk) in run.chunks(sector_size).enumerate() {
+                    inner.insert(lba + (index + i) as u64, chunk, self.capacity);
+                }
+            }
+            index = end;
+        }
+        Ok(())
+    }
+
+    async fn write(&self, lba: u64, buf: &[u8]) -> io::Result<()> {
+        let sector_size = self.sector_size();
+        {
+            let mut inner = self.inner.lock().unwrap();
+            inner.generation += 1;
+            for i in 0..(buf.len() / sector_size) as u64 {
+                inner.sectors.remove(&(lba + i));
+            }
+        }
+        self.dev.write_sectors_async(lba, buf).await?;
+
+        let mut inner = self.inner.lock().unwrap();
+        inner.generation += 1;
+        if buf.len() / sector_size <= self.capacity {
+            for (i, chunk) in buf.chunks(sector_size).enumerate() {
+                inner.insert(lba + i as u64, chunk, self.capacity);
+            }
+        }
+        Ok(())
+    }
+
+    /// Discards all cached sectors
+    pub fn invalidate(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.generation += 1;
+        inner.sectors.clear();
+    }
+}
+
+impl BlockCacheInner {
+    fn get(&mut self, lba: u64, buf: &mut [u8]) -> bool {
+        self.tick += 1;
+        let tick = self.tick;
+        match self.sectors.get_mut(&lba) {
+            Some(entry) => {
+                entry.0 = tick;
+                buf.copy_from_slice(&entry.1);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn insert(&mut self, lba: u64, data: &[u8], capacity: usize) {
+        self.tick += 1;
+        let tick = self.tick;
+        if let Some(entry) = self.sectors.get_mut(&lba) {
+            entry.0 = tick;
+            entry.1.copy_from_slice(data);
+            return;
+        }
+        if self.sectors.len() >= capacity {
+            let lru = self
+                .sectors
+                .iter()
+                .min_by_key(|(_, v)| v.0)
+                .map(|(k, _)| *k);
+            if let Some(lru) = lru {
+                self.sectors.remove(&lru);
+            }
+        }
+        self.sectors.insert(lba, (tick, data.into()));
+    }
+}
+
+impl BlockDevice for BlockCache {
+    #[inline]
+    fn sector_size(&self) -> usize {
+        self.dev.sector_size()
+    }
+
+    #[inline]
+    fn total_sectors(&self) -> u64 {
+        self.dev.total_sectors()
+    }
+
+    #[inline]
+    fn is_read_only(&self) -> bool {
+        self.dev.is_read_only()
+    }
+
+    fn read_sectors_async<'a>(&'a self, lba: u64, buf: &'a mut [u8]) -> BlockFuture<'a> {
+        Box::pin(self.read(lba, buf))
+    }
+
+    fn write_sectors_async<'a>(&'a self, lba: u64, buf: &'a [u8]) -> BlockFuture<'a> {
+        Box::pin(self.write(lba, buf))
+    }
+}
+
+/// Command slots of a device that runs several commands at once
+pub struct BlockSlots {
+    /// Slots that are not in use
+    free: AtomicU32,
+    /// Slots whose commands are running
+    issued: AtomicU32,
+    /// Slots whose commands have failed
+    failed: AtomicU32,
+    /// Slots whose owners have gone before their commands finished
+    orphaned: AtomicU32,
+    wakers: Box<[AtomicWaker]>,
+}
+
+impl BlockSlots {
+    pub const MAX_SLOTS: usize = 32;
+
+    pub fn new(n_slots: usize) -> Self {
+        let n_slots = usize::min(n_slots, Self::MAX_SLOTS);
+        let mut wakers = Vec::with_capacity(n_slots);
+        wakers.resize_with(n_slots, AtomicWaker::new);
+        Self {
+            free: AtomicU32::new(((1u64 << n_slots) - 1) as u32),
+            issued: AtomicU32::new(0),
+            failed: AtomicU32::new(0),
+            orphaned: AtomicU32::new(0),
+            wakers: wakers.into_boxed_slice(),
+        }
+    }
+
+    /// Returns the slots whose commands are running
+    #[inline]
+    pub fn issued(&self) -> u32 {
+        self.issued.load(Ordering::SeqCst)
+    }
+
+    pub fn try_alloc(&self) -> Option<BlockSlot> {
+        self.free
+            .fetch_update(Ordering::SeqCst, Ordering::Relaxed, |v| {
+                (v != 0).then(|| v & (v - 1))
+            })
+            .ok()
+            .map(|v| BlockSlot {
+                slots: self,
+                index: v.trailing_zeros() as usize,
+            })
+    }
+
+    /// Waits for a free slot
+    #[inline]
+    pub fn alloc(&self) -> BlockSlotFuture {
+        BlockSlotFuture { slots: self }
+    }
+
+    /// Marks the command in the slot as running; the driver calls this just before it starts the command
+    pub fn issue(&self, slot: usize) {
+        let bit = 1 << slot;
+        self.failed.fetch_and(!bit, Ordering::SeqCst);
+        self.issued.fetch_or(bit, Ordering::SeqCst);
+    }
+
+    /// Marks the command in the slot as finished and wakes up its owner
+    pub fn complete(&self, slot: usize, is_ok: bool) {
+        let bit = 1 << slot;
+        if !is_ok {
+            self.failed.fetch_or(bit, Ordering::SeqCst);
+        }
+        self.issued.fetch_and(!bit, Ordering::SeqCst);
+        self.wakers[slot].wake();
+        if (self.orphaned.fetch_and(!bit, Ordering::SeqCst) & bit) != 0 {
+            self.free.fetch_or(bit, Ordering::SeqCst);
+        }
+    }
+}
+
+/// A command slot in use; it is released when its command is finished
+pub struct BlockSlot<'a> {
+    slots: &'a BlockSlots,
+    index: usize,
+}
+
+impl<'a> BlockSlot<'a> {
+    #[inline]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Waits for the command in the slot to finish
+    ///
+    /// Without interrupts, `poll` is given to complete the commands, and the future calls it by
+    /// waking itself up, which keeps the CPU busy until the command finishes.
+    #[inline]
+    pub fn wait<F: Fn()>(&'a self, poll: Option<F>) -> BlockCommandFuture<'a, F> {
+        BlockCommandFuture { slot: self, poll }
+    }
+}
+
+impl Drop for BlockSlot<'_> {
+    fn drop(&mut self) {
+        // If the command is still running, the interrupt handler releases the slot later
+        let bit = 1 << self.index;
+        let slots = self.slots;
+        slots.orphaned.fetch_or(bit, Ordering::SeqCst);
+        if (slots.issued.load(Ordering::SeqCst) & bit) == 0
+            && (slots.orphaned.fetch_and(!bit, Ordering::SeqCst) & bit) != 0
+        {
+            slots.free.fetch_or(bit, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Waits for a free command slot
+///
+/// Nothing wakes the future up when a slot is released, so it is polled again and again until
+/// a slot is free. Slots are held only while their commands run, so the wait is short.
+pub struct BlockSlotFuture<'a> {
+    slots: &'a BlockSlots,
+}
+
+impl<'a> Future for BlockSlotFuture<'a> {
+    type Output = BlockSlot<'a>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.slots.try_alloc() {
+            Some(slot) => Poll::Ready(slot),
+            None => {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Waits for the command in the slot to finish
+pub struct BlockCommandFuture<'a, F> {
+    slot: &'a BlockSlot<'a>,
+    poll: Option<F>,
+}
+
+impl<F: Fn()> Future for BlockCommandFuture<'_, F> {
+    type Output = io::Result<()>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let slots = self.slot.slots;
+        let slot = self.slot.index;
+        let bit = 1 << slot;
+        if let Some(poll) = self.poll.as_ref() {
+            poll();
+        }
+        slots.wakers[slot].register(cx.waker());
+        if (slots.issued.load(Ordering::SeqCst) & bit) == 0 {
+            slots.wakers[slot].take();
+            if (slots.failed.fetch_and(!bit, Ordering::SeqCst) & bit) != 0 {
+                Poll::Ready(Err(io::ErrorKind::Other.into()))
+            } else {
+                Poll::Ready(Ok(()))
+            }
+        } else {
+            if self.poll.is_some() {
+                cx.waker().wake_by_ref();
+            }
+            Poll::Pending
+        }
+    }
+}
+
+static mut BDM: BlockDeviceManager = BlockDeviceManager::new();
+
+/// The list of block devices found by the drivers
+pub struct BlockDeviceManager {
+    devices: Mutex<Vec<(String, Arc<dyn BlockDevice>)>>,
+}
+
+impl BlockDeviceManager {
+    const fn new() -> Self {
+        Self {
+            devices: Mutex::new(Vec::new()),
+        }
+    }
+
+    #[inline]
+    fn shared<'a>() -> &'a Self {
+        unsafe { &BDM }
+    }
+
+    /// Registers the device with the specified name; the device is cached from now on.
+    ///
+    /// The partitions of the device are also registered, named after the device and their numbers
+    /// such as `sata0p1`.
+    pub fn register(name: String, dev: Arc<dyn BlockDevice>) -> Arc<dyn BlockDevice> {
+        let dev: Arc<dyn BlockDevice> =
+            Arc::new(BlockCache::new(dev, BlockCache::DEFAULT_CAPACITY));
+        let partitions = BlockPartition::scan(&dev);
+
+        let mut devices = Self::shared().devices.lock().unwrap();
+        devices.push((name.clone(), dev.clone()));
+        for (number, partition) in partitions {
+            devices.push((format!("{}p{}", name, number), Arc::new(partition)));
+        }
+        dev
+    }
+
+    /// Returns the registered device with the specified name
+    pub fn get(name: &str) -> Option<Arc<dyn BlockDevice>> {
+        Self::shared()
+            .devices
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(v, _)| v == name)
+            .map(|(_, dev)| dev.clone())
+    }
+
+    /// Returns a list of the names and devices
+    pub fn devices() -> Vec<(String, Arc<dyn BlockDevice>)> {
+        Self::shared().devices.lock().unwrap().clone()
+    }
+}
@@ -1,2 +1,5 @@
 // Device
+pub mod ahci;
+pub mod block;
 pub mod uart;
+pub mod virtio;
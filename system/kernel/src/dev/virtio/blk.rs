@@ -0,0 +1,352 @@
+// Virtio Block Device
+
+use super::*;
+use crate::arch::cpu::Cpu;
+use crate::arch::page::{PageManager, PhysicalAddress};
+use crate::bus::pci::*;
+use crate::dev::block::*;
+use crate::mem::{mmio::Mmio, MemoryManager};
+use crate::sync::spinlock::Spinlock;
+use alloc::{boxed::Box, format, sync::Arc};
+use core::{alloc::Layout, num::NonZeroUsize, ptr::null_mut, slice, sync::atomic::*};
+use megstd::io;
+
+const MAX_DEVICES: usize = 4;
+
+/// Devices that the MSI handler looks for the finished requests
+static DEVICES: [AtomicPtr<VirtioBlk>; MAX_DEVICES] = [NULL_DEVICE; MAX_DEVICES];
+const NULL_DEVICE: AtomicPtr<VirtioBlk> = AtomicPtr::new(null_mut());
+
+static NEXT_DISK_INDEX: AtomicUsize = AtomicUsize::new(0);
+
+pub struct VirtioBlkRegistrar();
+
+impl VirtioBlkRegistrar {
+    pub fn init() -> Box<dyn PciDriverRegistrar> {
+        Box::new(Self())
+    }
+}
+
+impl PciDriverRegistrar for VirtioBlkRegistrar {
+    fn instantiate(&self, device: &PciDevice) -> Option<Arc<dyn PciDriver>> {
+        if VirtioPci::device_type(device) == Some(VirtioDeviceType::BLOCK) {
+            unsafe { VirtioBlk::new(device).map(|v| v as Arc<dyn PciDriver>) }
+        } else {
+            None
+        }
+    }
+}
+
+/// Virtio block device
+pub struct VirtioBlk {
+    transport: VirtioPci,
+    queue: Virtqueue,
+    /// Request headers and status bytes
+    headers: Mmio,
+    /// A bounce buffer for each slot
+    buffers: usize,
+    n_slots: usize,
+    has_interrupt: bool,
+    slots: BlockSlots,
+    lock: Spinlock,
+    total_sectors: u64,
+    is_read_only: bool,
+    max_transfer: usize,
+}
+
+impl VirtioBlk {
+    const SECTOR_SIZE: usize = 512;
+    const MAX_SLOTS: usize = 8;
+    const BUFFER_SIZE: usize = 0x1_0000;
+    /// Header, data and status
+    const DESC_PER_SLOT: usize = 3;
+    const HEADER_SIZE: usize = 32;
+    const OFFSET_STATUS: usize = 16;
+
+    const F_SIZE_MAX: u64 = 1 << 1;
+    const F_RO: u64 = 1 << 5;
+
+    const CONFIG_CAPACITY: usize = 0x00;
+    const CONFIG_SIZE_MAX: usize = 0x08;
+
+    const T_IN: u32 = 0;
+    const T_OUT: u32 = 1;
+
+    const S_OK: u8 = 0;
+
+    unsafe fn new(device: &PciDevice) -> Option<Arc<Self>> {
+        let mut transport = VirtioPci::new(device)?;
+        transport.reset();
+        transport.add_status(VirtioDeviceStatus::ACKNOWLEDGE | VirtioDeviceStatus::DRIVER);
+
+        let device_features = transport.device_features();
+        let mut features = device_features & (Self::F_SIZE_MAX | Self::F_RO);
+        if transport.is_modern() {
+            if (device_features & VIRTIO_F_VERSION_1) == 0 {
+                transport.add_status(VirtioDeviceStatus::FAILED);
+                return None;
+            }
+            features |= VIRTIO_F_VERSION_1;
+        }
+        transport.set_driver_features(features);
+        if transport.is_modern() {
+            transport.add_status(VirtioDeviceStatus::FEATURES_OK);
+            if !transport.status().contains(VirtioDeviceStatus::FEATURES_OK) {
+                transport.add_status(VirtioDeviceStatus::FAILED);
+                return None;
+            }
+        }
+
+        let queue_size = transport.queue_size(0);
+        let n_slots = usize::min(Self::MAX_SLOTS, queue_size as usize / Self::DESC_PER_SLOT);
+        if n_slots == 0 {
+            transport.add_status(VirtioDeviceStatus::FAILED);
+            return None;
+        }
+        // The queue is freed when it is dropped
+        let queue = Virtqueue::new(0, queue_size);
+        let slots = queue.as_ref().and_then(|_| Self::alloc_slots(n_slots));
+        let (queue, (headers, headers_pa, buffers_pa)) = match (queue, slots) {
+            (Some(queue), Some(slots)) => (queue, slots),
+            _ => {
+                transport.add_status(VirtioDeviceStatus::FAILED);
+                return None;
+            }
+        };
+
+        // Each slot owns a fixed chain of the descriptors
+        for slot in 0..n_slots {
+            let head = (slot * Self::DESC_PER_SLOT) as u16;
+            let header_pa = headers_pa + (Self::HEADER_SIZE * slot) as u64;
+            queue.set_descriptor(head, header_pa, 16, VirtqDescFlags::NEXT, head + 1);
+            queue.set_descriptor(
+                head + 1,
+                buffers_pa + (Self::BUFFER_SIZE * slot) as u64,
+                0,
+                VirtqDescFlags::NEXT,
+                head + 2,
+            );
+            queue.set_descriptor(
+                head + 2,
+                header_pa + Self::OFFSET_STATUS as u64,
+                1,
+                VirtqDescFlags::WRITE,
+                0,
+            );
+        }
+
+        // The MSI handler can only find the devices that have a room in the list
+        let has_room = DEVICES.iter().any(|v| v.load(Ordering::SeqCst).is_null());
+        let mut has_interrupt = has_room && device.register_msix(Self::handle_msi).is_ok();
+        if has_interrupt {
+            transport.enable_msix();
+            transport.set_config_vector(VirtioPci::NO_VECTOR);
+        }
+        let vector = if has_interrupt {
+            0
+        } else {
+            VirtioPci::NO_VECTOR
+        };
+        has_interrupt &= transport.setup_queue(&queue, vector);
+
+        let total_sectors = transport.read_config_u64(Self::CONFIG_CAPACITY);
+        let size_max = if (features & Self::F_SIZE_MAX) != 0 {
+            transport.read_config_u32(Self::CONFIG_SIZE_MAX) as usize
+        } else {
+            usize::MAX
+        };
+        let max_transfer = usize::max(
+            Self::SECTOR_SIZE,
+            usize::min(Self::BUFFER_SIZE, size_max) & !(Self::SECTOR_SIZE - 1),
+        );
+
+        let blk = Arc::new(Self {
+            transport,
+            queue,
+            headers,
+            buffers: PageManager::direct_map(buffers_pa),
+            n_slots,
+            has_interrupt,
+            slots: BlockSlots::new(n_slots),
+            lock: Spinlock::new(),
+            total_sectors,
+            is_read_only: (features & Self::F_RO) != 0,
+            max_transfer,
+        });
+
+        let ptr = Arc::as_ptr(&blk) as *mut VirtioBlk;
+        DEVICES.iter().any(|v| {
+            v.compare_exchange(null_mut(), ptr, Ordering::SeqCst, Ordering::Relaxed)
+                .is_ok()
+        });
+        blk.transport.add_status(VirtioDeviceStatus::DRIVER_OK);
+
+        let name = format!("vblk{}", NEXT_DISK_INDEX.fetch_add(1, Ordering::SeqCst));
+        BlockDeviceManager::register(name, blk.clone());
+
+        Some(blk)
+    }
+
+    /// Allocates the request headers and the bounce buffers of the slots, or nothing
+    unsafe fn alloc_slots(n_slots: usize) -> Option<(Mmio, PhysicalAddress, PhysicalAddress)> {
+        let page_size = MemoryManager::PAGE_SIZE_MIN;
+        let headers_size = Self::HEADER_SIZE * n_slots;
+        let buffers_size = Self::BUFFER_SIZE * n_slots;
+        let headers_layout = Layout::from_size_align(headers_size, page_size).ok()?;
+        let buffers_layout = Layout::from_size_align(buffers_size, page_size).ok()?;
+        let headers_pa = MemoryManager::pg_alloc(headers_layout)?;
+        let buffers_pa = match MemoryManager::pg_alloc(buffers_layout) {
+            Some(v) => v,
+            None => {
+                let _ = MemoryManager::pg_dealloc(headers_pa, headers_layout);
+                return None;
+            }
+        };
+        let headers_pa = headers_pa.get() as PhysicalAddress;
+        // The direct map of an allocated page is never null
+        let headers = Mmio::from_virt(
+            NonZeroUsize::new_unchecked(PageManager::direct_map(headers_pa)),
+            headers_size,
+        );
+        Some((headers, headers_pa, buffers_pa.get() as PhysicalAddress))
+    }
+
+    /// MSI handler shared by all devices
+    fn handle_msi() {
+        for device in &DEVICES {
+            let ptr = device.load(Ordering::SeqCst);
+            if let Some(blk) = unsafe { ptr.as_ref() } {
+                unsafe { blk.update() };
+            }
+        }
+    }
+
+    #[inline]
+    unsafe fn buffer(&self, slot: usize) -> &mut [u8] {
+        slice::from_raw_parts_mut(
+            (self.buffers + Self::BUFFER_SIZE * slot) as *mut u8,
+            Self::BUFFER_SIZE,
+        )
+    }
+
+    /// Builds the request in the slot and makes it available to the device
+    unsafe fn issue(&self, slot: usize, request_type: u32, lba: u64, len: usize) {
+        let header = Self::HEADER_SIZE * slot;
+        self.headers.write_u32(header, request_type);
+        self.headers.write_u32(header + 4, 0);
+        self.headers.write_u64(header + 8, lba);
+        self.headers.write_u8(header + Self::OFFSET_STATUS, 0xFF);
+
+        let head = (slot * Self::DESC_PER_SLOT) as u16;
+        let flags = if request_type == Self::T_IN {
+            VirtqDescFlags::NEXT | VirtqDescFlags::WRITE
+        } else {
+            VirtqDescFlags::NEXT
+        };
+        self.queue.set_descriptor_len(head + 1, len as u32, flags);
+
+        Cpu::without_interrupts(|| {
+            self.lock.synchronized(|| {
+                self.slots.issue(slot);
+                self.queue.push_avail(head);
+            })
+        });
+        self.transport.notify(self.queue.index());
+    }
+
+    /// Completes the finished requests
+    unsafe fn update(&self) {
+        Cpu::without_interrupts(|| {
+            self.lock.synchronized(|| {
+                while let Some((head, _)) = self.queue.pop_used() {
+                    let slot = head as usize / Self::DESC_PER_SLOT;
+                    if slot >= self.n_slots {
+                        continue;
+                    }
+                    let status = self
+                        .headers
+                        .read_u8(Self::HEADER_SIZE * slot + Self::OFFSET_STATUS);
+                    self.slots.complete(slot, status == Self::S_OK);
+                }
+            })
+        })
+    }
+
+    /// Returns the function that completes the requests if interrupts are not available
+    fn poller(&self) -> Option<impl Fn() + '_> {
+        (!self.has_interrupt).then(move || move || unsafe { self.update() })
+    }
+
+    fn check_range(&self, lba: u64, len: usize) -> io::Result<()> {
+        let count = (len / Self::SECTOR_SIZE) as u64;
+        if len % Self::SECTOR_SIZE != 0 {
+            return Err(io::ErrorKind::InvalidInput.into());
+        }
+        match lba.checked_add(count) {
+            Some(end) if end <= self.total_sectors => Ok(()),
+            _ => Err(io::ErrorKind::InvalidInput.into()),
+        }
+    }
+
+    async fn read(&self, mut lba: u64, buf: &mut [u8]) -> io::Result<()> {
+        self.check_range(lba, buf.len())?;
+        for chunk in buf.chunks_mut(self.max_transfer) {
+            let slot = self.slots.alloc().await;
+            unsafe {
+                self.issue(slot.index(), Self::T_IN, lba, chunk.len());
+            }
+            slot.wait(self.poller()).await?;
+            chunk.copy_from_slice(unsafe { &self.buffer(slot.index())[..chunk.len()] });
+            lba += (chunk.len() / Self::SECTOR_SIZE) as u64;
+        }
+        Ok(())
+    }
+
+    async fn write(&self, mut lba: u64, buf: &[u8]) -> io::Result<()> {
+        if self.is_read_only {
+            return Err(io::ErrorKind::ReadOnlyFilesystem.into());
+        }
+        self.check_range(lba, buf.len())?;
+        for chunk in buf.chunks(self.max_transfer) {
+            let slot = self.slots.alloc().await;
+            unsafe {
+                self.buffer(slot.index())[..chunk.len()].copy_from_slice(chunk);
+                self.issue(slot.index(), Self::T_OUT, lba, chunk.len());
+            }
+            slot.wait(self.poller()).await?;
+            lba += (chunk.len() / Self::SECTOR_SIZE) as u64;
+        }
+        Ok(())
+    }
+}
+
+impl PciDriver for VirtioBlk {
+    fn name<'a>(&self) -> &'a str {
+        "virtio-blk"
+    }
+}
+
+impl BlockDevice for VirtioBlk {
+    #[inline]
+    fn sector_size(&self) -> usize {
+        Self::SECTOR_SIZE
+    }
+
+    #[inline]
+    fn total_sectors(&self) -> u64 {
+        self.total_sectors
+    }
+
+    #[inline]
+    fn is_read_only(&self) -> bool {
+        self.is_read_only
+    }
+
+    fn read_sectors_async<'a>(&'a self, lba: u64, buf: &'a mut [u8]) -> BlockFuture<'a> {
+        Box::pin(self.read(lba, buf))
+    }
+
+    fn write_sectors_async<'a>(&'a self, lba: u64, buf: &'a [u8]) -> BlockFuture<'a> {
+        Box::pin(self.write(lba, buf))
+    }
+}
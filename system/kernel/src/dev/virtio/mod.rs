@@ -0,0 +1,35 @@
+// Virtual I/O Device
+pub mod blk;
+mod pci;
+pub use pci::*;
+mod queue;
+pub use queue::*;
+
+use bitflags::*;
+
+bitflags! {
+    /// Device Status Field
+    pub struct VirtioDeviceStatus: u8 {
+        const ACKNOWLEDGE           = 0x01;
+        const DRIVER                = 0x02;
+        const DRIVER_OK             = 0x04;
+        const FEATURES_OK           = 0x08;
+        const DEVICE_NEEDS_RESET    = 0x40;
+        const FAILED                = 0x80;
+    }
+}
+
+/// Device types that appear in the PCI device ID
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VirtioDeviceType(pub u16);
+
+impl VirtioDeviceType {
+    pub const NETWORK: Self = Self(1);
+    pub const BLOCK: Self = Self(2);
+    pub const CONSOLE: Self = Self(3);
+    pub const ENTROPY: Self = Self(4);
+}
+
+/// The feature bit that the device complies with version 1.0 or later
+pub const VIRTIO_F_VERSION_1: u64 = 1 << 32;
@@ -0,0 +1,348 @@
+// Virtio over PCI Bus
+
+use super::*;
+use crate::arch::cpu::Cpu;
+use crate::bus::pci::*;
+use crate::mem::mmio::Mmio;
+use alloc::vec::Vec;
+use core::num::NonZeroUsize;
+
+/// Virtio PCI transport, either legacy or modern
+pub struct VirtioPci {
+    regs: VirtioPciRegs,
+    /// Notification offsets of the queues that are set up
+    notify_offsets: Vec<(u16, usize)>,
+}
+
+enum VirtioPciRegs {
+    /// Legacy interface on the I/O space
+    Legacy { base: u16, config_offset: u16 },
+    /// Virtio 1.0 interface on the memory space
+    Modern {
+        common: Mmio,
+        notify: Mmio,
+        notify_multiplier: u32,
+        device: Mmio,
+    },
+}
+
+impl VirtioPci {
+    pub const VENDOR_ID: PciVendorId = PciVendorId(0x1AF4);
+
+    /// The value that disables the MSI-X vector
+    pub const NO_VECTOR: u16 = 0xFFFF;
+
+    const CAP_COMMON_CFG: u8 = 1;
+    const CAP_NOTIFY_CFG: u8 = 2;
+    const CAP_DEVICE_CFG: u8 = 4;
+
+    const LEGACY_HOST_FEATURES: u16 = 0x00;
+    const LEGACY_GUEST_FEATURES: u16 = 0x04;
+    const LEGACY_QUEUE_PFN: u16 = 0x08;
+    const LEGACY_QUEUE_SIZE: u16 = 0x0C;
+    const LEGACY_QUEUE_SELECT: u16 = 0x0E;
+    const LEGACY_QUEUE_NOTIFY: u16 = 0x10;
+    const LEGACY_STATUS: u16 = 0x12;
+    const LEGACY_CONFIG_VECTOR: u16 = 0x14;
+    const LEGACY_QUEUE_VECTOR: u16 = 0x16;
+    const LEGACY_CONFIG: u16 = 0x14;
+    const LEGACY_CONFIG_MSIX: u16 = 0x18;
+
+    const COMMON_DEVICE_FEATURE_SELECT: usize = 0x00;
+    const COMMON_DEVICE_FEATURE: usize = 0x04;
+    const COMMON_DRIVER_FEATURE_SELECT: usize = 0x08;
+    const COMMON_DRIVER_FEATURE: usize = 0x0C;
+    const COMMON_MSIX_CONFIG: usize = 0x10;
+    const COMMON_STATUS: usize = 0x14;
+    const COMMON_QUEUE_SELECT: usize = 0x16;
+    const COMMON_QUEUE_SIZE: usize = 0x18;
+    const COMMON_QUEUE_MSIX_VECTOR: usize = 0x1A;
+    const COMMON_QUEUE_ENABLE: usize = 0x1C;
+    const COMMON_QUEUE_NOTIFY_OFF: usize = 0x1E;
+    const COMMON_QUEUE_DESC: usize = 0x20;
+    const COMMON_QUEUE_DRIVER: usize = 0x28;
+    const COMMON_QUEUE_DEVICE: usize = 0x30;
+
+    /// Returns the type of the device if it is a virtio device
+    pub fn device_type(device: &PciDevice) -> Option<VirtioDeviceType> {
+        if device.vendor_id() != Self::VENDOR_ID {
+            return None;
+        }
+        match device.device_id().0 {
+            // Transitional devices have the type in the subsystem ID
+            0x1000..=0x103F => Some(VirtioDeviceType(device.subsys_device_id().0)),
+            0x1040..=0x107F => Some(VirtioDeviceType(device.device_id().0 - 0x1040)),
+            _ => None,
+        }
+    }
+
+    /// Makes the transport from the vendor specific capabilities, or the legacy I/O BAR0.
+    pub unsafe fn new(device: &PciDevice) -> Option<Self> {
+        Self::device_type(device)?;
+        device.set_command(PciCommand::IO_SPACE | PciCommand::MEM_SPACE | PciCommand::BUS_MASTER);
+
+        let regs = match Self::parse_capabilities(device) {
+            Some(v) => v,
+            None => match device.device_id().0 {
+                0x1000..=0x103F => {
+                    let bar = device.bar_at(0).filter(|v| v.is_isolated_io())?;
+                    VirtioPciRegs::Legacy {
+                        base: bar.base() as u16,
+                        config_offset: Self::LEGACY_CONFIG,
+                    }
+                }
+                _ => return None,
+            },
+        };
+
+        Some(Self {
+            regs,
+            notify_offsets: Vec::new(),
+        })
+    }
+
+    unsafe fn parse_capabilities(device: &PciDevice) -> Option<VirtioPciRegs> {
+        let mut bars = Vec::new();
+        let mut common = None;
+        let mut notify = None;
+        let mut notify_multiplier = 0;
+        let mut device_cfg = None;
+        for (id, register) in device.capabilities() {
+            if *id != PciCapabilityId::VENDOR_SPECIFIC {
+                continue;
+            }
+            let cfg_type = (device.read_register(*register) >> 24) as u8;
+            let bar_index = device.read_register(register + 1) as u8;
+            let offset = device.read_register(register + 2) as usize;
+            let length = device.read_register(register + 3) as usize;
+            // Other structures such as the PCI configuration access may point to a BAR that
+            // cannot be mapped
+            match cfg_type {
+                Self::CAP_COMMON_CFG | Self::CAP_NOTIFY_CFG | Self::CAP_DEVICE_CFG => (),
+                _ => continue,
+            }
+            if bar_index > 5 {
+                continue;
+            }
+
+            // Maps each BAR only once
+            let bar = match bars.iter().find(|v: &&(u8, Mmio)| v.0 == bar_index) {
+                Some(v) => v.1,
+                None => {
+                    let mmio = match device
+                        .bar_at(bar_index)
+                        .filter(|v| v.is_mmio())
+                        .and_then(|v| Mmio::from_bar(v))
+                    {
+                        Some(v) => v,
+                        None => continue,
+                    };
+                    bars.push((bar_index, mmio));
+                    mmio
+                }
+            };
+            if offset + length > bar.size() {
+                continue;
+            }
+            let mmio = Mmio::from_virt(NonZeroUsize::new(bar.base() + offset)?, length);
+
+            match cfg_type {
+                Self::CAP_COMMON_CFG => common = common.or(Some(mmio)),
+                Self::CAP_NOTIFY_CFG => {
+                    if notify.is_none() {
+                        notify = Some(mmio);
+                        notify_multiplier = device.read_register(register + 4);
+                    }
+                }
+                Self::CAP_DEVICE_CFG => device_cfg = device_cfg.or(Some(mmio)),
+                _ => (),
+            }
+        }
+
+        Some(VirtioPciRegs::Modern {
+            common: common?,
+            notify: notify?,
+            notify_multiplier,
+            device: device_cfg?,
+        })
+    }
+
+    #[inline]
+    pub fn is_modern(&self) -> bool {
+        matches!(self.regs, VirtioPciRegs::Modern { .. })
+    }
+
+    pub unsafe fn status(&self) -> VirtioDeviceStatus {
+        let value = match self.regs {
+            VirtioPciRegs::Legacy { base, .. } => Cpu::in8(base + Self::LEGACY_STATUS),
+            VirtioPciRegs::Modern { common, .. } => common.read_u8(Self::COMMON_STATUS),
+        };
+        VirtioDeviceStatus::from_bits_truncate(value)
+    }
+
+    pub unsafe fn set_status(&self, status: VirtioDeviceStatus) {
+        match self.regs {
+            VirtioPciRegs::Legacy { base, .. } => {
+                Cpu::out8(base + Self::LEGACY_STATUS, status.bits())
+            }
+            VirtioPciRegs::Modern { common, .. } => {
+                common.write_u8(Self::COMMON_STATUS, status.bits())
+            }
+        }
+    }
+
+    #[inline]
+    pub unsafe fn add_status(&self, status: VirtioDeviceStatus) {
+        self.set_status(self.status() | status);
+    }
+
+    /// Resets the device and waits for it to finish
+    pub unsafe fn reset(&self) {
+        self.set_status(VirtioDeviceStatus::empty());
+        while !self.status().is_empty() {
+            Cpu::spin_loop_hint();
+        }
+    }
+
+    pub unsafe fn device_features(&self) -> u64 {
+        match self.regs {
+            VirtioPciRegs::Legacy { base, .. } => {
+                Cpu::in32(base + Self::LEGACY_HOST_FEATURES) as u64
+            }
+            VirtioPciRegs::Modern { common, .. } => {
+                common.write_u32(Self::COMMON_DEVICE_FEATURE_SELECT, 0);
+                let low = common.read_u32(Self::COMMON_DEVICE_FEATURE) as u64;
+                common.write_u32(Self::COMMON_DEVICE_FEATURE_SELECT, 1);
+                let high = common.read_u32(Self::COMMON_DEVICE_FEATURE) as u64;
+                low | (high << 32)
+            }
+        }
+    }
+
+    pub unsafe fn set_driver_features(&self, features: u64) {
+        match self.regs {
+            VirtioPciRegs::Legacy { base, .. } => {
+                Cpu::out32(base + Self::LEGACY_GUEST_FEATURES, features as u32)
+            }
+            VirtioPciRegs::Modern { common, .. } => {
+                common.write_u32(Self::COMMON_DRIVER_FEATURE_SELECT, 0);
+                common.write_u32(Self::COMMON_DRIVER_FEATURE, features as u32);
+                common.write_u32(Self::COMMON_DRIVER_FEATURE_SELECT, 1);
+                common.write_u32(Self::COMMON_DRIVER_FEATURE, (features >> 32) as u32);
+            }
+        }
+    }
+
+    /// Tells the transport that MSI-X of the device is enabled, as it moves the legacy registers.
+    pub fn enable_msix(&mut self) {
+        if let VirtioPciRegs::Legacy {
+            ref mut config_offset,
+            ..
+        } = self.regs
+        {
+            *config_offset = Self::LEGACY_CONFIG_MSIX;
+        }
+    }
+
+    /// Sets the MSI-X vector for the configuration change notifications
+    pub unsafe fn set_config_vector(&self, vector: u16) {
+        match self.regs {
+            VirtioPciRegs::Legacy { base, .. } => {
+                Cpu::out16(base + Self::LEGACY_CONFIG_VECTOR, vector)
+            }
+            VirtioPciRegs::Modern { common, .. } => {
+                common.write_u16(Self::COMMON_MSIX_CONFIG, vector)
+            }
+        }
+    }
+
+    /// Returns the maximum size of the queue, or 0 if the queue is not available
+    pub unsafe fn queue_size(&self, index: u16) -> u16 {
+        match self.regs {
+            VirtioPciRegs::Legacy { base, .. } => {
+                Cpu::out16(base + Self::LEGACY_QUEUE_SELECT, index);
+                Cpu::in16(base + Self::LEGACY_QUEUE_SIZE)
+            }
+            VirtioPciRegs::Modern { common, .. } => {
+                common.write_u16(Self::COMMON_QUEUE_SELECT, index);
+                common.read_u16(Self::COMMON_QUEUE_SIZE)
+            }
+        }
+    }
+
+    /// Tells the device where the queue is and enables it.
+    /// Returns whether or not the device accepted the MSI-X vector.
+    pub unsafe fn setup_queue(&mut self, queue: &Virtqueue, vector: u16) -> bool {
+        let index = queue.index();
+        match self.regs {
+            VirtioPciRegs::Legacy { base, .. } => {
+                Cpu::out16(base + Self::LEGACY_QUEUE_SELECT, index);
+                let vector = if vector != Self::NO_VECTOR {
+                    Cpu::out16(base + Self::LEGACY_QUEUE_VECTOR, vector);
+                    Cpu::in16(base + Self::LEGACY_QUEUE_VECTOR)
+                } else {
+                    vector
+                };
+                let pfn = queue.desc_pa() / Virtqueue::LEGACY_ALIGN as u64;
+                Cpu::out32(base + Self::LEGACY_QUEUE_PFN, pfn as u32);
+                self.notify_offsets
+                    .push((index, Self::LEGACY_QUEUE_NOTIFY as usize));
+                vector != Self::NO_VECTOR
+            }
+            VirtioPciRegs::Modern {
+                common,
+                notify_multiplier,
+                ..
+            } => {
+                common.write_u16(Self::COMMON_QUEUE_SELECT, index);
+                common.write_u16(Self::COMMON_QUEUE_SIZE, queue.size());
+                common.write_u16(Self::COMMON_QUEUE_MSIX_VECTOR, vector);
+                let vector = common.read_u16(Self::COMMON_QUEUE_MSIX_VECTOR);
+                // 64-bit fields are written as two 32-bit halves
+                for (register, pa) in [
+                    (Self::COMMON_QUEUE_DESC, queue.desc_pa()),
+                    (Self::COMMON_QUEUE_DRIVER, queue.avail_pa()),
+                    (Self::COMMON_QUEUE_DEVICE, queue.used_pa()),
+                ]
+                .iter()
+                {
+                    common.write_u32(*register, *pa as u32);
+                    common.write_u32(*register + 4, (*pa >> 32) as u32);
+                }
+                let notify_off = common.read_u16(Self::COMMON_QUEUE_NOTIFY_OFF) as usize;
+                common.write_u16(Self::COMMON_QUEUE_ENABLE, 1);
+                self.notify_offsets
+                    .push((index, notify_off * notify_multiplier as usize));
+                vector != Self::NO_VECTOR
+            }
+        }
+    }
+
+    /// Notifies the device that there are new buffers in the queue
+    pub unsafe fn notify(&self, index: u16) {
+        let offset = match self.notify_offsets.iter().find(|v| v.0 == index) {
+            Some(v) => v.1,
+            None => return,
+        };
+        match self.regs {
+            VirtioPciRegs::Legacy { base, .. } => Cpu::out16(base + offset as u16, index),
+            VirtioPciRegs::Modern { notify, .. } => notify.write_u16(offset, index),
+        }
+    }
+
+    pub unsafe fn read_config_u32(&self, offset: usize) -> u32 {
+        match self.regs {
+            VirtioPciRegs::Legacy {
+                base,
+                config_offset,
+            } => Cpu::in32(base + config_offset + offset as u16),
+            VirtioPciRegs::Modern { device, .. } => device.read_u32(offset),
+        }
+    }
+
+    pub unsafe fn read_config_u64(&self, offset: usize) -> u64 {
+        let low = self.read_config_u32(offset) as u64;
+        let high = self.read_config_u32(offset + 4) as u64;
+        low | (high << 32)
+    }
+}
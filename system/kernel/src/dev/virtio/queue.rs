@@ -0,0 +1,149 @@
+// Virtqueue
+
+use crate::arch::page::{PageManager, PhysicalAddress};
+use crate::mem::{mmio::Mmio, MemoryManager};
+use bitflags::*;
+use core::{alloc::Layout, num::NonZeroUsize, sync::atomic::*};
+
+bitflags! {
+    pub struct VirtqDescFlags: u16 {
+        /// The buffer continues via the next field
+        const NEXT      = 0x0001;
+        /// The buffer is device write-only
+        const WRITE     = 0x0002;
+        const INDIRECT  = 0x0004;
+    }
+}
+
+/// Split virtqueue placed in the legacy layout, which the modern interface also accepts
+///
+/// The memory of the queue is freed when it is dropped, so the device must not use it any more.
+pub struct Virtqueue {
+    index: u16,
+    size: u16,
+    mem: Mmio,
+    pa: PhysicalAddress,
+    used_offset: usize,
+    last_used: AtomicU16,
+}
+
+impl Virtqueue {
+    pub const LEGACY_ALIGN: usize = 0x1000;
+
+    const DESC_SIZE: usize = 16;
+
+    pub unsafe fn new(index: u16, size: u16) -> Option<Self> {
+        let n = size as usize;
+        let align_m1 = Self::LEGACY_ALIGN - 1;
+        let avail_offset = Self::DESC_SIZE * n;
+        let used_offset = (avail_offset + 6 + 2 * n + align_m1) & !align_m1;
+        let mem_size = (used_offset + 6 + 8 * n + align_m1) & !align_m1;
+
+        let layout = Layout::from_size_align(mem_size, Self::LEGACY_ALIGN).ok()?;
+        let pa = MemoryManager::pg_alloc(layout)?.get() as PhysicalAddress;
+        // The direct map of an allocated page is never null
+        let mem = Mmio::from_virt(
+            NonZeroUsize::new_unchecked(PageManager::direct_map(pa)),
+            mem_size,
+        );
+        (mem.base() as *mut u8).write_bytes(0, mem_size);
+
+        Some(Self {
+            index,
+            size,
+            mem,
+            pa,
+            used_offset,
+            last_used: AtomicU16::new(0),
+        })
+    }
+
+    #[inline]
+    pub const fn index(&self) -> u16 {
+        self.index
+    }
+
+    #[inline]
+    pub const fn size(&self) -> u16 {
+        self.size
+    }
+
+    #[inline]
+    pub const fn desc_pa(&self) -> PhysicalAddress {
+        self.pa
+    }
+
+    #[inline]
+    pub const fn avail_pa(&self) -> PhysicalAddress {
+        self.pa + self.avail_offset() as PhysicalAddress
+    }
+
+    #[inline]
+    pub const fn used_pa(&self) -> PhysicalAddress {
+        self.pa + self.used_offset as PhysicalAddress
+    }
+
+    #[inline]
+    const fn avail_offset(&self) -> usize {
+        Self::DESC_SIZE * self.size as usize
+    }
+
+    pub unsafe fn set_descriptor(
+        &self,
+        index: u16,
+        pa: PhysicalAddress,
+        len: u32,
+        flags: VirtqDescFlags,
+        next: u16,
+    ) {
+        let offset = Self::DESC_SIZE * index as usize;
+        self.mem.write_u64(offset, pa);
+        self.mem.write_u32(offset + 8, len);
+        self.mem.write_u16(offset + 12, flags.bits());
+        self.mem.write_u16(offset + 14, next);
+    }
+
+    pub unsafe fn set_descriptor_len(&self, index: u16, len: u32, flags: VirtqDescFlags) {
+        let offset = Self::DESC_SIZE * index as usize;
+        self.mem.write_u32(offset + 8, len);
+        self.mem.write_u16(offset + 12, flags.bits());
+    }
+
+    /// Makes the descriptor chain available to the device; the caller must serialize it.
+    pub unsafe fn push_avail(&self, head: u16) {
+        let avail = self.avail_offset();
+        let idx = self.mem.read_u16(avail + 2);
+        self.mem
+            .write_u16(avail + 4 + 2 * (idx % self.size) as usize, head);
+        self.mem.write_u16(avail + 2, idx.wrapping_add(1));
+    }
+
+    /// Takes the head and the written length of a chain that the device has finished with;
+    /// the caller must serialize it.
+    pub unsafe fn pop_used(&self) -> Option<(u16, u32)> {
+        let used = self.used_offset;
+        let last_used = self.last_used.load(Ordering::SeqCst);
+        if last_used == self.mem.read_u16(used + 2) {
+            return None;
+        }
+        let entry = used + 4 + 8 * (last_used % self.size) as usize;
+        let id = self.mem.read_u32(entry) as u16;
+        let len = self.mem.read_u32(entry + 4);
+        self.last_used
+            .store(last_used.wrapping_add(1), Ordering::SeqCst);
+        Some((id, len))
+    }
+}
+
+impl Drop for Virtqueue {
+    fn drop(&mut self) {
+        if let (Ok(layout), Some(base)) = (
+            Layout::from_size_align(self.mem.size(), Self::LEGACY_ALIGN),
+            NonZeroUsize::new(self.pa as usize),
+        ) {
+            unsafe {
+                let _ = MemoryManager::pg_dealloc(base, layout);
+            }
+        }
+    }
+}
@@ -0,0 +1,1605 @@
+// File Allocation Table Filesystem
+
+use super::*;
+use crate::dev::block::*;
+use crate::sync::Mutex;
+use crate::system::System;
+use alloc::{collections::BTreeMap, string::String, sync::Arc, vec::Vec};
+use byteorder::*;
+use core::fmt::Write;
+use megstd::io;
+use megstd::time::SystemTime;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FatType {
+    Fat12,
+    Fat16,
+    Fat32,
+}
+
+/// FAT12/16/32 filesystem on a block device
+pub struct FatFs {
+    dev: Arc<dyn BlockDevice>,
+    fat_type: FatType,
+    bytes_per_sector: usize,
+    sectors_per_cluster: usize,
+    dev_sectors_per_sector: u64,
+    n_fats: usize,
+    fat_lba: u64,
+    sectors_per_fat: u64,
+    root_lba: u64,
+    root_sectors: u64,
+    root_cluster: u32,
+    data_lba: u64,
+    max_cluster: u32,
+    fsinfo_lba: Option<u64>,
+    state: Mutex<FatState>,
+}
+
+/// Mutable state of the volume, also used as the lock for every operation
+struct FatState {
+    fat_sector: Option<u64>,
+    fat_buf: Vec<u8>,
+    next_free: u32,
+    fsinfo_valid: bool,
+    inodes: FatINodes,
+    /// Entries of the directory being enumerated by `read_dir`
+    dir_cache: Option<(FatDir, Vec<FatDirEntry>)>,
+}
+
+/// Location of the entries of a directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FatDir {
+    /// Fixed root directory region of FAT12/16
+    Root,
+    Cluster(u32),
+}
+
+/// Numbers the files of the volume, since FAT has no inodes
+///
+/// A file gets its number when it is first looked up. The number follows the short directory
+/// entry of the file when it is renamed, and is never reused after the file is deleted.
+struct FatINodes {
+    next_inode: INodeType,
+    locations: BTreeMap<INodeType, u64>,
+    inodes: BTreeMap<u64, NonZeroINodeType>,
+}
+
+impl FatINodes {
+    #[inline]
+    const fn new(first_inode: INodeType) -> Self {
+        Self {
+            next_inode: first_inode,
+            locations: BTreeMap::new(),
+            inodes: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the inode of the short directory entry at the location
+    fn inode_of(&mut self, location: u64) -> NonZeroINodeType {
+        if let Some(inode) = self.inodes.get(&location) {
+            return *inode;
+        }
+        let inode = NonZeroINodeType::new(self.next_inode).unwrap();
+        self.next_inode += 1;
+        self.inodes.insert(location, inode);
+        self.locations.insert(inode.get(), location);
+        inode
+    }
+
+    /// Returns the location of the short directory entry, or `NotFound` if the file was deleted
+    fn location_of(&self, inode: NonZeroINodeType) -> io::Result<u64> {
+        self.locations
+            .get(&inode.get())
+            .copied()
+            .ok_or(io::ErrorKind::NotFound.into())
+    }
+
+    fn moved(&mut self, from: u64, to: u64) {
+        if let Some(inode) = self.inodes.remove(&from) {
+            self.inodes.insert(to, inode);
+            self.locations.insert(inode.get(), to);
+        }
+    }
+
+    fn remove(&mut self, location: u64) {
+        if let Some(inode) = self.inodes.remove(&location) {
+            self.locations.remove(&inode.get());
+        }
+    }
+}
+
+impl FatFs {
+    const INODE_ROOT: INodeType = 1;
+    const SIZE_OF_DIRENT: usize = 32;
+    const MAX_LFN_LEN: usize = 255;
+
+    /// Mounts the FAT volume on the block device
+    pub fn new(dev: Arc<dyn BlockDevice>) -> io::Result<Self> {
+        let dev_sector_size = dev.sector_size();
+        let count = (BootSector::PREFERRED_SIZE + dev_sector_size - 1) / dev_sector_size;
+        let mut boot = Vec::new();
+        boot.resize(count * dev_sector_size, 0);
+        dev.read_sectors(0, &mut boot)?;
+        let bpb = BootSector::from_bytes(&boot).ok_or(io::ErrorKind::InvalidData)?;
+
+        let bytes_per_sector = bpb.bytes_per_sector as usize;
+        if bytes_per_sector % dev_sector_size != 0 {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+        let sectors_per_cluster = bpb.sectors_per_cluster as usize;
+        let root_sectors =
+            ((bpb.root_entries_count as usize * Self::SIZE_OF_DIRENT + bytes_per_sector - 1)
+                / bytes_per_sector) as u64;
+        let fat_lba = bpb.reserved_sectors_count as u64;
+        let sectors_per_fat = bpb.sectors_per_fat as u64;
+        let n_fats = bpb.n_fats as usize;
+        let root_lba = fat_lba + sectors_per_fat * n_fats as u64;
+        let data_lba = root_lba + root_sectors;
+        if data_lba >= bpb.total_sectors {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+        let n_clusters = (bpb.total_sectors - data_lba) / sectors_per_cluster as u64;
+        let fat_type = if n_clusters < 4085 {
+            FatType::Fat12
+        } else if n_clusters < 65525 {
+            FatType::Fat16
+        } else {
+            FatType::Fat32
+        };
+        if (fat_type == FatType::Fat32) != (bpb.root_entries_count == 0) {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+        let max_cluster = u32::min(
+            n_clusters as u32 + 1,
+            ((sectors_per_fat as usize * bytes_per_sector * 2) / Self::fat_nibbles(fat_type) - 1)
+                as u32,
+        );
+
+        let mut fat_buf = Vec::new();
+        fat_buf.resize(bytes_per_sector, 0);
+
+        Ok(Self {
+            dev,
+            fat_type,
+            bytes_per_sector,
+            sectors_per_cluster,
+            dev_sectors_per_sector: (bytes_per_sector / dev_sector_size) as u64,
+            n_fats,
+            fat_lba,
+            sectors_per_fat,
+            root_lba,
+            root_sectors,
+            root_cluster: bpb.root_cluster,
+            data_lba,
+            max_cluster,
+            fsinfo_lba: bpb.fsinfo_sector.map(|v| v as u64),
+            state: Mutex::new(FatState {
+                fat_sector: None,
+                fat_buf,
+                next_free: 2,
+                fsinfo_valid: true,
+                inodes: FatINodes::new(Self::INODE_ROOT + 1),
+                dir_cache: None,
+            }),
+        })
+    }
+
+    #[inline]
+    pub const fn fat_type(&self) -> FatType {
+        self.fat_type
+    }
+
+    #[inline]
+    const fn fat_nibbles(fat_type: FatType) -> usize {
+        match fat_type {
+            FatType::Fat12 => 3,
+            FatType::Fat16 => 4,
+            FatType::Fat32 => 8,
+        }
+    }
+
+    #[inline]
+    fn bytes_per_cluster(&self) -> usize {
+        self.bytes_per_sector * self.sectors_per_cluster
+    }
+
+    fn read_sector(&self, lba: u64, buf: &mut [u8]) -> io::Result<()> {
+        self.dev
+            .read_sectors(lba * self.dev_sectors_per_sector, buf)
+    }
+
+    fn write_sector(&self, lba: u64, buf: &[u8]) -> io::Result<()> {
+        if self.dev.is_read_only() {
+            return Err(io::ErrorKind::ReadOnlyFilesystem.into());
+        }
+        self.dev
+            .write_sectors(lba * self.dev_sectors_per_sector, buf)
+    }
+
+    #[inline]
+    fn sector_buf(&self) -> Vec<u8> {
+        let mut vec = Vec::new();
+        vec.resize(self.bytes_per_sector, 0);
+        vec
+    }
+
+    #[inline]
+    fn cluster_lba(&self, cluster: u32) -> u64 {
+        self.data_lba + (cluster as u64 - 2) * self.sectors_per_cluster as u64
+    }
+
+    #[inline]
+    fn is_valid_cluster(&self, cluster: u32) -> bool {
+        cluster >= 2 && cluster <= self.max_cluster
+    }
+
+    #[inline]
+    const fn end_of_chain(&self) -> u32 {
+        match self.fat_type {
+            FatType::Fat12 => 0xFFF,
+            FatType::Fat16 => 0xFFFF,
+            FatType::Fat32 => 0x0FFF_FFFF,
+        }
+    }
+
+    // FAT entries
+
+    fn load_fat_sector(&self, state: &mut FatState, sector: u64) -> io::Result<()> {
+        if state.fat_sector != Some(sector) {
+            state.fat_sector = None;
+            self.read_sector(self.fat_lba + sector, &mut state.fat_buf)?;
+            state.fat_sector = Some(sector);
+        }
+        Ok(())
+    }
+
+    /// Writes the cached FAT sector through to every copy of the FAT
+    fn store_fat_sector(&self, state: &mut FatState) -> io::Result<()> {
+        if let Some(sector) = state.fat_sector {
+            for index in 0..self.n_fats as u64 {
+                let lba = self.fat_lba + index * self.sectors_per_fat + sector;
+                self.write_sector(lba, &state.fat_buf)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn fat_byte(&self, state: &mut FatState, pos: usize) -> io::Result<u8> {
+        self.load_fat_sector(state, (pos / self.bytes_per_sector) as u64)?;
+        Ok(state.fat_buf[pos % self.bytes_per_sector])
+    }
+
+    fn update_fat_byte<F>(&self, state: &mut FatState, pos: usize, f: F) -> io::Result<()>
+    where
+        F: FnOnce(u8) -> u8,
+    {
+        self.load_fat_sector(state, (pos / self.bytes_per_sector) as u64)?;
+        let offset = pos % self.bytes_per_sector;
+        state.fat_buf[offset] = f(state.fat_buf[offset]);
+        self.store_fat_sector(state)
+    }
+
+    fn get_fat(&self, state: &mut FatState, cluster: u32) -> io::Result<u32> {
+        let index = cluster as usize;
+        match self.fat_type {
+            FatType::Fat12 => {
+                let pos = index + index / 2;
+                let value = self.fat_byte(state, pos)? as u32
+                    | (self.fat_byte(state, pos + 1)? as u32) << 8;
+                if (index & 1) != 0 {
+                    Ok(value >> 4)
+                } else {
+                    Ok(value & 0xFFF)
+                }
+            }
+            FatType::Fat16 => {
+                let pos = index * 2;
+                self.load_fat_sector(state, (pos / self.bytes_per_sector) as u64)?;
+                let offset = pos % self.bytes_per_sector;
+                Ok(LE::read_u16(&state.fat_buf[offset..offset + 2]) as u32)
+            }
+            FatType::Fat32 => {
+                let pos = index * 4;
+                self.load_fat_sector(state, (pos / self.bytes_per_sector) as u64)?;
+                let offset = pos % self.bytes_per_sector;
+                Ok(LE::read_u32(&state.fat_buf[offset..offset + 4]) & 0x0FFF_FFFF)
+            }
+        }
+    }
+
+    fn set_fat(&self, state: &mut FatState, cluster: u32, value: u32) -> io::Result<()> {
+        let index = cluster as usize;
+        match self.fat_type {
+            FatType::Fat12 => {
+                let pos = index + index / 2;
+                if (index & 1) != 0 {
+                    self.update_fat_byte(state, pos, |v| (v & 0x0F) | (value << 4) as u8)?;
+                    self.update_fat_byte(state, pos + 1, |_| (value >> 4) as u8)
+                } else {
+                    self.update_fat_byte(state, pos, |_| value as u8)?;
+                    self.update_fat_byte(state, pos + 1, |v| {
+                        (v & 0xF0) | ((value >> 8) as u8 & 0x0F)
+                    })
+                }
+            }
+            FatType::Fat16 => {
+                let pos = index * 2;
+                self.load_fat_sector(state, (pos / self.bytes_per_sector) as u64)?;
+                let offset = pos % self.bytes_per_sector;
+                LE::write_u16(&mut state.fat_buf[offset..offset + 2], value as u16);
+                self.store_fat_sector(state)
+            }
+            FatType::Fat32 => {
+                let pos = index * 4;
+                self.load_fat_sector(state, (pos / self.bytes_per_sector) as u64)?;
+                let offset = pos % self.bytes_per_sector;
+                let slice = &mut state.fat_buf[offset..offset + 4];
+                let value = (LE::read_u32(slice) & 0xF000_0000) | (value & 0x0FFF_FFFF);
+                LE::write_u32(slice, value);
+                self.store_fat_sector(state)
+            }
+        }
+    }
+
+    /// Returns the next cluster of the chain, or `None` at the end of the chain
+    fn next_cluster(&self, state: &mut FatState, cluster: u32) -> io::Result<Option<u32>> {
+        let next = self.get_fat(state, cluster)?;
+        if self.is_valid_cluster(next) {
+            Ok(Some(next))
+        } else if next >= (self.end_of_chain() & !7) {
+            Ok(None)
+        } else {
+            Err(io::ErrorKind::InvalidData.into())
+        }
+    }
+
+    /// Allocates a free cluster and links it after `prev`
+    fn alloc_cluster(&self, state: &mut FatState, prev: Option<u32>) -> io::Result<u32> {
+        let n_clusters = self.max_cluster - 1;
+        let mut cluster = state.next_free;
+        for _ in 0..n_clusters {
+            if !self.is_valid_cluster(cluster) {
+                cluster = 2;
+            }
+            if self.get_fat(state, cluster)? == 0 {
+                self.invalidate_fsinfo(state)?;
+                self.set_fat(state, cluster, self.end_of_chain())?;
+                if let Some(prev) = prev {
+                    self.set_fat(state, prev, cluster)?;
+                }
+                state.next_free = cluster + 1;
+                return Ok(cluster);
+            }
+            cluster += 1;
+        }
+        Err(io::ErrorKind::StorageFull.into())
+    }
+
+    /// Frees the chain starting at the cluster
+    fn free_chain(&self, state: &mut FatState, cluster: u32) -> io::Result<()> {
+        let mut cluster = cluster;
+        while self.is_valid_cluster(cluster) {
+            let next = self.get_fat(state, cluster)?;
+            self.set_fat(state, cluster, 0)?;
+            state.next_free = u32::min(state.next_free, cluster);
+            cluster = next;
+        }
+        Ok(())
+    }
+
+    /// Fills the cluster with zero
+    fn clear_cluster(&self, cluster: u32) -> io::Result<()> {
+        let buf = self.sector_buf();
+        let lba = self.cluster_lba(cluster);
+        for index in 0..self.sectors_per_cluster as u64 {
+            self.write_sector(lba + index, &buf)?;
+        }
+        Ok(())
+    }
+
+    /// Marks the free cluster count of FSInfo as unknown before the first allocation
+    fn invalidate_fsinfo(&self, state: &mut FatState) -> io::Result<()> {
+        if !state.fsinfo_valid {
+            return Ok(());
+        }
+        state.fsinfo_valid = false;
+        if let Some(lba) = self.fsinfo_lba {
+            let mut buf = self.sector_buf();
+            self.read_sector(lba, &mut buf)?;
+            if LE::read_u32(&buf[0..4]) == 0x4161_5252
+                && LE::read_u32(&buf[484..488]) == 0x6141_7272
+            {
+                LE::write_u32(&mut buf[488..492], u32::MAX);
+                LE::write_u32(&mut buf[492..496], u32::MAX);
+                self.write_sector(lba, &buf)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Directories
+
+    fn dir_of(&self, state: &FatState, inode: NonZeroINodeType) -> io::Result<FatDir> {
+        if inode.get() == Self::INODE_ROOT {
+            return Ok(match self.fat_type {
+                FatType::Fat32 => FatDir::Cluster(self.root_cluster),
+                _ => FatDir::Root,
+            });
+        }
+        let dir_ent = self.read_dir_ent(state.inodes.location_of(inode)?)?;
+        if !dir_ent.attr().contains(DosAttributes::SUBDIR) {
+            return Err(io::ErrorKind::NotADirectory.into());
+        }
+        Ok(FatDir::Cluster(dir_ent.cluster()))
+    }
+
+    /// Returns the cluster that ".." of a subdirectory of the directory points to
+    fn parent_cluster(&self, state: &FatState, dir: NonZeroINodeType) -> io::Result<u32> {
+        if dir.get() == Self::INODE_ROOT {
+            // even on FAT32, ".." of the root's subdirectories is 0
+            return Ok(0);
+        }
+        match self.dir_of(state, dir)? {
+            FatDir::Cluster(v) => Ok(v),
+            FatDir::Root => Ok(0),
+        }
+    }
+
+    /// Returns the sectors that hold the entries of the directory
+    fn dir_sectors(&self, state: &mut FatState, dir: FatDir) -> io::Result<Vec<u64>> {
+        let mut sectors = Vec::new();
+        match dir {
+            FatDir::Root => {
+                sectors.extend(self.root_lba..self.root_lba + self.root_sectors);
+            }
+            FatDir::Cluster(cluster) => {
+                let mut cluster = Some(cluster);
+                while let Some(current) = cluster {
+                    if !self.is_valid_cluster(current) {
+                        return Err(io::ErrorKind::InvalidData.into());
+                    }
+                    let lba = self.cluster_lba(current);
+                    sectors.extend(lba..lba + self.sectors_per_cluster as u64);
+                    cluster = self.next_cluster(state, current)?;
+                }
+            }
+        }
+        Ok(sectors)
+    }
+
+    /// Reads all entries of the directory, joining long file names with their short entries
+    fn read_dir_entries(&self, state: &mut FatState, dir: FatDir) -> io::Result<Vec<FatDirEntry>> {
+        let mut result = Vec::new();
+        let mut lfn = LfnBuilder::new();
+        let mut buf = self.sector_buf();
+        'sectors: for lba in self.dir_sectors(state, dir)? {
+            self.read_sector(lba, &mut buf)?;
+            for (index, raw) in buf.chunks_exact(Self::SIZE_OF_DIRENT).enumerate() {
+                let location =
+                    lba * self.bytes_per_sector as u64 + (index * Self::SIZE_OF_DIRENT) as u64;
+                let dir_ent = DosDirEnt::from_bytes(raw);
+                match dir_ent.name[0] {
+                    0 => break 'sectors,
+                    DosDirEnt::DELETED => {
+                        lfn.reset();
+                        continue;
+                    }
+                    _ => (),
+                }
+                if dir_ent.attr() == DosAttributes::LFN_ENTRY {
+                    lfn.push(&dir_ent, location);
+                    continue;
+                }
+                if dir_ent.attr().contains(DosAttributes::LABEL) || dir_ent.is_dot_entry() {
+                    lfn.reset();
+                    continue;
+                }
+                let (name, lfn_locations) = match lfn.take(dir_ent.checksum()) {
+                    Some(v) => v,
+                    None => (dir_ent.short_name(), Vec::new()),
+                };
+                result.push(FatDirEntry {
+                    location,
+                    lfn_locations,
+                    name,
+                    dir_ent,
+                });
+            }
+        }
+        Ok(result)
+    }
+
+    fn find_entry(&self, state: &mut FatState, dir: FatDir, name: &str) -> io::Result<FatDirEntry> {
+        self.read_dir_entries(state, dir)?
+            .into_iter()
+            .find(|v| v.matches(name))
+            .ok_or(io::ErrorKind::NotFound.into())
+    }
+
+    fn read_dir_ent(&self, location: u64) -> io::Result<DosDirEnt> {
+        let lba = location / self.bytes_per_sector as u64;
+        let offset = (location % self.bytes_per_sector as u64) as usize;
+        if lba < self.root_lba {
+            return Err(io::ErrorKind::NotFound.into());
+        }
+        let mut buf = self.sector_buf();
+        self.read_sector(lba, &mut buf)?;
+        let dir_ent = DosDirEnt::from_bytes(&buf[offset..offset + Self::SIZE_OF_DIRENT]);
+        if dir_ent.name[0] == 0
+            || dir_ent.name[0] == DosDirEnt::DELETED
+            || dir_ent.attr() == DosAttributes::LFN_ENTRY
+        {
+            return Err(io::ErrorKind::NotFound.into());
+        }
+        Ok(dir_ent)
+    }
+
+    fn write_raw_entry(&self, state: &mut FatState, location: u64, raw: &[u8]) -> io::Result<()> {
+        state.dir_cache = None;
+        let lba = location / self.bytes_per_sector as u64;
+        let offset = (location % self.bytes_per_sector as u64) as usize;
+        let mut buf = self.sector_buf();
+        self.read_sector(lba, &mut buf)?;
+        buf[offset..offset + Self::SIZE_OF_DIRENT].copy_from_slice(raw);
+        self.write_sector(lba, &buf)
+    }
+
+    /// Finds `count` consecutive free entries in the directory, extending it if needed
+    fn alloc_entries(
+        &self,
+        state: &mut FatState,
+        dir: FatDir,
+        count: usize,
+    ) -> io::Result<Vec<u64>> {
+        let mut result = Vec::with_capacity(count);
+        let mut buf = self.sector_buf();
+        let sectors = self.dir_sectors(state, dir)?;
+        for lba in &sectors {
+            self.read_sector(*lba, &mut buf)?;
+            for (index, raw) in buf.chunks_exact(Self::SIZE_OF_DIRENT).enumerate() {
+                if raw[0] == 0 || raw[0] == DosDirEnt::DELETED {
+                    result.push(
+                        lba * self.bytes_per_sector as u64 + (index * Self::SIZE_OF_DIRENT) as u64,
+                    );
+                    if result.len() == count {
+                        return Ok(result);
+                    }
+                } else {
+                    result.clear();
+                }
+            }
+        }
+
+        let mut last = match dir {
+            FatDir::Root => return Err(io::ErrorKind::StorageFull.into()),
+            FatDir::Cluster(cluster) => cluster,
+        };
+        while let Some(next) = self.next_cluster(state, last)? {
+            last = next;
+        }
+        while result.len() < count {
+            let cluster = self.alloc_cluster(state, Some(last))?;
+            self.clear_cluster(cluster)?;
+            let base = self.cluster_lba(cluster) * self.bytes_per_sector as u64;
+            let entries_per_cluster = self.bytes_per_cluster() / Self::SIZE_OF_DIRENT;
+            for index in 0..entries_per_cluster {
+                if result.len() == count {
+                    break;
+                }
+                result.push(base + (index * Self::SIZE_OF_DIRENT) as u64);
+            }
+            last = cluster;
+        }
+        Ok(result)
+    }
+
+    /// Writes the long and short entries of a new name and returns the location of the short one
+    fn write_name(
+        &self,
+        state: &mut FatState,
+        dir: FatDir,
+        name: &str,
+        dir_ent: &mut DosDirEnt,
+    ) -> io::Result<u64> {
+        let entries = self.read_dir_entries(state, dir)?;
+        let lfn = match DosDirEnt::short_name_for(name) {
+            Some((short_name, nt_reserved)) => {
+                dir_ent.name = short_name;
+                dir_ent.nt_reserved = nt_reserved;
+                None
+            }
+            None => {
+                dir_ent.name = DosDirEnt::numbered_tail(name, |v| {
+                    entries.iter().any(|entry| entry.dir_ent.name == *v)
+                })
+                .ok_or(io::ErrorKind::AlreadyExists)?;
+                dir_ent.nt_reserved = 0;
+                Some(name.encode_utf16().collect::<Vec<_>>())
+            }
+        };
+
+        let n_lfn = lfn
+            .as_ref()
+            .map(|v| (v.len() + LfnBuilder::CHARS_PER_ENTRY - 1) / LfnBuilder::CHARS_PER_ENTRY)
+            .unwrap_or(0);
+        let locations = self.alloc_entries(state, dir, n_lfn + 1)?;
+        if let Some(lfn) = lfn {
+            let checksum = dir_ent.checksum();
+            for (index, location) in locations.iter().take(n_lfn).enumerate() {
+                let seq = n_lfn - index;
+                let raw = LfnBuilder::entry(&lfn, seq, seq == n_lfn, checksum);
+                self.write_raw_entry(state, *location, &raw)?;
+            }
+        }
+        let location = locations[n_lfn];
+        self.write_raw_entry(state, location, dir_ent.as_bytes())?;
+        Ok(location)
+    }
+
+    /// Marks the entry and its long name as deleted
+    fn delete_name(&self, state: &mut FatState, entry: &FatDirEntry) -> io::Result<()> {
+        state.dir_cache = None;
+        for location in entry
+            .lfn_locations
+            .iter()
+            .chain(core::iter::once(&entry.location))
+        {
+            let lba = location / self.bytes_per_sector as u64;
+            let offset = (location % self.bytes_per_sector as u64) as usize;
+            let mut buf = self.sector_buf();
+            self.read_sector(lba, &mut buf)?;
+            buf[offset] = DosDirEnt::DELETED;
+            self.write_sector(lba, &buf)?;
+        }
+        Ok(())
+    }
+
+    /// Checks that the entry is neither read-only nor a directory with entries
+    fn check_removable(&self, state: &mut FatState, entry: &FatDirEntry) -> io::Result<()> {
+        let attr = entry.dir_ent.attr();
+        if attr.contains(DosAttributes::READONLY) {
+            return Err(io::ErrorKind::PermissionDenied.into());
+        }
+        if attr.contains(DosAttributes::SUBDIR) {
+            let subdir = FatDir::Cluster(entry.dir_ent.cluster());
+            if !self.read_dir_entries(state, subdir)?.is_empty() {
+                return Err(io::ErrorKind::DirectoryNotEmpty.into());
+            }
+        }
+        Ok(())
+    }
+
+    // File data
+
+    /// Returns the cluster holding the byte at `offset`, allocating clusters if `alloc` is set
+    ///
+    /// `cursor` keeps the last cluster found, so that sequential access does not walk the chain
+    /// from the beginning every time.
+    fn cluster_at(
+        &self,
+        state: &mut FatState,
+        dir_ent: &mut DosDirEnt,
+        offset: u64,
+        alloc: bool,
+        cursor: &mut Option<(u64, u32)>,
+    ) -> io::Result<Option<u32>> {
+        let index = offset / self.bytes_per_cluster() as u64;
+        let (mut current, mut cluster) = match *cursor {
+            Some((current, cluster)) if current <= index => (current, cluster),
+            _ => {
+                let mut cluster = dir_ent.cluster();
+                if !self.is_valid_cluster(cluster) {
+                    if !alloc {
+                        return Ok(None);
+                    }
+                    cluster = self.alloc_cluster(state, None)?;
+                    dir_ent.set_cluster(cluster);
+                }
+                (0, cluster)
+            }
+        };
+        while current < index {
+            cluster = match self.next_cluster(state, cluster)? {
+                Some(v) => v,
+                None if alloc => self.alloc_cluster(state, Some(cluster))?,
+                None => return Ok(None),
+            };
+            current += 1;
+        }
+        *cursor = Some((index, cluster));
+        Ok(Some(cluster))
+    }
+
+    /// Writes the data, or zeros if `data` is `None`, and extends the file as needed
+    fn write_at(
+        &self,
+        state: &mut FatState,
+        dir_ent: &mut DosDirEnt,
+        offset: u64,
+        data: Option<&[u8]>,
+        len: usize,
+    ) -> io::Result<()> {
+        let mut buf = self.sector_buf();
+        let bps = self.bytes_per_sector as u64;
+        let mut cursor = None;
+        let mut done = 0;
+        while done < len {
+            let pos = offset + done as u64;
+            let cluster = self
+                .cluster_at(state, dir_ent, pos, true, &mut cursor)?
+                .ok_or(io::ErrorKind::InvalidData)?;
+            let in_cluster = pos % self.bytes_per_cluster() as u64;
+            let lba = self.cluster_lba(cluster) + in_cluster / bps;
+            let in_sector = (pos % bps) as usize;
+            let count = usize::min(len - done, self.bytes_per_sector - in_sector);
+            if count < self.bytes_per_sector {
+                self.read_sector(lba, &mut buf)?;
+            }
+            let dst = &mut buf[in_sector..in_sector + count];
+            match data {
+                Some(data) => dst.copy_from_slice(&data[done..done + count]),
+                None => dst.fill(0),
+            }
+            self.write_sector(lba, &buf)?;
+            done += count;
+        }
+        Ok(())
+    }
+
+    fn update_entry(
+        &self,
+        state: &mut FatState,
+        location: u64,
+        dir_ent: &mut DosDirEnt,
+    ) -> io::Result<()> {
+        let now = DosFileTimeStamp::from_system_time(System::system_time());
+        dir_ent.mtime = now.0;
+        dir_ent.atime = now.0.date;
+        dir_ent.attr = dir_ent.attr() | DosAttributes::ARCHIVE;
+        self.write_raw_entry(state, location, dir_ent.as_bytes())
+    }
+
+    /// Returns the location and the entry of the regular file
+    fn file_dir_ent(
+        &self,
+        state: &FatState,
+        inode: NonZeroINodeType,
+    ) -> io::Result<(u64, DosDirEnt)> {
+        if inode.get() == Self::INODE_ROOT {
+            return Err(io::ErrorKind::IsADirectory.into());
+        }
+        let location = state.inodes.location_of(inode)?;
+        let dir_ent = self.read_dir_ent(location)?;
+        if dir_ent.attr().contains(DosAttributes::SUBDIR) {
+            return Err(io::ErrorKind::IsADirectory.into());
+        }
+        Ok((location, dir_ent))
+    }
+
+    fn writable_file_dir_ent(
+        &self,
+        state: &FatState,
+        inode: NonZeroINodeType,
+    ) -> io::Result<(u64, DosDirEnt)> {
+        let (location, dir_ent) = self.file_dir_ent(state, inode)?;
+        if dir_ent.attr().contains(DosAttributes::READONLY) {
+            return Err(io::ErrorKind::PermissionDenied.into());
+        }
+        Ok((location, dir_ent))
+    }
+}
+
+impl FsDriver for FatFs {
+    #[inline]
+    fn name(&self) -> &str {
+        match self.fat_type {
+            FatType::Fat12 => "fat12",
+            FatType::Fat16 => "fat16",
+            FatType::Fat32 => "fat32",
+        }
+    }
+
+    #[inline]
+    fn root_dir(&self) -> NonZeroINodeType {
+        NonZeroINodeType::new(Self::INODE_ROOT).unwrap()
+    }
+
+    fn read_dir(&self, dir: NonZeroINodeType, index: usize) -> Option<FsRawDirEntry> {
+        let mut state = self.state.lock().unwrap();
+        let dir = self.dir_of(&state, dir).ok()?;
+        // The entries are read again when the enumeration starts over or the directory changes
+        if index == 0 || state.dir_cache.as_ref().map(|v| v.0) != Some(dir) {
+            let entries = self.read_dir_entries(&mut state, dir).ok()?;
+            state.dir_cache = Some((dir, entries));
+        }
+        let state = &mut *state;
+        let entry = state.dir_cache.as_ref()?.1.get(index)?;
+        let inode = state.inodes.inode_of(entry.location);
+        let metadata = entry.dir_ent.metadata();
+        Some(FsRawDirEntry::new(
+            inode,
+            entry.name.clone(),
+            Some(metadata),
+        ))
+    }
+
+    fn find_file(&self, dir: NonZeroINodeType, lpc: &str) -> io::Result<NonZeroINodeType> {
+        let mut state = self.state.lock().unwrap();
+        let dir = self.dir_of(&state, dir)?;
+        self.find_entry(&mut state, dir, lpc)
+            .map(|v| state.inodes.inode_of(v.location))
+    }
+
+    fn stat(&self, inode: NonZeroINodeType) -> Option<FsRawMetaData> {
+        if inode.get() == Self::INODE_ROOT {
+            return Some(FsRawMetaData::new(FsRawFileType::Directory, 0));
+        }
+        let state = self.state.lock().unwrap();
+        let location = state.inodes.location_of(inode).ok()?;
+        self.read_dir_ent(location).ok().map(|v| v.metadata())
+    }
+
+    fn read_data(
+        &self,
+        inode: NonZeroINodeType,
+        offset: OffsetType,
+        buf: &mut [u8],
+    ) -> io::Result<usize> {
+        let mut state = self.state.lock().unwrap();
+        let (_, mut dir_ent) = self.file_dir_ent(&state, inode)?;
+        let file_size = dir_ent.file_size as u64;
+        if offset < 0 {
+            return Err(io::ErrorKind::InvalidInput.into());
+        }
+        let offset = u64::min(offset as u64, file_size);
+        let len = u64::min(file_size - offset, buf.len() as u64) as usize;
+
+        let mut sector = self.sector_buf();
+        let bps = self.bytes_per_sector as u64;
+        let mut cursor = None;
+        let mut done = 0;
+        while done < len {
+            let pos = offset + done as u64;
+            let cluster = self
+                .cluster_at(&mut state, &mut dir_ent, pos, false, &mut cursor)?
+                .ok_or(io::ErrorKind::InvalidData)?;
+            let in_cluster = pos % self.bytes_per_cluster() as u64;
+            let lba = self.cluster_lba(cluster) + in_cluster / bps;
+            let in_sector = (pos % bps) as usize;
+            let count = usize::min(len - done, self.bytes_per_sector - in_sector);
+            self.read_sector(lba, &mut sector)?;
+            buf[done..done + count].copy_from_slice(&sector[in_sector..in_sector + count]);
+            done += count;
+        }
+        Ok(len)
+    }
+
+    fn write_data(
+        &self,
+        inode: NonZeroINodeType,
+        offset: OffsetType,
+        buf: &[u8],
+    ) -> io::Result<usize> {
+        let mut state = self.state.lock().unwrap();
+        let (location, mut dir_ent) = self.writable_file_dir_ent(&state, inode)?;
+        let file_size = dir_ent.file_size as u64;
+        let end = (offset as u64)
+            .checked_add(buf.len() as u64)
+            .filter(|v| offset >= 0 && *v <= u32::MAX as u64)
+            .ok_or(io::ErrorKind::InvalidInput)?;
+        let offset = offset as u64;
+
+        let result = if offset > file_size {
+            self.write_at(
+                &mut state,
+                &mut dir_ent,
+                file_size,
+                None,
+                (offset - file_size) as usize,
+            )
+        } else {
+            Ok(())
+        }
+        .and_then(|_| self.write_at(&mut state, &mut dir_ent, offset, Some(buf), buf.len()));
+        // The allocated clusters are saved anyway, but the size grows only on success
+        if result.is_ok() && end > file_size {
+            dir_ent.file_size = end as u32;
+        }
+        self.update_entry(&mut state, location, &mut dir_ent)?;
+        result.map(|_| buf.len())
+    }
+
+    fn set_len(&self, inode: NonZeroINodeType, len: OffsetType) -> io::Result<()> {
+        if len < 0 || len > u32::MAX as OffsetType {
+            return Err(io::ErrorKind::InvalidInput.into());
+        }
+        let len = len as u64;
+        let mut state = self.state.lock().unwrap();
+        let (location, mut dir_ent) = self.writable_file_dir_ent(&state, inode)?;
+        let file_size = dir_ent.file_size as u64;
+
+        if len > file_size {
+            let result = self.write_at(
+                &mut state,
+                &mut dir_ent,
+                file_size,
+                None,
+                (len - file_size) as usize,
+            );
+            if result.is_ok() {
+                dir_ent.file_size = len as u32;
+            }
+            self.update_entry(&mut state, location, &mut dir_ent)?;
+            return result;
+        }
+        if len == 0 {
+            let first = dir_ent.cluster();
+            dir_ent.set_cluster(0);
+            dir_ent.file_size = 0;
+            self.update_entry(&mut state, location, &mut dir_ent)?;
+            return self.free_chain(&mut state, first);
+        }
+        if let Some(last) = self.cluster_at(&mut state, &mut dir_ent, len - 1, false, &mut None)? {
+            if let Some(next) = self.next_cluster(&mut state, last)? {
+                self.set_fat(&mut state, last, self.end_of_chain())?;
+                self.free_chain(&mut state, next)?;
+            }
+        }
+        dir_ent.file_size = len as u32;
+        self.update_entry(&mut state, location, &mut dir_ent)
+    }
+
+    fn create(
+        &self,
+        dir: NonZeroINodeType,
+        name: &str,
+        file_type: FsRawFileType,
+    ) -> io::Result<NonZeroINodeType> {
+        if !DosDirEnt::is_valid_long_name(name) {
+            return Err(io::ErrorKind::InvalidInput.into());
+        }
+        let mut state = self.state.lock().unwrap();
+        let parent_cluster = self.parent_cluster(&state, dir)?;
+        let dir = self.dir_of(&state, dir)?;
+        if self.find_entry(&mut state, dir, name).is_ok() {
+            return Err(io::ErrorKind::AlreadyExists.into());
+        }
+
+        let now = DosFileTimeStamp::from_system_time(System::system_time());
+        let mut dir_ent = DosDirEnt::new();
+        dir_ent.ctime = now.0;
+        dir_ent.ctime_ms = now.1;
+        dir_ent.mtime = now.0;
+        dir_ent.atime = now.0.date;
+        match file_type {
+            FsRawFileType::RegularFile => {
+                dir_ent.attr = DosAttributes::ARCHIVE;
+            }
+            FsRawFileType::Directory => {
+                dir_ent.attr = DosAttributes::SUBDIR;
+                let cluster = self.alloc_cluster(&mut state, None)?;
+                self.clear_cluster(cluster)?;
+                dir_ent.set_cluster(cluster);
+
+                let mut dot = dir_ent;
+                dot.name = *b".          ";
+                let mut dotdot = dir_ent;
+                dotdot.name = *b"..         ";
+                dotdot.set_cluster(parent_cluster);
+                let base = self.cluster_lba(cluster) * self.bytes_per_sector as u64;
+                self.write_raw_entry(&mut state, base, dot.as_bytes())?;
+                self.write_raw_entry(
+                    &mut state,
+                    base + Self::SIZE_OF_DIRENT as u64,
+                    dotdot.as_bytes(),
+                )?;
+            }
+        }
+
+        match self.write_name(&mut state, dir, name, &mut dir_ent) {
+            Ok(v) => Ok(state.inodes.inode_of(v)),
+            Err(err) => {
+                self.free_chain(&mut state, dir_ent.cluster())?;
+                Err(err)
+            }
+        }
+    }
+
+    fn unlink(&self, dir: NonZeroINodeType, name: &str) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let dir = self.dir_of(&state, dir)?;
+        let entry = self.find_entry(&mut state, dir, name)?;
+        self.check_removable(&mut state, &entry)?;
+        self.delete_name(&mut state, &entry)?;
+        state.inodes.remove(entry.location);
+        self.free_chain(&mut state, entry.dir_ent.cluster())
+    }
+
+    fn rename(
+        &self,
+        old_dir: NonZeroINodeType,
+        old_name: &str,
+        new_dir: NonZeroINodeType,
+        new_name: &str,
+    ) -> io::Result<()> {
+        if !DosDirEnt::is_valid_long_name(new_name) {
+            return Err(io::ErrorKind::InvalidInput.into());
+        }
+        let mut state = self.state.lock().unwrap();
+        let new_parent_cluster = self.parent_cluster(&state, new_dir)?;
+        let old_dir = self.dir_of(&state, old_dir)?;
+        let new_dir = self.dir_of(&state, new_dir)?;
+        let entry = self.find_entry(&mut state, old_dir, old_name)?;
+        let target = match self.find_entry(&mut state, new_dir, new_name) {
+            // Only the case of the name changes
+            Ok(v) if v.location == entry.location => None,
+            Ok(v) => Some(v),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+            Err(err) => return Err(err),
+        };
+
+        let mut dir_ent = entry.dir_ent;
+        match target {
+            Some(target) => {
+                // The short entry of the target is overwritten in place, so that the target
+                // remains as it was if anything fails before that
+                self.check_removable(&mut state, &target)?;
+                dir_ent.name = target.dir_ent.name;
+                dir_ent.nt_reserved = target.dir_ent.nt_reserved;
+                self.write_raw_entry(&mut state, target.location, dir_ent.as_bytes())?;
+                state.inodes.remove(target.location);
+                self.delete_name(&mut state, &entry)?;
+                state.inodes.moved(entry.location, target.location);
+                self.free_chain(&mut state, target.dir_ent.cluster())?;
+            }
+            None => {
+                let location = self.write_name(&mut state, new_dir, new_name, &mut dir_ent)?;
+                self.delete_name(&mut state, &entry)?;
+                state.inodes.moved(entry.location, location);
+            }
+        }
+
+        if dir_ent.attr().contains(DosAttributes::SUBDIR) && old_dir != new_dir {
+            let lba = self.cluster_lba(dir_ent.cluster());
+            let location = lba * self.bytes_per_sector as u64 + Self::SIZE_OF_DIRENT as u64;
+            let mut buf = self.sector_buf();
+            self.read_sector(lba, &mut buf)?;
+            let mut dotdot =
+                DosDirEnt::from_bytes(&buf[Self::SIZE_OF_DIRENT..Self::SIZE_OF_DIRENT * 2]);
+            if dotdot.is_dot_entry() {
+                dotdot.set_cluster(new_parent_cluster);
+                self.write_raw_entry(&mut state, location, dotdot.as_bytes())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The BIOS parameter block of the boot sector
+struct BootSector {
+    bytes_per_sector: u16,
+    sectors_per_cluster: u8,
+    reserved_sectors_count: u16,
+    n_fats: u8,
+    root_entries_count: u16,
+    total_sectors: u64,
+    sectors_per_fat: u32,
+    root_cluster: u32,
+    fsinfo_sector: Option<u16>,
+}
+
+impl BootSector {
+    const PREFERRED_SIZE: usize = 512;
+    const BOOT_SIGNATURE: [u8; 2] = [0x55, 0xAA];
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes[510..512] != Self::BOOT_SIGNATURE {
+            return None;
+        }
+        let bytes_per_sector = LE::read_u16(&bytes[11..13]);
+        let sectors_per_cluster = bytes[13];
+        let reserved_sectors_count = LE::read_u16(&bytes[14..16]);
+        let n_fats = bytes[16];
+        let root_entries_count = LE::read_u16(&bytes[17..19]);
+        let total_sectors16 = LE::read_u16(&bytes[19..21]);
+        let sectors_per_fat16 = LE::read_u16(&bytes[22..24]);
+        let total_sectors32 = LE::read_u32(&bytes[32..36]);
+
+        if !bytes_per_sector.is_power_of_two()
+            || bytes_per_sector < 512
+            || !sectors_per_cluster.is_power_of_two()
+            || reserved_sectors_count == 0
+            || n_fats == 0
+        {
+            return None;
+        }
+
+        let total_sectors = if total_sectors16 != 0 {
+            total_sectors16 as u64
+        } else {
+            total_sectors32 as u64
+        };
+        let (sectors_per_fat, root_cluster, fsinfo_sector) = if sectors_per_fat16 != 0 {
+            (sectors_per_fat16 as u32, 0, None)
+        } else {
+            let fsinfo = LE::read_u16(&bytes[48..50]);
+            (
+                LE::read_u32(&bytes[36..40]),
+                LE::read_u32(&bytes[44..48]),
+                (fsinfo != 0 && fsinfo != 0xFFFF).then(|| fsinfo),
+            )
+        };
+        if sectors_per_fat == 0 {
+            return None;
+        }
+
+        Some(Self {
+            bytes_per_sector,
+            sectors_per_cluster,
+            reserved_sectors_count,
+            n_fats,
+            root_entries_count,
+            total_sectors,
+            sectors_per_fat,
+            root_cluster,
+            fsinfo_sector,
+        })
+    }
+}
+
+bitflags! {
+    pub struct DosAttributes: u8 {
+        const READONLY  = 0b0000_0001;
+        const HIDDEN    = 0b0000_0010;
+        const SYSTEM    = 0b0000_0100;
+        const LABEL     = 0b0000_1000;
+        const SUBDIR    = 0b0001_0000;
+        const ARCHIVE   = 0b0010_0000;
+
+        const LFN_ENTRY = Self::READONLY.bits | Self::HIDDEN.bits | Self::SYSTEM.bits | Self::LABEL.bits;
+    }
+}
+
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DosFileTime(pub u16);
+
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DosFileDate(pub u16);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DosFileTimeStamp {
+    pub time: DosFileTime,
+    pub date: DosFileDate,
+}
+
+impl DosFileTime {
+    pub const EMPTY: Self = Self(0);
+}
+
+impl DosFileDate {
+    pub const EMPTY: Self = Self(0);
+
+    const EPOCH_YEAR: u64 = 1980;
+
+    /// Returns the number of days since the Unix epoch
+    fn days(&self) -> Option<u64> {
+        let year = Self::EPOCH_YEAR + (self.0 >> 9) as u64;
+        let month = ((self.0 >> 5) & 15) as u64;
+        let day = (self.0 & 31) as u64;
+        if !(1..=12).contains(&month) || day < 1 {
+            return None;
+        }
+        // Days from civil: the year is shifted to start in March
+        let year = if month <= 2 { year - 1 } else { year };
+        let era = year / 400;
+        let yoe = year - era * 400;
+        let mp = (month + 9) % 12;
+        let doy = (153 * mp + 2) / 5 + day - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        Some(era * 146097 + doe - 719468)
+    }
+
+    /// Makes the date from the number of days since the Unix epoch
+    fn from_days(days: u64) -> Self {
+        let z = days + 719468;
+        let era = z / 146097;
+        let doe = z - era * 146097;
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+        Self((((year - Self::EPOCH_YEAR) << 9) | (month << 5) | day) as u16)
+    }
+}
+
+impl DosFileTimeStamp {
+    pub const EMPTY: Self = Self {
+        time: DosFileTime::EMPTY,
+        date: DosFileDate::EMPTY,
+    };
+
+    const SECS_PER_DAY: u64 = 86400;
+
+    /// Converts the system time to the timestamp and its 10 ms fraction, within the range of FAT
+    pub fn from_system_time(time: SystemTime) -> (Self, u8) {
+        let min = DosFileDate((1 << 5) | 1).days().unwrap() * Self::SECS_PER_DAY;
+        let max = DosFileDate((127 << 9) | (12 << 5) | 31).days().unwrap() * Self::SECS_PER_DAY
+            + Self::SECS_PER_DAY
+            - 1;
+        let (secs, nanos) = if time.secs < min {
+            (min + time.secs % Self::SECS_PER_DAY, time.nanos)
+        } else if time.secs > max {
+            (max, 0)
+        } else {
+            (time.secs, time.nanos)
+        };
+        let days = secs / Self::SECS_PER_DAY;
+        let tod = secs % Self::SECS_PER_DAY;
+        let hour = tod / 3600;
+        let min = (tod / 60) % 60;
+        let sec = tod % 60;
+        let ms10 = (sec & 1) * 100 + nanos as u64 / 10_000_000;
+        (
+            Self {
+                time: DosFileTime(((hour << 11) | (min << 5) | (sec >> 1)) as u16),
+                date: DosFileDate::from_days(days),
+            },
+            ms10 as u8,
+        )
+    }
+
+    pub fn to_system_time(&self, ms10: u8) -> Option<SystemTime> {
+        let days = self.date.days()?;
+        let hour = (self.time.0 >> 11) as u64;
+        let min = ((self.time.0 >> 5) & 63) as u64;
+        let sec = ((self.time.0 & 31) * 2) as u64;
+        let ms10 = u8::min(ms10, 199) as u64;
+        Some(SystemTime {
+            secs: days * Self::SECS_PER_DAY + hour * 3600 + min * 60 + sec + ms10 / 100,
+            nanos: ((ms10 % 100) * 10_000_000) as u32,
+        })
+    }
+}
+
+#[repr(packed)]
+#[derive(Clone, Copy)]
+pub struct DosDirEnt {
+    pub name: [u8; 11],
+    pub attr: DosAttributes,
+    pub nt_reserved: u8,
+    pub ctime_ms: u8,
+    pub ctime: DosFileTimeStamp,
+    pub atime: DosFileDate,
+    pub cluster_hi: u16,
+    pub mtime: DosFileTimeStamp,
+    pub first_cluster: u16,
+    pub file_size: u32,
+}
+
+impl DosDirEnt {
+    const DELETED: u8 = 0xE5;
+    /// The first byte 0xE5 of a name is stored as 0x05
+    const KANJI_E5: u8 = 0x05;
+    const NT_LOWER_NAME: u8 = 0x08;
+    const NT_LOWER_EXT: u8 = 0x10;
+
+    pub const fn new() -> Self {
+        Self {
+            name: [0x20; 11],
+            attr: DosAttributes::empty(),
+            nt_reserved: 0,
+            ctime_ms: 0,
+            ctime: DosFileTimeStamp::EMPTY,
+            atime: DosFileDate::EMPTY,
+            mtime: DosFileTimeStamp::EMPTY,
+            first_cluster: 0,
+            cluster_hi: 0,
+            file_size: 0,
+        }
+    }
+
+    #[inline]
+    fn from_bytes(bytes: &[u8]) -> Self {
+        assert!(bytes.len() >= FatFs::SIZE_OF_DIRENT);
+        unsafe { (bytes.as_ptr() as *const Self).read_unaligned() }
+    }
+
+    #[inline]
+    fn as_bytes(&self) -> &[u8; 32] {
+        unsafe { &*(self as *const Self as *const [u8; 32]) }
+    }
+
+    #[inline]
+    fn attr(&self) -> DosAttributes {
+        DosAttributes::from_bits_truncate(self.attr.bits())
+    }
+
+    #[inline]
+    fn cluster(&self) -> u32 {
+        self.first_cluster as u32 | (self.cluster_hi as u32) << 16
+    }
+
+    #[inline]
+    fn set_cluster(&mut self, cluster: u32) {
+        self.first_cluster = cluster as u16;
+        self.cluster_hi = (cluster >> 16) as u16;
+    }
+
+    fn metadata(&self) -> FsRawMetaData {
+        let (file_type, len) = if self.attr().contains(DosAttributes::SUBDIR) {
+            (FsRawFileType::Directory, 0)
+        } else {
+            (FsRawFileType::RegularFile, self.file_size as OffsetType)
+        };
+        let ctime = self.ctime;
+        let mtime = self.mtime;
+        let atime = DosFileTimeStamp {
+            time: DosFileTime::EMPTY,
+            date: self.atime,
+        };
+        FsRawMetaData::new(file_type, len).with_timestamps(
+            ctime.to_system_time(self.ctime_ms),
+            mtime.to_system_time(0),
+            atime.to_system_time(0),
+        )
+    }
+
+    #[inline]
+    fn is_dot_entry(&self) -> bool {
+        self.name[0] == b'.'
+    }
+
+    fn checksum(&self) -> u8 {
+        self.name
+            .iter()
+            .fold(0u8, |acc, v| (acc >> 1 | acc << 7).wrapping_add(*v))
+    }
+
+    fn short_name(&self) -> String {
+        let lower_name = (self.nt_reserved & Self::NT_LOWER_NAME) != 0;
+        let lower_ext = (self.nt_reserved & Self::NT_LOWER_EXT) != 0;
+        let name = self.name;
+        let mut result = String::with_capacity(12);
+        for (index, c) in name.iter().enumerate() {
+            let c = match (index, *c) {
+                (0, Self::KANJI_E5) => Self::DELETED,
+                (_, c) => c,
+            };
+            if c == 0x20 {
+                continue;
+            }
+            if index == 8 {
+                result.push('.');
+            }
+            let lower = if index < 8 { lower_name } else { lower_ext };
+            let c = if c.is_ascii() { c as char } else { '_' };
+            result.push(if lower { c.to_ascii_lowercase() } else { c });
+        }
+        result
+    }
+
+    fn is_valid_long_name(name: &str) -> bool {
+        !name.is_empty()
+            && name != "."
+            && name != ".."
+            && !name.ends_with('.')
+            && !name.ends_with(' ')
+            && name.encode_utf16().count() <= FatFs::MAX_LFN_LEN
+            && name.chars().all(|c| {
+                c >= ' ' && !matches!(c, '"' | '*' | '/' | ':' | '<' | '>' | '?' | '\\' | '|')
+            })
+    }
+
+    #[inline]
+    fn is_valid_short_char(c: char) -> bool {
+        c.is_ascii_alphanumeric()
+            || matches!(
+                c,
+                '!' | '#'
+                    | '$'
+                    | '%'
+                    | '&'
+                    | '\''
+                    | '('
+                    | ')'
+                    | '-'
+                    | '@'
+                    | '^'
+                    | '_'
+                    | '`'
+                    | '{'
+                    | '}'
+                    | '~'
+            )
+    }
+
+    /// Returns the 8.3 name and the case flags if the name fits in a short entry
+    fn short_name_for(name: &str) -> Option<([u8; 11], u8)> {
+        let (base, ext) = match name.find('.') {
+            Some(pos) => (&name[..pos], &name[pos + 1..]),
+            None => (name, ""),
+        };
+        if base.is_empty() || base.len() > 8 || ext.len() > 3 || ext.contains('.') {
+            return None;
+        }
+        let mut result = [0x20; 11];
+        let mut nt_reserved = 0;
+        for (part, offset, flag) in [(base, 0, Self::NT_LOWER_NAME), (ext, 8, Self::NT_LOWER_EXT)] {
+            if !part.chars().all(Self::is_valid_short_char) {
+                return None;
+            }
+            let has_upper = part.chars().any(|c| c.is_ascii_uppercase());
+            let has_lower = part.chars().any(|c| c.is_ascii_lowercase());
+            if has_upper && has_lower {
+                return None;
+            }
+            if has_lower {
+                nt_reserved |= flag;
+            }
+            for (index, c) in part.bytes().enumerate() {
+                result[offset + index] = c.to_ascii_uppercase();
+            }
+        }
+        if result[0] == Self::DELETED {
+            result[0] = Self::KANJI_E5;
+        }
+        Some((result, nt_reserved))
+    }
+
+    /// Makes a unique short name such as `LONGFI~1.TXT` for the long name
+    fn numbered_tail<F>(name: &str, exists: F) -> Option<[u8; 11]>
+    where
+        F: Fn(&[u8; 11]) -> bool,
+    {
+        let name = name.trim_start_matches('.');
+        let (base, ext) = match name.rfind('.') {
+            Some(pos) => (&name[..pos], &name[pos + 1..]),
+            None => (name, ""),
+        };
+        let convert = |s: &str, len: usize| {
+            let mut result = Vec::with_capacity(len);
+            for c in s.chars().filter(|c| *c != ' ' && *c != '.') {
+                if result.len() >= len {
+                    break;
+                }
+                let c = c.to_ascii_uppercase();
+                result.push(if Self::is_valid_short_char(c) {
+                    c as u8
+                } else {
+                    b'_'
+                });
+            }
+            result
+        };
+        let base = convert(base, 8);
+        let ext = convert(ext, 3);
+
+        let mut result = [0x20; 11];
+        result[8..8 + ext.len()].copy_from_slice(&ext);
+        let mut tail = String::with_capacity(8);
+        for number in 1..1_000_000u32 {
+            tail.clear();
+            write!(tail, "~{}", number).unwrap();
+            let len = usize::min(base.len(), 8 - tail.len());
+            result[..8].fill(0x20);
+            result[..len].copy_from_slice(&base[..len]);
+            result[len..len + tail.len()].copy_from_slice(tail.as_bytes());
+            if !exists(&result) {
+                return Some(result);
+            }
+        }
+        None
+    }
+}
+
+/// A directory entry with its decoded name
+struct FatDirEntry {
+    location: u64,
+    lfn_locations: Vec<u64>,
+    name: String,
+    dir_ent: DosDirEnt,
+}
+
+impl FatDirEntry {
+    fn matches(&self, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name)
+            || DosDirEnt::short_name_for(name)
+                .map(|(v, _)| v == self.dir_ent.name)
+                .unwrap_or(false)
+    }
+}
+
+/// Collects the fragments of a VFAT long file name
+struct LfnBuilder {
+    chars: Vec<u16>,
+    locations: Vec<u64>,
+    next_seq: usize,
+    checksum: u8,
+}
+
+impl LfnBuilder {
+    const CHARS_PER_ENTRY: usize = 13;
+    const LAST_ENTRY: u8 = 0x40;
+    const CHAR_OFFSETS: [usize; 13] = [1, 3, 5, 7, 9, 14, 16, 18, 20, 22, 24, 28, 30];
+
+    const fn new() -> Self {
+        Self {
+            chars: Vec::new(),
+            locations: Vec::new(),
+            next_seq: 0,
+            checksum: 0,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.chars.clear();
+        self.locations.clear();
+        self.next_seq = 0;
+    }
+
+    fn push(&mut self, dir_ent: &DosDirEnt, location: u64) {
+        let raw = dir_ent.as_bytes();
+        let seq = (raw[0] & 0x1F) as usize;
+        if (raw[0] & Self::LAST_ENTRY) != 0 {
+            self.reset();
+            self.chars.resize(seq * Self::CHARS_PER_ENTRY, 0xFFFF);
+            self.checksum = raw[13];
+        } else if seq == 0 || seq != self.next_seq || raw[13] != self.checksum {
+            self.reset();
+            return;
+        }
+        if seq == 0 || self.chars.len() < seq * Self::CHARS_PER_ENTRY {
+            self.reset();
+            return;
+        }
+        let base = (seq - 1) * Self::CHARS_PER_ENTRY;
+        for (index, offset) in Self::CHAR_OFFSETS.iter().enumerate() {
+            self.chars[base + index] = LE::read_u16(&raw[*offset..*offset + 2]);
+        }
+        self.locations.push(location);
+        self.next_seq = seq - 1;
+    }
+
+    /// Returns the long name and the locations of its entries if it belongs to the short entry
+    fn take(&mut self, checksum: u8) -> Option<(String, Vec<u64>)> {
+        let result = (!self.locations.is_empty()
+            && self.next_seq == 0
+            && self.checksum == checksum)
+            .then(|| {
+                let len = self
+                    .chars
+                    .iter()
+                    .position(|c| *c == 0 || *c == 0xFFFF)
+                    .unwrap_or(self.chars.len());
+                let name = char::decode_utf16(self.chars[..len].iter().copied())
+                    .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                    .collect::<String>();
+                (name, core::mem::take(&mut self.locations))
+            });
+        self.reset();
+        result
+    }
+
+    /// Makes the raw entry holding the `seq`th fragment of the name
+    fn entry(name: &[u16], seq: usize, is_last: bool, checksum: u8) -> [u8; 32] {
+        let mut raw = [0u8; 32];
+        raw[0] = seq as u8 | if is_last { Self::LAST_ENTRY } else { 0 };
+        raw[11] = DosAttributes::LFN_ENTRY.bits();
+        raw[13] = checksum;
+        let base = (seq - 1) * Self::CHARS_PER_ENTRY;
+        for (index, offset) in Self::CHAR_OFFSETS.iter().enumerate() {
+            let c = match name.get(base + index) {
+                Some(c) => *c,
+                None if base + index == name.len() => 0,
+                None => 0xFFFF,
+            };
+            LE::write_u16(&mut raw[*offset..*offset + 2], c);
+        }
+        raw
+    }
+}
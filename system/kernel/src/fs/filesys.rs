@@ -1,11 +1,17 @@
 // FileSystem
 
 use super::initramfs::*;
+use super::tmpfs::*;
+use crate::sync::Mutex;
+use crate::task::scheduler::*;
 use alloc::string::String;
+use alloc::sync::Arc;
 use alloc::vec::Vec;
 use core::cell::UnsafeCell;
 use core::num::{NonZeroU64, NonZeroUsize};
 use megstd::io;
+use megstd::path::{Component, Path, PathBuf};
+use megstd::time::SystemTime;
 
 static mut FS: UnsafeCell<FileManager> = UnsafeCell::new(FileManager::new());
 
@@ -13,66 +19,493 @@ pub type OffsetType = i64;
 pub type INodeType = u64;
 pub type NonZeroINodeType = NonZeroU64;
 
+/// Filesystem driver that can be mounted on the virtual file system
+pub trait FsDriver {
+    /// Returns the name of the filesystem type
+    fn name(&self) -> &str;
+
+    /// Returns the inode of the root directory
+    fn root_dir(&self) -> NonZeroINodeType;
+
+    /// Returns the entry at the specified index of the directory
+    fn read_dir(&self, dir: NonZeroINodeType, index: usize) -> Option<FsRawDirEntry>;
+
+    /// Finds the entry with the specified name in the directory
+    fn find_file(&self, dir: NonZeroINodeType, lpc: &str) -> io::Result<NonZeroINodeType>;
+
+    fn stat(&self, inode: NonZeroINodeType) -> Option<FsRawMetaData>;
+
+    fn read_data(
+        &self,
+        inode: NonZeroINodeType,
+        offset: OffsetType,
+        buf: &mut [u8],
+    ) -> io::Result<usize>;
+
+    fn write_data(
+        &self,
+        _inode: NonZeroINodeType,
+        _offset: OffsetType,
+        _buf: &[u8],
+    ) -> io::Result<usize> {
+        Err(io::ErrorKind::ReadOnlyFilesystem.into())
+    }
+
+    /// Truncates or extends the file to the specified length
+    fn set_len(&self, _inode: NonZeroINodeType, _len: OffsetType) -> io::Result<()> {
+        Err(io::ErrorKind::ReadOnlyFilesystem.into())
+    }
+
+    /// Creates a new entry with the specified name in the directory
+    fn create(
+        &self,
+        _dir: NonZeroINodeType,
+        _name: &str,
+        _file_type: FsRawFileType,
+    ) -> io::Result<NonZeroINodeType> {
+        Err(io::ErrorKind::ReadOnlyFilesystem.into())
+    }
+
+    /// Removes the entry from the directory. Directories must be empty.
+    fn unlink(&self, _dir: NonZeroINodeType, _name: &str) -> io::Result<()> {
+        Err(io::ErrorKind::ReadOnlyFilesystem.into())
+    }
+
+    /// Moves the entry to another name, replacing the entry of that name if any.
+    /// A replaced directory must be empty.
+    fn rename(
+        &self,
+        _old_dir: NonZeroINodeType,
+        _old_name: &str,
+        _new_dir: NonZeroINodeType,
+        _new_name: &str,
+    ) -> io::Result<()> {
+        Err(io::ErrorKind::ReadOnlyFilesystem.into())
+    }
+}
+
 pub struct FileManager {
-    initramfs: Option<InitRamfs>,
+    mount_points: Mutex<Vec<FsMountPoint>>,
+}
+
+#[derive(Clone)]
+struct FsMountPoint {
+    path: String,
+    fs: Arc<dyn FsDriver>,
 }
 
 impl FileManager {
+    const ROOT_DIR: &'static str = "/";
+    const TMP_DIR: &'static str = "/tmp";
+
     #[inline]
     const fn new() -> Self {
-        Self { initramfs: None }
+        Self {
+            mount_points: Mutex::new(Vec::new()),
+        }
     }
 
     pub unsafe fn init(initrd_base: usize, initrd_size: usize) {
-        let shared = Self::shared_mut();
-        shared.initramfs = InitRamfs::from_static(initrd_base, initrd_size);
+        if let Some(initramfs) = InitRamfs::from_static(initrd_base, initrd_size) {
+            let _ = Self::mount(Arc::new(initramfs), Self::ROOT_DIR);
+            let _ = Self::mount(Arc::new(TmpFs::new()), Self::TMP_DIR);
+        }
+    }
+
+    #[inline]
+    fn shared<'a>() -> &'a Self {
+        unsafe { &*FS.get() }
+    }
+
+    /// Mounts the filesystem on the specified directory.
+    ///
+    /// The first filesystem must be mounted on the root directory.
+    /// Any other mount point needs an existing parent directory, but does not need to exist itself.
+    pub fn mount<P: AsRef<Path>>(fs: Arc<dyn FsDriver>, path: P) -> io::Result<()> {
+        let shared = Self::shared();
+        let path = path.as_ref();
+        // The mount points must not change until the new one is added
+        let mut mount_points = shared.mount_points.lock().unwrap();
+        let mount_path = if Self::find_mount_point(&mount_points, Self::ROOT_DIR).is_none() {
+            match path.to_str() {
+                Some(Self::ROOT_DIR) => Self::ROOT_DIR.into(),
+                _ => return Err(io::ErrorKind::NotConnected.into()),
+            }
+        } else {
+            let parent = path.parent().unwrap_or(Path::new(Self::ROOT_DIR));
+            let name = match path.file_name().and_then(|v| v.to_str()) {
+                Some(v) => v,
+                None => return Err(io::ErrorKind::InvalidInput.into()),
+            };
+            let walker = FsPathWalker::walk(parent, &mount_points)?;
+            if !walker.current().is_dir() {
+                return Err(io::ErrorKind::NotADirectory.into());
+            }
+            match walker.child(name) {
+                Ok(node) => {
+                    if !node.is_dir() {
+                        return Err(io::ErrorKind::NotADirectory.into());
+                    }
+                }
+                Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+                Err(err) => return Err(err),
+            }
+            walker.path_of(name)
+        };
+
+        if mount_points.iter().any(|v| v.path == mount_path) {
+            return Err(io::ErrorKind::AlreadyExists.into());
+        }
+        mount_points.push(FsMountPoint {
+            path: mount_path,
+            fs,
+        });
+        Ok(())
+    }
+
+    /// Unmounts the filesystem mounted on the specified directory.
+    pub fn unmount<P: AsRef<Path>>(path: P) -> io::Result<Arc<dyn FsDriver>> {
+        let shared = Self::shared();
+        let path = Self::canonicalize(path)?;
+        let path = path.to_str().unwrap_or_default();
+        let mut mount_points = shared.mount_points.lock().unwrap();
+        if path == Self::ROOT_DIR && mount_points.len() > 1 {
+            return Err(io::ErrorKind::PermissionDenied.into());
+        }
+        match mount_points.iter().position(|v| v.path == path) {
+            Some(index) => Ok(mount_points.remove(index).fs),
+            None => Err(io::ErrorKind::InvalidInput.into()),
+        }
     }
 
+    /// Returns a copy of the mount points, so that paths can be resolved without the lock
     #[inline]
-    fn shared_mut<'a>() -> &'a mut Self {
-        unsafe { &mut *FS.get() }
+    fn mount_points() -> Vec<FsMountPoint> {
+        Self::shared().mount_points.lock().unwrap().clone()
     }
 
     #[inline]
-    fn shared<'a>() -> &'a Self {
-        unsafe { &*FS.get() }
+    fn mounted_fs(path: &str) -> Option<Arc<dyn FsDriver>> {
+        let shared = Self::shared();
+        let mount_points = shared.mount_points.lock().unwrap();
+        Self::find_mount_point(&mount_points, path)
     }
 
     #[inline]
-    pub fn read_dir(_path: &str) -> io::Result<FsRawReadDir> {
-        Ok(FsRawReadDir::new())
+    fn find_mount_point(mount_points: &[FsMountPoint], path: &str) -> Option<Arc<dyn FsDriver>> {
+        mount_points
+            .iter()
+            .find(|v| v.path == path)
+            .map(|v| v.fs.clone())
+    }
+
+    /// Returns the current directory of the current process
+    pub fn current_dir() -> PathBuf {
+        Scheduler::current_pid()
+            .current_dir()
+            .unwrap_or_else(|| PathBuf::from(Self::ROOT_DIR))
+    }
+
+    /// Changes the current directory of the current process
+    pub fn set_current_dir<P: AsRef<Path>>(path: P) -> io::Result<()> {
+        let walker = FsPathWalker::new(path.as_ref())?;
+        if !walker.current().is_dir() {
+            return Err(io::ErrorKind::NotADirectory.into());
+        }
+        Scheduler::current_pid().set_current_dir(PathBuf::from(&walker.path()));
+        Ok(())
+    }
+
+    /// Returns the absolute path with all `.` and `..` components resolved
+    pub fn canonicalize<P: AsRef<Path>>(path: P) -> io::Result<PathBuf> {
+        FsPathWalker::new(path.as_ref()).map(|v| PathBuf::from(&v.path()))
     }
 
-    pub fn open(path: &str) -> io::Result<FsRawFileControlBlock> {
-        let shared = FileManager::shared();
-        let fs = match shared.initramfs.as_ref() {
+    pub fn read_dir<P: AsRef<Path>>(path: P) -> io::Result<FsRawReadDir> {
+        let walker = FsPathWalker::new(path.as_ref())?;
+        let dir = walker.current();
+        if !dir.is_dir() {
+            return Err(io::ErrorKind::NotADirectory.into());
+        }
+        Ok(FsRawReadDir::new(dir.fs.clone(), dir.inode))
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<FsRawFileControlBlock> {
+        let walker = FsPathWalker::new(path.as_ref())?;
+        let file = walker.current();
+        let stat = match file.fs.stat(file.inode) {
             Some(v) => v,
-            None => return Err(io::ErrorKind::NotConnected.into()),
+            None => return Err(io::ErrorKind::InvalidData.into()),
         };
+        if stat.is_dir() {
+            return Err(io::ErrorKind::IsADirectory.into());
+        }
+
+        let fcb = FsRawFileControlBlock::new(file.fs.clone(), file.inode, stat.len());
+
+        Ok(fcb)
+    }
+
+    /// Opens the file for writing, creating it if it does not exist and truncating it if it does
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<FsRawFileControlBlock> {
+        let (walker, name) = FsPathWalker::parent_of(path.as_ref())?;
+        match walker.child(&name) {
+            Ok(file) => {
+                if file.is_dir() {
+                    return Err(io::ErrorKind::IsADirectory.into());
+                }
+                file.fs.set_len(file.inode, 0)?;
+                Ok(FsRawFileControlBlock::new(file.fs, file.inode, 0))
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                let dir = walker.current();
+                let inode = dir
+                    .fs
+                    .create(dir.inode, &name, FsRawFileType::RegularFile)?;
+                Ok(FsRawFileControlBlock::new(dir.fs.clone(), inode, 0))
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn create_dir<P: AsRef<Path>>(path: P) -> io::Result<()> {
+        let (walker, name) = FsPathWalker::parent_of(path.as_ref())?;
+        match walker.child(&name) {
+            Ok(_) => Err(io::ErrorKind::AlreadyExists.into()),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                let dir = walker.current();
+                dir.fs
+                    .create(dir.inode, &name, FsRawFileType::Directory)
+                    .map(|_| ())
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn remove_file<P: AsRef<Path>>(path: P) -> io::Result<()> {
+        Self::remove(path.as_ref(), FsRawFileType::RegularFile)
+    }
+
+    pub fn remove_dir<P: AsRef<Path>>(path: P) -> io::Result<()> {
+        Self::remove(path.as_ref(), FsRawFileType::Directory)
+    }
+
+    fn remove(path: &Path, file_type: FsRawFileType) -> io::Result<()> {
+        let (walker, name) = FsPathWalker::parent_of(path)?;
+        let dir = walker.current();
+        let target = walker.child(&name)?;
+        match (file_type, target.is_dir()) {
+            (FsRawFileType::RegularFile, true) => Err(io::ErrorKind::IsADirectory.into()),
+            (FsRawFileType::Directory, false) => Err(io::ErrorKind::NotADirectory.into()),
+            _ => dir.fs.unlink(dir.inode, &name),
+        }
+    }
+
+    /// Renames the file or directory, replacing the destination if it already exists
+    pub fn rename<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q) -> io::Result<()> {
+        let (old_walker, old_name) = FsPathWalker::parent_of(from.as_ref())?;
+        let (new_walker, new_name) = FsPathWalker::parent_of(to.as_ref())?;
+        let old_dir = old_walker.current();
+        let new_dir = new_walker.current();
+        if !Arc::ptr_eq(&old_dir.fs, &new_dir.fs) {
+            return Err(io::ErrorKind::InvalidInput.into());
+        }
+        let source = old_walker.child(&old_name)?;
+
+        // A directory cannot be moved into itself
+        let old_path = old_walker.path_of(&old_name);
+        let new_path = new_walker.path_of(&new_name);
+        if old_path == new_path {
+            return Ok(());
+        }
+        if source.is_dir() && Path::new(&new_path).starts_with(&old_path) {
+            return Err(io::ErrorKind::InvalidInput.into());
+        }
+
+        // The driver replaces the destination, so that it is not lost if the rename fails
+        match new_walker.child(&new_name) {
+            // On case-insensitive filesystems, only the case of the name changes
+            Ok(target) if target.inode == source.inode => (),
+            Ok(target) => match (source.is_dir(), target.is_dir()) {
+                (false, true) => return Err(io::ErrorKind::IsADirectory.into()),
+                (true, false) => return Err(io::ErrorKind::NotADirectory.into()),
+                _ => (),
+            },
+            Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+            Err(err) => return Err(err),
+        }
 
-        let lpc = path; // TODO: parse path
-        let inode = match fs.find_file(lpc) {
+        old_dir
+            .fs
+            .rename(old_dir.inode, &old_name, new_dir.inode, &new_name)
+    }
+}
+
+/// A node of the virtual file system
+struct FsNode {
+    fs: Arc<dyn FsDriver>,
+    inode: NonZeroINodeType,
+}
+
+impl FsNode {
+    #[inline]
+    fn is_dir(&self) -> bool {
+        self.fs
+            .stat(self.inode)
+            .map(|v| v.is_dir())
+            .unwrap_or(false)
+    }
+}
+
+/// Resolves a path one component at a time, crossing mount points
+struct FsPathWalker {
+    names: Vec<String>,
+    nodes: Vec<FsNode>,
+}
+
+impl FsPathWalker {
+    fn new(path: &Path) -> io::Result<Self> {
+        Self::walk(path, &FileManager::mount_points())
+    }
+
+    /// Resolves the path with the mount points, which the caller has either locked or copied
+    fn walk(path: &Path, mount_points: &[FsMountPoint]) -> io::Result<Self> {
+        let fs = match FileManager::find_mount_point(mount_points, FileManager::ROOT_DIR) {
             Some(v) => v,
-            None => return Err(io::ErrorKind::NotFound.into()),
+            None => return Err(io::ErrorKind::NotConnected.into()),
+        };
+        let root = FsNode {
+            inode: fs.root_dir(),
+            fs,
+        };
+        let mut walker = Self {
+            names: Vec::new(),
+            nodes: Vec::new(),
         };
-        let stat = match fs.stat(inode) {
+        walker.nodes.push(root);
+
+        if path.is_relative() {
+            let cwd = FileManager::current_dir();
+            for component in cwd.components() {
+                walker.step(component, mount_points)?;
+            }
+        }
+        for component in path.components() {
+            walker.step(component, mount_points)?;
+        }
+
+        Ok(walker)
+    }
+
+    /// Resolves the parent directory of the path and returns it with the last component
+    fn parent_of(path: &Path) -> io::Result<(Self, String)> {
+        let name = match path.file_name().and_then(|v| v.to_str()) {
             Some(v) => v,
-            None => return Err(io::ErrorKind::InvalidData.into()),
+            None => return Err(io::ErrorKind::InvalidInput.into()),
         };
+        let parent = path.parent().unwrap_or(Path::new(FileManager::ROOT_DIR));
+        let walker = Self::new(parent)?;
+        if !walker.current().is_dir() {
+            return Err(io::ErrorKind::NotADirectory.into());
+        }
+        if FileManager::mounted_fs(&walker.path_of(name)).is_some() {
+            return Err(io::ErrorKind::PermissionDenied.into());
+        }
+        Ok((walker, name.into()))
+    }
+
+    /// Finds the entry in the current directory without crossing mount points
+    fn child(&self, name: &str) -> io::Result<FsNode> {
+        let dir = self.current();
+        dir.fs.find_file(dir.inode, name).map(|inode| FsNode {
+            fs: dir.fs.clone(),
+            inode,
+        })
+    }
 
-        let fcb = FsRawFileControlBlock::new(inode, stat.len());
+    fn step(&mut self, component: Component, mount_points: &[FsMountPoint]) -> io::Result<()> {
+        match component {
+            Component::Prefix(_) => return Err(io::ErrorKind::InvalidInput.into()),
+            Component::RootDir => {
+                self.names.clear();
+                self.nodes.truncate(1);
+            }
+            Component::CurDir => (),
+            Component::ParentDir => {
+                // The parent of the root directory is the root directory itself
+                if self.names.pop().is_some() {
+                    self.nodes.pop();
+                }
+            }
+            Component::Normal(name) => {
+                let name = match name.to_str() {
+                    Some(v) => v,
+                    None => return Err(io::ErrorKind::InvalidInput.into()),
+                };
+                let current = self.current();
+                if !current.is_dir() {
+                    return Err(io::ErrorKind::NotADirectory.into());
+                }
+                let (fs, dir) = (current.fs.clone(), current.inode);
+                self.names.push(name.into());
+                let node = match FileManager::find_mount_point(mount_points, &self.path()) {
+                    Some(fs) => FsNode {
+                        inode: fs.root_dir(),
+                        fs,
+                    },
+                    None => match fs.find_file(dir, name) {
+                        Ok(inode) => FsNode { fs, inode },
+                        Err(err) => {
+                            self.names.pop();
+                            return Err(err);
+                        }
+                    },
+                };
+                self.nodes.push(node);
+            }
+        }
+        Ok(())
+    }
 
-        Ok(fcb)
+    #[inline]
+    fn current(&self) -> &FsNode {
+        self.nodes.last().unwrap()
+    }
+
+    /// Returns the absolute path of the current node
+    fn path(&self) -> String {
+        if self.names.is_empty() {
+            return FileManager::ROOT_DIR.into();
+        }
+        let mut path = String::new();
+        for name in &self.names {
+            path.push_str(FileManager::ROOT_DIR);
+            path.push_str(name);
+        }
+        path
+    }
+
+    /// Returns the absolute path of the entry in the current directory
+    fn path_of(&self, name: &str) -> String {
+        let mut path = self.path();
+        if !self.names.is_empty() {
+            path.push_str(FileManager::ROOT_DIR);
+        }
+        path.push_str(name);
+        path
     }
 }
 
 pub struct FsRawReadDir {
+    fs: Arc<dyn FsDriver>,
+    dir: NonZeroINodeType,
     index: usize,
 }
 
 impl FsRawReadDir {
-    fn new() -> Self {
-        Self { index: 0 }
+    #[inline]
+    fn new(fs: Arc<dyn FsDriver>, dir: NonZeroINodeType) -> Self {
+        Self { fs, dir, index: 0 }
     }
 }
 
@@ -80,15 +513,10 @@ impl Iterator for FsRawReadDir {
     type Item = FsRawDirEntry;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let shared = FileManager::shared();
-        shared
-            .initramfs
-            .as_ref()
-            .and_then(|v| v.read_dir(self.index))
-            .map(|v| {
-                self.index += 1;
-                v
-            })
+        self.fs.read_dir(self.dir, self.index).map(|v| {
+            self.index += 1;
+            v
+        })
     }
 }
 
@@ -132,13 +560,72 @@ impl FsRawDirEntry {
     }
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FsRawFileType {
+    RegularFile,
+    Directory,
+}
+
 pub struct FsRawMetaData {
+    file_type: FsRawFileType,
     len: OffsetType,
+    created: Option<SystemTime>,
+    modified: Option<SystemTime>,
+    accessed: Option<SystemTime>,
 }
 
 impl FsRawMetaData {
-    pub const fn new(len: OffsetType) -> Self {
-        Self { len }
+    pub const fn new(file_type: FsRawFileType, len: OffsetType) -> Self {
+        Self {
+            file_type,
+            len,
+            created: None,
+            modified: None,
+            accessed: None,
+        }
+    }
+
+    #[inline]
+    pub const fn with_timestamps(
+        mut self,
+        created: Option<SystemTime>,
+        modified: Option<SystemTime>,
+        accessed: Option<SystemTime>,
+    ) -> Self {
+        self.created = created;
+        self.modified = modified;
+        self.accessed = accessed;
+        self
+    }
+
+    #[inline]
+    pub const fn created(&self) -> Option<SystemTime> {
+        self.created
+    }
+
+    #[inline]
+    pub const fn modified(&self) -> Option<SystemTime> {
+        self.modified
+    }
+
+    #[inline]
+    pub const fn accessed(&self) -> Option<SystemTime> {
+        self.accessed
+    }
+
+    #[inline]
+    pub const fn file_type(&self) -> FsRawFileType {
+        self.file_type
+    }
+
+    #[inline]
+    pub fn is_dir(&self) -> bool {
+        self.file_type == FsRawFileType::Directory
+    }
+
+    #[inline]
+    pub fn is_file(&self) -> bool {
+        self.file_type == FsRawFileType::RegularFile
     }
 
     pub const fn len(&self) -> OffsetType {
@@ -147,32 +634,42 @@ impl FsRawMetaData {
 }
 
 pub struct FsRawFileControlBlock {
-    inode: Option<NonZeroINodeType>,
+    fs: Arc<dyn FsDriver>,
+    inode: NonZeroINodeType,
     file_pos: OffsetType,
     file_size: OffsetType,
 }
 
 impl FsRawFileControlBlock {
     #[inline]
-    const fn new(inode: NonZeroINodeType, file_size: OffsetType) -> Self {
+    fn new(fs: Arc<dyn FsDriver>, inode: NonZeroINodeType, file_size: OffsetType) -> Self {
         Self {
-            inode: Some(inode),
+            fs,
+            inode,
             file_pos: 0,
             file_size,
         }
     }
 
     pub fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        let shared = FileManager::shared();
-        shared
-            .initramfs
-            .as_ref()
-            .ok_or(io::ErrorKind::NotConnected.into())
-            .and_then(|v| v.read_data(self.inode, self.file_pos, buf))
-            .map(|v| {
-                self.file_pos += v as OffsetType;
-                v
-            })
+        self.fs.read_data(self.inode, self.file_pos, buf).map(|v| {
+            self.file_pos += v as OffsetType;
+            v
+        })
+    }
+
+    pub fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.fs.write_data(self.inode, self.file_pos, buf).map(|v| {
+            self.file_pos += v as OffsetType;
+            self.file_size = OffsetType::max(self.file_size, self.file_pos);
+            v
+        })
+    }
+
+    pub fn set_len(&mut self, len: OffsetType) -> io::Result<()> {
+        self.fs.set_len(self.inode, len).map(|_| {
+            self.file_size = len;
+        })
     }
 
     pub fn read_to_end(&mut self, vec: &mut Vec<u8>) -> io::Result<usize> {
@@ -194,9 +691,7 @@ impl FsRawFileControlBlock {
     }
 
     pub fn stat(&self) -> Option<FsRawMetaData> {
-        let shared = FileManager::shared();
-        self.inode
-            .and_then(|inode| shared.initramfs.as_ref().and_then(|v| v.stat(inode)))
+        self.fs.stat(self.inode)
     }
 }
 
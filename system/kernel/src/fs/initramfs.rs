@@ -15,6 +15,8 @@ impl InitRamfs {
     const MAGIC_CURRENT: u32 = 0x0001beef;
     const SIZE_OF_RAW_DIR: usize = 32;
     const OFFSET_DATA: usize = 16;
+    const INODE_ROOT: INodeType = 1;
+    const INODE_FIRST_FILE: INodeType = 2;
 
     #[inline]
     pub unsafe fn from_static(base: usize, len: usize) -> Option<Self> {
@@ -38,7 +40,7 @@ impl InitRamfs {
                 String::from_utf8(data[dir_offset + 1..dir_offset + name_len + 1].to_owned())
                     .unwrap_or("#NAME?".to_owned());
             dir.push(CurFsDirEntry {
-                inode: NonZeroINodeType::new(index as INodeType + 1).unwrap(),
+                inode: NonZeroINodeType::new(index as INodeType + Self::INODE_FIRST_FILE).unwrap(),
                 name,
                 offset: LE::read_u32(&data[dir_offset + 0x18..dir_offset + 0x1C]) as usize,
                 size: LE::read_u32(&data[dir_offset + 0x1C..dir_offset + 0x20]) as usize,
@@ -49,33 +51,62 @@ impl InitRamfs {
     }
 
     #[inline]
-    pub fn read_dir(&self, index: usize) -> Option<FsRawDirEntry> {
-        self.dir.get(index).map(|v| v.into())
+    fn get_file(&self, inode: NonZeroINodeType) -> Option<&CurFsDirEntry> {
+        inode
+            .get()
+            .checked_sub(Self::INODE_FIRST_FILE)
+            .and_then(|v| self.dir.get(v as usize))
     }
+}
 
+impl FsDriver for InitRamfs {
     #[inline]
-    pub fn find_file(&self, lpc: &str) -> Option<NonZeroINodeType> {
-        self.dir.iter().find(|v| lpc == v.name).map(|v| v.inode)
+    fn name(&self) -> &str {
+        "initramfs"
     }
 
     #[inline]
-    pub fn stat(&self, inode: NonZeroINodeType) -> Option<FsRawMetaData> {
-        self.get_file(inode)
-            .and_then(|v| FsRawDirEntry::from(v).into_metadata())
+    fn root_dir(&self) -> NonZeroINodeType {
+        NonZeroINodeType::new(Self::INODE_ROOT).unwrap()
     }
 
     #[inline]
-    fn get_file(&self, inode: NonZeroINodeType) -> Option<&CurFsDirEntry> {
-        self.dir.get(inode.get() as usize - 1)
+    fn read_dir(&self, dir: NonZeroINodeType, index: usize) -> Option<FsRawDirEntry> {
+        if dir.get() != Self::INODE_ROOT {
+            return None;
+        }
+        self.dir.get(index).map(|v| v.into())
     }
 
-    pub fn read_data(
+    fn find_file(&self, dir: NonZeroINodeType, lpc: &str) -> io::Result<NonZeroINodeType> {
+        if dir.get() != Self::INODE_ROOT {
+            return Err(io::ErrorKind::NotADirectory.into());
+        }
+        self.dir
+            .iter()
+            .find(|v| lpc == v.name)
+            .map(|v| v.inode)
+            .ok_or(io::ErrorKind::NotFound.into())
+    }
+
+    fn stat(&self, inode: NonZeroINodeType) -> Option<FsRawMetaData> {
+        if inode.get() == Self::INODE_ROOT {
+            return Some(FsRawMetaData::new(FsRawFileType::Directory, 0));
+        }
+        self.get_file(inode)
+            .and_then(|v| FsRawDirEntry::from(v).into_metadata())
+    }
+
+    fn read_data(
         &self,
-        inode: Option<NonZeroINodeType>,
+        inode: NonZeroINodeType,
         offset: OffsetType,
         buf: &mut [u8],
     ) -> io::Result<usize> {
-        let dir_ent = match inode.and_then(|v| self.get_file(v)) {
+        if inode.get() == Self::INODE_ROOT {
+            return Err(io::ErrorKind::IsADirectory.into());
+        }
+        let dir_ent = match self.get_file(inode) {
             Some(v) => v,
             None => return Err(io::ErrorKind::NotFound.into()),
         };
@@ -105,7 +136,7 @@ struct CurFsDirEntry {
 
 impl From<&CurFsDirEntry> for FsRawDirEntry {
     fn from(src: &CurFsDirEntry) -> Self {
-        let metadata = FsRawMetaData::new(src.size as OffsetType);
+        let metadata = FsRawMetaData::new(FsRawFileType::RegularFile, src.size as OffsetType);
         FsRawDirEntry::new(src.inode, src.name.clone(), Some(metadata))
     }
 }
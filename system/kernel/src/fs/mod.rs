@@ -1,5 +1,9 @@
 //! Filesystem supports
 
+mod fat;
 mod filesys;
+pub use fat::*;
 pub use filesys::*;
 mod initramfs;
+mod tmpfs;
+pub use tmpfs::*;
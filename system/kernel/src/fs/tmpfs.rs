@@ -0,0 +1,387 @@
+// Temporary Filesystem
+
+use super::*;
+use crate::sync::Mutex;
+use crate::system::System;
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+use megstd::io;
+use megstd::time::SystemTime;
+
+/// Writable filesystem that keeps everything in memory
+pub struct TmpFs {
+    inner: Mutex<TmpFsInner>,
+    capacity: usize,
+}
+
+struct TmpFsInner {
+    nodes: BTreeMap<INodeType, TmpFsNode>,
+    next_inode: INodeType,
+    /// Total size of the files in bytes
+    used: usize,
+}
+
+struct TmpFsNode {
+    data: TmpFsNodeData,
+    /// Directory that contains this node
+    parent: INodeType,
+    created: SystemTime,
+    modified: SystemTime,
+    accessed: SystemTime,
+}
+
+enum TmpFsNodeData {
+    File(Vec<u8>),
+    /// Entries in the order of creation
+    Directory(Vec<(String, NonZeroINodeType)>),
+}
+
+impl TmpFs {
+    const INODE_ROOT: INodeType = 1;
+
+    /// Default capacity of the files in bytes
+    pub const DEFAULT_CAPACITY: usize = 0x400_0000;
+
+    #[inline]
+    pub fn new() -> Self {
+        Self::with_capacity(Self::DEFAULT_CAPACITY)
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        let mut nodes = BTreeMap::new();
+        nodes.insert(
+            Self::INODE_ROOT,
+            TmpFsNode::new(
+                TmpFsNodeData::Directory(Vec::new()),
+                Self::INODE_ROOT,
+                System::system_time(),
+            ),
+        );
+        Self {
+            inner: Mutex::new(TmpFsInner {
+                nodes,
+                next_inode: Self::INODE_ROOT + 1,
+                used: 0,
+            }),
+            capacity,
+        }
+    }
+
+    #[inline]
+    fn is_valid_name(name: &str) -> bool {
+        !name.is_empty() && name != "." && name != ".." && !name.contains(&['/', '\0'][..])
+    }
+}
+
+impl TmpFsInner {
+    #[inline]
+    fn node(&self, inode: NonZeroINodeType) -> io::Result<&TmpFsNode> {
+        self.nodes
+            .get(&inode.get())
+            .ok_or(io::ErrorKind::NotFound.into())
+    }
+
+    #[inline]
+    fn node_mut(&mut self, inode: NonZeroINodeType) -> io::Result<&mut TmpFsNode> {
+        self.nodes
+            .get_mut(&inode.get())
+            .ok_or(io::ErrorKind::NotFound.into())
+    }
+
+    fn entries(&self, dir: NonZeroINodeType) -> io::Result<&Vec<(String, NonZeroINodeType)>> {
+        match self.node(dir)?.data {
+            TmpFsNodeData::Directory(ref entries) => Ok(entries),
+            TmpFsNodeData::File(_) => Err(io::ErrorKind::NotADirectory.into()),
+        }
+    }
+
+    fn find(&self, dir: NonZeroINodeType, name: &str) -> io::Result<NonZeroINodeType> {
+        self.entries(dir)?
+            .iter()
+            .find(|(v, _)| v == name)
+            .map(|(_, inode)| *inode)
+            .ok_or(io::ErrorKind::NotFound.into())
+    }
+
+    /// Removes the entry from the directory and returns its inode
+    fn detach(&mut self, dir: NonZeroINodeType, name: &str) -> io::Result<NonZeroINodeType> {
+        let now = System::system_time();
+        let node = self.node_mut(dir)?;
+        node.modified = now;
+        match node.data {
+            TmpFsNodeData::Directory(ref mut entries) => {
+                let index = entries
+                    .iter()
+                    .position(|(v, _)| v == name)
+                    .ok_or(io::ErrorKind::NotFound)?;
+                Ok(entries.remove(index).1)
+            }
+            TmpFsNodeData::File(_) => Err(io::ErrorKind::NotADirectory.into()),
+        }
+    }
+
+    fn attach(&mut self, dir: NonZeroINodeType, name: &str, inode: NonZeroINodeType) {
+        let now = System::system_time();
+        if let Ok(node) = self.node_mut(dir) {
+            node.modified = now;
+            if let TmpFsNodeData::Directory(ref mut entries) = node.data {
+                entries.push((name.into(), inode));
+            }
+        }
+        if let Ok(node) = self.node_mut(inode) {
+            node.parent = dir.get();
+        }
+    }
+
+    /// Removes the node and releases the size of its data
+    fn remove(&mut self, inode: NonZeroINodeType) {
+        if let Some(node) = self.nodes.remove(&inode.get()) {
+            if let TmpFsNodeData::File(data) = node.data {
+                self.used -= data.len();
+            }
+        }
+    }
+
+    fn file_mut(&mut self, inode: NonZeroINodeType) -> io::Result<&mut TmpFsNode> {
+        let node = self.node_mut(inode)?;
+        match node.data {
+            TmpFsNodeData::File(_) => Ok(node),
+            TmpFsNodeData::Directory(_) => Err(io::ErrorKind::IsADirectory.into()),
+        }
+    }
+}
+
+impl TmpFsNode {
+    #[inline]
+    const fn new(data: TmpFsNodeData, parent: INodeType, now: SystemTime) -> Self {
+        Self {
+            data,
+            parent,
+            created: now,
+            modified: now,
+            accessed: now,
+        }
+    }
+
+    fn metadata(&self) -> FsRawMetaData {
+        let (file_type, len) = match self.data {
+            TmpFsNodeData::File(ref data) => (FsRawFileType::RegularFile, data.len()),
+            TmpFsNodeData::Directory(_) => (FsRawFileType::Directory, 0),
+        };
+        FsRawMetaData::new(file_type, len as OffsetType).with_timestamps(
+            Some(self.created),
+            Some(self.modified),
+            Some(self.accessed),
+        )
+    }
+
+    #[inline]
+    fn data_mut(&mut self) -> &mut Vec<u8> {
+        match self.data {
+            TmpFsNodeData::File(ref mut data) => data,
+            TmpFsNodeData::Directory(_) => unreachable!(),
+        }
+    }
+}
+
+impl FsDriver for TmpFs {
+    #[inline]
+    fn name(&self) -> &str {
+        "tmpfs"
+    }
+
+    #[inline]
+    fn root_dir(&self) -> NonZeroINodeType {
+        NonZeroINodeType::new(Self::INODE_ROOT).unwrap()
+    }
+
+    fn read_dir(&self, dir: NonZeroINodeType, index: usize) -> Option<FsRawDirEntry> {
+        let inner = self.inner.lock().unwrap();
+        let (name, inode) = inner.entries(dir).ok()?.get(index)?;
+        let metadata = inner.node(*inode).ok().map(|v| v.metadata());
+        Some(FsRawDirEntry::new(*inode, name.clone(), metadata))
+    }
+
+    fn find_file(&self, dir: NonZeroINodeType, lpc: &str) -> io::Result<NonZeroINodeType> {
+        self.inner.lock().unwrap().find(dir, lpc)
+    }
+
+    fn stat(&self, inode: NonZeroINodeType) -> Option<FsRawMetaData> {
+        self.inner
+            .lock()
+            .unwrap()
+            .node(inode)
+            .ok()
+            .map(|v| v.metadata())
+    }
+
+    fn read_data(
+        &self,
+        inode: NonZeroINodeType,
+        offset: OffsetType,
+        buf: &mut [u8],
+    ) -> io::Result<usize> {
+        let now = System::system_time();
+        let mut inner = self.inner.lock().unwrap();
+        let node = inner.file_mut(inode)?;
+        node.accessed = now;
+        let data = node.data_mut();
+        if offset < 0 {
+            return Err(io::ErrorKind::InvalidInput.into());
+        }
+        let offset = usize::min(offset as usize, data.len());
+        let count = usize::min(data.len() - offset, buf.len());
+        buf[..count].copy_from_slice(&data[offset..offset + count]);
+        Ok(count)
+    }
+
+    fn write_data(
+        &self,
+        inode: NonZeroINodeType,
+        offset: OffsetType,
+        buf: &[u8],
+    ) -> io::Result<usize> {
+        if offset < 0 {
+            return Err(io::ErrorKind::InvalidInput.into());
+        }
+        let offset = offset as usize;
+        let end = offset
+            .checked_add(buf.len())
+            .ok_or(io::ErrorKind::InvalidInput)?;
+        let now = System::system_time();
+        let mut inner = self.inner.lock().unwrap();
+        let available = self.capacity - inner.used;
+        let node = inner.file_mut(inode)?;
+        let data = node.data_mut();
+        let grown = end.saturating_sub(data.len());
+        if grown > 0 {
+            if grown > available {
+                return Err(io::ErrorKind::StorageFull.into());
+            }
+            data.try_reserve_exact(grown)
+                .map_err(|_| io::ErrorKind::StorageFull)?;
+            data.resize(end, 0);
+        }
+        data[offset..end].copy_from_slice(buf);
+        node.modified = now;
+        inner.used += grown;
+        Ok(buf.len())
+    }
+
+    fn set_len(&self, inode: NonZeroINodeType, len: OffsetType) -> io::Result<()> {
+        if len < 0 {
+            return Err(io::ErrorKind::InvalidInput.into());
+        }
+        let len = len as usize;
+        let now = System::system_time();
+        let mut inner = self.inner.lock().unwrap();
+        let available = self.capacity - inner.used;
+        let node = inner.file_mut(inode)?;
+        let data = node.data_mut();
+        let old_len = data.len();
+        if len > old_len {
+            let grown = len - old_len;
+            if grown > available {
+                return Err(io::ErrorKind::StorageFull.into());
+            }
+            data.try_reserve_exact(grown)
+                .map_err(|_| io::ErrorKind::StorageFull)?;
+        }
+        data.resize(len, 0);
+        data.shrink_to_fit();
+        node.modified = now;
+        inner.used = inner.used - old_len + len;
+        Ok(())
+    }
+
+    fn create(
+        &self,
+        dir: NonZeroINodeType,
+        name: &str,
+        file_type: FsRawFileType,
+    ) -> io::Result<NonZeroINodeType> {
+        if !Self::is_valid_name(name) {
+            return Err(io::ErrorKind::InvalidInput.into());
+        }
+        let mut inner = self.inner.lock().unwrap();
+        match inner.find(dir, name) {
+            Ok(_) => return Err(io::ErrorKind::AlreadyExists.into()),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+            Err(err) => return Err(err),
+        }
+
+        let data = match file_type {
+            FsRawFileType::RegularFile => TmpFsNodeData::File(Vec::new()),
+            FsRawFileType::Directory => TmpFsNodeData::Directory(Vec::new()),
+        };
+        let inode = NonZeroINodeType::new(inner.next_inode).unwrap();
+        inner.next_inode += 1;
+        inner.nodes.insert(
+            inode.get(),
+            TmpFsNode::new(data, dir.get(), System::system_time()),
+        );
+        inner.attach(dir, name, inode);
+        Ok(inode)
+    }
+
+    fn unlink(&self, dir: NonZeroINodeType, name: &str) -> io::Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        let inode = inner.find(dir, name)?;
+        if let TmpFsNodeData::Directory(ref entries) = inner.node(inode)?.data {
+            if !entries.is_empty() {
+                return Err(io::ErrorKind::DirectoryNotEmpty.into());
+            }
+        }
+        inner.detach(dir, name)?;
+        inner.remove(inode);
+        Ok(())
+    }
+
+    fn rename(
+        &self,
+        old_dir: NonZeroINodeType,
+        old_name: &str,
+        new_dir: NonZeroINodeType,
+        new_name: &str,
+    ) -> io::Result<()> {
+        if !Self::is_valid_name(new_name) {
+            return Err(io::ErrorKind::InvalidInput.into());
+        }
+        let mut inner = self.inner.lock().unwrap();
+        let inode = inner.find(old_dir, old_name)?;
+        let target = match inner.find(new_dir, new_name) {
+            Ok(v) if v == inode => return Ok(()),
+            Ok(v) => Some(v),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+            Err(err) => return Err(err),
+        };
+
+        // A directory cannot be moved into itself
+        let mut parent = new_dir.get();
+        loop {
+            if parent == inode.get() {
+                return Err(io::ErrorKind::InvalidInput.into());
+            }
+            if parent == Self::INODE_ROOT {
+                break;
+            }
+            parent = inner
+                .nodes
+                .get(&parent)
+                .map(|v| v.parent)
+                .unwrap_or(Self::INODE_ROOT);
+        }
+
+        if let Some(target) = target {
+            if let TmpFsNodeData::Directory(ref entries) = inner.node(target)?.data {
+                if !entries.is_empty() {
+                    return Err(io::ErrorKind::DirectoryNotEmpty.into());
+                }
+            }
+            inner.detach(new_dir, new_name)?;
+            inner.remove(target);
+        }
+        inner.detach(old_dir, old_name)?;
+        inner.attach(new_dir, new_name, inode);
+        Ok(())
+    }
+}
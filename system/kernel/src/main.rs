@@ -7,9 +7,11 @@
 #![feature(asm)]
 
 use alloc::string::*;
+use alloc::sync::Arc;
 use alloc::vec::*;
 use bootprot::*;
 use core::fmt::Write;
+use kernel::dev::block::BlockDeviceManager;
 use kernel::fs::*;
 use kernel::mem::*;
 use kernel::rt::*;
@@ -19,6 +21,7 @@ use kernel::task::Task;
 use kernel::ui::window::WindowManager;
 use kernel::*;
 use kernel::{arch::cpu::*, bus::pci::PciClass};
+use megstd::path::Path;
 use megstd::string::*;
 
 extern crate alloc;
@@ -91,7 +94,14 @@ impl Shell {
     fn exec_cmd(cmdline: &str) {
         match Self::parse_cmd(&cmdline, |name, args| match name {
             "clear" | "cls" => System::stdout().reset().unwrap(),
-            "cd" | "exit" => println!("Feature not available"),
+            "cd" => match args.get(1) {
+                Some(path) => match FileManager::set_current_dir(path) {
+                    Ok(_) => (),
+                    Err(err) => println!("{:?}", err.kind()),
+                },
+                None => println!("{}", FileManager::current_dir().to_str().unwrap_or("")),
+            },
+            "exit" => println!("Feature not available"),
             "echo" => {
                 let stdout = System::stdout();
                 for (index, word) in args.iter().skip(1).enumerate() {
@@ -262,6 +272,14 @@ impl Shell {
 
     fn spawn_main(name: &str, argv: &[&str], wait_until: bool) -> Option<usize> {
         FileManager::open(name)
+            .or_else(|err| {
+                // Commands without a directory are also searched in the root directory
+                if name.contains('/') {
+                    Err(err)
+                } else {
+                    FileManager::open(Path::new("/").join(name))
+                }
+            })
             .map(|mut fcb| {
                 let stat = fcb.stat().unwrap();
                 let file_size = stat.len() as usize;
@@ -304,14 +322,18 @@ impl Shell {
         None
     }
 
-    const COMMAND_TABLE: [(&'static str, fn(&[&str]) -> isize, &'static str); 6] = [
+    const COMMAND_TABLE: [(&'static str, fn(&[&str]) -> isize, &'static str); 10] = [
         ("dir", Self::cmd_dir, "Show directory"),
         ("help", Self::cmd_help, "Show Help"),
         ("type", Self::cmd_type, "Show file"),
         //
         ("ps", Self::cmd_ps, ""),
         ("lspci", Self::cmd_lspci, "Show List of PCI Devices"),
+        ("lsblk", Self::cmd_lsblk, "Show List of Block Devices"),
+        ("mount", Self::cmd_mount, "Mount FAT Volume"),
         ("sysctl", Self::cmd_sysctl, "System Control"),
+        ("dbg", Self::cmd_dbg, "Debug Arlequin Apps"),
+        ("wasmdump", Self::cmd_wasmdump, "Disassemble Arlequin Apps"),
     ];
 
     fn cmd_help(_: &[&str]) -> isize {
@@ -348,6 +370,30 @@ impl Shell {
                 WindowManager::get_statistics(&mut sb);
                 print!("{}", sb.as_str());
             }
+            "jit" => match argv.get(2) {
+                Some(&"on") => megos::ArleBinaryLoader::set_jit_enabled(true),
+                Some(&"off") => megos::ArleBinaryLoader::set_jit_enabled(false),
+                _ => println!(
+                    "jit: {}",
+                    if megos::ArleBinaryLoader::is_jit_enabled() {
+                        "on"
+                    } else {
+                        "off"
+                    }
+                ),
+            },
+            "prof" => match argv.get(2) {
+                Some(&"on") => megos::ArleBinaryLoader::set_profiler_enabled(true),
+                Some(&"off") => megos::ArleBinaryLoader::set_profiler_enabled(false),
+                _ => println!(
+                    "prof: {}",
+                    if megos::ArleBinaryLoader::is_profiler_enabled() {
+                        "on"
+                    } else {
+                        "off"
+                    }
+                ),
+            },
             "random" => match Cpu::secure_rand() {
                 Ok(rand) => println!("{:016x}", rand),
                 Err(_) => println!("# No SecureRandom"),
@@ -360,10 +406,191 @@ impl Shell {
         0
     }
 
-    fn cmd_dir(_args: &[&str]) -> isize {
-        let dir = match FileManager::read_dir("/") {
+    fn cmd_dbg(argv: &[&str]) -> isize {
+        if argv.len() < 2 {
+            println!("usage: dbg command [options]");
+            println!("attach PID\tAttach and stop the app");
+            println!("detach\t\tDetach and resume the app");
+            println!("break FUNC[+OFFSET]\tSet a breakpoint");
+            println!("delete [N]\tDelete the breakpoint, or show breakpoints");
+            println!("stop\t\tStop at the next instruction");
+            println!("step\t\tExecute one instruction");
+            println!("continue\tResume the app");
+            println!("locals\t\tShow local variables");
+            println!("stack\t\tShow the value stack");
+            println!("mem ADDR [LEN]\tDump the memory");
+            return 1;
+        }
+        let subcmd = argv[1];
+        if subcmd == "attach" {
+            let pid = match argv.get(2).and_then(|v| v.parse::<usize>().ok()) {
+                Some(v) => ProcessId(v),
+                None => {
+                    println!("usage: dbg attach PID");
+                    return 1;
+                }
+            };
+            return match megos::ArleDebugger::attach(pid) {
+                Some(_) => 0,
+                None => {
+                    println!("dbg: {} is not an Arlequin app", pid.0);
+                    1
+                }
+            };
+        }
+        let debugger = match megos::ArleDebugger::current() {
+            Some(v) => v,
+            None => {
+                println!("dbg: not attached");
+                return 1;
+            }
+        };
+        let parse_hex = |s: &str| usize::from_str_radix(s.trim_start_matches("0x"), 16).ok();
+        match subcmd {
+            "detach" => megos::ArleDebugger::detach(),
+            "break" => {
+                let mut iter = argv.get(2).unwrap_or(&"").splitn(2, '+');
+                let function = iter.next().and_then(|v| v.parse::<usize>().ok());
+                let position = iter.next().map(|v| parse_hex(v));
+                match (function, position) {
+                    (Some(function), None) => debugger.set_breakpoint(megos::ArleBreakpoint {
+                        function,
+                        position: None,
+                    }),
+                    (Some(function), Some(Some(position))) => {
+                        debugger.set_breakpoint(megos::ArleBreakpoint {
+                            function,
+                            position: Some(position),
+                        })
+                    }
+                    _ => {
+                        println!("usage: dbg break FUNC[+OFFSET]");
+                        return 1;
+                    }
+                }
+            }
+            "delete" => match argv.get(2).and_then(|v| v.parse::<usize>().ok()) {
+                Some(index) => {
+                    if debugger.remove_breakpoint(index).is_none() {
+                        println!("dbg: no breakpoint {}", index);
+                        return 1;
+                    }
+                }
+                None => {
+                    for (index, breakpoint) in debugger.breakpoints().iter().enumerate() {
+                        println!("#{} {}", index, breakpoint);
+                    }
+                }
+            },
+            "stop" => debugger.interrupt(),
+            "step" | "continue" => {
+                if !debugger.resume(subcmd == "step") {
+                    println!("dbg: not stopped");
+                    return 1;
+                }
+            }
+            "locals" | "stack" => {
+                let frame = match debugger.frame() {
+                    Some(v) => v,
+                    None => {
+                        println!("dbg: not stopped");
+                        return 1;
+                    }
+                };
+                println!(
+                    "{} {}",
+                    frame.thread.as_ref().and_then(|v| v.name()).unwrap_or(""),
+                    frame
+                );
+                if subcmd == "locals" {
+                    for (index, value) in frame.locals.iter().enumerate() {
+                        println!("  ${} {:?}", index, value);
+                    }
+                } else {
+                    for (index, value) in frame.stack.iter().enumerate().rev() {
+                        println!("  [{}] {:016x}", index, value);
+                    }
+                }
+            }
+            "mem" => {
+                let base = match argv.get(2).and_then(|v| parse_hex(v)) {
+                    Some(v) => v,
+                    None => {
+                        println!("usage: dbg mem ADDR [LEN]");
+                        return 1;
+                    }
+                };
+                let len = argv.get(3).and_then(|v| parse_hex(v)).unwrap_or(0x80);
+                let data = match debugger.read_memory(base, len) {
+                    Some(v) => v,
+                    None => {
+                        println!("dbg: cannot read memory");
+                        return 1;
+                    }
+                };
+                for (index, line) in data.chunks(16).enumerate() {
+                    let mut sb = StringBuffer::with_capacity(80);
+                    write!(sb, "{:08x}:", base + index * 16).unwrap();
+                    for byte in line {
+                        write!(sb, " {:02x}", byte).unwrap();
+                    }
+                    println!("{}", sb.as_str());
+                }
+            }
+            _ => {
+                println!("Unknown command: {}", subcmd);
+                return 1;
+            }
+        }
+        0
+    }
+
+    fn cmd_wasmdump(argv: &[&str]) -> isize {
+        let path = match argv.get(1) {
+            Some(v) => v,
+            None => {
+                println!("usage: wasmdump FILE");
+                return 1;
+            }
+        };
+        let mut file = match FileManager::open(path) {
+            Ok(v) => v,
+            Err(err) => {
+                println!("{:?}", err.kind());
+                return 1;
+            }
+        };
+        let file_size = file.stat().unwrap().len() as usize;
+        let mut vec = Vec::with_capacity(file_size);
+        vec.resize(file_size, 0);
+        let act_size = match file.read(vec.as_mut_slice()) {
+            Ok(v) => v,
+            Err(err) => {
+                println!("Error: {:?}", err.kind());
+                return 1;
+            }
+        };
+        let blob = &vec[..act_size];
+        if !wasm::WasmLoader::identity(blob) {
+            println!("Bad executable");
+            return 1;
+        }
+        let mut loader = megos::ArleBinaryLoader::new();
+        if loader.load(blob).is_err() {
+            return 1;
+        }
+        print!("{}", wasm::wat::WasmDisassembler::new(loader.module()));
+        0
+    }
+
+    fn cmd_dir(args: &[&str]) -> isize {
+        let path = args.get(1).unwrap_or(&".");
+        let dir = match FileManager::read_dir(path) {
             Ok(v) => v,
-            Err(_) => return 1,
+            Err(err) => {
+                println!("{:?}", err.kind());
+                return 1;
+            }
         };
         for dir_ent in dir {
             print!(" {:<14} ", dir_ent.name());
@@ -441,6 +668,45 @@ impl Shell {
         0
     }
 
+    fn cmd_lsblk(_: &[&str]) -> isize {
+        for (name, dev) in BlockDeviceManager::devices() {
+            let size = dev.total_sectors() * dev.sector_size() as u64;
+            println!(
+                "{:<8} {:>8} MB {:>5} bytes/sector{}",
+                name,
+                size >> 20,
+                dev.sector_size(),
+                if dev.is_read_only() {
+                    " (read only)"
+                } else {
+                    ""
+                },
+            );
+        }
+        0
+    }
+
+    fn cmd_mount(argv: &[&str]) -> isize {
+        if argv.len() < 3 {
+            println!("usage: mount DEVICE DIRECTORY");
+            return 1;
+        }
+        let dev = match BlockDeviceManager::get(argv[1]) {
+            Some(v) => v,
+            None => {
+                println!("No such device: {}", argv[1]);
+                return 1;
+            }
+        };
+        match FatFs::new(dev).and_then(|fs| FileManager::mount(Arc::new(fs), argv[2])) {
+            Ok(_) => 0,
+            Err(err) => {
+                println!("{:?}", err.kind());
+                1
+            }
+        }
+    }
+
     fn find_class_string(cc: PciClass) -> &'static str {
         #[rustfmt::skip]
         let entries = [
@@ -103,6 +103,16 @@ impl MemoryManager {
         va
     }
 
+    /// Removes the mapping created by `mmap`
+    #[inline]
+    pub unsafe fn munmap(va: NonZeroUsize, len: usize) {
+        PageManager::unmap(va.get(), len);
+        if Scheduler::is_enabled() {
+            // Other processors may still have the stale entries in their TLB
+            let _ = PageManager::broadcast_invalidate_tlb();
+        }
+    }
+
     #[inline]
     pub fn page_size_min(&self) -> usize {
         self.page_size_min
@@ -146,6 +156,36 @@ impl MemoryManager {
         None
     }
 
+    /// Deallocate pages allocated by `pg_alloc`
+    pub unsafe fn pg_dealloc(base: NonZeroUsize, layout: Layout) -> Result<(), DeallocationError> {
+        let shared = Self::shared();
+
+        let align_m1 = Self::PAGE_SIZE_MIN - 1;
+        let base = base.get();
+        let size = (layout.size() + align_m1) & !(align_m1);
+        if (base & align_m1) != 0 || size == 0 {
+            return Err(DeallocationError::InvalidArgument);
+        }
+
+        // Give the pages back to an adjacent free area if any
+        let n_free = shared.n_free.load(Ordering::SeqCst);
+        for free_pair in &shared.pairs[..n_free] {
+            if free_pair.merge(base, size) {
+                return Ok(());
+            }
+        }
+
+        // Otherwise they become a new free area
+        for (index, free_pair) in shared.pairs.iter().enumerate() {
+            if free_pair.replace_if_empty(base, size) {
+                shared.n_free.fetch_max(index + 1, Ordering::SeqCst);
+                return Ok(());
+            }
+        }
+
+        Err(DeallocationError::Unexpected)
+    }
+
     /// Allocate kernel memory
     pub unsafe fn zalloc(layout: Layout) -> Option<NonZeroUsize> {
         let shared = Self::shared();
@@ -276,6 +316,51 @@ impl MemFreePair {
         }
     }
 
+    /// Adds the area to this pair if they are adjacent
+    #[inline]
+    pub fn merge(&self, base: usize, size: usize) -> bool {
+        let new_base = base / Self::PAGE_SIZE;
+        let new_size = size / Self::PAGE_SIZE;
+
+        let p: &AtomicU64 = unsafe { transmute(&self.inner) };
+        let mut data = p.load(Ordering::SeqCst);
+        loop {
+            let (base, limit) = ((data & 0xFFFF_FFFF) as usize, (data >> 32) as usize);
+            let new_data = if limit == 0 {
+                return false;
+            } else if base + limit == new_base {
+                (base as u64) | (((limit + new_size) as u64) << 32)
+            } else if new_base + new_size == base {
+                (new_base as u64) | (((limit + new_size) as u64) << 32)
+            } else {
+                return false;
+            };
+
+            data = match p.compare_exchange(data, new_data, Ordering::SeqCst, Ordering::Relaxed) {
+                Ok(_) => return true,
+                Err(v) => v,
+            };
+        }
+    }
+
+    /// Replaces this pair with the area if this pair has no pages left
+    #[inline]
+    pub fn replace_if_empty(&self, base: usize, size: usize) -> bool {
+        let new_data = Self::new(base, size).inner;
+
+        let p: &AtomicU64 = unsafe { transmute(&self.inner) };
+        let mut data = p.load(Ordering::SeqCst);
+        loop {
+            if (data >> 32) != 0 {
+                return false;
+            }
+            data = match p.compare_exchange(data, new_data, Ordering::SeqCst, Ordering::Relaxed) {
+                Ok(_) => return true,
+                Err(v) => v,
+            };
+        }
+    }
+
     #[inline]
     fn split(&self) -> (usize, usize) {
         let p: &AtomicU64 = unsafe { transmute(&self.inner) };
@@ -303,7 +388,7 @@ bitflags! {
         const NONE  = 0x0;
 
         const READ_WRITE = Self::READ.bits | Self::WRITE.bits;
-        const READ_EXEC = Self::READ.bits | Self::WRITE.bits;
+        const READ_EXEC = Self::READ.bits | Self::EXEC.bits;
     }
 }
 
@@ -328,7 +413,7 @@ pub enum MemoryMapRequest {
     Mmio(PhysicalAddress, usize),
     /// for VRAM (physical_address, length)
     Vram(PhysicalAddress, usize),
-    /// for Kernel Mode Heap (base, length, attr)
+    /// for Kernel Mode alias of allocated pages (physical_address, length, attr)
     Kernel(usize, usize, MProtect),
     /// for User Mode Heap (base, length, attr)
     User(usize, usize, MProtect),
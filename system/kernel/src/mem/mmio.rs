@@ -71,6 +71,16 @@ impl Mmio {
         result
     }
 
+    #[inline]
+    #[track_caller]
+    pub unsafe fn read_u16(&self, offset: usize) -> u16 {
+        let mut result = 0;
+        self.check_limit(offset, &result);
+        let ptr: &AtomicU16 = transmute(self.base + offset);
+        result = ptr.load(Ordering::SeqCst);
+        result
+    }
+
     #[inline]
     #[track_caller]
     pub unsafe fn read_u32(&self, offset: usize) -> u32 {
@@ -100,6 +110,14 @@ impl Mmio {
         ptr.store(value, Ordering::SeqCst);
     }
 
+    #[inline]
+    #[track_caller]
+    pub unsafe fn write_u16(&self, offset: usize, value: u16) {
+        self.check_limit(offset, &value);
+        let ptr: &AtomicU16 = transmute(self.base + offset);
+        ptr.store(value, Ordering::SeqCst);
+    }
+
     #[inline]
     #[track_caller]
     pub unsafe fn write_u32(&self, offset: usize, value: u32) {
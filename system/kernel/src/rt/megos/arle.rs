@@ -2,34 +2,176 @@
 
 use super::*;
 use crate::{
-    sync::Mutex,
+    arch::page::{PageManager, PhysicalAddress},
+    mem::*,
+    sync::{semaphore::Semaphore, Mutex},
     ui::theme::Theme,
     *,
     {io::hid::*, ui::text::*, ui::window::*},
 };
-use alloc::collections::BTreeMap;
+use alloc::{collections::BTreeMap, sync::Arc};
 use byteorder::*;
 use core::{
-    alloc::Layout, intrinsics::transmute, num::NonZeroU32, sync::atomic::*, time::Duration,
+    alloc::Layout,
+    intrinsics::transmute,
+    num::{NonZeroU32, NonZeroUsize},
+    ptr::NonNull,
+    sync::atomic::*,
+    time::Duration,
 };
 use megstd::drawing::*;
 use megstd::rand::*;
 use num_traits::FromPrimitive;
-use wasm::{wasmintr::*, *};
+use wasm::{wasmintr::*, wasmjit::*, *};
+
+static JIT_ENABLED: AtomicBool = AtomicBool::new(false);
+
+static PROFILER_ENABLED: AtomicBool = AtomicBool::new(false);
+
+static JIT_ALLOCATOR: ArleJitAllocator = ArleJitAllocator { _phantom: () };
+
+/// Precompiled images of the modules loaded recently.
+///
+/// They are kept in memory only, since the file system cannot write next to the binaries yet.
+static IMAGE_CACHE: Mutex<Vec<ArleCachedImage>> = Mutex::new(Vec::new());
+
+/// A precompiled image with the binary it was made from
+///
+/// The digest is not a cryptographic hash, so the binary itself is compared before the image is used.
+struct ArleCachedImage {
+    digest: u64,
+    binary: Box<[u8]>,
+    image: Vec<u8>,
+}
 
 pub struct ArleBinaryLoader {
     loader: WasmLoader,
     lio: LoadedImageOption,
+    use_jit: bool,
 }
 
 impl ArleBinaryLoader {
     pub fn new() -> Self {
         Self {
             loader: WasmLoader::new(),
-            lio: LoadedImageOption::default(),
+            lio: LoadedImageOption {
+                memory_quota: Some(Self::DEFAULT_MEMORY_QUOTA),
+                ..Default::default()
+            },
+            use_jit: Self::is_jit_enabled(),
         }
     }
 
+    /// Sets the maximum size of the memory of this app in bytes, or `None` for no limit.
+    ///
+    /// `memory.grow` and the allocation beyond the quota fail, and the app is not started if its initial memory exceeds it.
+    #[inline]
+    pub fn memory_quota(&mut self, quota: Option<usize>) -> &mut Self {
+        self.lio.memory_quota = quota;
+        self
+    }
+
+    /// Returns whether newly loaded modules are compiled by the JIT by default
+    #[inline]
+    pub fn is_jit_enabled() -> bool {
+        JIT_ENABLED.load(Ordering::Relaxed)
+    }
+
+    /// Sets whether newly loaded modules are compiled by the JIT by default
+    #[inline]
+    pub fn set_jit_enabled(enabled: bool) {
+        JIT_ENABLED.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Returns whether newly loaded modules are profiled
+    #[inline]
+    pub fn is_profiler_enabled() -> bool {
+        PROFILER_ENABLED.load(Ordering::Relaxed)
+    }
+
+    /// Sets whether newly loaded modules are profiled, the report is printed when the app exits.
+    #[inline]
+    pub fn set_profiler_enabled(enabled: bool) {
+        PROFILER_ENABLED.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Selects whether this module is compiled by the JIT
+    #[inline]
+    pub fn use_jit(&mut self, enabled: bool) -> &mut Self {
+        self.use_jit = enabled;
+        self
+    }
+
+    /// Default maximum size of the memory of an app
+    pub const DEFAULT_MEMORY_QUOTA: usize = 0x200_0000;
+
+    /// Maximum number of precompiled images in the cache
+    const MAX_CACHED_IMAGES: usize = 8;
+
+    /// Returns the module loaded by this loader
+    #[inline]
+    pub fn module(&self) -> &WasmModule {
+        self.loader.module()
+    }
+
+    fn resolve(
+        mod_name: &str,
+        name: &str,
+        _type_ref: &WasmType,
+    ) -> Result<WasmDynFunc, WasmDecodeErrorType> {
+        match mod_name {
+            ArleRuntime::MOD_NAME => match name {
+                "svc0" | "svc1" | "svc2" | "svc3" | "svc4" | "svc5" | "svc6" => {
+                    Ok(ArleRuntime::syscall)
+                }
+                _ => Err(WasmDecodeErrorType::NoMethod),
+            },
+            _ => Err(WasmDecodeErrorType::NoModule),
+        }
+    }
+
+    /// Loads the module from the precompiled image in the cache, if any.
+    fn load_cached(&mut self, blob: &[u8], digest: u64) -> bool {
+        let cache = IMAGE_CACHE.lock().unwrap();
+        let image = match cache
+            .iter()
+            .find(|v| v.digest == digest && *v.binary == *blob)
+        {
+            Some(v) => &v.image,
+            None => return false,
+        };
+        if self.loader.load_image(image, digest, Self::resolve).is_ok() {
+            true
+        } else {
+            // The image is stale, so it will be replaced
+            self.loader = WasmLoader::new();
+            self.loader.set_memory_quota(self.lio.memory_quota);
+            false
+        }
+    }
+
+    fn store_cache(&self, blob: &[u8], digest: u64) {
+        // Modules that import other than functions cannot be precompiled
+        let image = match self.loader.module().to_image(digest) {
+            Ok(v) => v,
+            Err(_) => return,
+        };
+        let mut cache = IMAGE_CACHE.lock().unwrap();
+        cache.retain(|v| v.digest != digest);
+        if cache.len() >= Self::MAX_CACHED_IMAGES {
+            cache.remove(0);
+        }
+        cache.push(ArleCachedImage {
+            digest,
+            binary: blob.into(),
+            image,
+        });
+    }
+
+    fn profile_clock() -> u64 {
+        Timer::monotonic().as_nanos() as u64
+    }
+
     fn start(_: usize) {
         Scheduler::current_personality(|personality| match personality.context() {
             PersonalityContext::Arlequin(rt) => rt.start(),
@@ -44,28 +186,52 @@ impl BinaryLoader for ArleBinaryLoader {
     }
 
     fn load(&mut self, blob: &[u8]) -> Result<(), ()> {
-        self.loader
-            .load(blob, |mod_name, name, _type_ref| match mod_name {
-                ArleRuntime::MOD_NAME => match name {
-                    "svc0" | "svc1" | "svc2" | "svc3" | "svc4" | "svc5" | "svc6" => {
-                        Ok(ArleRuntime::syscall)
-                    }
-                    _ => Err(WasmDecodeErrorType::NoMethod),
-                },
-                _ => Err(WasmDecodeErrorType::NoModule),
-            })
-            .map_err(|v| {
-                println!("Load error: {:?}", v);
-                ()
-            })
+        self.loader.set_memory_quota(self.lio.memory_quota);
+        let digest = WasmLoader::digest(blob);
+        if self.load_cached(blob, digest) {
+            return Ok(());
+        }
+        self.loader.load(blob, Self::resolve).map_err(|v| {
+            println!("Load error: {:?}", v);
+            ()
+        })?;
+        self.store_cache(blob, digest);
+        Ok(())
     }
 
     fn invoke_start(self: Box<Self>) -> Option<ProcessId> {
         match self.loader.module().func(ArleRuntime::ENTRY_FUNC_NAME) {
             Ok(_) => {
-                let module = self.loader.into_module();
+                let memory_quota = self.lio.memory_quota;
+                if let Some(quota) = memory_quota {
+                    let size = self
+                        .loader
+                        .module()
+                        .memories()
+                        .iter()
+                        .fold(0, |acc, v| acc + v.capacity());
+                    if size > quota {
+                        println!(
+                            "error: memory size {} KB exceeds the quota {} KB",
+                            size / 1024,
+                            quota / 1024
+                        );
+                        return None;
+                    }
+                }
+                let mut module = self.loader.into_module();
+                if Self::is_profiler_enabled() {
+                    // Profiled modules run on the interpreter
+                    module.enable_profiler(Some(Self::profile_clock));
+                    module.set_fuel(Some(ArleRuntime::FUEL_QUANTUM));
+                } else if self.use_jit {
+                    module.compile_jit(&JIT_ALLOCATOR);
+                } else {
+                    // Apps that never call the system can still be closed
+                    module.set_fuel(Some(ArleRuntime::FUEL_QUANTUM));
+                }
                 SpawnOption::new()
-                    .personality(ArleRuntime::new(module))
+                    .personality(ArleRuntime::new(module, memory_quota))
                     .start_process(Self::start, 0, self.lio.name.as_ref())
             }
             Err(err) => {
@@ -76,17 +242,76 @@ impl BinaryLoader for ArleBinaryLoader {
     }
 }
 
+/// Allocates executable pages for JIT compiled code
+struct ArleJitAllocator {
+    _phantom: (),
+}
+
+impl ArleJitAllocator {
+    #[inline]
+    fn layout(len: usize) -> Layout {
+        unsafe {
+            Layout::from_size_align_unchecked(
+                (len + MemoryManager::PAGE_SIZE_MIN - 1) & !(MemoryManager::PAGE_SIZE_MIN - 1),
+                MemoryManager::PAGE_SIZE_MIN,
+            )
+        }
+    }
+}
+
+impl WasmJitAllocator for ArleJitAllocator {
+    fn alloc_code(&self, code: &[u8]) -> Option<NonNull<u8>> {
+        let layout = Self::layout(code.len());
+        unsafe {
+            // The code is written through the direct map, which is never executable,
+            // and then executed through a read-only alias.
+            let pa = MemoryManager::pg_alloc(layout)?.get();
+            let p = PageManager::direct_map(pa as PhysicalAddress) as *mut u8;
+            p.copy_from_nonoverlapping(code.as_ptr(), code.len());
+            p.add(code.len())
+                .write_bytes(0xCC, layout.size() - code.len());
+            MemoryManager::mmap(MemoryMapRequest::Kernel(
+                pa,
+                layout.size(),
+                MProtect::READ_EXEC,
+            ))
+            .and_then(|va| NonNull::new(va.get() as *mut u8))
+        }
+    }
+
+    unsafe fn free_code(&self, ptr: NonNull<u8>, len: usize) {
+        // The executable alias goes away first, then the pages are wiped and returned.
+        let layout = Self::layout(len);
+        let va = ptr.as_ptr() as usize;
+        let pa = PageManager::kernel_alias_to_physical(va);
+        MemoryManager::munmap(NonZeroUsize::new_unchecked(va), layout.size());
+        (PageManager::direct_map(pa) as *mut u8).write_bytes(0xCC, layout.size());
+        let _ = MemoryManager::pg_dealloc(NonZeroUsize::new_unchecked(pa as usize), layout);
+    }
+}
+
 /// Contextual structure of the MEG-OS Arlequin subsystem
-#[allow(dead_code)]
+///
+/// Each thread of an app runs on its own instance of the module, and shares the rest with the other threads.
 pub struct ArleRuntime {
-    // uuid: Uuid,
     module: WasmModule,
+    shared: Arc<ArleShared>,
+}
+
+/// The state of an app shared by its threads
+#[allow(dead_code)]
+struct ArleShared {
+    // uuid: Uuid,
     next_handle: AtomicUsize,
-    windows: Mutex<BTreeMap<usize, UnsafeCell<OsWindow>>>,
-    rng32: XorShift32,
+    windows: Mutex<BTreeMap<usize, Arc<Mutex<OsWindow>>>>,
+    rng32: Mutex<XorShift32>,
     key_buffer: Mutex<Vec<KeyEvent>>,
     malloc: Mutex<SimpleAllocator>,
     has_to_exit: AtomicBool,
+    threads: Mutex<Vec<ThreadHandle>>,
+    waiters: Mutex<Vec<Arc<ArleWaiter>>>,
+    debugger: Arc<ArleDebugger>,
+    memory_quota: Option<usize>,
 }
 
 impl ArleRuntime {
@@ -95,25 +320,38 @@ impl ArleRuntime {
 
     const SIZE_KEYBUFFER: usize = 32;
 
-    fn new(module: WasmModule) -> Box<Self> {
+    /// Amount of fuel between checks for pending window messages
+    const FUEL_QUANTUM: u64 = 0x10_0000;
+
+    fn new(mut module: WasmModule, memory_quota: Option<usize>) -> Box<Self> {
+        module.set_fuel_handler(Some(Self::refill_fuel));
+        module.set_atomic_handlers(Some(Self::atomic_wait), Some(Self::atomic_notify));
+        module.set_grow_handler(Some(Self::grow_memory));
         Box::new(Self {
-            // uuid: Uuid::generate().unwrap(),
             module,
-            next_handle: AtomicUsize::new(1),
-            windows: Mutex::new(BTreeMap::new()),
-            rng32: XorShift32::default(),
-            key_buffer: Mutex::new(Vec::with_capacity(Self::SIZE_KEYBUFFER)),
-            malloc: Mutex::new(SimpleAllocator::default()),
-            has_to_exit: AtomicBool::new(false),
+            shared: Arc::new(ArleShared {
+                // uuid: Uuid::generate().unwrap(),
+                next_handle: AtomicUsize::new(1),
+                windows: Mutex::new(BTreeMap::new()),
+                rng32: Mutex::new(XorShift32::default()),
+                key_buffer: Mutex::new(Vec::with_capacity(Self::SIZE_KEYBUFFER)),
+                malloc: Mutex::new(SimpleAllocator::default()),
+                has_to_exit: AtomicBool::new(false),
+                threads: Mutex::new(Vec::new()),
+                waiters: Mutex::new(Vec::new()),
+                debugger: ArleDebugger::new(),
+                memory_quota,
+            }),
         })
     }
 
     fn next_handle(&self) -> usize {
-        let result = 1 + self.next_handle.load(Ordering::SeqCst);
-        self.next_handle.swap(result, Ordering::SeqCst)
+        self.shared.next_handle.fetch_add(1, Ordering::SeqCst)
     }
 
     fn start(&self) -> ! {
+        ArleDebugger::register(Scheduler::current_pid(), &self.shared.debugger);
+
         let function = match self.module.func(Self::ENTRY_FUNC_NAME) {
             Ok(v) => v,
             Err(err) => {
@@ -126,13 +364,111 @@ impl ArleRuntime {
             Ok(_v) => (),
             Err(err) => match err.kind() {
                 WasmRuntimeErrorType::Exit => (),
-                _ => println!("error: {:?}", err),
+                _ => {
+                    println!("error: {:?}", err);
+                    self.print_backtrace(&err);
+                }
             },
         }
 
+        // The app ends when all of its threads have ended.
+        let threads = {
+            let mut threads = self.shared.threads.lock().unwrap();
+            self.request_exit();
+            core::mem::take(&mut *threads)
+        };
+        for thread in threads {
+            thread.join();
+        }
+
+        if let Some(profiler) = self.module.profiler() {
+            println!("profile:");
+            print!("{}", profiler.report(&self.module));
+        }
+
         RuntimeEnvironment::exit(0);
     }
 
+    /// Tells all threads of the app to exit, and wakes the threads waiting in `memory.atomic.wait`.
+    fn request_exit(&self) {
+        self.shared.has_to_exit.store(true, Ordering::SeqCst);
+        let waiters = core::mem::take(&mut *self.shared.waiters.lock().unwrap());
+        for waiter in waiters {
+            waiter.signal.signal();
+        }
+    }
+
+    /// Spawns a thread that runs the exported function on another instance of the module,
+    /// which shares the memory with this instance.
+    fn spawn_thread(&self, name: &str, arg: u32) -> Option<ThreadHandle> {
+        let function = self.module.func(name).ok()?;
+        if function.function().param_types() != &[WasmValType::I32] {
+            return None;
+        }
+        let args = function.function().index() << 32 | arg as usize;
+        let module = self.module.instantiate_thread()?;
+        let mut threads = self.shared.threads.lock().unwrap();
+        if self.shared.has_to_exit.load(Ordering::SeqCst) {
+            return None;
+        }
+        let personality = Box::new(ArleThread {
+            runtime: ArleRuntime {
+                module,
+                shared: self.shared.clone(),
+            },
+        });
+        let thread =
+            SpawnOption::new()
+                .personality(personality)
+                .start(Self::start_thread, args, name)?;
+        threads.push(thread);
+        Some(thread)
+    }
+
+    fn start_thread(args: usize) {
+        Scheduler::current_personality(|personality| match personality.context() {
+            PersonalityContext::Arlequin(rt) => rt.run_thread(args >> 32, args as u32),
+            _ => unreachable!(),
+        });
+    }
+
+    fn run_thread(&self, func_index: usize, arg: u32) {
+        let result = self
+            .module
+            .func_by_index(func_index)
+            .and_then(|v| v.invoke(&[WasmValue::from(arg)]));
+        match result {
+            Ok(_v) => (),
+            Err(err) => match err.kind() {
+                WasmRuntimeErrorType::Exit => (),
+                _ => {
+                    println!("error: {:?}", err);
+                    self.print_backtrace(&err);
+                }
+            },
+        }
+    }
+
+    fn print_backtrace(&self, err: &WasmRuntimeError) {
+        for (index, frame) in err.backtrace().enumerate() {
+            match self.module.func_name(frame.function()) {
+                Some(name) => println!(
+                    "  #{} {}+{:x} (function {})",
+                    index,
+                    name,
+                    frame.position(),
+                    frame.function()
+                ),
+                None => println!(
+                    "  #{} function {}+{:x}",
+                    index,
+                    frame.function(),
+                    frame.position()
+                ),
+            }
+        }
+    }
+
     fn syscall(_: &WasmModule, params: &[WasmValue]) -> Result<WasmValue, WasmRuntimeErrorType> {
         Scheduler::current_personality(|personality| match personality.context() {
             PersonalityContext::Arlequin(rt) => rt.dispatch_syscall(&params),
@@ -141,6 +477,161 @@ impl ArleRuntime {
         .unwrap()
     }
 
+    fn refill_fuel(_: &WasmModule) -> Result<u64, WasmRuntimeErrorType> {
+        Scheduler::current_personality(|personality| match personality.context() {
+            PersonalityContext::Arlequin(rt) => rt.process_pending_messages(),
+            _ => unreachable!(),
+        })
+        .unwrap()
+    }
+
+    fn atomic_wait(_: &WasmModule, wait: &WasmAtomicWait) -> Result<u32, WasmRuntimeErrorType> {
+        Scheduler::current_personality(|personality| match personality.context() {
+            PersonalityContext::Arlequin(rt) => rt.wait(wait),
+            _ => unreachable!(),
+        })
+        .unwrap()
+    }
+
+    fn grow_memory(_: &WasmModule, current: u32, requested: u32) -> bool {
+        Scheduler::current_personality(|personality| match personality.context() {
+            PersonalityContext::Arlequin(rt) => rt.check_memory_quota(current, requested),
+            _ => unreachable!(),
+        })
+        .unwrap()
+    }
+
+    /// Decides whether the memory can grow from `current` to `requested` pages within the quota.
+    fn check_memory_quota(&self, current: u32, requested: u32) -> bool {
+        let quota = match self.shared.memory_quota {
+            Some(v) => v,
+            None => return true,
+        };
+        let size = requested as usize * WasmMemory::PAGE_SIZE;
+        let result = size <= quota;
+        println!(
+            "memory.grow: pid {} {} => {} pages, quota {} KB: {}",
+            Scheduler::current_pid().0,
+            current,
+            requested,
+            quota / 1024,
+            if result { "allowed" } else { "denied" }
+        );
+        result
+    }
+
+    fn debug(frame: &WasmDebugFrame) -> Result<(), WasmRuntimeErrorType> {
+        Scheduler::current_personality(|personality| match personality.context() {
+            PersonalityContext::Arlequin(rt) => rt.shared.debugger.check(frame),
+            _ => unreachable!(),
+        })
+        .unwrap()
+    }
+
+    /// Installs the debug handler if the debugger has been attached since the last check.
+    fn check_debugger(&self) {
+        if self.shared.debugger.is_attached() && self.module.debug_handler().is_none() {
+            self.module.set_debug_handler(Some(Self::debug));
+        }
+    }
+
+    fn atomic_notify(
+        _: &WasmModule,
+        address: usize,
+        count: u32,
+    ) -> Result<u32, WasmRuntimeErrorType> {
+        Scheduler::current_personality(|personality| match personality.context() {
+            PersonalityContext::Arlequin(rt) => Ok(rt.notify(address, count)),
+            _ => unreachable!(),
+        })
+        .unwrap()
+    }
+
+    /// memory.atomic.wait
+    fn wait(&self, wait: &WasmAtomicWait) -> Result<u32, WasmRuntimeErrorType> {
+        let waiter = Arc::new(ArleWaiter {
+            address: wait.address(),
+            signal: Semaphore::new(0),
+        });
+        {
+            let mut waiters = self.shared.waiters.lock().unwrap();
+            if self.shared.has_to_exit.load(Ordering::SeqCst) {
+                return Err(WasmRuntimeErrorType::Exit);
+            }
+            if !wait.is_expected() {
+                return Ok(1);
+            }
+            waiters.push(waiter.clone());
+        }
+
+        let is_signalled = match wait.timeout() {
+            Some(timeout) => waiter.signal.wait_timeout(timeout),
+            None => {
+                waiter.signal.wait();
+                true
+            }
+        };
+        if self.shared.has_to_exit.load(Ordering::SeqCst) {
+            self.remove_waiter(&waiter);
+            return Err(WasmRuntimeErrorType::Exit);
+        }
+        if is_signalled || !self.remove_waiter(&waiter) {
+            Ok(0)
+        } else {
+            Ok(2)
+        }
+    }
+
+    /// Removes the waiter from the queue, returns false if it has already been notified.
+    fn remove_waiter(&self, waiter: &Arc<ArleWaiter>) -> bool {
+        let mut waiters = self.shared.waiters.lock().unwrap();
+        match waiters.iter().position(|v| Arc::ptr_eq(v, waiter)) {
+            Some(index) => {
+                waiters.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// memory.atomic.notify
+    fn notify(&self, address: usize, count: u32) -> u32 {
+        let mut waiters = self.shared.waiters.lock().unwrap();
+        let mut woken = 0;
+        waiters.retain(|waiter| {
+            if woken < count && waiter.address == address {
+                waiter.signal.signal();
+                woken += 1;
+                false
+            } else {
+                true
+            }
+        });
+        woken
+    }
+
+    fn process_pending_messages(&self) -> Result<u64, WasmRuntimeErrorType> {
+        self.check_debugger();
+        let windows = self
+            .shared
+            .windows
+            .lock()
+            .unwrap()
+            .values()
+            .map(|v| v.lock().unwrap().native())
+            .collect::<Vec<_>>();
+        for handle in windows {
+            while let Some(message) = handle.read_message() {
+                self.process_message(handle, message);
+            }
+        }
+        if self.shared.has_to_exit.load(Ordering::Relaxed) {
+            Err(WasmRuntimeErrorType::Exit)
+        } else {
+            Ok(Self::FUEL_QUANTUM)
+        }
+    }
+
     fn dispatch_syscall(
         &mut self,
         params: &[WasmValue],
@@ -154,12 +645,14 @@ impl ArleRuntime {
         let func_no = params.get_u32().and_then(|v| {
             FromPrimitive::from_u32(v).ok_or(WasmRuntimeErrorType::InvalidParameter)
         })?;
-        if self.has_to_exit.load(Ordering::Relaxed) {
+        if self.shared.has_to_exit.load(Ordering::Relaxed) {
             return Err(WasmRuntimeErrorType::Exit);
         }
+        self.check_debugger();
 
         match func_no {
             Function::Exit => {
+                self.request_exit();
                 return Err(WasmRuntimeErrorType::Exit);
             }
 
@@ -207,17 +700,18 @@ impl ArleRuntime {
 
                 if window.as_usize() != 0 {
                     let handle = self.next_handle();
-                    let window = UnsafeCell::new(OsWindow::new(handle, window));
-                    self.windows.lock().unwrap().insert(handle, window);
+                    let window = Arc::new(Mutex::new(OsWindow::new(handle, window)));
+                    self.shared.windows.lock().unwrap().insert(handle, window);
                     return Ok(WasmValue::I32(handle as i32));
                 }
             }
             Function::CloseWindow => {
                 let handle = params.get_usize()?;
-                self.windows.lock().unwrap().remove(&handle);
+                self.shared.windows.lock().unwrap().remove(&handle);
             }
             Function::BeginDraw => match params.get_window(self) {
                 Ok(window) => {
+                    let mut window = window.lock().unwrap();
                     window.begin_draw();
                     return Ok(WasmValue::from(window.handle() as u32));
                 }
@@ -225,13 +719,14 @@ impl ArleRuntime {
             },
             Function::EndDraw => match params.get_window(self) {
                 Ok(window) => {
-                    window.end_draw();
+                    window.lock().unwrap().end_draw();
                 }
                 Err(err) => return Err(err),
             },
 
             Function::DrawString => {
                 let window = params.get_window(self)?;
+                let mut window = window.lock().unwrap();
 
                 let max_lines = 0;
                 let origin = params.get_point()?;
@@ -252,6 +747,7 @@ impl ArleRuntime {
             }
             Function::FillRect => {
                 let window = params.get_window(self)?;
+                let mut window = window.lock().unwrap();
                 let origin = params.get_point()?;
                 let size = params.get_size()?;
                 let color = params.get_color()?;
@@ -262,6 +758,7 @@ impl ArleRuntime {
             }
             Function::DrawRect => {
                 let window = params.get_window(self)?;
+                let mut window = window.lock().unwrap();
                 let origin = params.get_point()?;
                 let size = params.get_size()?;
                 let color = params.get_color()?;
@@ -272,6 +769,7 @@ impl ArleRuntime {
             }
             Function::DrawLine => {
                 let window = params.get_window(self)?;
+                let mut window = window.lock().unwrap();
                 let c1 = params.get_point()?;
                 let c2 = params.get_point()?;
                 let color = params.get_color()?;
@@ -281,13 +779,13 @@ impl ArleRuntime {
                 });
             }
             Function::WaitChar => {
-                let window = params.get_window(self)?;
+                let window = params.get_window(self)?.lock().unwrap().native();
                 return self
                     .wait_key(window)
                     .map(|c| WasmValue::I32(c.unwrap_or('\0') as i32));
             }
             Function::ReadChar => {
-                let window = params.get_window(self)?;
+                let window = params.get_window(self)?.lock().unwrap().native();
                 let c = self.read_key(window);
                 return Ok(WasmValue::from(
                     c.map(|v| v as u32).unwrap_or(megosabi::OPTION_CHAR_NONE),
@@ -295,6 +793,7 @@ impl ArleRuntime {
             }
             Function::Blt8 => {
                 let window = params.get_window(self)?;
+                let mut window = window.lock().unwrap();
                 let origin = params.get_point()?;
                 let src = params.get_bitmap8(memory)?;
                 let rect = Rect {
@@ -312,6 +811,7 @@ impl ArleRuntime {
             }
             Function::Blt32 => {
                 let window = params.get_window(self)?;
+                let mut window = window.lock().unwrap();
                 let origin = params.get_point()?;
                 let src = params.get_bitmap32(memory)?;
                 let rect = Rect {
@@ -337,6 +837,7 @@ impl ArleRuntime {
             }
             Function::Blt1 => {
                 let window = params.get_window(self)?;
+                let mut window = window.lock().unwrap();
                 let origin = params.get_point()?;
                 let os_bitmap = params.get_bitmap1(memory)?;
                 let color = params.get_color()?;
@@ -347,11 +848,12 @@ impl ArleRuntime {
             }
 
             Function::Rand => {
-                return Ok(WasmValue::from(self.rng32.next()));
+                return Ok(WasmValue::from(self.shared.rng32.lock().unwrap().next()));
             }
             Function::Srand => {
                 let seed = params.get_u32()?;
-                NonZeroU32::new(seed).map(|v| self.rng32 = XorShift32::new(v));
+                NonZeroU32::new(seed)
+                    .map(|v| *self.shared.rng32.lock().unwrap() = XorShift32::new(v));
             }
 
             Function::Alloc => {
@@ -359,7 +861,7 @@ impl ArleRuntime {
                 let align = params.get_usize()?;
                 let layout = Layout::from_size_align(size, align)
                     .map_err(|_| WasmRuntimeErrorType::InvalidParameter)?;
-                let mut malloc = self.malloc.lock().unwrap();
+                let mut malloc = self.shared.malloc.lock().unwrap();
                 // return Err(WasmRuntimeErrorType::OutOfMemory);
 
                 if let Some(result) = malloc.alloc(layout) {
@@ -369,7 +871,7 @@ impl ArleRuntime {
                     let min_alloc = WasmMemory::PAGE_SIZE;
                     let delta = (((layout.size() + min_alloc - 1) / min_alloc) * min_alloc
                         / WasmMemory::PAGE_SIZE) as i32;
-                    let new_page = memory.grow(delta);
+                    let new_page = self.module.grow_memory(0, delta);
                     if new_page > 0 {
                         println!("grow {} => {}", delta, new_page);
                         malloc.append_block(
@@ -399,18 +901,25 @@ impl ArleRuntime {
                 println!("dealloc {:08x} {:?}", base, layout);
                 memory.write_bytes(base as usize, 0xCC, size)?;
 
-                self.malloc.lock().unwrap().dealloc(base, layout);
+                self.shared.malloc.lock().unwrap().dealloc(base, layout);
+            }
+
+            Function::SpawnThread => {
+                let name = params.get_string(memory).map(|v| v.to_string());
+                let arg = params.get_u32()?;
+                if let Some(thread) = name.and_then(|name| self.spawn_thread(&name, arg)) {
+                    return Ok(WasmValue::from(thread.as_usize() as u32));
+                }
             }
         }
 
         Ok(WasmValue::I32(0))
     }
 
-    fn wait_key(&self, window: &OsWindow) -> Result<Option<char>, WasmRuntimeErrorType> {
-        let handle = window.native();
+    fn wait_key(&self, handle: WindowHandle) -> Result<Option<char>, WasmRuntimeErrorType> {
         while let Some(message) = handle.wait_message() {
             self.process_message(handle, message);
-            if self.has_to_exit.load(Ordering::Relaxed) {
+            if self.shared.has_to_exit.load(Ordering::Relaxed) {
                 return Err(WasmRuntimeErrorType::Exit);
             }
 
@@ -424,8 +933,7 @@ impl ArleRuntime {
         Err(WasmRuntimeErrorType::TypeMismatch)
     }
 
-    fn read_key(&self, window: &OsWindow) -> Option<char> {
-        let handle = window.native();
+    fn read_key(&self, handle: WindowHandle) -> Option<char> {
         while let Some(message) = handle.read_message() {
             self.process_message(handle, message);
         }
@@ -433,7 +941,7 @@ impl ArleRuntime {
     }
 
     fn read_key_buffer(&self) -> Option<KeyEvent> {
-        let mut buffer = self.key_buffer.lock().unwrap();
+        let mut buffer = self.shared.key_buffer.lock().unwrap();
         if buffer.len() > 0 {
             Some(buffer.remove(0))
         } else {
@@ -444,17 +952,17 @@ impl ArleRuntime {
     fn process_message(&self, window: WindowHandle, message: WindowMessage) {
         match message {
             WindowMessage::Close => {
-                if self.windows.lock().unwrap().values().count() > 1 {
+                if self.shared.windows.lock().unwrap().values().count() > 1 {
                     // todo:
                     window.close();
                 } else {
-                    self.has_to_exit.store(true, Ordering::SeqCst);
+                    self.request_exit();
                 }
             }
             WindowMessage::Key(event) => {
                 event
                     .key_data()
-                    .map(|data| self.key_buffer.lock().unwrap().push(data));
+                    .map(|data| self.shared.key_buffer.lock().unwrap().push(data));
             }
             _ => window.handle_default_message(message),
         }
@@ -467,54 +975,33 @@ impl Personality for ArleRuntime {
     }
 
     fn on_exit(&mut self) {
-        self.windows.lock().unwrap().clear();
+        ArleDebugger::unregister(&self.shared.debugger);
+        self.shared.windows.lock().unwrap().clear();
     }
 }
 
-struct ParamsDecoder<'a> {
-    params: &'a [WasmValue],
-    index: usize,
-}
-
-impl<'a> ParamsDecoder<'a> {
-    #[inline]
-    pub const fn new(params: &'a [WasmValue]) -> Self {
-        Self { params, index: 0 }
-    }
+/// Personality of the threads spawned by an app
+///
+/// Each thread runs on its own instance of the module, and the main thread waits for these threads before the app exits.
+struct ArleThread {
+    runtime: ArleRuntime,
 }
 
-impl ParamsDecoder<'_> {
-    #[inline]
-    fn get_u32(&mut self) -> Result<u32, WasmRuntimeErrorType> {
-        let index = self.index;
-        self.params
-            .get(index)
-            .ok_or(WasmRuntimeErrorType::InvalidParameter)
-            .and_then(|v| v.get_u32())
-            .map(|v| {
-                self.index += 1;
-                v
-            })
+impl Personality for ArleThread {
+    fn context(&mut self) -> PersonalityContext {
+        PersonalityContext::Arlequin(&mut self.runtime)
     }
 
-    #[inline]
-    fn get_i32(&mut self) -> Result<i32, WasmRuntimeErrorType> {
-        let index = self.index;
-        self.params
-            .get(index)
-            .ok_or(WasmRuntimeErrorType::InvalidParameter)
-            .and_then(|v| v.get_i32())
-            .map(|v| {
-                self.index += 1;
-                v
-            })
-    }
+    fn on_exit(&mut self) {}
+}
 
-    #[inline]
-    fn get_usize(&mut self) -> Result<usize, WasmRuntimeErrorType> {
-        self.get_u32().map(|v| v as usize)
-    }
+/// A thread waiting in `memory.atomic.wait`, which is signalled by `memory.atomic.notify`
+struct ArleWaiter {
+    address: usize,
+    signal: Semaphore,
+}
 
+impl ParamsDecoder<'_> {
     #[inline]
     fn get_memarg(&mut self) -> Result<MemArg, WasmRuntimeErrorType> {
         let base = self.get_u32()? as usize;
@@ -612,17 +1099,18 @@ impl ParamsDecoder<'_> {
     }
 
     #[inline]
-    fn get_window<'a>(
+    fn get_window(
         &mut self,
-        rt: &'a ArleRuntime,
-    ) -> Result<&'a mut OsWindow, WasmRuntimeErrorType> {
+        rt: &ArleRuntime,
+    ) -> Result<Arc<Mutex<OsWindow>>, WasmRuntimeErrorType> {
         match self.get_u32() {
             Ok(v) => rt
+                .shared
                 .windows
                 .lock()
                 .unwrap()
                 .get(&(v as usize))
-                .map(|v| unsafe { &mut *v.get() })
+                .cloned()
                 .ok_or(WasmRuntimeErrorType::InvalidParameter),
             Err(err) => Err(err),
         }
@@ -0,0 +1,333 @@
+// Debugger of the Arlequin apps
+
+use super::*;
+use crate::sync::{semaphore::Semaphore, Mutex};
+use alloc::{collections::VecDeque, sync::Arc};
+use core::{fmt, sync::atomic::*};
+use wasm::{opcode::WasmOpcode, wasmintr::*, *};
+
+/// Apps that can be attached
+static DEBUGGEES: Mutex<Vec<(ProcessId, Arc<ArleDebugger>)>> = Mutex::new(Vec::new());
+
+/// The app currently attached from the shell
+static SESSION: Mutex<Option<Arc<ArleDebugger>>> = Mutex::new(None);
+
+/// Debugger of an Arlequin app
+///
+/// The shell sets breakpoints and sends requests, and the threads of the app check them in the debug handler of the module.
+/// A thread that stops waits for the requests from the shell until it is resumed.
+pub struct ArleDebugger {
+    is_attached: AtomicBool,
+    is_stepping: AtomicBool,
+    breakpoints: Mutex<Vec<ArleBreakpoint>>,
+    frame: Mutex<Option<ArleDebugFrame>>,
+    requests: Mutex<VecDeque<ArleDebugRequest>>,
+    request_signal: Semaphore,
+    response: Mutex<Option<Vec<u8>>>,
+    response_signal: Semaphore,
+    /// Only one thread can stop at a time
+    stop_lock: Mutex<()>,
+}
+
+impl ArleDebugger {
+    /// Maximum number of bytes of memory that can be read at once
+    pub const MAX_READ_MEMORY: usize = 0x1000;
+
+    pub(super) fn new() -> Arc<Self> {
+        Arc::new(Self {
+            is_attached: AtomicBool::new(false),
+            is_stepping: AtomicBool::new(false),
+            breakpoints: Mutex::new(Vec::new()),
+            frame: Mutex::new(None),
+            requests: Mutex::new(VecDeque::new()),
+            request_signal: Semaphore::new(0),
+            response: Mutex::new(None),
+            response_signal: Semaphore::new(0),
+            stop_lock: Mutex::new(()),
+        })
+    }
+
+    /// Makes the app attachable by the process id
+    pub(super) fn register(pid: ProcessId, debugger: &Arc<Self>) {
+        DEBUGGEES.lock().unwrap().push((pid, debugger.clone()));
+    }
+
+    pub(super) fn unregister(debugger: &Arc<Self>) {
+        DEBUGGEES
+            .lock()
+            .unwrap()
+            .retain(|(_, v)| !Arc::ptr_eq(v, debugger));
+        let mut session = SESSION.lock().unwrap();
+        if session.as_ref().map(|v| Arc::ptr_eq(v, debugger)) == Some(true) {
+            *session = None;
+        }
+    }
+
+    #[inline]
+    pub(super) fn is_attached(&self) -> bool {
+        self.is_attached.load(Ordering::Relaxed)
+    }
+
+    /// Attaches the app and stops it at the next instruction.
+    ///
+    /// The app installs the debug handler at its next system call or fuel check,
+    /// so an app that never calls the system without fuel metering cannot be stopped.
+    pub fn attach(pid: ProcessId) -> Option<Arc<Self>> {
+        let debugger = DEBUGGEES
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(v, _)| *v == pid)
+            .map(|(_, v)| v.clone())?;
+        Self::detach();
+        debugger.is_stepping.store(true, Ordering::SeqCst);
+        debugger.is_attached.store(true, Ordering::SeqCst);
+        *SESSION.lock().unwrap() = Some(debugger.clone());
+        Some(debugger)
+    }
+
+    /// Detaches the current app, and resumes it if it is stopped.
+    pub fn detach() {
+        let debugger = match SESSION.lock().unwrap().take() {
+            Some(v) => v,
+            None => return,
+        };
+        debugger.is_attached.store(false, Ordering::SeqCst);
+        debugger.is_stepping.store(false, Ordering::SeqCst);
+        debugger.breakpoints.lock().unwrap().clear();
+        debugger.send_request(ArleDebugRequest::Detach);
+    }
+
+    /// Returns the app currently attached
+    #[inline]
+    pub fn current() -> Option<Arc<Self>> {
+        SESSION.lock().unwrap().clone()
+    }
+
+    #[inline]
+    pub fn breakpoints(&self) -> Vec<ArleBreakpoint> {
+        self.breakpoints.lock().unwrap().clone()
+    }
+
+    pub fn set_breakpoint(&self, breakpoint: ArleBreakpoint) {
+        let mut breakpoints = self.breakpoints.lock().unwrap();
+        if !breakpoints.contains(&breakpoint) {
+            breakpoints.push(breakpoint);
+        }
+    }
+
+    pub fn remove_breakpoint(&self, index: usize) -> Option<ArleBreakpoint> {
+        let mut breakpoints = self.breakpoints.lock().unwrap();
+        if index < breakpoints.len() {
+            Some(breakpoints.remove(index))
+        } else {
+            None
+        }
+    }
+
+    /// Stops the app at the next instruction.
+    #[inline]
+    pub fn interrupt(&self) {
+        self.is_stepping.store(true, Ordering::SeqCst);
+    }
+
+    /// Returns the frame where the app is stopped, or `None` if it is running.
+    #[inline]
+    pub fn frame(&self) -> Option<ArleDebugFrame> {
+        self.frame.lock().unwrap().clone()
+    }
+
+    /// Resumes the stopped app, and stops it again at the next instruction if `step` is true.
+    pub fn resume(&self, step: bool) -> bool {
+        self.send_request(ArleDebugRequest::Resume { step })
+    }
+
+    /// Reads the memory of the stopped app.
+    pub fn read_memory(&self, base: usize, len: usize) -> Option<Vec<u8>> {
+        let len = usize::min(len, Self::MAX_READ_MEMORY);
+        if !self.send_request(ArleDebugRequest::ReadMemory { base, len }) {
+            return None;
+        }
+        self.response_signal.wait();
+        self.response.lock().unwrap().take()
+    }
+
+    /// Queues the request to the stopped thread, returns false if no thread is stopped.
+    ///
+    /// The stopped thread clears the frame under the same lock when it leaves,
+    /// so a request is never left behind for the next stop.
+    fn send_request(&self, request: ArleDebugRequest) -> bool {
+        let frame = self.frame.lock().unwrap();
+        if frame.is_none() {
+            return false;
+        }
+        self.requests.lock().unwrap().push_back(request);
+        self.request_signal.signal();
+        true
+    }
+
+    /// Discards the pending requests and their signals, the frame must be locked.
+    fn discard_requests(&self) {
+        let requests = core::mem::take(&mut *self.requests.lock().unwrap());
+        for request in requests {
+            if let ArleDebugRequest::ReadMemory { .. } = request {
+                // The shell is waiting for the response
+                self.respond(None);
+            }
+        }
+        while self.request_signal.try_lock() {}
+    }
+
+    fn respond(&self, data: Option<Vec<u8>>) {
+        *self.response.lock().unwrap() = data;
+        self.response_signal.signal();
+    }
+
+    /// Called by the debug handler before each instruction
+    pub(super) fn check(&self, frame: &WasmDebugFrame) -> Result<(), WasmRuntimeErrorType> {
+        if !self.is_attached() {
+            frame.module().set_debug_handler(None);
+            return Ok(());
+        }
+        if self.is_stepping.load(Ordering::Relaxed)
+            || self
+                .breakpoints
+                .lock()
+                .unwrap()
+                .iter()
+                .any(|v| v.matches(frame))
+        {
+            self.stop(frame);
+        }
+        Ok(())
+    }
+
+    /// Stops the current thread until the shell resumes it
+    fn stop(&self, frame: &WasmDebugFrame) {
+        let _lock = self.stop_lock.lock().unwrap();
+        if !self.is_attached() {
+            return;
+        }
+        self.is_stepping.store(false, Ordering::SeqCst);
+
+        let snapshot = ArleDebugFrame::new(frame);
+        println!("dbg: stopped at {}", snapshot);
+        {
+            let mut current = self.frame.lock().unwrap();
+            self.discard_requests();
+            *current = Some(snapshot);
+        }
+
+        loop {
+            self.request_signal.wait();
+            let mut current = self.frame.lock().unwrap();
+            let request = match self.requests.lock().unwrap().pop_front() {
+                Some(v) => v,
+                None => continue,
+            };
+            match request {
+                ArleDebugRequest::Resume { step } => {
+                    self.is_stepping.store(step, Ordering::SeqCst);
+                }
+                ArleDebugRequest::ReadMemory { base, len } => {
+                    drop(current);
+                    let data = frame
+                        .module()
+                        .memory(0)
+                        .and_then(|v| v.read_bytes(base, len).ok())
+                        .map(|v| v.to_vec());
+                    self.respond(data);
+                    continue;
+                }
+                ArleDebugRequest::Detach => {
+                    frame.module().set_debug_handler(None);
+                }
+            }
+            *current = None;
+            self.discard_requests();
+            break;
+        }
+    }
+}
+
+/// A breakpoint at the entry of the function, or at the position in its body
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArleBreakpoint {
+    pub function: usize,
+    pub position: Option<usize>,
+}
+
+impl ArleBreakpoint {
+    #[inline]
+    fn matches(&self, frame: &WasmDebugFrame) -> bool {
+        frame.function() == self.function
+            && match self.position {
+                Some(position) => frame.position() == position,
+                None => frame.is_entry(),
+            }
+    }
+}
+
+impl fmt::Display for ArleBreakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.position {
+            Some(position) => write!(f, "function {}+{:x}", self.function, position),
+            None => write!(f, "function {}", self.function),
+        }
+    }
+}
+
+/// The state of a stopped thread
+#[derive(Clone)]
+pub struct ArleDebugFrame {
+    pub thread: Option<ThreadHandle>,
+    pub function: usize,
+    pub name: Option<String>,
+    pub position: usize,
+    pub opcode: Option<WasmOpcode>,
+    pub locals: Vec<WasmValue>,
+    /// Since the values on the stack are untyped, they are shown as raw 64-bit values.
+    pub stack: Vec<u64>,
+}
+
+impl ArleDebugFrame {
+    fn new(frame: &WasmDebugFrame) -> Self {
+        Self {
+            thread: Scheduler::current_thread(),
+            function: frame.function(),
+            name: frame
+                .module()
+                .func_name(frame.function())
+                .map(|v| v.to_string()),
+            position: frame.position(),
+            opcode: frame.opcode(),
+            locals: (0..frame.local_types().len())
+                .filter_map(|index| frame.local(index))
+                .collect(),
+            stack: frame.stack().iter().map(|v| v.get_u64()).collect(),
+        }
+    }
+}
+
+impl fmt::Display for ArleDebugFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name.as_ref() {
+            Some(name) => write!(
+                f,
+                "{}+{:x} (function {})",
+                name, self.position, self.function
+            )?,
+            None => write!(f, "function {}+{:x}", self.function, self.position)?,
+        }
+        match self.opcode {
+            Some(opcode) => write!(f, " {}", opcode.to_str()),
+            None => Ok(()),
+        }
+    }
+}
+
+enum ArleDebugRequest {
+    Resume { step: bool },
+    ReadMemory { base: usize, len: usize },
+    Detach,
+}
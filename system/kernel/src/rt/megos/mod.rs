@@ -2,6 +2,8 @@
 
 mod arle;
 pub use arle::*;
+mod debug;
+pub use debug::*;
 
 use super::*;
 use alloc::boxed::Box;
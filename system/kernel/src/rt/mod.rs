@@ -2,12 +2,14 @@
 
 pub mod haribote;
 pub mod megos;
+pub mod wasi;
 
 use core::cell::UnsafeCell;
 
 use crate::arch::cpu::*;
 use crate::task::scheduler::*;
 use alloc::{boxed::Box, string::String, string::*, vec::Vec};
+use wasm::{WasmRuntimeErrorType, WasmValue};
 
 static mut RE: UnsafeCell<RuntimeEnvironment> = UnsafeCell::new(RuntimeEnvironment::new());
 
@@ -28,13 +30,16 @@ impl RuntimeEnvironment {
     #[inline]
     pub unsafe fn init() {
         let shared = &mut *RE.get();
+        shared.add_image("wasm", wasi::WasiRecognizer::new());
         shared.add_image("wasm", megos::WasmRecognizer::new());
         shared.add_image("hrb", haribote::HrbRecognizer::new());
     }
 
     #[inline]
     fn add_image(&mut self, ext: &str, loader: Box<dyn BinaryRecognizer>) {
-        self.exts.push(ext.to_string());
+        if !self.exts.iter().any(|v| v == ext) {
+            self.exts.push(ext.to_string());
+        }
         self.image_loaders.push(loader);
     }
 
@@ -87,6 +92,8 @@ pub enum PersonalityContext<'a> {
     Arlequin(&'a mut megos::ArleRuntime),
     /// Haribote OS Emulation subsystem
     Hoe(&'a mut haribote::Hoe),
+    /// WebAssembly System Interface subsystem
+    Wasi(&'a mut wasi::WasiRuntime),
 }
 
 pub trait BinaryRecognizer {
@@ -106,6 +113,8 @@ pub trait BinaryLoader {
 pub struct LoadedImageOption {
     pub name: String,
     pub argv: Vec<String>,
+    /// Maximum size of the memory of the app in bytes, or `None` for no limit
+    pub memory_quota: Option<usize>,
 }
 
 /// Contextual data for legacy applications
@@ -128,3 +137,51 @@ pub struct LegacyAppContext {
     /// Initial stack pointer
     pub stack_pointer: u32,
 }
+
+/// Takes the parameters of a wasm host function in order
+pub struct ParamsDecoder<'a> {
+    params: &'a [WasmValue],
+    index: usize,
+}
+
+impl<'a> ParamsDecoder<'a> {
+    #[inline]
+    pub const fn new(params: &'a [WasmValue]) -> Self {
+        Self { params, index: 0 }
+    }
+}
+
+impl ParamsDecoder<'_> {
+    #[inline]
+    pub fn next(&mut self) -> Result<WasmValue, WasmRuntimeErrorType> {
+        let index = self.index;
+        self.params
+            .get(index)
+            .copied()
+            .ok_or(WasmRuntimeErrorType::InvalidParameter)
+            .map(|v| {
+                self.index += 1;
+                v
+            })
+    }
+
+    #[inline]
+    pub fn get_u32(&mut self) -> Result<u32, WasmRuntimeErrorType> {
+        self.next().and_then(|v| v.get_u32())
+    }
+
+    #[inline]
+    pub fn get_i32(&mut self) -> Result<i32, WasmRuntimeErrorType> {
+        self.next().and_then(|v| v.get_i32())
+    }
+
+    #[inline]
+    pub fn get_u64(&mut self) -> Result<u64, WasmRuntimeErrorType> {
+        self.next().and_then(|v| v.get_u64())
+    }
+
+    #[inline]
+    pub fn get_usize(&mut self) -> Result<usize, WasmRuntimeErrorType> {
+        self.get_u32().map(|v| v as usize)
+    }
+}
@@ -0,0 +1,29 @@
+//! WebAssembly System Interface subsystem
+
+mod wasi;
+pub use wasi::*;
+
+use super::*;
+use alloc::boxed::Box;
+use wasm::*;
+
+/// Recognize .wasm file that imports WASI
+pub struct WasiRecognizer {
+    _phantom: (),
+}
+
+impl WasiRecognizer {
+    pub fn new() -> Box<Self> {
+        Box::new(Self { _phantom: () })
+    }
+}
+
+impl BinaryRecognizer for WasiRecognizer {
+    fn recognize(&self, blob: &[u8]) -> Option<Box<dyn BinaryLoader>> {
+        if WasmLoader::imports_module(blob, WasiRuntime::MOD_NAME) {
+            Some(Box::new(WasiBinaryLoader::new()) as Box<dyn BinaryLoader>)
+        } else {
+            None
+        }
+    }
+}
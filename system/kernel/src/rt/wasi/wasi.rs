@@ -0,0 +1,697 @@
+// WASI preview1
+
+use super::*;
+use crate::{arch::cpu::Cpu, fs::*, io::tty::*, system::System, *};
+use alloc::{string::String, vec, vec::Vec};
+use core::{
+    fmt::Write,
+    future::Future,
+    ptr::null,
+    task::{Context, Poll, RawWaker, RawWakerVTable, Waker},
+    time::Duration,
+};
+use megstd::{
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+    rand::*,
+};
+use wasm::{wasmintr::*, *};
+
+pub struct WasiBinaryLoader {
+    loader: WasmLoader,
+    lio: LoadedImageOption,
+}
+
+impl WasiBinaryLoader {
+    pub fn new() -> Self {
+        Self {
+            loader: WasmLoader::new(),
+            lio: LoadedImageOption::default(),
+        }
+    }
+
+    fn start(_: usize) {
+        Scheduler::current_personality(|personality| match personality.context() {
+            PersonalityContext::Wasi(rt) => rt.start(),
+            _ => unreachable!(),
+        });
+    }
+}
+
+impl BinaryLoader for WasiBinaryLoader {
+    fn option(&mut self) -> &mut LoadedImageOption {
+        &mut self.lio
+    }
+
+    fn load(&mut self, blob: &[u8]) -> Result<(), ()> {
+        self.loader
+            .load(blob, |mod_name, name, _type_ref| match mod_name {
+                WasiRuntime::MOD_NAME => Ok(WasiRuntime::resolve(name)),
+                _ => Err(WasmDecodeErrorType::NoModule),
+            })
+            .map_err(|v| {
+                println!("Load error: {:?}", v);
+                ()
+            })
+    }
+
+    fn invoke_start(self: Box<Self>) -> Option<ProcessId> {
+        match self.loader.module().func(WasiRuntime::ENTRY_FUNC_NAME) {
+            Ok(_) => {
+                let module = self.loader.into_module();
+                SpawnOption::new()
+                    .personality(WasiRuntime::new(module, self.lio.argv))
+                    .start_process(Self::start, 0, self.lio.name.as_ref())
+            }
+            Err(err) => {
+                println!("error: {:?}", err);
+                None
+            }
+        }
+    }
+}
+
+/// Contextual structure of the WASI subsystem
+pub struct WasiRuntime {
+    module: WasmModule,
+    args: Vec<String>,
+    files: Vec<Option<WasiFile>>,
+    rng: XorShift64,
+    exit_code: u32,
+}
+
+/// Objects referenced by file descriptors
+enum WasiFile {
+    Stdin,
+    Stdout,
+    Stderr,
+    PreopenDir(&'static str),
+    File(FsRawFileControlBlock),
+}
+
+/// Error numbers of WASI
+#[repr(u16)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Errno {
+    Success = 0,
+    Acces = 2,
+    Badf = 8,
+    Exist = 20,
+    Fault = 21,
+    Inval = 28,
+    Io = 29,
+    Isdir = 31,
+    Noent = 44,
+    Nospc = 51,
+    Nosys = 52,
+    Notdir = 54,
+    Notempty = 55,
+    Notsup = 58,
+    Rofs = 69,
+}
+
+impl From<WasmRuntimeErrorType> for Errno {
+    #[inline]
+    fn from(err: WasmRuntimeErrorType) -> Self {
+        match err {
+            WasmRuntimeErrorType::InvalidParameter => Self::Inval,
+            _ => Self::Fault,
+        }
+    }
+}
+
+impl From<io::Error> for Errno {
+    fn from(err: io::Error) -> Self {
+        match err.kind() {
+            ErrorKind::NotFound => Self::Noent,
+            ErrorKind::PermissionDenied => Self::Acces,
+            ErrorKind::AlreadyExists => Self::Exist,
+            ErrorKind::InvalidInput => Self::Inval,
+            ErrorKind::NotADirectory => Self::Notdir,
+            ErrorKind::IsADirectory => Self::Isdir,
+            ErrorKind::DirectoryNotEmpty => Self::Notempty,
+            ErrorKind::ReadOnlyFilesystem => Self::Rofs,
+            ErrorKind::StorageFull => Self::Nospc,
+            ErrorKind::Unsupported => Self::Notsup,
+            _ => Self::Io,
+        }
+    }
+}
+
+impl WasiRuntime {
+    pub const MOD_NAME: &'static str = "wasi_snapshot_preview1";
+    const ENTRY_FUNC_NAME: &'static str = "_start";
+
+    const PREOPEN_ROOT: &'static str = "/";
+    const FD_FIRST_PREOPEN: usize = 3;
+
+    const FILETYPE_CHARACTER_DEVICE: u8 = 2;
+    const FILETYPE_DIRECTORY: u8 = 3;
+    const FILETYPE_REGULAR_FILE: u8 = 4;
+
+    const CLOCK_REALTIME: u32 = 0;
+
+    const OFLAGS_CREAT: u32 = 0x0001;
+    const OFLAGS_DIRECTORY: u32 = 0x0002;
+    const OFLAGS_EXCL: u32 = 0x0004;
+    const OFLAGS_TRUNC: u32 = 0x0008;
+
+    const RIGHTS_ALL: u64 = 0x1FFF_FFFF;
+
+    fn new(module: WasmModule, args: Vec<String>) -> Box<Self> {
+        Box::new(Self {
+            module,
+            args,
+            files: vec![
+                Some(WasiFile::Stdin),
+                Some(WasiFile::Stdout),
+                Some(WasiFile::Stderr),
+                Some(WasiFile::PreopenDir(Self::PREOPEN_ROOT)),
+            ],
+            rng: XorShift64::default(),
+            exit_code: 0,
+        })
+    }
+
+    fn start(&self) -> ! {
+        let function = match self.module.func(Self::ENTRY_FUNC_NAME) {
+            Ok(v) => v,
+            Err(err) => {
+                println!("error: {:?}", err);
+                RuntimeEnvironment::exit(1);
+            }
+        };
+
+        match function.invoke(&[]) {
+            Ok(_v) => (),
+            Err(err) => match err.kind() {
+                WasmRuntimeErrorType::Exit => (),
+                _ => {
+                    println!("error: {:?}", err);
+                    RuntimeEnvironment::exit(1);
+                }
+            },
+        }
+
+        RuntimeEnvironment::exit(self.exit_code as usize);
+    }
+
+    /// Returns the function that implements the specified import.
+    ///
+    /// Unimplemented functions are resolved to a stub that returns `ENOSYS`,
+    /// so that the programs that only reference them can be loaded.
+    fn resolve(name: &str) -> WasmDynFunc {
+        match name {
+            "args_get" => |m, p| Self::dispatch(m, p, Self::args_get),
+            "args_sizes_get" => |m, p| Self::dispatch(m, p, Self::args_sizes_get),
+            "environ_get" => |m, p| Self::dispatch(m, p, Self::environ_get),
+            "environ_sizes_get" => |m, p| Self::dispatch(m, p, Self::environ_sizes_get),
+            "clock_time_get" => |m, p| Self::dispatch(m, p, Self::clock_time_get),
+            "random_get" => |m, p| Self::dispatch(m, p, Self::random_get),
+            "fd_write" => |m, p| Self::dispatch(m, p, Self::fd_write),
+            "fd_read" => |m, p| Self::dispatch(m, p, Self::fd_read),
+            "fd_seek" => |m, p| Self::dispatch(m, p, Self::fd_seek),
+            "fd_close" => |m, p| Self::dispatch(m, p, Self::fd_close),
+            "fd_fdstat_get" => |m, p| Self::dispatch(m, p, Self::fd_fdstat_get),
+            "fd_prestat_get" => |m, p| Self::dispatch(m, p, Self::fd_prestat_get),
+            "fd_prestat_dir_name" => |m, p| Self::dispatch(m, p, Self::fd_prestat_dir_name),
+            "path_open" => |m, p| Self::dispatch(m, p, Self::path_open),
+            "path_create_directory" => |m, p| Self::dispatch(m, p, Self::path_create_directory),
+            "path_remove_directory" => |m, p| Self::dispatch(m, p, Self::path_remove_directory),
+            "path_unlink_file" => |m, p| Self::dispatch(m, p, Self::path_unlink_file),
+            "path_rename" => |m, p| Self::dispatch(m, p, Self::path_rename),
+            "proc_exit" => Self::proc_exit,
+            _ => |_, _| Ok(WasmValue::I32(Errno::Nosys as i32)),
+        }
+    }
+
+    fn dispatch(
+        module: &WasmModule,
+        params: &[WasmValue],
+        f: fn(&mut Self, &WasmMemory, &mut ParamsDecoder) -> Result<(), Errno>,
+    ) -> Result<WasmValue, WasmRuntimeErrorType> {
+        let memory = module.memory(0).ok_or(WasmRuntimeErrorType::OutOfMemory)?;
+        let mut params = ParamsDecoder::new(params);
+        Scheduler::current_personality(|personality| match personality.context() {
+            PersonalityContext::Wasi(rt) => {
+                let errno = match f(rt, memory, &mut params) {
+                    Ok(_) => Errno::Success,
+                    Err(errno) => errno,
+                };
+                Ok(WasmValue::I32(errno as i32))
+            }
+            _ => unreachable!(),
+        })
+        .unwrap()
+    }
+
+    fn proc_exit(_: &WasmModule, params: &[WasmValue]) -> Result<WasmValue, WasmRuntimeErrorType> {
+        let exit_code = ParamsDecoder::new(params).get_u32()?;
+        Scheduler::current_personality(|personality| match personality.context() {
+            PersonalityContext::Wasi(rt) => rt.exit_code = exit_code,
+            _ => unreachable!(),
+        });
+        Err(WasmRuntimeErrorType::Exit)
+    }
+
+    fn args_get(&mut self, memory: &WasmMemory, params: &mut ParamsDecoder) -> Result<(), Errno> {
+        let argv = params.get_usize()?;
+        let argv_buf = params.get_usize()?;
+        Self::write_strings(memory, self.args.as_slice(), argv, argv_buf)
+    }
+
+    fn args_sizes_get(
+        &mut self,
+        memory: &WasmMemory,
+        params: &mut ParamsDecoder,
+    ) -> Result<(), Errno> {
+        let argc = params.get_usize()?;
+        let argv_buf_size = params.get_usize()?;
+        memory.write_u32(argc, self.args.len() as u32)?;
+        memory.write_u32(argv_buf_size, Self::strings_size(self.args.as_slice()))?;
+        Ok(())
+    }
+
+    fn environ_get(
+        &mut self,
+        memory: &WasmMemory,
+        params: &mut ParamsDecoder,
+    ) -> Result<(), Errno> {
+        let environ = params.get_usize()?;
+        let environ_buf = params.get_usize()?;
+        Self::write_strings(memory, &[], environ, environ_buf)
+    }
+
+    fn environ_sizes_get(
+        &mut self,
+        memory: &WasmMemory,
+        params: &mut ParamsDecoder,
+    ) -> Result<(), Errno> {
+        let environc = params.get_usize()?;
+        let environ_buf_size = params.get_usize()?;
+        memory.write_u32(environc, 0)?;
+        memory.write_u32(environ_buf_size, 0)?;
+        Ok(())
+    }
+
+    fn clock_time_get(
+        &mut self,
+        memory: &WasmMemory,
+        params: &mut ParamsDecoder,
+    ) -> Result<(), Errno> {
+        let clock_id = params.get_u32()?;
+        let _precision = params.get_u64()?;
+        let time = params.get_usize()?;
+        let nanos = match clock_id {
+            Self::CLOCK_REALTIME => {
+                let time = System::system_time();
+                time.secs * 1_000_000_000 + time.nanos as u64
+            }
+            // Both the monotonic clock and the cputime clocks are based on the monotonic timer
+            1..=3 => Timer::monotonic().as_nanos() as u64,
+            _ => return Err(Errno::Inval),
+        };
+        memory.write_u64(time, nanos)?;
+        Ok(())
+    }
+
+    fn random_get(&mut self, memory: &WasmMemory, params: &mut ParamsDecoder) -> Result<(), Errno> {
+        let buf = params.get_usize()?;
+        let buf_len = params.get_usize()?;
+        let mut bytes = Vec::with_capacity(buf_len);
+        while bytes.len() < buf_len {
+            let rand = Cpu::secure_rand().unwrap_or_else(|_| self.rng.next());
+            for byte in rand.to_le_bytes().iter().take(buf_len - bytes.len()) {
+                bytes.push(*byte);
+            }
+        }
+        memory.write_slice(buf, bytes.as_slice())?;
+        Ok(())
+    }
+
+    fn fd_write(&mut self, memory: &WasmMemory, params: &mut ParamsDecoder) -> Result<(), Errno> {
+        let fd = params.get_usize()?;
+        let iovs = params.get_usize()?;
+        let iovs_len = params.get_usize()?;
+        let nwritten = params.get_usize()?;
+
+        let mut total = 0;
+        match self.file(fd)? {
+            WasiFile::Stdout | WasiFile::Stderr => {
+                let stdout = System::stdout();
+                for index in 0..iovs_len {
+                    let (base, len) = Self::iovec(memory, iovs, index)?;
+                    let bytes = memory.read_bytes(base, len)?;
+                    let _ = stdout.write_str(&String::from_utf8_lossy(bytes));
+                    total += len;
+                }
+            }
+            WasiFile::File(fcb) => {
+                for index in 0..iovs_len {
+                    let (base, len) = Self::iovec(memory, iovs, index)?;
+                    let bytes = memory.read_bytes(base, len)?;
+                    total += fcb.write(bytes)?;
+                }
+            }
+            WasiFile::PreopenDir(_) => return Err(Errno::Isdir),
+            WasiFile::Stdin => return Err(Errno::Badf),
+        }
+        memory.write_u32(nwritten, total as u32)?;
+        Ok(())
+    }
+
+    fn fd_read(&mut self, memory: &WasmMemory, params: &mut ParamsDecoder) -> Result<(), Errno> {
+        let fd = params.get_usize()?;
+        let iovs = params.get_usize()?;
+        let iovs_len = params.get_usize()?;
+        let nread = params.get_usize()?;
+
+        let mut total = 0;
+        match self.file(fd)? {
+            WasiFile::Stdin => {
+                // Reads one line, like the canonical mode of a terminal
+                let mut bytes = Vec::new();
+                while let Some(c) = Self::read_char() {
+                    let mut buf = [0; 4];
+                    bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                    if c == '\n' {
+                        break;
+                    }
+                }
+                let mut bytes = bytes.as_slice();
+                for index in 0..iovs_len {
+                    let (base, len) = Self::iovec(memory, iovs, index)?;
+                    let len = usize::min(len, bytes.len());
+                    memory.write_slice(base, &bytes[..len])?;
+                    bytes = &bytes[len..];
+                    total += len;
+                }
+            }
+            WasiFile::File(fcb) => {
+                for index in 0..iovs_len {
+                    let (base, len) = Self::iovec(memory, iovs, index)?;
+                    let mut buf = Vec::with_capacity(len);
+                    buf.resize(len, 0);
+                    let size = fcb.read(buf.as_mut_slice()).map_err(|_| Errno::Io)?;
+                    memory.write_slice(base, &buf[..size])?;
+                    total += size;
+                    if size < len {
+                        break;
+                    }
+                }
+            }
+            WasiFile::PreopenDir(_) => return Err(Errno::Isdir),
+            WasiFile::Stdout | WasiFile::Stderr => return Err(Errno::Badf),
+        }
+        memory.write_u32(nread, total as u32)?;
+        Ok(())
+    }
+
+    fn fd_seek(&mut self, memory: &WasmMemory, params: &mut ParamsDecoder) -> Result<(), Errno> {
+        let fd = params.get_usize()?;
+        let offset = params.get_u64()? as OffsetType;
+        let whence = params.get_usize()?;
+        let newoffset = params.get_usize()?;
+
+        match self.file(fd)? {
+            WasiFile::File(fcb) => {
+                let position = fcb.lseek(offset, Whence::from(whence));
+                memory.write_u64(newoffset, position as u64)?;
+                Ok(())
+            }
+            _ => Err(Errno::Badf),
+        }
+    }
+
+    fn fd_close(&mut self, _: &WasmMemory, params: &mut ParamsDecoder) -> Result<(), Errno> {
+        let fd = params.get_usize()?;
+        match self.files.get_mut(fd).and_then(|v| v.take()) {
+            Some(_) => Ok(()),
+            None => Err(Errno::Badf),
+        }
+    }
+
+    fn fd_fdstat_get(
+        &mut self,
+        memory: &WasmMemory,
+        params: &mut ParamsDecoder,
+    ) -> Result<(), Errno> {
+        let fd = params.get_usize()?;
+        let buf = params.get_usize()?;
+
+        let filetype = match self.file(fd)? {
+            WasiFile::Stdin | WasiFile::Stdout | WasiFile::Stderr => {
+                Self::FILETYPE_CHARACTER_DEVICE
+            }
+            WasiFile::PreopenDir(_) => Self::FILETYPE_DIRECTORY,
+            WasiFile::File(_) => Self::FILETYPE_REGULAR_FILE,
+        };
+        memory.write_u8(buf, filetype)?;
+        memory.write_u8(buf + 1, 0)?;
+        memory.write_u16(buf + 2, 0)?;
+        memory.write_u32(buf + 4, 0)?;
+        memory.write_u64(buf + 8, Self::RIGHTS_ALL)?;
+        memory.write_u64(buf + 16, Self::RIGHTS_ALL)?;
+        Ok(())
+    }
+
+    fn fd_prestat_get(
+        &mut self,
+        memory: &WasmMemory,
+        params: &mut ParamsDecoder,
+    ) -> Result<(), Errno> {
+        let fd = params.get_usize()?;
+        let buf = params.get_usize()?;
+
+        match self.file(fd)? {
+            WasiFile::PreopenDir(name) => {
+                // tag 0: directory
+                memory.write_u32(buf, 0)?;
+                memory.write_u32(buf + 4, name.len() as u32)?;
+                Ok(())
+            }
+            _ => Err(Errno::Badf),
+        }
+    }
+
+    fn fd_prestat_dir_name(
+        &mut self,
+        memory: &WasmMemory,
+        params: &mut ParamsDecoder,
+    ) -> Result<(), Errno> {
+        let fd = params.get_usize()?;
+        let path = params.get_usize()?;
+        let path_len = params.get_usize()?;
+
+        match self.file(fd)? {
+            WasiFile::PreopenDir(name) => {
+                let len = usize::min(path_len, name.len());
+                memory.write_slice(path, &name.as_bytes()[..len])?;
+                Ok(())
+            }
+            _ => Err(Errno::Badf),
+        }
+    }
+
+    fn path_open(&mut self, memory: &WasmMemory, params: &mut ParamsDecoder) -> Result<(), Errno> {
+        let dirfd = params.get_usize()?;
+        let _dirflags = params.get_u32()?;
+        let path = params.get_usize()?;
+        let path_len = params.get_usize()?;
+        let oflags = params.get_u32()?;
+        let _fs_rights_base = params.get_u64()?;
+        let _fs_rights_inheriting = params.get_u64()?;
+        let _fdflags = params.get_u32()?;
+        let fd = params.get_usize()?;
+
+        let path = self.path(memory, dirfd, path, path_len)?;
+        if (oflags & Self::OFLAGS_DIRECTORY) != 0 {
+            return Err(Errno::Notsup);
+        }
+
+        let create = (oflags & Self::OFLAGS_CREAT) != 0;
+        let fcb = match FileManager::open(&path) {
+            Ok(_) if create && (oflags & Self::OFLAGS_EXCL) != 0 => return Err(Errno::Exist),
+            Ok(_) if (oflags & Self::OFLAGS_TRUNC) != 0 => FileManager::create(&path)?,
+            Ok(v) => v,
+            Err(err) if create && err.kind() == ErrorKind::NotFound => FileManager::create(&path)?,
+            Err(err) => return Err(err.into()),
+        };
+
+        let new_fd = self.alloc_fd(WasiFile::File(fcb));
+        memory.write_u32(fd, new_fd as u32)?;
+        Ok(())
+    }
+
+    fn path_create_directory(
+        &mut self,
+        memory: &WasmMemory,
+        params: &mut ParamsDecoder,
+    ) -> Result<(), Errno> {
+        let dirfd = params.get_usize()?;
+        let path = params.get_usize()?;
+        let path_len = params.get_usize()?;
+
+        let path = self.path(memory, dirfd, path, path_len)?;
+        FileManager::create_dir(path).map_err(|v| v.into())
+    }
+
+    fn path_remove_directory(
+        &mut self,
+        memory: &WasmMemory,
+        params: &mut ParamsDecoder,
+    ) -> Result<(), Errno> {
+        let dirfd = params.get_usize()?;
+        let path = params.get_usize()?;
+        let path_len = params.get_usize()?;
+
+        let path = self.path(memory, dirfd, path, path_len)?;
+        FileManager::remove_dir(path).map_err(|v| v.into())
+    }
+
+    fn path_unlink_file(
+        &mut self,
+        memory: &WasmMemory,
+        params: &mut ParamsDecoder,
+    ) -> Result<(), Errno> {
+        let dirfd = params.get_usize()?;
+        let path = params.get_usize()?;
+        let path_len = params.get_usize()?;
+
+        let path = self.path(memory, dirfd, path, path_len)?;
+        FileManager::remove_file(path).map_err(|v| v.into())
+    }
+
+    fn path_rename(
+        &mut self,
+        memory: &WasmMemory,
+        params: &mut ParamsDecoder,
+    ) -> Result<(), Errno> {
+        let old_dirfd = params.get_usize()?;
+        let old_path = params.get_usize()?;
+        let old_path_len = params.get_usize()?;
+        let new_dirfd = params.get_usize()?;
+        let new_path = params.get_usize()?;
+        let new_path_len = params.get_usize()?;
+
+        let old_path = self.path(memory, old_dirfd, old_path, old_path_len)?;
+        let new_path = self.path(memory, new_dirfd, new_path, new_path_len)?;
+        FileManager::rename(old_path, new_path).map_err(|v| v.into())
+    }
+
+    /// Returns the path relative to the preopened directory
+    fn path(
+        &mut self,
+        memory: &WasmMemory,
+        dirfd: usize,
+        path: usize,
+        path_len: usize,
+    ) -> Result<PathBuf, Errno> {
+        let dir = match self.file(dirfd)? {
+            WasiFile::PreopenDir(name) => *name,
+            _ => return Err(Errno::Notdir),
+        };
+        let path = memory.read_bytes(path, path_len)?;
+        let path = core::str::from_utf8(path).map_err(|_| Errno::Inval)?;
+        Ok(Path::new(dir).join(path))
+    }
+
+    #[inline]
+    fn file(&mut self, fd: usize) -> Result<&mut WasiFile, Errno> {
+        self.files
+            .get_mut(fd)
+            .and_then(|v| v.as_mut())
+            .ok_or(Errno::Badf)
+    }
+
+    fn alloc_fd(&mut self, file: WasiFile) -> usize {
+        for (fd, slot) in self
+            .files
+            .iter_mut()
+            .enumerate()
+            .skip(Self::FD_FIRST_PREOPEN)
+        {
+            if slot.is_none() {
+                *slot = Some(file);
+                return fd;
+            }
+        }
+        self.files.push(Some(file));
+        self.files.len() - 1
+    }
+
+    #[inline]
+    fn iovec(memory: &WasmMemory, iovs: usize, index: usize) -> Result<(usize, usize), Errno> {
+        let iov = iovs + index * 8;
+        let base = memory.read_u32(iov)? as usize;
+        let len = memory.read_u32(iov + 4)? as usize;
+        Ok((base, len))
+    }
+
+    fn strings_size(strings: &[String]) -> u32 {
+        strings.iter().fold(0, |acc, v| acc + v.len() as u32 + 1)
+    }
+
+    fn write_strings(
+        memory: &WasmMemory,
+        strings: &[String],
+        ptrs: usize,
+        buf: usize,
+    ) -> Result<(), Errno> {
+        let mut offset = buf;
+        for (index, string) in strings.iter().enumerate() {
+            memory.write_u32(ptrs + index * 4, offset as u32)?;
+            memory.write_slice(offset, string.as_bytes())?;
+            memory.write_u8(offset + string.len(), 0)?;
+            offset += string.len() + 1;
+        }
+        Ok(())
+    }
+
+    /// Waits for a character from the console
+    fn read_char() -> Option<char> {
+        let waker = unsafe { Waker::from_raw(Self::null_waker()) };
+        let mut context = Context::from_waker(&waker);
+        let stdout = System::stdout();
+        let result = {
+            let mut future = stdout.read_async();
+            loop {
+                match future.as_mut().poll(&mut context) {
+                    Poll::Ready(v) => break v,
+                    Poll::Pending => Timer::sleep(Duration::from_millis(10)),
+                }
+            }
+        };
+        match result {
+            // ^C or ^D
+            Ok('\x03') | Ok('\x04') | Err(_) => None,
+            Ok(c) => {
+                let c = if c == '\r' { '\n' } else { c };
+                match c {
+                    '\n' => stdout.write_str("\r\n").unwrap(),
+                    _ => stdout.write_char(c).unwrap(),
+                }
+                Some(c)
+            }
+        }
+    }
+
+    fn null_waker() -> RawWaker {
+        fn clone(_: *const ()) -> RawWaker {
+            WasiRuntime::null_waker()
+        }
+        fn noop(_: *const ()) {}
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+        RawWaker::new(null(), &VTABLE)
+    }
+}
+
+impl Personality for WasiRuntime {
+    fn context(&mut self) -> PersonalityContext {
+        PersonalityContext::Wasi(self)
+    }
+
+    fn on_exit(&mut self) {
+        self.files.clear();
+    }
+}
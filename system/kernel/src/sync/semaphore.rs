@@ -2,7 +2,7 @@
 
 use super::signal::SignallingObject;
 use crate::arch::cpu::Cpu;
-use core::sync::atomic::*;
+use core::{sync::atomic::*, time::Duration};
 
 /// counting semaphore
 pub struct Semaphore {
@@ -40,6 +40,12 @@ impl Semaphore {
         self.signal.wait_for(|| self.try_lock());
     }
 
+    /// Waits for the signal until the timeout expires, returns false if it timed out.
+    #[inline]
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        self.signal.wait_for_timeout(|| self.try_lock(), timeout)
+    }
+
     #[inline]
     pub fn signal(&self) {
         let _ = Cpu::interlocked_increment(&self.value);
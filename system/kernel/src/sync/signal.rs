@@ -85,6 +85,32 @@ impl SignallingObject {
         }
     }
 
+    /// Same as `wait_for`, but gives up when the timeout expires and returns false.
+    pub fn wait_for_timeout<F>(&self, mut f: F, timeout: Duration) -> bool
+    where
+        F: FnMut() -> bool,
+    {
+        let current = Scheduler::current_thread().unwrap();
+        let deadline = Timer::monotonic() + timeout;
+        loop {
+            if f() {
+                return true;
+            }
+            let now = Timer::monotonic();
+            if now >= deadline {
+                return false;
+            }
+            match self.compare_and_swap(None, Some(current)) {
+                Ok(_) => {
+                    // The signal wakes the thread before the timer does
+                    Timer::sleep(deadline - now);
+                    let _ = self.compare_and_swap(Some(current), None);
+                }
+                Err(_) => Timer::sleep(Duration::min(deadline - now, Duration::from_millis(1))),
+            }
+        }
+    }
+
     #[inline]
     fn sleep(&self) -> Result<Option<ThreadHandle>, Option<ThreadHandle>> {
         let current = Scheduler::current_thread().unwrap();
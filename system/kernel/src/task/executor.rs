@@ -2,7 +2,8 @@
 
 use super::{Task, TaskId};
 use crate::{sync::fifo::*, sync::semaphore::*};
-use alloc::{collections::BTreeMap, sync::Arc, task::Wake};
+use alloc::{boxed::Box, collections::BTreeMap, sync::Arc, task::Wake};
+use core::future::Future;
 use core::task::{Context, Poll, Waker};
 
 pub struct Executor {
@@ -60,6 +61,33 @@ impl Executor {
             self.task_queue.wait();
         }
     }
+
+    /// Runs the future to completion, blocking the current thread while it is pending.
+    pub fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = Box::pin(future);
+        let signal = Arc::new(ThreadWaker(Semaphore::new(0)));
+        let waker = Waker::from(signal.clone());
+        let mut context = Context::from_waker(&waker);
+        loop {
+            match future.as_mut().poll(&mut context) {
+                Poll::Ready(result) => return result,
+                Poll::Pending => signal.0.wait(),
+            }
+        }
+    }
+}
+
+/// Wakes up the thread that is waiting in `Executor::block_on`
+struct ThreadWaker(Semaphore);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.signal();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.0.signal();
+    }
 }
 
 struct TaskWaker {
@@ -17,6 +17,7 @@ use bitflags::*;
 use core::{
     cell::UnsafeCell, ffi::c_void, fmt::Write, num::*, ops::*, sync::atomic::*, time::Duration,
 };
+use megstd::path::PathBuf;
 use megstd::string::*;
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
@@ -1247,6 +1248,20 @@ impl ProcessId {
     pub fn name<'a>(&self) -> Option<&'a str> {
         self.get().and_then(|v| v.name())
     }
+
+    /// Returns the current directory of the process
+    #[inline]
+    pub fn current_dir(&self) -> Option<PathBuf> {
+        self.get().map(|v| v.cwd.read().unwrap().clone())
+    }
+
+    /// Sets the current directory of the process, which must be an absolute path
+    #[inline]
+    pub fn set_current_dir(&self, path: PathBuf) {
+        if let Some(process) = self.get() {
+            *process.cwd.write().unwrap() = path;
+        }
+    }
 }
 
 #[allow(dead_code)]
@@ -1263,6 +1278,7 @@ struct ProcessContextData {
     load: AtomicU32,
 
     name: [u8; CONTEXT_LABEL_LENGTH],
+    cwd: RwLock<PathBuf>,
 }
 
 const CONTEXT_LABEL_LENGTH: usize = 32;
@@ -1282,6 +1298,7 @@ fn set_name_array(array: &mut [u8; CONTEXT_LABEL_LENGTH], name: &str) {
 impl ProcessContextData {
     fn new(parent: ProcessId, priority: Priority, name: &str) -> Box<ProcessContextData> {
         let pid = Self::next_pid();
+        let cwd = parent.current_dir().unwrap_or_else(|| PathBuf::from("/"));
         let mut child = Self {
             parent,
             pid,
@@ -1293,6 +1310,7 @@ impl ProcessContextData {
             load0: AtomicU32::new(0),
             load: AtomicU32::new(0),
             name: [0u8; CONTEXT_LABEL_LENGTH],
+            cwd: RwLock::new(cwd),
         };
 
         child.set_name(name);
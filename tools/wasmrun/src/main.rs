@@ -0,0 +1,776 @@
+// Run MEG-OS Arlequin applications on the host
+// Copyright(c) 2021 The MEG-OS Project
+
+use byteorder::*;
+use megosabi::svc::Function;
+use megstd::drawing::*;
+use megstd::rand::*;
+use num_traits::FromPrimitive;
+use std::{
+    alloc::Layout,
+    cell::RefCell,
+    collections::BTreeMap,
+    env,
+    fs::File,
+    io::{Read, Write},
+    num::NonZeroU32,
+    path::Path,
+    process,
+};
+use wasm::{wasmintr::*, *};
+
+fn usage() -> ! {
+    let mut args = env::args_os();
+    let arg = args.next().unwrap();
+    let path = Path::new(&arg);
+    let lpc = path.file_name().unwrap();
+    eprintln!("{} [OPTIONS] FILE", lpc.to_str().unwrap());
+    eprintln!("  --frames N     exit after N frames have been drawn");
+    eprintln!("  --fuel N       exit with status 2 after N units of fuel have been consumed;");
+    eprintln!("                 one unit is charged for each backward branch and each call");
+    eprintln!("  --bmp PREFIX   dump each window to PREFIX-<handle>.bmp");
+    process::exit(1);
+}
+
+fn main() {
+    let mut args = env::args();
+    let _ = args.next().unwrap();
+
+    let mut path_input = None;
+    let mut option = RunOption::default();
+
+    while let Some(arg) = args.next() {
+        let arg = arg.as_str();
+        if arg.chars().next().unwrap_or_default() == '-' {
+            match arg {
+                "--frames" => {
+                    option.frames = args.next().and_then(|v| v.parse().ok());
+                    if option.frames.is_none() {
+                        usage();
+                    }
+                }
+                "--fuel" => {
+                    option.fuel = args.next().and_then(|v| v.parse().ok());
+                    if option.fuel.is_none() {
+                        usage();
+                    }
+                }
+                "--bmp" => {
+                    option.bmp_prefix = args.next();
+                    if option.bmp_prefix.is_none() {
+                        usage();
+                    }
+                }
+                "--" => {
+                    path_input = args.next();
+                    break;
+                }
+                _ => panic!("unknown option: {}", arg),
+            }
+        } else {
+            path_input = Some(arg.to_owned());
+            break;
+        }
+    }
+
+    let path_input = match path_input {
+        Some(v) => v,
+        None => usage(),
+    };
+
+    let mut blob = Vec::new();
+    {
+        let mut is = File::open(&path_input).expect("cannot open file");
+        is.read_to_end(&mut blob).expect("read file error");
+    }
+
+    let mut loader = WasmLoader::new();
+    if let Err(err) = loader.load(&blob, |mod_name, name, _type_ref| match mod_name {
+        HostRuntime::MOD_NAME => match name {
+            "svc0" | "svc1" | "svc2" | "svc3" | "svc4" | "svc5" | "svc6" => {
+                Ok(HostRuntime::syscall)
+            }
+            _ => Err(WasmDecodeErrorType::NoMethod),
+        },
+        _ => Err(WasmDecodeErrorType::NoModule),
+    }) {
+        eprintln!("Load error: {:?}", err);
+        process::exit(1);
+    }
+    let module = loader.into_module();
+    if option.fuel.is_some() {
+        module.set_fuel(option.fuel);
+    }
+
+    let status = HostRuntime::run(module, option);
+    process::exit(status);
+}
+
+#[derive(Default)]
+struct RunOption {
+    frames: Option<usize>,
+    fuel: Option<u64>,
+    bmp_prefix: Option<String>,
+}
+
+thread_local! {
+    static RUNTIME: RefCell<Option<HostRuntime>> = const { RefCell::new(None) };
+}
+
+/// Headless implementation of the Arlequin subsystem
+///
+/// Console output goes to stdout and windows are drawn into in-memory bitmaps.
+/// The clock is virtual and only advances by `usleep`, so that runs are reproducible.
+struct HostRuntime {
+    option: RunOption,
+    next_handle: usize,
+    windows: BTreeMap<usize, HostWindow>,
+    rng32: XorShift32,
+    malloc: HostAllocator,
+    monotonic: u64,
+    frames: usize,
+}
+
+impl HostRuntime {
+    const MOD_NAME: &'static str = "megos-canary";
+    const ENTRY_FUNC_NAME: &'static str = "_start";
+
+    fn run(module: WasmModule, option: RunOption) -> i32 {
+        let function = match module.func(Self::ENTRY_FUNC_NAME) {
+            Ok(v) => v,
+            Err(err) => {
+                eprintln!("error: {:?}", err);
+                return 1;
+            }
+        };
+
+        RUNTIME.with(|rt| {
+            *rt.borrow_mut() = Some(Self {
+                option,
+                next_handle: 1,
+                windows: BTreeMap::new(),
+                rng32: XorShift32::default(),
+                malloc: HostAllocator::default(),
+                monotonic: 0,
+                frames: 0,
+            })
+        });
+
+        let status = match function.invoke(&[]) {
+            Ok(_v) => 0,
+            Err(err) => match err.kind() {
+                WasmRuntimeErrorType::Exit => 0,
+                WasmRuntimeErrorType::OutOfFuel => {
+                    eprintln!("out of fuel");
+                    2
+                }
+                _ => {
+                    eprintln!("error: {:?}", err);
+                    Self::print_backtrace(&module, &err);
+                    1
+                }
+            },
+        };
+        let _ = std::io::stdout().flush();
+
+        RUNTIME.with(|rt| {
+            if let Some(rt) = rt.borrow_mut().take() {
+                for (handle, window) in rt.windows.iter() {
+                    rt.dump_window(*handle, window);
+                }
+            }
+        });
+
+        status
+    }
+
+    fn print_backtrace(module: &WasmModule, err: &WasmRuntimeError) {
+        for (index, frame) in err.backtrace().enumerate() {
+            match module.func_name(frame.function()) {
+                Some(name) => eprintln!(
+                    "  #{} {}+{:x} (function {})",
+                    index,
+                    name,
+                    frame.position(),
+                    frame.function()
+                ),
+                None => eprintln!(
+                    "  #{} function {}+{:x}",
+                    index,
+                    frame.function(),
+                    frame.position()
+                ),
+            }
+        }
+    }
+
+    fn syscall(
+        module: &WasmModule,
+        params: &[WasmValue],
+    ) -> Result<WasmValue, WasmRuntimeErrorType> {
+        RUNTIME.with(|rt| match rt.borrow_mut().as_mut() {
+            Some(rt) => rt.dispatch_syscall(module, params),
+            None => Err(WasmRuntimeErrorType::Unreachable),
+        })
+    }
+
+    fn dispatch_syscall(
+        &mut self,
+        module: &WasmModule,
+        params: &[WasmValue],
+    ) -> Result<WasmValue, WasmRuntimeErrorType> {
+        let mut params = ParamsDecoder::new(params);
+        let memory = module.memory(0).ok_or(WasmRuntimeErrorType::OutOfMemory)?;
+        let func_no = params.get_u32().and_then(|v| {
+            FromPrimitive::from_u32(v).ok_or(WasmRuntimeErrorType::InvalidParameter)
+        })?;
+
+        match func_no {
+            Function::Exit => {
+                return Err(WasmRuntimeErrorType::Exit);
+            }
+
+            Function::Monotonic => {
+                return Ok(WasmValue::I32(self.monotonic as i32));
+            }
+            Function::Time => {
+                let sub_func_no = params.get_usize()?;
+                if sub_func_no == 0 {
+                    let secs = self.monotonic / 1_000_000;
+                    return Ok(WasmValue::from((secs % 86400) as u32));
+                }
+            }
+            Function::Usleep => {
+                let us = params.get_u32()? as u64;
+                self.monotonic += us;
+            }
+
+            Function::GetSystemInfo => {
+                let sub_func_no = params.get_usize()?;
+                if sub_func_no == 0 {
+                    return Ok(WasmValue::from(0u32));
+                }
+            }
+
+            Function::PrintString => {
+                if let Some(s) = params.get_string(memory) {
+                    print!("{}", s);
+                }
+            }
+
+            Function::NewWindow => {
+                let title = params.get_string(memory).unwrap_or("");
+                let size = params.get_size()?;
+                let bg_color = params.get_color().ok();
+                let _window_option = params.get_u32().unwrap_or(0);
+
+                let handle = self.next_handle;
+                self.next_handle += 1;
+                let window = HostWindow::new(title, size, bg_color.unwrap_or(SomeColor::WHITE));
+                self.windows.insert(handle, window);
+                return Ok(WasmValue::I32(handle as i32));
+            }
+            Function::CloseWindow => {
+                let handle = params.get_usize()?;
+                if let Some(window) = self.windows.remove(&handle) {
+                    self.dump_window(handle, &window);
+                }
+            }
+            Function::BeginDraw => {
+                let handle = params.get_usize()?;
+                self.get_window(handle)?;
+                return Ok(WasmValue::from(handle as u32));
+            }
+            Function::EndDraw => {
+                let handle = params.get_usize()?;
+                self.get_window(handle)?;
+                self.frames += 1;
+                if let Some(frames) = self.option.frames {
+                    if self.frames >= frames {
+                        return Err(WasmRuntimeErrorType::Exit);
+                    }
+                }
+            }
+
+            Function::DrawString => {
+                // There are no fonts on the host, so the text goes to the console instead.
+                let handle = params.get_usize()?;
+                self.get_window(handle)?;
+                let _origin = params.get_point()?;
+                let text = params.get_string(memory).unwrap_or("");
+                let _color = params.get_color()?;
+                println!("{}", text);
+            }
+            Function::FillRect => {
+                let window = params.get_window(self)?;
+                let origin = params.get_point()?;
+                let size = params.get_size()?;
+                let color = params.get_color()?;
+                let rect = Rect { origin, size };
+                window.draw_in_rect(rect, |bitmap| {
+                    bitmap.fill_rect(rect.size.into(), color);
+                });
+            }
+            Function::DrawRect => {
+                let window = params.get_window(self)?;
+                let origin = params.get_point()?;
+                let size = params.get_size()?;
+                let color = params.get_color()?;
+                let rect = Rect { origin, size };
+                window.draw_in_rect(rect, |bitmap| {
+                    bitmap.draw_rect(rect.size.into(), color);
+                });
+            }
+            Function::DrawLine => {
+                let window = params.get_window(self)?;
+                let c1 = params.get_point()?;
+                let c2 = params.get_point()?;
+                let color = params.get_color()?;
+                let rect = Rect::from(Coordinates::from_diagonal(c1, c2)) + Size::new(1, 1);
+                window.draw_in_rect(rect, |bitmap| {
+                    bitmap.draw_line(c1 - rect.origin, c2 - rect.origin, color);
+                });
+            }
+            Function::WaitChar => {
+                // No keyboard is attached, so waiting for a key would never return.
+                let handle = params.get_usize()?;
+                self.get_window(handle)?;
+                return Err(WasmRuntimeErrorType::Exit);
+            }
+            Function::ReadChar => {
+                let handle = params.get_usize()?;
+                self.get_window(handle)?;
+                return Ok(WasmValue::from(megosabi::OPTION_CHAR_NONE));
+            }
+            Function::Blt8 => {
+                let window = params.get_window(self)?;
+                let origin = params.get_point()?;
+                let src = params.get_bitmap8(memory)?;
+                let rect = Rect {
+                    origin,
+                    size: src.size(),
+                };
+                window.draw_in_rect(rect, |bitmap| {
+                    bitmap.blt_transparent(
+                        &ConstBitmap::from(&src),
+                        Point::default(),
+                        src.size().into(),
+                        IndexedColor::DEFAULT_KEY,
+                    );
+                });
+            }
+            Function::Blt32 => {
+                let window = params.get_window(self)?;
+                let origin = params.get_point()?;
+                let src = params.get_bitmap32(memory)?;
+                let rect = Rect {
+                    origin,
+                    size: src.size(),
+                };
+                window.draw_in_rect(rect, |bitmap| {
+                    bitmap.blt(
+                        &ConstBitmap::from(&src),
+                        Point::default(),
+                        src.size().into(),
+                    );
+                });
+            }
+            Function::BlendRect => {
+                let bitmap = params.get_bitmap32(memory)?;
+                let origin = params.get_point()?;
+                let size = params.get_size()?;
+                let color = params.get_u32().map(TrueColor::from_argb)?;
+                let rect = Rect { origin, size };
+                let mut bitmap: Bitmap32 = unsafe { std::mem::transmute(bitmap) };
+                bitmap.blend_rect(rect, color);
+            }
+            Function::Blt1 => {
+                let window = params.get_window(self)?;
+                let origin = params.get_point()?;
+                let os_bitmap = params.get_bitmap1(memory)?;
+                let color = params.get_color()?;
+                let mode = params.get_usize()?;
+                window.draw_in_rect(os_bitmap.rect(origin, mode), |bitmap| {
+                    os_bitmap.blt(bitmap, Point::default(), color, mode);
+                });
+            }
+
+            Function::Rand => {
+                return Ok(WasmValue::from(self.rng32.next()));
+            }
+            Function::Srand => {
+                let seed = params.get_u32()?;
+                if let Some(v) = NonZeroU32::new(seed) {
+                    self.rng32 = XorShift32::new(v);
+                }
+            }
+
+            Function::Alloc => {
+                let size = params.get_usize()?;
+                let align = params.get_usize()?;
+                let layout = Layout::from_size_align(size, align)
+                    .map_err(|_| WasmRuntimeErrorType::InvalidParameter)?;
+                return self.malloc.alloc(memory, layout).map(WasmValue::from);
+            }
+            Function::Dealloc => {
+                let base = params.get_u32()?;
+                let size = params.get_usize()?;
+                let align = params.get_usize()?;
+                let layout = Layout::from_size_align(size, align)
+                    .map_err(|_| WasmRuntimeErrorType::InvalidParameter)?;
+                memory.write_bytes(base as usize, 0xCC, size)?;
+                self.malloc.dealloc(base, layout);
+            }
+
+            Function::SpawnThread => {
+                // The host runner has no threads, so the app has to run without them
+                return Ok(WasmValue::I32(0));
+            }
+        }
+
+        Ok(WasmValue::I32(0))
+    }
+
+    #[inline]
+    fn get_window(&mut self, handle: usize) -> Result<&mut HostWindow, WasmRuntimeErrorType> {
+        self.windows
+            .get_mut(&handle)
+            .ok_or(WasmRuntimeErrorType::InvalidParameter)
+    }
+
+    fn dump_window(&self, handle: usize, window: &HostWindow) {
+        let prefix = match self.option.bmp_prefix.as_ref() {
+            Some(v) => v,
+            None => return,
+        };
+        let path = format!("{}-{}.bmp", prefix, handle);
+        let mut os = File::create(&path).expect("cannot create file");
+        os.write_all(&window.to_msdib()).expect("write file error");
+        eprintln!("DUMPED: {} <= {:?}", path, window.title);
+    }
+}
+
+/// A window that is drawn into an in-memory bitmap
+struct HostWindow {
+    title: String,
+    bitmap: BoxedBitmap32<'static>,
+}
+
+impl HostWindow {
+    fn new(title: &str, size: Size, bg_color: SomeColor) -> Self {
+        Self {
+            title: title.to_owned(),
+            bitmap: BoxedBitmap32::new(size, bg_color.into()),
+        }
+    }
+
+    #[inline]
+    fn draw_in_rect<F>(&mut self, rect: Rect, f: F)
+    where
+        F: FnOnce(&mut Bitmap),
+    {
+        self.bitmap.draw(|bitmap| {
+            let _ = bitmap.view(rect, |mut bitmap| f(&mut bitmap));
+        });
+    }
+
+    /// Encodes the contents as a bottom-up 32bpp Windows bitmap
+    fn to_msdib(&self) -> Vec<u8> {
+        const SIZE_OF_FILE_HEADER: usize = 14;
+        const SIZE_OF_INFO_HEADER: usize = 40;
+        let width = self.bitmap.width();
+        let height = self.bitmap.height();
+        let stride = self.bitmap.stride();
+        let offset = SIZE_OF_FILE_HEADER + SIZE_OF_INFO_HEADER;
+        let size_image = width * height * 4;
+
+        let mut vec = Vec::with_capacity(offset + size_image);
+        vec.extend_from_slice(b"BM");
+        vec.write_u32::<LE>((offset + size_image) as u32).unwrap();
+        vec.write_u32::<LE>(0).unwrap();
+        vec.write_u32::<LE>(offset as u32).unwrap();
+
+        vec.write_u32::<LE>(SIZE_OF_INFO_HEADER as u32).unwrap();
+        vec.write_i32::<LE>(width as i32).unwrap();
+        vec.write_i32::<LE>(height as i32).unwrap();
+        vec.write_u16::<LE>(1).unwrap();
+        vec.write_u16::<LE>(32).unwrap();
+        vec.write_u32::<LE>(0).unwrap();
+        vec.write_u32::<LE>(size_image as u32).unwrap();
+        vec.write_i32::<LE>(0).unwrap();
+        vec.write_i32::<LE>(0).unwrap();
+        vec.write_u32::<LE>(0).unwrap();
+        vec.write_u32::<LE>(0).unwrap();
+
+        let slice = self.bitmap.slice();
+        for y in (0..height).rev() {
+            for pixel in &slice[y * stride..y * stride + width] {
+                vec.write_u32::<LE>(pixel.argb()).unwrap();
+            }
+        }
+        vec
+    }
+}
+
+/// Allocator that grows the linear memory and recycles freed blocks of the same layout
+#[derive(Default)]
+struct HostAllocator {
+    free_list: BTreeMap<(usize, usize), Vec<u32>>,
+    cursor: u32,
+    limit: u32,
+}
+
+impl HostAllocator {
+    const MIN_ALIGN: usize = 16;
+
+    fn alloc(&mut self, memory: &WasmMemory, layout: Layout) -> Result<u32, WasmRuntimeErrorType> {
+        let key = Self::key(layout);
+        if let Some(base) = self.free_list.get_mut(&key).and_then(|v| v.pop()) {
+            return Ok(base);
+        }
+
+        let (size, align) = key;
+        let base = (self.cursor as usize + align - 1) & !(align - 1);
+        if self.limit == 0 || base + size > self.limit as usize {
+            // Start a new arena at the end of the linear memory
+            let page_size = WasmMemory::PAGE_SIZE;
+            let delta = size.div_ceil(page_size);
+            let new_page = memory.grow(delta as i32);
+            if new_page <= 0 {
+                return Err(WasmRuntimeErrorType::OutOfMemory);
+            }
+            let base = new_page as usize * page_size;
+            self.cursor = (base + size) as u32;
+            self.limit = (base + delta * page_size) as u32;
+            return Ok(base as u32);
+        }
+
+        self.cursor = (base + size) as u32;
+        Ok(base as u32)
+    }
+
+    fn dealloc(&mut self, base: u32, layout: Layout) {
+        self.free_list
+            .entry(Self::key(layout))
+            .or_default()
+            .push(base);
+    }
+
+    #[inline]
+    fn key(layout: Layout) -> (usize, usize) {
+        let align = usize::max(layout.align(), Self::MIN_ALIGN);
+        let size = (usize::max(layout.size(), 1) + align - 1) & !(align - 1);
+        (size, align)
+    }
+}
+
+struct ParamsDecoder<'a> {
+    params: &'a [WasmValue],
+    index: usize,
+}
+
+impl<'a> ParamsDecoder<'a> {
+    #[inline]
+    pub const fn new(params: &'a [WasmValue]) -> Self {
+        Self { params, index: 0 }
+    }
+}
+
+impl ParamsDecoder<'_> {
+    #[inline]
+    fn get_u32(&mut self) -> Result<u32, WasmRuntimeErrorType> {
+        let index = self.index;
+        self.params
+            .get(index)
+            .ok_or(WasmRuntimeErrorType::InvalidParameter)
+            .and_then(|v| v.get_u32())
+            .inspect(|_| self.index += 1)
+    }
+
+    #[inline]
+    fn get_i32(&mut self) -> Result<i32, WasmRuntimeErrorType> {
+        let index = self.index;
+        self.params
+            .get(index)
+            .ok_or(WasmRuntimeErrorType::InvalidParameter)
+            .and_then(|v| v.get_i32())
+            .inspect(|_| self.index += 1)
+    }
+
+    #[inline]
+    fn get_usize(&mut self) -> Result<usize, WasmRuntimeErrorType> {
+        self.get_u32().map(|v| v as usize)
+    }
+
+    #[inline]
+    fn get_memarg(&mut self) -> Result<MemArg, WasmRuntimeErrorType> {
+        let base = self.get_u32()? as usize;
+        let len = self.get_u32()? as usize;
+        Ok(MemArg::new(base, len))
+    }
+
+    #[inline]
+    fn get_string<'a>(&mut self, memory: &'a WasmMemory) -> Option<&'a str> {
+        self.get_memarg()
+            .ok()
+            .and_then(|memarg| memory.read_bytes(memarg.base(), memarg.len()).ok())
+            .and_then(|v| core::str::from_utf8(v).ok())
+    }
+
+    #[inline]
+    fn get_point(&mut self) -> Result<Point, WasmRuntimeErrorType> {
+        let x = self.get_i32()? as isize;
+        let y = self.get_i32()? as isize;
+        Ok(Point::new(x, y))
+    }
+
+    #[inline]
+    fn get_size(&mut self) -> Result<Size, WasmRuntimeErrorType> {
+        let width = self.get_i32()? as isize;
+        let height = self.get_i32()? as isize;
+        Ok(Size::new(width, height))
+    }
+
+    #[inline]
+    fn get_color(&mut self) -> Result<SomeColor, WasmRuntimeErrorType> {
+        self.get_u32().map(|v| IndexedColor::from(v as u8).into())
+    }
+
+    fn get_bitmap8<'a>(
+        &mut self,
+        memory: &'a WasmMemory,
+    ) -> Result<ConstBitmap8<'a>, WasmRuntimeErrorType> {
+        const SIZE_OF_BITMAP: usize = 20;
+        let base = self.get_u32()? as usize;
+        let array = memory.read_bytes(base, SIZE_OF_BITMAP)?;
+
+        let width = LE::read_u32(&array[0..4]) as usize;
+        let height = LE::read_u32(&array[4..8]) as usize;
+        let _stride = LE::read_u32(&array[8..12]) as usize;
+        let base = LE::read_u32(&array[12..16]) as usize;
+
+        let len = width * height;
+        let slice = memory.read_bytes(base, len)?;
+
+        Ok(ConstBitmap8::from_bytes(
+            slice,
+            Size::new(width as isize, height as isize),
+        ))
+    }
+
+    fn get_bitmap32<'a>(
+        &mut self,
+        memory: &'a WasmMemory,
+    ) -> Result<ConstBitmap32<'a>, WasmRuntimeErrorType> {
+        const SIZE_OF_BITMAP: usize = 20;
+        let base = self.get_u32()? as usize;
+        let array = memory.read_bytes(base, SIZE_OF_BITMAP)?;
+
+        let width = LE::read_u32(&array[0..4]) as usize;
+        let height = LE::read_u32(&array[4..8]) as usize;
+        let _stride = LE::read_u32(&array[8..12]) as usize;
+        let base = LE::read_u32(&array[12..16]) as usize;
+
+        let len = width * height;
+        let slice = memory.read_u32_array(base, len)?;
+
+        Ok(ConstBitmap32::from_bytes(
+            slice,
+            Size::new(width as isize, height as isize),
+        ))
+    }
+
+    fn get_bitmap1<'a>(
+        &mut self,
+        memory: &'a WasmMemory,
+    ) -> Result<OsBitmap1<'a>, WasmRuntimeErrorType> {
+        let base = self.get_u32()?;
+        OsBitmap1::from_memory(memory, base)
+    }
+
+    #[inline]
+    fn get_window<'a>(
+        &mut self,
+        rt: &'a mut HostRuntime,
+    ) -> Result<&'a mut HostWindow, WasmRuntimeErrorType> {
+        let handle = self.get_usize()?;
+        rt.get_window(handle)
+    }
+}
+
+struct MemArg {
+    base: usize,
+    len: usize,
+}
+
+impl MemArg {
+    #[inline]
+    const fn new(base: usize, len: usize) -> Self {
+        Self { base, len }
+    }
+
+    #[inline]
+    const fn base(&self) -> usize {
+        self.base
+    }
+
+    #[inline]
+    const fn len(&self) -> usize {
+        self.len
+    }
+}
+
+struct OsBitmap1<'a> {
+    slice: &'a [u8],
+    dim: Size,
+    stride: usize,
+}
+
+impl<'a> OsBitmap1<'a> {
+    fn from_memory(memory: &'a WasmMemory, base: u32) -> Result<Self, WasmRuntimeErrorType> {
+        const SIZE_OF_BITMAP: usize = 16;
+        let array = memory.read_bytes(base as usize, SIZE_OF_BITMAP)?;
+
+        let width = LE::read_u32(&array[0..4]) as usize;
+        let height = LE::read_u32(&array[4..8]) as usize;
+        let stride = LE::read_u32(&array[8..12]) as usize;
+        let base = LE::read_u32(&array[12..16]) as usize;
+
+        let dim = Size::new(width as isize, height as isize);
+        let size = stride * height;
+        let slice = memory.read_bytes(base, size)?;
+
+        Ok(Self { slice, dim, stride })
+    }
+}
+
+impl OsBitmap1<'_> {
+    #[inline]
+    const fn rect(&self, origin: Point, mode: usize) -> Rect {
+        let scale = mode as isize;
+        Rect {
+            origin,
+            size: Size::new(self.dim.width * scale, self.dim.height * scale),
+        }
+    }
+
+    fn blt(&self, to: &mut Bitmap, origin: Point, color: SomeColor, mode: usize) {
+        let scale = mode as isize;
+        let stride = self.stride;
+        let mut cursor = 0;
+        for y in 0..self.dim.height {
+            for x in 0..self.dim.width as usize {
+                let data = self.slice[cursor + x / 8];
+                if (data & (0x80u8 >> (x & 7))) != 0 {
+                    let x = scale * x as isize;
+                    let y = y * scale;
+                    for offset in &[(0, 0), (0, 1), (1, 0), (1, 1)] {
+                        let point = Point::new(origin.x + x + offset.0, origin.y + y + offset.1);
+                        to.set_pixel(point, color);
+                    }
+                }
+            }
+            cursor += stride;
+        }
+    }
+}
@@ -0,0 +1,214 @@
+// Run the WebAssembly spec testsuite against lib/wasm
+// Copyright(c) 2021 The MEG-OS Project
+
+mod runner;
+mod script;
+
+use runner::*;
+use script::*;
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    process, thread,
+};
+
+/// Stack size of the thread running the scripts, since the interpreter calls functions recursively
+const STACK_SIZE: usize = 0x1000_0000;
+
+fn usage() -> ! {
+    let mut args = env::args_os();
+    let arg = args.next().unwrap();
+    let path = Path::new(&arg);
+    let lpc = path.file_name().unwrap();
+    eprintln!("{} [OPTIONS] PATH...", lpc.to_str().unwrap());
+    eprintln!("  -v                 print each failed assertion and the reasons for skipping");
+    eprintln!("  --proposal NAME    run only the scripts of the proposal");
+    process::exit(1);
+}
+
+#[derive(Default)]
+struct Stat {
+    passed: usize,
+    failed: usize,
+    skipped: usize,
+}
+
+#[derive(Default)]
+struct Report {
+    stats: BTreeMap<String, Stat>,
+    skip_reasons: BTreeMap<String, usize>,
+}
+
+fn main() {
+    let mut args = env::args();
+    let _ = args.next().unwrap();
+
+    let mut paths = Vec::new();
+    let mut verbose = false;
+    let mut proposal = None;
+
+    while let Some(arg) = args.next() {
+        let arg = arg.as_str();
+        if arg.chars().next().unwrap_or_default() == '-' {
+            match arg {
+                "-v" => verbose = true,
+                "--proposal" => {
+                    proposal = args.next();
+                    if proposal.is_none() {
+                        usage();
+                    }
+                }
+                _ => {
+                    eprintln!("Unknown option: {}", arg);
+                    usage();
+                }
+            }
+        } else {
+            paths.push(PathBuf::from(arg));
+        }
+    }
+    if paths.is_empty() {
+        usage();
+    }
+
+    let mut scripts = Vec::new();
+    for path in &paths {
+        if !path.exists() {
+            eprintln!("{}: not found", path.display());
+            process::exit(1);
+        }
+        collect_scripts(path, &mut scripts);
+    }
+    if let Some(proposal) = proposal.as_ref() {
+        scripts.retain(|v| proposal_name(v) == *proposal);
+    }
+
+    // Panics in the interpreter are reported as failures
+    panic::set_hook(Box::new(|_| {}));
+
+    let report = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(move || run_scripts(&scripts, verbose))
+        .unwrap()
+        .join()
+        .unwrap();
+
+    println!(
+        "{:<32} {:>8} {:>8} {:>8}",
+        "proposal", "passed", "failed", "skipped"
+    );
+    let mut total = Stat::default();
+    for (proposal, stat) in &report.stats {
+        println!(
+            "{:<32} {:>8} {:>8} {:>8}",
+            proposal, stat.passed, stat.failed, stat.skipped
+        );
+        total.passed += stat.passed;
+        total.failed += stat.failed;
+        total.skipped += stat.skipped;
+    }
+    println!(
+        "{:<32} {:>8} {:>8} {:>8}",
+        "total", total.passed, total.failed, total.skipped
+    );
+
+    if verbose {
+        for (reason, count) in &report.skip_reasons {
+            println!("skipped {}: {}", count, reason);
+        }
+    }
+
+    if total.failed > 0 {
+        process::exit(1);
+    }
+}
+
+fn run_scripts(scripts: &[PathBuf], verbose: bool) -> Report {
+    let mut report = Report::default();
+    for path in scripts {
+        let stat = report.stats.entry(proposal_name(path)).or_default();
+        let commands = match Command::load(path) {
+            Ok(v) => v,
+            Err(err) => {
+                eprintln!("{}: {}", path.display(), err);
+                stat.failed += 1;
+                continue;
+            }
+        };
+
+        let mut runner = Runner::new();
+        for command in &commands {
+            let outcome = panic::catch_unwind(AssertUnwindSafe(|| runner.run(command)))
+                .unwrap_or_else(|payload| {
+                    let message = payload
+                        .downcast_ref::<&str>()
+                        .map(|v| v.to_string())
+                        .or_else(|| payload.downcast_ref::<String>().cloned())
+                        .unwrap_or_default();
+                    Outcome::Fail(format!("panicked: {}", message))
+                });
+            match outcome {
+                Outcome::Pass => stat.passed += 1,
+                Outcome::Fail(reason) => {
+                    stat.failed += 1;
+                    if verbose {
+                        println!(
+                            "{}:{}: {}: {}",
+                            path.display(),
+                            command.line(),
+                            command.kind(),
+                            reason
+                        );
+                    }
+                }
+                Outcome::Skip(reason) => {
+                    stat.skipped += 1;
+                    *report.skip_reasons.entry(reason).or_default() += 1;
+                }
+            }
+        }
+    }
+    report
+}
+
+/// Collects `.wast` scripts, or `.json` scripts converted by `wast2json` if there is no `.wast` with the same name.
+fn collect_scripts(path: &Path, scripts: &mut Vec<PathBuf>) {
+    if path.is_file() {
+        scripts.push(path.to_path_buf());
+        return;
+    }
+    let mut entries = match fs::read_dir(path) {
+        Ok(v) => v
+            .filter_map(|v| v.ok())
+            .map(|v| v.path())
+            .collect::<Vec<_>>(),
+        Err(_) => return,
+    };
+    entries.sort();
+    for entry in &entries {
+        if entry.is_dir() {
+            collect_scripts(entry, scripts);
+            continue;
+        }
+        match entry.extension().and_then(|v| v.to_str()) {
+            Some("wast") => scripts.push(entry.clone()),
+            Some("json") if !entry.with_extension("wast").exists() => scripts.push(entry.clone()),
+            _ => (),
+        }
+    }
+}
+
+/// Scripts under `proposals/NAME/` belong to the proposal, others belong to "core".
+fn proposal_name(path: &Path) -> String {
+    let mut components = path.components().map(|v| v.as_os_str().to_string_lossy());
+    while let Some(component) = components.next() {
+        if component == "proposals" {
+            if let Some(name) = components.next() {
+                return name.to_string();
+            }
+        }
+    }
+    "core".to_string()
+}
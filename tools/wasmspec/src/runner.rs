@@ -0,0 +1,298 @@
+// Runs spec test commands against WasmLoader
+
+use crate::script::*;
+use std::fmt;
+use wasm::{wasmintr::*, *};
+
+/// The result of a command
+pub enum Outcome {
+    Pass,
+    Fail(String),
+    Skip(String),
+}
+
+/// Why a module could not be instantiated
+enum InstantiateError {
+    Load(WasmDecodeErrorType),
+    Trap(WasmRuntimeErrorType),
+}
+
+impl fmt::Display for InstantiateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Load(kind) => write!(f, "load error {:?}", kind),
+            Self::Trap(kind) => write!(f, "start trapped {:?}", kind),
+        }
+    }
+}
+
+/// Returns whether the trap is the one described by the message of the spec test
+fn trap_matches(kind: WasmRuntimeErrorType, message: &str) -> bool {
+    let expected: &[&str] = match kind {
+        WasmRuntimeErrorType::Unreachable => &["unreachable"],
+        WasmRuntimeErrorType::DivideByZero => &["integer divide by zero"],
+        WasmRuntimeErrorType::IntegerOverflow => &["integer overflow"],
+        WasmRuntimeErrorType::InvalidConversionToInteger => &["invalid conversion to integer"],
+        WasmRuntimeErrorType::OutOfBounds => &[
+            "out of bounds memory access",
+            "out of bounds table access",
+            "out of bounds",
+        ],
+        WasmRuntimeErrorType::NoMethod => &[
+            "undefined element",
+            "uninitialized element",
+            "uninitialized",
+        ],
+        WasmRuntimeErrorType::TypeMismatch => &["indirect call type mismatch"],
+        WasmRuntimeErrorType::UnalignedAtomic => &["unaligned atomic"],
+        WasmRuntimeErrorType::Exception => &["uncaught exception"],
+        _ => &[],
+    };
+    expected.iter().any(|v| message.starts_with(v))
+}
+
+/// Objects exported by the "spectest" module
+struct SpecTest {
+    memory: WasmMemory,
+    table: WasmTable,
+    global_i32: WasmGlobal,
+    global_i64: WasmGlobal,
+    global_f32: WasmGlobal,
+    global_f64: WasmGlobal,
+}
+
+impl SpecTest {
+    fn new() -> Self {
+        Self {
+            memory: WasmMemory::new(WasmLimit::new(1, 2)),
+            table: WasmTable::new(WasmLimit::new(10, 20)),
+            global_i32: WasmGlobal::new(WasmValue::I32(666), false),
+            global_i64: WasmGlobal::new(WasmValue::I64(666), false),
+            global_f32: WasmGlobal::new(WasmValue::F32(666.6), false),
+            global_f64: WasmGlobal::new(WasmValue::F64(666.6), false),
+        }
+    }
+
+    fn define(&self, loader: &mut WasmLoader) {
+        loader.define_memory("spectest", "memory", self.memory.share());
+        loader.define_table("spectest", "table", self.table.share());
+        loader.define_global("spectest", "global_i32", self.global_i32.share());
+        loader.define_global("spectest", "global_i64", self.global_i64.share());
+        loader.define_global("spectest", "global_f32", self.global_f32.share());
+        loader.define_global("spectest", "global_f64", self.global_f64.share());
+    }
+
+    fn print(_: &WasmModule, _: &[WasmValue]) -> Result<WasmValue, WasmRuntimeErrorType> {
+        Ok(WasmValue::I32(0))
+    }
+
+    /// Only the functions of "spectest" can be imported,
+    /// because the host functions cannot hold the module they belong to.
+    fn resolve(
+        mod_name: &str,
+        name: &str,
+        _type_ref: &WasmType,
+    ) -> Result<WasmDynFunc, WasmDecodeErrorType> {
+        match mod_name {
+            "spectest" if name.starts_with("print") => Ok(Self::print),
+            _ => Err(WasmDecodeErrorType::NoMethod),
+        }
+    }
+}
+
+struct Instance {
+    name: Option<String>,
+    module: WasmModule,
+}
+
+/// Runs the commands of one script
+pub struct Runner {
+    spectest: SpecTest,
+    instances: Vec<Instance>,
+    current: Option<usize>,
+    registered: Vec<(String, usize)>,
+}
+
+impl Runner {
+    pub fn new() -> Self {
+        Self {
+            spectest: SpecTest::new(),
+            instances: Vec::new(),
+            current: None,
+            registered: Vec::new(),
+        }
+    }
+
+    pub fn run(&mut self, command: &Command) -> Outcome {
+        match command {
+            Command::Module { name, binary, .. } => {
+                self.current = None;
+                let binary = match binary {
+                    Ok(v) => v,
+                    Err(reason) => return Outcome::Skip(reason.clone()),
+                };
+                match self.instantiate(binary) {
+                    Ok(module) => {
+                        self.instances.push(Instance {
+                            name: name.clone(),
+                            module,
+                        });
+                        self.current = Some(self.instances.len() - 1);
+                        Outcome::Pass
+                    }
+                    Err(err) => Outcome::Fail(err.to_string()),
+                }
+            }
+
+            Command::Register { name, as_name, .. } => match self.instance(name) {
+                Some(index) => {
+                    self.registered.push((as_name.clone(), index));
+                    Outcome::Pass
+                }
+                None => Outcome::Fail("no module to register".to_string()),
+            },
+
+            Command::Action { action, .. } => match self.perform(action) {
+                Ok(Ok(_)) => Outcome::Pass,
+                Ok(Err(kind)) => Outcome::Fail(format!("trapped {:?}", kind)),
+                Err(reason) => Outcome::Fail(reason),
+            },
+
+            Command::AssertReturn {
+                action, expected, ..
+            } => match self.perform(action) {
+                Ok(Ok(results)) => {
+                    if results.len() == expected.len()
+                        && expected
+                            .iter()
+                            .zip(results.iter())
+                            .all(|(e, v)| e.matches(v))
+                    {
+                        Outcome::Pass
+                    } else {
+                        Outcome::Fail(format!("expected {:?}, got {:?}", expected, results))
+                    }
+                }
+                Ok(Err(kind)) => Outcome::Fail(format!("trapped {:?}", kind)),
+                Err(reason) => Outcome::Fail(reason),
+            },
+
+            Command::AssertTrap {
+                action, message, ..
+            } => match self.perform(action) {
+                Ok(Ok(results)) => Outcome::Fail(format!("expected a trap, got {:?}", results)),
+                Ok(Err(kind)) => Self::check_trap(kind, message),
+                Err(reason) => Outcome::Fail(reason),
+            },
+
+            Command::AssertModuleTrap {
+                binary, message, ..
+            } => match self.instantiate(binary) {
+                Ok(_) => Outcome::Fail("expected a trap, but instantiated".to_string()),
+                Err(InstantiateError::Trap(kind)) => Self::check_trap(kind, message),
+                Err(err) => Outcome::Fail(format!("expected a trap, got {}", err)),
+            },
+
+            Command::AssertInvalid { binary, .. }
+            | Command::AssertMalformed { binary, .. }
+            | Command::AssertUnlinkable { binary, .. } => match self.instantiate(binary) {
+                Ok(_) => Outcome::Fail("expected an error, but instantiated".to_string()),
+                Err(_) => Outcome::Pass,
+            },
+
+            Command::Skip { reason, .. } => Outcome::Skip(reason.clone()),
+        }
+    }
+
+    fn check_trap(kind: WasmRuntimeErrorType, message: &str) -> Outcome {
+        if trap_matches(kind, message) {
+            Outcome::Pass
+        } else {
+            Outcome::Fail(format!("expected \"{}\", trapped {:?}", message, kind))
+        }
+    }
+
+    fn instance(&self, name: &Option<String>) -> Option<usize> {
+        match name {
+            Some(name) => self
+                .instances
+                .iter()
+                .rposition(|v| v.name.as_ref() == Some(name)),
+            None => self.current,
+        }
+    }
+
+    /// Loads the module and runs its start function
+    fn instantiate(&self, binary: &[u8]) -> Result<WasmModule, InstantiateError> {
+        let mut loader = WasmLoader::new();
+        self.spectest.define(&mut loader);
+        for (as_name, index) in &self.registered {
+            let module = &self.instances[*index].module;
+            for export in module.exports() {
+                let name = export.name();
+                match export.index() {
+                    WasmExportIndex::Memory(_) => {
+                        if let Some(v) = module.exported_memory(name) {
+                            loader.define_memory(as_name, name, v.share());
+                        }
+                    }
+                    WasmExportIndex::Table(_) => {
+                        if let Some(v) = module.exported_table(name) {
+                            loader.define_table(as_name, name, v.share());
+                        }
+                    }
+                    WasmExportIndex::Global(_) => {
+                        if let Some(v) = module.exported_global(name) {
+                            loader.define_global(as_name, name, v.share());
+                        }
+                    }
+                    WasmExportIndex::Function(_) | WasmExportIndex::Tag(_) => {}
+                }
+            }
+        }
+
+        loader
+            .load(binary, SpecTest::resolve)
+            .map_err(|err| InstantiateError::Load(err.kind()))?;
+        let module = loader.into_module();
+        if let Ok(start) = module.entry_point() {
+            start
+                .invoke(&[])
+                .map_err(|err| InstantiateError::Trap(err.kind()))?;
+        }
+        Ok(module)
+    }
+
+    /// Performs the action, the outer error is a problem of the script rather than a trap.
+    fn perform(
+        &self,
+        action: &Action,
+    ) -> Result<Result<Vec<WasmValue>, WasmRuntimeErrorType>, String> {
+        match action {
+            Action::Invoke {
+                module,
+                field,
+                args,
+            } => {
+                let module = self.module(module)?;
+                let func = module
+                    .func(field)
+                    .map_err(|_| format!("no function {}", field))?;
+                Ok(func.invoke_multi(args).map_err(|err| err.kind()))
+            }
+            Action::Get { module, field } => {
+                let module = self.module(module)?;
+                module
+                    .exported_global(field)
+                    .map(|v| Ok(vec![*v.value()]))
+                    .ok_or_else(|| format!("no global {}", field))
+            }
+        }
+    }
+
+    fn module(&self, name: &Option<String>) -> Result<&WasmModule, String> {
+        self.instance(name)
+            .map(|index| &self.instances[index].module)
+            .ok_or_else(|| "no module".to_string())
+    }
+}
@@ -0,0 +1,578 @@
+// Spec test scripts
+
+use serde_json::Value;
+use std::{fs, path::Path};
+use wasm::WasmValue;
+use wast::{
+    core::{AbstractHeapType, HeapType, NanPattern, V128Pattern, WastArgCore, WastRetCore},
+    parser::{self, ParseBuffer},
+    QuoteWatTest, Wast, WastArg, WastDirective, WastExecute, WastInvoke, WastRet,
+};
+
+/// A command of the spec test script
+pub enum Command {
+    Module {
+        line: usize,
+        name: Option<String>,
+        binary: Result<Vec<u8>, String>,
+    },
+    Register {
+        line: usize,
+        name: Option<String>,
+        as_name: String,
+    },
+    Action {
+        line: usize,
+        action: Action,
+    },
+    AssertReturn {
+        line: usize,
+        action: Action,
+        expected: Vec<Expected>,
+    },
+    AssertTrap {
+        line: usize,
+        action: Action,
+        message: String,
+    },
+    /// assert_trap with a module, or assert_uninstantiable
+    AssertModuleTrap {
+        line: usize,
+        binary: Vec<u8>,
+        message: String,
+    },
+    AssertInvalid {
+        line: usize,
+        binary: Vec<u8>,
+    },
+    AssertMalformed {
+        line: usize,
+        binary: Vec<u8>,
+    },
+    AssertUnlinkable {
+        line: usize,
+        binary: Vec<u8>,
+    },
+    /// A command that this harness does not run
+    Skip {
+        line: usize,
+        reason: String,
+    },
+}
+
+impl Command {
+    pub fn line(&self) -> usize {
+        match self {
+            Self::Module { line, .. }
+            | Self::Register { line, .. }
+            | Self::Action { line, .. }
+            | Self::AssertReturn { line, .. }
+            | Self::AssertTrap { line, .. }
+            | Self::AssertModuleTrap { line, .. }
+            | Self::AssertInvalid { line, .. }
+            | Self::AssertMalformed { line, .. }
+            | Self::AssertUnlinkable { line, .. }
+            | Self::Skip { line, .. } => *line,
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Module { .. } => "module",
+            Self::Register { .. } => "register",
+            Self::Action { .. } => "action",
+            Self::AssertReturn { .. } => "assert_return",
+            Self::AssertTrap { .. } | Self::AssertModuleTrap { .. } => "assert_trap",
+            Self::AssertInvalid { .. } => "assert_invalid",
+            Self::AssertMalformed { .. } => "assert_malformed",
+            Self::AssertUnlinkable { .. } => "assert_unlinkable",
+            Self::Skip { .. } => "skip",
+        }
+    }
+
+    /// Loads the commands from a `.wast` script, or a `.json` script converted by `wast2json`.
+    pub fn load(path: &Path) -> Result<Vec<Command>, String> {
+        match path.extension().and_then(|v| v.to_str()) {
+            Some("json") => Self::from_json(path),
+            _ => Self::from_wast(path),
+        }
+    }
+
+    fn from_wast(path: &Path) -> Result<Vec<Command>, String> {
+        let text = fs::read_to_string(path).map_err(|err| err.to_string())?;
+        let buf = ParseBuffer::new(&text).map_err(|err| err.to_string())?;
+        let wast = parser::parse::<Wast>(&buf).map_err(|mut err| {
+            err.set_text(&text);
+            err.to_string()
+        })?;
+        Ok(wast
+            .directives
+            .into_iter()
+            .map(|directive| {
+                let line = directive.span().linecol_in(&text).0 + 1;
+                Self::from_directive(line, directive)
+            })
+            .collect())
+    }
+
+    fn from_directive(line: usize, directive: WastDirective) -> Self {
+        let skip = |reason: &str| Self::Skip {
+            line,
+            reason: reason.to_string(),
+        };
+        match directive {
+            WastDirective::Module(mut module) => Self::Module {
+                line,
+                name: module.name().map(|v| v.name().to_string()),
+                binary: module.encode().map_err(|err| err.message()),
+            },
+            WastDirective::ModuleInstance { instance, .. } => Self::Module {
+                line,
+                name: instance.map(|v| v.name().to_string()),
+                binary: Err("module instances are not supported".to_string()),
+            },
+            WastDirective::ModuleDefinition(_) => skip("module definition"),
+            WastDirective::Register { name, module, .. } => Self::Register {
+                line,
+                name: module.map(|v| v.name().to_string()),
+                as_name: name.to_string(),
+            },
+            WastDirective::Invoke(invoke) => match Action::from_invoke(invoke) {
+                Ok(action) => Self::Action { line, action },
+                Err(reason) => skip(&reason),
+            },
+            WastDirective::AssertReturn { exec, results, .. } => {
+                let action = match Action::from_execute(exec) {
+                    Ok(v) => v,
+                    Err(reason) => return skip(&reason),
+                };
+                match results.into_iter().map(Expected::from_wast).collect() {
+                    Ok(expected) => Self::AssertReturn {
+                        line,
+                        action,
+                        expected,
+                    },
+                    Err(reason) => skip(&reason),
+                }
+            }
+            WastDirective::AssertTrap {
+                exec: WastExecute::Wat(mut module),
+                message,
+                ..
+            } => match module.encode() {
+                Ok(binary) => Self::AssertModuleTrap {
+                    line,
+                    binary,
+                    message: message.to_string(),
+                },
+                Err(err) => skip(&err.message()),
+            },
+            WastDirective::AssertTrap { exec, message, .. } => match Action::from_execute(exec) {
+                Ok(action) => Self::AssertTrap {
+                    line,
+                    action,
+                    message: message.to_string(),
+                },
+                Err(reason) => skip(&reason),
+            },
+            WastDirective::AssertInvalid { mut module, .. } => match module.encode() {
+                Ok(binary) => Self::AssertInvalid { line, binary },
+                Err(err) => skip(&err.message()),
+            },
+            WastDirective::AssertMalformed { mut module, .. } => match module.to_test() {
+                Ok(QuoteWatTest::Binary(binary)) => Self::AssertMalformed { line, binary },
+                // Malformed text is a matter of the text format parser
+                _ => skip("malformed text"),
+            },
+            WastDirective::AssertUnlinkable { mut module, .. } => match module.encode() {
+                Ok(binary) => Self::AssertUnlinkable { line, binary },
+                Err(err) => skip(&err.message()),
+            },
+            // The interpreter does not limit the depth of the native call stack
+            WastDirective::AssertExhaustion { .. } => skip("assert_exhaustion"),
+            WastDirective::AssertException { .. } => skip("assert_exception"),
+            WastDirective::AssertSuspension { .. } => skip("assert_suspension"),
+            WastDirective::Thread(_) | WastDirective::Wait { .. } => skip("threads"),
+        }
+    }
+
+    fn from_json(path: &Path) -> Result<Vec<Command>, String> {
+        let text = fs::read_to_string(path).map_err(|err| err.to_string())?;
+        let json: Value = serde_json::from_str(&text).map_err(|err| err.to_string())?;
+        let base = path.parent().unwrap_or_else(|| Path::new("."));
+        let commands = json["commands"]
+            .as_array()
+            .ok_or_else(|| "no commands".to_string())?;
+        Ok(commands
+            .iter()
+            .map(|command| Self::from_json_command(base, command))
+            .collect())
+    }
+
+    fn from_json_command(base: &Path, command: &Value) -> Self {
+        let line = command["line"].as_u64().unwrap_or_default() as usize;
+        let skip = |reason: &str| Self::Skip {
+            line,
+            reason: reason.to_string(),
+        };
+        let binary = || match command["filename"].as_str() {
+            Some(filename) => fs::read(base.join(filename)).map_err(|err| err.to_string()),
+            None => Err("no filename".to_string()),
+        };
+        let is_text = command["module_type"].as_str() == Some("text");
+        let message = command["text"].as_str().unwrap_or_default().to_string();
+
+        match command["type"].as_str().unwrap_or_default() {
+            "module" => Self::Module {
+                line,
+                name: command["name"].as_str().map(|v| v.to_string()),
+                binary: binary(),
+            },
+            "register" => Self::Register {
+                line,
+                name: command["name"].as_str().map(|v| v.to_string()),
+                as_name: command["as"].as_str().unwrap_or_default().to_string(),
+            },
+            "action" => match Action::from_json(&command["action"]) {
+                Ok(action) => Self::Action { line, action },
+                Err(reason) => skip(&reason),
+            },
+            "assert_return" => {
+                let action = match Action::from_json(&command["action"]) {
+                    Ok(v) => v,
+                    Err(reason) => return skip(&reason),
+                };
+                let expected = command["expected"]
+                    .as_array()
+                    .map(|v| v.iter().map(Expected::from_json).collect())
+                    .unwrap_or_else(|| Ok(Vec::new()));
+                match expected {
+                    Ok(expected) => Self::AssertReturn {
+                        line,
+                        action,
+                        expected,
+                    },
+                    Err(reason) => skip(&reason),
+                }
+            }
+            "assert_trap" => match Action::from_json(&command["action"]) {
+                Ok(action) => Self::AssertTrap {
+                    line,
+                    action,
+                    message,
+                },
+                Err(reason) => skip(&reason),
+            },
+            "assert_invalid" | "assert_malformed" if is_text => skip("malformed text"),
+            "assert_invalid"
+            | "assert_malformed"
+            | "assert_uninstantiable"
+            | "assert_unlinkable" => {
+                let binary = match binary() {
+                    Ok(v) => v,
+                    Err(reason) => return skip(&reason),
+                };
+                match command["type"].as_str().unwrap_or_default() {
+                    "assert_invalid" => Self::AssertInvalid { line, binary },
+                    "assert_malformed" => Self::AssertMalformed { line, binary },
+                    "assert_uninstantiable" => Self::AssertModuleTrap {
+                        line,
+                        binary,
+                        message,
+                    },
+                    _ => Self::AssertUnlinkable { line, binary },
+                }
+            }
+            other => skip(other),
+        }
+    }
+}
+
+/// An action to be performed on a module
+pub enum Action {
+    Invoke {
+        module: Option<String>,
+        field: String,
+        args: Vec<WasmValue>,
+    },
+    Get {
+        module: Option<String>,
+        field: String,
+    },
+}
+
+impl Action {
+    fn from_invoke(invoke: WastInvoke) -> Result<Self, String> {
+        Ok(Self::Invoke {
+            module: invoke.module.map(|v| v.name().to_string()),
+            field: invoke.name.to_string(),
+            args: invoke
+                .args
+                .into_iter()
+                .map(|arg| match arg {
+                    WastArg::Core(arg) => wast_value(arg),
+                    #[allow(unreachable_patterns)]
+                    _ => Err("component value".to_string()),
+                })
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    fn from_execute(exec: WastExecute) -> Result<Self, String> {
+        match exec {
+            WastExecute::Invoke(invoke) => Self::from_invoke(invoke),
+            WastExecute::Get { module, global, .. } => Ok(Self::Get {
+                module: module.map(|v| v.name().to_string()),
+                field: global.to_string(),
+            }),
+            WastExecute::Wat(_) => Err("module execution".to_string()),
+        }
+    }
+
+    fn from_json(action: &Value) -> Result<Self, String> {
+        let module = action["module"].as_str().map(|v| v.to_string());
+        let field = action["field"].as_str().unwrap_or_default().to_string();
+        match action["type"].as_str().unwrap_or_default() {
+            "invoke" => Ok(Self::Invoke {
+                module,
+                field,
+                args: action["args"]
+                    .as_array()
+                    .map(|v| v.iter().map(json_value).collect())
+                    .unwrap_or_else(|| Ok(Vec::new()))?,
+            }),
+            "get" => Ok(Self::Get { module, field }),
+            other => Err(format!("action {}", other)),
+        }
+    }
+}
+
+/// An expected result of assert_return
+#[derive(Debug)]
+pub enum Expected {
+    Value(WasmValue),
+    CanonicalNan32,
+    ArithmeticNan32,
+    CanonicalNan64,
+    ArithmeticNan64,
+    /// A null reference of any type
+    NullRef,
+    /// A non-null funcref
+    AnyFuncRef,
+    /// A non-null externref
+    AnyExternRef,
+    /// A v128 whose lanes are matched separately as f32, for NaN patterns
+    F32x4(Vec<Expected>),
+    /// A v128 whose lanes are matched separately as f64, for NaN patterns
+    F64x2(Vec<Expected>),
+    Either(Vec<Expected>),
+}
+
+impl Expected {
+    pub fn matches(&self, value: &WasmValue) -> bool {
+        match (self, *value) {
+            (Self::Value(WasmValue::I32(a)), WasmValue::I32(b)) => *a == b,
+            (Self::Value(WasmValue::I64(a)), WasmValue::I64(b)) => *a == b,
+            (Self::Value(WasmValue::F32(a)), WasmValue::F32(b)) => a.to_bits() == b.to_bits(),
+            (Self::Value(WasmValue::F64(a)), WasmValue::F64(b)) => a.to_bits() == b.to_bits(),
+            (Self::Value(WasmValue::V128(a)), WasmValue::V128(b)) => *a == b,
+            (Self::Value(WasmValue::FuncRef(a)), WasmValue::FuncRef(b)) => *a == b,
+            (Self::Value(WasmValue::ExternRef(a)), WasmValue::ExternRef(b)) => *a == b,
+            (Self::CanonicalNan32, WasmValue::F32(v)) => v.to_bits() & 0x7FFF_FFFF == 0x7FC0_0000,
+            (Self::ArithmeticNan32, WasmValue::F32(v)) => v.to_bits() & 0x7FC0_0000 == 0x7FC0_0000,
+            (Self::CanonicalNan64, WasmValue::F64(v)) => {
+                v.to_bits() & 0x7FFF_FFFF_FFFF_FFFF == 0x7FF8_0000_0000_0000
+            }
+            (Self::ArithmeticNan64, WasmValue::F64(v)) => {
+                v.to_bits() & 0x7FF8_0000_0000_0000 == 0x7FF8_0000_0000_0000
+            }
+            (Self::NullRef, WasmValue::FuncRef(None)) => true,
+            (Self::NullRef, WasmValue::ExternRef(None)) => true,
+            (Self::AnyFuncRef, WasmValue::FuncRef(Some(_))) => true,
+            (Self::AnyExternRef, WasmValue::ExternRef(Some(_))) => true,
+            (Self::F32x4(lanes), WasmValue::V128(v)) => {
+                lanes.iter().enumerate().all(|(i, lane)| {
+                    lane.matches(&WasmValue::F32(f32::from_bits((v >> (i * 32)) as u32)))
+                })
+            }
+            (Self::F64x2(lanes), WasmValue::V128(v)) => {
+                lanes.iter().enumerate().all(|(i, lane)| {
+                    lane.matches(&WasmValue::F64(f64::from_bits((v >> (i * 64)) as u64)))
+                })
+            }
+            (Self::Either(list), _) => list.iter().any(|v| v.matches(value)),
+            _ => false,
+        }
+    }
+
+    fn from_wast(ret: WastRet) -> Result<Self, String> {
+        let ret = match ret {
+            WastRet::Core(v) => v,
+            #[allow(unreachable_patterns)]
+            _ => return Err("component value".to_string()),
+        };
+        match ret {
+            WastRetCore::I32(v) => Ok(Self::Value(WasmValue::I32(v))),
+            WastRetCore::I64(v) => Ok(Self::Value(WasmValue::I64(v))),
+            WastRetCore::F32(NanPattern::Value(v)) => {
+                Ok(Self::Value(WasmValue::F32(f32::from_bits(v.bits))))
+            }
+            WastRetCore::F32(NanPattern::CanonicalNan) => Ok(Self::CanonicalNan32),
+            WastRetCore::F32(NanPattern::ArithmeticNan) => Ok(Self::ArithmeticNan32),
+            WastRetCore::F64(NanPattern::Value(v)) => {
+                Ok(Self::Value(WasmValue::F64(f64::from_bits(v.bits))))
+            }
+            WastRetCore::F64(NanPattern::CanonicalNan) => Ok(Self::CanonicalNan64),
+            WastRetCore::F64(NanPattern::ArithmeticNan) => Ok(Self::ArithmeticNan64),
+            WastRetCore::RefNull(_) => Ok(Self::NullRef),
+            WastRetCore::RefExtern(Some(v)) => {
+                Ok(Self::Value(WasmValue::ExternRef(Some(v as usize))))
+            }
+            WastRetCore::RefExtern(None) => Ok(Self::AnyExternRef),
+            WastRetCore::RefFunc(_) => Ok(Self::AnyFuncRef),
+            WastRetCore::Either(list) => list
+                .into_iter()
+                .map(|v| Self::from_wast(WastRet::Core(v)))
+                .collect::<Result<_, _>>()
+                .map(Self::Either),
+            WastRetCore::V128(V128Pattern::F32x4(lanes)) => lanes
+                .iter()
+                .map(|v| Self::from_wast(WastRet::Core(WastRetCore::F32(*v))))
+                .collect::<Result<_, _>>()
+                .map(Self::F32x4),
+            WastRetCore::V128(V128Pattern::F64x2(lanes)) => lanes
+                .iter()
+                .map(|v| Self::from_wast(WastRet::Core(WastRetCore::F64(*v))))
+                .collect::<Result<_, _>>()
+                .map(Self::F64x2),
+            WastRetCore::V128(pattern) => {
+                let mut bytes = Vec::with_capacity(16);
+                match pattern {
+                    V128Pattern::I8x16(lanes) => lanes
+                        .iter()
+                        .for_each(|v| bytes.extend_from_slice(&v.to_le_bytes())),
+                    V128Pattern::I16x8(lanes) => lanes
+                        .iter()
+                        .for_each(|v| bytes.extend_from_slice(&v.to_le_bytes())),
+                    V128Pattern::I32x4(lanes) => lanes
+                        .iter()
+                        .for_each(|v| bytes.extend_from_slice(&v.to_le_bytes())),
+                    V128Pattern::I64x2(lanes) => lanes
+                        .iter()
+                        .for_each(|v| bytes.extend_from_slice(&v.to_le_bytes())),
+                    _ => unreachable!(),
+                }
+                let mut v = [0; 16];
+                v.copy_from_slice(&bytes);
+                Ok(Self::Value(WasmValue::V128(u128::from_le_bytes(v))))
+            }
+            _ => Err("unsupported result type".to_string()),
+        }
+    }
+
+    fn from_json(value: &Value) -> Result<Self, String> {
+        let val_type = value["type"].as_str().unwrap_or_default();
+        let val = value["value"].as_str();
+        match (val_type, val) {
+            ("f32", Some("nan:canonical")) => Ok(Self::CanonicalNan32),
+            ("f32", Some("nan:arithmetic")) => Ok(Self::ArithmeticNan32),
+            ("f64", Some("nan:canonical")) => Ok(Self::CanonicalNan64),
+            ("f64", Some("nan:arithmetic")) => Ok(Self::ArithmeticNan64),
+            ("funcref", Some("null")) | ("externref", Some("null")) => Ok(Self::NullRef),
+            ("funcref", _) => Ok(Self::AnyFuncRef),
+            ("externref", None) => Ok(Self::AnyExternRef),
+            ("v128", _) if matches!(value["lane_type"].as_str(), Some("f32") | Some("f64")) => {
+                let lane_type = value["lane_type"].as_str().unwrap_or_default();
+                let lanes = value["value"]
+                    .as_array()
+                    .ok_or_else(|| "v128".to_string())?
+                    .iter()
+                    .map(|v| {
+                        let mut lane = Value::default();
+                        lane["type"] = Value::from(lane_type);
+                        lane["value"] = v.clone();
+                        Self::from_json(&lane)
+                    })
+                    .collect::<Result<_, _>>()?;
+                if lane_type == "f32" {
+                    Ok(Self::F32x4(lanes))
+                } else {
+                    Ok(Self::F64x2(lanes))
+                }
+            }
+            ("either", _) => value["values"]
+                .as_array()
+                .ok_or_else(|| "either".to_string())?
+                .iter()
+                .map(Self::from_json)
+                .collect::<Result<_, _>>()
+                .map(Self::Either),
+            _ => json_value(value).map(Self::Value),
+        }
+    }
+}
+
+fn wast_value(arg: WastArgCore) -> Result<WasmValue, String> {
+    match arg {
+        WastArgCore::I32(v) => Ok(WasmValue::I32(v)),
+        WastArgCore::I64(v) => Ok(WasmValue::I64(v)),
+        WastArgCore::F32(v) => Ok(WasmValue::F32(f32::from_bits(v.bits))),
+        WastArgCore::F64(v) => Ok(WasmValue::F64(f64::from_bits(v.bits))),
+        WastArgCore::RefNull(HeapType::Abstract {
+            ty: AbstractHeapType::Func,
+            ..
+        }) => Ok(WasmValue::FuncRef(None)),
+        WastArgCore::RefNull(HeapType::Abstract {
+            ty: AbstractHeapType::Extern,
+            ..
+        }) => Ok(WasmValue::ExternRef(None)),
+        WastArgCore::RefExtern(v) => Ok(WasmValue::ExternRef(Some(v as usize))),
+        WastArgCore::V128(v) => Ok(WasmValue::V128(u128::from_le_bytes(v.to_le_bytes()))),
+        _ => Err("unsupported argument type".to_string()),
+    }
+}
+
+/// Values in `wast2json` scripts are decimal strings of their bit patterns.
+fn json_value(value: &Value) -> Result<WasmValue, String> {
+    let val_type = value["type"].as_str().unwrap_or_default();
+    let val = value["value"].as_str().unwrap_or_default();
+    let bad_value = |_| format!("bad value {}", val);
+    match val_type {
+        "i32" => val
+            .parse::<u32>()
+            .map(|v| WasmValue::I32(v as i32))
+            .map_err(bad_value),
+        "i64" => val
+            .parse::<u64>()
+            .map(|v| WasmValue::I64(v as i64))
+            .map_err(bad_value),
+        "f32" => val
+            .parse::<u32>()
+            .map(|v| WasmValue::F32(f32::from_bits(v)))
+            .map_err(bad_value),
+        "f64" => val
+            .parse::<u64>()
+            .map(|v| WasmValue::F64(f64::from_bits(v)))
+            .map_err(bad_value),
+        "funcref" if val == "null" => Ok(WasmValue::FuncRef(None)),
+        "externref" if val == "null" => Ok(WasmValue::ExternRef(None)),
+        "externref" => val
+            .parse::<usize>()
+            .map(|v| WasmValue::ExternRef(Some(v)))
+            .map_err(bad_value),
+        "v128" => {
+            let lanes = value["value"]
+                .as_array()
+                .ok_or_else(|| "v128".to_string())?;
+            let width = 128 / lanes.len().max(1);
+            lanes.iter().enumerate().try_fold(0u128, |acc, (i, lane)| {
+                let lane = lane.as_str().unwrap_or_default();
+                lane.parse::<u64>()
+                    .map(|v| acc | (v as u128) << (i * width))
+                    .map_err(|_| format!("bad value {}", lane))
+            })
+        }
+        .map(WasmValue::V128),
+        other => Err(other.to_string()),
+    }
+}